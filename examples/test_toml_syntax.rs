@@ -1,8 +1,8 @@
-use syntect::parsing::{SyntaxSet, SyntaxSetBuilder, SyntaxDefinition};
+use syntect::parsing::{SyntaxSetBuilder, SyntaxDefinition};
 
 fn main() {
     // Load TOML syntax
-    const TOML_SYNTAX: &str = include_str!("../themes/TOML.tmLanguage");
+    const TOML_SYNTAX: &str = include_str!("../syntaxes/TOML.sublime-syntax");
     
     match SyntaxDefinition::load_from_str(TOML_SYNTAX, true, None) {
         Ok(syntax) => {
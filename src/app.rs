@@ -1,24 +1,197 @@
-use crate::backend::{Backend, Entry, ListResult, PreviewContent};
-use crate::fuzzy::FuzzyMatcher;
+use crate::audit::AuditRecord;
+use crate::backend::{self, Backend, Entry, ListResult, PreviewContent, RestoreState};
+use crate::config::{self, Config, Confirmations, DownloadDestination, KeyBindings, PreviewLayout, ProgressPanePosition};
+use crate::diff::{self, DiffLine};
+use crate::events::{self, Action};
+use crate::fuzzy::{FilterJob, FilterUpdate, FuzzyMatcher};
+use crate::grep::{self, GrepMatch};
+use crate::history::ImportedHistory;
+use crate::ipc::{ControlCommand, Responder};
+use crate::jsonl::{self, JqJob, JqResult, JsonLines};
+use crate::plugin::{CapturedOutput, Plugin, PluginRun};
+use crate::preview_handler::{self, PreviewHandler};
+use crate::session::PreviewPosition;
+use crate::state::{HistoryEntry, PaneSizes};
 use crate::status::StatusMessage;
-use anyhow::Result;
-use crossterm::event::KeyEvent;
+use crate::summary::PrefixSummary;
+use crate::sync::{SyncDirection, SyncPlan, UploadReport};
+use crate::table::{self, Table};
+use crate::transfer::{self, SessionUsage, TransferRate};
+use crate::ui::text_utils;
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::text::Line;
+use regex_lite::Regex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
 
-/// Events that can occur in the application
+/// Events that drive the application. Terminal input, timers and background
+/// tasks all post these; `App::handle_event` consumes them.
 #[derive(Debug)]
 pub enum AppEvent {
     /// Key press from terminal
     KeyPress(KeyEvent),
-    /// Directory listing result
-    ListResult(Result<ListResult>),
+    /// Terminal was resized
+    Resize,
+    /// Timer tick for animations and time-based housekeeping
+    Tick,
     /// Preview content ready
     PreviewReady(String, PreviewContent),
+    /// Syntax-highlighted lines of a previewed file from line `start` on, the
+    /// last of them if `complete`, with the text they were highlighted from
+    Highlighted {
+        path: String,
+        text: Arc<str>,
+        start: usize,
+        lines: Vec<Line<'static>>,
+        complete: bool,
+    },
+    /// Matches found so far by a background filter
+    Filtered(FilterUpdate),
+    /// Output of a jq filter run in the background
+    JqFiltered(JqResult),
+    /// No input for a while; time to prefetch around the cursor
+    Idle,
+    /// Preview of a file next to the cursor, fetched ahead of time
+    PreviewPrefetched(String, PreviewContent),
+    /// Number of keys of a directory listed ahead of time
+    ListingPrefetched(usize),
+    /// Bytes received by a running download
+    DownloadProgress {
+        path: String,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    /// Download finished; `warning` tells why the file couldn't be given the
+    /// object's modification time or metadata
+    DownloadComplete { path: String, warning: Option<String> },
+    /// Download stopped after a cancel request
+    DownloadCanceled { path: String },
+    /// Download failed
+    DownloadFailed { path: String, error: String },
+    /// Archived object became readable
+    RestoreComplete { path: String, expiry: Option<String> },
+    /// Restore request or polling failed
+    RestoreFailed { path: String, error: String },
+    /// Partial or final totals of a `:du` scan
+    SummaryProgress {
+        location: String,
+        summary: PrefixSummary,
+        complete: bool,
+    },
+    /// `:du` scan failed
+    SummaryFailed { location: String, error: String },
+    /// Matches found by a `:grep` since the last update
+    GrepProgress {
+        location: String,
+        matches: Vec<GrepMatch>,
+        files_searched: usize,
+        complete: bool,
+    },
+    /// `:grep` failed
+    GrepFailed { location: String, error: String },
+    /// Keys fetched so far by a load-all of the flat view
+    FlatLoadProgress { location: String, loaded: usize },
+    /// Every key of the flat view, or the error the load-all failed with
+    FlatLoaded {
+        location: String,
+        result: Result<ListResult, String>,
+    },
+    /// Details of an object for the properties popup, or the error they failed with
+    PropertiesLoaded {
+        location: String,
+        result: Result<Vec<(String, String)>, String>,
+    },
+    /// What a plugin run into a popup printed
+    PluginFinished { name: String, output: CapturedOutput },
+    /// Result of loading a `:diff`
+    DiffReady(Result<DiffView>),
+    /// Result of comparing for `:sync` or `:upload`
+    SyncPlanned(Result<SyncPlan>),
+    /// Files uploaded so far by a running `:upload`
+    UploadProgress { done: usize, total: usize },
+    /// `:upload` finished
+    UploadDone(UploadReport),
+    /// A background request failed because the credentials expired
+    CredentialsExpired,
+    /// Line printed by the running credential command
+    LoginOutput(String),
+    /// Credential command finished, or the error it failed with
+    LoginDone(Result<(), String>),
+    /// The `pre_operation_hook` answered: the operation goes ahead, or the
+    /// reason it was refused
+    HookAnswered { operation: GuardedOperation, verdict: Result<(), String> },
+    /// `:import-history` finished reading the histories
+    HistoryImported(ImportedHistory),
     /// Quit signal
     Quit,
+    /// Suspend signal (SIGTSTP)
+    Suspend,
+    /// Command from the control socket (`--listen`), answered through `responder`
+    Control { command: ControlCommand, responder: Responder },
 }
 
+/// Work the reducer can't do by itself, carried out by the event loop
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    /// Run the action a key press resolved to. `flush` is a pending search
+    /// key that must be typed before the action's own character.
+    Action { action: Action, flush: Option<char> },
+    /// Syntax-highlight the text previewed for `path`
+    HighlightPreview { path: String, text: String },
+    /// Re-list the current prefix, keeping the selection
+    RefreshListing,
+    /// List `prefixes` into the listing cache and load the previews of
+    /// `files` in the background, nearest to the cursor first
+    Prefetch { prefixes: Vec<String>, files: Vec<String> },
+    /// Offer to refresh the expired credentials, then run `retry`
+    CredentialsExpired { retry: Option<Action> },
+    /// Connect again with refreshed credentials, then run `retry`
+    Reconnect { retry: Option<Action> },
+    /// Carry out a control socket command that needs the backend
+    Control { command: ControlCommand, responder: Responder },
+    /// Carry out an operation the `pre_operation_hook` allowed
+    RunGuarded(GuardedOperation),
+}
+
+/// Result of handling one event
+#[derive(Debug, Default)]
+pub struct EventOutcome {
+    /// Whether the screen needs to be redrawn
+    pub redraw: bool,
+    pub effects: Vec<Effect>,
+}
+
+impl EventOutcome {
+    fn redraw() -> Self {
+        Self { redraw: true, effects: Vec::new() }
+    }
+
+    fn effect(effect: Effect) -> Self {
+        Self { redraw: true, effects: vec![effect] }
+    }
+}
+
+/// Where the current listing came from, shown in the file list title
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListingSource {
+    /// Listed from the backend, taking this long
+    Fetched(std::time::Duration),
+    /// Reused from the listing cache
+    Cached,
+}
+
+/// Listings with at least this many entries are filtered on a worker thread
+const BACKGROUND_FILTER_THRESHOLD: usize = 20_000;
+
+/// Locations kept for back/forward navigation in each direction
+const MAX_NAVIGATION_DEPTH: usize = 100;
+
+/// Lines shown above a `:grep` match when its file is opened
+const GREP_CONTEXT_LINES: usize = 5;
+
 /// Download status information for a single file
 #[derive(Debug)]
 pub struct DownloadInfo {
@@ -28,6 +201,24 @@ pub struct DownloadInfo {
     pub status: DownloadState,
     pub completed_at: Option<std::time::Instant>,
     pub cancel_tx: Option<tokio::sync::oneshot::Sender<()>>,
+    /// Recent transfer rate
+    pub rate: TransferRate,
+}
+
+impl DownloadInfo {
+    /// Current speed in bytes per second (None while unknown or not running)
+    pub fn bytes_per_sec(&self, now: std::time::Instant) -> Option<f64> {
+        if self.status != DownloadState::InProgress {
+            return None;
+        }
+        self.rate.bytes_per_sec(now)
+    }
+
+    /// Estimated time until this download completes
+    pub fn eta(&self, now: std::time::Instant) -> Option<std::time::Duration> {
+        let remaining = self.total?.saturating_sub(self.downloaded);
+        transfer::eta(remaining, self.bytes_per_sec(now)?)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -38,6 +229,16 @@ pub enum DownloadState {
     Error(String),
 }
 
+/// Status of a tracked archive restore request
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestoreTaskState {
+    /// Restore requested or still running
+    InProgress,
+    /// Object is readable again (until the expiry date, if known)
+    Restored { expiry: Option<String> },
+    Error(String),
+}
+
 /// Application mode
 #[derive(Debug, Clone, PartialEq)]
 pub enum AppMode {
@@ -51,6 +252,260 @@ pub enum AppMode {
     History,
     /// Download destination selection
     Download,
+    /// Typing a `:` command
+    Command,
+    /// Prefix size summary overlay (`:du`)
+    Summary,
+    /// Diff of two files/versions in the preview pane (`:diff`)
+    Diff,
+    /// Confirming a sync plan (`:sync`)
+    Sync,
+    /// Picking a named context (`:context`)
+    Context,
+    /// Browsing the results of a content search (`:grep`)
+    Grep,
+    /// Showing the details of an object
+    Properties,
+    /// Browsing the audit log (`:log`)
+    AuditLog,
+    /// Asking whether to run an operation
+    Confirm,
+    /// Showing what a plugin printed
+    PluginOutput,
+}
+
+/// State of the `:du` summary overlay
+#[derive(Debug)]
+pub struct SummaryView {
+    /// Display path of the summarized prefix
+    pub location: String,
+    /// Totals gathered so far
+    pub summary: PrefixSummary,
+    /// Whether the scan has finished
+    pub complete: bool,
+    /// Error that stopped the scan, if any
+    pub error: Option<String>,
+    cancel_tx: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+/// State of the `:grep` results overlay
+#[derive(Debug)]
+pub struct GrepView {
+    /// Text searched for
+    pub pattern: String,
+    /// Display path of the searched prefix
+    pub location: String,
+    /// Matching lines found so far, in the order the files were searched
+    pub matches: Vec<GrepMatch>,
+    /// Number of files searched so far
+    pub files_searched: usize,
+    /// Whether the search has finished
+    pub complete: bool,
+    /// Error that stopped the search, if any
+    pub error: Option<String>,
+    /// Selected match
+    pub selected: usize,
+    cancel_tx: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+/// State of the properties popup of an object
+#[derive(Debug)]
+pub struct PropertiesView {
+    /// Display path of the object
+    pub location: String,
+    /// Properties as (name, value), in display order
+    pub rows: Vec<(String, String)>,
+    /// Whether the backend's details have arrived
+    pub complete: bool,
+    /// Error the backend's details failed with, if any
+    pub error: Option<String>,
+    /// Selected row
+    pub selected: usize,
+}
+
+/// State of the popup showing a plugin's output
+#[derive(Debug)]
+pub struct PluginOutputView {
+    /// Name of the plugin
+    pub name: String,
+    /// Lines it printed
+    pub lines: Vec<String>,
+    /// Whether it has exited
+    pub complete: bool,
+    /// Why it failed, if it did
+    pub error: Option<String>,
+    /// First line shown
+    pub scroll: usize,
+}
+
+/// Operation held back until the user confirms it
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingOperation {
+    /// Download each remote path to its local target
+    Download(Vec<(String, PathBuf)>),
+    /// Switch to the named context
+    SwitchContext(String),
+    /// Open a history entry on another backend
+    OpenHistory(HistoryEntry),
+    /// Run the credential command, then retry the action that failed
+    Login(Option<Action>),
+}
+
+/// Operation held back until the `pre_operation_hook` allows it
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuardedOperation {
+    /// Carry out an upload (`:upload`) or a sync (`:sync`)
+    Sync(SyncPlan),
+    /// Request the restore of the archived object at this path
+    Restore(String),
+}
+
+impl GuardedOperation {
+    /// What the operation is called when the hook refuses it
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sync(plan) if plan.direction == SyncDirection::Upload => "Upload",
+            Self::Sync(_) => "Sync",
+            Self::Restore(_) => "Restore",
+        }
+    }
+}
+
+/// Credential command running to refresh expired credentials
+#[derive(Debug)]
+struct Login {
+    command: String,
+    /// Action to run again once it succeeds
+    retry: Option<Action>,
+    /// Lines it printed so far (SSO logins print a URL and a code)
+    output: Vec<String>,
+}
+
+/// Yes/no question asked before an operation runs (`[confirmations]`)
+#[derive(Debug, Clone)]
+pub struct Confirmation {
+    pub question: String,
+    pub operation: PendingOperation,
+}
+
+/// State of the `:log` overlay, or of `:downloads`
+#[derive(Debug)]
+pub struct AuditLogView {
+    /// Most recent records, oldest first
+    pub records: Vec<AuditRecord>,
+    /// Selected record
+    pub selected: usize,
+    /// Only completed downloads are listed (`:downloads`)
+    pub downloads_only: bool,
+}
+
+/// Diff shown in the preview pane (`:diff`)
+#[derive(Debug, Clone)]
+pub struct DiffView {
+    /// Label of the old side (file path or version)
+    pub old_label: String,
+    /// Label of the new side
+    pub new_label: String,
+    pub lines: Vec<DiffLine>,
+    /// Side-by-side instead of unified layout
+    pub side_by_side: bool,
+}
+
+impl DiffView {
+    /// Number of rows in the current layout
+    pub fn row_count(&self) -> usize {
+        if self.side_by_side {
+            diff::side_by_side(&self.lines).len()
+        } else {
+            self.lines.len()
+        }
+    }
+}
+
+/// Syntax-highlighted lines of a preview, filled in as the background
+/// highlighting streams them
+#[derive(Debug)]
+struct Highlight {
+    /// Text being highlighted; later chunks must be of the same text
+    source: Arc<str>,
+    lines: Vec<Line<'static>>,
+    complete: bool,
+}
+
+/// Rows of the preview pane's text area and the display rows the preview's
+/// lines take in it, which is more than one for lines that wrap
+#[derive(Debug, Clone, Default)]
+pub struct PreviewGeometry {
+    /// Rows inside the pane's borders
+    pub height: usize,
+    /// Rows of each line, None when every line takes one
+    rows: Option<Vec<usize>>,
+}
+
+impl PreviewGeometry {
+    /// Geometry of a preview that doesn't wrap
+    pub fn unwrapped(height: usize) -> Self {
+        Self { height, rows: None }
+    }
+
+    /// Display rows taken by line `line`
+    pub fn rows(&self, line: usize) -> usize {
+        self.rows.as_ref().and_then(|rows| rows.get(line).copied()).unwrap_or(1)
+    }
+
+    /// Display rows taken by the lines from `start` to `end` (inclusive)
+    fn rows_between(&self, start: usize, end: usize) -> usize {
+        match &self.rows {
+            Some(_) => (start..=end).map(|line| self.rows(line)).sum(),
+            None => (end + 1).saturating_sub(start),
+        }
+    }
+
+    /// Smallest scroll offset that still shows `line` in full within the
+    /// first `height` rows. A line taller than that is scrolled to itself.
+    fn offset_ending_at(&self, line: usize, height: usize) -> usize {
+        if self.rows.is_none() {
+            return (line + 1).saturating_sub(height);
+        }
+        let mut offset = line;
+        let mut used = self.rows(line);
+        while offset > 0 && used + self.rows(offset - 1) <= height {
+            offset -= 1;
+            used += self.rows(offset);
+        }
+        offset
+    }
+
+    /// `offset`, moved down as little as needed to show `line` in full
+    fn scrolled_to(&self, offset: usize, line: usize) -> usize {
+        if line < offset {
+            line
+        } else if self.rows_between(offset, line) > self.height {
+            self.offset_ending_at(line, self.height)
+        } else {
+            offset
+        }
+    }
+
+    /// Scroll offset showing the last of `max_lines` lines with up to four
+    /// blank rows below it
+    fn bottom_offset(&self, max_lines: usize) -> usize {
+        let max_empty_lines = 4;
+        if self.height > max_empty_lines && max_lines >= self.height - max_empty_lines {
+            self.offset_ending_at(max_lines - 1, self.height - max_empty_lines)
+        } else {
+            0
+        }
+    }
+
+    /// Geometry of the lines in `lines` shown one after another, as the
+    /// filtered preview search view shows its results
+    pub fn of_lines(&self, lines: &[usize]) -> Self {
+        Self {
+            height: self.height,
+            rows: self.rows.as_ref().map(|_| lines.iter().map(|&line| self.rows(line)).collect()),
+        }
+    }
 }
 
 /// Focused panel
@@ -62,7 +517,107 @@ pub enum FocusedPanel {
     Preview,
 }
 
+/// Picking files for another program (`--pick`): Enter on a file picks it
+/// and quits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickMode {
+    /// Pick the file under the cursor
+    Single,
+    /// Pick the selected files (Space), or the file under the cursor when
+    /// none is selected
+    Multi,
+}
+
 /// Main application state
+/// Header of a group of files with the same extension, shown when grouping
+/// by extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryGroup {
+    /// Lowercase extension without the dot; empty for files without one
+    pub extension: String,
+    /// Number of matching files in the group
+    pub count: usize,
+    /// Total size of the matching files
+    pub size: u64,
+    /// Whether the group's files are hidden
+    pub collapsed: bool,
+}
+
+/// Where the flat view is in the keys below its prefix, which can be too many
+/// to fetch at once
+#[derive(Debug, Default)]
+pub struct FlatPaging {
+    /// Key each page up to the shown one starts after (`None` for the first)
+    starts: Vec<Option<String>>,
+    /// Whether keys follow the shown page
+    more: bool,
+    /// Load-all running in the background
+    loading: Option<FlatLoad>,
+}
+
+/// Load-all of the flat view; dropping it cancels the load
+#[derive(Debug)]
+struct FlatLoad {
+    location: String,
+    loaded: usize,
+    _cancel_tx: tokio::sync::oneshot::Sender<()>,
+}
+
+impl FlatPaging {
+    /// Number of the shown page, from 1
+    pub fn page(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Whether keys follow the shown page
+    pub fn has_more(&self) -> bool {
+        self.more
+    }
+
+    /// Keys fetched so far while loading all of them
+    pub fn loaded(&self) -> Option<usize> {
+        self.loading.as_ref().map(|load| load.loaded)
+    }
+}
+
+/// Kind of entries the listing shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KindFilter {
+    #[default]
+    All,
+    Directories,
+    Files,
+}
+
+impl KindFilter {
+    /// Next kind in the cycle all -> directories -> files -> all
+    pub fn next(self) -> Self {
+        match self {
+            KindFilter::All => KindFilter::Directories,
+            KindFilter::Directories => KindFilter::Files,
+            KindFilter::Files => KindFilter::All,
+        }
+    }
+
+    /// Whether `entry` is shown
+    pub fn keeps(self, entry: &Entry) -> bool {
+        match self {
+            KindFilter::All => true,
+            KindFilter::Directories => entry.is_dir,
+            KindFilter::Files => !entry.is_dir,
+        }
+    }
+
+    /// Short description for the title and status messages
+    pub fn label(self) -> &'static str {
+        match self {
+            KindFilter::All => "all entries",
+            KindFilter::Directories => "directories only",
+            KindFilter::Files => "files only",
+        }
+    }
+}
+
 pub struct App {
     /// Backend for storage operations
     backend: Arc<dyn Backend>,
@@ -70,24 +625,109 @@ pub struct App {
     current_prefix: String,
     /// Current directory entries
     entries: Vec<Entry>,
-    /// Filtered entries (after fuzzy search)
+    /// Entry names, shared with background filter jobs
+    entry_names: Arc<Vec<String>>,
+    /// Where the current entries came from (unknown for the initial listing)
+    listing_source: Option<ListingSource>,
+    /// Data listed, previewed and transferred this session
+    usage: SessionUsage,
+    /// Filtered entries (after fuzzy search). When grouping by extension,
+    /// indices past the end of `entries` are the headers in `groups`.
     filtered_entries: Vec<usize>,
+    /// Number of entries matching the filter, including those in collapsed groups
+    match_count: usize,
+    /// Directories entered in one keypress by auto-descend to reach the
+    /// current location (`2024/01/15`), shown in the title
+    descended_chain: Option<String>,
+    /// The listing holds every file below the current prefix
+    flat_view: bool,
+    /// Page of the flat view shown
+    flat_paging: Option<FlatPaging>,
+    /// Show only directories or only files
+    kind_filter: KindFilter,
+    /// Cluster files by extension under collapsible headers
+    group_by_extension: bool,
+    /// Group headers of the filtered list, in order
+    groups: Vec<EntryGroup>,
+    /// Extensions whose groups are collapsed
+    collapsed_groups: HashSet<String>,
     /// Matched char positions per entry index (for highlight rendering)
     match_positions: HashMap<usize, Vec<u32>>,
     /// Currently selected index in filtered list
     selected_index: usize,
     /// Search query
     search_query: String,
+    /// Filters confirmed before the current query, each narrowing what the next one sees
+    pinned_filters: Vec<String>,
+    /// Entries left by the pinned filters with their match positions (None without pinned filters)
+    pinned_matches: Option<Vec<(usize, Vec<u32>)>>,
+    /// Entry index of each name handed to the running background filter, when it
+    /// only matches the entries left by the pinned filters
+    filter_base: Option<Vec<usize>>,
     /// Current mode
     mode: AppMode,
     /// Should quit
     should_quit: bool,
+    /// Set when the terminal should be handed back to the shell for a while
+    suspend_requested: bool,
+    /// Set when picking files for another program
+    pick_mode: Option<PickMode>,
+    /// Display paths of the picked files, once picked
+    picked: Option<Vec<String>>,
     /// Status message
     status_message: Option<StatusMessage>,
+    /// How long a status message stays visible
+    status_timeout: std::time::Duration,
+    /// How long a pending key waits for the rest of its key sequence
+    sequence_timeout: std::time::Duration,
+    /// Key bindings used to resolve key presses
+    key_bindings: KeyBindings,
     /// Fuzzy matcher
     fuzzy_matcher: FuzzyMatcher,
+    /// Listings with at least this many entries are filtered in the background
+    background_filter_threshold: usize,
+    /// Generation of the latest filter; results of older ones are dropped
+    filter_generation: u64,
+    /// Background filter waiting to be started by the event loop
+    pending_filter: Option<FilterJob>,
+    /// Set to stop the background filter in flight
+    filter_cancel: Option<Arc<AtomicBool>>,
+    /// Whether a background filter is still matching
+    filtering: bool,
+    /// Entry to select once the background filter has finished
+    filter_select: Option<String>,
+    /// Filter presets as (key, filter) pairs
+    filter_presets: Vec<(String, String)>,
+    /// Entries on each side of the cursor prefetched while idle (0 = off)
+    prefetch_distance: usize,
+    /// Location and entry the last prefetch was for
+    prefetched_for: Option<(String, String)>,
     /// Preview cache (path -> content)
     preview_cache: HashMap<String, PreviewContent>,
+    /// Parsed tables for cached tabular previews (path -> table)
+    preview_tables: HashMap<String, Table>,
+    /// Record views of cached JSON Lines previews (path -> records)
+    preview_jsonl: HashMap<String, JsonLines>,
+    /// jq filter of the previewed JSON Lines file before `:jq` was typed, so
+    /// leaving the command line restores it
+    jq_draft: Option<Option<String>>,
+    /// Generation of the latest jq filter; results of older ones are dropped
+    jq_generation: u64,
+    /// jq filter waiting to be started by the event loop
+    pending_jq: Option<JqJob>,
+    /// Set to stop the jq filter in flight
+    jq_cancel: Option<Arc<AtomicBool>>,
+    /// Preview the jq filter in flight is for, and whether it was entered
+    /// with `:jq` (rather than being typed), so its error is reported
+    jq_target: Option<(String, bool)>,
+    /// Delimiters set at runtime, overriding the extension default (path -> delimiter)
+    delimiter_overrides: HashMap<String, char>,
+    /// Encodings chosen at runtime, overriding the detected one (path -> encoding)
+    encoding_overrides: HashMap<String, &'static encoding_rs::Encoding>,
+    /// Preview handlers by file extension (`[preview.handlers]`)
+    preview_handlers: BTreeMap<String, PreviewHandler>,
+    /// S3 Select queries whose results replace the cached preview (path -> query)
+    preview_queries: HashMap<String, String>,
     /// Currently displayed preview path
     current_preview_path: Option<String>,
     /// Pending key for multi-key sequences (e.g., waiting for second 'g' in 'gg')
@@ -95,7 +735,15 @@ pub struct App {
     /// When the pending key was set (for timeout)
     pending_key_instant: Option<std::time::Instant>,
     /// History of visited paths (most recent first)
-    history: Vec<String>,
+    history: Vec<HistoryEntry>,
+    /// Maximum number of history entries
+    history_max_entries: usize,
+    /// Leave all-digit folder names out of the history
+    history_skip_numeric_folders: bool,
+    /// Display paths never added to the history (glob patterns)
+    history_exclude_patterns: Vec<String>,
+    /// Display paths never added to the history (the `re:` patterns)
+    history_exclude_regexes: Vec<Regex>,
     /// Filtered history indices (after fuzzy search)
     filtered_history: Vec<usize>,
     /// Matched char positions per history index (for highlight rendering)
@@ -112,22 +760,86 @@ pub struct App {
     preview_scroll_offset: usize,
     /// Preview cursor line (highlighted line in preview)
     preview_cursor_line: usize,
+    /// First visible column of a table preview (horizontal scroll)
+    preview_column_offset: usize,
     /// Whether preview is in visual mode
     preview_visual_mode: bool,
     /// Visual mode selection start line
     preview_visual_start: usize,
-    /// Preview window width percentage (0-100)
-    preview_width_percent: u16,
+    /// Preview width percentage and the heights of the status and progress panes
+    pane_sizes: PaneSizes,
+    /// Whether the panes were resized or rearranged this session, so the
+    /// sizes, layout and zoom are saved
+    panes_resized: bool,
+    /// Placement of the preview pane (right, bottom or hidden)
+    preview_layout: PreviewLayout,
+    /// Whether the focused pane is zoomed to fill the content area
+    zoomed: bool,
+    /// Zen mode: the search bar and status pane are hidden
+    zen: bool,
+    /// Whether the downloads progress pane is shown
+    show_progress_pane: bool,
+    /// Entry name patterns hidden from listings
+    ignore_patterns: Vec<String>,
+    /// Fold `_$folder$` marker objects into the directories they stand for
+    hide_folder_markers: bool,
     /// Selected file indices (for multi-file selection)
     selected_files: HashSet<usize>,
     /// Visual selection mode start index
     visual_start_index: Option<usize>,
+    /// Destinations offered in download mode, for the current bucket
+    download_destinations: Vec<DownloadDestination>,
     /// Selected download destination index
     download_destination_index: usize,
+    /// Digits typed of a destination number that more digits could extend
+    download_number: String,
+    /// Selected index in the context picker (into the config's contexts, by name)
+    context_index: usize,
+    /// Named context the current location was opened from
+    active_context: Option<String>,
+    /// Preview position to restore once that file's preview arrives (sessions)
+    pending_preview_position: Option<(String, PreviewPosition)>,
+    /// Where each file's preview was left this session, by path and version
+    /// (ETag, or modification time where there is none)
+    preview_positions: HashMap<(String, Option<String>), PreviewPosition>,
+    /// Locations to go back to (most recent last)
+    back_stack: Vec<NavLocation>,
+    /// Locations gone back from, to go forward to again (most recent last)
+    forward_stack: Vec<NavLocation>,
     /// Active and recent downloads (file path -> download info)
     downloads: HashMap<String, DownloadInfo>,
+    /// Tracked archive restore requests (object path -> state)
+    restores: HashMap<String, RestoreTaskState>,
+    /// Command line input (in command mode)
+    command_input: String,
+    /// Prefix summary overlay state
+    summary_view: Option<SummaryView>,
+    /// Content search results overlay state
+    grep_view: Option<GrepView>,
+    /// Properties popup state
+    properties_view: Option<PropertiesView>,
+    /// Plugin output popup state
+    plugin_output_view: Option<PluginOutputView>,
+    /// External commands by name, in config order
+    plugins: Vec<(String, Plugin)>,
+    /// Plugin to run in the terminal, with the UI out of the way
+    terminal_plugin: Option<PluginRun>,
+    /// Audit log overlay state
+    audit_log_view: Option<AuditLogView>,
+    /// Diff shown in the preview pane
+    diff_view: Option<DiffView>,
+    /// Sync plan awaiting confirmation
+    sync_plan: Option<SyncPlan>,
+    /// Operation waiting for a yes/no answer
+    confirmation: Option<Confirmation>,
+    /// Operations that ask before they run
+    confirmations: Confirmations,
+    /// Scroll offset of the sync plan popup
+    sync_plan_scroll: usize,
     /// Whether to show help/keyboard shortcuts
     show_help: bool,
+    /// Scroll offset of the help popup
+    help_scroll: usize,
     /// Whether preview search mode is active
     preview_search_active: bool,
     /// Preview search query
@@ -136,30 +848,93 @@ pub struct App {
     preview_search_results: Vec<usize>,
     /// Currently selected search result index
     preview_search_selected: usize,
+    /// Scroll offset of the filtered preview, in results rather than lines
+    preview_search_scroll: usize,
+    /// Preview cursor line and scroll offset when the search started, restored
+    /// when it's canceled
+    preview_search_origin: (usize, usize),
+    /// Syntax-highlighted preview lines per file path
+    highlighted: HashMap<String, Highlight>,
+    /// Paths in `highlighted`, least recently shown first
+    highlight_order: Vec<String>,
+    /// Maximum number of files kept in `highlighted`
+    highlight_cache_size: usize,
+    /// File whose preview is being loaded, and since when
+    preview_loading: Option<(String, std::time::Instant)>,
+    /// File whose preview loading was canceled with Escape
+    preview_canceled: Option<String>,
+    /// Credential command running after the credentials expired
+    login: Option<Login>,
+    /// Whether refreshing expired credentials was declined; they are only
+    /// reported from then on
+    login_declined: bool,
+    /// Listings and previews slower than this are reported (None = never)
+    slow_request_threshold: Option<std::time::Duration>,
 }
 
 impl App {
     pub fn new(backend: Arc<dyn Backend>, initial_prefix: String, preview_width_percent: u16) -> Self {
-        // Clamp preview width to valid range
-        let preview_width = preview_width_percent.clamp(20, 80);
-
         Self {
             backend,
             current_prefix: initial_prefix,
             entries: Vec::new(),
+            entry_names: Arc::new(Vec::new()),
+            listing_source: None,
+            usage: SessionUsage::default(),
             filtered_entries: Vec::new(),
+            match_count: 0,
+            descended_chain: None,
+            flat_view: false,
+            flat_paging: None,
+            kind_filter: KindFilter::All,
+            group_by_extension: false,
+            groups: Vec::new(),
+            collapsed_groups: HashSet::new(),
             match_positions: HashMap::new(),
             selected_index: 0,
             search_query: String::new(),
+            pinned_filters: Vec::new(),
+            pinned_matches: None,
+            filter_base: None,
             mode: AppMode::Normal,
             should_quit: false,
+            suspend_requested: false,
+            pick_mode: None,
+            picked: None,
             status_message: None,
+            status_timeout: std::time::Duration::from_secs(5),
+            sequence_timeout: std::time::Duration::from_millis(300),
+            key_bindings: KeyBindings::default(),
             fuzzy_matcher: FuzzyMatcher::new(),
+            background_filter_threshold: BACKGROUND_FILTER_THRESHOLD,
+            filter_generation: 0,
+            pending_filter: None,
+            filter_cancel: None,
+            filtering: false,
+            filter_select: None,
+            filter_presets: Vec::new(),
+            prefetch_distance: 0,
+            prefetched_for: None,
             preview_cache: HashMap::new(),
+            preview_tables: HashMap::new(),
+            preview_jsonl: HashMap::new(),
+            jq_draft: None,
+            jq_generation: 0,
+            pending_jq: None,
+            jq_cancel: None,
+            jq_target: None,
+            delimiter_overrides: HashMap::new(),
+            encoding_overrides: HashMap::new(),
+            preview_handlers: BTreeMap::new(),
+            preview_queries: HashMap::new(),
             current_preview_path: None,
             pending_key: None,
             pending_key_instant: None,
             history: Vec::new(),
+            history_max_entries: 100,
+            history_skip_numeric_folders: true,
+            history_exclude_patterns: Vec::new(),
+            history_exclude_regexes: Vec::new(),
             filtered_history: Vec::new(),
             history_match_positions: HashMap::new(),
             history_selected_index: 0,
@@ -167,19 +942,61 @@ impl App {
             wrap_text: false,
             focused_panel: FocusedPanel::Explorer,
             preview_scroll_offset: 0,
+            preview_column_offset: 0,
             preview_cursor_line: 0,
             preview_visual_mode: false,
             preview_visual_start: 0,
-            preview_width_percent: preview_width,
+            // Clamps the preview width to the valid range
+            pane_sizes: PaneSizes { preview_percent: preview_width_percent, ..Default::default() }.clamped(),
+            panes_resized: false,
+            preview_layout: PreviewLayout::default(),
+            zoomed: false,
+            zen: false,
+            show_progress_pane: false,
+            ignore_patterns: Vec::new(),
+            hide_folder_markers: true,
             selected_files: HashSet::new(),
             visual_start_index: None,
+            download_destinations: Vec::new(),
             download_destination_index: 0,
+            download_number: String::new(),
+            context_index: 0,
+            active_context: None,
+            pending_preview_position: None,
+            preview_positions: HashMap::new(),
+            back_stack: Vec::new(),
+            forward_stack: Vec::new(),
             downloads: HashMap::new(),
+            restores: HashMap::new(),
+            command_input: String::new(),
+            summary_view: None,
+            grep_view: None,
+            properties_view: None,
+            plugin_output_view: None,
+            plugins: Vec::new(),
+            terminal_plugin: None,
+            audit_log_view: None,
+            diff_view: None,
+            sync_plan: None,
+            confirmation: None,
+            confirmations: Confirmations::default(),
+            sync_plan_scroll: 0,
             show_help: false,
+            help_scroll: 0,
             preview_search_active: false,
             preview_search_query: String::new(),
             preview_search_results: Vec::new(),
             preview_search_selected: 0,
+            preview_search_scroll: 0,
+            preview_search_origin: (0, 0),
+            highlighted: HashMap::new(),
+            highlight_order: Vec::new(),
+            highlight_cache_size: 2,
+            preview_loading: None,
+            preview_canceled: None,
+            login: None,
+            login_declined: false,
+            slow_request_threshold: None,
         }
     }
 
@@ -193,6 +1010,51 @@ impl App {
         self.should_quit = true;
     }
 
+    /// Ask the event loop to suspend the process (Ctrl-Z)
+    pub fn request_suspend(&mut self) {
+        self.suspend_requested = true;
+    }
+
+    /// Whether a suspend was requested since the last call
+    pub fn take_suspend_request(&mut self) -> bool {
+        std::mem::take(&mut self.suspend_requested)
+    }
+
+    /// Pick files for another program instead of only browsing
+    pub fn set_pick_mode(&mut self, mode: Option<PickMode>) {
+        self.pick_mode = mode;
+    }
+
+    pub fn pick_mode(&self) -> Option<PickMode> {
+        self.pick_mode
+    }
+
+    /// Pick the file under the cursor, or in multi mode the selected files,
+    /// and quit. Returns false (picking nothing) on a directory or outside
+    /// pick mode.
+    pub fn pick(&mut self) -> bool {
+        let Some(mode) = self.pick_mode else {
+            return false;
+        };
+        let paths = match mode {
+            PickMode::Multi if !self.selected_files.is_empty() => {
+                self.get_selected_file_paths().iter().map(|path| self.backend.get_display_path(path)).collect()
+            }
+            _ => match self.selected_entry() {
+                Some(entry) if !entry.is_dir => vec![self.selected_display_path()],
+                _ => return false,
+            },
+        };
+        self.picked = Some(paths);
+        self.quit();
+        true
+    }
+
+    /// Display paths of the picked files; None if the user quit without picking
+    pub fn picked(&self) -> Option<&[String]> {
+        self.picked.as_deref()
+    }
+
     /// Get current entries
     pub fn entries(&self) -> &[Entry] {
         &self.entries
@@ -203,6 +1065,200 @@ impl App {
         &self.filtered_entries
     }
 
+    /// Number of entries matching the filter (all entries without one)
+    pub fn match_count(&self) -> usize {
+        self.match_count
+    }
+
+    /// Group header shown for a filtered index, if it is one
+    pub fn group_header(&self, entry_idx: usize) -> Option<&EntryGroup> {
+        entry_idx.checked_sub(self.entries.len()).and_then(|group| self.groups.get(group))
+    }
+
+    /// Record the directories auto-descend went through to reach the current
+    /// location, until the next location is listed
+    pub fn set_descended_chain(&mut self, chain: String) {
+        self.descended_chain = Some(chain);
+    }
+
+    /// Directories auto-descend went through to reach the current location
+    pub fn descended_chain(&self) -> Option<&str> {
+        self.descended_chain.as_deref()
+    }
+
+    /// Mark the listing as flat (every file below the prefix) or not
+    pub fn set_flat_view(&mut self, flat: bool) {
+        self.flat_view = flat;
+        if !flat {
+            self.flat_paging = None;
+        }
+    }
+
+    /// Whether the listing holds every file below the current prefix
+    pub fn is_flat_view(&self) -> bool {
+        self.flat_view
+    }
+
+    /// Mark the listing as the first page of the flat view; `more` tells
+    /// whether keys follow it. Cancels a running load-all.
+    pub fn start_flat_paging(&mut self, more: bool) {
+        self.flat_paging = Some(FlatPaging { starts: vec![None], more, loading: None });
+    }
+
+    /// Page of the flat view shown, if any
+    pub fn flat_paging(&self) -> Option<&FlatPaging> {
+        self.flat_paging.as_ref()
+    }
+
+    /// Key the page after the shown one starts after, if keys follow it
+    pub fn next_flat_page_start(&self) -> Option<String> {
+        self.flat_paging.as_ref().filter(|paging| paging.more)?;
+        let last = self.entries.last()?;
+        Some(if self.current_prefix.is_empty() {
+            last.name.clone()
+        } else {
+            format!("{}/{}", self.current_prefix.trim_end_matches('/'), last.name)
+        })
+    }
+
+    /// Key the page before the shown one starts after (`Some(None)` for the
+    /// first page), if the shown page isn't the first
+    pub fn previous_flat_page_start(&self) -> Option<Option<String>> {
+        let starts = &self.flat_paging.as_ref()?.starts;
+        starts.len().checked_sub(2).map(|i| starts[i].clone())
+    }
+
+    /// Show the page that starts after `start`, following the shown one
+    pub fn show_next_flat_page(&mut self, result: ListResult, start: String, more: bool) {
+        self.usage.record_listing(result.entries.len());
+        if let Some(paging) = self.flat_paging.as_mut() {
+            paging.starts.push(Some(start));
+            paging.more = more;
+            paging.loading = None;
+        }
+        self.update_entries(result);
+    }
+
+    /// Show the page before the shown one
+    pub fn show_previous_flat_page(&mut self, result: ListResult, more: bool) {
+        self.usage.record_listing(result.entries.len());
+        if let Some(paging) = self.flat_paging.as_mut() {
+            paging.starts.pop();
+            paging.more = more;
+            paging.loading = None;
+        }
+        self.update_entries(result);
+    }
+
+    /// Record a load-all of the flat view at `location` started
+    pub fn start_flat_load(&mut self, location: String, cancel_tx: tokio::sync::oneshot::Sender<()>) {
+        if let Some(paging) = self.flat_paging.as_mut() {
+            paging.loading = Some(FlatLoad { location, loaded: 0, _cancel_tx: cancel_tx });
+        }
+    }
+
+    /// Whether `location` is the one being loaded
+    fn is_flat_loading(&self, location: &str) -> bool {
+        self.flat_paging
+            .as_ref()
+            .and_then(|paging| paging.loading.as_ref())
+            .is_some_and(|load| load.location == location)
+    }
+
+    /// Update the number of keys a load-all fetched so far (ignored if it's
+    /// for another location)
+    pub fn update_flat_load(&mut self, location: &str, loaded: usize) {
+        if let Some(load) = self.flat_paging.as_mut().and_then(|paging| paging.loading.as_mut()) {
+            if load.location == location {
+                load.loaded = loaded;
+            }
+        }
+    }
+
+    /// Show every key a load-all fetched, keeping the selection, or the error
+    /// it failed with (ignored if it's for another location)
+    pub fn finish_flat_load(&mut self, location: &str, result: Result<ListResult, String>) {
+        if !self.is_flat_loading(location) {
+            return;
+        }
+        match result {
+            Ok(result) => {
+                let count = result.entries.len();
+                match self.selected_entry().map(|e| e.name.clone()) {
+                    Some(name) => self.update_entries_and_select(result, &name),
+                    None => self.update_entries(result),
+                }
+                self.start_flat_paging(false);
+                self.show_success(format!("Loaded all {} keys", count));
+            }
+            Err(e) => {
+                if let Some(paging) = self.flat_paging.as_mut() {
+                    paging.loading = None;
+                }
+                self.show_error(format!("Loading all keys failed: {}", e));
+            }
+        }
+    }
+
+    /// Kind of entries the listing shows
+    pub fn kind_filter(&self) -> KindFilter {
+        self.kind_filter
+    }
+
+    /// Show the next kind of entries (all, directories only, files only),
+    /// keeping the cursor on its entry if it is still shown
+    pub fn cycle_kind_filter(&mut self) {
+        let name = self.selected_entry().map(|e| e.name.clone());
+        self.kind_filter = self.kind_filter.next();
+        self.apply_filter();
+        if let Some(name) = name {
+            self.select_entry_by_name(&name);
+        }
+    }
+
+    /// Whether files are grouped by extension
+    pub fn is_grouped_by_extension(&self) -> bool {
+        self.group_by_extension
+    }
+
+    /// Switch grouping by extension on or off, keeping the cursor on its entry
+    pub fn toggle_group_by_extension(&mut self) {
+        let name = self.selected_entry().map(|e| e.name.clone());
+        self.group_by_extension = !self.group_by_extension;
+        self.apply_filter();
+        if let Some(name) = name {
+            self.select_entry_by_name(&name);
+        }
+    }
+
+    /// Collapse the group of the file under the cursor, moving the cursor to
+    /// its header, or expand/collapse the group whose header is under the
+    /// cursor. Returns false if there is no group there.
+    pub fn toggle_group_collapsed(&mut self) -> bool {
+        let Some(&entry_idx) = self.filtered_entries.get(self.selected_index) else {
+            return false;
+        };
+        let extension = match self.group_header(entry_idx) {
+            Some(group) => group.extension.clone(),
+            None if self.group_by_extension => match self.entries.get(entry_idx) {
+                Some(entry) if !entry.is_dir => group_extension(&entry.name),
+                _ => return false,
+            },
+            None => return false,
+        };
+        if !self.collapsed_groups.remove(&extension) {
+            self.collapsed_groups.insert(extension.clone());
+        }
+        self.apply_filter();
+        if let Some(header) = self.groups.iter().position(|g| g.extension == extension) {
+            let header_idx = self.entries.len() + header;
+            if let Some(row) = self.filtered_entries.iter().position(|&idx| idx == header_idx) {
+                self.selected_index = row;
+            }
+        }
+        true
+    }
+
     /// Get selected index
     pub fn selected_index(&self) -> usize {
         self.selected_index
@@ -269,80 +1325,558 @@ impl App {
 
     /// Update entries from listing result
     pub fn update_entries(&mut self, result: ListResult) {
-        self.entries = result.entries;
+        if result.prefix != self.current_prefix {
+            self.descended_chain = None;
+        }
+        self.set_entries(result.entries);
         self.current_prefix = result.prefix;
         self.apply_filter();
         // Clear selections when navigating to a new directory
         self.clear_selection();
     }
 
-    /// Select the entry with the given name in the current filtered list.
-    /// Used to restore selection after exiting search mode.
-    pub fn select_entry_by_name(&mut self, name: &str) {
-        for (filtered_idx, &entry_idx) in self.filtered_entries.iter().enumerate() {
-            if let Some(entry) = self.entries.get(entry_idx) {
-                if entry.name == name {
-                    self.selected_index = filtered_idx;
-                    return;
-                }
-            }
+    /// Apply the settings the app reads from the config
+    pub fn apply_config(&mut self, config: &Config) {
+        self.set_preview_layout(config.preview_layout);
+        self.set_progress_pane_shown(config.show_progress_pane);
+        self.zen = config.zen_mode;
+        self.set_ignore_patterns(config.ignore_patterns.clone());
+        self.hide_folder_markers = config.hide_folder_markers;
+        self.set_key_bindings(config.key_bindings.clone());
+        self.set_status_timeout_secs(config.status_message_timeout_secs);
+        self.set_sequence_timeout_ms(config.key_sequence_timeout_ms);
+        self.set_highlight_cache_size(config.highlight_cache_size);
+        self.set_slow_request_threshold(config.slow_request_warning_ms);
+        if let Err(e) = self.set_history_rules(
+            config.history_max_entries,
+            config.history_skip_numeric_folders,
+            config.history_exclude_patterns.clone(),
+        ) {
+            self.show_warning(format!("{:#}", e));
         }
+        self.fuzzy_matcher.set_weights(config.fuzzy);
+        self.set_prefetch_distance(config.prefetch_distance);
+        self.set_filter_presets(config.filter_presets.clone().into_iter().collect());
+        self.set_plugins(config.plugins.clone().into_iter().collect());
+        self.set_preview_handlers(config.preview.handlers.clone());
+        self.set_confirmations(config.confirmations.clone());
     }
 
-    /// Update entries and select a specific entry by name
-    pub fn update_entries_and_select(&mut self, result: ListResult, select_name: &str) {
-        self.entries = result.entries;
-        self.current_prefix = result.prefix;
-        self.apply_filter();
+    /// Set the operations that ask before they run
+    pub fn set_confirmations(&mut self, confirmations: Confirmations) {
+        self.confirmations = confirmations;
+    }
 
-        // Find the entry with the given name and select it
-        for (filtered_idx, &entry_idx) in self.filtered_entries.iter().enumerate() {
-            if let Some(entry) = self.entries.get(entry_idx) {
-                if entry.name == select_name {
-                    self.selected_index = filtered_idx;
-                    break;
-                }
-            }
-        }
+    /// Set the preview handlers by file extension
+    pub fn set_preview_handlers(&mut self, handlers: BTreeMap<String, PreviewHandler>) {
+        self.preview_handlers = handlers;
     }
 
-    /// Update search query and re-filter
-    pub fn set_search_query(&mut self, query: String) {
-        self.search_query = query;
+    /// Handler configured for the extension of `path`, if any
+    pub fn preview_handler_for(&self, path: &str) -> Option<&PreviewHandler> {
+        preview_handler::handler_for(&self.preview_handlers, path)
+    }
 
-        // Apply filter to either history or entries depending on what we're searching
-        if self.searching_history || self.mode == AppMode::History {
-            self.apply_history_filter();
-        } else {
-            self.apply_filter();
-        }
+    /// Set the filters toggled by a single key, as (key, filter) pairs
+    pub fn set_filter_presets(&mut self, presets: Vec<(String, String)>) {
+        self.filter_presets = presets;
     }
 
-    /// Apply fuzzy filter to entries
-    fn apply_filter(&mut self) {
-        let entry_names: Vec<String> = self.entries.iter().map(|e| e.name.clone()).collect();
-        let results = self.fuzzy_matcher.match_entries(&entry_names, &self.search_query);
-        self.match_positions = results.iter().map(|(idx, pos)| (*idx, pos.clone())).collect();
-        self.filtered_entries = results.into_iter().map(|(idx, _)| idx).collect();
+    /// Set the external commands, as (name, plugin) pairs
+    pub fn set_plugins(&mut self, plugins: Vec<(String, Plugin)>) {
+        self.plugins = plugins;
+    }
 
-        // Reset selection if out of bounds
-        if self.selected_index >= self.filtered_entries.len() {
-            self.selected_index = 0;
+    /// Apply `filter` to the listing, or clear it when it's already the
+    /// active filter. Returns whether the filter is now active.
+    pub fn toggle_filter(&mut self, filter: &str) -> bool {
+        if self.search_query == filter {
+            self.set_search_query(String::new());
+            false
+        } else {
+            self.set_search_query(filter.to_string());
+            true
         }
     }
 
-    /// Get matched char positions for an entry index (for highlight rendering)
-    pub fn match_positions_for(&self, entry_idx: usize) -> &[u32] {
-        self.match_positions.get(&entry_idx).map(|v| v.as_slice()).unwrap_or(&[])
+    /// Set how many entries on each side of the cursor are prefetched while idle
+    pub fn set_prefetch_distance(&mut self, distance: usize) {
+        self.prefetch_distance = distance;
     }
 
-    /// Get matched char positions for a history entry index (for highlight rendering)
-    pub fn history_match_positions_for(&self, history_idx: usize) -> &[u32] {
-        self.history_match_positions.get(&history_idx).map(|v| v.as_slice()).unwrap_or(&[])
+    /// Location and selected entry a prefetch would be for
+    fn prefetch_key(&self) -> Option<(String, String)> {
+        let entry = self.selected_entry()?;
+        Some((self.backend.get_display_path(&self.current_prefix), entry.name.clone()))
     }
 
-    /// Move selection up
-    pub fn move_up(&mut self) {
+    /// Whether the cursor moved since the last prefetch, so an idle
+    /// moment should prefetch around it
+    pub fn wants_prefetch(&self) -> bool {
+        self.prefetch_distance > 0 && self.mode != AppMode::History && self.prefetch_key() != self.prefetched_for
+    }
+
+    /// Directories (as prefixes) and files (as paths) around the cursor,
+    /// nearest first: the selected entry, then the ones right below and above it
+    pub fn prefetch_targets(&self) -> (Vec<String>, Vec<String>) {
+        let (mut prefixes, mut files) = (Vec::new(), Vec::new());
+        let selected = self.selected_index;
+        let nearby = (0..=self.prefetch_distance).flat_map(|d| {
+            let below = selected.checked_add(d);
+            let above = if d > 0 { selected.checked_sub(d) } else { None };
+            below.into_iter().chain(above)
+        });
+        for filtered_idx in nearby {
+            let Some(entry) = self.filtered_entries.get(filtered_idx).and_then(|&i| self.entries.get(i)) else {
+                continue;
+            };
+            let path = if self.current_prefix.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", self.current_prefix, entry.name)
+            };
+            if entry.is_dir {
+                prefixes.push(path);
+            } else if filtered_idx != selected
+                && !self.preview_cache.contains_key(&path)
+                && !self.preview_queries.contains_key(&path)
+                && !self.preview_handler_for(&path).is_some_and(PreviewHandler::reads_file)
+            {
+                // The selected file's preview is loaded anyway; handlers can be
+                // slow commands, so they only run for the selected file
+                files.push(path);
+            }
+        }
+        (prefixes, files)
+    }
+
+    /// Set how many history entries are kept and which locations are left out.
+    /// Exclude patterns starting with `re:` are regular expressions; the ones
+    /// that don't compile are left out and reported in the error.
+    pub fn set_history_rules(&mut self, max_entries: usize, skip_numeric_folders: bool, exclude_patterns: Vec<String>) -> Result<()> {
+        self.history_max_entries = max_entries;
+        self.history_skip_numeric_folders = skip_numeric_folders;
+        self.history.truncate(max_entries);
+
+        self.history_exclude_patterns.clear();
+        self.history_exclude_regexes.clear();
+        let mut invalid = Vec::new();
+        for pattern in exclude_patterns {
+            match pattern.strip_prefix("re:") {
+                Some(regex) => match Regex::new(regex) {
+                    Ok(regex) => self.history_exclude_regexes.push(regex),
+                    Err(e) => invalid.push(format!("{:?} ({})", regex, e)),
+                },
+                None => self.history_exclude_patterns.push(pattern),
+            }
+        }
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid history_exclude_patterns regex: {}", invalid.join(", ")))
+        }
+    }
+
+    /// Set how long status messages stay visible
+    pub fn set_status_timeout_secs(&mut self, secs: u64) {
+        self.status_timeout = std::time::Duration::from_secs(secs);
+    }
+
+    /// Set how long a pending key waits for the rest of its key sequence
+    pub fn set_sequence_timeout_ms(&mut self, ms: u64) {
+        self.sequence_timeout = std::time::Duration::from_millis(ms);
+    }
+
+    /// Set the key bindings used to resolve key presses
+    pub fn set_key_bindings(&mut self, bindings: KeyBindings) {
+        self.key_bindings = bindings;
+    }
+
+    /// Set how many files' highlighted previews are kept
+    pub fn set_highlight_cache_size(&mut self, size: usize) {
+        self.highlight_cache_size = size;
+    }
+
+    /// Report listings and previews taking longer than `millis` (0 = never)
+    pub fn set_slow_request_threshold(&mut self, millis: u64) {
+        self.slow_request_threshold = (millis > 0).then(|| std::time::Duration::from_millis(millis));
+    }
+
+    /// Warn about a request that took `elapsed`, if that's slow
+    fn report_slow_request(&mut self, what: &str, elapsed: std::time::Duration) {
+        if self.slow_request_threshold.is_some_and(|threshold| elapsed >= threshold) {
+            self.show_warning(format!("Slow request: {} took {:.1}s", what, elapsed.as_secs_f64()));
+        }
+    }
+
+    /// Record that the preview of `path` started loading
+    pub fn start_preview_load(&mut self, path: String) {
+        self.preview_canceled = None;
+        self.preview_loading = Some((path, std::time::Instant::now()));
+    }
+
+    /// Record that the preview loading was stopped; it loads again when the
+    /// file is selected again
+    pub fn cancel_preview_load(&mut self) {
+        self.preview_canceled = self.preview_loading.take().map(|(path, _)| path);
+    }
+
+    /// Whether loading the selected file's preview was canceled
+    pub fn is_preview_canceled(&self) -> bool {
+        self.preview_canceled.is_some() && self.preview_canceled == self.selected_object_path()
+    }
+
+    /// How long the preview of the selected file has been loading, if it is
+    pub fn preview_load_elapsed(&self) -> Option<std::time::Duration> {
+        let (path, started) = self.preview_loading.as_ref()?;
+        let waiting = self.selected_object_path().as_deref() == Some(path.as_str())
+            && self.current_preview_path.as_deref() != Some(path.as_str());
+        waiting.then(|| started.elapsed())
+    }
+
+    /// Syntax-highlighted lines of `path`, as far as highlighting has got
+    pub fn highlighted(&self, path: &str) -> Option<&[Line<'static>]> {
+        self.highlighted.get(path).map(|highlight| highlight.lines.as_slice())
+    }
+
+    /// Whether every line of `path` has been highlighted
+    pub fn is_highlight_complete(&self, path: &str) -> bool {
+        self.highlighted.get(path).is_some_and(|highlight| highlight.complete)
+    }
+
+    /// Drop the highlighted lines of `path` (its preview content is being replaced)
+    pub fn forget_highlight(&mut self, path: &str) {
+        self.highlighted.remove(path);
+        self.highlight_order.retain(|p| p != path);
+    }
+
+    /// Keep the highlighting of `path`, which is current for its cached
+    /// preview, evicting that of the file shown least recently
+    fn store_highlight(&mut self, path: String, highlight: Highlight) {
+        self.forget_highlight(&path);
+        while self.highlighted.len() >= self.highlight_cache_size.max(1) {
+            // Never the file on screen
+            let Some(index) = self.highlight_order.iter().position(|p| Some(p) != self.current_preview_path.as_ref()) else {
+                break;
+            };
+            let evicted = self.highlight_order.remove(index);
+            self.highlighted.remove(&evicted);
+        }
+        self.highlight_order.push(path.clone());
+        self.highlighted.insert(path, highlight);
+    }
+
+    /// Add a chunk of highlighted lines of `path`. The first chunk is kept if
+    /// it was highlighted from the text the preview shows; later ones if they
+    /// continue it. Returns whether the lines were kept.
+    fn receive_highlight(&mut self, path: String, text: Arc<str>, start: usize, lines: Vec<Line<'static>>, complete: bool) -> bool {
+        if start == 0 {
+            // The preview may have been reloaded or decoded differently since
+            let current = matches!(self.preview_cache.get(&path), Some(PreviewContent::Text(current, _)) if **current == *text);
+            if current {
+                self.store_highlight(path, Highlight { source: text, lines, complete });
+            }
+            return current;
+        }
+        match self.highlighted.get_mut(&path) {
+            Some(highlight) if Arc::ptr_eq(&highlight.source, &text) && highlight.lines.len() == start => {
+                highlight.lines.extend(lines);
+                highlight.complete = complete;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Mark the highlighted lines of `path` as just shown
+    fn touch_highlight(&mut self, path: &str) {
+        if let Some(index) = self.highlight_order.iter().position(|p| p == path) {
+            let path = self.highlight_order.remove(index);
+            self.highlight_order.push(path);
+        }
+    }
+
+    /// Set the hidden entry name patterns (applied on the next listing)
+    pub fn set_ignore_patterns(&mut self, patterns: Vec<String>) {
+        self.ignore_patterns = patterns;
+    }
+
+    /// Replace the current entries, folding folder markers and objects named like a
+    /// directory into it and dropping those matching an ignore pattern
+    fn set_entries(&mut self, mut entries: Vec<Entry>) {
+        if self.hide_folder_markers {
+            // The flat view lists files only
+            if self.flat_view {
+                entries.retain(|e| !backend::is_folder_marker(e));
+            } else {
+                backend::fold_folder_markers(&mut entries);
+            }
+        }
+        if !self.flat_view {
+            backend::merge_dir_objects(&mut entries);
+        }
+        if !self.ignore_patterns.is_empty() {
+            entries.retain(|e| !self.ignore_patterns.iter().any(|p| glob_match(p, &e.name)));
+        }
+        self.entry_names = Arc::new(entries.iter().map(|e| e.name.clone()).collect());
+        self.entries = entries;
+        self.listing_source = None;
+        // Indices into the old entries; a background filter may take a while to replace them
+        self.filtered_entries.clear();
+        self.match_positions.clear();
+        self.apply_pinned_filters();
+    }
+
+    /// Select the entry with the given name in the current filtered list.
+    /// Used to restore selection after exiting search mode.
+    pub fn select_entry_by_name(&mut self, name: &str) {
+        for (filtered_idx, &entry_idx) in self.filtered_entries.iter().enumerate() {
+            if let Some(entry) = self.entries.get(entry_idx) {
+                if entry.name == name {
+                    self.selected_index = filtered_idx;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Update entries and select a specific entry by name
+    pub fn update_entries_and_select(&mut self, result: ListResult, select_name: &str) {
+        if result.prefix != self.current_prefix {
+            self.descended_chain = None;
+        }
+        self.set_entries(result.entries);
+        self.current_prefix = result.prefix;
+        self.apply_filter();
+        if self.filtering {
+            self.filter_select = Some(select_name.to_string());
+            return;
+        }
+
+        // Find the entry with the given name and select it
+        for (filtered_idx, &entry_idx) in self.filtered_entries.iter().enumerate() {
+            if let Some(entry) = self.entries.get(entry_idx) {
+                if entry.name == select_name {
+                    self.selected_index = filtered_idx;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Update search query and re-filter
+    pub fn set_search_query(&mut self, query: String) {
+        self.search_query = query;
+
+        // Apply filter to either history or entries depending on what we're searching
+        if self.searching_history || self.mode == AppMode::History {
+            self.apply_history_filter();
+        } else {
+            self.apply_filter();
+        }
+    }
+
+    /// Apply fuzzy filter to entries, narrowing those left by the pinned
+    /// filters. Large listings are handed to a background job; the current
+    /// matches stay until its results arrive.
+    fn apply_filter(&mut self) {
+        // A newer filter supersedes the one in flight
+        if let Some(canceled) = self.filter_cancel.take() {
+            canceled.store(true, Ordering::Relaxed);
+        }
+        self.filter_generation += 1;
+        self.pending_filter = None;
+        self.filter_base = None;
+
+        let candidates = self.pinned_matches.as_ref().map_or(self.entries.len(), Vec::len);
+        if !self.search_query.is_empty() && !is_glob(&self.search_query) && candidates >= self.background_filter_threshold {
+            let names = match &self.pinned_matches {
+                Some(base) => {
+                    self.filter_base = Some(base.iter().map(|(idx, _)| *idx).collect());
+                    Arc::new(base.iter().map(|(idx, _)| self.entry_names[*idx].clone()).collect())
+                }
+                None => self.entry_names.clone(),
+            };
+            let canceled = Arc::new(AtomicBool::new(false));
+            self.pending_filter = Some(FilterJob::new(
+                self.filter_generation,
+                names,
+                self.search_query.clone(),
+                self.fuzzy_matcher.weights(),
+                canceled.clone(),
+            ));
+            self.filter_cancel = Some(canceled);
+            self.filtering = true;
+            return;
+        }
+
+        self.filtering = false;
+        self.filter_select = None;
+        let results = narrow_entries(
+            &mut self.fuzzy_matcher,
+            &self.entries,
+            &self.entry_names,
+            self.pinned_matches.as_deref(),
+            &self.search_query,
+        );
+        self.set_filter_results(results);
+    }
+
+    /// Recompute the entries left by the pinned filters
+    fn apply_pinned_filters(&mut self) {
+        let mut matches: Option<Vec<(usize, Vec<u32>)>> = None;
+        for filter in &self.pinned_filters {
+            matches = Some(narrow_entries(&mut self.fuzzy_matcher, &self.entries, &self.entry_names, matches.as_deref(), filter));
+        }
+        self.pinned_matches = matches;
+    }
+
+    /// Pin the filter confirmed in normal mode, so the next query narrows its
+    /// matches instead of replacing it. Returns whether there was one to pin.
+    pub fn pin_search_query(&mut self) -> bool {
+        if self.mode != AppMode::Normal || self.search_query.is_empty() {
+            return false;
+        }
+        self.pinned_filters.push(std::mem::take(&mut self.search_query));
+        self.apply_pinned_filters();
+        self.apply_filter();
+        true
+    }
+
+    /// Filters pinned before the current query, oldest first
+    pub fn pinned_filters(&self) -> &[String] {
+        &self.pinned_filters
+    }
+
+    /// Whether the listing is narrowed by a query or pinned filters
+    pub fn is_filtered(&self) -> bool {
+        !self.search_query.is_empty() || !self.pinned_filters.is_empty()
+    }
+
+    fn set_filter_results(&mut self, mut results: Vec<(usize, Vec<u32>)>) {
+        if self.kind_filter != KindFilter::All {
+            results.retain(|(idx, _)| self.entries.get(*idx).is_some_and(|entry| self.kind_filter.keeps(entry)));
+        }
+        self.match_positions = results.iter().map(|(idx, pos)| (*idx, pos.clone())).collect();
+        self.match_count = results.len();
+        self.filtered_entries = results.into_iter().map(|(idx, _)| idx).collect();
+        self.groups.clear();
+        if self.group_by_extension {
+            self.group_filtered_entries();
+        }
+
+        // Reset selection if out of bounds
+        if self.selected_index >= self.filtered_entries.len() {
+            self.selected_index = 0;
+        }
+    }
+
+    /// Reorder the filtered entries into directories followed by one group of
+    /// files per extension (alphabetical, files without one last), each after
+    /// its header and left out if collapsed. Files keep their filter order
+    /// within a group.
+    fn group_filtered_entries(&mut self) {
+        let mut dirs = Vec::new();
+        let mut files: BTreeMap<(bool, String), Vec<usize>> = BTreeMap::new();
+        for &idx in &self.filtered_entries {
+            let entry = &self.entries[idx];
+            if entry.is_dir {
+                dirs.push(idx);
+            } else {
+                let extension = group_extension(&entry.name);
+                files.entry((extension.is_empty(), extension)).or_default().push(idx);
+            }
+        }
+
+        let mut rows = dirs;
+        for ((_, extension), members) in files {
+            let collapsed = self.collapsed_groups.contains(&extension);
+            rows.push(self.entries.len() + self.groups.len());
+            self.groups.push(EntryGroup {
+                count: members.len(),
+                size: members.iter().filter_map(|&idx| self.entries[idx].size).sum(),
+                extension,
+                collapsed,
+            });
+            if !collapsed {
+                rows.extend(members);
+            }
+        }
+        self.filtered_entries = rows;
+    }
+
+    /// Show the matches of the latest background filter, ignoring superseded ones
+    fn receive_filter_update(&mut self, update: FilterUpdate) {
+        if update.generation != self.filter_generation {
+            return;
+        }
+        let mut matches = update.matches;
+        if let Some(base) = &self.filter_base {
+            for (idx, _) in &mut matches {
+                *idx = base[*idx];
+            }
+        }
+        self.set_filter_results(matches);
+        if update.complete {
+            self.filtering = false;
+            self.filter_cancel = None;
+            if let Some(name) = self.filter_select.take() {
+                self.select_entry_by_name(&name);
+            }
+        }
+    }
+
+    /// Record where the current listing came from
+    pub fn set_listing_source(&mut self, source: ListingSource) {
+        self.listing_source = Some(source);
+        if let ListingSource::Fetched(elapsed) = source {
+            let location = self.backend.get_display_path(&self.current_prefix);
+            self.report_slow_request(&format!("listing {}", location), elapsed);
+        }
+    }
+
+    /// Where the current listing came from, if known
+    pub fn listing_source(&self) -> Option<ListingSource> {
+        self.listing_source
+    }
+
+    /// Count a listing of `keys` keys fetched from the backend
+    pub fn record_listing(&mut self, keys: usize) {
+        self.usage.record_listing(keys);
+    }
+
+    /// Data listed, previewed and transferred this session
+    pub fn usage(&self) -> &SessionUsage {
+        &self.usage
+    }
+
+    /// Background filter for the event loop to start, if one is due
+    pub fn take_filter_job(&mut self) -> Option<FilterJob> {
+        self.pending_filter.take()
+    }
+
+    /// Whether a background filter is still matching the entries
+    pub fn is_filtering(&self) -> bool {
+        self.filtering
+    }
+
+    /// Set the listing size from which filtering runs in the background
+    pub fn set_background_filter_threshold(&mut self, entries: usize) {
+        self.background_filter_threshold = entries;
+    }
+
+    /// Get matched char positions for an entry index (for highlight rendering)
+    pub fn match_positions_for(&self, entry_idx: usize) -> &[u32] {
+        self.match_positions.get(&entry_idx).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// Get matched char positions for a history entry index (for highlight rendering)
+    pub fn history_match_positions_for(&self, history_idx: usize) -> &[u32] {
+        self.history_match_positions.get(&history_idx).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// Move selection up
+    pub fn move_up(&mut self) {
         if self.selected_index > 0 {
             self.selected_index -= 1;
         }
@@ -355,6 +1889,24 @@ impl App {
         }
     }
 
+    /// Select the next (or previous) file of the listing, skipping
+    /// directories. Returns false if there is none.
+    pub fn select_adjacent_file(&mut self, forward: bool) -> bool {
+        let is_file = |&idx: &usize| self.entries.get(idx).is_some_and(|e| !e.is_dir);
+        let found = if forward {
+            self.filtered_entries.iter().skip(self.selected_index + 1).position(is_file).map(|i| self.selected_index + 1 + i)
+        } else {
+            self.filtered_entries[..self.selected_index.min(self.filtered_entries.len())].iter().rposition(is_file)
+        };
+        match found {
+            Some(index) => {
+                self.selected_index = index;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Jump up by multiple items
     pub fn jump_up(&mut self, count: usize) {
         self.selected_index = self.selected_index.saturating_sub(count);
@@ -400,20 +1952,94 @@ impl App {
         self.pending_key_instant.map(|t| t.elapsed())
     }
 
-    /// Add path to history (most recent first, avoid duplicates)
-    pub fn add_to_history(&mut self, path: String) {
+    /// Give up on a pending key that waited longer than the sequence timeout:
+    /// typed into the query in search mode, dropped otherwise. Returns whether
+    /// there was one.
+    fn expire_pending_key(&mut self) -> bool {
+        let Some(c) = self.pending_key else {
+            return false;
+        };
+        if self.pending_key_elapsed().is_none_or(|d| d <= self.sequence_timeout) {
+            return false;
+        }
+        self.clear_pending_key();
+        if self.is_search_mode() {
+            self.append_search_char(c);
+        }
+        true
+    }
+
+    /// Add a location to the history (most recent first, avoid duplicates)
+    pub fn add_to_history(&mut self, entry: impl Into<HistoryEntry>) {
+        let entry = entry.into();
         // Remove existing entry if present
-        self.history.retain(|p| p != &path);
+        self.history.retain(|e| e.uri != entry.uri);
         // Add to front
-        self.history.insert(0, path);
-        // Keep only last 100 entries
-        if self.history.len() > 100 {
-            self.history.truncate(100);
+        self.history.insert(0, entry);
+        self.history.truncate(self.history_max_entries);
+    }
+
+    /// Add a visited prefix of the current backend to the history, unless the
+    /// history rules leave it out (the root, all-digit folders, excluded paths)
+    pub fn remember_location(&mut self, prefix: &str) {
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            return;
+        }
+        let display_path = self.backend.get_display_path(prefix);
+        if self.history_excludes(prefix, &display_path) {
+            return;
+        }
+        let visited_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|since| since.as_secs());
+        self.add_to_history(HistoryEntry {
+            uri: display_path,
+            scheme: self.backend.kind(),
+            root: self.backend.location_name(),
+            prefix: prefix.to_string(),
+            profile: self.backend.profile(),
+            region: self.backend.region(),
+            visited_at,
+        });
+    }
+
+    /// Whether the history rules leave the location at `prefix` (shown as
+    /// `display_path`) out: all-digit last folders (unless allowed) and
+    /// excluded paths (globs, or regexes found anywhere in the path). A bucket
+    /// or root is never an all-digit folder.
+    fn history_excludes(&self, prefix: &str, display_path: &str) -> bool {
+        let numeric_folder = prefix
+            .split('/')
+            .rfind(|s| !s.is_empty())
+            .is_some_and(|folder| folder.chars().all(|c| c.is_ascii_digit()));
+        (self.history_skip_numeric_folders && numeric_folder)
+            || self.history_exclude_patterns.iter().any(|p| glob_match(p, display_path))
+            || self.history_exclude_regexes.iter().any(|re| re.is_match(display_path))
+    }
+
+    /// Add locations found elsewhere (`:import-history`) below the ones
+    /// visited here, following the history rules. Returns how many were new.
+    pub fn import_history(&mut self, uris: &[String]) -> usize {
+        let mut added = 0;
+        for uri in uris {
+            if self.history.len() >= self.history_max_entries {
+                break;
+            }
+            let entry = HistoryEntry::from_uri(uri.clone());
+            if self.history_excludes(&entry.prefix, uri) || self.history.iter().any(|entry| entry.uri == *uri) {
+                continue;
+            }
+            self.history.push(entry);
+            added += 1;
         }
+        self.apply_history_filter();
+        added
     }
 
     /// Get history entries
-    pub fn history(&self) -> &[String] {
+    pub fn history(&self) -> &[HistoryEntry] {
         &self.history
     }
 
@@ -423,18 +2049,16 @@ impl App {
     }
 
     /// Load history from state
-    pub fn load_history(&mut self, history: Vec<String>) {
+    pub fn load_history(&mut self, history: Vec<HistoryEntry>) {
         self.history = history;
-        // Keep only last 100 entries
-        if self.history.len() > 100 {
-            self.history.truncate(100);
-        }
+        self.history.truncate(self.history_max_entries);
         self.apply_history_filter();
     }
 
     /// Apply fuzzy filter to history entries
     fn apply_history_filter(&mut self) {
-        let results = self.fuzzy_matcher.match_entries(&self.history, &self.search_query);
+        let uris: Vec<String> = self.history.iter().map(|entry| entry.uri.clone()).collect();
+        let results = self.fuzzy_matcher.match_entries(&uris, &self.search_query);
         self.history_match_positions = results.iter().map(|(idx, pos)| (*idx, pos.clone())).collect();
         self.filtered_history = results.into_iter().map(|(idx, _)| idx).collect();
 
@@ -464,7 +2088,7 @@ impl App {
     }
 
     /// Get selected history entry
-    pub fn selected_history_entry(&self) -> Option<&String> {
+    pub fn selected_history_entry(&self) -> Option<&HistoryEntry> {
         self.filtered_history
             .get(self.history_selected_index)
             .and_then(|&idx| self.history.get(idx))
@@ -518,8 +2142,7 @@ impl App {
                     // Get the last component of the path
                     self.current_prefix
                         .split('/')
-                        .filter(|s| !s.is_empty())
-                        .last()
+                        .rfind(|s| !s.is_empty())
                         .map(|s| s.to_string())
                 };
 
@@ -537,58 +2160,659 @@ impl App {
         self.mode = AppMode::Search;
     }
 
-    /// Exit search mode and clear search
-    pub fn exit_search_mode(&mut self) {
-        // If we were searching history, go back to history mode
-        if self.searching_history {
-            self.mode = AppMode::History;
-            self.searching_history = false;
-        } else {
-            self.mode = AppMode::Normal;
-        }
-        self.set_search_query(String::new());
+    /// Enter command mode (`:`)
+    pub fn enter_command_mode(&mut self) {
+        self.mode = AppMode::Command;
+        self.command_input.clear();
     }
 
-    /// Check if in search mode
-    pub fn is_search_mode(&self) -> bool {
-        self.mode == AppMode::Search
+    /// Exit command mode, returning the typed command
+    pub fn exit_command_mode(&mut self) -> String {
+        self.mode = AppMode::Normal;
+        let command = std::mem::take(&mut self.command_input);
+        self.update_jq_draft();
+        command
     }
 
-    /// Check if searching history
-    pub fn is_searching_history(&self) -> bool {
-        self.searching_history
+    /// Get the command line input
+    pub fn command_input(&self) -> &str {
+        &self.command_input
     }
 
-    /// Append character to search query (only in search mode)
-    pub fn append_search_char(&mut self, c: char) {
-        if self.is_search_mode() {
-            self.search_query.push(c);
+    /// Append character to the command line
+    pub fn append_command_char(&mut self, c: char) {
+        self.command_input.push(c);
+        self.update_jq_draft();
+    }
 
-            // Apply filter to either history or entries depending on what we're searching
-            if self.searching_history {
-                self.apply_history_filter();
-            } else {
-                self.apply_filter();
-            }
+    /// Remove the last character from the command line; leaves command mode when empty
+    pub fn command_backspace(&mut self) {
+        if self.command_input.pop().is_none() {
+            self.mode = AppMode::Normal;
         }
+        self.update_jq_draft();
+    }
+
+    /// Apply a `:jq` filter to the JSON Lines preview while it's being typed,
+    /// restoring the previous filter once the command line no longer holds one
+    fn update_jq_draft(&mut self) {
+        let Some(path) = self.current_preview_path.clone() else { return };
+        let Some(view) = self.preview_jsonl.get(&path) else { return };
+        let filter = match self.command_input.strip_prefix("jq ") {
+            Some(filter) if self.mode == AppMode::Command => {
+                self.jq_draft.get_or_insert_with(|| view.filter().map(str::to_string));
+                filter.to_string()
+            }
+            _ => match self.jq_draft.take() {
+                Some(previous) => previous.unwrap_or_default(),
+                None => return,
+            },
+        };
+        self.start_jq_filter(path, &filter, false);
     }
 
-    /// Remove last character from search query (only in search mode)
-    pub fn backspace_search(&mut self) {
-        if self.is_search_mode() {
-            self.search_query.pop();
+    /// Filter the JSON Lines preview of `path` with `filter`, superseding the
+    /// filter in flight. An empty filter shows the records again right away;
+    /// others are handed to a background job, and the current output stays
+    /// until its result arrives.
+    fn start_jq_filter(&mut self, path: String, filter: &str, entered: bool) {
+        if let Some(canceled) = self.jq_cancel.take() {
+            canceled.store(true, Ordering::Relaxed);
+        }
+        self.jq_generation += 1;
+        self.pending_jq = None;
+        self.jq_target = None;
+        let Some(view) = self.preview_jsonl.get_mut(&path) else { return };
+        if filter.trim().is_empty() {
+            view.set_filter("");
+            self.reset_preview_scroll();
+            return;
+        }
+        let canceled = Arc::new(AtomicBool::new(false));
+        self.pending_jq = Some(view.filter_job(filter, self.jq_generation, canceled.clone()));
+        self.jq_cancel = Some(canceled);
+        self.jq_target = Some((path, entered));
+    }
 
-            // Apply filter to either history or entries depending on what we're searching
-            if self.searching_history {
-                self.apply_history_filter();
-            } else {
-                self.apply_filter();
-            }
+    /// Show the output of the latest jq filter, ignoring superseded ones
+    fn receive_jq_result(&mut self, result: JqResult) {
+        if result.generation != self.jq_generation {
+            return;
+        }
+        self.jq_cancel = None;
+        let Some((path, entered)) = self.jq_target.take() else { return };
+        let Some(view) = self.preview_jsonl.get_mut(&path) else { return };
+        let error = result.output.as_ref().err().filter(|_| entered).map(|e| format!("jq: {}", e));
+        view.set_filter_result(result);
+        if let Some(error) = error {
+            self.show_error(error);
+        }
+        if self.current_preview_path.as_deref() == Some(path.as_str()) {
+            self.reset_preview_scroll();
         }
     }
 
-    /// Get backend reference
-    pub fn backend(&self) -> &Arc<dyn Backend> {
+    /// jq filter for the event loop to start, if one is due
+    pub fn take_jq_job(&mut self) -> Option<JqJob> {
+        self.pending_jq.take()
+    }
+
+    /// Whether a jq filter of the shown JSON Lines preview is still running
+    pub fn is_jq_filtering(&self) -> bool {
+        self.jq_target.as_ref().is_some_and(|(path, _)| self.current_preview_path.as_ref() == Some(path))
+    }
+
+    /// Open the summary overlay for a new scan
+    pub fn start_summary(&mut self, location: String, cancel_tx: tokio::sync::oneshot::Sender<()>) {
+        self.close_summary();
+        self.summary_view = Some(SummaryView {
+            location,
+            summary: PrefixSummary::default(),
+            complete: false,
+            error: None,
+            cancel_tx: Some(cancel_tx),
+        });
+        self.mode = AppMode::Summary;
+    }
+
+    /// Update the summary overlay with new totals (ignored if it shows another location)
+    pub fn update_summary(&mut self, location: &str, summary: PrefixSummary, complete: bool) {
+        if let Some(view) = self.summary_view.as_mut().filter(|v| v.location == location) {
+            view.summary = summary;
+            view.complete = complete;
+            if complete {
+                view.cancel_tx = None;
+            }
+        }
+    }
+
+    /// Record a failed summary scan
+    pub fn fail_summary(&mut self, location: &str, error: String) {
+        if let Some(view) = self.summary_view.as_mut().filter(|v| v.location == location) {
+            view.error = Some(error);
+            view.cancel_tx = None;
+        }
+    }
+
+    /// Close the summary overlay, canceling the scan if it's still running
+    pub fn close_summary(&mut self) {
+        if let Some(mut view) = self.summary_view.take() {
+            if let Some(cancel_tx) = view.cancel_tx.take() {
+                let _ = cancel_tx.send(());
+            }
+        }
+        if self.mode == AppMode::Summary {
+            self.mode = AppMode::Normal;
+        }
+    }
+
+    /// Get the summary overlay state
+    pub fn summary_view(&self) -> Option<&SummaryView> {
+        self.summary_view.as_ref()
+    }
+
+    /// Open the grep results overlay for a new search
+    pub fn start_grep(&mut self, pattern: String, location: String, cancel_tx: tokio::sync::oneshot::Sender<()>) {
+        self.close_grep();
+        self.grep_view = Some(GrepView {
+            pattern,
+            location,
+            matches: Vec::new(),
+            files_searched: 0,
+            complete: false,
+            error: None,
+            selected: 0,
+            cancel_tx: Some(cancel_tx),
+        });
+        self.mode = AppMode::Grep;
+    }
+
+    /// Add matches to the grep overlay (ignored if it shows another search)
+    pub fn update_grep(&mut self, location: &str, matches: Vec<GrepMatch>, files_searched: usize, complete: bool) {
+        if let Some(view) = self.grep_view.as_mut().filter(|v| v.location == location) {
+            view.matches.extend(matches);
+            view.files_searched = files_searched;
+            view.complete = complete;
+            if complete {
+                view.cancel_tx = None;
+            }
+        }
+    }
+
+    /// Record a failed content search
+    pub fn fail_grep(&mut self, location: &str, error: String) {
+        if let Some(view) = self.grep_view.as_mut().filter(|v| v.location == location) {
+            view.error = Some(error);
+            view.cancel_tx = None;
+        }
+    }
+
+    /// Close the grep overlay, canceling the search if it's still running
+    pub fn close_grep(&mut self) {
+        if let Some(mut view) = self.grep_view.take() {
+            if let Some(cancel_tx) = view.cancel_tx.take() {
+                let _ = cancel_tx.send(());
+            }
+        }
+        if self.mode == AppMode::Grep {
+            self.mode = AppMode::Normal;
+        }
+    }
+
+    /// Get the grep overlay state
+    pub fn grep_view(&self) -> Option<&GrepView> {
+        self.grep_view.as_ref()
+    }
+
+    /// Move up in the grep results
+    pub fn grep_move_up(&mut self) {
+        if let Some(view) = self.grep_view.as_mut() {
+            view.selected = view.selected.saturating_sub(1);
+        }
+    }
+
+    /// Move down in the grep results
+    pub fn grep_move_down(&mut self) {
+        if let Some(view) = self.grep_view.as_mut() {
+            if view.selected + 1 < view.matches.len() {
+                view.selected += 1;
+            }
+        }
+    }
+
+    /// Open the properties popup of the selected file with what its listing
+    /// entry tells. Returns the path to ask the backend about; None (with a
+    /// warning) for directories, unless an object shares the directory's name.
+    pub fn open_properties(&mut self) -> Option<String> {
+        let entry = self.selected_entry()?;
+        if entry.is_dir && !entry.has_object() {
+            self.show_warning("Properties are only shown for files");
+            return None;
+        }
+        let rows = [
+            ("Size", entry.size.map(|size| format!("{} bytes", size))),
+            ("Last modified", entry.modified.clone()),
+            ("ETag", entry.etag.clone()),
+            ("Storage class", entry.storage_class.clone()),
+            (
+                "Permissions",
+                entry.permissions.as_ref().map(|p| format!("{} {} {}", p.mode_string(), p.owner, p.group).trim_end().to_string()),
+            ),
+        ];
+        let location = self.selected_display_path();
+        let mut rows: Vec<(String, String)> = rows
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value?)))
+            .collect();
+        rows.insert(0, ("Path".to_string(), location.clone()));
+        let path = self.selected_object_path();
+        self.properties_view = Some(PropertiesView { location, rows, complete: false, error: None, selected: 0 });
+        self.mode = AppMode::Properties;
+        path
+    }
+
+    /// Add the backend's details to the properties popup, replacing rows of
+    /// the same name (ignored if it shows another object)
+    pub fn update_properties(&mut self, location: &str, result: Result<Vec<(String, String)>, String>) {
+        let Some(view) = self.properties_view.as_mut().filter(|v| v.location == location) else {
+            return;
+        };
+        view.complete = true;
+        match result {
+            Ok(details) => {
+                for (name, value) in details {
+                    match view.rows.iter_mut().find(|(existing, _)| *existing == name) {
+                        Some(row) => row.1 = value,
+                        None => view.rows.push((name, value)),
+                    }
+                }
+            }
+            Err(error) => view.error = Some(error),
+        }
+    }
+
+    /// Close the properties popup
+    pub fn close_properties(&mut self) {
+        self.properties_view = None;
+        if self.mode == AppMode::Properties {
+            self.mode = AppMode::Normal;
+        }
+    }
+
+    /// Get the properties popup state
+    pub fn properties_view(&self) -> Option<&PropertiesView> {
+        self.properties_view.as_ref()
+    }
+
+    /// Move up in the properties popup
+    pub fn properties_move_up(&mut self) {
+        if let Some(view) = self.properties_view.as_mut() {
+            view.selected = view.selected.saturating_sub(1);
+        }
+    }
+
+    /// Move down in the properties popup
+    pub fn properties_move_down(&mut self) {
+        if let Some(view) = self.properties_view.as_mut() {
+            if view.selected + 1 < view.rows.len() {
+                view.selected += 1;
+            }
+        }
+    }
+
+    /// The plugin `name` set up to run on the selected entries (the one under
+    /// the cursor when nothing is selected); None with an error if there's
+    /// no such plugin
+    pub fn plugin_run(&mut self, name: &str) -> Option<PluginRun> {
+        let Some((_, plugin)) = self.plugins.iter().find(|(plugin_name, _)| plugin_name == name) else {
+            self.show_error(format!("No plugin named {}", name));
+            return None;
+        };
+        Some(PluginRun {
+            name: name.to_string(),
+            command: plugin.command.clone(),
+            files: self.selection_uris(),
+            prefix: self.backend.get_display_path(&self.current_prefix),
+            profile: self.backend.profile(),
+            output: plugin.output,
+        })
+    }
+
+    /// Display paths of the selected entries, or of the one under the cursor
+    /// when nothing is selected
+    pub fn selection_uris(&self) -> Vec<String> {
+        let mut selected: Vec<usize> = self.selected_files.iter().copied().collect();
+        if selected.is_empty() {
+            return self.selected_entry().map(|_| self.selected_display_path()).into_iter().collect();
+        }
+        selected.sort_unstable();
+        selected
+            .into_iter()
+            .filter_map(|idx| self.entries.get(idx))
+            .map(|entry| {
+                if self.current_prefix.is_empty() {
+                    self.backend.get_display_path(&entry.name)
+                } else {
+                    self.backend.get_display_path(&format!("{}/{}", self.current_prefix, entry.name))
+                }
+            })
+            .collect()
+    }
+
+    /// Open the popup for the output of the plugin `name`, which just started
+    pub fn open_plugin_output(&mut self, name: &str) {
+        self.plugin_output_view =
+            Some(PluginOutputView { name: name.to_string(), lines: Vec::new(), complete: false, error: None, scroll: 0 });
+        self.mode = AppMode::PluginOutput;
+    }
+
+    /// Show what the plugin `name` printed (ignored if the popup was closed
+    /// or shows another plugin)
+    pub fn finish_plugin_output(&mut self, name: &str, output: CapturedOutput) {
+        let Some(view) = self.plugin_output_view.as_mut().filter(|v| v.name == name) else {
+            return;
+        };
+        // Tabs would leave stray cells behind in the popup
+        view.lines = output.text.lines().map(|line| line.replace('\t', "    ")).collect();
+        view.error = output.error;
+        view.complete = true;
+    }
+
+    /// Close the plugin output popup
+    pub fn close_plugin_output(&mut self) {
+        self.plugin_output_view = None;
+        if self.mode == AppMode::PluginOutput {
+            self.mode = AppMode::Normal;
+        }
+    }
+
+    /// Get the plugin output popup state
+    pub fn plugin_output_view(&self) -> Option<&PluginOutputView> {
+        self.plugin_output_view.as_ref()
+    }
+
+    /// Scroll the plugin output popup up by `lines`
+    pub fn plugin_output_scroll_up(&mut self, lines: usize) {
+        if let Some(view) = self.plugin_output_view.as_mut() {
+            view.scroll = view.scroll.saturating_sub(lines);
+        }
+    }
+
+    /// Scroll the plugin output popup down by `lines`, keeping the last line
+    /// in view
+    pub fn plugin_output_scroll_down(&mut self, lines: usize) {
+        if let Some(view) = self.plugin_output_view.as_mut() {
+            view.scroll = (view.scroll + lines).min(view.lines.len().saturating_sub(1));
+        }
+    }
+
+    /// Run `run` in the terminal once the current events are handled
+    pub fn request_terminal_plugin(&mut self, run: PluginRun) {
+        self.terminal_plugin = Some(run);
+    }
+
+    /// Plugin to run in the terminal, if one was requested since the last call
+    pub fn take_terminal_plugin(&mut self) -> Option<PluginRun> {
+        self.terminal_plugin.take()
+    }
+
+    /// Ask whether to run `operation`; it is handed back by `take_confirmation`
+    pub fn ask_confirmation(&mut self, question: String, operation: PendingOperation) {
+        self.confirmation = Some(Confirmation { question, operation });
+        self.mode = AppMode::Confirm;
+    }
+
+    /// Close the confirmation popup, returning its operation (to run it if confirmed)
+    pub fn take_confirmation(&mut self) -> Option<Confirmation> {
+        if self.mode == AppMode::Confirm {
+            self.mode = AppMode::Normal;
+        }
+        self.confirmation.take()
+    }
+
+    /// Record that `command` runs to refresh the credentials, to retry
+    /// `retry` once it succeeds
+    pub fn start_login(&mut self, command: String, retry: Option<Action>) {
+        self.show_info(format!("Running {} (finish the login in your browser if it opens)", command));
+        self.login = Some(Login { command, retry, output: Vec::new() });
+    }
+
+    /// Whether a credential command is running
+    pub fn is_logging_in(&self) -> bool {
+        self.login.is_some()
+    }
+
+    /// Stop offering to refresh expired credentials
+    pub fn decline_login(&mut self) {
+        self.login_declined = true;
+    }
+
+    /// Whether refreshing expired credentials was declined
+    pub fn is_login_declined(&self) -> bool {
+        self.login_declined
+    }
+
+    /// Get the question awaiting an answer
+    pub fn confirmation(&self) -> Option<&Confirmation> {
+        self.confirmation.as_ref()
+    }
+
+    /// Open the audit log overlay on `records` (oldest first), with the most
+    /// recent one selected. `downloads_only` when they're the completed
+    /// downloads of `:downloads`.
+    pub fn open_audit_log(&mut self, records: Vec<AuditRecord>, downloads_only: bool) {
+        let selected = records.len().saturating_sub(1);
+        self.audit_log_view = Some(AuditLogView { records, selected, downloads_only });
+        self.mode = AppMode::AuditLog;
+    }
+
+    /// Close the audit log overlay
+    pub fn close_audit_log(&mut self) {
+        self.audit_log_view = None;
+        if self.mode == AppMode::AuditLog {
+            self.mode = AppMode::Normal;
+        }
+    }
+
+    /// Get the audit log overlay state
+    pub fn audit_log_view(&self) -> Option<&AuditLogView> {
+        self.audit_log_view.as_ref()
+    }
+
+    /// The selected record of the audit log overlay
+    pub fn selected_audit_record(&self) -> Option<&AuditRecord> {
+        self.audit_log_view.as_ref().and_then(|view| view.records.get(view.selected))
+    }
+
+    /// Move up in the audit log overlay
+    pub fn audit_log_move_up(&mut self) {
+        if let Some(view) = self.audit_log_view.as_mut() {
+            view.selected = view.selected.saturating_sub(1);
+        }
+    }
+
+    /// Move down in the audit log overlay
+    pub fn audit_log_move_down(&mut self) {
+        if let Some(view) = self.audit_log_view.as_mut() {
+            if view.selected + 1 < view.records.len() {
+                view.selected += 1;
+            }
+        }
+    }
+
+    /// Close the grep overlay, returning the selected match
+    pub fn take_grep_match(&mut self) -> Option<GrepMatch> {
+        let found = self.grep_view.as_ref().and_then(|v| v.matches.get(v.selected).cloned());
+        self.close_grep();
+        found
+    }
+
+    /// Show the file of a grep match: select it in its (already listed) parent
+    /// and put the preview cursor on the matching line once it loads
+    pub fn open_grep_match(&mut self, found: &GrepMatch, listing: ListResult) {
+        // A filter could hide the file
+        if self.is_search_mode() || self.is_filtered() {
+            self.exit_search_mode();
+        }
+        let (_, name) = grep::split_path(&found.path);
+        self.update_entries_and_select(listing, name);
+        self.focus_preview();
+        self.restore_preview_position(
+            found.path.clone(),
+            PreviewPosition {
+                cursor_line: found.line,
+                scroll_offset: found.line.saturating_sub(GREP_CONTEXT_LINES),
+                column_offset: 0,
+            },
+        );
+    }
+
+    /// Show a diff in the preview pane and focus it for scrolling
+    pub fn open_diff(&mut self, view: DiffView) {
+        self.diff_view = Some(view);
+        self.mode = AppMode::Diff;
+        self.focused_panel = FocusedPanel::Preview;
+        self.reset_preview_scroll();
+    }
+
+    /// Close the diff and return to the file preview
+    pub fn close_diff(&mut self) {
+        self.diff_view = None;
+        if self.mode == AppMode::Diff {
+            self.mode = AppMode::Normal;
+        }
+        self.focused_panel = FocusedPanel::Explorer;
+        self.reset_preview_scroll();
+    }
+
+    /// Switch the diff between unified and side-by-side layout
+    pub fn toggle_diff_layout(&mut self) {
+        if let Some(view) = self.diff_view.as_mut() {
+            view.side_by_side = !view.side_by_side;
+            self.reset_preview_scroll();
+        }
+    }
+
+    /// Get the diff shown in the preview pane
+    pub fn diff_view(&self) -> Option<&DiffView> {
+        self.diff_view.as_ref()
+    }
+
+    /// Show a sync plan for confirmation
+    pub fn open_sync_plan(&mut self, plan: SyncPlan) {
+        self.sync_plan = Some(plan);
+        self.sync_plan_scroll = 0;
+        self.mode = AppMode::Sync;
+    }
+
+    /// Close the sync plan popup, returning the plan (to run it if confirmed)
+    pub fn take_sync_plan(&mut self) -> Option<SyncPlan> {
+        if self.mode == AppMode::Sync {
+            self.mode = AppMode::Normal;
+        }
+        self.sync_plan.take()
+    }
+
+    /// Get the sync plan awaiting confirmation
+    pub fn sync_plan(&self) -> Option<&SyncPlan> {
+        self.sync_plan.as_ref()
+    }
+
+    /// Get sync plan popup scroll offset
+    pub fn sync_plan_scroll(&self) -> usize {
+        self.sync_plan_scroll
+    }
+
+    /// Scroll the sync plan popup up
+    pub fn sync_plan_scroll_up(&mut self, amount: usize) {
+        self.sync_plan_scroll = self.sync_plan_scroll.saturating_sub(amount);
+    }
+
+    /// Scroll the sync plan popup down, never past `max_scroll`
+    pub fn sync_plan_scroll_down(&mut self, amount: usize, max_scroll: usize) {
+        self.sync_plan_scroll = (self.sync_plan_scroll + amount).min(max_scroll);
+    }
+
+    /// Exit search mode and clear search
+    pub fn exit_search_mode(&mut self) {
+        // If we were searching history, go back to history mode
+        if self.searching_history {
+            self.mode = AppMode::History;
+            self.searching_history = false;
+        } else {
+            self.mode = AppMode::Normal;
+        }
+        self.pinned_filters.clear();
+        self.pinned_matches = None;
+        self.set_search_query(String::new());
+    }
+
+    /// Leave search mode keeping the query, so the filtered listing can be
+    /// browsed in normal mode (Esc in normal mode doesn't clear it; `/` narrows it)
+    pub fn accept_search(&mut self) {
+        if !self.is_search_mode() || self.searching_history {
+            return;
+        }
+        self.mode = AppMode::Normal;
+        // An empty query after pinned filters accepts the last of them
+        if self.search_query.is_empty() {
+            if let Some(filter) = self.pinned_filters.pop() {
+                self.search_query = filter;
+                self.apply_pinned_filters();
+                self.apply_filter();
+            }
+        }
+    }
+
+    /// Check if in search mode
+    pub fn is_search_mode(&self) -> bool {
+        self.mode == AppMode::Search
+    }
+
+    /// Check if searching history
+    pub fn is_searching_history(&self) -> bool {
+        self.searching_history
+    }
+
+    /// Append character to search query (only in search mode)
+    pub fn append_search_char(&mut self, c: char) {
+        if self.is_search_mode() {
+            self.search_query.push(c);
+
+            // Apply filter to either history or entries depending on what we're searching
+            if self.searching_history {
+                self.apply_history_filter();
+            } else {
+                self.apply_filter();
+            }
+        }
+    }
+
+    /// Remove last character from search query (only in search mode). At an
+    /// empty query, the last pinned filter is popped back into the query.
+    pub fn backspace_search(&mut self) {
+        if self.is_search_mode() {
+            if self.search_query.is_empty() && !self.searching_history {
+                if let Some(filter) = self.pinned_filters.pop() {
+                    self.search_query = filter;
+                    self.apply_pinned_filters();
+                    self.apply_filter();
+                }
+                return;
+            }
+            self.search_query.pop();
+
+            // Apply filter to either history or entries depending on what we're searching
+            if self.searching_history {
+                self.apply_history_filter();
+            } else {
+                self.apply_filter();
+            }
+        }
+    }
+
+    /// Get backend reference
+    pub fn backend(&self) -> &Arc<dyn Backend> {
         &self.backend
     }
 
@@ -602,6 +2826,17 @@ impl App {
         self.backend.location_name()
     }
 
+    /// Full display path (`s3://bucket/key`, a local path, ...) of the selected
+    /// entry, or of the current location when nothing is selected
+    pub fn selected_display_path(&self) -> String {
+        let bare = match self.selected_entry() {
+            Some(entry) if !self.current_prefix.is_empty() => format!("{}/{}", self.current_prefix, entry.name),
+            Some(entry) => entry.name.clone(),
+            None => self.current_prefix.clone(),
+        };
+        self.backend.get_display_path(&bare)
+    }
+
     /// Get the path for the currently selected file (for preview)
     pub fn get_selected_file_path(&self) -> Option<String> {
         let entry = self.selected_entry()?;
@@ -615,9 +2850,24 @@ impl App {
         })
     }
 
+    /// Path of the object behind the selection, as previewed: the selected file,
+    /// or the object sharing the selected directory's name
+    pub fn selected_object_path(&self) -> Option<String> {
+        let entry = self.selected_entry()?;
+        if entry.is_dir && !entry.has_object() {
+            return None;
+        }
+        Some(if self.current_prefix.is_empty() {
+            entry.name.clone()
+        } else {
+            format!("{}/{}", self.current_prefix, entry.name)
+        })
+    }
+
     /// Set preview content for a path
     pub fn set_preview(&mut self, path: String, content: PreviewContent) {
-        self.preview_cache.insert(path.clone(), content);
+        self.save_preview_position();
+        self.cache_preview(path.clone(), content);
         self.current_preview_path = Some(path);
         self.reset_preview_scroll();
     }
@@ -626,11 +2876,253 @@ impl App {
     /// Always caches the result; only updates the current preview path
     /// if the path is still the currently selected file.
     pub fn receive_preview(&mut self, path: String, content: PreviewContent) {
-        self.preview_cache.insert(path.clone(), content);
-        if self.get_selected_file_path().as_deref() == Some(&path) {
-            self.current_preview_path = Some(path);
+        if let Some((_, started)) = self.preview_loading.take_if(|(loading, _)| *loading == path) {
+            self.report_slow_request(&format!("preview of {}", path), started.elapsed());
+        }
+        let selected = self.selected_object_path().as_deref() == Some(&path);
+        if selected {
+            self.save_preview_position();
+        }
+        self.cache_preview(path.clone(), content);
+        if selected {
             self.reset_preview_scroll();
+            self.restore_saved_preview_position(&path);
+            self.apply_pending_preview_position(&path);
+            self.current_preview_path = Some(path);
+        }
+    }
+
+    /// Version of the cached preview of `path` its position is remembered
+    /// under: the ETag, or the modification time for local files
+    fn preview_version(&self, path: &str) -> Option<String> {
+        match self.preview_cache.get(path)? {
+            PreviewContent::Text(_, meta) => meta.etag.clone().or_else(|| meta.modified.clone()),
+            PreviewContent::Binary { etag, modified, .. } | PreviewContent::TooLarge { etag, modified, .. } => {
+                etag.clone().or_else(|| modified.clone())
+            }
+            PreviewContent::Error(_) => None,
+        }
+    }
+
+    /// Remember where the current file's preview is, to return there when
+    /// the same version of the file is previewed again
+    fn save_preview_position(&mut self) {
+        let Some(path) = self.current_preview_path.clone() else { return };
+        // The diff view scrolls with the preview's position
+        if self.diff_view.is_some() {
+            return;
+        }
+        let key = (path.clone(), self.preview_version(&path));
+        let position = self.preview_position();
+        if position == PreviewPosition::default() {
+            self.preview_positions.remove(&key);
+        } else {
+            self.preview_positions.insert(key, position);
+        }
+    }
+
+    /// Move the preview cursor back to where the preview of `path` was left
+    fn restore_saved_preview_position(&mut self, path: &str) {
+        let key = (path.to_string(), self.preview_version(path));
+        if let Some(position) = self.preview_positions.get(&key) {
+            self.preview_cursor_line = position.cursor_line;
+            self.preview_scroll_offset = position.scroll_offset;
+            self.preview_column_offset = position.column_offset;
+        }
+    }
+
+    /// Move the preview cursor to the position restored for `path`, if any
+    fn apply_pending_preview_position(&mut self, path: &str) {
+        if let Some((_, position)) = self.pending_preview_position.take_if(|(p, _)| p == path) {
+            self.preview_cursor_line = position.cursor_line;
+            self.preview_scroll_offset = position.scroll_offset;
+            self.preview_column_offset = position.column_offset;
+        }
+    }
+
+    /// Cursor and scroll position in the preview pane
+    pub fn preview_position(&self) -> PreviewPosition {
+        PreviewPosition {
+            cursor_line: self.preview_cursor_line,
+            scroll_offset: self.preview_scroll_offset,
+            column_offset: self.preview_column_offset,
+        }
+    }
+
+    /// Move the preview of `path` to `position` when it is next received
+    pub fn restore_preview_position(&mut self, path: String, position: PreviewPosition) {
+        self.pending_preview_position = Some((path, position));
+    }
+
+    /// Cache preview content, parsing tabular files for the table view.
+    /// S3 Select results have no header row, so they stay plain text.
+    fn cache_preview(&mut self, path: String, mut content: PreviewContent) {
+        if let (Some(&encoding), PreviewContent::Text(text, meta)) = (self.encoding_overrides.get(&path), &mut content) {
+            let raw = meta.encoding.as_ref().map_or_else(|| Arc::from(text.as_bytes()), |decoding| decoding.raw.clone());
+            (*text, meta.encoding) = backend::decode_as(raw, encoding);
+        }
+        // Highlighted lines of other text would show that text
+        let unchanged = matches!(
+            (self.preview_cache.get(&path), &content),
+            (Some(PreviewContent::Text(old, _)), PreviewContent::Text(new, _)) if old == new
+        );
+        if !unchanged {
+            self.forget_highlight(&path);
+        }
+        self.preview_tables.remove(&path);
+        self.preview_jsonl.remove(&path);
+        if let PreviewContent::Text(text, _) = &content {
+            if !self.preview_queries.contains_key(&path) {
+                if let Some(delimiter) = self.delimiter_for(&path) {
+                    self.preview_tables.insert(path.clone(), Table::parse(text, delimiter));
+                } else if jsonl::is_jsonl_path(&path) && self.preview_handler_for(&path).is_none() {
+                    self.preview_jsonl.insert(path.clone(), JsonLines::parse(text));
+                }
+            }
+        }
+        self.preview_cache.insert(path, content);
+    }
+
+    /// Delimiter used to show `path` as a table, if any. A handler other than
+    /// `table` shows even CSV files as it renders them.
+    fn delimiter_for(&self, path: &str) -> Option<char> {
+        if let Some(&delimiter) = self.delimiter_overrides.get(path) {
+            return Some(delimiter);
+        }
+        match self.preview_handler_for(path) {
+            Some(PreviewHandler::Table) => table::delimiter_for_path(path).or(Some(',')),
+            Some(_) => None,
+            None => table::delimiter_for_path(path),
+        }
+    }
+
+    /// Decode the previewed file as the next of the preview encodings. Returns
+    /// the path and its new text, to be highlighted again.
+    pub fn cycle_preview_encoding(&mut self) -> Option<(String, String)> {
+        let path = self.current_preview_path.clone()?;
+        let Some(PreviewContent::Text(_, meta)) = self.preview_cache.get(&path) else {
+            self.show_warning("Only text previews can be decoded differently");
+            return None;
+        };
+        let current = meta.encoding.as_ref().map_or(encoding_rs::UTF_8, |decoding| decoding.encoding);
+        let next = match backend::PREVIEW_ENCODINGS.iter().position(|&encoding| encoding == current) {
+            Some(position) => backend::PREVIEW_ENCODINGS[(position + 1) % backend::PREVIEW_ENCODINGS.len()],
+            None => backend::PREVIEW_ENCODINGS[0],
+        };
+        self.encoding_overrides.insert(path.clone(), next);
+        let content = self.preview_cache.remove(&path)?;
+        self.cache_preview(path.clone(), content);
+        self.reset_preview_scroll();
+        let Some(PreviewContent::Text(text, meta)) = self.preview_cache.get(&path) else {
+            return None;
+        };
+        let label = meta.encoding.as_ref().map_or_else(|| "UTF-8".to_string(), |decoding| decoding.label());
+        let text = text.clone();
+        self.show_info(format!("Decoded as {}", label));
+        Some((path, text))
+    }
+
+    /// Show `path` as a table split on `delimiter` (None restores the default
+    /// for its extension), re-parsing the cached preview
+    pub fn set_preview_delimiter(&mut self, path: &str, delimiter: Option<char>) {
+        match delimiter {
+            Some(d) => self.delimiter_overrides.insert(path.to_string(), d),
+            None => self.delimiter_overrides.remove(path),
+        };
+        if let Some(content) = self.preview_cache.remove(path) {
+            self.cache_preview(path.to_string(), content);
         }
+        self.preview_column_offset = 0;
+    }
+
+    /// Parsed table for the current preview, if it is a tabular file
+    pub fn preview_table(&self) -> Option<&Table> {
+        self.current_preview_path.as_ref()
+            .and_then(|path| self.preview_tables.get(path))
+    }
+
+    /// Record view for the current preview, if it is a JSON Lines file
+    pub fn preview_jsonl(&self) -> Option<&JsonLines> {
+        self.current_preview_path.as_ref()
+            .and_then(|path| self.preview_jsonl.get(path))
+    }
+
+    /// Expand or collapse the JSON Lines record under the preview cursor,
+    /// moving the cursor to the record's first line. Returns false if the
+    /// preview has no record there.
+    pub fn toggle_preview_fold(&mut self) -> bool {
+        let Some(path) = self.current_preview_path.as_ref() else { return false };
+        let Some(view) = self.preview_jsonl.get_mut(path) else { return false };
+        let Some(line) = view.toggle_fold(self.preview_cursor_line) else { return false };
+        self.preview_cursor_line = line;
+        self.preview_scroll_offset = self.preview_scroll_offset.min(line);
+        true
+    }
+
+    /// Show the output of a jq filter in place of the JSON Lines preview's
+    /// records (an empty filter shows them again) once it has run; an error
+    /// is reported then. Returns false if the preview isn't a JSON Lines file.
+    pub fn set_preview_jq_filter(&mut self, filter: &str) -> bool {
+        self.jq_draft = None;
+        let Some(path) = self.current_preview_path.clone().filter(|path| self.preview_jsonl.contains_key(path)) else {
+            return false;
+        };
+        self.start_jq_filter(path, filter, true);
+        true
+    }
+
+    /// Text of each scrollable preview line, as shown (records for JSON Lines
+    /// files, the file's lines otherwise)
+    pub fn preview_line_texts(&self) -> Vec<&str> {
+        if let Some(view) = self.preview_jsonl() {
+            return view.lines().map(|line| line.text.as_str()).collect();
+        }
+        match self.get_preview() {
+            Some(PreviewContent::Text(content, _)) => content.lines().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Number of scrollable preview lines (table rows for tabular files, diff rows
+    /// while a diff is shown)
+    pub fn preview_line_count(&self) -> usize {
+        if let Some(view) = &self.diff_view {
+            return view.row_count();
+        }
+        if let Some(table) = self.preview_table() {
+            return table.rows.len();
+        }
+        if let Some(view) = self.preview_jsonl() {
+            return view.line_count();
+        }
+        match self.get_preview() {
+            Some(PreviewContent::Text(content, _)) => content.lines().count(),
+            _ => 0,
+        }
+    }
+
+    /// Geometry of a preview text area `width` columns by `height` rows. With
+    /// wrapping on, text and JSON Lines previews take a row per `width`
+    /// columns of each line after its gutter; tables and diffs never wrap.
+    pub fn preview_geometry(&self, width: usize, height: usize) -> PreviewGeometry {
+        if !self.wrap_text || self.diff_view.is_some() || self.preview_table().is_some() {
+            return PreviewGeometry::unwrapped(height);
+        }
+        let rows = if let Some(view) = self.preview_jsonl() {
+            // Fold marker gutter
+            let width = width.saturating_sub(2);
+            (0..view.line_count())
+                .map(|idx| view.line(idx).map_or(1, |line| text_utils::wrapped_rows(&line.text, width)))
+                .collect()
+        } else if let Some(PreviewContent::Text(content, _)) = self.get_preview() {
+            let lines: Vec<&str> = content.lines().collect();
+            // Line number gutter, "123 │ "
+            let width = width.saturating_sub(lines.len().to_string().len() + 3);
+            lines.iter().map(|line| text_utils::wrapped_rows(line, width)).collect()
+        } else {
+            return PreviewGeometry::unwrapped(height);
+        };
+        PreviewGeometry { height, rows: Some(rows) }
     }
 
     /// Get current preview content
@@ -647,7 +3139,7 @@ impl App {
     /// Check if preview needs loading for current selection
     /// Returns (path, needs_loading) - path is always returned if available
     pub fn needs_preview_load(&self) -> Option<(String, bool)> {
-        let path = self.get_selected_file_path()?;
+        let path = self.selected_object_path()?;
         let needs_loading = !self.preview_cache.contains_key(&path);
         Some((path, needs_loading))
     }
@@ -655,13 +3147,50 @@ impl App {
     /// Update current preview path (for cached items)
     pub fn update_current_preview_path(&mut self, path: String) {
         if self.preview_cache.contains_key(&path) {
-            self.current_preview_path = Some(path);
+            self.touch_highlight(&path);
+            self.save_preview_position();
             self.reset_preview_scroll();
+            self.restore_saved_preview_position(&path);
+            self.apply_pending_preview_position(&path);
+            self.current_preview_path = Some(path);
+        }
+    }
+
+    /// Start an S3 Select query for a file. The cached preview is dropped so the
+    /// query result (delivered like a normal preview) takes its place.
+    pub fn start_preview_query(&mut self, path: String, query: String) {
+        self.preview_cache.remove(&path);
+        self.preview_tables.remove(&path);
+        self.preview_jsonl.remove(&path);
+        if self.current_preview_path.as_deref() == Some(path.as_str()) {
+            self.clear_preview();
+        }
+        self.preview_queries.insert(path, query);
+    }
+
+    /// Drop a query result so the file's regular preview is loaded again.
+    /// Returns false if the file had no query.
+    pub fn clear_preview_query(&mut self, path: &str) -> bool {
+        if self.preview_queries.remove(path).is_none() {
+            return false;
+        }
+        self.preview_cache.remove(path);
+        self.preview_tables.remove(path);
+        self.preview_jsonl.remove(path);
+        if self.current_preview_path.as_deref() == Some(path) {
+            self.clear_preview();
         }
+        true
+    }
+
+    /// Query whose result is shown as the preview of `path`, if any
+    pub fn preview_query(&self, path: &str) -> Option<&str> {
+        self.preview_queries.get(path).map(|q| q.as_str())
     }
 
     /// Clear current preview (when directory is selected)
     pub fn clear_preview(&mut self) {
+        self.save_preview_position();
         self.current_preview_path = None;
         self.reset_preview_scroll();
     }
@@ -806,54 +3335,202 @@ impl App {
         paths
     }
 
-    /// Clear all selections
-    pub fn clear_selection(&mut self) {
-        self.selected_files.clear();
-        self.visual_start_index = None;
+    /// The file at backend path `path`, if the current listing has it
+    pub fn listed_file(&self, path: &str) -> Option<&Entry> {
+        let name = if self.current_prefix.is_empty() {
+            path
+        } else {
+            path.strip_prefix(self.current_prefix.as_str())?.strip_prefix('/')?
+        };
+        self.entries.iter().find(|entry| !entry.is_dir && entry.name == name)
+    }
+
+    /// Total size of the selected files
+    pub fn selected_files_size(&self) -> u64 {
+        self.selected_files
+            .iter()
+            .filter_map(|&idx| self.entries.get(idx))
+            .filter(|entry| !entry.is_dir)
+            .filter_map(|entry| entry.size)
+            .sum()
+    }
+
+    /// Clear all selections
+    pub fn clear_selection(&mut self) {
+        self.selected_files.clear();
+        self.visual_start_index = None;
+    }
+
+    /// Check if a file is selected
+    pub fn is_file_selected(&self, entry_idx: usize) -> bool {
+        self.selected_files.contains(&entry_idx)
+    }
+
+    /// Get number of selected files
+    pub fn selected_count(&self) -> usize {
+        self.selected_files.len()
+    }
+
+    /// Enter download mode offering `destinations`, the first one selected
+    pub fn enter_download_mode(&mut self, destinations: Vec<DownloadDestination>) {
+        // Can only download if files are selected
+        if self.selected_files.is_empty() {
+            return;
+        }
+        self.mode = AppMode::Download;
+        self.download_destinations = destinations;
+        self.download_destination_index = 0;
+        self.download_number.clear();
+    }
+
+    /// Exit download mode
+    pub fn exit_download_mode(&mut self) {
+        self.mode = AppMode::Normal;
+    }
+
+    /// Move up in download destination list
+    pub fn download_move_up(&mut self) {
+        self.download_number.clear();
+        if self.download_destination_index > 0 {
+            self.download_destination_index -= 1;
+        }
+    }
+
+    /// Move down in download destination list
+    pub fn download_move_down(&mut self) {
+        self.download_number.clear();
+        if self.download_destination_index < self.download_destinations.len().saturating_sub(1) {
+            self.download_destination_index += 1;
+        }
+    }
+
+    /// Type a digit of a destination's number (1 for the first), selecting
+    /// it. Returns true once no further digit could name another destination,
+    /// so the download can start; with ten or more destinations, 1 waits for
+    /// a second digit (or Enter). A number naming no destination is dropped.
+    pub fn download_type_digit(&mut self, digit: char) -> bool {
+        let count = self.download_destinations.len();
+        let valid = |number: &str| number.parse::<usize>().ok().filter(|n| (1..=count).contains(n));
+
+        self.download_number.push(digit);
+        let number = match valid(&self.download_number) {
+            Some(number) => number,
+            // Start over from this digit
+            None => match valid(&digit.to_string()) {
+                Some(number) => {
+                    self.download_number = digit.to_string();
+                    number
+                }
+                None => {
+                    self.download_number.clear();
+                    return false;
+                }
+            },
+        };
+
+        self.download_destination_index = number - 1;
+        if number * 10 <= count {
+            return false;
+        }
+        self.download_number.clear();
+        true
+    }
+
+    /// Digits typed of a destination number still waiting for more
+    pub fn download_number(&self) -> &str {
+        &self.download_number
+    }
+
+    /// Get selected download destination index
+    pub fn download_destination_index(&self) -> usize {
+        self.download_destination_index
+    }
+
+    /// Destinations offered in download mode
+    pub fn download_destinations(&self) -> &[DownloadDestination] {
+        &self.download_destinations
+    }
+
+    /// The selected download destination
+    pub fn selected_download_destination(&self) -> Option<&DownloadDestination> {
+        self.download_destinations.get(self.download_destination_index)
     }
 
-    /// Check if a file is selected
-    pub fn is_file_selected(&self, entry_idx: usize) -> bool {
-        self.selected_files.contains(&entry_idx)
+    /// Open the context picker on `active` (the index of the active context, if any)
+    pub fn enter_context_picker(&mut self, active: Option<usize>) {
+        self.mode = AppMode::Context;
+        self.context_index = active.unwrap_or(0);
     }
 
-    /// Get number of selected files
-    pub fn selected_count(&self) -> usize {
-        self.selected_files.len()
+    /// Close the context picker
+    pub fn exit_context_picker(&mut self) {
+        self.mode = AppMode::Normal;
     }
 
-    /// Enter download mode
-    pub fn enter_download_mode(&mut self) {
-        // Can only download if files are selected
-        if self.selected_files.is_empty() {
-            return;
+    /// Move up in the context picker
+    pub fn context_move_up(&mut self) {
+        self.context_index = self.context_index.saturating_sub(1);
+    }
+
+    /// Move down in the context picker (`max` = number of contexts)
+    pub fn context_move_down(&mut self, max: usize) {
+        if self.context_index < max.saturating_sub(1) {
+            self.context_index += 1;
         }
-        self.mode = AppMode::Download;
-        self.download_destination_index = 0;
     }
 
-    /// Exit download mode
-    pub fn exit_download_mode(&mut self) {
-        self.mode = AppMode::Normal;
+    /// Selected index in the context picker
+    pub fn context_index(&self) -> usize {
+        self.context_index
     }
 
-    /// Move up in download destination list
-    pub fn download_move_up(&mut self) {
-        if self.download_destination_index > 0 {
-            self.download_destination_index -= 1;
-        }
+    /// Named context the current location was opened from
+    pub fn active_context(&self) -> Option<&str> {
+        self.active_context.as_deref()
     }
 
-    /// Move down in download destination list
-    pub fn download_move_down(&mut self, max: usize) {
-        if self.download_destination_index < max.saturating_sub(1) {
-            self.download_destination_index += 1;
+    /// Record the context the current location was opened from (None after
+    /// navigating to a location outside any context)
+    pub fn set_active_context(&mut self, name: Option<String>) {
+        self.active_context = name;
+    }
+
+    /// The current location, to come back to later
+    pub fn current_location(&self) -> NavLocation {
+        NavLocation {
+            backend: self.backend.clone(),
+            prefix: self.current_prefix.clone(),
+            selected: self.selected_entry().map(|e| e.name.clone()),
+            context: self.active_context.clone(),
         }
     }
 
-    /// Get selected download destination index
-    pub fn download_destination_index(&self) -> usize {
-        self.download_destination_index
+    /// Remember `left` after navigating away from it. A new navigation
+    /// starts a new branch, so the forward locations are dropped.
+    pub fn record_location(&mut self, left: NavLocation) {
+        self.forward_stack.clear();
+        push_location(&mut self.back_stack, left);
+    }
+
+    /// The location to go back to (or forward to, with `forward`)
+    pub fn peek_location(&self, forward: bool) -> Option<&NavLocation> {
+        if forward { self.forward_stack.last() } else { self.back_stack.last() }
+    }
+
+    /// Take the location to go back to (or forward to, with `forward`)
+    pub fn pop_location(&mut self, forward: bool) -> Option<NavLocation> {
+        if forward { self.forward_stack.pop() } else { self.back_stack.pop() }
+    }
+
+    /// Put a location on the back stack (or the forward stack, with `forward`)
+    /// without touching the other one
+    pub fn push_location(&mut self, location: NavLocation, forward: bool) {
+        push_location(if forward { &mut self.forward_stack } else { &mut self.back_stack }, location);
+    }
+
+    /// Number of locations to go back and forward to
+    pub fn navigation_depth(&self) -> (usize, usize) {
+        (self.back_stack.len(), self.forward_stack.len())
     }
 
     /// Get preview scroll offset
@@ -867,7 +3544,7 @@ impl App {
     }
 
     /// Scroll preview up by one line
-    pub fn preview_scroll_up(&mut self, _visible_height: usize) {
+    pub fn preview_scroll_up(&mut self) {
         if self.preview_cursor_line > 0 {
             self.preview_cursor_line -= 1;
             // Adjust scroll offset if cursor goes above visible area
@@ -878,14 +3555,11 @@ impl App {
     }
 
     /// Scroll preview down by one line
-    pub fn preview_scroll_down(&mut self, max_lines: usize, visible_height: usize) {
+    pub fn preview_scroll_down(&mut self, max_lines: usize, geometry: &PreviewGeometry) {
         if max_lines > 0 && self.preview_cursor_line < max_lines - 1 {
             self.preview_cursor_line += 1;
             // Adjust scroll offset if cursor goes below visible area
-            let max_visible_line = self.preview_scroll_offset + visible_height - 1;
-            if self.preview_cursor_line > max_visible_line {
-                self.preview_scroll_offset = self.preview_cursor_line.saturating_sub(visible_height - 1);
-            }
+            self.preview_scroll_offset = geometry.scrolled_to(self.preview_scroll_offset, self.preview_cursor_line);
         }
     }
 
@@ -896,10 +3570,12 @@ impl App {
     }
 
     /// Scroll preview down by page (half screen)
-    pub fn preview_scroll_page_down(&mut self, page_size: usize, max_lines: usize, visible_height: usize) {
+    pub fn preview_scroll_page_down(&mut self, page_size: usize, max_lines: usize, geometry: &PreviewGeometry) {
         if max_lines > 0 {
             self.preview_cursor_line = (self.preview_cursor_line + page_size).min(max_lines - 1);
-            self.preview_scroll_offset = (self.preview_scroll_offset + page_size).min(max_lines.saturating_sub(visible_height));
+            let max_offset = geometry.offset_ending_at(max_lines - 1, geometry.height);
+            let offset = (self.preview_scroll_offset + page_size).min(max_offset);
+            self.preview_scroll_offset = geometry.scrolled_to(offset, self.preview_cursor_line);
         }
     }
 
@@ -910,16 +3586,11 @@ impl App {
     }
 
     /// Jump to bottom of preview
-    pub fn preview_jump_to_bottom(&mut self, max_lines: usize, visible_height: usize) {
+    pub fn preview_jump_to_bottom(&mut self, max_lines: usize, geometry: &PreviewGeometry) {
         if max_lines > 0 {
             self.preview_cursor_line = max_lines - 1;
             // Limit to max 4 empty lines at bottom (if file is long enough)
-            let max_empty_lines = 4;
-            if visible_height > max_empty_lines && max_lines >= visible_height - max_empty_lines {
-                self.preview_scroll_offset = max_lines - (visible_height - max_empty_lines);
-            } else {
-                self.preview_scroll_offset = 0;
-            }
+            self.preview_scroll_offset = geometry.bottom_offset(max_lines);
         }
     }
 
@@ -952,126 +3623,756 @@ impl App {
 
     /// Get preview window width percentage
     pub fn preview_width_percent(&self) -> u16 {
-        self.preview_width_percent
+        self.pane_sizes.preview_percent
     }
 
     /// Increase preview width
     pub fn increase_preview_width(&mut self) {
-        self.preview_width_percent = (self.preview_width_percent + 5).min(80);
+        self.pane_sizes.preview_percent = (self.pane_sizes.preview_percent + 5).min(80);
+        self.panes_resized = true;
     }
 
     /// Decrease preview width
     pub fn decrease_preview_width(&mut self) {
-        self.preview_width_percent = (self.preview_width_percent.saturating_sub(5)).max(20);
+        self.pane_sizes.preview_percent = (self.pane_sizes.preview_percent.saturating_sub(5)).max(20);
+        self.panes_resized = true;
+    }
+
+    /// Height of the status pane, borders included
+    pub fn status_height(&self) -> u16 {
+        self.pane_sizes.status_height
+    }
+
+    /// Grow (positive `delta`) or shrink the status pane by `delta` lines
+    pub fn resize_status_pane(&mut self, delta: i16) {
+        self.pane_sizes.status_height = self.pane_sizes.status_height.saturating_add_signed(delta);
+        self.pane_sizes = self.pane_sizes.clamped();
+        self.panes_resized = true;
+    }
+
+    /// Width of the progress pane docked on the right
+    pub fn progress_pane_width(&self) -> u16 {
+        self.pane_sizes.progress_pane_width
+    }
+
+    /// Height of the progress pane docked at the bottom
+    pub fn progress_pane_height(&self) -> u16 {
+        self.pane_sizes.progress_pane_height
+    }
+
+    /// Grow (positive `delta`) or shrink the progress pane along the side it's
+    /// docked on: columns when docked right, lines at the bottom
+    pub fn resize_progress_pane(&mut self, position: ProgressPanePosition, delta: i16) {
+        match position {
+            ProgressPanePosition::Right => {
+                self.pane_sizes.progress_pane_width = self.pane_sizes.progress_pane_width.saturating_add_signed(delta * 4);
+            }
+            ProgressPanePosition::Bottom => {
+                self.pane_sizes.progress_pane_height = self.pane_sizes.progress_pane_height.saturating_add_signed(delta);
+            }
+        }
+        self.pane_sizes = self.pane_sizes.clamped();
+        self.panes_resized = true;
+    }
+
+    /// Current pane sizes, layout and zoom
+    pub fn pane_sizes(&self) -> PaneSizes {
+        PaneSizes { layout: Some(self.preview_layout), zoomed: self.zoomed, ..self.pane_sizes }
+    }
+
+    /// Pane sizes to save, if the panes were resized or rearranged this session
+    pub fn resized_pane_sizes(&self) -> Option<PaneSizes> {
+        self.panes_resized.then(|| self.pane_sizes())
+    }
+
+    /// Restore pane sizes, layout and zoom saved by an earlier session
+    pub fn set_pane_sizes(&mut self, sizes: PaneSizes) {
+        self.pane_sizes = sizes.clamped();
+        if let Some(layout) = sizes.layout {
+            self.preview_layout = layout;
+        }
+        self.zoomed = sizes.zoomed;
+    }
+
+    /// Get the current preview pane placement
+    pub fn preview_layout(&self) -> PreviewLayout {
+        self.preview_layout
+    }
+
+    /// Set the preview pane placement
+    pub fn set_preview_layout(&mut self, layout: PreviewLayout) {
+        self.preview_layout = layout;
+    }
+
+    /// Cycle the preview pane placement (right -> bottom -> hidden)
+    pub fn cycle_preview_layout(&mut self) {
+        self.preview_layout = self.preview_layout.next();
+        self.zoomed = false;
+        self.panes_resized = true;
+    }
+
+    /// Toggle full-screen zoom of the focused pane
+    pub fn toggle_zoom(&mut self) {
+        self.zoomed = !self.zoomed;
+        self.panes_resized = true;
+    }
+
+    /// Toggle zen mode
+    pub fn toggle_zen_mode(&mut self) {
+        self.zen = !self.zen;
+    }
+
+    /// Whether the search bar and status pane are hidden
+    pub fn is_zen_mode(&self) -> bool {
+        self.zen
+    }
+
+    /// Whether the search bar is shown: always, except in zen mode while
+    /// nothing is being typed into it
+    pub fn shows_search_bar(&self) -> bool {
+        !self.zen || matches!(self.mode, AppMode::Search | AppMode::Command) || self.is_preview_search_active()
+    }
+
+    /// Whether only the focused pane is shown (zoomed, or the preview is hidden)
+    pub fn is_single_pane(&self) -> bool {
+        self.zoomed || self.preview_layout == PreviewLayout::Hidden
+    }
+
+    /// Check if the focused pane is zoomed
+    pub fn is_zoomed(&self) -> bool {
+        self.zoomed
+    }
+
+    /// Check if the downloads progress pane is shown
+    pub fn is_progress_pane_shown(&self) -> bool {
+        self.show_progress_pane
+    }
+
+    /// Show or hide the downloads progress pane
+    pub fn set_progress_pane_shown(&mut self, shown: bool) {
+        self.show_progress_pane = shown;
+    }
+
+    /// Toggle the downloads progress pane
+    pub fn toggle_progress_pane(&mut self) {
+        self.show_progress_pane = !self.show_progress_pane;
     }
 
     /// Reset preview scroll offset (called when preview content changes)
+    /// First visible column of a table preview
+    pub fn preview_column_offset(&self) -> usize {
+        self.preview_column_offset
+    }
+
+    /// Scroll a table preview one column left
+    pub fn scroll_columns_left(&mut self) {
+        self.preview_column_offset = self.preview_column_offset.saturating_sub(1);
+    }
+
+    /// Scroll a table preview one column right (the last column stays visible)
+    pub fn scroll_columns_right(&mut self) {
+        let columns = self.preview_table().map(|t| t.column_count()).unwrap_or(0);
+        if self.preview_column_offset + 1 < columns {
+            self.preview_column_offset += 1;
+        }
+    }
+
     pub fn reset_preview_scroll(&mut self) {
         self.preview_scroll_offset = 0;
+        self.preview_column_offset = 0;
         self.preview_cursor_line = 0;
         self.preview_visual_mode = false;
         self.preview_visual_start = 0;
     }
 
-    /// Start tracking a download with cancellation support
-    pub fn start_download(&mut self, path: String, cancel_tx: tokio::sync::oneshot::Sender<()>) {
-        self.downloads.insert(path.clone(), DownloadInfo {
-            path,
-            downloaded: 0,
-            total: None,
-            status: DownloadState::InProgress,
-            completed_at: None,
-            cancel_tx: Some(cancel_tx),
-        });
+    /// Start tracking a download with cancellation support
+    pub fn start_download(&mut self, path: String, cancel_tx: tokio::sync::oneshot::Sender<()>) {
+        self.downloads.insert(path.clone(), DownloadInfo {
+            path,
+            downloaded: 0,
+            total: None,
+            status: DownloadState::InProgress,
+            completed_at: None,
+            cancel_tx: Some(cancel_tx),
+            rate: TransferRate::default(),
+        });
+    }
+
+    /// Update download progress
+    pub fn update_download(&mut self, path: String, downloaded: u64, total: Option<u64>) {
+        let now = std::time::Instant::now();
+        if let Some(info) = self.downloads.get_mut(&path) {
+            self.usage.download_bytes += downloaded.saturating_sub(info.downloaded);
+            info.downloaded = downloaded;
+            info.total = total;
+            info.status = DownloadState::InProgress;
+            info.rate.record(now, downloaded);
+        } else {
+            // Fallback if start_download wasn't called
+            self.usage.download_bytes += downloaded;
+            let mut rate = TransferRate::default();
+            rate.record(now, downloaded);
+            self.downloads.insert(path.clone(), DownloadInfo {
+                path,
+                downloaded,
+                total,
+                status: DownloadState::InProgress,
+                completed_at: None,
+                cancel_tx: None,
+                rate,
+            });
+        }
+    }
+
+    /// Mark download as complete
+    pub fn complete_download(&mut self, path: String) {
+        if let Some(info) = self.downloads.get_mut(&path) {
+            info.status = DownloadState::Complete;
+            info.completed_at = Some(std::time::Instant::now());
+        }
+    }
+
+    /// Mark download as failed
+    pub fn fail_download(&mut self, path: String, error: String) {
+        if let Some(info) = self.downloads.get_mut(&path) {
+            info.status = DownloadState::Error(error);
+            info.completed_at = Some(std::time::Instant::now());
+        }
+    }
+
+    /// Mark download as canceled
+    pub fn cancel_download(&mut self, path: String) {
+        if let Some(info) = self.downloads.get_mut(&path) {
+            info.status = DownloadState::Canceled;
+            info.completed_at = Some(std::time::Instant::now());
+        }
+    }
+
+    /// Cancel all in-progress downloads
+    pub fn cancel_all_downloads(&mut self) -> usize {
+        let mut canceled_count = 0;
+
+        for info in self.downloads.values_mut() {
+            if info.status == DownloadState::InProgress {
+                // Send cancel signal if we have the sender
+                if let Some(cancel_tx) = info.cancel_tx.take() {
+                    let _ = cancel_tx.send(());
+                }
+                info.status = DownloadState::Canceled;
+                info.completed_at = Some(std::time::Instant::now());
+                canceled_count += 1;
+            }
+        }
+
+        canceled_count
+    }
+
+    /// Check if any downloads are in progress
+    pub fn has_active_downloads(&self) -> bool {
+        self.downloads.values().any(|info| info.status == DownloadState::InProgress)
+    }
+
+    /// Combined speed of the running downloads in bytes per second
+    pub fn download_rate(&self, now: std::time::Instant) -> Option<f64> {
+        let rates: Vec<f64> = self.downloads.values().filter_map(|info| info.bytes_per_sec(now)).collect();
+        if rates.is_empty() {
+            None
+        } else {
+            Some(rates.iter().sum())
+        }
+    }
+
+    /// Estimated time until all running downloads complete (None while the
+    /// speed or a download's size is unknown)
+    pub fn download_eta(&self, now: std::time::Instant) -> Option<std::time::Duration> {
+        let mut remaining = 0;
+        for info in self.downloads.values().filter(|info| info.status == DownloadState::InProgress) {
+            remaining += info.total?.saturating_sub(info.downloaded);
+        }
+        transfer::eta(remaining, self.download_rate(now)?)
+    }
+
+    /// Get all downloads
+    pub fn downloads(&self) -> &HashMap<String, DownloadInfo> {
+        &self.downloads
+    }
+
+    /// Get tracked restore requests
+    pub fn restores(&self) -> &HashMap<String, RestoreTaskState> {
+        &self.restores
+    }
+
+    /// Record the state of a restore request, and reflect it on the listed entry
+    pub fn update_restore(&mut self, path: String, state: RestoreTaskState) {
+        let entry_state = match &state {
+            RestoreTaskState::InProgress => Some(RestoreState::InProgress),
+            RestoreTaskState::Restored { expiry } => Some(RestoreState::Restored { expiry: expiry.clone() }),
+            RestoreTaskState::Error(_) => None,
+        };
+        if let Some(entry_state) = entry_state {
+            let prefix = if self.current_prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", self.current_prefix)
+            };
+            if let Some(name) = path.strip_prefix(&prefix) {
+                if let Some(entry) = self.entries.iter_mut().find(|e| e.name == name) {
+                    entry.restore_state = Some(entry_state);
+                }
+            }
+        }
+        self.restores.insert(path, state);
+    }
+
+    /// Drop entries that can't be read yet (archived objects) from the selection.
+    /// Returns the number of entries removed.
+    pub fn deselect_unreadable(&mut self) -> usize {
+        let before = self.selected_files.len();
+        let entries = &self.entries;
+        self.selected_files
+            .retain(|&idx| entries.get(idx).map(|e| e.is_readable()).unwrap_or(true));
+        before - self.selected_files.len()
+    }
+
+    /// Remove expired downloads (completed > 5 seconds ago)
+    pub fn remove_expired_downloads(&mut self) {
+        let now = std::time::Instant::now();
+        self.downloads.retain(|_, info| {
+            if let Some(completed_at) = info.completed_at {
+                now.duration_since(completed_at).as_secs() < 5
+            } else {
+                true // Keep in-progress downloads
+            }
+        });
+    }
+
+    /// Apply an event to the state. Anything that needs the backend or the
+    /// terminal is handed back as an effect for the event loop to run.
+    pub fn handle_event(&mut self, event: AppEvent) -> EventOutcome {
+        match event {
+            AppEvent::KeyPress(key) => {
+                // Ticks may not have run since the sequence timed out
+                self.expire_pending_key();
+                let pending = if self.is_search_mode() { self.pending_key } else { None };
+                let action = self.action_for_key(key);
+                // Any key but the one completing the sequence has the withheld
+                // key typed first, before it takes effect
+                let flush = pending.filter(|_| action != Action::ExitSearchMode);
+                EventOutcome::effect(Effect::Action { action, flush })
+            }
+            AppEvent::Resize => EventOutcome::redraw(),
+            AppEvent::Tick => self.tick(),
+            AppEvent::PreviewReady(path, content) => {
+                self.usage.preview_bytes += preview_bytes(&content);
+                let mut outcome = EventOutcome::redraw();
+                if let PreviewContent::Text(text, _) = &content {
+                    outcome.effects.push(Effect::HighlightPreview { path: path.clone(), text: text.clone() });
+                }
+                self.receive_preview(path, content);
+                outcome
+            }
+            AppEvent::Idle => {
+                if !self.wants_prefetch() {
+                    return EventOutcome::default();
+                }
+                self.prefetched_for = self.prefetch_key();
+                let (prefixes, files) = self.prefetch_targets();
+                EventOutcome { redraw: false, effects: vec![Effect::Prefetch { prefixes, files }] }
+            }
+            AppEvent::PreviewPrefetched(path, content) => {
+                if self.selected_object_path().as_deref() == Some(path.as_str()) {
+                    // Moved onto the file while it was fetched
+                    return self.handle_event(AppEvent::PreviewReady(path, content));
+                }
+                self.usage.preview_bytes += preview_bytes(&content);
+                // Errors may be transient; the file is loaded again when selected
+                if !self.preview_cache.contains_key(&path) && !matches!(content, PreviewContent::Error(_)) {
+                    self.cache_preview(path, content);
+                }
+                EventOutcome::default()
+            }
+            AppEvent::ListingPrefetched(keys) => {
+                self.usage.record_listing(keys);
+                EventOutcome::default()
+            }
+            AppEvent::Filtered(update) => {
+                self.receive_filter_update(update);
+                EventOutcome::redraw()
+            }
+            AppEvent::JqFiltered(result) => {
+                self.receive_jq_result(result);
+                EventOutcome::redraw()
+            }
+            AppEvent::Highlighted { path, text, start, lines, complete } => {
+                let shown = self.current_preview_path.as_deref() == Some(path.as_str());
+                if self.receive_highlight(path, text, start, lines, complete) && shown {
+                    EventOutcome::redraw()
+                } else {
+                    EventOutcome::default()
+                }
+            }
+            AppEvent::DownloadProgress { path, downloaded, total } => {
+                self.update_download(path, downloaded, total);
+                EventOutcome::redraw()
+            }
+            AppEvent::DownloadComplete { path, warning } => {
+                self.complete_download(path);
+                self.report_finished_downloads();
+                if let Some(warning) = warning {
+                    self.show_warning(warning);
+                }
+                EventOutcome::redraw()
+            }
+            AppEvent::DownloadCanceled { path } => {
+                // Only mark as canceled if still in progress (not already complete/error)
+                if self.downloads.get(&path).is_some_and(|d| d.status == DownloadState::InProgress) {
+                    self.cancel_download(path);
+                }
+                EventOutcome::redraw()
+            }
+            AppEvent::DownloadFailed { path, error } => {
+                self.fail_download(path, error);
+                EventOutcome::redraw()
+            }
+            AppEvent::RestoreComplete { path, expiry } => {
+                self.show_success(format!("Restore complete: {}", path));
+                self.update_restore(path, RestoreTaskState::Restored { expiry });
+                EventOutcome::redraw()
+            }
+            AppEvent::RestoreFailed { path, error } => {
+                self.show_error(format!("Restore of {} failed: {}", path, error));
+                self.update_restore(path, RestoreTaskState::Error(error));
+                EventOutcome::redraw()
+            }
+            AppEvent::SummaryProgress { location, summary, complete } => {
+                self.update_summary(&location, summary, complete);
+                EventOutcome::redraw()
+            }
+            AppEvent::SummaryFailed { location, error } => {
+                self.fail_summary(&location, error);
+                EventOutcome::redraw()
+            }
+            AppEvent::GrepProgress { location, matches, files_searched, complete } => {
+                self.update_grep(&location, matches, files_searched, complete);
+                EventOutcome::redraw()
+            }
+            AppEvent::GrepFailed { location, error } => {
+                self.fail_grep(&location, error);
+                EventOutcome::redraw()
+            }
+            AppEvent::PropertiesLoaded { location, result } => {
+                self.update_properties(&location, result);
+                EventOutcome::redraw()
+            }
+            AppEvent::PluginFinished { name, output } => {
+                self.finish_plugin_output(&name, output);
+                EventOutcome::redraw()
+            }
+            AppEvent::FlatLoadProgress { location, loaded } => {
+                self.update_flat_load(&location, loaded);
+                EventOutcome::redraw()
+            }
+            AppEvent::FlatLoaded { location, result } => {
+                // Counted even when the view moved on; the keys were fetched
+                if let Ok(result) = &result {
+                    self.usage.record_listing(result.entries.len());
+                }
+                self.finish_flat_load(&location, result);
+                EventOutcome::redraw()
+            }
+            AppEvent::DiffReady(result) => {
+                match result {
+                    Ok(view) => self.open_diff(view),
+                    Err(e) => self.show_error(format!("Diff failed: {:#}", e)),
+                }
+                EventOutcome::redraw()
+            }
+            AppEvent::SyncPlanned(result) => {
+                match result {
+                    Ok(plan) => {
+                        self.clear_status();
+                        let large = plan.direction == SyncDirection::Download
+                            && self.confirmations.download_needs_confirmation(plan.transfers.len(), plan.transfer_bytes());
+                        let confirm = self.confirmations.sync_plans
+                            || large
+                            || (self.confirmations.deletes && !plan.deletions.is_empty());
+                        self.open_sync_plan(plan);
+                        if !confirm {
+                            return EventOutcome::effect(Effect::Action { action: Action::ConfirmSync, flush: None });
+                        }
+                    }
+                    Err(e) => self.show_error(format!("Sync failed: {:#}", e)),
+                }
+                EventOutcome::redraw()
+            }
+            AppEvent::CredentialsExpired => EventOutcome::effect(Effect::CredentialsExpired { retry: None }),
+            AppEvent::LoginOutput(line) => {
+                if let Some(login) = &mut self.login {
+                    login.output.push(line);
+                    let message = format!("{}: {}", login.command, login.output.join("  "));
+                    self.show_info(message);
+                }
+                EventOutcome::redraw()
+            }
+            AppEvent::HookAnswered { operation, verdict } => match verdict {
+                Ok(()) => EventOutcome::effect(Effect::RunGuarded(operation)),
+                Err(reason) => {
+                    self.show_error(format!("{} refused: {}", operation.name(), reason));
+                    EventOutcome::redraw()
+                }
+            },
+            AppEvent::LoginDone(result) => {
+                let Some(login) = self.login.take() else {
+                    return EventOutcome::default();
+                };
+                match result {
+                    Ok(()) => {
+                        self.login_declined = false;
+                        // Previews that failed with the expired credentials load again
+                        self.preview_cache.retain(|_, content| !matches!(content, PreviewContent::Error(_)));
+                        self.show_success("Credentials refreshed");
+                        EventOutcome::effect(Effect::Reconnect { retry: login.retry })
+                    }
+                    Err(e) => {
+                        self.show_error(format!("{} failed: {}", login.command, e));
+                        EventOutcome::redraw()
+                    }
+                }
+            }
+            AppEvent::UploadProgress { done, total } => {
+                self.show_info(format!("Uploading... {}/{} file(s)", done, total));
+                EventOutcome::redraw()
+            }
+            AppEvent::UploadDone(report) => {
+                self.usage.upload_bytes += report.bytes;
+                if report.failed.is_empty() {
+                    self.show_success(report.summary());
+                } else {
+                    self.show_warning(report.summary());
+                }
+
+                // Show the new files if the uploaded prefix is still open
+                if self.backend.get_display_path(&self.current_prefix) == report.location {
+                    EventOutcome::effect(Effect::RefreshListing)
+                } else {
+                    EventOutcome::redraw()
+                }
+            }
+            AppEvent::HistoryImported(imported) => {
+                let added = self.import_history(&imported.locations);
+                if imported.sources_read == 0 {
+                    self.show_warning("No shell or AWS CLI history found to import");
+                } else if added == 0 {
+                    self.show_info(format!("No new s3:// locations in {} histories", imported.sources_read));
+                } else {
+                    self.show_success(format!(
+                        "Imported {} locations from {} histories (R to browse them)",
+                        added, imported.sources_read
+                    ));
+                }
+                EventOutcome::redraw()
+            }
+            AppEvent::Quit => {
+                self.quit();
+                EventOutcome::redraw()
+            }
+            AppEvent::Suspend => {
+                self.request_suspend();
+                EventOutcome::redraw()
+            }
+            AppEvent::Control { command, responder } => match command {
+                ControlCommand::GetSelection => {
+                    responder.send(Ok(self.selection_uris().into()));
+                    EventOutcome::default()
+                }
+                ControlCommand::GetLocation => {
+                    responder.send(Ok(self.backend.get_display_path(&self.current_prefix).into()));
+                    EventOutcome::default()
+                }
+                ControlCommand::Quit => {
+                    self.quit();
+                    responder.send(Ok(serde_json::Value::Null));
+                    EventOutcome::redraw()
+                }
+                command => EventOutcome::effect(Effect::Control { command, responder }),
+            },
+        }
     }
 
-    /// Update download progress
-    pub fn update_download(&mut self, path: String, downloaded: u64, total: Option<u64>) {
-        if let Some(info) = self.downloads.get_mut(&path) {
-            info.downloaded = downloaded;
-            info.total = total;
-            info.status = DownloadState::InProgress;
-        } else {
-            // Fallback if start_download wasn't called
-            self.downloads.insert(path.clone(), DownloadInfo {
-                path,
-                downloaded,
-                total,
-                status: DownloadState::InProgress,
-                completed_at: None,
-                cancel_tx: None,
-            });
+    /// Filter of the preset bound to `key`; presets apply while browsing the explorer
+    fn filter_preset_for(&self, key: &KeyEvent) -> Option<String> {
+        if self.mode != AppMode::Normal || self.focused_panel != FocusedPanel::Explorer || self.pending_key.is_some() {
+            return None;
+        }
+        self.filter_presets
+            .iter()
+            .find(|(preset_key, _)| config::matches_key(key, preset_key))
+            .map(|(_, filter)| filter.clone())
+    }
+
+    /// Name of the plugin bound to `key`; plugins run from the explorer,
+    /// also on a visual selection
+    fn plugin_for(&self, key: &KeyEvent) -> Option<String> {
+        let browsing = matches!(self.mode, AppMode::Normal | AppMode::Visual);
+        if !browsing || self.focused_panel != FocusedPanel::Explorer || self.pending_key.is_some() {
+            return None;
+        }
+        self.plugins
+            .iter()
+            .find(|(_, plugin)| !plugin.key.is_empty() && config::matches_key(key, &plugin.key))
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Resolve a key press to an action for the current mode
+    pub fn action_for_key(&self, key: KeyEvent) -> Action {
+        let bindings = &self.key_bindings;
+        let in_history_mode = self.mode == AppMode::History;
+        let in_visual_mode = self.mode == AppMode::Visual;
+        let in_download_mode = self.mode == AppMode::Download;
+        let preview_focused = self.focused_panel == FocusedPanel::Preview;
+
+        // Suspending works the same in every mode
+        if key.kind == KeyEventKind::Press && bindings.is_suspend(&key) {
+            return Action::Suspend;
         }
-    }
 
-    /// Mark download as complete
-    pub fn complete_download(&mut self, path: String) {
-        if let Some(info) = self.downloads.get_mut(&path) {
-            info.status = DownloadState::Complete;
-            info.completed_at = Some(std::time::Instant::now());
+        if self.show_help {
+            events::handle_help_key(key, bindings)
+        } else if self.mode == AppMode::Command {
+            events::handle_command_key(key, bindings)
+        } else if self.mode == AppMode::Summary {
+            events::handle_summary_key(key, bindings)
+        } else if self.mode == AppMode::Diff {
+            events::handle_diff_key(key, bindings)
+        } else if self.mode == AppMode::Sync {
+            events::handle_sync_key(key, bindings)
+        } else if self.mode == AppMode::Context {
+            events::handle_context_key(key, bindings)
+        } else if self.mode == AppMode::Grep {
+            events::handle_grep_key(key, bindings)
+        } else if self.mode == AppMode::Properties {
+            events::handle_properties_key(key, bindings)
+        } else if self.mode == AppMode::AuditLog {
+            events::handle_audit_log_key(key, bindings)
+        } else if self.mode == AppMode::Confirm {
+            events::handle_confirm_key(key, bindings)
+        } else if self.mode == AppMode::PluginOutput {
+            events::handle_plugin_output_key(key, bindings)
+        } else if key.code == KeyCode::Esc
+            && self.mode == AppMode::Normal
+            && self.focused_panel == FocusedPanel::Explorer
+            && self.is_filtered()
+        {
+            // Escape clears a filter kept after leaving search mode
+            Action::ClearFilter
+        } else if key.code == KeyCode::Esc
+            && self.mode == AppMode::Normal
+            && !in_visual_mode
+            && !self.preview_visual_mode
+            && !self.preview_search_active
+            && self.preview_load_elapsed().is_some()
+        {
+            // Escape stops a preview that is still loading
+            Action::CancelPreview
+        } else if key.code == KeyCode::Esc
+            && !self.is_search_mode()
+            && !in_history_mode
+            && !in_download_mode
+            && !in_visual_mode
+            && !self.preview_visual_mode
+            && !self.preview_search_active
+            && self.has_active_downloads()
+        {
+            // Escape cancels running downloads unless it leaves a mode first
+            Action::CancelDownloads
+        } else if let Some(filter) = self.filter_preset_for(&key) {
+            Action::ToggleFilterPreset(filter)
+        } else if let Some(name) = self.plugin_for(&key) {
+            Action::RunPlugin(name)
+        } else {
+            match events::handle_key(
+                key,
+                bindings,
+                self.is_search_mode(),
+                in_history_mode,
+                in_visual_mode,
+                in_download_mode,
+                preview_focused,
+                self.preview_visual_mode,
+                self.preview_search_active,
+                self.pending_key,
+            ) {
+                // Searching the history has no filter to keep; Enter goes to the location
+                Action::AcceptFilter if self.searching_history => Action::NavigateInto,
+                action => action,
+            }
         }
     }
 
-    /// Mark download as failed
-    pub fn fail_download(&mut self, path: String, error: String) {
-        if let Some(info) = self.downloads.get_mut(&path) {
-            info.status = DownloadState::Error(error);
-            info.completed_at = Some(std::time::Instant::now());
+    /// Time-based housekeeping: expire status messages, finished downloads and
+    /// a pending key whose sequence timed out
+    fn tick(&mut self) -> EventOutcome {
+        // Running downloads animate their spinner, speed and ETA, loading
+        // previews their spinner
+        let mut redraw = self.has_active_downloads() || self.preview_load_elapsed().is_some();
+
+        let had_status = self.status_message.is_some();
+        if self.status_message.as_ref().is_some_and(|msg| msg.is_expired(self.status_timeout)) {
+            self.clear_status();
         }
+        redraw |= had_status && self.status_message.is_none();
+
+        let download_count = self.downloads.len();
+        self.remove_expired_downloads();
+        redraw |= self.downloads.len() != download_count;
+
+        // Only a key typed into the query changes what's shown
+        let searching = self.is_search_mode();
+        redraw |= self.expire_pending_key() && searching;
+
+        EventOutcome { redraw, effects: Vec::new() }
     }
 
-    /// Mark download as canceled
-    pub fn cancel_download(&mut self, path: String) {
-        if let Some(info) = self.downloads.get_mut(&path) {
-            info.status = DownloadState::Canceled;
-            info.completed_at = Some(std::time::Instant::now());
+    /// Summarize the batch in the status bar once every download has finished
+    fn report_finished_downloads(&mut self) {
+        let all_finished = self.downloads.values().all(|d| d.status != DownloadState::InProgress);
+        if !all_finished {
+            return;
         }
-    }
 
-    /// Cancel all in-progress downloads
-    pub fn cancel_all_downloads(&mut self) -> usize {
-        let mut canceled_count = 0;
+        let count = |f: fn(&DownloadState) -> bool| self.downloads.values().filter(|d| f(&d.status)).count();
+        let completed = count(|s| *s == DownloadState::Complete);
+        let failed = count(|s| matches!(s, DownloadState::Error(_)));
+        let canceled = count(|s| *s == DownloadState::Canceled);
 
-        for info in self.downloads.values_mut() {
-            if info.status == DownloadState::InProgress {
-                // Send cancel signal if we have the sender
-                if let Some(cancel_tx) = info.cancel_tx.take() {
-                    let _ = cancel_tx.send(());
-                }
-                info.status = DownloadState::Canceled;
-                info.completed_at = Some(std::time::Instant::now());
-                canceled_count += 1;
-            }
+        if canceled > 0 {
+            self.show_info(format!("Canceled {} download(s)", canceled));
+        } else if failed > 0 {
+            self.show_warning(format!("Downloaded {} file(s), {} failed", completed, failed));
+        } else {
+            self.show_success(format!("Downloaded {} file(s)", completed));
         }
-
-        canceled_count
     }
 
-    /// Check if any downloads are in progress
-    pub fn has_active_downloads(&self) -> bool {
-        self.downloads.values().any(|info| info.status == DownloadState::InProgress)
+    /// Toggle help display
+    pub fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+        self.help_scroll = 0;
     }
 
-    /// Get all downloads
-    pub fn downloads(&self) -> &HashMap<String, DownloadInfo> {
-        &self.downloads
+    /// Get help popup scroll offset
+    pub fn help_scroll(&self) -> usize {
+        self.help_scroll
     }
 
-    /// Remove expired downloads (completed > 5 seconds ago)
-    pub fn remove_expired_downloads(&mut self) {
-        let now = std::time::Instant::now();
-        self.downloads.retain(|_, info| {
-            if let Some(completed_at) = info.completed_at {
-                now.duration_since(completed_at).as_secs() < 5
-            } else {
-                true // Keep in-progress downloads
-            }
-        });
+    /// Scroll the help popup up
+    pub fn help_scroll_up(&mut self, amount: usize) {
+        self.help_scroll = self.help_scroll.saturating_sub(amount);
     }
 
-    /// Toggle help display
-    pub fn toggle_help(&mut self) {
-        self.show_help = !self.show_help;
+    /// Scroll the help popup down, never past `max_scroll`
+    pub fn help_scroll_down(&mut self, amount: usize, max_scroll: usize) {
+        self.help_scroll = (self.help_scroll + amount).min(max_scroll);
     }
 
     /// Check if help is shown
@@ -1091,6 +4392,9 @@ impl App {
 
     /// Set preview search query and update results
     pub fn set_preview_search_query(&mut self, query: String) {
+        if !self.preview_search_active {
+            self.preview_search_origin = (self.preview_cursor_line, self.preview_scroll_offset);
+        }
         self.preview_search_active = true;
         self.preview_search_query = query;
         self.update_preview_search_results();
@@ -1114,35 +4418,42 @@ impl App {
         self.preview_search_query.clear();
         self.preview_search_results.clear();
         self.preview_search_selected = 0;
+        self.preview_search_scroll = 0;
+    }
+
+    /// Leave preview search, moving the cursor back to where the search started
+    pub fn cancel_preview_search(&mut self) {
+        (self.preview_cursor_line, self.preview_scroll_offset) = self.preview_search_origin;
+        self.clear_preview_search();
     }
 
-    /// Update preview search results based on current query
+    /// Update preview search results based on current query. The first match
+    /// at or after the cursor is selected.
     fn update_preview_search_results(&mut self) {
         self.preview_search_results.clear();
         self.preview_search_selected = 0;
+        self.preview_search_scroll = 0;
 
         if self.preview_search_query.is_empty() {
             return;
         }
 
-        // Get preview content and clone it to avoid borrow checker issues
-        let content_opt = self.get_preview().and_then(|preview| {
-            if let PreviewContent::Text(content, _) = preview {
-                Some(content.clone())
-            } else {
-                None
-            }
-        });
-
-        // Search in the cloned content
-        if let Some(content) = content_opt {
-            let query_lower = self.preview_search_query.to_lowercase();
-            for (line_num, line) in content.lines().enumerate() {
-                if line.to_lowercase().contains(&query_lower) {
-                    self.preview_search_results.push(line_num);
-                }
-            }
-        }
+        let query_lower = self.preview_search_query.to_lowercase();
+        let matches = |line: &str| line.to_lowercase().contains(&query_lower);
+        let results: Vec<usize> = if let Some(table) = self.preview_table() {
+            // The header stays pinned above the rows, so it isn't a result
+            (1..table.rows.len()).filter(|&idx| table.rows[idx].iter().any(|field| matches(field))).collect()
+        } else {
+            self.preview_line_texts()
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| matches(line))
+                .map(|(line_num, _)| line_num)
+                .collect()
+        };
+        let origin = self.preview_search_origin.0;
+        self.preview_search_selected = results.iter().position(|&line| line >= origin).unwrap_or(0);
+        self.preview_search_results = results;
     }
 
     /// Get preview search results
@@ -1155,52 +4466,77 @@ impl App {
         self.preview_search_selected
     }
 
+    /// Whether the preview shows only the lines matching the search query.
+    /// The filtered view has its own cursor (the selected result) and scroll
+    /// offset, counted in results.
+    pub fn is_preview_filtered(&self) -> bool {
+        self.preview_search_active && !self.preview_search_query.is_empty()
+    }
+
+    /// First result shown in the filtered preview, keeping the selected one
+    /// within the preview's rows
+    pub fn preview_search_scroll(&self, geometry: &PreviewGeometry) -> usize {
+        let results = geometry.of_lines(&self.preview_search_results);
+        results.scrolled_to(self.preview_search_scroll, self.preview_search_selected)
+    }
+
     /// Move to next preview search result
-    pub fn preview_search_next(&mut self, max_lines: usize, visible_height: usize) {
+    pub fn preview_search_next(&mut self, geometry: &PreviewGeometry) {
         if !self.preview_search_results.is_empty() {
             self.preview_search_selected = (self.preview_search_selected + 1) % self.preview_search_results.len();
-            self.jump_to_preview_search_result(max_lines, visible_height);
+            self.preview_search_scroll = self.preview_search_scroll(geometry);
         }
     }
 
     /// Move to previous preview search result
-    pub fn preview_search_prev(&mut self, max_lines: usize, visible_height: usize) {
+    pub fn preview_search_prev(&mut self, geometry: &PreviewGeometry) {
         if !self.preview_search_results.is_empty() {
             if self.preview_search_selected == 0 {
                 self.preview_search_selected = self.preview_search_results.len() - 1;
             } else {
                 self.preview_search_selected -= 1;
             }
-            self.jump_to_preview_search_result(max_lines, visible_height);
+            self.preview_search_scroll = self.preview_search_scroll(geometry);
         }
     }
 
-    /// Jump to currently selected search result
-    fn jump_to_preview_search_result(&mut self, max_lines: usize, visible_height: usize) {
-        if let Some(&line_num) = self.preview_search_results.get(self.preview_search_selected) {
-            self.preview_cursor_line = line_num;
-            // Center the result in the view if possible, but limit max empty lines at bottom
-            let center_offset = line_num.saturating_sub(5);
-
-            // Check if centering would create more than 4 empty lines at bottom
-            let max_empty_lines = 4;
-            let max_offset = if visible_height > max_empty_lines && max_lines >= visible_height - max_empty_lines {
-                max_lines - (visible_height - max_empty_lines)
-            } else {
-                0
-            };
-
-            self.preview_scroll_offset = center_offset.min(max_offset);
-        }
+    /// Leave the filtered view with the cursor on the selected result's line
+    /// in the whole file, scrolled to show it a few lines from the top. Without
+    /// a result the cursor goes back to where the search started.
+    pub fn confirm_preview_search(&mut self, max_lines: usize, geometry: &PreviewGeometry) {
+        let Some(&line_num) = self.preview_search_results.get(self.preview_search_selected) else {
+            self.cancel_preview_search();
+            return;
+        };
+        self.preview_cursor_line = line_num;
+        // Center the result in the view if possible, but limit max empty lines at bottom
+        let center_offset = line_num.saturating_sub(5);
+        let offset = center_offset.min(geometry.bottom_offset(max_lines));
+        // Lines above it that wrap could still push it out of view
+        self.preview_scroll_offset = geometry.scrolled_to(offset, line_num);
+        self.clear_preview_search();
     }
+}
 
-    /// Confirm preview search result (jump to it and exit search)
-    pub fn confirm_preview_search(&mut self, max_lines: usize, visible_height: usize) {
-        self.jump_to_preview_search_result(max_lines, visible_height);
-        self.clear_preview_search();
+/// Bytes of file content a preview fetched. Binary files within the preview
+/// size limit are fetched whole; larger files only cost a metadata request.
+fn preview_bytes(content: &PreviewContent) -> u64 {
+    match content {
+        PreviewContent::Text(text, _) => text.len() as u64,
+        PreviewContent::Binary { size, .. } => *size,
+        PreviewContent::TooLarge { .. } | PreviewContent::Error(_) => 0,
     }
 }
 
+/// Extension a file is grouped under: lowercase, without the dot, empty if
+/// it has none (dotfiles like `.env` have none)
+fn group_extension(name: &str) -> String {
+    std::path::Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
 /// Navigation direction
 #[derive(Debug, Clone, Copy)]
 pub enum NavigateDirection {
@@ -1208,11 +4544,103 @@ pub enum NavigateDirection {
     Up,
 }
 
+/// A place visited this session, for back/forward navigation
+#[derive(Clone)]
+pub struct NavLocation {
+    pub backend: Arc<dyn Backend>,
+    pub prefix: String,
+    /// Entry selected when the location was left
+    pub selected: Option<String>,
+    pub context: Option<String>,
+}
+
+/// Push onto a navigation stack, dropping the oldest entry when it is full
+fn push_location(stack: &mut Vec<NavLocation>, location: NavLocation) {
+    if stack.len() >= MAX_NAVIGATION_DEPTH {
+        stack.remove(0);
+    }
+    stack.push(location);
+}
+
+/// Narrow `base` (all entries when None) to those matching `query`: a glob on
+/// the name, keeping directories and the positions matched so far, or a fuzzy
+/// query ranked by score
+fn narrow_entries(
+    matcher: &mut FuzzyMatcher,
+    entries: &[Entry],
+    names: &[String],
+    base: Option<&[(usize, Vec<u32>)]>,
+    query: &str,
+) -> Vec<(usize, Vec<u32>)> {
+    if is_glob(query) {
+        // Directories stay visible so a filter like `*.parquet` can be kept while navigating
+        let keep = |idx: usize| entries[idx].is_dir || glob_match_smart_case(query, &entries[idx].name);
+        return match base {
+            Some(base) => base.iter().filter(|(idx, _)| keep(*idx)).cloned().collect(),
+            None => (0..entries.len()).filter(|&idx| keep(idx)).map(|idx| (idx, Vec::new())).collect(),
+        };
+    }
+    match base {
+        Some(base) if query.is_empty() => base.to_vec(),
+        Some(base) => {
+            let subset: Vec<String> = base.iter().map(|(idx, _)| names[*idx].clone()).collect();
+            matcher
+                .match_entries(&subset, query)
+                .into_iter()
+                .map(|(idx, positions)| (base[idx].0, positions))
+                .collect()
+        }
+        None => matcher.match_entries(names, query),
+    }
+}
+
+/// Whether a filter is a glob pattern rather than a fuzzy query
+fn is_glob(filter: &str) -> bool {
+    filter.contains(['*', '?'])
+}
+
+/// Glob match that ignores case unless the pattern has uppercase letters,
+/// like the fuzzy search
+fn glob_match_smart_case(pattern: &str, name: &str) -> bool {
+    if pattern.chars().any(char::is_uppercase) {
+        glob_match(pattern, name)
+    } else {
+        glob_match(pattern, &name.to_lowercase())
+    }
+}
+
+/// Match a name against a simple glob pattern supporting `*` and `?`
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the name index it matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::backend::{Backend, Entry, ListResult, PreviewContent};
     use async_trait::async_trait;
+    use crate::sync::{SyncReason, SyncTransfer};
     use std::path::Path;
 
     // Mock backend for testing
@@ -1229,18 +4657,30 @@ mod tests {
                         is_dir: false,
                         size: Some(100),
                         modified: None,
+                        storage_class: None,
+                        restore_state: None,
+                        etag: None,
+                        permissions: None,
                     },
                     Entry {
                         name: "dir1".to_string(),
                         is_dir: true,
                         size: None,
                         modified: None,
+                        storage_class: None,
+                        restore_state: None,
+                        etag: None,
+                        permissions: None,
                     },
                     Entry {
                         name: "file2.txt".to_string(),
                         is_dir: false,
                         size: Some(200),
                         modified: None,
+                        storage_class: None,
+                        restore_state: None,
+                        etag: None,
+                        permissions: None,
                     },
                 ],
             }
@@ -1273,28 +4713,439 @@ mod tests {
             "mock".to_string()
         }
 
-        fn get_display_path(&self, prefix: &str) -> String {
-            format!("mock://{}", prefix)
-        }
+        fn get_display_path(&self, prefix: &str) -> String {
+            format!("mock://{}", prefix)
+        }
+
+        fn uri_to_prefix(&self, uri: &str) -> Option<String> {
+            uri.strip_prefix("mock://").map(|s| s.to_string())
+        }
+
+        fn get_parent(&self, _prefix: &str) -> Option<String> {
+            Some("parent".to_string())
+        }
+    }
+
+    fn create_test_app() -> App {
+        let backend = Arc::new(MockBackend::new());
+        let mut app = App::new(backend, String::new(), 50);
+        // Initialize with test data
+        app.update_entries(ListResult {
+            entries: MockBackend::new().entries.clone(),
+            prefix: String::new(),
+        });
+        app
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.pyc", "mod.pyc"));
+        assert!(!glob_match("*.pyc", "mod.py"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(glob_match("__pycache__", "__pycache__"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+    }
+
+    #[test]
+    fn test_ignore_patterns_hide_entries() {
+        let backend = Arc::new(MockBackend::new());
+        let mut app = App::new(backend, String::new(), 50);
+        app.set_ignore_patterns(vec!["*.txt".to_string()]);
+        app.update_entries(ListResult {
+            entries: MockBackend::new().entries.clone(),
+            prefix: String::new(),
+        });
+        assert!(app.entries().iter().all(|e| !e.name.ends_with(".txt")));
+        assert!(app.entries().iter().any(|e| e.name == "dir1"));
+    }
+
+    #[test]
+    fn test_restore_tracking_updates_entry() {
+        let mut app = create_test_app();
+        app.entries[0].storage_class = Some("GLACIER".to_string());
+        app.entries[0].restore_state = Some(RestoreState::Archived);
+        let path = app.entries[0].name.clone();
+
+        // Archived entries are dropped from the selection
+        app.selected_files.insert(0);
+        app.selected_files.insert(2);
+        assert_eq!(app.deselect_unreadable(), 1);
+        assert!(!app.is_file_selected(0));
+
+        app.update_restore(path.clone(), RestoreTaskState::InProgress);
+        assert_eq!(app.entries()[0].restore_state, Some(RestoreState::InProgress));
+
+        app.update_restore(path.clone(), RestoreTaskState::Restored { expiry: None });
+        assert!(app.entries()[0].is_readable());
+        assert_eq!(app.restores().get(&path), Some(&RestoreTaskState::Restored { expiry: None }));
+    }
+
+    #[test]
+    fn test_command_mode() {
+        let mut app = create_test_app();
+        app.enter_command_mode();
+        assert_eq!(app.mode(), &AppMode::Command);
+        app.append_command_char('d');
+        app.append_command_char('u');
+        assert_eq!(app.command_input(), "du");
+        assert_eq!(app.exit_command_mode(), "du");
+        assert_eq!(app.mode(), &AppMode::Normal);
+
+        // Backspace on an empty command line leaves command mode
+        app.enter_command_mode();
+        app.command_backspace();
+        assert_eq!(app.mode(), &AppMode::Normal);
+    }
+
+    #[test]
+    fn test_preview_query_replaces_preview() {
+        let mut app = create_test_app();
+        let text = |s: &str| PreviewContent::Text(s.to_string(), Default::default());
+        app.set_preview("data.csv".to_string(), text("a,b\n1,2\n"));
+
+        app.start_preview_query("data.csv".to_string(), "SELECT * FROM s3object".to_string());
+        assert!(app.get_preview().is_none());
+        assert_eq!(app.preview_query("data.csv"), Some("SELECT * FROM s3object"));
+
+        // Dropping the query forgets its result so the file is previewed normally again
+        app.set_preview("data.csv".to_string(), text("1,2\n"));
+        assert!(app.clear_preview_query("data.csv"));
+        assert!(app.preview_query("data.csv").is_none());
+        assert!(app.get_preview().is_none());
+        assert!(!app.clear_preview_query("data.csv"));
+    }
+
+    #[test]
+    fn test_csv_preview_table() {
+        let mut app = create_test_app();
+        let text = |s: &str| PreviewContent::Text(s.to_string(), Default::default());
+        app.set_preview("data.csv".to_string(), text("a,b,c\n1,\"x, y\",3\n"));
+
+        let table = app.preview_table().unwrap();
+        assert_eq!(table.rows[1], vec!["1", "x, y", "3"]);
+        assert_eq!(app.preview_line_count(), 2);
+
+        // Horizontal scrolling stops at the last column
+        for _ in 0..5 {
+            app.scroll_columns_right();
+        }
+        assert_eq!(app.preview_column_offset(), 2);
+        app.scroll_columns_left();
+        assert_eq!(app.preview_column_offset(), 1);
+
+        app.set_preview("notes.txt".to_string(), text("a;b\n1;2\n"));
+        assert!(app.preview_table().is_none());
+        assert_eq!(app.preview_column_offset(), 0);
+
+        // A runtime delimiter turns any text file into a table
+        app.set_preview_delimiter("notes.txt", Some(';'));
+        assert_eq!(app.preview_table().unwrap().rows[1], vec!["1", "2"]);
+        app.set_preview_delimiter("notes.txt", None);
+        assert!(app.preview_table().is_none());
+    }
+
+    #[test]
+    fn test_jsonl_preview() {
+        let mut app = create_test_app();
+        let text = "{\"id\": 1, \"ok\": true}\n{\"id\": 2, \"ok\": false}\n";
+        app.set_preview("events.jsonl".to_string(), PreviewContent::Text(text.to_string(), Default::default()));
+        assert_eq!(app.preview_jsonl().unwrap().record_count(), 2);
+        assert_eq!(app.preview_line_count(), 2);
+
+        // Expanding the second record keeps the cursor on its first line
+        app.preview_cursor_line = 1;
+        assert!(app.toggle_preview_fold());
+        assert_eq!(app.preview_line_count(), 5);
+        assert_eq!(app.preview_line_texts()[2], "  \"id\": 2,");
+        app.preview_cursor_line = 3;
+        assert!(app.toggle_preview_fold());
+        assert_eq!(app.preview_cursor_line(), 1);
+        assert_eq!(app.preview_line_count(), 2);
+
+        // A `:jq` filter shows live while typed and is dropped on leaving the command line
+        app.enter_command_mode();
+        for c in "jq .id".chars() {
+            app.append_command_char(c);
+        }
+        assert!(app.is_jq_filtering());
+        run_jq_job(&mut app);
+        assert!(!app.is_jq_filtering());
+        assert_eq!(app.preview_line_texts(), vec!["1", "2"]);
+        app.exit_command_mode();
+        assert!(app.preview_jsonl().unwrap().filter().is_none());
+
+        assert!(app.set_preview_jq_filter("select(.ok) | .id"));
+        run_jq_job(&mut app);
+        assert_eq!(app.preview_line_texts(), vec!["1"]);
+        assert!(!app.toggle_preview_fold());
+        assert!(app.set_preview_jq_filter(".id +"));
+        run_jq_job(&mut app);
+        assert!(app.status_message().unwrap().content.starts_with("jq: "));
+        assert!(app.set_preview_jq_filter(""));
+        assert!(app.take_jq_job().is_none());
+        assert_eq!(app.preview_line_count(), 2);
+
+        // A newer filter supersedes the one in flight
+        app.set_preview_jq_filter(".id");
+        let stale = app.take_jq_job().unwrap();
+        app.set_preview_jq_filter(".ok");
+        assert!(stale.run().is_none());
+        run_jq_job(&mut app);
+        assert_eq!(app.preview_line_texts(), vec!["true", "false"]);
+
+        // Other files have no record view
+        app.set_preview("notes.txt".to_string(), PreviewContent::Text(text.to_string(), Default::default()));
+        assert!(app.preview_jsonl().is_none());
+        assert!(!app.set_preview_jq_filter(".id"));
+    }
+
+    /// Run the jq filter the app is waiting for, as the event loop would
+    fn run_jq_job(app: &mut App) {
+        let result = app.take_jq_job().unwrap().run().unwrap();
+        assert!(app.handle_event(AppEvent::JqFiltered(result)).redraw);
+    }
+
+    #[test]
+    fn test_preview_search_filtered_view() {
+        let mut app = create_test_app();
+        let text: String = (0..100).map(|i| if i % 10 == 0 { format!("match {}\n", i) } else { format!("line {}\n", i) }).collect();
+        app.set_preview("notes.txt".to_string(), PreviewContent::Text(text, Default::default()));
+        app.preview_cursor_line = 35;
+        app.preview_scroll_offset = 30;
+
+        // The first match after the cursor is selected
+        app.set_preview_search_query(String::new());
+        assert!(!app.is_preview_filtered());
+        for c in "match".chars() {
+            app.append_preview_search_char(c);
+        }
+        assert!(app.is_preview_filtered());
+        assert_eq!(app.preview_search_results().len(), 10);
+        assert_eq!(app.preview_search_selected(), 4);
+
+        // The filtered view scrolls in results, keeping the selection visible
+        let three_rows = PreviewGeometry::unwrapped(3);
+        assert_eq!(app.preview_search_scroll(&three_rows), 2);
+        app.preview_search_next(&three_rows);
+        app.preview_search_next(&three_rows);
+        assert_eq!(app.preview_search_selected(), 6);
+        assert_eq!(app.preview_search_scroll(&three_rows), 4);
+        app.preview_search_prev(&three_rows);
+        assert_eq!(app.preview_search_scroll(&three_rows), 4);
+        for _ in 0..5 {
+            app.preview_search_next(&three_rows);
+        }
+        assert_eq!(app.preview_search_selected(), 0);
+        assert_eq!(app.preview_search_scroll(&three_rows), 0);
+
+        // Canceling returns to where the search started
+        app.preview_search_next(&three_rows);
+        app.cancel_preview_search();
+        assert!(!app.is_preview_search_active());
+        assert_eq!((app.preview_cursor_line(), app.preview_scroll_offset()), (35, 30));
+
+        // Confirming jumps to the match's line in the whole file
+        app.set_preview_search_query("match 7".to_string());
+        app.confirm_preview_search(100, &PreviewGeometry::unwrapped(20));
+        assert!(!app.is_preview_search_active());
+        assert_eq!((app.preview_cursor_line(), app.preview_scroll_offset()), (70, 65));
+
+        // Without a match the cursor stays put
+        app.set_preview_search_query("nothing".to_string());
+        app.confirm_preview_search(100, &PreviewGeometry::unwrapped(20));
+        assert_eq!(app.preview_cursor_line(), 70);
+    }
+
+    #[test]
+    fn test_wrapped_preview_geometry() {
+        let mut app = create_test_app();
+        let text: String = (0..10).map(|i| format!("{}{}\n", i, "x".repeat(29))).collect();
+        app.set_preview("notes.txt".to_string(), PreviewContent::Text(text, Default::default()));
+        assert_eq!(app.preview_geometry(20, 6).rows(0), 1);
+
+        // A 5 column gutter leaves 15 for the text: every line takes two rows
+        app.toggle_wrap();
+        let geometry = app.preview_geometry(20, 6);
+        assert_eq!(geometry.rows(0), 2);
+        for _ in 0..3 {
+            app.preview_scroll_down(10, &geometry);
+        }
+        // Lines 1 to 3 fill the six rows
+        assert_eq!((app.preview_cursor_line(), app.preview_scroll_offset()), (3, 1));
+
+        app.preview_scroll_page_down(10, 10, &geometry);
+        assert_eq!((app.preview_cursor_line(), app.preview_scroll_offset()), (9, 7));
+        app.preview_jump_to_bottom(10, &geometry);
+        assert_eq!(app.preview_scroll_offset(), 9);
+
+        // Confirmed search results are scrolled into view by rows too
+        app.preview_jump_to_top();
+        app.set_preview_search_query("8x".to_string());
+        app.confirm_preview_search(10, &geometry);
+        assert_eq!((app.preview_cursor_line(), app.preview_scroll_offset()), (8, 6));
+
+        // The filtered view keeps the selected result's rows in view
+        app.preview_jump_to_top();
+        app.set_preview_search_query("x".to_string());
+        for _ in 0..4 {
+            app.preview_search_next(&geometry);
+        }
+        assert_eq!(app.preview_search_selected(), 4);
+        assert_eq!(app.preview_search_scroll(&geometry), 2);
+    }
+
+    #[test]
+    fn test_diff_view() {
+        let mut app = create_test_app();
+        app.open_diff(DiffView {
+            old_label: "a.toml".to_string(),
+            new_label: "b.toml".to_string(),
+            lines: diff::diff_lines("x\ny\nz\n", "x\nY\nz\nw\n"),
+            side_by_side: false,
+        });
+        assert_eq!(app.mode(), &AppMode::Diff);
+        assert!(matches!(app.focused_panel(), FocusedPanel::Preview));
+        assert_eq!(app.preview_line_count(), 5);
+
+        // Side by side pairs the changed line with its replacement
+        app.toggle_diff_layout();
+        assert_eq!(app.preview_line_count(), 4);
+
+        app.close_diff();
+        assert!(app.diff_view().is_none());
+        assert_eq!(app.mode(), &AppMode::Normal);
+        assert_eq!(app.preview_line_count(), 0);
+    }
+
+    #[test]
+    fn test_summary_view_cancel_on_close() {
+        let mut app = create_test_app();
+        let (cancel_tx, mut cancel_rx) = tokio::sync::oneshot::channel();
+        app.start_summary("/data".to_string(), cancel_tx);
+        assert_eq!(app.mode(), &AppMode::Summary);
+
+        // Updates for another location are ignored
+        let summary = PrefixSummary {
+            object_count: 3,
+            ..Default::default()
+        };
+        app.update_summary("/other", summary.clone(), false);
+        assert_eq!(app.summary_view().unwrap().summary.object_count, 0);
+        app.update_summary("/data", summary, false);
+        assert_eq!(app.summary_view().unwrap().summary.object_count, 3);
+
+        app.close_summary();
+        assert!(app.summary_view().is_none());
+        assert_eq!(app.mode(), &AppMode::Normal);
+        assert!(cancel_rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_kind_filter() {
+        let mut app = create_test_app();
+        app.select_entry_by_name("file2.txt");
+        app.cycle_kind_filter();
+        assert_eq!(app.kind_filter(), KindFilter::Directories);
+        assert_eq!(app.match_count(), 1);
+        assert_eq!(app.selected_entry().unwrap().name, "dir1");
 
-        fn uri_to_prefix(&self, uri: &str) -> Option<String> {
-            uri.strip_prefix("mock://").map(|s| s.to_string())
-        }
+        app.cycle_kind_filter();
+        assert_eq!(app.kind_filter(), KindFilter::Files);
+        assert_eq!(app.match_count(), 2);
+        assert!(app.selected_entry().is_some_and(|e| !e.is_dir));
 
-        fn get_parent(&self, _prefix: &str) -> Option<String> {
-            Some("parent".to_string())
-        }
+        // The fuzzy filter narrows what the kind filter leaves
+        app.set_search_query("1".to_string());
+        assert_eq!(app.filtered_indices().len(), 1);
+        assert_eq!(app.selected_entry().unwrap().name, "file1.txt");
+        app.set_search_query(String::new());
+
+        app.cycle_kind_filter();
+        assert_eq!(app.kind_filter(), KindFilter::All);
+        assert_eq!(app.match_count(), 3);
     }
 
-    fn create_test_app() -> App {
-        let backend = Arc::new(MockBackend::new());
-        let mut app = App::new(backend, String::new(), 50);
-        // Initialize with test data
+    #[test]
+    fn test_group_by_extension() {
+        let file = |name: &str, size| Entry { name: name.to_string(), is_dir: false, size: Some(size), ..MockBackend::new().entries[0].clone() };
+        let mut app = App::new(Arc::new(MockBackend::new()), String::new(), 50);
+        let mut dir = MockBackend::new().entries[1].clone();
+        dir.name = "logs".to_string();
         app.update_entries(ListResult {
-            entries: MockBackend::new().entries.clone(),
+            entries: vec![dir, file("b.csv", 10), file("README", 1), file("a.CSV", 5), file("run.log", 2)],
             prefix: String::new(),
         });
-        app
+        app.select_entry_by_name("run.log");
+        app.toggle_group_by_extension();
+        assert_eq!(app.selected_entry().unwrap().name, "run.log");
+
+        // Directories first, then csv, log and the files without an extension
+        let rows = |app: &App| -> Vec<String> {
+            app.filtered_indices()
+                .iter()
+                .map(|&idx| match app.group_header(idx) {
+                    Some(group) => format!("[{} {} {}]", group.extension, group.count, group.size),
+                    None => app.entries()[idx].name.clone(),
+                })
+                .collect()
+        };
+        assert_eq!(rows(&app), ["logs", "[csv 2 15]", "b.csv", "a.CSV", "[log 1 2]", "run.log", "[ 1 1]", "README"]);
+        assert_eq!(app.match_count(), 5);
+
+        // Collapsing from a file moves the cursor to its header; toggling there expands it again
+        app.select_entry_by_name("a.CSV");
+        assert!(app.toggle_group_collapsed());
+        assert_eq!(rows(&app), ["logs", "[csv 2 15]", "[log 1 2]", "run.log", "[ 1 1]", "README"]);
+        assert_eq!(app.selected_index(), 1);
+        assert!(app.selected_entry().is_none());
+        assert!(app.group_header(app.filtered_indices()[1]).unwrap().collapsed);
+
+        // The filter narrows the groups
+        app.set_search_query("b".to_string());
+        assert_eq!(rows(&app), ["[csv 1 10]"]);
+        app.set_search_query(String::new());
+
+        app.jump_to_top();
+        app.move_down();
+        assert!(app.toggle_group_collapsed());
+        assert_eq!(rows(&app).len(), 8);
+
+        app.toggle_group_by_extension();
+        assert_eq!(app.filtered_indices().len(), 5);
+        assert!(!app.toggle_group_collapsed());
+    }
+
+    #[test]
+    fn test_properties_view() {
+        let mut app = create_test_app();
+        app.select_entry_by_name("dir1");
+        assert!(app.open_properties().is_none());
+        assert!(app.properties_view().is_none());
+
+        app.select_entry_by_name("file1.txt");
+        assert_eq!(app.open_properties().as_deref(), Some("file1.txt"));
+        assert_eq!(app.mode(), &AppMode::Properties);
+        let location = app.properties_view().unwrap().location.clone();
+        assert_eq!(app.properties_view().unwrap().rows[1], ("Size".to_string(), "100 bytes".to_string()));
+
+        // Details for another object are ignored, the rest replace or extend the rows
+        app.update_properties("/other", Ok(vec![("Size".to_string(), "1 byte".to_string())]));
+        assert!(!app.properties_view().unwrap().complete);
+        let details = vec![
+            ("Size".to_string(), "100 bytes (100 B)".to_string()),
+            ("Content type".to_string(), "text/plain".to_string()),
+        ];
+        app.update_properties(&location, Ok(details));
+        let view = app.properties_view().unwrap();
+        assert!(view.complete);
+        assert_eq!(view.rows.len(), 3);
+        assert_eq!(view.rows[1].1, "100 bytes (100 B)");
+        assert_eq!(view.rows[2].0, "Content type");
+
+        app.close_properties();
+        assert!(app.properties_view().is_none());
+        assert_eq!(app.mode(), &AppMode::Normal);
     }
 
     #[test]
@@ -1313,6 +5164,21 @@ mod tests {
         assert!(app.should_quit());
     }
 
+    #[test]
+    fn test_suspend() {
+        let mut app = create_test_app();
+        let ctrl_z = KeyEvent::new(KeyCode::Char('z'), crossterm::event::KeyModifiers::CONTROL);
+        assert_eq!(app.action_for_key(ctrl_z), Action::Suspend);
+        // Also while typing a search
+        app.enter_search_mode();
+        assert_eq!(app.action_for_key(ctrl_z), Action::Suspend);
+
+        assert!(!app.take_suspend_request());
+        app.handle_event(AppEvent::Suspend);
+        assert!(app.take_suspend_request());
+        assert!(!app.take_suspend_request());
+    }
+
     #[test]
     fn test_status_messages() {
         let mut app = create_test_app();
@@ -1417,6 +5283,10 @@ mod tests {
         assert_eq!(app.selected_index(), 1);
     }
 
+    fn history_uris(app: &App) -> Vec<&str> {
+        app.history().iter().map(|entry| entry.uri.as_str()).collect()
+    }
+
     #[test]
     fn test_history_management() {
         let mut app = create_test_app();
@@ -1429,8 +5299,60 @@ mod tests {
         assert_eq!(app.history().len(), 2);
 
         // Most recently added is at index 0
-        assert_eq!(app.history()[0], "/path2");
-        assert_eq!(app.history()[1], "/path1");
+        assert_eq!(history_uris(&app), ["/path2", "/path1"]);
+    }
+
+    #[test]
+    fn test_remember_location_rules() {
+        let mut app = create_test_app();
+        app.remember_location("");
+        app.remember_location("jobs/8323");
+        app.remember_location("data/2024/06/05");
+        app.remember_location("logs/");
+        assert_eq!(history_uris(&app), ["mock://logs"]);
+        let entry = &app.history()[0];
+        assert_eq!((entry.scheme.as_str(), entry.prefix.as_str()), ("mock", "logs"));
+        assert!(entry.visited_at.is_some());
+
+        // Date partitions are kept once numeric folders are allowed
+        app.set_history_rules(2, false, vec!["*/tmp*".to_string(), r"re:/run-\d+$".to_string()]).unwrap();
+        app.remember_location("data/2024/06/05");
+        app.remember_location("scratch/tmp/run");
+        app.remember_location("jobs/run-42");
+        app.remember_location("data");
+        assert_eq!(history_uris(&app), ["mock://data", "mock://data/2024/06/05"]);
+
+        // Regexes that don't compile are reported, the rest still apply
+        let err = app.set_history_rules(2, false, vec!["re:(".to_string(), "re:^mock://data$".to_string()]);
+        assert!(err.unwrap_err().to_string().contains("\"(\""));
+        app.remember_location("data");
+        assert_eq!(history_uris(&app), ["mock://data", "mock://data/2024/06/05"]);
+        app.remember_location("logs");
+        assert_eq!(history_uris(&app), ["mock://logs", "mock://data"]);
+
+        app.set_history_rules(1, false, Vec::new()).unwrap();
+        assert_eq!(history_uris(&app), ["mock://logs"]);
+        app.load_history(vec![HistoryEntry::from_uri("a"), HistoryEntry::from_uri("b")]);
+        assert_eq!(history_uris(&app), ["a"]);
+    }
+
+    #[test]
+    fn test_import_history() {
+        let mut app = create_test_app();
+        app.remember_location("logs");
+        let imported = ["s3://bucket/in", "mock://logs", "s3://bucket/2024", "s3://other", "s3://123456"].map(String::from);
+        assert_eq!(app.import_history(&imported), 3);
+        // Below what was visited here, without duplicates or numeric folders;
+        // an all-digit bucket is not a folder
+        assert_eq!(history_uris(&app), ["mock://logs", "s3://bucket/in", "s3://other", "s3://123456"]);
+
+        let outcome = app.handle_event(AppEvent::HistoryImported(ImportedHistory {
+            locations: vec!["s3://bucket/in".to_string()],
+            sources_read: 2,
+        }));
+        assert!(outcome.redraw);
+        assert_eq!(app.history().len(), 4);
+        assert!(app.status_message().unwrap().content.contains("No new s3:// locations"));
     }
 
     #[test]
@@ -1508,19 +5430,63 @@ mod tests {
 
         // Download mode requires selected files
         app.toggle_selection();
-        app.enter_download_mode();
+        app.enter_download_mode(Config::default().download_destinations_for("bucket"));
         assert_eq!(app.mode(), &AppMode::Download);
+        assert_eq!(app.selected_download_destination().unwrap().name, "Downloads");
+        app.download_move_down();
+        app.download_move_down();
+        assert_eq!(app.selected_download_destination().unwrap().name, "Temp");
 
         app.exit_download_mode();
         assert_eq!(app.mode(), &AppMode::Normal);
     }
 
+    #[test]
+    fn test_download_destination_numbers() {
+        let destination = |i: usize| DownloadDestination {
+            name: format!("dest{}", i),
+            path: format!("/tmp/{}", i),
+            for_buckets: Vec::new(),
+        };
+        let mut app = create_test_app();
+        app.toggle_selection();
+
+        // Up to nine, a digit picks at once
+        app.enter_download_mode((1..=3).map(destination).collect());
+        assert!(app.download_type_digit('2'));
+        assert_eq!(app.selected_download_destination().unwrap().name, "dest2");
+        assert!(!app.download_type_digit('7'));
+        assert!(!app.download_type_digit('0'));
+        assert_eq!(app.selected_download_destination().unwrap().name, "dest2");
+
+        // With twelve, 1 waits for a second digit
+        app.enter_download_mode((1..=12).map(destination).collect());
+        assert!(!app.download_type_digit('1'));
+        assert_eq!(app.download_number(), "1");
+        assert_eq!(app.selected_download_destination().unwrap().name, "dest1");
+        assert!(app.download_type_digit('2'));
+        assert_eq!(app.selected_download_destination().unwrap().name, "dest12");
+        assert_eq!(app.download_number(), "");
+        assert!(app.download_type_digit('5'));
+        assert_eq!(app.selected_download_destination().unwrap().name, "dest5");
+
+        // 1 then 5 is 15, which doesn't exist: 5 starts over
+        assert!(!app.download_type_digit('1'));
+        assert!(app.download_type_digit('5'));
+        assert_eq!(app.selected_download_destination().unwrap().name, "dest5");
+
+        app.download_type_digit('1');
+        app.download_move_down();
+        assert_eq!(app.download_number(), "");
+        assert_eq!(app.selected_download_destination().unwrap().name, "dest2");
+    }
+
     #[test]
     fn test_download_destination_navigation() {
         let mut app = create_test_app();
         let initial_idx = app.download_destination_index();
 
-        app.download_move_down(3);
+        app.download_move_down();
         // Index might wrap or stay depending on available destinations
 
         app.download_move_up();
@@ -1565,6 +5531,75 @@ mod tests {
         // Should be back to initial or clamped
     }
 
+    #[test]
+    fn test_resize_panes() {
+        let mut app = create_test_app();
+        assert_eq!(app.resized_pane_sizes(), None);
+
+        app.resize_status_pane(2);
+        assert_eq!(app.status_height(), 7);
+        for _ in 0..10 {
+            app.resize_status_pane(-1);
+        }
+        assert_eq!(app.status_height(), 3);
+
+        app.resize_progress_pane(ProgressPanePosition::Right, 1);
+        assert_eq!(app.progress_pane_width(), 44);
+        app.resize_progress_pane(ProgressPanePosition::Bottom, -1);
+        assert_eq!(app.progress_pane_height(), 11);
+        assert_eq!(app.resized_pane_sizes(), Some(app.pane_sizes()));
+
+        // Saved sizes are clamped like the resize keys
+        app.set_pane_sizes(PaneSizes { preview_percent: 95, status_height: 1, ..Default::default() });
+        assert_eq!(app.preview_width_percent(), 80);
+        assert_eq!(app.status_height(), 3);
+    }
+
+    #[test]
+    fn test_preview_layout_and_zoom() {
+        let mut app = create_test_app();
+        assert_eq!(app.preview_layout(), PreviewLayout::Right);
+        assert!(!app.is_single_pane());
+
+        app.toggle_zoom();
+        assert!(app.is_zoomed());
+        assert!(app.is_single_pane());
+
+        // Cycling the layout drops the zoom
+        app.cycle_preview_layout();
+        assert_eq!(app.preview_layout(), PreviewLayout::Bottom);
+        assert!(!app.is_zoomed());
+
+        app.cycle_preview_layout();
+        assert_eq!(app.preview_layout(), PreviewLayout::Hidden);
+        assert!(app.is_single_pane());
+
+        // Saved with the pane sizes, and restored from them
+        app.toggle_zoom();
+        let saved = app.resized_pane_sizes().unwrap();
+        assert_eq!((saved.layout, saved.zoomed), (Some(PreviewLayout::Hidden), true));
+        let mut restored = create_test_app();
+        restored.set_pane_sizes(saved);
+        assert_eq!(restored.preview_layout(), PreviewLayout::Hidden);
+        assert!(restored.is_zoomed());
+
+        // Sizes saved without a layout keep the configured one
+        restored.set_preview_layout(PreviewLayout::Bottom);
+        restored.set_pane_sizes(PaneSizes::default());
+        assert_eq!(restored.preview_layout(), PreviewLayout::Bottom);
+        assert!(!restored.is_zoomed());
+    }
+
+    #[test]
+    fn test_progress_pane_toggle() {
+        let mut app = create_test_app();
+        assert!(!app.is_progress_pane_shown());
+        app.toggle_progress_pane();
+        assert!(app.is_progress_pane_shown());
+        app.toggle_progress_pane();
+        assert!(!app.is_progress_pane_shown());
+    }
+
     #[test]
     fn test_help_toggle() {
         let mut app = create_test_app();
@@ -1577,6 +5612,62 @@ mod tests {
         assert!(!app.is_help_shown());
     }
 
+    #[test]
+    fn test_help_scroll() {
+        let mut app = create_test_app();
+        app.toggle_help();
+        app.help_scroll_down(5, 3);
+        assert_eq!(app.help_scroll(), 3);
+        app.help_scroll_up(1);
+        assert_eq!(app.help_scroll(), 2);
+
+        // Reopening resets the scroll
+        app.toggle_help();
+        app.toggle_help();
+        assert_eq!(app.help_scroll(), 0);
+    }
+
+    #[test]
+    fn test_sync_plan_confirmation() {
+        let mut app = create_test_app();
+        app.open_sync_plan(SyncPlan::default());
+        assert_eq!(app.mode(), &AppMode::Sync);
+        app.sync_plan_scroll_down(5, 2);
+        assert_eq!(app.sync_plan_scroll(), 2);
+
+        assert!(app.take_sync_plan().is_some());
+        assert_eq!(app.mode(), &AppMode::Normal);
+        assert!(app.sync_plan().is_none());
+        assert!(app.take_sync_plan().is_none());
+    }
+
+    #[test]
+    fn test_sync_plan_without_confirmation() {
+        let mut app = create_test_app();
+        app.set_confirmations(Confirmations { sync_plans: false, ..Default::default() });
+        let outcome = app.handle_event(AppEvent::SyncPlanned(Ok(SyncPlan::default())));
+        assert!(matches!(outcome.effects[..], [Effect::Action { action: Action::ConfirmSync, .. }]));
+
+        // Deleting local files is still shown first
+        let plan = SyncPlan { deletions: vec![PathBuf::from("/tmp/old.csv")], ..Default::default() };
+        assert!(app.handle_event(AppEvent::SyncPlanned(Ok(plan))).effects.is_empty());
+        assert_eq!(app.mode(), &AppMode::Sync);
+        app.take_sync_plan();
+
+        // So is downloading more than the size guard allows
+        app.set_confirmations(Confirmations { sync_plans: false, download_files: 1, ..Default::default() });
+        let transfer = SyncTransfer {
+            remote: "data/a.csv".to_string(),
+            relative: "a.csv".to_string(),
+            local: PathBuf::from("/tmp/a.csv"),
+            size: 1,
+            reason: SyncReason::New,
+        };
+        let plan = SyncPlan { transfers: vec![transfer.clone(), transfer], ..Default::default() };
+        assert!(app.handle_event(AppEvent::SyncPlanned(Ok(plan))).effects.is_empty());
+        assert_eq!(app.mode(), &AppMode::Sync);
+    }
+
     #[test]
     fn test_wrap_toggle() {
         let mut app = create_test_app();
@@ -1595,6 +5686,267 @@ mod tests {
         assert!(!app.has_active_downloads());
     }
 
+    #[test]
+    fn test_download_rate_and_eta() {
+        let mut app = create_test_app();
+        let (cancel_tx, _cancel_rx) = tokio::sync::oneshot::channel();
+        app.start_download("data/a.bin".to_string(), cancel_tx);
+        app.update_download("data/a.bin".to_string(), 0, Some(1000));
+        app.update_download("data/a.bin".to_string(), 500, Some(1000));
+
+        let now = std::time::Instant::now();
+        assert_eq!(app.download_rate(now), None);
+
+        // ~500 bytes in the last second, 500 bytes left
+        let later = now + std::time::Duration::from_secs(1);
+        let rate = app.download_rate(later).unwrap();
+        assert!((450.0..=500.0).contains(&rate), "rate {}", rate);
+        let eta = app.download_eta(later).unwrap();
+        assert!((1.0..1.2).contains(&eta.as_secs_f64()), "eta {:?}", eta);
+
+        app.complete_download("data/a.bin".to_string());
+        assert_eq!(app.download_rate(later), None);
+    }
+
+    #[test]
+    fn test_key_press_resolves_to_action() {
+        let mut app = create_test_app();
+        let down = KeyEvent::new(KeyCode::Down, crossterm::event::KeyModifiers::NONE);
+        let outcome = app.handle_event(AppEvent::KeyPress(down));
+        assert!(outcome.redraw);
+        assert_eq!(outcome.effects, vec![Effect::Action { action: Action::MoveDown, flush: None }]);
+
+        // Escape cancels running downloads
+        let (cancel_tx, _cancel_rx) = tokio::sync::oneshot::channel();
+        app.start_download("file1.txt".to_string(), cancel_tx);
+        let esc = KeyEvent::new(KeyCode::Esc, crossterm::event::KeyModifiers::NONE);
+        assert_eq!(app.action_for_key(esc), Action::CancelDownloads);
+    }
+
+    #[test]
+    fn test_download_events_report_batch() {
+        let mut app = create_test_app();
+        for path in ["a.bin", "b.bin"] {
+            let (cancel_tx, _cancel_rx) = tokio::sync::oneshot::channel();
+            app.start_download(path.to_string(), cancel_tx);
+        }
+
+        app.handle_event(AppEvent::DownloadProgress { path: "a.bin".to_string(), downloaded: 10, total: Some(20) });
+        assert_eq!(app.downloads()["a.bin"].downloaded, 10);
+
+        app.handle_event(AppEvent::DownloadFailed { path: "b.bin".to_string(), error: "denied".to_string() });
+        // A late cancel doesn't overwrite the failure
+        app.handle_event(AppEvent::DownloadCanceled { path: "b.bin".to_string() });
+        assert!(matches!(app.downloads()["b.bin"].status, DownloadState::Error(_)));
+        assert!(app.status_message().is_none());
+
+        app.handle_event(AppEvent::DownloadComplete { path: "a.bin".to_string(), warning: None });
+        assert_eq!(app.status_message().unwrap().content, "Downloaded 1 file(s), 1 failed");
+    }
+
+    #[test]
+    fn test_session_usage() {
+        let mut app = create_test_app();
+        let (cancel_tx, _cancel_rx) = tokio::sync::oneshot::channel();
+        app.start_download("a.bin".to_string(), cancel_tx);
+        // Progress is cumulative; only the new bytes count
+        for downloaded in [10, 25, 25] {
+            app.handle_event(AppEvent::DownloadProgress { path: "a.bin".to_string(), downloaded, total: Some(40) });
+        }
+        app.handle_event(AppEvent::DownloadCanceled { path: "a.bin".to_string() });
+        assert_eq!(app.usage().download_bytes, 25);
+
+        // A prefetched preview counts once, also when it's shown right away
+        let text = |s: &str| PreviewContent::Text(s.to_string(), Default::default());
+        app.handle_event(AppEvent::PreviewPrefetched("other.txt".to_string(), text("abc")));
+        let selected = app.get_selected_file_path().unwrap();
+        app.handle_event(AppEvent::PreviewPrefetched(selected, text("hello")));
+        app.handle_event(AppEvent::PreviewReady("big.bin".to_string(), PreviewContent::TooLarge {
+            size: 1 << 30,
+            modified: None,
+            etag: None,
+            storage_class: None,
+            version_id: None,
+            version_number: None,
+        }));
+        assert_eq!(app.usage().preview_bytes, 8);
+
+        app.handle_event(AppEvent::ListingPrefetched(7));
+        app.handle_event(AppEvent::UploadDone(UploadReport { uploaded: 1, bytes: 100, ..Default::default() }));
+        assert_eq!(app.usage().listings, 1);
+        assert_eq!(app.usage().keys_listed, 7);
+        assert_eq!(app.usage().upload_bytes, 100);
+    }
+
+    #[test]
+    fn test_slow_requests() {
+        let mut app = create_test_app();
+        app.set_slow_request_threshold(1000);
+        app.set_listing_source(ListingSource::Fetched(std::time::Duration::from_millis(300)));
+        assert!(app.status_message().is_none());
+        app.set_listing_source(ListingSource::Fetched(std::time::Duration::from_millis(2500)));
+        let message = app.status_message().unwrap();
+        assert_eq!(message.severity, crate::status::StatusSeverity::Warning);
+        assert!(message.content.starts_with("Slow request: listing "), "{}", message.content);
+        assert!(message.content.ends_with(" took 2.5s"), "{}", message.content);
+
+        // The preview spinner runs until the selected file's preview arrives
+        let selected = app.get_selected_file_path().unwrap();
+        app.start_preview_load(selected.clone());
+        assert!(app.preview_load_elapsed().is_some());
+        app.set_slow_request_threshold(0);
+        app.clear_status();
+        app.receive_preview(selected, PreviewContent::Text("hello".to_string(), Default::default()));
+        assert!(app.preview_load_elapsed().is_none());
+        assert!(app.status_message().is_none());
+    }
+
+    #[test]
+    fn test_tick_expires_status_and_flushes_pending_key() {
+        let mut app = create_test_app();
+        app.set_status_timeout_secs(0);
+        app.show_info("hello");
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(app.handle_event(AppEvent::Tick).redraw);
+        assert!(app.status_message().is_none());
+
+        // Nothing changed, nothing to redraw
+        assert!(!app.handle_event(AppEvent::Tick).redraw);
+
+        app.enter_search_mode();
+        app.set_pending_key('j');
+        app.pending_key_instant = Some(std::time::Instant::now() - std::time::Duration::from_secs(1));
+        assert!(app.handle_event(AppEvent::Tick).redraw);
+        assert_eq!(app.pending_key(), None);
+        assert_eq!(app.search_query(), "j");
+    }
+
+    #[test]
+    fn test_sequence_timeout() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+        let press = |app: &mut App, c: char| match app.handle_event(AppEvent::KeyPress(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))).effects.as_slice() {
+            [Effect::Action { action, flush }] => (action.clone(), *flush),
+            effects => panic!("unexpected effects: {:?}", effects),
+        };
+        let mut app = create_test_app();
+        app.set_sequence_timeout_ms(100);
+        let stale = std::time::Instant::now() - std::time::Duration::from_millis(200);
+
+        // A g long after the last one starts a new sequence
+        app.set_pending_key('g');
+        assert_eq!(press(&mut app, 'g'), (Action::JumpToTop, None));
+        app.set_pending_key('g');
+        app.pending_key_instant = Some(stale);
+        assert_eq!(press(&mut app, 'g'), (Action::PendingKey('g'), None));
+
+        // Dropped on a tick, without anything to redraw
+        app.pending_key_instant = Some(stale);
+        assert!(!app.handle_event(AppEvent::Tick).redraw);
+        assert_eq!(app.pending_key(), None);
+
+        // In search mode the late j is typed before the next key
+        app.enter_search_mode();
+        app.set_pending_key('j');
+        app.pending_key_instant = Some(stale);
+        assert_eq!(press(&mut app, 'j'), (Action::PendingKey('j'), None));
+        assert_eq!(app.search_query(), "j");
+        app.set_pending_key('j');
+        assert_eq!(press(&mut app, 'j'), (Action::ExitSearchMode, None));
+    }
+
+    #[test]
+    fn test_preview_and_highlight_events() {
+        let mut app = create_test_app();
+        let content = PreviewContent::Text("fn main() {}".to_string(), Default::default());
+        let outcome = app.handle_event(AppEvent::PreviewReady("file1.txt".to_string(), content));
+        assert_eq!(
+            outcome.effects,
+            vec![Effect::HighlightPreview { path: "file1.txt".to_string(), text: "fn main() {}".to_string() }]
+        );
+        assert_eq!(app.current_preview_path(), Some("file1.txt"));
+
+        // The cache keeps at most two files, never evicting the one shown
+        let highlighted = |path: &str, text: &str| AppEvent::Highlighted {
+            path: path.to_string(),
+            text: Arc::from(text),
+            start: 0,
+            lines: vec![Line::from(text.to_string())],
+            complete: true,
+        };
+        app.handle_event(highlighted("file1.txt", "fn main() {}"));
+        for path in ["a.rs", "b.rs", "c.rs"] {
+            app.set_preview(path.to_string(), PreviewContent::Text(path.to_string(), Default::default()));
+            app.update_current_preview_path("file1.txt".to_string());
+            app.handle_event(highlighted(path, path));
+        }
+        assert_eq!(app.highlighted.len(), 2);
+        assert!(app.highlighted("file1.txt").is_some());
+        assert!(app.highlighted("c.rs").is_some());
+
+        app.forget_highlight("c.rs");
+        assert!(app.highlighted("c.rs").is_none());
+
+        // Highlighting of text the preview no longer shows is dropped
+        assert!(!app.handle_event(highlighted("a.rs", "old text")).redraw);
+        assert!(app.highlighted("a.rs").is_none());
+
+        // So is the cached highlighting when the file's text changes
+        let reloaded = PreviewContent::Text("fn main() {}".to_string(), Default::default());
+        app.handle_event(AppEvent::PreviewReady("file1.txt".to_string(), reloaded));
+        assert!(app.highlighted("file1.txt").is_some());
+        let changed = PreviewContent::Text("fn main() { run() }".to_string(), Default::default());
+        app.handle_event(AppEvent::PreviewReady("file1.txt".to_string(), changed));
+        assert!(app.highlighted("file1.txt").is_none());
+    }
+
+    #[test]
+    fn test_streamed_highlighting() {
+        let mut app = create_test_app();
+        let text = "a\nb\nc\n";
+        app.set_preview("file1.txt".to_string(), PreviewContent::Text(text.to_string(), Default::default()));
+        let source: Arc<str> = Arc::from(text);
+        let chunk = |source: &Arc<str>, start: usize, lines: &[&str], complete: bool| AppEvent::Highlighted {
+            path: "file1.txt".to_string(),
+            text: source.clone(),
+            start,
+            lines: lines.iter().map(|l| Line::from(l.to_string())).collect(),
+            complete,
+        };
+
+        assert!(app.handle_event(chunk(&source, 0, &["a", "b"], false)).redraw);
+        assert_eq!(app.highlighted("file1.txt").unwrap().len(), 2);
+        assert!(!app.is_highlight_complete("file1.txt"));
+
+        // Chunks of another highlighting run, or out of order, don't mix in
+        let other: Arc<str> = Arc::from(text);
+        assert!(!app.handle_event(chunk(&other, 2, &["x"], true)).redraw);
+        assert!(!app.handle_event(chunk(&source, 3, &["x"], true)).redraw);
+        assert_eq!(app.highlighted("file1.txt").unwrap().len(), 2);
+
+        assert!(app.handle_event(chunk(&source, 2, &["c"], true)).redraw);
+        assert_eq!(app.highlighted("file1.txt").unwrap().len(), 3);
+        assert!(app.is_highlight_complete("file1.txt"));
+    }
+
+    #[test]
+    fn test_background_results() {
+        let mut app = create_test_app();
+        app.handle_event(AppEvent::DiffReady(Err(anyhow::anyhow!("a.bin is a binary file"))));
+        assert_eq!(app.status_message().unwrap().content, "Diff failed: a.bin is a binary file");
+
+        app.handle_event(AppEvent::SyncPlanned(Ok(SyncPlan::default())));
+        assert_eq!(app.mode(), &AppMode::Sync);
+        assert!(app.status_message().is_none());
+
+        // Uploads into the open prefix refresh the listing
+        let report = UploadReport { location: "mock://".to_string(), uploaded: 1, ..Default::default() };
+        let outcome = app.handle_event(AppEvent::UploadDone(report));
+        assert_eq!(outcome.effects, vec![Effect::RefreshListing]);
+
+        let report = UploadReport { location: "mock://elsewhere".to_string(), ..Default::default() };
+        assert!(app.handle_event(AppEvent::UploadDone(report)).effects.is_empty());
+    }
+
     #[test]
     fn test_entries_and_filtered_indices() {
         let app = create_test_app();
@@ -1620,12 +5972,20 @@ mod tests {
                     is_dir: false,
                     size: Some(100),
                     modified: None,
+                    storage_class: None,
+                    restore_state: None,
+                    etag: None,
+                    permissions: None,
                 },
                 Entry {
                     name: "other.txt".to_string(),
                     is_dir: false,
                     size: Some(200),
                     modified: None,
+                    storage_class: None,
+                    restore_state: None,
+                    etag: None,
+                    permissions: None,
                 },
             ],
             prefix: String::new(),
@@ -1644,4 +6004,262 @@ mod tests {
         // Fuzzy matching should filter results
         assert!(app.filtered_indices().len() <= 3);
     }
+
+    #[test]
+    fn test_background_filter() {
+        let mut app = create_test_app();
+        app.set_background_filter_threshold(3);
+        app.enter_search_mode();
+
+        app.set_search_query("file".to_string());
+        assert!(app.is_filtering());
+        // The previous matches stay until the job reports
+        assert_eq!(app.filtered_indices().len(), 3);
+        let stale = app.take_filter_job().unwrap();
+        assert!(app.take_filter_job().is_none());
+
+        app.set_search_query("file2".to_string());
+        let job = app.take_filter_job().unwrap();
+
+        // Results of the superseded query are dropped
+        stale.run(|update| {
+            app.handle_event(AppEvent::Filtered(update));
+            true
+        });
+        assert_eq!(app.filtered_indices().len(), 3);
+        assert!(app.is_filtering());
+
+        let mut updates = Vec::new();
+        job.run(|update| {
+            updates.push(update);
+            true
+        });
+        for update in updates {
+            app.handle_event(AppEvent::Filtered(update));
+        }
+        assert!(!app.is_filtering());
+        assert_eq!(app.selected_entry().unwrap().name, "file2.txt");
+        assert_eq!(app.filtered_indices().len(), 1);
+    }
+
+    #[test]
+    fn test_prefetch_on_idle() {
+        let mut app = create_test_app();
+        assert!(!app.wants_prefetch());
+        assert_eq!(app.handle_event(AppEvent::Idle).effects, vec![]);
+
+        app.set_prefetch_distance(1);
+        app.move_down();
+        assert_eq!(app.selected_entry().unwrap().name, "dir1");
+        let outcome = app.handle_event(AppEvent::Idle);
+        assert_eq!(
+            outcome.effects,
+            vec![Effect::Prefetch {
+                prefixes: vec!["dir1".to_string()],
+                files: vec!["file2.txt".to_string(), "file1.txt".to_string()],
+            }]
+        );
+        // Once per cursor position
+        assert!(!app.wants_prefetch());
+        assert_eq!(app.handle_event(AppEvent::Idle).effects, vec![]);
+
+        // Prefetched previews are cached without becoming the current preview
+        let content = PreviewContent::Text("two".to_string(), Default::default());
+        app.handle_event(AppEvent::PreviewPrefetched("file2.txt".to_string(), content));
+        assert!(app.current_preview_path().is_none());
+        app.move_down();
+        assert_eq!(app.needs_preview_load(), Some(("file2.txt".to_string(), false)));
+        assert!(app.wants_prefetch());
+    }
+
+    #[test]
+    fn test_selected_display_path() {
+        let mut app = create_test_app();
+        assert_eq!(app.selected_display_path(), "mock://file1.txt");
+
+        app.update_entries(ListResult { entries: Vec::new(), prefix: "logs/2024".to_string() });
+        assert_eq!(app.selected_display_path(), "mock://logs/2024");
+    }
+
+    #[test]
+    fn test_plugins() {
+        use crate::plugin::{Plugin, PluginOutput};
+
+        let mut app = create_test_app();
+        let plugin = Plugin { key: "Alt-p".to_string(), command: "less {files}".to_string(), output: PluginOutput::Terminal };
+        app.set_plugins(vec![("page".to_string(), plugin)]);
+        let alt_p = KeyEvent::new(KeyCode::Char('p'), crossterm::event::KeyModifiers::ALT);
+        assert_eq!(app.action_for_key(alt_p), Action::RunPlugin("page".to_string()));
+
+        let run = app.plugin_run("page").unwrap();
+        assert_eq!(run.files, vec![app.selected_display_path()]);
+        assert_eq!(run.output, PluginOutput::Terminal);
+        assert!(app.plugin_run("other").is_none());
+        assert!(app.status_message().unwrap().content.contains("No plugin named other"));
+
+        // Not while typing a query
+        app.enter_search_mode();
+        assert_ne!(app.action_for_key(alt_p), Action::RunPlugin("page".to_string()));
+    }
+
+    #[test]
+    fn test_filter_presets() {
+        let mut app = create_test_app();
+        app.set_filter_presets(vec![("1".to_string(), "*2.TXT".to_string()), ("2".to_string(), "*.txt".to_string())]);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), crossterm::event::KeyModifiers::NONE);
+
+        let action = app.action_for_key(key('2'));
+        assert_eq!(action, Action::ToggleFilterPreset("*.txt".to_string()));
+        assert!(app.toggle_filter("*.txt"));
+        // Globs keep directories visible
+        assert_eq!(app.filtered_indices(), &[0, 1, 2]);
+
+        // Case matters once the pattern has uppercase letters
+        assert!(app.toggle_filter("*2.TXT"));
+        assert_eq!(app.filtered_indices(), &[1]);
+        assert!(app.toggle_filter("*2.txt"));
+        assert_eq!(app.filtered_indices(), &[1, 2]);
+
+        // The same preset again clears the filter
+        assert!(!app.toggle_filter("*2.txt"));
+        assert_eq!(app.search_query(), "");
+        assert_eq!(app.filtered_indices().len(), 3);
+
+        // Typed text in search mode isn't a preset
+        app.enter_search_mode();
+        assert_eq!(app.action_for_key(key('2')), Action::AppendChar('2'));
+    }
+
+    #[test]
+    fn test_pinned_filters() {
+        let mut app = create_test_app();
+        assert!(!app.pin_search_query());
+
+        // `/` on a confirmed filter pins it; the new query narrows its matches
+        app.toggle_filter("file");
+        assert!(app.pin_search_query());
+        app.enter_search_mode();
+        assert_eq!(app.pinned_filters(), &["file".to_string()]);
+        assert_eq!(app.search_query(), "");
+        assert!(app.is_filtered());
+        let mut matched = app.filtered_indices().to_vec();
+        matched.sort();
+        assert_eq!(matched, vec![0, 2]);
+
+        app.append_search_char('2');
+        assert_eq!(app.filtered_indices(), &[2]);
+
+        // Backspace at an empty query pops the pinned filter back for editing
+        app.backspace_search();
+        assert_eq!(app.filtered_indices().len(), 2);
+        app.backspace_search();
+        assert!(app.pinned_filters().is_empty());
+        assert_eq!(app.search_query(), "file");
+        assert_eq!(app.filtered_indices().len(), 2);
+
+        // Esc clears the whole stack
+        app.exit_search_mode();
+        app.toggle_filter("*.txt");
+        app.pin_search_query();
+        app.enter_search_mode();
+        app.exit_search_mode();
+        assert!(!app.is_filtered());
+        assert_eq!(app.filtered_indices().len(), 3);
+    }
+
+    #[test]
+    fn test_grep_view() {
+        let mut app = create_test_app();
+        let (cancel_tx, mut cancel_rx) = tokio::sync::oneshot::channel();
+        app.start_grep("x".to_string(), "/data".to_string(), cancel_tx);
+        assert_eq!(app.mode(), &AppMode::Grep);
+
+        let found = |line| GrepMatch { path: "dir1/file3.txt".to_string(), line, text: "x".to_string() };
+        app.update_grep("/other", vec![found(0)], 1, false);
+        assert!(app.grep_view().unwrap().matches.is_empty());
+        app.update_grep("/data", vec![found(0)], 1, false);
+        app.update_grep("/data", vec![found(7)], 2, true);
+        app.grep_move_down();
+        app.grep_move_down();
+        assert_eq!(app.grep_view().unwrap().selected, 1);
+
+        assert_eq!(app.take_grep_match(), Some(found(7)));
+        assert_eq!(app.mode(), &AppMode::Normal);
+        // The search had finished, so there was nothing to cancel
+        assert!(cancel_rx.try_recv().is_err());
+
+        // Opening the match selects the file and moves the preview cursor there
+        app.set_search_query("file1".to_string());
+        let listing = ListResult {
+            entries: vec![Entry {
+                name: "file3.txt".to_string(),
+                is_dir: false,
+                size: Some(1),
+                modified: None,
+                storage_class: None,
+                restore_state: None,
+                etag: None,
+                permissions: None,
+            }],
+            prefix: "dir1".to_string(),
+        };
+        app.open_grep_match(&found(7), listing);
+        assert!(!app.is_filtered());
+        assert_eq!(app.selected_entry().unwrap().name, "file3.txt");
+        app.receive_preview("dir1/file3.txt".to_string(), PreviewContent::Text("x\n".repeat(10), Default::default()));
+        assert_eq!(app.preview_position().cursor_line, 7);
+        assert_eq!(app.preview_position().scroll_offset, 2);
+    }
+
+    #[test]
+    fn test_accept_filter() {
+        let mut app = create_test_app();
+        let key = |code| KeyEvent::new(code, crossterm::event::KeyModifiers::NONE);
+        app.enter_search_mode();
+        app.set_search_query("file2".to_string());
+        assert_eq!(app.action_for_key(key(KeyCode::Enter)), Action::AcceptFilter);
+        assert_eq!(app.action_for_key(key(KeyCode::Right)), Action::NavigateInto);
+
+        app.accept_search();
+        assert_eq!(app.mode(), &AppMode::Normal);
+        assert_eq!(app.search_query(), "file2");
+        assert_eq!(app.filtered_indices(), &[2]);
+        // Escape in normal mode now clears the kept filter
+        assert_eq!(app.action_for_key(key(KeyCode::Esc)), Action::ClearFilter);
+
+        // Accepting an empty narrowing query keeps the pinned filter as the query
+        app.pin_search_query();
+        app.enter_search_mode();
+        app.accept_search();
+        assert_eq!(app.search_query(), "file2");
+        assert!(app.pinned_filters().is_empty());
+
+        app.exit_search_mode();
+        assert_eq!(app.action_for_key(key(KeyCode::Esc)), Action::None);
+
+        // Searching the history, Enter still goes to the location
+        app.add_to_history("s3://bucket/a".to_string());
+        app.enter_history_mode();
+        app.enter_search_mode();
+        assert_eq!(app.action_for_key(key(KeyCode::Enter)), Action::NavigateInto);
+    }
+
+    #[test]
+    fn test_pinned_filters_background() {
+        let mut app = create_test_app();
+        app.set_background_filter_threshold(2);
+        app.toggle_filter("*.txt");
+        app.pin_search_query();
+        app.enter_search_mode();
+
+        app.append_search_char('2');
+        let job = app.take_filter_job().unwrap();
+        job.run(|update| {
+            app.handle_event(AppEvent::Filtered(update));
+            true
+        });
+        // Indices of the narrowed names map back to the full listing
+        assert_eq!(app.filtered_indices(), &[2]);
+        assert_eq!(app.selected_entry().unwrap().name, "file2.txt");
+    }
 }
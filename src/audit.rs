@@ -0,0 +1,193 @@
+use crate::state::AppState;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Kind of operation recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOperation {
+    Download,
+    Upload,
+    /// Local file deleted by `:sync --delete`
+    Delete,
+    /// Restore of an archived object requested
+    Restore,
+}
+
+impl AuditOperation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Download => "download",
+            Self::Upload => "upload",
+            Self::Delete => "delete",
+            Self::Restore => "restore",
+        }
+    }
+}
+
+/// How an audited operation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Ok,
+    Failed,
+    Canceled,
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the operation ended, RFC 3339 in UTC
+    pub time: String,
+    pub operation: AuditOperation,
+    /// Path the operation read from (or deleted, or restored)
+    pub source: String,
+    /// Path it wrote to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    /// Bytes transferred, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    pub outcome: AuditOutcome,
+    /// What went wrong, for failed operations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// Record of an operation on `source` that ended now
+    pub fn new(operation: AuditOperation, source: impl Into<String>, outcome: AuditOutcome) -> Self {
+        Self {
+            time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            operation,
+            source: source.into(),
+            destination: None,
+            bytes: None,
+            outcome,
+            error: None,
+        }
+    }
+
+    /// Record of an operation that failed with `error`
+    pub fn failed(operation: AuditOperation, source: impl Into<String>, error: impl Into<String>) -> Self {
+        Self { error: Some(error.into()), ..Self::new(operation, source, AuditOutcome::Failed) }
+    }
+
+    pub fn with_destination(mut self, destination: impl Into<String>) -> Self {
+        self.destination = Some(destination.into());
+        self
+    }
+
+    pub fn with_bytes(mut self, bytes: Option<u64>) -> Self {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Whether this is a download that finished, so it can be run again
+    pub fn is_completed_download(&self) -> bool {
+        self.operation == AuditOperation::Download && self.outcome == AuditOutcome::Ok && self.destination.is_some()
+    }
+}
+
+/// Append-only JSON Lines log of downloads, uploads, deletes and restores,
+/// viewable with `:log`. A log without a file records nothing.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+}
+
+impl AuditLog {
+    /// Log appending to `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self { path: Some(path) }
+    }
+
+    /// The audit file in the state directory
+    pub fn file() -> Result<PathBuf> {
+        Ok(AppState::state_dir()?.join("audit.jsonl"))
+    }
+
+    /// Append a record
+    pub fn record(&self, record: &AuditRecord) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut line = serde_json::to_string(record).context("Failed to serialize audit record")?;
+        line.push('\n');
+        // One write per line, so records of concurrent transfers don't interleave
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The last `max` records, oldest first. Lines that don't parse are skipped.
+    pub fn read_recent(&self, max: usize) -> Result<Vec<AuditRecord>> {
+        self.read_matching(max, |_| true)
+    }
+
+    /// The last `max` completed downloads, oldest first (`:downloads`)
+    pub fn read_downloads(&self, max: usize) -> Result<Vec<AuditRecord>> {
+        self.read_matching(max, AuditRecord::is_completed_download)
+    }
+
+    fn read_matching(&self, max: usize, wanted: impl Fn(&AuditRecord) -> bool) -> Result<Vec<AuditRecord>> {
+        let Some(path) = self.path.as_ref().filter(|path| path.exists()) else {
+            return Ok(Vec::new());
+        };
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut records: Vec<AuditRecord> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .filter(|record| wanted(record))
+            .collect();
+        records.drain(..records.len().saturating_sub(max));
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::new(path.clone());
+        assert!(log.read_recent(10).unwrap().is_empty());
+
+        let download = AuditRecord::new(AuditOperation::Download, "s3://bucket/a.csv", AuditOutcome::Ok)
+            .with_destination("/tmp/a.csv")
+            .with_bytes(Some(8));
+        log.record(&download).unwrap();
+        log.record(&AuditRecord::failed(AuditOperation::Upload, "/tmp/b.csv", "denied")).unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "not json\n").unwrap();
+        log.record(&AuditRecord::new(AuditOperation::Restore, "s3://bucket/c.tar", AuditOutcome::Ok)).unwrap();
+
+        let records = log.read_recent(10).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], download);
+        assert_eq!(records[1].error.as_deref(), Some("denied"));
+        assert_eq!(records[1].outcome, AuditOutcome::Failed);
+        assert_eq!(log.read_recent(1).unwrap()[0].operation, AuditOperation::Restore);
+
+        assert_eq!(log.read_downloads(10).unwrap(), vec![download]);
+
+        let line = fs::read_to_string(&path).unwrap().lines().next().unwrap().to_string();
+        assert!(line.contains(r#""operation":"download""#), "{}", line);
+        assert!(!line.contains("error"), "{}", line);
+    }
+
+    #[test]
+    fn test_disabled_log() {
+        let log = AuditLog::default();
+        log.record(&AuditRecord::new(AuditOperation::Delete, "/tmp/x", AuditOutcome::Ok)).unwrap();
+        assert!(log.read_recent(10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,472 @@
+use super::{Backend, Entry, FileMetadata, ListResult, PreviewContent};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, LAST_MODIFIED, RANGE};
+use reqwest::{Client, Method, StatusCode, Url};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How long to wait for the server to accept a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// PROPFIND body asking only for what a listing shows
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop><d:resourcetype/><d:getcontentlength/><d:getlastmodified/><d:getetag/></d:prop>
+</d:propfind>"#;
+
+/// Read-only backend for static file servers: directory index pages (Apache,
+/// nginx autoindex, `python -m http.server`, ...) and WebDAV servers.
+///
+/// Paths are relative to the server root, like S3 keys are relative to the bucket.
+pub struct HttpBackend {
+    client: Client,
+    /// Server root, e.g. `https://example.com/`
+    root: Url,
+    /// Whether the server answers PROPFIND; probed by the first listing
+    webdav: OnceLock<bool>,
+}
+
+impl HttpBackend {
+    /// Parse an `http(s)://` URI into a backend for its server and the path below the root
+    pub fn from_uri(uri: &str) -> Result<(Self, String)> {
+        let url = Url::parse(uri).with_context(|| format!("Invalid URL: {}", uri))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            bail!("Not an HTTP URL: {}", uri);
+        }
+
+        let prefix = url_prefix(&url);
+        let mut root = url.clone();
+        root.set_path("/");
+        root.set_query(None);
+        root.set_fragment(None);
+
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .user_agent(concat!("rats3/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok((Self { client, root, webdav: OnceLock::new() }, prefix))
+    }
+
+    /// URL of a file or, with `dir`, a directory (with trailing slash)
+    fn url(&self, path: &str, dir: bool) -> Url {
+        let mut url = self.root.clone();
+        {
+            let mut segments = url.path_segments_mut().expect("http URLs have a path");
+            segments.pop_if_empty();
+            segments.extend(path.split('/').filter(|s| !s.is_empty()));
+            if dir && !path.trim_matches('/').is_empty() {
+                segments.push("");
+            }
+        }
+        url
+    }
+
+    /// List via WebDAV PROPFIND; None if the server doesn't speak WebDAV
+    async fn propfind(&self, dir_url: &Url) -> Result<Option<Vec<Entry>>> {
+        let response = self
+            .client
+            .request(Method::from_bytes(b"PROPFIND").expect("valid method"), dir_url.clone())
+            .header("Depth", "1")
+            .header(CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .with_context(|| format!("Failed to list {}", dir_url))?;
+        if response.status() != StatusCode::MULTI_STATUS {
+            return Ok(None);
+        }
+        let body = response.text().await.context("Failed to read WebDAV response")?;
+        parse_multistatus(&body, dir_url).map(Some)
+    }
+
+    /// List by parsing the server's HTML directory index
+    async fn index(&self, dir_url: &Url) -> Result<Vec<Entry>> {
+        let response = self
+            .client
+            .get(dir_url.clone())
+            .send()
+            .await
+            .with_context(|| format!("Failed to list {}", dir_url))?
+            .error_for_status()
+            .with_context(|| format!("Failed to list {}", dir_url))?;
+        let body = response.text().await.context("Failed to read directory index")?;
+        Ok(parse_index(&body, dir_url))
+    }
+}
+
+#[async_trait]
+impl Backend for HttpBackend {
+    async fn list(&self, prefix: &str) -> Result<ListResult> {
+        let prefix = prefix.trim_matches('/');
+        let dir_url = self.url(prefix, true);
+
+        let mut entries = match self.webdav.get() {
+            Some(true) => self.propfind(&dir_url).await?.unwrap_or_default(),
+            Some(false) => self.index(&dir_url).await?,
+            None => match self.propfind(&dir_url).await? {
+                Some(entries) => {
+                    let _ = self.webdav.set(true);
+                    entries
+                }
+                None => {
+                    let entries = self.index(&dir_url).await?;
+                    let _ = self.webdav.set(false);
+                    entries
+                }
+            },
+        };
+
+        // Directories first, then by name
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        entries.dedup_by(|a, b| a.name == b.name && a.is_dir == b.is_dir);
+        Ok(ListResult {
+            entries,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    async fn get_preview(&self, path: &str, max_size: usize) -> Result<PreviewContent> {
+        let url = self.url(path, false);
+        // Only the first max_size bytes are needed; servers without range
+        // support send everything, so the body is cut off while reading
+        let mut response = match self
+            .client
+            .get(url.clone())
+            .header(RANGE, format!("bytes=0-{}", max_size.saturating_sub(1)))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(response) => response,
+            Err(e) => return Ok(PreviewContent::Error(format!("Failed to fetch {}: {}", url, e))),
+        };
+
+        let headers = response.headers();
+        let size = headers
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(content_range_total)
+            .or_else(|| response.content_length());
+        let modified = headers.get(LAST_MODIFIED).and_then(|v| v.to_str().ok()).and_then(format_http_date);
+        let mime_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(';').next().unwrap_or(v).trim().to_string());
+
+        if let Some(size) = size.filter(|&size| size > max_size as u64) {
+            return Ok(PreviewContent::TooLarge {
+                size,
+                modified,
+                etag: None,
+                storage_class: None,
+                version_id: None,
+                version_number: None,
+            });
+        }
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await.context("Failed to read response body")? {
+            bytes.extend_from_slice(&chunk);
+            if bytes.len() > max_size {
+                return Ok(PreviewContent::TooLarge {
+                    size: size.unwrap_or(bytes.len() as u64),
+                    modified,
+                    etag: None,
+                    storage_class: None,
+                    version_id: None,
+                    version_number: None,
+                });
+            }
+        }
+
+        let size = size.unwrap_or(bytes.len() as u64);
+        match super::decode_preview(bytes, false) {
+            Some((text, encoding)) => Ok(PreviewContent::Text(
+                text,
+                FileMetadata {
+                    size: Some(size),
+                    modified,
+                    encoding,
+                    ..Default::default()
+                },
+            )),
+            None => Ok(PreviewContent::Binary {
+                size,
+                mime_type,
+                modified,
+                etag: None,
+                storage_class: None,
+                version_id: None,
+                version_number: None,
+            }),
+        }
+    }
+
+    async fn download_file(
+        &self,
+        path: &str,
+        destination: &Path,
+        progress_callback: Option<crate::backend::ProgressCallback>,
+    ) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let url = self.url(path, false);
+        let mut response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Failed to fetch {}", url))?;
+        let total_size = response.content_length();
+
+        let mut file = tokio::fs::File::create(destination)
+            .await
+            .with_context(|| format!("Failed to create {}", destination.display()))?;
+        let mut downloaded = 0u64;
+        while let Some(chunk) = response.chunk().await.with_context(|| format!("Failed to read {}", url))? {
+            file.write_all(&chunk)
+                .await
+                .with_context(|| format!("Failed to write to {}", destination.display()))?;
+            downloaded += chunk.len() as u64;
+            if let Some(ref callback) = progress_callback {
+                callback(downloaded, total_size);
+            }
+        }
+        file.flush().await?;
+        Ok(())
+    }
+
+    fn location_name(&self) -> String {
+        self.root.host_str().unwrap_or_default().to_string()
+    }
+
+    fn get_display_path(&self, prefix: &str) -> String {
+        // The root URL already ends with a slash
+        format!("{}{}", self.root, prefix.trim_matches('/'))
+    }
+
+    fn uri_to_prefix(&self, uri: &str) -> Option<String> {
+        let url = Url::parse(uri).ok()?;
+        if url.origin() != self.root.origin() {
+            return None;
+        }
+        Some(url_prefix(&url))
+    }
+
+    fn get_parent(&self, prefix: &str) -> Option<String> {
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            return None;
+        }
+        Some(prefix.rsplit_once('/').map(|(parent, _)| parent.to_string()).unwrap_or_default())
+    }
+}
+
+/// Decode a percent-encoded path segment
+fn decode(segment: &str) -> String {
+    percent_decode_str(segment).decode_utf8_lossy().into_owned()
+}
+
+/// Decoded path of a URL without leading and trailing slashes
+fn url_prefix(url: &Url) -> String {
+    url.path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).map(decode).collect::<Vec<_>>().join("/"))
+        .unwrap_or_default()
+}
+
+/// Name of `url` if it is a direct child of `dir_url`, with whether it is a
+/// directory. Links elsewhere (parent, siblings, sort options, other hosts)
+/// give None.
+fn child_name(dir_url: &Url, url: &Url) -> Option<(String, bool)> {
+    if url.origin() != dir_url.origin() || url.query().is_some() {
+        return None;
+    }
+    let rest = url.path().strip_prefix(dir_url.path())?;
+    let (name, is_dir) = match rest.strip_suffix('/') {
+        Some(name) => (name, true),
+        None => (rest, false),
+    };
+    if name.is_empty() || name.contains('/') {
+        return None;
+    }
+    Some((decode(name), is_dir))
+}
+
+fn entry(name: String, is_dir: bool) -> Entry {
+    Entry {
+        name,
+        is_dir,
+        size: None,
+        modified: None,
+        storage_class: None,
+        restore_state: None,
+        etag: None,
+        permissions: None,
+    }
+}
+
+/// Entries linked from an HTML directory index. Index pages rarely have
+/// machine-readable sizes, so those are left unknown.
+fn parse_index(html: &str, dir_url: &Url) -> Vec<Entry> {
+    let lower = html.to_ascii_lowercase();
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find("href=") {
+        let start = pos + offset + "href=".len();
+        pos = start;
+        let Some(quote) = html[start..].chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        let Some(len) = html[start + 1..].find(quote) else { break };
+        let href = unescape_html(&html[start + 1..start + 1 + len]);
+        pos = start + 1 + len;
+
+        if href.starts_with('#') {
+            continue;
+        }
+        if let Some((name, is_dir)) = dir_url.join(&href).ok().and_then(|url| child_name(dir_url, &url)) {
+            entries.push(entry(name, is_dir));
+        }
+    }
+    entries
+}
+
+/// Undo the entity escaping HTML attributes may use
+fn unescape_html(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Entries of a WebDAV multistatus response (the directory's own entry is skipped)
+fn parse_multistatus(xml: &str, dir_url: &Url) -> Result<Vec<Entry>> {
+    let doc = roxmltree::Document::parse(xml).context("Invalid WebDAV response")?;
+    let dav = |node: &roxmltree::Node, name: &str| node.tag_name().name() == name && node.tag_name().namespace() == Some("DAV:");
+
+    let mut entries = Vec::new();
+    for response in doc.descendants().filter(|n| dav(n, "response")) {
+        let Some(href) = response.descendants().find(|n| dav(n, "href")).and_then(|n| n.text()) else {
+            continue;
+        };
+        let Some((name, href_dir)) = dir_url.join(href.trim()).ok().and_then(|url| child_name(dir_url, &url)) else {
+            continue;
+        };
+
+        let prop_text = |name: &str| response.descendants().find(|n| dav(n, name)).and_then(|n| n.text()).map(str::trim);
+        let is_dir = href_dir
+            || response
+                .descendants()
+                .any(|n| dav(&n, "resourcetype") && n.children().any(|c| dav(&c, "collection")));
+        let mut entry = entry(name, is_dir);
+        if !is_dir {
+            entry.size = prop_text("getcontentlength").and_then(|s| s.parse().ok());
+            entry.etag = prop_text("getetag").map(|s| s.trim_start_matches("W/").trim_matches('"').to_string());
+        }
+        entry.modified = prop_text("getlastmodified").and_then(format_http_date);
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Total size from a `Content-Range` header, e.g. `bytes 0-99/1234`
+fn content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// Format an HTTP date (`Mon, 01 Jan 2024 10:00:00 GMT`) like the S3 backend does
+fn format_http_date(value: &str) -> Option<String> {
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&chrono::Utc).format("%Y-%m-%d %H:%M:%S UTC").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(entries: &[Entry]) -> Vec<(&str, bool)> {
+        entries.iter().map(|e| (e.name.as_str(), e.is_dir)).collect()
+    }
+
+    #[test]
+    fn test_from_uri() {
+        let (backend, prefix) = HttpBackend::from_uri("https://example.com/pub/data%20sets/?C=M").unwrap();
+        assert_eq!(prefix, "pub/data sets");
+        assert_eq!(backend.location_name(), "example.com");
+        assert_eq!(backend.get_display_path(""), "https://example.com/");
+        assert_eq!(backend.url("pub/data sets", true).as_str(), "https://example.com/pub/data%20sets/");
+        assert_eq!(backend.url("pub/a.txt", false).as_str(), "https://example.com/pub/a.txt");
+        assert_eq!(backend.uri_to_prefix("https://example.com/pub/data%20sets").as_deref(), Some("pub/data sets"));
+        assert_eq!(backend.uri_to_prefix("https://other.com/pub"), None);
+        assert_eq!(backend.get_parent("pub/data"), Some("pub".to_string()));
+        assert_eq!(backend.get_parent("pub"), Some(String::new()));
+        assert_eq!(backend.get_parent(""), None);
+
+        assert!(HttpBackend::from_uri("ftp://example.com/").is_err());
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let dir = Url::parse("http://host/pub/").unwrap();
+        let html = r##"<html><body><h1>Index of /pub</h1>
+            <a href="?C=N;O=D">Name</a>
+            <a href="/">Parent Directory</a>
+            <a href="../">../</a>
+            <A HREF="docs/">docs/</A>
+            <a href='notes%20v2.txt'>notes v2.txt</a>
+            <a href="/pub/data.csv">data.csv</a>
+            <a href="https://elsewhere.org/x">mirror</a>
+            <a href="docs/deep/file">deep</a>
+            <a href="#top">top</a>
+            <a href="a&amp;b.txt">a&amp;b.txt</a>
+        </body></html>"##;
+        assert_eq!(
+            names(&parse_index(html, &dir)),
+            [("docs", true), ("notes v2.txt", false), ("data.csv", false), ("a&b.txt", false)]
+        );
+    }
+
+    #[test]
+    fn test_parse_multistatus() {
+        let dir = Url::parse("https://dav.example.com/files/").unwrap();
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+              <d:response><d:href>/files/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+              </d:response>
+              <d:response><d:href>/files/photos</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+              </d:response>
+              <d:response><d:href>https://dav.example.com/files/report%202024.pdf</d:href>
+                <d:propstat><d:prop>
+                  <d:resourcetype/>
+                  <d:getcontentlength>1234</d:getcontentlength>
+                  <d:getlastmodified>Mon, 01 Jan 2024 10:00:00 GMT</d:getlastmodified>
+                  <d:getetag>"abc123"</d:getetag>
+                </d:prop></d:propstat>
+              </d:response>
+            </d:multistatus>"#;
+        let entries = parse_multistatus(xml, &dir).unwrap();
+        assert_eq!(names(&entries), [("photos", true), ("report 2024.pdf", false)]);
+        assert_eq!(entries[1].size, Some(1234));
+        assert_eq!(entries[1].modified.as_deref(), Some("2024-01-01 10:00:00 UTC"));
+        assert_eq!(entries[1].etag.as_deref(), Some("abc123"));
+
+        assert!(parse_multistatus("not xml", &dir).is_err());
+    }
+
+    #[test]
+    fn test_content_range_total() {
+        assert_eq!(content_range_total("bytes 0-99/1234"), Some(1234));
+        assert_eq!(content_range_total("bytes 0-99/*"), None);
+        assert_eq!(content_range_total("garbage"), None);
+    }
+}
@@ -1,7 +1,10 @@
-use super::{Backend, Entry, ListResult, PreviewContent};
+use super::{Backend, Entry, ListResult, Permissions, PreviewContent};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 /// Local filesystem backend for testing
@@ -20,13 +23,15 @@ impl LocalBackend {
         Ok(Self { root })
     }
 
+    /// The file a listed path stands for: names that aren't UTF-8 are listed
+    /// under their [`display_name`] and looked up again by it here
     fn resolve_path(&self, prefix: &str) -> PathBuf {
         let prefix = prefix.trim_start_matches('/');
-        if prefix.is_empty() {
-            self.root.clone()
-        } else {
-            self.root.join(prefix)
+        let mut path = self.root.clone();
+        for component in prefix.split('/').filter(|c| !c.is_empty()) {
+            path = resolve_component(path, component);
         }
+        path
     }
 }
 
@@ -38,11 +43,34 @@ impl Backend for LocalBackend {
         let mut entries = Vec::new();
         let dir_entries = fs::read_dir(&path)
             .with_context(|| format!("Failed to read directory: {}", path.display()))?;
+        let mut owners = Owners::default();
 
         for entry in dir_entries {
             let entry = entry?;
-            let metadata = entry.metadata()?;
-            let name = entry.file_name().to_string_lossy().to_string();
+            let name = display_name(&entry.file_name());
+            // One entry that can't be inspected shouldn't fail the whole listing
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    entries.push(Entry {
+                        name,
+                        is_dir: entry.file_type().is_ok_and(|t| t.is_dir()),
+                        size: None,
+                        modified: None,
+                        storage_class: None,
+                        restore_state: None,
+                        etag: None,
+                        permissions: Some(Permissions {
+                            mode: None,
+                            owner: String::new(),
+                            group: String::new(),
+                            readable: false,
+                        }),
+                    });
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
 
             entries.push(Entry {
                 name,
@@ -66,6 +94,10 @@ impl Backend for LocalBackend {
                             .unwrap_or_default();
                         dt.format("%Y-%m-%d %H:%M:%S").to_string()
                     }),
+                storage_class: None,
+                restore_state: None,
+                etag: None,
+                permissions: owners.permissions(&entry.path(), &metadata),
             });
         }
 
@@ -107,21 +139,23 @@ impl Backend for LocalBackend {
                     .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
             });
 
-        if size > max_size as u64 {
-            return Ok(PreviewContent::TooLarge { size, modified, etag: None, storage_class: None, version_id: None, version_number: None });
-        }
+        // Read no more than the head of big files (giant logs preview quickly)
+        let truncated = size > max_size as u64;
+        let mut bytes = Vec::with_capacity(size.min(max_size as u64) as usize);
+        fs::File::open(&file_path)?.take(max_size as u64).read_to_end(&mut bytes)?;
 
-        // Try to read as text
-        match fs::read_to_string(&file_path) {
-            Ok(content) => Ok(PreviewContent::Text(content, super::FileMetadata {
+        match super::decode_preview(bytes, truncated) {
+            Some((content, encoding)) => Ok(PreviewContent::Text(content, super::FileMetadata {
                 size: Some(size),
                 modified: modified.clone(),
                 etag: None,
                 storage_class: None,
                 version_id: None,
                 version_number: None,
+                truncated,
+                encoding,
             })),
-            Err(_) => {
+            None => {
                 // Binary file
                 let mime_type = mime_guess::from_path(&file_path)
                     .first()
@@ -177,22 +211,38 @@ impl Backend for LocalBackend {
         Ok(())
     }
 
+    async fn upload_file(&self, source: &Path, path: &str) -> Result<()> {
+        let destination = self.resolve_path(path);
+        if let Some(parent) = destination.parent() {
+            tokio::fs::create_dir_all(parent).await
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        tokio::fs::copy(source, &destination).await
+            .with_context(|| format!("Failed to copy {} to {}", source.display(), destination.display()))?;
+        Ok(())
+    }
+
     fn location_name(&self) -> String {
         self.root.to_string_lossy().to_string()
     }
 
     fn get_display_path(&self, prefix: &str) -> String {
-        let path = self.resolve_path(prefix);
+        // The listed names, so that `uri_to_prefix` leads back to the same file
+        let path = self.root.join(prefix.trim_start_matches('/'));
         format!("local://{}", path.display())
     }
 
+    fn local_path(&self, prefix: &str) -> Option<PathBuf> {
+        Some(self.resolve_path(prefix))
+    }
+
     fn uri_to_prefix(&self, uri: &str) -> Option<String> {
         let local_scheme = "local://";
         let abs_path = uri.strip_prefix(local_scheme)?;
         let abs = std::path::PathBuf::from(abs_path);
         abs.strip_prefix(&self.root)
             .ok()
-            .map(|rel| rel.to_string_lossy().to_string())
+            .map(|rel| rel.iter().map(display_name).collect::<Vec<_>>().join("/"))
     }
 
     fn get_parent(&self, prefix: &str) -> Option<String> {
@@ -202,15 +252,118 @@ impl Backend for LocalBackend {
         }
 
         let path = Path::new(prefix);
-        path.parent().and_then(|p| {
-            let parent = p.to_string_lossy().to_string();
-            if parent.is_empty() {
-                Some(String::new())
-            } else {
-                Some(parent)
+        path.parent().map(|p| p.to_string_lossy().to_string())
+    }
+}
+
+/// Name of a file as listed: the name itself if it is UTF-8, otherwise with
+/// the bytes that aren't shown as `\xNN` escapes (so that names differing
+/// only in those bytes stay apart)
+fn display_name(name: &OsStr) -> String {
+    if let Some(name) = name.to_str() {
+        return name.to_string();
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let mut bytes = name.as_bytes();
+        let mut text = String::new();
+        while !bytes.is_empty() {
+            match std::str::from_utf8(bytes) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, rest) = bytes.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    let invalid = e.error_len().unwrap_or(rest.len());
+                    for byte in &rest[..invalid] {
+                        text.push_str(&format!("\\x{:02x}", byte));
+                    }
+                    bytes = &rest[invalid..];
+                }
             }
-        })
+        }
+        text
+    }
+    #[cfg(not(unix))]
+    name.to_string_lossy().to_string()
+}
+
+/// `dir/name`, or the entry of `dir` listed as `name` when that is the
+/// display name of a file whose name isn't UTF-8
+fn resolve_component(dir: PathBuf, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !name.contains("\\x") || path.symlink_metadata().is_ok() {
+        return path;
+    }
+    let raw = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.file_name())
+        .find(|raw| raw.to_str().is_none() && display_name(raw) == name);
+    match raw {
+        Some(raw) => dir.join(raw),
+        None => path,
+    }
+}
+
+/// Owner and group names looked up for a listing, by id
+#[derive(Default)]
+struct Owners {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Owners {
+    #[cfg(unix)]
+    fn permissions(&mut self, path: &Path, metadata: &fs::Metadata) -> Option<Permissions> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::MetadataExt;
+
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        let owner = self.users.entry(uid).or_insert_with(|| user_name(uid).unwrap_or_else(|| uid.to_string()));
+        let owner = owner.clone();
+        let group = self.groups.entry(gid).or_insert_with(|| group_name(gid).unwrap_or_else(|| gid.to_string()));
+        let group = group.clone();
+        // Listing a directory takes execute permission as well
+        let wanted = if metadata.is_dir() { libc::R_OK | libc::X_OK } else { libc::R_OK };
+        let readable = std::ffi::CString::new(path.as_os_str().as_bytes())
+            .is_ok_and(|path| unsafe { libc::access(path.as_ptr(), wanted) } == 0);
+        Some(Permissions { mode: Some(metadata.mode()), owner, group, readable })
+    }
+
+    #[cfg(not(unix))]
+    fn permissions(&mut self, _path: &Path, _metadata: &fs::Metadata) -> Option<Permissions> {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> Option<String> {
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    let status = unsafe { libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+    if status != 0 || result.is_null() {
+        return None;
+    }
+    Some(unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) }.to_string_lossy().into_owned())
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> Option<String> {
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    // Large groups list many members
+    let mut buffer = vec![0 as libc::c_char; 65536];
+    let mut result = std::ptr::null_mut();
+    let status = unsafe { libc::getgrgid_r(gid, &mut group, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+    if status != 0 || result.is_null() {
+        return None;
     }
+    Some(unsafe { std::ffi::CStr::from_ptr(group.gr_name) }.to_string_lossy().into_owned())
 }
 
 #[cfg(test)]
@@ -287,6 +440,75 @@ mod tests {
         assert!(result.entries.iter().any(|e| e.name == "file2.txt"));
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_list_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.txt");
+        fs::write(&file, "test").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let backend = LocalBackend::new(temp_dir.path().to_path_buf()).unwrap();
+        let result = backend.list("").await.unwrap();
+        let permissions = result.entries[0].permissions.as_ref().unwrap();
+        assert_eq!(permissions.mode_string(), "-rw-r-----");
+        assert!(!permissions.owner.is_empty() && !permissions.group.is_empty());
+        assert!(permissions.readable);
+        assert!(result.entries[0].is_readable());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+        let temp_dir = TempDir::new().unwrap();
+        let raw = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::create_dir(temp_dir.path().join(OsStr::from_bytes(b"dir\xff"))).unwrap();
+        fs::write(temp_dir.path().join(OsStr::from_bytes(b"dir\xff")).join(raw), "latin-1").unwrap();
+        // Differs only in the byte that isn't shown
+        fs::write(temp_dir.path().join(OsStr::from_bytes(b"caf\xe8.txt")), "other").unwrap();
+
+        let backend = LocalBackend::new(temp_dir.path().to_path_buf()).unwrap();
+        let names: Vec<String> = backend.list("").await.unwrap().entries.into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["dir\\xff", "caf\\xe8.txt"]);
+
+        let result = backend.list("dir\\xff").await.unwrap();
+        assert_eq!(result.entries[0].name, "caf\\xe9.txt");
+        let preview = backend.get_preview("dir\\xff/caf\\xe9.txt", 1024).await.unwrap();
+        assert!(matches!(preview, PreviewContent::Text(ref text, _) if text == "latin-1"));
+
+        let destination = temp_dir.path().join("copy.txt");
+        backend.download_file("dir\\xff/caf\\xe9.txt", &destination, None).await.unwrap();
+        assert_eq!(fs::read_to_string(destination).unwrap(), "latin-1");
+
+        let uri = backend.get_display_path("dir\\xff/caf\\xe9.txt");
+        assert_eq!(backend.uri_to_prefix(&uri).as_deref(), Some("dir\\xff/caf\\xe9.txt"));
+
+        // The real path, e.g. for the shell to cd into
+        assert_eq!(backend.local_path("dir\\xff"), Some(temp_dir.path().join(OsStr::from_bytes(b"dir\xff"))));
+    }
+
+    #[tokio::test]
+    async fn test_walk_recursive() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("a/b")).unwrap();
+        fs::write(temp_dir.path().join("top.txt"), "1").unwrap();
+        fs::write(temp_dir.path().join("a/b/deep.txt"), "22").unwrap();
+
+        let backend = LocalBackend::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut seen = Vec::new();
+        backend
+            .walk("", &mut |path, entry| seen.push((path.to_string(), entry.size)))
+            .await
+            .unwrap();
+        seen.sort();
+        assert_eq!(
+            seen,
+            vec![("a/b/deep.txt".to_string(), Some(2)), ("top.txt".to_string(), Some(1))]
+        );
+    }
+
     #[tokio::test]
     async fn test_list_directories_first() {
         let temp_dir = TempDir::new().unwrap();
@@ -339,7 +561,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_get_preview_too_large() {
+    async fn test_get_preview_truncated() {
         let temp_dir = TempDir::new().unwrap();
         let file_path = temp_dir.path().join("large.txt");
         let large_content = "x".repeat(2000);
@@ -348,8 +570,19 @@ mod tests {
         let backend = LocalBackend::new(temp_dir.path().to_path_buf()).unwrap();
         let preview = backend.get_preview("large.txt", 100).await.unwrap();
         match preview {
-            PreviewContent::TooLarge { size, .. } => assert_eq!(size, 2000),
-            _ => panic!("Expected too large preview"),
+            PreviewContent::Text(text, meta) => {
+                assert_eq!(text, "x".repeat(100));
+                assert_eq!(meta.size, Some(2000));
+                assert!(meta.truncated);
+            }
+            _ => panic!("Expected the head of the file"),
+        }
+
+        // A character split by the cut is dropped
+        fs::write(&file_path, "aé").unwrap();
+        match backend.get_preview("large.txt", 2).await.unwrap() {
+            PreviewContent::Text(text, _) => assert_eq!(text, "a"),
+            _ => panic!("Expected the head of the file"),
         }
     }
 
@@ -371,7 +604,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let file_path = temp_dir.path().join("binary.bin");
         // Write binary data (invalid UTF-8)
-        fs::write(&file_path, vec![0xFF, 0xFE, 0x00, 0x01]).unwrap();
+        fs::write(&file_path, vec![0x00, 0x01, 0xFF, 0xFE]).unwrap();
 
         let backend = LocalBackend::new(temp_dir.path().to_path_buf()).unwrap();
         let preview = backend.get_preview("binary.bin", 1024).await.unwrap();
@@ -0,0 +1,418 @@
+use super::{is_archive_storage_class, Backend, Entry, FileMetadata, ListResult, PreviewContent, RestoreState};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Fixture behind `--demo`
+const DEMO_FIXTURE: &str = include_str!("../../fixtures/demo.toml");
+
+/// A file held by the memory backend
+#[derive(Debug, Clone, Default)]
+struct MemoryFile {
+    contents: Vec<u8>,
+    modified: Option<String>,
+    storage_class: Option<String>,
+}
+
+impl MemoryFile {
+    /// MD5 of the contents, as S3 reports for objects uploaded in one part
+    fn etag(&self) -> String {
+        format!("{:x}", Md5::digest(&self.contents))
+    }
+
+    fn restore_state(&self) -> Option<RestoreState> {
+        self.storage_class
+            .as_deref()
+            .filter(|sc| is_archive_storage_class(sc))
+            .map(|_| RestoreState::Archived)
+    }
+}
+
+/// Declarative fixture: a `[files]` table mapping paths to their contents, or
+/// to a table with `contents` and optional `modified` and `storage_class`
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    files: BTreeMap<String, FixtureFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FixtureFile {
+    Contents(String),
+    Detailed {
+        contents: String,
+        #[serde(default)]
+        modified: Option<String>,
+        #[serde(default)]
+        storage_class: Option<String>,
+    },
+}
+
+/// In-memory backend: a flat map of file paths to contents. Directories are
+/// implied by the paths, like S3 prefixes.
+#[derive(Debug)]
+pub struct MemoryBackend {
+    name: String,
+    files: Mutex<BTreeMap<String, MemoryFile>>,
+    /// Time listings and previews take, to try out slow or hung requests
+    latency: Mutex<Duration>,
+    /// Error listings and previews fail with, to try out failing requests
+    failure: Mutex<Option<String>>,
+}
+
+impl MemoryBackend {
+    /// Empty backend; `name` shows up in display paths (`memory://<name>/...`)
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            files: Mutex::new(BTreeMap::new()),
+            latency: Mutex::new(Duration::ZERO),
+            failure: Mutex::new(None),
+        }
+    }
+
+    /// Backend seeded from a TOML fixture
+    pub fn from_fixture(name: impl Into<String>, fixture: &str) -> Result<Self> {
+        let fixture: Fixture = toml::from_str(fixture).context("Invalid fixture")?;
+        let backend = Self::new(name);
+        {
+            let mut files = backend.files.lock().unwrap();
+            for (path, file) in fixture.files {
+                let file = match file {
+                    FixtureFile::Contents(contents) => MemoryFile { contents: contents.into_bytes(), ..Default::default() },
+                    FixtureFile::Detailed { contents, modified, storage_class } => MemoryFile {
+                        contents: contents.into_bytes(),
+                        modified,
+                        storage_class,
+                    },
+                };
+                files.insert(path.trim_matches('/').to_string(), file);
+            }
+        }
+        Ok(backend)
+    }
+
+    /// Sample bucket for trying out the UI without credentials (`--demo`)
+    pub fn demo() -> Self {
+        Self::from_fixture("demo", DEMO_FIXTURE).expect("built-in demo fixture is valid")
+    }
+
+    /// Add a file (builder style)
+    pub fn with_file(self, path: &str, contents: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, contents.into());
+        self
+    }
+
+    /// Make listings and previews take `latency` from now on
+    pub fn set_latency(&self, latency: Duration) {
+        *self.latency.lock().unwrap() = latency;
+    }
+
+    /// Make listings and previews fail with `error` from now on (None to
+    /// let them succeed again)
+    pub fn set_failure(&self, error: Option<&str>) {
+        *self.failure.lock().unwrap() = error.map(str::to_string);
+    }
+
+    /// Wait as long as a request takes, and fail it if requests fail
+    async fn request(&self) -> Result<()> {
+        let latency = *self.latency.lock().unwrap();
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        match self.failure.lock().unwrap().clone() {
+            Some(error) => bail!(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Contents of a file
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        self.get(path).map(|file| file.contents)
+    }
+
+    fn get(&self, path: &str) -> Option<MemoryFile> {
+        self.files.lock().unwrap().get(path.trim_matches('/')).cloned()
+    }
+
+    fn insert(&self, path: &str, contents: Vec<u8>) {
+        let file = MemoryFile { contents, ..Default::default() };
+        self.files.lock().unwrap().insert(path.trim_matches('/').to_string(), file);
+    }
+}
+
+#[async_trait]
+impl Backend for MemoryBackend {
+    async fn list(&self, prefix: &str) -> Result<ListResult> {
+        self.request().await?;
+        let prefix = prefix.trim_matches('/');
+        let base = if prefix.is_empty() { String::new() } else { format!("{}/", prefix) };
+
+        let files = self.files.lock().unwrap();
+        let mut dirs = BTreeSet::new();
+        let mut entries = Vec::new();
+        for (path, file) in files.iter() {
+            let Some(rest) = path.strip_prefix(&base) else { continue };
+            match rest.split_once('/') {
+                Some((dir, _)) => {
+                    dirs.insert(dir.to_string());
+                }
+                None => entries.push(Entry {
+                    name: rest.to_string(),
+                    is_dir: false,
+                    size: Some(file.contents.len() as u64),
+                    modified: file.modified.clone(),
+                    storage_class: file.storage_class.clone(),
+                    restore_state: file.restore_state(),
+                    etag: Some(file.etag()),
+                    permissions: None,
+                }),
+            }
+        }
+        // A `_$folder$` marker stands for its (possibly empty) directory, as on S3
+        let marked = files.contains_key(&format!("{}{}", prefix, super::FOLDER_MARKER_SUFFIX));
+        if !prefix.is_empty() && dirs.is_empty() && entries.is_empty() && !marked {
+            bail!("No such prefix: {}", prefix);
+        }
+
+        // Directories first, like the other backends
+        let dirs = dirs.into_iter().map(|name| Entry {
+            name,
+            is_dir: true,
+            size: None,
+            modified: None,
+            storage_class: None,
+            restore_state: None,
+            etag: None,
+            permissions: None,
+        });
+        Ok(ListResult {
+            entries: dirs.chain(entries).collect(),
+            prefix: prefix.to_string(),
+        })
+    }
+
+    async fn get_preview(&self, path: &str, max_size: usize) -> Result<PreviewContent> {
+        self.request().await?;
+        let Some(file) = self.get(path) else {
+            return Ok(PreviewContent::Error("File not found".to_string()));
+        };
+        if file.restore_state().is_some() {
+            return Ok(PreviewContent::Error(format!(
+                "Object is in {} storage and must be restored before it can be previewed or downloaded",
+                file.storage_class.as_deref().unwrap_or("archive")
+            )));
+        }
+
+        let size = file.contents.len() as u64;
+        let etag = Some(file.etag());
+        if file.contents.len() > max_size {
+            return Ok(PreviewContent::TooLarge {
+                size,
+                modified: file.modified,
+                etag,
+                storage_class: file.storage_class,
+                version_id: None,
+                version_number: None,
+            });
+        }
+        match super::decode_preview(file.contents, false) {
+            Some((text, encoding)) => Ok(PreviewContent::Text(
+                text,
+                FileMetadata {
+                    size: Some(size),
+                    modified: file.modified,
+                    etag,
+                    storage_class: file.storage_class,
+                    version_id: None,
+                    version_number: None,
+                    truncated: false,
+                    encoding,
+                },
+            )),
+            None => Ok(PreviewContent::Binary {
+                size,
+                mime_type: mime_guess::from_path(path).first().map(|m| m.to_string()),
+                modified: file.modified,
+                etag,
+                storage_class: file.storage_class,
+                version_id: None,
+                version_number: None,
+            }),
+        }
+    }
+
+    async fn download_file(
+        &self,
+        path: &str,
+        destination: &Path,
+        progress_callback: Option<super::ProgressCallback>,
+    ) -> Result<()> {
+        let file = self.get(path).with_context(|| format!("No such file: {}", path))?;
+        if file.restore_state().is_some() {
+            bail!("{} must be restored before it can be downloaded", path);
+        }
+        tokio::fs::write(destination, &file.contents)
+            .await
+            .with_context(|| format!("Failed to write {}", destination.display()))?;
+        if let Some(callback) = progress_callback {
+            let size = file.contents.len() as u64;
+            callback(size, Some(size));
+        }
+        Ok(())
+    }
+
+    async fn upload_file(&self, source: &Path, path: &str) -> Result<()> {
+        let contents = tokio::fs::read(source)
+            .await
+            .with_context(|| format!("Failed to read {}", source.display()))?;
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    /// What HeadObject would tell about a file this size and class
+    async fn properties(&self, path: &str) -> Result<Vec<(String, String)>> {
+        let file = self.get(path).with_context(|| format!("No such file: {}", path))?;
+        let content_type = mime_guess::from_path(path).first_or_octet_stream().to_string();
+        Ok(vec![
+            ("Size".to_string(), format!("{} bytes", file.contents.len())),
+            ("ETag".to_string(), file.etag()),
+            ("Content type".to_string(), content_type),
+            ("Storage class".to_string(), file.storage_class.unwrap_or_else(|| "STANDARD".to_string())),
+        ])
+    }
+
+    fn location_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get_display_path(&self, prefix: &str) -> String {
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            format!("memory://{}", self.name)
+        } else {
+            format!("memory://{}/{}", self.name, prefix)
+        }
+    }
+
+    fn uri_to_prefix(&self, uri: &str) -> Option<String> {
+        let rest = uri.strip_prefix("memory://")?.strip_prefix(&self.name)?;
+        if rest.is_empty() {
+            Some(String::new())
+        } else {
+            rest.strip_prefix('/').map(|prefix| prefix.to_string())
+        }
+    }
+
+    fn get_parent(&self, prefix: &str) -> Option<String> {
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            return None;
+        }
+        Some(prefix.rsplit_once('/').map(|(parent, _)| parent.to_string()).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::WalkPage;
+    use tempfile::TempDir;
+
+    fn names(result: ListResult) -> Vec<String> {
+        result.entries.into_iter().map(|e| e.name).collect()
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let backend = MemoryBackend::new("test")
+            .with_file("README.md", "hello")
+            .with_file("data/report.csv", "a,b\n")
+            .with_file("data/2024/jan.csv", "a,b\n");
+        assert_eq!(names(backend.list("").await.unwrap()), ["data", "README.md"]);
+        assert_eq!(names(backend.list("data/").await.unwrap()), ["2024", "report.csv"]);
+        assert!(backend.list("missing").await.is_err());
+
+        let entries = backend.list("").await.unwrap().entries;
+        assert_eq!(entries[1].size, Some(5));
+        assert_eq!(entries[1].etag.as_deref(), Some("5d41402abc4b2a76b9719d911017c592"));
+    }
+
+    #[tokio::test]
+    async fn test_walk_page() {
+        let backend = MemoryBackend::new("test")
+            .with_file("README.md", "hello")
+            .with_file("data/report.csv", "a,b\n")
+            .with_file("data/2024/jan.csv", "a,b\n");
+        let paths = |page: &WalkPage| page.files.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>();
+
+        let page = backend.walk_page("", None, 2).await.unwrap();
+        assert_eq!(paths(&page), ["README.md", "data/2024/jan.csv"]);
+        assert!(page.more);
+        let page = backend.walk_page("", Some("data/2024/jan.csv"), 2).await.unwrap();
+        assert_eq!(paths(&page), ["data/report.csv"]);
+        assert!(!page.more);
+        let page = backend.walk_page("data", None, 2).await.unwrap();
+        assert_eq!(paths(&page), ["data/2024/jan.csv", "data/report.csv"]);
+        assert!(!page.more);
+    }
+
+    #[tokio::test]
+    async fn test_fixture() {
+        let fixture = r#"
+            [files]
+            "notes.txt" = "first\nsecond\n"
+            "archive/old.tar" = { contents = "...", modified = "2023-01-02 03:04:05", storage_class = "GLACIER" }
+        "#;
+        let backend = MemoryBackend::from_fixture("fx", fixture).unwrap();
+        let archived = &backend.list("archive").await.unwrap().entries[0];
+        assert_eq!(archived.modified.as_deref(), Some("2023-01-02 03:04:05"));
+        assert_eq!(archived.restore_state, Some(RestoreState::Archived));
+        assert!(matches!(backend.get_preview("archive/old.tar", 1024).await.unwrap(), PreviewContent::Error(_)));
+
+        assert!(MemoryBackend::from_fixture("fx", "files = 1").is_err());
+        // The built-in demo parses
+        assert!(!MemoryBackend::demo().list("").await.unwrap().entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_preview_and_download() {
+        let backend = MemoryBackend::new("test").with_file("a.txt", "hello").with_file("b.bin", vec![0x00, 0xff]);
+        match backend.get_preview("a.txt", 1024).await.unwrap() {
+            PreviewContent::Text(text, meta) => {
+                assert_eq!(text, "hello");
+                assert_eq!(meta.size, Some(5));
+            }
+            other => panic!("unexpected preview: {:?}", other),
+        }
+        assert!(matches!(backend.get_preview("a.txt", 2).await.unwrap(), PreviewContent::TooLarge { size: 5, .. }));
+        assert!(matches!(backend.get_preview("b.bin", 1024).await.unwrap(), PreviewContent::Binary { size: 2, .. }));
+
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("a.txt");
+        backend.download_file("a.txt", &target, None).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "hello");
+        assert!(backend.download_file("missing", &target, None).await.is_err());
+
+        backend.upload_file(&target, "copy/a.txt").await.unwrap();
+        assert_eq!(backend.file("copy/a.txt").as_deref(), Some(b"hello".as_slice()));
+    }
+
+    #[test]
+    fn test_paths() {
+        let backend = MemoryBackend::new("demo");
+        assert_eq!(backend.get_display_path(""), "memory://demo");
+        assert_eq!(backend.get_display_path("data/2024"), "memory://demo/data/2024");
+        assert_eq!(backend.uri_to_prefix("memory://demo/data/2024").as_deref(), Some("data/2024"));
+        assert_eq!(backend.uri_to_prefix("memory://demo").as_deref(), Some(""));
+        assert_eq!(backend.uri_to_prefix("memory://demox/data"), None);
+        assert_eq!(backend.get_parent("data/2024").as_deref(), Some("data"));
+        assert_eq!(backend.get_parent("data").as_deref(), Some(""));
+        assert_eq!(backend.get_parent(""), None);
+    }
+}
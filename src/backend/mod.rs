@@ -1,8 +1,13 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use std::path::Path;
+use encoding_rs::Encoding;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+pub mod http;
 pub mod local;
+pub mod memory;
+pub mod registry;
 
 #[cfg(feature = "s3")]
 pub mod s3;
@@ -12,17 +17,189 @@ pub mod s3;
 pub struct Entry {
     pub name: String,
     pub is_dir: bool,
+    /// Size of a file; set on a directory only when an object of the same name
+    /// exists too (see [`merge_dir_objects`])
     pub size: Option<u64>,
     pub modified: Option<String>,
+    /// Storage class (S3 only; None for the default class or non-S3 backends)
+    pub storage_class: Option<String>,
+    /// Restore state for objects in an archive storage class (None otherwise)
+    pub restore_state: Option<RestoreState>,
+    /// Entity tag (S3 only; the MD5 of the content for objects uploaded in one part)
+    pub etag: Option<String>,
+    /// Mode bits and ownership (local files only)
+    pub permissions: Option<Permissions>,
+}
+
+impl Entry {
+    /// Whether the entry's content can be read right now (false for archived objects
+    /// that haven't been restored yet)
+    pub fn is_readable(&self) -> bool {
+        !matches!(self.restore_state, Some(RestoreState::Archived) | Some(RestoreState::InProgress))
+            && self.permissions.as_ref().is_none_or(|permissions| permissions.readable)
+    }
+
+    /// Whether a directory also exists as an object of the same name
+    /// (`report` next to `report/`)
+    pub fn has_object(&self) -> bool {
+        self.is_dir && self.size.is_some()
+    }
+}
+
+/// Unix permissions of a local entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    /// `st_mode` (file type and permission bits); None when the entry
+    /// couldn't be inspected
+    pub mode: Option<u32>,
+    /// Owner and group names (the numeric ids for unknown ones)
+    pub owner: String,
+    pub group: String,
+    /// Whether the current user may read the file (list the directory)
+    pub readable: bool,
+}
+
+impl Permissions {
+    /// `ls -l` style mode string, e.g. `drwxr-xr-x` (`?---------` when unknown)
+    pub fn mode_string(&self) -> String {
+        let Some(mode) = self.mode else {
+            return "?---------".to_string();
+        };
+        let kind = match mode & 0o170000 {
+            0o040000 => 'd',
+            0o120000 => 'l',
+            0o010000 => 'p',
+            0o140000 => 's',
+            0o020000 => 'c',
+            0o060000 => 'b',
+            _ => '-',
+        };
+        let mut text = String::with_capacity(10);
+        text.push(kind);
+        for shift in [6, 3, 0] {
+            let bits = (mode >> shift) & 0o7;
+            text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            text.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+        }
+        text
+    }
+}
+
+/// Retrieval state of an object in an archive storage class (GLACIER, DEEP_ARCHIVE)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreState {
+    /// Archived; must be restored before it can be previewed or downloaded
+    Archived,
+    /// Restore requested and still running
+    InProgress,
+    /// Temporary copy available until the given expiry date (if known)
+    Restored { expiry: Option<String> },
+}
+
+/// Whether objects of this storage class must be restored before they can be read
+pub fn is_archive_storage_class(storage_class: &str) -> bool {
+    matches!(storage_class, "GLACIER" | "DEEP_ARCHIVE")
+}
+
+/// Parts of the messages backends and the AWS SDK fail with when credentials
+/// expired mid-session (temporary credentials, an SSO session), lowercase
+const EXPIRED_CREDENTIALS_MARKERS: &[&str] = &[
+    "expiredtoken",
+    "invalidtoken",
+    "token has expired",
+    "token is expired",
+    "security token included in the request is expired",
+    "sso session associated with this profile has expired",
+    "sso session has expired",
+];
+
+/// Whether `error` (or one of its causes) says the credentials expired, so
+/// logging in again and retrying can fix it
+pub fn is_auth_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        let message = cause.to_string().to_lowercase();
+        EXPIRED_CREDENTIALS_MARKERS.iter().any(|marker| message.contains(marker))
+    })
+}
+
+/// Suffix of the marker objects Hadoop and EMR leave for directories
+/// (`logs_$folder$` for `logs/`)
+pub const FOLDER_MARKER_SUFFIX: &str = "_$folder$";
+
+/// Whether `entry` is a `_$folder$` directory marker object
+pub fn is_folder_marker(entry: &Entry) -> bool {
+    !entry.is_dir && entry.name.len() > FOLDER_MARKER_SUFFIX.len() && entry.name.ends_with(FOLDER_MARKER_SUFFIX)
+}
+
+/// Replace the `_$folder$` markers of a directory listing with the
+/// directories they stand for: dropped when the directory is listed anyway,
+/// listed as an (empty) directory otherwise. Directories stay first, in
+/// name order.
+pub fn fold_folder_markers(entries: &mut Vec<Entry>) {
+    let mut marked: Vec<Entry> = Vec::new();
+    for entry in entries.iter().filter(|entry| is_folder_marker(entry)) {
+        let name = entry.name.strip_suffix(FOLDER_MARKER_SUFFIX).unwrap_or(&entry.name);
+        if !entries.iter().any(|e| e.is_dir && e.name == name) && !marked.iter().any(|e| e.name == name) {
+            marked.push(Entry {
+                name: name.to_string(),
+                is_dir: true,
+                size: None,
+                modified: entry.modified.clone(),
+                storage_class: None,
+                restore_state: None,
+                etag: None,
+                permissions: None,
+            });
+        }
+    }
+    entries.retain(|entry| !is_folder_marker(entry));
+    for dir in marked {
+        let position = entries.iter().position(|e| !e.is_dir || e.name > dir.name).unwrap_or(entries.len());
+        entries.insert(position, dir);
+    }
+}
+
+/// Merge the objects named like a directory of the same listing (a `report`
+/// object next to `report/`) into the directory entry, which takes over the
+/// object's size, time, ETag and storage class
+pub fn merge_dir_objects(entries: &mut Vec<Entry>) {
+    let objects: Vec<Entry> = entries
+        .iter()
+        .filter(|entry| !entry.is_dir && entries.iter().any(|e| e.is_dir && e.name == entry.name))
+        .cloned()
+        .collect();
+    if objects.is_empty() {
+        return;
+    }
+    entries.retain(|entry| entry.is_dir || !objects.iter().any(|object| object.name == entry.name));
+    for object in objects {
+        if let Some(dir) = entries.iter_mut().find(|e| e.is_dir && e.name == object.name) {
+            dir.size = Some(object.size.unwrap_or(0));
+            dir.modified = object.modified;
+            dir.etag = object.etag;
+            dir.storage_class = object.storage_class;
+            dir.restore_state = object.restore_state;
+        }
+    }
 }
 
 /// Result of listing a directory/prefix
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ListResult {
     pub entries: Vec<Entry>,
     pub prefix: String,
 }
 
+/// One page of files below a prefix (see [`Backend::walk_page`])
+#[derive(Debug, Clone)]
+pub struct WalkPage {
+    /// Full paths and entries of the files, in path order
+    pub files: Vec<(String, Entry)>,
+    /// Whether more files follow
+    pub more: bool,
+}
+
 /// Metadata associated with a file (used in all preview variants)
 #[derive(Debug, Clone, Default)]
 pub struct FileMetadata {
@@ -34,6 +211,136 @@ pub struct FileMetadata {
     /// 1-based ordinal of this version (oldest = 1, newest = N); None if versioning
     /// is disabled or the version list could not be fetched.
     pub version_number: Option<usize>,
+    /// Only the head of the file was read (see `size` for the whole)
+    pub truncated: bool,
+    /// How the text was decoded when it isn't plain UTF-8 (shown in the
+    /// preview title)
+    pub encoding: Option<Decoding>,
+}
+
+/// How the text of a preview was decoded when it isn't plain UTF-8
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoding {
+    pub encoding: &'static Encoding,
+    /// Some bytes couldn't be decoded and were replaced
+    pub lossy: bool,
+    /// The bytes read, to decode them differently
+    pub raw: Arc<[u8]>,
+}
+
+impl Decoding {
+    /// `Shift_JIS`, or `UTF-8, lossy` when bytes were replaced
+    pub fn label(&self) -> String {
+        if self.lossy {
+            format!("{}, lossy", self.encoding.name())
+        } else {
+            self.encoding.name().to_string()
+        }
+    }
+}
+
+/// Encodings the preview cycles through when overriding the detected one
+pub const PREVIEW_ENCODINGS: &[&Encoding] = &[
+    encoding_rs::UTF_8,
+    encoding_rs::WINDOWS_1252,
+    encoding_rs::SHIFT_JIS,
+    encoding_rs::EUC_JP,
+    encoding_rs::GBK,
+    encoding_rs::BIG5,
+    encoding_rs::EUC_KR,
+    encoding_rs::WINDOWS_1251,
+    encoding_rs::UTF_16LE,
+    encoding_rs::UTF_16BE,
+];
+
+/// How much of a file's start is inspected to tell binary files from text
+pub const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Share of control characters (other than whitespace and escapes) in the
+/// inspected start above which a file counts as binary
+const BINARY_CONTROL_RATIO: f64 = 0.1;
+
+/// Whether `bytes` look like binary content: a NUL byte near the start, as
+/// git and ripgrep check, or many control characters, as `file` does
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    if sample.contains(&0) {
+        return true;
+    }
+    let controls = sample
+        .iter()
+        .filter(|&&byte| (byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || byte == 0x7f)
+        .count();
+    controls as f64 > sample.len() as f64 * BINARY_CONTROL_RATIO
+}
+
+/// The byte order of UTF-16 text without a byte order mark: most ASCII
+/// characters leave every other byte zero
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_LEN) & !1];
+    if sample.len() < 4 {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let zeros = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|&&byte| byte == 0).count();
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 10 > pairs * 7 && even * 20 < pairs {
+        Some(encoding_rs::UTF_16LE)
+    } else if even * 10 > pairs * 7 && odd * 20 < pairs {
+        Some(encoding_rs::UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Decode `raw` as `encoding` for previewing. The decoding is None for text
+/// that is plain UTF-8.
+pub fn decode_as(raw: Arc<[u8]>, encoding: &'static Encoding) -> (String, Option<Decoding>) {
+    let (text, lossy) = encoding.decode_without_bom_handling(&raw);
+    let text = text.into_owned();
+    if encoding == encoding_rs::UTF_8 && !lossy {
+        return (text, None);
+    }
+    (text, Some(Decoding { encoding, lossy, raw }))
+}
+
+/// Decode file content for previewing: None for binary content, otherwise
+/// the text and, unless it is plain UTF-8, how it was decoded. The encoding
+/// is taken from a byte order mark, or detected (Shift-JIS, latin-1, ...)
+/// when the content isn't valid UTF-8. A character split at the end of a
+/// `truncated` head is dropped rather than replaced.
+pub fn decode_preview(mut bytes: Vec<u8>, truncated: bool) -> Option<(String, Option<Decoding>)> {
+    // UTF-16 is mostly NUL bytes; binary content shows once decoded
+    let utf16 = match Encoding::for_bom(&bytes) {
+        Some((encoding, bom_length)) => {
+            bytes.drain(..bom_length);
+            (encoding != encoding_rs::UTF_8).then_some(encoding)
+        }
+        None => utf16_without_bom(&bytes),
+    };
+    if let Some(encoding) = utf16 {
+        let (text, decoding) = decode_as(bytes.into(), encoding);
+        return (!looks_binary(text.as_bytes())).then_some((text, decoding));
+    }
+    if looks_binary(&bytes) {
+        return None;
+    }
+    if truncated {
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            if e.error_len().is_none() {
+                bytes.truncate(e.valid_up_to());
+            }
+        }
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Some((text, None)),
+        Err(e) => {
+            let bytes = e.into_bytes();
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(&bytes, !truncated);
+            Some(decode_as(bytes.into(), detector.guess(None, true)))
+        }
+    }
 }
 
 /// Preview content for a file
@@ -60,6 +367,14 @@ pub enum PreviewContent {
     Error(String),
 }
 
+/// A stored version of an object
+#[derive(Debug, Clone)]
+pub struct ObjectVersion {
+    pub version_id: String,
+    pub modified: Option<String>,
+    pub is_latest: bool,
+}
+
 /// Progress information for downloads
 #[derive(Debug, Clone)]
 pub struct DownloadProgress {
@@ -71,7 +386,7 @@ pub struct DownloadProgress {
 /// Callback for download progress updates
 pub type ProgressCallback = Box<dyn Fn(u64, Option<u64>) + Send + Sync>;
 
-/// Backend trait for different storage systems (S3, local filesystem)
+/// Backend trait for different storage systems (S3, local filesystem, HTTP)
 #[async_trait]
 pub trait Backend: Send + Sync {
     /// List entries at the given prefix/path
@@ -89,6 +404,11 @@ pub trait Backend: Send + Sync {
         progress_callback: Option<ProgressCallback>,
     ) -> Result<()>;
 
+    /// Upload a local file to the given path, replacing any existing file
+    async fn upload_file(&self, _source: &Path, _path: &str) -> Result<()> {
+        anyhow::bail!("Uploads are not supported by this backend")
+    }
+
     /// Get the root name for this backend (bucket name for S3, root path for local)
     fn location_name(&self) -> String;
 
@@ -101,4 +421,216 @@ pub trait Backend: Send + Sync {
 
     /// Get the parent prefix/path (for navigating up)
     fn get_parent(&self, prefix: &str) -> Option<String>;
+
+    /// Short name of the storage type ("s3", "local", ...), the scheme of the
+    /// display path by default
+    fn kind(&self) -> String {
+        match self.get_display_path("").split_once("://") {
+            Some((scheme, _)) => scheme.to_string(),
+            None => "local".to_string(),
+        }
+    }
+
+    /// Where `prefix` is on this machine's filesystem, for local backends.
+    /// Unlike the display path, it keeps names that aren't UTF-8 as they are.
+    fn local_path(&self, _prefix: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// AWS profile the backend's credentials come from, if any
+    fn profile(&self) -> Option<String> {
+        None
+    }
+
+    /// AWS region the backend talks to, if any
+    fn region(&self) -> Option<String> {
+        None
+    }
+
+    /// Recursively visit every file below `prefix`, calling `visit` with the full path.
+    /// The default walks directories with `list()`; backends with a flat listing
+    /// (S3) override this.
+    async fn walk(&self, prefix: &str, visit: &mut (dyn for<'p> FnMut(&'p str, &'p Entry) + Send)) -> Result<()> {
+        let mut pending = vec![prefix.to_string()];
+        while let Some(dir) = pending.pop() {
+            let result = self.list(&dir).await?;
+            for entry in &result.entries {
+                let path = if result.prefix.is_empty() {
+                    entry.name.clone()
+                } else {
+                    format!("{}/{}", result.prefix, entry.name)
+                };
+                if entry.is_dir {
+                    pending.push(path);
+                } else {
+                    visit(&path, entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Up to `max` files below `prefix` whose paths sort after `after`, in path
+    /// order, for going through huge prefixes page by page. The default walks
+    /// everything with `walk()`; backends that list keys in order (S3) override it.
+    async fn walk_page(&self, prefix: &str, after: Option<&str>, max: usize) -> Result<WalkPage> {
+        let mut files = Vec::new();
+        self.walk(prefix, &mut |path, entry| {
+            if after.is_none_or(|after| path > after) {
+                files.push((path.to_string(), entry.clone()));
+            }
+        })
+        .await?;
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        let more = files.len() > max;
+        files.truncate(max);
+        Ok(WalkPage { files, more })
+    }
+
+    /// Run an S3 Select SQL query against a CSV/JSON/Parquet object and return the
+    /// matching records (at most `max_size` bytes) as a text preview
+    async fn select_object(&self, _path: &str, _query: &str, _max_size: usize) -> Result<PreviewContent> {
+        anyhow::bail!("S3 Select queries are only supported for S3 objects")
+    }
+
+    /// Versions of an object, newest first (empty if the backend has no versioning)
+    async fn list_versions(&self, _path: &str) -> Result<Vec<ObjectVersion>> {
+        Ok(Vec::new())
+    }
+
+    /// Read (at most `max_size` bytes of) a specific version of an object as text
+    async fn read_version(&self, _path: &str, _version_id: &str, _max_size: usize) -> Result<String> {
+        anyhow::bail!("Object versions are not supported by this backend")
+    }
+
+    /// Request a temporary restore of an archived object, kept for `days` days
+    async fn restore_object(&self, _path: &str, _days: i32) -> Result<()> {
+        anyhow::bail!("Restoring archived objects is not supported by this backend")
+    }
+
+    /// Current restore state of an object (None if it isn't archived)
+    async fn restore_state(&self, _path: &str) -> Result<Option<RestoreState>> {
+        Ok(None)
+    }
+
+    /// Details of an object beyond its listing entry, as (name, value) pairs
+    /// in display order (S3: the HeadObject response). Empty if the backend
+    /// has nothing more to tell.
+    async fn properties(&self, _path: &str) -> Result<Vec<(String, String)>> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    fn entry(name: &str, is_dir: bool) -> Entry {
+        Entry {
+            name: name.to_string(),
+            is_dir,
+            size: None,
+            modified: None,
+            storage_class: None,
+            restore_state: None,
+            etag: None,
+            permissions: None,
+        }
+    }
+
+    #[test]
+    fn test_fold_folder_markers() {
+        let mut entries = vec![
+            entry("logs", true),
+            entry("tmp", true),
+            entry("empty_$folder$", false),
+            entry("logs_$folder$", false),
+            entry("report.csv", false),
+            entry("_$folder$", false),
+        ];
+        fold_folder_markers(&mut entries);
+        let names: Vec<(&str, bool)> = entries.iter().map(|e| (e.name.as_str(), e.is_dir)).collect();
+        assert_eq!(
+            names,
+            vec![("empty", true), ("logs", true), ("tmp", true), ("report.csv", false), ("_$folder$", false)]
+        );
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(looks_binary(b"PK\x03\x04\x00\x00"));
+        assert!(!looks_binary("plain text, ünïcode".as_bytes()));
+        // Only the start is checked
+        let mut late = vec![b'a'; BINARY_SNIFF_LEN];
+        late.push(0);
+        assert!(!looks_binary(&late));
+    }
+
+    #[test]
+    fn test_decode_preview() {
+        assert_eq!(decode_preview(b"plain".to_vec(), false), Some(("plain".to_string(), None)));
+        assert_eq!(decode_preview(b"\xef\xbb\xbfbom".to_vec(), false), Some(("bom".to_string(), None)));
+        // One latin-1 byte doesn't make a log binary
+        let (text, decoding) = decode_preview(b"caf\xe9 au lait\n".to_vec(), false).unwrap();
+        assert_eq!(text, "caf\u{e9} au lait\n");
+        assert_eq!(decoding.unwrap().label(), "windows-1252");
+        let (text, decoding) = decode_preview(b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x65\x83\x4c\x83\x58\x83\x67".to_vec(), false).unwrap();
+        assert_eq!(text, "日本語のテキスト");
+        assert_eq!(decoding.unwrap().label(), "Shift_JIS");
+        // UTF-16 is mostly NUL bytes, with or without a byte order mark
+        let utf16: Vec<u8> = "hello".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode_preview(utf16.clone(), false).unwrap().0, "hello");
+        let with_bom = [vec![0xff, 0xfe], utf16].concat();
+        let (text, decoding) = decode_preview(with_bom, false).unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(decoding.unwrap().label(), "UTF-16LE");
+        assert!(decode_preview(vec![0xff, 0xfe, 0x00, 0x00, 0x01, 0x00], false).is_none());
+
+        let (text, decoding) = decode_as(Arc::from(&b"caf\xe9"[..]), encoding_rs::UTF_8);
+        assert_eq!(text, "caf\u{fffd}");
+        assert_eq!(decoding.unwrap().label(), "UTF-8, lossy");
+        // Colored output keeps its escapes
+        assert!(decode_preview(b"\x1b[31merror\x1b[0m".to_vec(), false).is_some());
+        assert!(decode_preview(vec![0x01, 0x02, 0x03, b'a', 0x04], false).is_none());
+        // A character cut off at the end of the head is dropped
+        assert_eq!(decode_preview("a\u{e9}".as_bytes()[..2].to_vec(), true), Some(("a".to_string(), None)));
+    }
+
+    #[test]
+    fn test_mode_string() {
+        let permissions = |mode| Permissions { mode, owner: "alice".to_string(), group: "staff".to_string(), readable: true };
+        assert_eq!(permissions(Some(0o040755)).mode_string(), "drwxr-xr-x");
+        assert_eq!(permissions(Some(0o100600)).mode_string(), "-rw-------");
+        assert_eq!(permissions(Some(0o120777)).mode_string(), "lrwxrwxrwx");
+        assert_eq!(permissions(None).mode_string(), "?---------");
+    }
+
+    #[test]
+    fn test_merge_dir_objects() {
+        let mut entries = vec![
+            entry("logs", true),
+            entry("report", true),
+            Entry { size: Some(0), etag: Some("\"d41d8\"".to_string()), ..entry("report", false) },
+            entry("report.csv", false),
+        ];
+        merge_dir_objects(&mut entries);
+        let names: Vec<(&str, bool)> = entries.iter().map(|e| (e.name.as_str(), e.is_dir)).collect();
+        assert_eq!(names, vec![("logs", true), ("report", true), ("report.csv", false)]);
+        assert!(!entries[0].has_object());
+        assert!(entries[1].has_object());
+        assert_eq!(entries[1].etag.as_deref(), Some("\"d41d8\""));
+    }
+
+    #[test]
+    fn test_is_auth_error() {
+        let expired: Result<()> = Err(anyhow!("unhandled error (ExpiredToken)")).context("Failed to list S3 objects");
+        assert!(is_auth_error(&expired.unwrap_err()));
+        let sso = anyhow!("The SSO session associated with this profile has expired or is otherwise invalid");
+        assert!(is_auth_error(&sso));
+
+        // Missing permissions aren't fixed by logging in again
+        assert!(!is_auth_error(&anyhow!("unhandled error (AccessDenied)")));
+        assert!(!is_auth_error(&anyhow!("File not found")));
+    }
 }
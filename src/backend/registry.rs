@@ -0,0 +1,215 @@
+use super::{http::HttpBackend, local::LocalBackend, Backend};
+#[cfg(feature = "s3")]
+use super::s3::S3Backend;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Scheme of plain paths (`/data`, `~/logs`), which have no `scheme://`
+pub const LOCAL_SCHEME: &str = "local";
+
+/// Opens backends for the URIs of one or more schemes. Implement this to
+/// browse another kind of storage and add it to a [`BackendRegistry`].
+#[async_trait]
+pub trait BackendFactory: Send + Sync {
+    /// URI schemes handled, without `://` (e.g. `["http", "https"]`)
+    fn schemes(&self) -> Vec<String>;
+
+    /// Open the backend for `uri`, returning it and the prefix to list first.
+    /// `profile` is the AWS profile of a context, for backends that use one.
+    async fn open(&self, uri: &str, profile: Option<&str>) -> Result<(Arc<dyn Backend>, String)>;
+}
+
+/// Backend factories by URI scheme. Every place a location is given as a URI
+/// (the command line, contexts, sessions, the history) opens it through here.
+#[derive(Clone)]
+pub struct BackendRegistry {
+    factories: BTreeMap<String, Arc<dyn BackendFactory>>,
+}
+
+impl Default for BackendRegistry {
+    /// The built-in backends: `s3://` (with the `s3` feature), `http(s)://`
+    /// and local paths
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Arc::new(S3Factory::default()));
+        registry.register(Arc::new(HttpFactory));
+        registry.register(Arc::new(LocalFactory));
+        registry
+    }
+}
+
+impl BackendRegistry {
+    /// A registry without any backends
+    pub fn empty() -> Self {
+        Self { factories: BTreeMap::new() }
+    }
+
+    /// Add a factory for its schemes, replacing any registered before for them
+    pub fn register(&mut self, factory: Arc<dyn BackendFactory>) {
+        for scheme in factory.schemes() {
+            self.factories.insert(scheme.to_lowercase(), factory.clone());
+        }
+    }
+
+    /// Open `s3://` locations without signing requests, for public buckets
+    /// (`--no-sign-request`); the AWS profile of contexts is ignored
+    pub fn no_sign_request(mut self) -> Self {
+        self.register(Arc::new(S3Factory { unsigned: true }));
+        self
+    }
+
+    /// Schemes that can be opened
+    pub fn schemes(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(|s| s.as_str())
+    }
+
+    /// Open a location given as a URI or a local path: its backend and the
+    /// prefix to start at
+    pub async fn open(&self, uri: &str, profile: Option<&str>) -> Result<(Arc<dyn Backend>, String)> {
+        let scheme = uri_scheme(uri).unwrap_or(LOCAL_SCHEME);
+        match self.factories.get(&scheme.to_lowercase()) {
+            Some(factory) => factory.open(uri, profile).await,
+            None => bail!("Unsupported URI scheme: {}", uri),
+        }
+    }
+}
+
+/// Scheme of a `scheme://...` URI
+fn uri_scheme(uri: &str) -> Option<&str> {
+    let (scheme, _) = uri.split_once("://")?;
+    let valid = scheme.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+/// Expand tilde (~) in path to home directory
+pub fn expand_tilde(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
+
+/// `s3://bucket/prefix`
+#[derive(Default)]
+struct S3Factory {
+    /// Send unsigned requests instead of using credentials
+    #[cfg_attr(not(feature = "s3"), allow(dead_code))]
+    unsigned: bool,
+}
+
+#[async_trait]
+impl BackendFactory for S3Factory {
+    fn schemes(&self) -> Vec<String> {
+        vec!["s3".to_string()]
+    }
+
+    #[cfg(feature = "s3")]
+    async fn open(&self, uri: &str, profile: Option<&str>) -> Result<(Arc<dyn Backend>, String)> {
+        let (bucket, prefix) = S3Backend::from_uri(uri)?;
+        let backend = if self.unsigned {
+            S3Backend::anonymous(bucket).await?
+        } else {
+            S3Backend::with_profile(bucket, profile).await?
+        };
+        Ok((Arc::new(backend), prefix))
+    }
+
+    #[cfg(not(feature = "s3"))]
+    async fn open(&self, _uri: &str, _profile: Option<&str>) -> Result<(Arc<dyn Backend>, String)> {
+        bail!("S3 support not enabled (build with --features s3)")
+    }
+}
+
+/// `http://` and `https://` servers (directory listings or WebDAV)
+struct HttpFactory;
+
+#[async_trait]
+impl BackendFactory for HttpFactory {
+    fn schemes(&self) -> Vec<String> {
+        vec!["http".to_string(), "https".to_string()]
+    }
+
+    async fn open(&self, uri: &str, _profile: Option<&str>) -> Result<(Arc<dyn Backend>, String)> {
+        let (backend, prefix) = HttpBackend::from_uri(uri)?;
+        Ok((Arc::new(backend), prefix))
+    }
+}
+
+/// Local paths, bare or as `local:///path`
+struct LocalFactory;
+
+#[async_trait]
+impl BackendFactory for LocalFactory {
+    fn schemes(&self) -> Vec<String> {
+        vec![LOCAL_SCHEME.to_string()]
+    }
+
+    async fn open(&self, uri: &str, _profile: Option<&str>) -> Result<(Arc<dyn Backend>, String)> {
+        let path = uri.strip_prefix("local://").unwrap_or(uri);
+        Ok((Arc::new(LocalBackend::new(expand_tilde(path))?), String::new()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    /// `memory://name/prefix` opens an in-memory bucket
+    pub(crate) struct MemoryFactory;
+
+    #[async_trait]
+    impl BackendFactory for MemoryFactory {
+        fn schemes(&self) -> Vec<String> {
+            vec!["memory".to_string()]
+        }
+
+        async fn open(&self, uri: &str, _profile: Option<&str>) -> Result<(Arc<dyn Backend>, String)> {
+            let rest = uri.strip_prefix("memory://").unwrap_or(uri);
+            let (name, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+            Ok((Arc::new(MemoryBackend::new(name).with_file("a.txt", "a")), prefix.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_uri_scheme() {
+        assert_eq!(uri_scheme("s3://bucket/key"), Some("s3"));
+        assert_eq!(uri_scheme("git+ssh://host"), Some("git+ssh"));
+        assert_eq!(uri_scheme("/data/file"), None);
+        assert_eq!(uri_scheme("dir/x://y"), None);
+    }
+
+    #[tokio::test]
+    async fn test_registered_scheme() {
+        let mut registry = BackendRegistry::default();
+        assert!(registry.open("memory://bucket/logs", None).await.is_err());
+
+        registry.register(Arc::new(MemoryFactory));
+        assert!(registry.schemes().any(|s| s == "memory"));
+        let (backend, prefix) = registry.open("memory://bucket/logs", None).await.unwrap();
+        assert_eq!(backend.location_name(), "bucket");
+        assert_eq!(prefix, "logs");
+    }
+
+    #[tokio::test]
+    async fn test_default_schemes() {
+        let registry = BackendRegistry::default();
+        let schemes: Vec<&str> = registry.schemes().collect();
+        assert_eq!(schemes, vec!["http", "https", "local", "s3"]);
+        let unsigned = BackendRegistry::default().no_sign_request();
+        assert_eq!(unsigned.schemes().collect::<Vec<_>>(), schemes);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().display().to_string();
+        let (_, prefix) = registry.open(&path, None).await.unwrap();
+        assert_eq!(prefix, "");
+        assert!(registry.open(&format!("local://{}", path), None).await.is_ok());
+        assert!(BackendRegistry::empty().open(&path, None).await.is_err());
+    }
+}
@@ -1,30 +1,92 @@
 #![cfg(feature = "s3")]
 
-use super::{Backend, Entry, ListResult, PreviewContent};
-use anyhow::{Context, Result};
+use super::{
+    is_archive_storage_class, Backend, Entry, FileMetadata, ListResult, ObjectVersion, PreviewContent, RestoreState,
+    WalkPage,
+};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
+use aws_sdk_s3::types::{
+    CompressionType, CsvInput, CsvOutput, ExpressionType, FileHeaderInfo, GlacierJobParameters, InputSerialization,
+    JsonInput, JsonOutput, JsonType, OptionalObjectAttributes, OutputSerialization, ParquetInput, RestoreRequest,
+    SelectObjectContentEventStream, Tier,
+};
+use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::Client;
 use std::collections::HashSet;
 use std::path::Path;
 
+/// Object formats S3 Select can query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SelectFormat {
+    Csv,
+    Tsv,
+    JsonDocument,
+    JsonLines,
+    Parquet,
+}
+
 /// S3 backend implementation
 pub struct S3Backend {
     client: Client,
     bucket: String,
+    /// Named profile the credentials come from (None for the default chain)
+    profile: Option<String>,
+    region: Option<String>,
 }
 
 impl S3Backend {
     pub async fn new(bucket: String) -> Result<Self> {
-        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-        let client = Client::new(&config);
+        Self::with_profile(bucket, None).await
+    }
+
+    /// Backend using the credentials of a named AWS profile (None for the
+    /// default credential chain)
+    pub async fn with_profile(bucket: String, profile: Option<&str>) -> Result<Self> {
+        Self::connect(bucket, profile, false).await
+    }
+
+    /// Backend sending unsigned requests, for public buckets; no credentials
+    /// need to be configured (`--no-sign-request`)
+    pub async fn anonymous(bucket: String) -> Result<Self> {
+        Self::connect(bucket, None, true).await
+    }
 
-        Ok(Self { client, bucket })
+    async fn connect(bucket: String, profile: Option<&str>, anonymous: bool) -> Result<Self> {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(profile) = profile {
+            loader = loader.profile_name(profile);
+        }
+        if anonymous {
+            // Public buckets are often browsed without any AWS config at all
+            let region = aws_config::meta::region::RegionProviderChain::default_provider().or_else("us-east-1");
+            loader = loader.no_credentials().region(region);
+        }
+        let config = loader.load().await;
+        // Access point ARNs name their region; send their requests there
+        let client = Client::from_conf(aws_sdk_s3::config::Builder::from(&config).use_arn_region(true).build());
+        let region = config.region().map(|r| r.to_string());
+        let profile = if anonymous {
+            None
+        } else {
+            profile.map(str::to_string).or_else(|| std::env::var("AWS_PROFILE").ok())
+        };
+
+        Ok(Self { client, bucket, profile, region })
     }
 
+    /// Split `s3://bucket/prefix` into the bucket and the prefix. The bucket
+    /// may be an access point ARN
+    /// (`s3://arn:aws:s3:us-east-1:123456789012:accesspoint/name/prefix`),
+    /// which requests address as is, or a bucket ARN (`arn:aws:s3:::name`),
+    /// which stands for the bucket name.
     pub fn from_uri(uri: &str) -> Result<(String, String)> {
         // Parse s3://bucket/prefix
         let uri = uri.strip_prefix("s3://")
             .context("URI must start with s3://")?;
+        if uri.starts_with("arn:") {
+            return Self::split_arn(uri);
+        }
 
         let parts: Vec<&str> = uri.splitn(2, '/').collect();
         let bucket = parts[0].to_string();
@@ -37,6 +99,140 @@ impl S3Backend {
         Ok((bucket, prefix))
     }
 
+    /// Split `arn:partition:service:region:account:resource/prefix` into the
+    /// bucket to address and the prefix
+    fn split_arn(uri: &str) -> Result<(String, String)> {
+        let fields: Vec<&str> = uri.splitn(6, ':').collect();
+        let [_, partition, service, region, account, resource] = fields[..] else {
+            bail!("Invalid ARN: {}", uri);
+        };
+        if !service.starts_with("s3") {
+            bail!("Not an S3 ARN: {}", uri);
+        }
+        // Path segments naming the resource; the rest is the prefix
+        let segments = match resource.split('/').next() {
+            Some("accesspoint") => 2,
+            // Outposts: outpost/<id>/accesspoint/<name>
+            Some("outpost") => 4,
+            _ if region.is_empty() && account.is_empty() => 1,
+            _ => bail!("Unsupported S3 ARN (expected a bucket or access point): {}", uri),
+        };
+        let mut parts = resource.splitn(segments + 1, '/');
+        let name: Vec<&str> = parts.by_ref().take(segments).collect();
+        if name.len() < segments || name.iter().any(|part| part.is_empty()) {
+            bail!("Incomplete S3 ARN: {}", uri);
+        }
+        let prefix = parts.next().unwrap_or("").to_string();
+        let bucket = if segments == 1 {
+            name[0].to_string()
+        } else {
+            format!("arn:{}:{}:{}:{}:{}", partition, service, region, account, name.join("/"))
+        };
+        Ok((bucket, prefix))
+    }
+
+    /// Derive the restore state from the `x-amz-restore` HEAD header, e.g.
+    /// `ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT"`.
+    /// Returns None for objects outside the archive storage classes.
+    fn restore_state_from_header(storage_class: Option<&str>, restore: Option<&str>) -> Option<RestoreState> {
+        if !storage_class.map(is_archive_storage_class).unwrap_or(false) {
+            return None;
+        }
+
+        let Some(restore) = restore else {
+            return Some(RestoreState::Archived);
+        };
+
+        if restore.contains(r#"ongoing-request="true""#) {
+            Some(RestoreState::InProgress)
+        } else {
+            let expiry = restore
+                .split_once(r#"expiry-date=""#)
+                .and_then(|(_, rest)| rest.split('"').next())
+                .map(|s| s.to_string());
+            Some(RestoreState::Restored { expiry })
+        }
+    }
+
+    /// Pick the S3 Select input format and compression from the key's extension,
+    /// e.g. `events.jsonl.gz` → JSON lines, gzip. None if S3 Select can't read it.
+    fn select_format(key: &str) -> Option<(SelectFormat, CompressionType)> {
+        let lower = key.to_lowercase();
+        let (name, compression) = if let Some(name) = lower.strip_suffix(".gz") {
+            (name, CompressionType::Gzip)
+        } else if let Some(name) = lower.strip_suffix(".bz2") {
+            (name, CompressionType::Bzip2)
+        } else {
+            (lower.as_str(), CompressionType::None)
+        };
+
+        let format = match name.rsplit_once('.')?.1 {
+            "csv" => SelectFormat::Csv,
+            "tsv" => SelectFormat::Tsv,
+            "json" => SelectFormat::JsonDocument,
+            "jsonl" | "ndjson" => SelectFormat::JsonLines,
+            // Parquet is compressed internally; S3 Select rejects an outer compression type
+            "parquet" if compression == CompressionType::None => SelectFormat::Parquet,
+            _ => return None,
+        };
+        Some((format, compression))
+    }
+
+    /// Input/output serialization for a Select query. CSV results stay CSV (header row
+    /// used for column names); JSON and Parquet results come back as JSON lines.
+    fn select_serialization(format: SelectFormat, compression: CompressionType) -> (InputSerialization, OutputSerialization) {
+        let csv_output = || OutputSerialization::builder().csv(CsvOutput::builder().build()).build();
+        let json_output = || {
+            OutputSerialization::builder()
+                .json(JsonOutput::builder().record_delimiter("\n").build())
+                .build()
+        };
+
+        match format {
+            SelectFormat::Csv => (
+                InputSerialization::builder()
+                    .csv(CsvInput::builder().file_header_info(FileHeaderInfo::Use).build())
+                    .compression_type(compression)
+                    .build(),
+                csv_output(),
+            ),
+            SelectFormat::Tsv => (
+                InputSerialization::builder()
+                    .csv(
+                        CsvInput::builder()
+                            .file_header_info(FileHeaderInfo::Use)
+                            .field_delimiter("\t")
+                            .build(),
+                    )
+                    .compression_type(compression)
+                    .build(),
+                OutputSerialization::builder()
+                    .csv(CsvOutput::builder().field_delimiter("\t").build())
+                    .build(),
+            ),
+            SelectFormat::JsonDocument | SelectFormat::JsonLines => {
+                let json_type = if format == SelectFormat::JsonLines {
+                    JsonType::Lines
+                } else {
+                    JsonType::Document
+                };
+                (
+                    InputSerialization::builder()
+                        .json(JsonInput::builder().r#type(json_type).build())
+                        .compression_type(compression)
+                        .build(),
+                    json_output(),
+                )
+            }
+            SelectFormat::Parquet => (
+                InputSerialization::builder()
+                    .parquet(ParquetInput::builder().build())
+                    .build(),
+                json_output(),
+            ),
+        }
+    }
+
     /// Download a large file using parallel range requests.
     /// Splits the file into 8 MB parts and fetches up to 8 concurrently,
     /// writing each part directly to its offset in a pre-allocated file.
@@ -160,6 +356,7 @@ impl Backend for S3Backend {
             .bucket(&self.bucket)
             .prefix(&prefix)
             .delimiter("/")
+            .optional_object_attributes(OptionalObjectAttributes::RestoreStatus)
             .into_paginator()
             .send();
 
@@ -185,6 +382,10 @@ impl Backend for S3Backend {
                             is_dir: true,
                             size: None,
                             modified: None,
+                            storage_class: None,
+                            restore_state: None,
+                            etag: None,
+                            permissions: None,
                         });
                     }
                 }
@@ -209,6 +410,26 @@ impl Backend for S3Backend {
                     continue;
                 }
 
+                let storage_class = object
+                    .storage_class()
+                    .map(|sc| sc.as_str().to_string())
+                    .filter(|sc| sc != "STANDARD");
+
+                let restore_state = if storage_class.as_deref().map(is_archive_storage_class).unwrap_or(false) {
+                    Some(match object.restore_status() {
+                        Some(status) if status.is_restore_in_progress() == Some(true) => RestoreState::InProgress,
+                        Some(status) => RestoreState::Restored {
+                            expiry: status.restore_expiry_date().and_then(|t| {
+                                chrono::DateTime::from_timestamp(t.secs(), 0)
+                                    .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                            }),
+                        },
+                        None => RestoreState::Archived,
+                    })
+                } else {
+                    None
+                };
+
                 entries.push(Entry {
                     name,
                     is_dir: false,
@@ -219,6 +440,10 @@ impl Backend for S3Backend {
                             .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
                             .unwrap_or_default()
                     }),
+                    storage_class,
+                    restore_state,
+                    etag: object.e_tag().map(|e| e.trim_matches('"').to_string()),
+                    permissions: None,
                 });
             }
         }
@@ -267,6 +492,23 @@ impl Backend for S3Backend {
                     .map(|sc| sc.as_str().to_string());
                 let version_id = head.version_id().map(|s| s.to_string());
 
+                // Archived objects can't be read until they are restored
+                match Self::restore_state_from_header(storage_class.as_deref(), head.restore()) {
+                    Some(RestoreState::Archived) => {
+                        return Ok(PreviewContent::Error(format!(
+                            "Object is in {} storage and must be restored before it can be previewed or downloaded",
+                            storage_class.as_deref().unwrap_or("archive")
+                        )));
+                    }
+                    Some(RestoreState::InProgress) => {
+                        return Ok(PreviewContent::Error(format!(
+                            "Restore of this {} object is in progress; it can be previewed once the restore completes",
+                            storage_class.as_deref().unwrap_or("archived")
+                        )));
+                    }
+                    _ => {}
+                }
+
                 // Resolve the 1-based ordinal for this version (oldest = 1, newest = N).
                 // list_object_versions returns versions newest-first; the current version
                 // sits at some index i, so its ordinal is total - i.
@@ -322,17 +564,19 @@ impl Backend for S3Backend {
                     .context("Failed to read S3 object body")?
                     .into_bytes();
 
-                // Try to convert to text
-                match String::from_utf8(bytes.to_vec()) {
-                    Ok(content) => Ok(PreviewContent::Text(content, super::FileMetadata {
+                // Mostly-text content is shown, decoded lossily if need be
+                match super::decode_preview(bytes.to_vec(), false) {
+                    Some((content, encoding)) => Ok(PreviewContent::Text(content, super::FileMetadata {
                         size: Some(size),
                         modified: modified.clone(),
                         etag: etag.clone(),
                         storage_class: storage_class.clone(),
                         version_id: version_id.clone(),
                         version_number,
+                        truncated: false,
+                        encoding,
                     })),
-                    Err(_) => {
+                    None => {
                         Ok(PreviewContent::Binary { size, mime_type, modified, etag, storage_class, version_id, version_number })
                     }
                 }
@@ -400,6 +644,35 @@ impl Backend for S3Backend {
         Ok(())
     }
 
+    /// Upload a local file (WRITE operation, only used by `:upload` when
+    /// `allow_writes` is enabled). Uses a single PutObject request.
+    async fn upload_file(&self, source: &Path, path: &str) -> Result<()> {
+        // Objects above 5 GiB need a multipart upload
+        const MAX_PUT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+        let key = path.trim_start_matches('/');
+        let size = tokio::fs::metadata(source)
+            .await
+            .with_context(|| format!("Failed to read {}", source.display()))?
+            .len();
+        if size > MAX_PUT_SIZE {
+            anyhow::bail!("{} is larger than 5 GiB, which a single upload can't handle", source.display());
+        }
+
+        let body = ByteStream::from_path(source)
+            .await
+            .with_context(|| format!("Failed to open {}", source.display()))?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(body)
+            .send()
+            .await
+            .context("Failed to upload S3 object")?;
+        Ok(())
+    }
+
     fn location_name(&self) -> String {
         format!("s3://{}", self.bucket)
     }
@@ -419,6 +692,302 @@ impl Backend for S3Backend {
         }
     }
 
+    /// Flat (delimiter-less) listing of every object below the prefix
+    async fn walk(&self, prefix: &str, visit: &mut (dyn for<'p> FnMut(&'p str, &'p Entry) + Send)) -> Result<()> {
+        let prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", prefix.trim_end_matches('/'))
+        };
+
+        let mut response = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(&prefix)
+            .into_paginator()
+            .send();
+
+        while let Some(result) = response.next().await {
+            let output = result.context("Failed to list S3 objects")?;
+            for object in output.contents() {
+                let key = object.key().unwrap_or("");
+                // Skip directory markers
+                if key.ends_with('/') {
+                    continue;
+                }
+
+                let entry = Entry {
+                    name: key.rsplit('/').next().unwrap_or(key).to_string(),
+                    is_dir: false,
+                    size: object.size().map(|s| s as u64),
+                    // Shown when the flat view loads all keys
+                    modified: object.last_modified().and_then(|t| {
+                        chrono::DateTime::from_timestamp(t.secs(), 0).map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                    }),
+                    storage_class: object.storage_class().map(|sc| sc.as_str().to_string()),
+                    restore_state: None,
+                    etag: object.e_tag().map(|e| e.trim_matches('"').to_string()),
+                    permissions: None,
+                };
+                visit(key, &entry);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetches only the page: S3 lists keys in order and can start after any key
+    async fn walk_page(&self, prefix: &str, after: Option<&str>, max: usize) -> Result<WalkPage> {
+        let prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", prefix.trim_end_matches('/'))
+        };
+
+        let mut files = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            // At most 1000 keys come back per request
+            let mut request = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&prefix)
+                .max_keys((max - files.len()).min(1000) as i32);
+            request = match (&continuation, after) {
+                (Some(token), _) => request.continuation_token(token),
+                (None, Some(after)) => request.start_after(after),
+                (None, None) => request,
+            };
+            let output = request.send().await.context("Failed to list S3 objects")?;
+
+            for object in output.contents() {
+                let key = object.key().unwrap_or("");
+                // Skip directory markers
+                if key.ends_with('/') {
+                    continue;
+                }
+                let entry = Entry {
+                    name: key.rsplit('/').next().unwrap_or(key).to_string(),
+                    is_dir: false,
+                    size: object.size().map(|s| s as u64),
+                    modified: object.last_modified().and_then(|t| {
+                        chrono::DateTime::from_timestamp(t.secs(), 0).map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                    }),
+                    storage_class: object.storage_class().map(|sc| sc.as_str().to_string()),
+                    restore_state: None,
+                    etag: object.e_tag().map(|e| e.trim_matches('"').to_string()),
+                    permissions: None,
+                };
+                files.push((key.to_string(), entry));
+            }
+
+            continuation = output.next_continuation_token().map(str::to_string);
+            let more = output.is_truncated().unwrap_or(false) && continuation.is_some();
+            if !more || files.len() >= max {
+                return Ok(WalkPage { files, more });
+            }
+        }
+    }
+
+    /// Run an S3 Select query; only the matching records are transferred
+    async fn select_object(&self, path: &str, query: &str, max_size: usize) -> Result<PreviewContent> {
+        let key = path.trim_start_matches('/');
+        let (format, compression) = Self::select_format(key)
+            .context("S3 Select only supports CSV, TSV, JSON, JSON lines and Parquet objects")?;
+        let (input, output) = Self::select_serialization(format, compression);
+
+        let mut response = self
+            .client
+            .select_object_content()
+            .bucket(&self.bucket)
+            .key(key)
+            .expression(query)
+            .expression_type(ExpressionType::Sql)
+            .input_serialization(input)
+            .output_serialization(output)
+            .send()
+            .await
+            .context("S3 Select query failed")?;
+
+        // Results arrive as a stream of record chunks; stop once the preview limit is reached
+        let mut data = Vec::new();
+        while let Some(event) = response
+            .payload
+            .recv()
+            .await
+            .context("Failed to read S3 Select results")?
+        {
+            if let SelectObjectContentEventStream::Records(records) = event {
+                if let Some(payload) = records.payload() {
+                    data.extend_from_slice(payload.as_ref());
+                }
+                if data.len() >= max_size {
+                    break;
+                }
+            }
+        }
+
+        // Keep whole records when truncating
+        if data.len() > max_size {
+            let end = data[..max_size].iter().rposition(|&b| b == b'\n').map_or(max_size, |i| i + 1);
+            data.truncate(end);
+        }
+
+        let metadata = FileMetadata {
+            size: Some(data.len() as u64),
+            ..Default::default()
+        };
+        Ok(PreviewContent::Text(String::from_utf8_lossy(&data).into_owned(), metadata))
+    }
+
+    async fn list_versions(&self, path: &str) -> Result<Vec<ObjectVersion>> {
+        let key = path.trim_start_matches('/');
+
+        let output = self
+            .client
+            .list_object_versions()
+            .bucket(&self.bucket)
+            .prefix(key)
+            .send()
+            .await
+            .context("Failed to list object versions")?;
+
+        // The prefix also matches longer keys; versions come back newest first
+        Ok(output
+            .versions()
+            .iter()
+            .filter(|v| v.key() == Some(key))
+            .filter_map(|v| {
+                Some(ObjectVersion {
+                    version_id: v.version_id()?.to_string(),
+                    modified: v.last_modified().and_then(|t| {
+                        chrono::DateTime::from_timestamp(t.secs(), 0)
+                            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                    }),
+                    is_latest: v.is_latest().unwrap_or(false),
+                })
+            })
+            .collect())
+    }
+
+    async fn read_version(&self, path: &str, version_id: &str, max_size: usize) -> Result<String> {
+        let key = path.trim_start_matches('/');
+
+        let response = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .version_id(version_id)
+            .range(format!("bytes=0-{}", max_size.saturating_sub(1)))
+            .send()
+            .await
+            .context("Failed to get object version")?;
+
+        let bytes = response
+            .body
+            .collect()
+            .await
+            .context("Failed to read object version")?
+            .into_bytes();
+
+        String::from_utf8(bytes.to_vec()).context("Object version is not a text file")
+    }
+
+    /// Issue a RestoreObject request (Standard retrieval tier)
+    async fn restore_object(&self, path: &str, days: i32) -> Result<()> {
+        let key = path.trim_start_matches('/');
+
+        let job_parameters = GlacierJobParameters::builder()
+            .tier(Tier::Standard)
+            .build()
+            .context("Failed to build restore parameters")?;
+        let request = RestoreRequest::builder()
+            .days(days)
+            .glacier_job_parameters(job_parameters)
+            .build();
+
+        self.client
+            .restore_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .restore_request(request)
+            .send()
+            .await
+            .context("Failed to request object restore")?;
+
+        Ok(())
+    }
+
+    async fn restore_state(&self, path: &str) -> Result<Option<RestoreState>> {
+        let key = path.trim_start_matches('/');
+
+        let head = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .context("Failed to check restore status")?;
+
+        let storage_class = head.storage_class().map(|sc| sc.as_str().to_string());
+        Ok(Self::restore_state_from_header(storage_class.as_deref(), head.restore()))
+    }
+
+    async fn properties(&self, path: &str) -> Result<Vec<(String, String)>> {
+        let key = path.trim_start_matches('/');
+
+        let head = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .context("Failed to get object details")?;
+
+        let timestamp = |t: &aws_sdk_s3::primitives::DateTime| {
+            chrono::DateTime::from_timestamp(t.secs(), 0).map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        };
+        let mut rows: Vec<(&str, Option<String>)> = vec![
+            ("Size", head.content_length().map(|len| format!("{} bytes", len))),
+            ("Last modified", head.last_modified().and_then(timestamp)),
+            ("ETag", head.e_tag().map(|e| e.trim_matches('"').to_string())),
+            ("Version ID", head.version_id().map(str::to_string)),
+            ("Content type", head.content_type().map(str::to_string)),
+            ("Content encoding", head.content_encoding().map(str::to_string)),
+            ("Content disposition", head.content_disposition().map(str::to_string)),
+            ("Cache control", head.cache_control().map(str::to_string)),
+            ("Storage class", Some(head.storage_class().map_or("STANDARD", |sc| sc.as_str()).to_string())),
+            ("Restore", head.restore().map(str::to_string)),
+            ("Encryption", head.server_side_encryption().map(|sse| sse.as_str().to_string())),
+            ("KMS key", head.ssekms_key_id().map(str::to_string)),
+            ("Bucket key", head.bucket_key_enabled().map(|enabled| enabled.to_string())),
+            ("Replication", head.replication_status().map(|status| status.as_str().to_string())),
+            ("Object lock", head.object_lock_mode().map(|mode| mode.as_str().to_string())),
+            ("Retain until", head.object_lock_retain_until_date().and_then(timestamp)),
+            ("Legal hold", head.object_lock_legal_hold_status().map(|status| status.as_str().to_string())),
+        ];
+
+        // The owner is only in the ACL, which the caller may not be allowed to read
+        if let Ok(acl) = self.client.get_object_acl().bucket(&self.bucket).key(key).send().await {
+            let owner = acl.owner().and_then(|owner| owner.display_name().or(owner.id()));
+            rows.push(("Owner", owner.map(str::to_string)));
+        }
+
+        let mut properties: Vec<(String, String)> = rows
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value?)))
+            .collect();
+        let mut metadata: Vec<_> = head.metadata().into_iter().flatten().collect();
+        metadata.sort();
+        properties.extend(metadata.into_iter().map(|(name, value)| (format!("x-amz-meta-{}", name), value.clone())));
+        Ok(properties)
+    }
+
     fn get_parent(&self, prefix: &str) -> Option<String> {
         let prefix = prefix.trim_end_matches('/');
         if prefix.is_empty() {
@@ -432,4 +1001,82 @@ impl Backend for S3Backend {
             Some(parts[..parts.len() - 1].join("/"))
         }
     }
+
+    fn profile(&self) -> Option<String> {
+        self.profile.clone()
+    }
+
+    fn region(&self) -> Option<String> {
+        self.region.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_state_from_header() {
+        assert_eq!(S3Backend::restore_state_from_header(Some("STANDARD"), None), None);
+        assert_eq!(S3Backend::restore_state_from_header(None, None), None);
+        assert_eq!(
+            S3Backend::restore_state_from_header(Some("GLACIER"), None),
+            Some(RestoreState::Archived)
+        );
+        assert_eq!(
+            S3Backend::restore_state_from_header(Some("DEEP_ARCHIVE"), Some(r#"ongoing-request="true""#)),
+            Some(RestoreState::InProgress)
+        );
+        assert_eq!(
+            S3Backend::restore_state_from_header(
+                Some("GLACIER"),
+                Some(r#"ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT""#)
+            ),
+            Some(RestoreState::Restored { expiry: Some("Fri, 21 Dec 2012 00:00:00 GMT".to_string()) })
+        );
+    }
+
+    #[test]
+    fn test_from_uri() {
+        let split = |uri: &str| S3Backend::from_uri(uri).unwrap();
+        assert_eq!(split("s3://bucket/data/2024"), ("bucket".to_string(), "data/2024".to_string()));
+        assert_eq!(split("s3://bucket"), ("bucket".to_string(), String::new()));
+
+        let access_point = "arn:aws:s3:us-west-2:123456789012:accesspoint/reports";
+        assert_eq!(split(&format!("s3://{}/2024/01", access_point)), (access_point.to_string(), "2024/01".to_string()));
+        assert_eq!(split(&format!("s3://{}", access_point)), (access_point.to_string(), String::new()));
+        let outpost = "arn:aws:s3-outposts:us-west-2:123456789012:outpost/op-01ac5d28a6a232904/accesspoint/logs";
+        assert_eq!(split(&format!("s3://{}/x", outpost)), (outpost.to_string(), "x".to_string()));
+        // Multi-Region Access Points have no region
+        let mrap = "arn:aws:s3::123456789012:accesspoint/mfzwi23gnjvgw.mrap";
+        assert_eq!(split(&format!("s3://{}/", mrap)), (mrap.to_string(), String::new()));
+        assert_eq!(split("s3://arn:aws:s3:::my-bucket/logs"), ("my-bucket".to_string(), "logs".to_string()));
+
+        assert!(S3Backend::from_uri("s3://arn:aws:s3:us-west-2:123456789012:accesspoint").is_err());
+        assert!(S3Backend::from_uri("s3://arn:aws:iam::123456789012:role/x").is_err());
+        assert!(S3Backend::from_uri("s3://arn:aws:s3").is_err());
+    }
+
+    #[test]
+    fn test_select_format() {
+        assert_eq!(
+            S3Backend::select_format("data/users.csv"),
+            Some((SelectFormat::Csv, CompressionType::None))
+        );
+        assert_eq!(
+            S3Backend::select_format("logs/events.JSONL.gz"),
+            Some((SelectFormat::JsonLines, CompressionType::Gzip))
+        );
+        assert_eq!(
+            S3Backend::select_format("dump.json.bz2"),
+            Some((SelectFormat::JsonDocument, CompressionType::Bzip2))
+        );
+        assert_eq!(
+            S3Backend::select_format("table.parquet"),
+            Some((SelectFormat::Parquet, CompressionType::None))
+        );
+        assert_eq!(S3Backend::select_format("table.parquet.gz"), None);
+        assert_eq!(S3Backend::select_format("README"), None);
+        assert_eq!(S3Backend::select_format("notes.txt"), None);
+    }
 }
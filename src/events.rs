@@ -1,20 +1,12 @@
 use crate::config::KeyBindings;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use std::time::Duration;
-
-/// Read next event with timeout
-pub fn read_event(timeout: Duration) -> anyhow::Result<Option<Event>> {
-    if event::poll(timeout)? {
-        Ok(Some(event::read()?))
-    } else {
-        Ok(None)
-    }
-}
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 
 /// Handle key event and return action
 #[derive(Debug, Clone, PartialEq)]
 pub enum Action {
     Quit,
+    /// Hand the terminal back to the shell until the process is resumed
+    Suspend,
     MoveUp,
     MoveDown,
     JumpUp(usize),
@@ -33,6 +25,8 @@ pub enum Action {
     EnterDownloadMode,
     ExitDownloadMode,
     ConfirmDownload,
+    /// A digit of the number of a download destination
+    DownloadDestinationDigit(char),
     EnterHistoryMode,
     EnterHistoryModeWithSearch,
     ExitHistoryMode,
@@ -46,17 +40,355 @@ pub enum Action {
     YankSelection,
     IncreasePreviewWidth,
     DecreasePreviewWidth,
+    GrowStatusPane,
+    ShrinkStatusPane,
+    GrowProgressPane,
+    ShrinkProgressPane,
+    ScrollColumnsLeft,
+    ScrollColumnsRight,
+    /// Expand or collapse the JSON Lines record under the preview cursor
+    TogglePreviewFold,
+    /// Decode the preview with the next encoding
+    CycleEncoding,
+    /// Preview the next file of the listing, keeping the preview focused
+    PreviewNextFile,
+    /// Preview the previous file of the listing, keeping the preview focused
+    PreviewPreviousFile,
     ToggleHelp,
+    HelpScrollUp(usize),
+    HelpScrollDown(usize),
+    ToggleZoom,
+    /// Hide or show the search bar and status pane
+    ToggleZenMode,
+    CycleLayout,
+    ToggleProgressPane,
+    RestoreObject,
+    EnterCommandMode,
+    ExitCommandMode,
+    CommandChar(char),
+    CommandBackspace,
+    ExecuteCommand,
+    ToggleDiffLayout,
+    CloseDiff,
+    CloseSummary,
+    SyncScrollUp(usize),
+    SyncScrollDown(usize),
+    ConfirmSync,
+    CancelSync,
+    ConfirmContext,
+    ExitContextPicker,
+    NavigateBack,
+    NavigateForward,
+    /// List the current prefix again, bypassing the listing cache
+    Refresh,
+    /// Leave search mode keeping the query as the listing's filter
+    AcceptFilter,
+    /// Open the file of the selected `:grep` match
+    ConfirmGrep,
+    CloseGrep,
+    /// Clear the filter kept in normal mode
+    ClearFilter,
+    /// Apply a filter preset, or clear it if it's the active filter
+    ToggleFilterPreset(String),
     EnterPreviewSearch,
     ExitPreviewSearch,
     PreviewSearchNext,
     PreviewSearchPrev,
     ConfirmPreviewSearch,
+    /// Stop loading the preview of the selected file
+    CancelPreview,
     CancelDownloads,
+    /// Open the properties popup of the selected file
+    ShowProperties,
+    CloseProperties,
+    /// Close the `:log` overlay
+    CloseAuditLog,
+    /// Download the selected record of the audit log again, to the same place
+    RerunDownload,
+    /// Run the operation the confirmation popup asks about
+    AcceptConfirmation,
+    /// Drop the operation the confirmation popup asks about
+    RejectConfirmation,
+    /// Copy the value of the selected property
+    YankProperty,
+    /// Copy all properties as `name: value` lines
+    YankAllProperties,
+    /// Run the plugin of this name on the selected entries
+    RunPlugin(String),
+    PluginOutputScrollUp(usize),
+    PluginOutputScrollDown(usize),
+    /// Copy everything the plugin printed
+    YankPluginOutput,
+    ClosePluginOutput,
+    /// Group files by extension, or stop grouping
+    ToggleGroupByExtension,
+    /// Collapse or expand the extension group under the cursor
+    ToggleGroup,
+    /// Show all entries, only directories or only files
+    CycleKindFilter,
+    /// Switch between the directory listing and all files below the prefix
+    ToggleFlatView,
+    /// Show the next page of the flat view
+    NextPage,
+    /// Show the previous page of the flat view
+    PreviousPage,
+    /// Fetch every key of the flat view in the background
+    LoadAll,
     PendingKey(char),
     None,
 }
 
+/// Handle a key while the help popup is open: scroll keys move the cheatsheet,
+/// anything else closes it
+pub fn handle_help_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::HelpScrollUp(1);
+    }
+    if bindings.is_move_down(&key) {
+        return Action::HelpScrollDown(1);
+    }
+    if bindings.is_jump_up(&key) {
+        return Action::HelpScrollUp(10);
+    }
+    if bindings.is_jump_down(&key) {
+        return Action::HelpScrollDown(10);
+    }
+    Action::ToggleHelp
+}
+
+/// Handle a key while typing a `:` command
+pub fn handle_command_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    match key.code {
+        KeyCode::Esc => Action::ExitCommandMode,
+        KeyCode::Enter => Action::ExecuteCommand,
+        KeyCode::Backspace => Action::CommandBackspace,
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => Action::CommandChar(c),
+        _ => Action::None,
+    }
+}
+
+/// Handle a key while the summary overlay is open
+pub fn handle_summary_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Action::CloseSummary,
+        _ => Action::None,
+    }
+}
+
+/// Handle a key while a sync plan is shown for confirmation
+pub fn handle_sync_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::SyncScrollUp(1);
+    }
+    if bindings.is_move_down(&key) {
+        return Action::SyncScrollDown(1);
+    }
+    if bindings.is_jump_up(&key) {
+        return Action::SyncScrollUp(10);
+    }
+    if bindings.is_jump_down(&key) {
+        return Action::SyncScrollDown(10);
+    }
+    match key.code {
+        KeyCode::Enter | KeyCode::Char('y') => Action::ConfirmSync,
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('n') => Action::CancelSync,
+        _ => Action::None,
+    }
+}
+
+/// Handle a key while the popup with a plugin's output is open
+pub fn handle_plugin_output_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::PluginOutputScrollUp(1);
+    }
+    if bindings.is_move_down(&key) {
+        return Action::PluginOutputScrollDown(1);
+    }
+    if bindings.is_jump_up(&key) {
+        return Action::PluginOutputScrollUp(10);
+    }
+    if bindings.is_jump_down(&key) {
+        return Action::PluginOutputScrollDown(10);
+    }
+    if bindings.is_yank_selection(&key) {
+        return Action::YankPluginOutput;
+    }
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Action::ClosePluginOutput,
+        _ => Action::None,
+    }
+}
+
+/// Handle a key while the context picker is open
+pub fn handle_context_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::MoveUp;
+    }
+    if bindings.is_move_down(&key) {
+        return Action::MoveDown;
+    }
+    match key.code {
+        KeyCode::Enter => Action::ConfirmContext,
+        KeyCode::Esc | KeyCode::Char('q') => Action::ExitContextPicker,
+        _ => Action::None,
+    }
+}
+
+/// Handle keys while browsing `:grep` results
+pub fn handle_grep_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::MoveUp;
+    }
+    if bindings.is_move_down(&key) {
+        return Action::MoveDown;
+    }
+    match key.code {
+        KeyCode::Enter => Action::ConfirmGrep,
+        KeyCode::Esc | KeyCode::Char('q') => Action::CloseGrep,
+        _ => Action::None,
+    }
+}
+
+/// Handle keys while a confirmation popup asks whether to run an operation
+pub fn handle_confirm_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    match key.code {
+        KeyCode::Enter | KeyCode::Char('y') => Action::AcceptConfirmation,
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('n') => Action::RejectConfirmation,
+        _ => Action::None,
+    }
+}
+
+/// Handle keys while the `:log` overlay is open
+pub fn handle_audit_log_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::MoveUp;
+    }
+    if bindings.is_move_down(&key) {
+        return Action::MoveDown;
+    }
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Action::CloseAuditLog,
+        KeyCode::Char('r') => Action::RerunDownload,
+        _ => Action::None,
+    }
+}
+
+/// Handle keys while the properties popup is open
+pub fn handle_properties_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::MoveUp;
+    }
+    if bindings.is_move_down(&key) {
+        return Action::MoveDown;
+    }
+    if bindings.is_yank_selection(&key) {
+        return Action::YankProperty;
+    }
+    if bindings.is_copy_path(&key) {
+        return Action::YankAllProperties;
+    }
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Action::CloseProperties,
+        _ if bindings.is_properties(&key) => Action::CloseProperties,
+        _ => Action::None,
+    }
+}
+
+/// Handle keys while a diff is shown in the preview pane
+pub fn handle_diff_key(key: KeyEvent, bindings: &KeyBindings) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if bindings.is_quit(&key) {
+        return Action::Quit;
+    }
+    if bindings.is_move_up(&key) {
+        return Action::MoveUp;
+    }
+    if bindings.is_move_down(&key) {
+        return Action::MoveDown;
+    }
+    if bindings.is_jump_up(&key) {
+        return Action::JumpUp(10);
+    }
+    if bindings.is_jump_down(&key) {
+        return Action::JumpDown(10);
+    }
+    if bindings.is_jump_to_bottom(&key) {
+        return Action::JumpToBottom;
+    }
+    if bindings.is_zoom_pane(&key) {
+        return Action::ToggleZoom;
+    }
+    match key.code {
+        KeyCode::Char('g') => Action::JumpToTop,
+        KeyCode::Char('s') => Action::ToggleDiffLayout,
+        KeyCode::Esc | KeyCode::Char('q') => Action::CloseDiff,
+        _ => Action::None,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, in_history_mode: bool, in_visual_mode: bool, in_download_mode: bool, preview_focused: bool, preview_visual_mode: bool, preview_search_mode: bool, pending_key: Option<char>) -> Action {
     // Only handle key press events, not release/repeat
     if key.kind != KeyEventKind::Press {
@@ -68,6 +400,26 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
         return Action::Quit;
     }
 
+    // Panes can be resized in every mode
+    if bindings.is_grow_preview(&key) {
+        return Action::IncreasePreviewWidth;
+    }
+    if bindings.is_shrink_preview(&key) {
+        return Action::DecreasePreviewWidth;
+    }
+    if bindings.is_grow_status_pane(&key) {
+        return Action::GrowStatusPane;
+    }
+    if bindings.is_shrink_status_pane(&key) {
+        return Action::ShrinkStatusPane;
+    }
+    if bindings.is_grow_progress_pane(&key) {
+        return Action::GrowProgressPane;
+    }
+    if bindings.is_shrink_progress_pane(&key) {
+        return Action::ShrinkProgressPane;
+    }
+
     // Handle multi-key sequences in normal mode
     if !in_search_mode && !in_history_mode {
         if let Some(pending) = pending_key {
@@ -83,6 +435,13 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
                     return Action::JumpToTop;
                 }
             }
+            let sequence_chars: Vec<char> = bindings.properties_sequence.chars().collect();
+            if sequence_chars.len() == 2
+                && pending == sequence_chars[0]
+                && matches!(key.code, KeyCode::Char(c) if c == sequence_chars[1])
+            {
+                return Action::ShowProperties;
+            }
             // Any other key after pending key - the sequence is broken
             // We'll continue processing this key normally below
         }
@@ -128,7 +487,13 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
             return Action::MoveUp;
         }
 
-        // Only Enter (and Right arrow) navigate in search mode, not l/L
+        // Enter keeps the filter and returns to normal mode
+        if bindings.is_accept_filter(&key) {
+            return Action::AcceptFilter;
+        }
+
+        // Only Enter (when not accepting the filter) and Right arrow navigate
+        // in search mode, not l/L
         if matches!(key.code, KeyCode::Enter | KeyCode::Right) {
             return Action::NavigateInto;
         }
@@ -202,7 +567,11 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
             return Action::ConfirmDownload;
         }
 
-        Action::None
+        // Digits pick a destination by its number
+        match key.code {
+            KeyCode::Char(c) if c.is_ascii_digit() => Action::DownloadDestinationDigit(c),
+            _ => Action::None,
+        }
     } else if in_visual_mode && !preview_focused {
         // Visual selection mode for file explorer
 
@@ -258,6 +627,11 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
             return Action::ToggleHelp;
         }
 
+        // Colon opens the command line (not while typing a preview search)
+        if matches!(key.code, KeyCode::Char(':')) && !preview_search_mode {
+            return Action::EnterCommandMode;
+        }
+
         // Forward slash enters search mode (only if preview not focused)
         if !preview_focused && matches!(key.code, KeyCode::Char('/')) {
             return Action::EnterSearchMode;
@@ -345,6 +719,27 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
                 return Action::DecreasePreviewWidth;
             }
 
+            // Folding of JSON Lines records
+            if !preview_visual_mode && bindings.is_toggle_fold(&key) {
+                return Action::TogglePreviewFold;
+            }
+
+            // Read through the files of the listing without leaving the preview
+            if !preview_visual_mode && bindings.is_next_file(&key) {
+                return Action::PreviewNextFile;
+            }
+            if !preview_visual_mode && bindings.is_previous_file(&key) {
+                return Action::PreviewPreviousFile;
+            }
+
+            // Horizontal scrolling of table previews
+            if bindings.is_scroll_columns_left(&key) {
+                return Action::ScrollColumnsLeft;
+            }
+            if bindings.is_scroll_columns_right(&key) {
+                return Action::ScrollColumnsRight;
+            }
+
             // Movement keys work in both normal and visual mode
             if bindings.is_move_up(&key) {
                 return Action::MoveUp;
@@ -388,6 +783,15 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
                 return Action::NavigateUp;
             }
         }
+        if bindings.is_navigate_back(&key) {
+            return Action::NavigateBack;
+        }
+        if bindings.is_navigate_forward(&key) {
+            return Action::NavigateForward;
+        }
+        if bindings.is_refresh(&key) {
+            return Action::Refresh;
+        }
         if bindings.is_download_mode(&key) {
             return Action::EnterDownloadMode;
         }
@@ -403,6 +807,9 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
         if bindings.is_wrap_text(&key) {
             return Action::ToggleWrap;
         }
+        if bindings.is_cycle_encoding(&key) {
+            return Action::CycleEncoding;
+        }
         if bindings.is_focus_preview(&key) {
             return Action::FocusPreview;
         }
@@ -412,13 +819,54 @@ pub fn handle_key(key: KeyEvent, bindings: &KeyBindings, in_search_mode: bool, i
         if bindings.is_toggle_focus(&key) {
             return Action::ToggleFocus;
         }
+        if bindings.is_zoom_pane(&key) {
+            return Action::ToggleZoom;
+        }
+        if bindings.is_zen_mode(&key) {
+            return Action::ToggleZenMode;
+        }
+        if bindings.is_cycle_layout(&key) {
+            return Action::CycleLayout;
+        }
+        if bindings.is_toggle_progress_pane(&key) {
+            return Action::ToggleProgressPane;
+        }
+        if bindings.is_restore_object(&key) && !preview_focused {
+            return Action::RestoreObject;
+        }
+        if bindings.is_properties(&key) {
+            return Action::ShowProperties;
+        }
+        if bindings.is_group_by_extension(&key) && !preview_focused {
+            return Action::ToggleGroupByExtension;
+        }
+        if bindings.is_toggle_group(&key) && !preview_focused {
+            return Action::ToggleGroup;
+        }
+        if bindings.is_cycle_kind_filter(&key) && !preview_focused {
+            return Action::CycleKindFilter;
+        }
+        if bindings.is_toggle_flat_view(&key) && !preview_focused {
+            return Action::ToggleFlatView;
+        }
+        if bindings.is_next_page(&key) && !preview_focused {
+            return Action::NextPage;
+        }
+        if bindings.is_previous_page(&key) && !preview_focused {
+            return Action::PreviousPage;
+        }
+        if bindings.is_load_all(&key) && !preview_focused {
+            return Action::LoadAll;
+        }
 
         // Check for start of multi-key sequences
-        let sequence_chars: Vec<char> = bindings.jump_to_top.chars().collect();
-        if sequence_chars.len() == 2 && pending_key.is_none() {
-            let first_char = sequence_chars[0];
-            if matches!(key.code, KeyCode::Char(c) if c == first_char) {
-                return Action::PendingKey(first_char);
+        for sequence in [&bindings.jump_to_top, &bindings.properties_sequence] {
+            let sequence_chars: Vec<char> = sequence.chars().collect();
+            if sequence_chars.len() == 2 && pending_key.is_none() {
+                let first_char = sequence_chars[0];
+                if matches!(key.code, KeyCode::Char(c) if c == first_char) {
+                    return Action::PendingKey(first_char);
+                }
             }
         }
 
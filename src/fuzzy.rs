@@ -2,26 +2,114 @@ use nucleo_matcher::{
     pattern::{CaseMatching, Normalization, Pattern},
     Config, Matcher, Utf32Str,
 };
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Extra score on top of nucleo's for matches in the last path component, so
+/// `main` ranks `src/main.rs` above `main/src/lib.rs`. nucleo scores 16 per
+/// matched char plus bonuses for word starts and consecutive chars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FuzzyWeights {
+    /// Added per matched char that falls in the basename
+    #[serde(default = "default_basename_bonus")]
+    pub basename_bonus: u32,
+
+    /// Added once when every matched char falls in the basename
+    #[serde(default = "default_basename_only_bonus")]
+    pub basename_only_bonus: u32,
+}
+
+fn default_basename_bonus() -> u32 {
+    16
+}
+
+fn default_basename_only_bonus() -> u32 {
+    32
+}
+
+impl Default for FuzzyWeights {
+    fn default() -> Self {
+        Self {
+            basename_bonus: default_basename_bonus(),
+            basename_only_bonus: default_basename_only_bonus(),
+        }
+    }
+}
+
+/// Char index where the basename of `path` starts (a trailing `/` of a
+/// directory doesn't count as a separator)
+fn basename_start(path: &str) -> u32 {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(pos) => trimmed[..=pos].chars().count() as u32,
+        None => 0,
+    }
+}
 
 /// Fuzzy matcher for filtering entries
 pub struct FuzzyMatcher {
     matcher: Matcher,
+    weights: FuzzyWeights,
 }
 
 impl FuzzyMatcher {
     pub fn new() -> Self {
         Self {
             matcher: Matcher::new(Config::DEFAULT),
+            weights: FuzzyWeights::default(),
         }
     }
 
+    /// Set the basename bonuses added to nucleo's scores
+    pub fn set_weights(&mut self, weights: FuzzyWeights) {
+        self.weights = weights;
+    }
+
+    /// nucleo's score plus the basename bonuses for the matched `positions`
+    fn score(&self, entry: &str, score: u32, positions: &[u32]) -> u32 {
+        let start = basename_start(entry);
+        let in_basename = positions.iter().filter(|&&p| p >= start).count() as u32;
+        let mut total = score.saturating_add(in_basename.saturating_mul(self.weights.basename_bonus));
+        if in_basename as usize == positions.len() {
+            total = total.saturating_add(self.weights.basename_only_bonus);
+        }
+        total
+    }
+
+    /// Weights of the basename bonuses
+    pub fn weights(&self) -> FuzzyWeights {
+        self.weights
+    }
+
     /// Match entries against a query.
-    /// Returns (entry_index, matched_char_positions) pairs sorted by score (best first).
-    /// Positions are char indices into the original entry string.
+    /// Returns (entry_index, matched_char_positions) pairs sorted by score (best first),
+    /// with matches in the basename of path-like entries ranked higher (see [`FuzzyWeights`]).
+    /// Positions are the sorted, unique char indices of the characters that
+    /// matched (e.g. `mnrs` gives the m, n, r and s of `main.rs`), ready for highlighting.
     /// When query is empty, positions are empty (no highlighting needed).
     pub fn match_entries(&mut self, entries: &[String], query: &str) -> Vec<(usize, Vec<u32>)> {
+        let mut results = Vec::new();
+        self.match_chunked(entries, query, entries.len().max(1), |matches, _| {
+            results = matches;
+            true
+        });
+        results
+    }
+
+    /// Like [`Self::match_entries`], `chunk_size` entries at a time: after each
+    /// chunk `progress` gets the ranked matches so far and whether all entries
+    /// have been matched. Returning false from `progress` stops the matching.
+    pub fn match_chunked(
+        &mut self,
+        entries: &[String],
+        query: &str,
+        chunk_size: usize,
+        mut progress: impl FnMut(Vec<(usize, Vec<u32>)>, bool) -> bool,
+    ) {
         if query.is_empty() {
-            return (0..entries.len()).map(|i| (i, vec![])).collect();
+            progress((0..entries.len()).map(|i| (i, vec![])).collect(), true);
+            return;
         }
 
         let pattern = Pattern::parse(
@@ -34,19 +122,77 @@ impl FuzzyMatcher {
         let mut buf = Vec::new();
         let mut indices = Vec::new();
 
-        for (idx, entry) in entries.iter().enumerate() {
-            let haystack = Utf32Str::new(entry, &mut buf);
-            indices.clear();
-            if let Some(score) = pattern.indices(haystack, &mut self.matcher, &mut indices) {
-                results.push((idx, score, indices.clone()));
+        let mut matched = 0;
+        loop {
+            let end = (matched + chunk_size.max(1)).min(entries.len());
+            for (idx, entry) in entries.iter().enumerate().take(end).skip(matched) {
+                let haystack = Utf32Str::new(entry, &mut buf);
+                indices.clear();
+                if let Some(score) = pattern.indices(haystack, &mut self.matcher, &mut indices) {
+                    // Each word of the query appends its own indices, unsorted and
+                    // possibly overlapping
+                    indices.sort_unstable();
+                    indices.dedup();
+                    results.push((idx, self.score(entry, score, &indices), indices.clone()));
+                }
+                buf.clear();
+            }
+            matched = end;
+
+            // Sort by score (higher is better); the sort is stable, so equal
+            // scores keep the listing order
+            results.sort_by_key(|r| std::cmp::Reverse(r.1));
+
+            let complete = matched == entries.len();
+            let ranked = results.iter().map(|(idx, _, positions)| (*idx, positions.clone())).collect();
+            if !progress(ranked, complete) || complete {
+                return;
             }
-            buf.clear();
         }
+    }
+}
+
+/// Entries matched by a [`FilterJob`] between two partial results
+const FILTER_CHUNK_SIZE: usize = 10_000;
+
+/// Filtering of a listing too large to match between two key presses, run on
+/// a worker thread so typing doesn't stall
+#[derive(Debug)]
+pub struct FilterJob {
+    generation: u64,
+    names: Arc<Vec<String>>,
+    query: String,
+    weights: FuzzyWeights,
+    canceled: Arc<AtomicBool>,
+}
+
+/// Matches found so far by a [`FilterJob`]
+#[derive(Debug)]
+pub struct FilterUpdate {
+    /// Filter generation the matches belong to; older ones are dropped
+    pub generation: u64,
+    /// Ranked (entry_index, matched_char_positions) pairs, as from [`FuzzyMatcher::match_entries`]
+    pub matches: Vec<(usize, Vec<u32>)>,
+    /// Whether all entries have been matched
+    pub complete: bool,
+}
 
-        // Sort by score (higher is better)
-        results.sort_by(|a, b| b.1.cmp(&a.1));
+impl FilterJob {
+    /// Match `names` against `query`. Setting `canceled` stops the job at the
+    /// next chunk.
+    pub fn new(generation: u64, names: Arc<Vec<String>>, query: String, weights: FuzzyWeights, canceled: Arc<AtomicBool>) -> Self {
+        Self { generation, names, query, weights, canceled }
+    }
 
-        results.into_iter().map(|(idx, _, positions)| (idx, positions)).collect()
+    /// Run the match, passing each partial result to `send` until it returns
+    /// false or the job is canceled
+    pub fn run(self, mut send: impl FnMut(FilterUpdate) -> bool) {
+        let mut matcher = FuzzyMatcher::new();
+        matcher.set_weights(self.weights);
+        matcher.match_chunked(&self.names, &self.query, FILTER_CHUNK_SIZE, |matches, complete| {
+            !self.canceled.load(Ordering::Relaxed)
+                && send(FilterUpdate { generation: self.generation, matches, complete })
+        });
     }
 }
 
@@ -103,4 +249,87 @@ mod tests {
         // Positions should be non-empty for a match
         assert!(!results[0].1.is_empty());
     }
+
+    #[test]
+    fn test_positions_are_the_matched_chars() {
+        let mut matcher = FuzzyMatcher::new();
+        let entries = vec!["main.rs".to_string()];
+        let results = matcher.match_entries(&entries, "mnrs");
+        assert_eq!(results[0].1, vec![0, 3, 5, 6]);
+
+        // Multi-word queries: positions of all words, sorted and without duplicates
+        let results = matcher.match_entries(&entries, "rs main ma");
+        assert_eq!(results[0].1, vec![0, 1, 2, 3, 5, 6]);
+    }
+
+    #[test]
+    fn test_basename_matches_rank_first() {
+        let mut matcher = FuzzyMatcher::new();
+        let entries = vec![
+            "s3://bucket/main/src/lib.rs".to_string(),
+            "s3://bucket/src/main.rs".to_string(),
+            "s3://bucket/main/".to_string(),
+        ];
+        let results = matcher.match_entries(&entries, "main");
+        assert_eq!(indices(&results), vec![1, 2, 0]);
+
+        // Without the bonuses nucleo scores all three the same
+        matcher.set_weights(FuzzyWeights { basename_bonus: 0, basename_only_bonus: 0 });
+        let results = matcher.match_entries(&entries, "main");
+        assert_eq!(indices(&results), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_basename_start() {
+        assert_eq!(basename_start("main.rs"), 0);
+        assert_eq!(basename_start("src/main.rs"), 4);
+        assert_eq!(basename_start("s3://b/dir/"), 7);
+        assert_eq!(basename_start("dir/"), 0);
+    }
+
+    #[test]
+    fn test_match_chunked_reports_partial_results() {
+        let mut matcher = FuzzyMatcher::new();
+        let entries: Vec<String> = (0..10).map(|i| format!("file{}.txt", i)).collect();
+        let mut updates = Vec::new();
+        matcher.match_chunked(&entries, "file", 4, |matches, complete| {
+            updates.push((matches.len(), complete));
+            true
+        });
+        assert_eq!(updates, vec![(4, false), (8, false), (10, true)]);
+        let full = matcher.match_entries(&entries, "file");
+        assert_eq!(indices(&full), (0..10).collect::<Vec<_>>());
+
+        // Stops as soon as progress says so
+        let mut calls = 0;
+        matcher.match_chunked(&entries, "file", 4, |_, _| {
+            calls += 1;
+            false
+        });
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_filter_job() {
+        let names = Arc::new(vec!["alpha".to_string(), "beta".to_string(), "gamma".to_string()]);
+        let canceled = Arc::new(AtomicBool::new(false));
+        let mut updates = Vec::new();
+        FilterJob::new(7, names.clone(), "a".to_string(), FuzzyWeights::default(), canceled.clone()).run(|update| {
+            updates.push(update);
+            true
+        });
+        let last = updates.last().unwrap();
+        assert_eq!(last.generation, 7);
+        assert!(last.complete);
+        assert_eq!(last.matches.len(), 3);
+
+        // A canceled job reports nothing
+        canceled.store(true, Ordering::Relaxed);
+        let mut sent = false;
+        FilterJob::new(8, names, "a".to_string(), FuzzyWeights::default(), canceled).run(|_| {
+            sent = true;
+            true
+        });
+        assert!(!sent);
+    }
 }
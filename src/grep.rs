@@ -0,0 +1,64 @@
+/// Results of a `:grep` are capped so a common word in a large prefix doesn't
+/// fill memory; the search stops once this many lines matched
+pub const MAX_MATCHES: usize = 1000;
+
+/// A line containing the `:grep` pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    /// Backend path of the file
+    pub path: String,
+    /// Line number, starting at 0 like the preview cursor
+    pub line: usize,
+    /// The matching line, trimmed
+    pub text: String,
+}
+
+/// Lines of `text` containing `pattern`; case-insensitive unless the pattern
+/// has uppercase letters (smart case, like the fuzzy filter)
+pub fn grep_text(path: &str, text: &str, pattern: &str) -> Vec<GrepMatch> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    let ignore_case = !pattern.chars().any(char::is_uppercase);
+    let pattern = if ignore_case { pattern.to_lowercase() } else { pattern.to_string() };
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            if ignore_case {
+                line.to_lowercase().contains(&pattern)
+            } else {
+                line.contains(&pattern)
+            }
+        })
+        .map(|(line, text)| GrepMatch { path: path.to_string(), line, text: text.trim().to_string() })
+        .collect()
+}
+
+/// Split a backend path into its parent prefix and file name
+pub fn split_path(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grep_text_smart_case() {
+        let text = "INFO start\n  error: disk full\nERROR: retry\n";
+        let lines = |pattern| grep_text("logs/app.log", text, pattern).into_iter().map(|m| m.line).collect::<Vec<_>>();
+        assert_eq!(lines("error"), vec![1, 2]);
+        assert_eq!(lines("ERROR"), vec![2]);
+        assert!(lines("").is_empty());
+
+        let found = &grep_text("logs/app.log", text, "disk")[0];
+        assert_eq!(found.text, "error: disk full");
+        assert_eq!(found.path, "logs/app.log");
+    }
+
+    #[test]
+    fn test_split_path() {
+        assert_eq!(split_path("logs/2024/app.log"), ("logs/2024", "app.log"));
+        assert_eq!(split_path("README.md"), ("", "README.md"));
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// What `:import-history` found
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportedHistory {
+    /// S3 locations, most recently used first
+    pub locations: Vec<String>,
+    /// How many of the sources could be read
+    pub sources_read: usize,
+}
+
+/// Files `:import-history` reads when given none: the AWS CLI's command
+/// history (kept with `cli_history = enabled`) and the usual shell histories
+pub fn default_sources() -> Vec<PathBuf> {
+    sources_for(std::env::var_os("HISTFILE").map(PathBuf::from), dirs::home_dir())
+}
+
+/// [`default_sources`] for a given `$HISTFILE` and home directory, each file
+/// once (`$HISTFILE` usually is one of the defaults)
+fn sources_for(histfile: Option<PathBuf>, home: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = histfile.into_iter().collect();
+    if let Some(home) = home {
+        candidates.push(home.join(".aws/cli/history/history.db"));
+        candidates.push(home.join(".bash_history"));
+        candidates.push(home.join(".zsh_history"));
+        candidates.push(home.join(".local/share/fish/fish_history"));
+    }
+    let mut sources = Vec::new();
+    for path in candidates {
+        if !sources.contains(&path) {
+            sources.push(path);
+        }
+    }
+    sources
+}
+
+/// Collect the S3 locations mentioned in `sources`, skipping files that are
+/// missing or can't be read. Later files count as more recent.
+pub fn import(sources: &[PathBuf]) -> ImportedHistory {
+    let mut mentions = Vec::new();
+    let mut sources_read = 0;
+    for source in sources {
+        if let Ok(bytes) = fs::read(source) {
+            sources_read += 1;
+            mentions.extend(s3_locations(&bytes));
+        }
+    }
+
+    // Most recent first, each location once
+    let mut locations: Vec<String> = Vec::new();
+    for location in mentions.into_iter().rev() {
+        if !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+    ImportedHistory { locations, sources_read }
+}
+
+/// The `s3://` URIs in `bytes`, in order of appearance, as the locations to
+/// browse: object keys (`s3://bucket/logs/app.log`) give their folder, and
+/// trailing slashes are dropped. The bytes can be anything containing text,
+/// like a shell history or the AWS CLI's SQLite database.
+fn s3_locations(bytes: &[u8]) -> Vec<String> {
+    const SCHEME: &[u8] = b"s3://";
+    let mut locations = Vec::new();
+    let mut rest = bytes;
+    while let Some(start) = rest.windows(SCHEME.len()).position(|window| window == SCHEME) {
+        let after = &rest[start + SCHEME.len()..];
+        let len = after.iter().position(|&b| !is_uri_byte(b)).unwrap_or(after.len());
+        if let Some(location) = std::str::from_utf8(&after[..len]).ok().and_then(location_of) {
+            locations.push(location);
+        }
+        rest = &after[len..];
+    }
+    locations
+}
+
+/// Bytes that can be part of a bucket name or key as typed on a command line
+fn is_uri_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'/' | b'.' | b'-' | b'_' | b'=' | b'+' | b'@' | b'~' | b'%')
+}
+
+/// `s3://bucket/prefix` for the part of a URI after the scheme, or None when
+/// it doesn't start with a valid bucket name (say `$BUCKET`, cut off at `$`)
+fn location_of(path: &str) -> Option<String> {
+    let path = path.trim_end_matches('/');
+    let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+    let valid_bucket = (3..=63).contains(&bucket.len())
+        && bucket.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-');
+    if !valid_bucket {
+        return None;
+    }
+
+    // A last segment with an extension names an object
+    let folder = match key.rsplit_once('/') {
+        Some((folder, name)) if name.contains('.') => folder,
+        None if key.contains('.') => "",
+        _ => key,
+    };
+    if folder.is_empty() {
+        Some(format!("s3://{}", bucket))
+    } else {
+        Some(format!("s3://{}/{}", bucket, folder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_s3_locations() {
+        let zsh = b": 1700000000:0;aws s3 ls s3://data-lake/raw/2024/\n\
+            : 1700000100:0;aws s3 cp s3://data-lake/raw/2024/06/part-0.parquet .\n\
+            aws s3 sync ./out \"s3://my.bucket/exports\" --delete\n\
+            aws s3 ls s3://$BUCKET/x s3://ab/too-short\n";
+        assert_eq!(
+            s3_locations(zsh),
+            vec!["s3://data-lake/raw/2024", "s3://data-lake/raw/2024/06", "s3://my.bucket/exports"]
+        );
+
+        // Text between binary, as in the CLI's SQLite database
+        let db = b"\x00\x13\x81{\"argv\":[\"s3\",\"ls\",\"s3://team-bucket\"]}\x00\xff";
+        assert_eq!(s3_locations(db), vec!["s3://team-bucket"]);
+        assert_eq!(s3_locations(b"s3://team-bucket/report.csv"), vec!["s3://team-bucket"]);
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let bash = dir.path().join("bash_history");
+        let fish = dir.path().join("fish_history");
+        fs::write(&bash, "aws s3 ls s3://old-bucket/logs\naws s3 ls s3://shared-bucket/in\n").unwrap();
+        fs::write(&fish, "- cmd: aws s3 ls s3://shared-bucket/in/\n  when: 1700000000\n").unwrap();
+
+        let imported = import(&[bash, dir.path().join("missing"), fish]);
+        assert_eq!(imported.sources_read, 2);
+        assert_eq!(imported.locations, vec!["s3://shared-bucket/in", "s3://old-bucket/logs"]);
+    }
+
+    #[test]
+    fn test_sources_histfile_is_a_default() {
+        let home = PathBuf::from("/home/me");
+        let sources = sources_for(Some(home.join(".zsh_history")), Some(home.clone()));
+        assert_eq!(sources.iter().filter(|p| **p == home.join(".zsh_history")).count(), 1);
+        assert_eq!(sources.len(), 4);
+        assert_eq!(sources[0], home.join(".zsh_history"));
+
+        let sources = sources_for(Some(PathBuf::from("/tmp/hist")), Some(home));
+        assert_eq!(sources.len(), 5);
+    }
+}
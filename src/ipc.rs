@@ -0,0 +1,236 @@
+use crate::app::AppEvent;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+/// A command a script or editor sends over the control socket (`--listen`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    /// Open a location: a URI, a local path, or a prefix of the current backend
+    Navigate(String),
+    /// Download the selected files (the file under the cursor when nothing is
+    /// selected) to a configured destination, by name, or a directory; the
+    /// first destination for the bucket when None
+    Download(Option<String>),
+    /// URIs of the selected entries, or of the one under the cursor
+    GetSelection,
+    /// URI of the current location
+    GetLocation,
+    Quit,
+}
+
+impl ControlCommand {
+    /// Parse the words of a command, as in `["navigate", "s3://bucket/logs"]`
+    pub fn parse(words: &[String]) -> Result<Self, String> {
+        let (name, args) = words.split_first().ok_or("Empty command")?;
+        let command = match (name.as_str(), args) {
+            ("navigate", [target]) => Self::Navigate(target.clone()),
+            ("download", []) => Self::Download(None),
+            ("download", [destination]) => Self::Download(Some(destination.clone())),
+            ("get-selection", []) => Self::GetSelection,
+            ("get-location", []) => Self::GetLocation,
+            ("quit", []) => Self::Quit,
+            ("navigate" | "download" | "get-selection" | "get-location" | "quit", _) => {
+                return Err(format!("Wrong number of arguments for {}", name));
+            }
+            _ => return Err(format!("Unknown command: {}", name)),
+        };
+        Ok(command)
+    }
+}
+
+/// What a command answers: its data, or why it failed
+pub type ControlReply = Result<Value, String>;
+
+/// Where the answer to a command goes. Clones answer the same client, once.
+#[derive(Debug, Clone)]
+pub struct Responder(Arc<Mutex<Option<oneshot::Sender<ControlReply>>>>);
+
+impl Responder {
+    /// A responder and the receiving end its answer arrives at
+    pub fn new() -> (Self, oneshot::Receiver<ControlReply>) {
+        let (tx, rx) = oneshot::channel();
+        (Self(Arc::new(Mutex::new(Some(tx)))), rx)
+    }
+
+    /// Answer the client; later answers are dropped
+    pub fn send(&self, reply: ControlReply) {
+        if let Some(tx) = self.0.lock().unwrap().take() {
+            let _ = tx.send(reply);
+        }
+    }
+}
+
+impl PartialEq for Responder {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A request line: JSON like mpv's (`{"command": ["get-location"],
+/// "request_id": 1}`), or the words of the command separated by spaces
+#[derive(Deserialize)]
+struct JsonRequest {
+    command: Vec<String>,
+    #[serde(default)]
+    request_id: Option<Value>,
+}
+
+/// Handle one request line, passing the command to the app through `events`,
+/// and return the JSON response line
+async fn handle_line(line: &str, events: &mpsc::UnboundedSender<AppEvent>) -> String {
+    let (words, request_id) = if line.trim_start().starts_with('{') {
+        match serde_json::from_str::<JsonRequest>(line) {
+            Ok(request) => (request.command, request.request_id),
+            Err(e) => return response(Err(format!("Invalid request: {}", e)), None),
+        }
+    } else {
+        (line.split_whitespace().map(str::to_string).collect(), None)
+    };
+
+    let reply = match ControlCommand::parse(&words) {
+        Ok(command) => {
+            let (responder, answer) = Responder::new();
+            if events.send(AppEvent::Control { command, responder }).is_err() {
+                Err("rats3 is shutting down".to_string())
+            } else {
+                answer.await.unwrap_or_else(|_| Err("No answer".to_string()))
+            }
+        }
+        Err(e) => Err(e),
+    };
+    response(reply, request_id)
+}
+
+/// mpv-style response: `error` is "success" or the error message
+fn response(reply: ControlReply, request_id: Option<Value>) -> String {
+    let mut response = match reply {
+        Ok(Value::Null) => json!({ "error": "success" }),
+        Ok(data) => json!({ "error": "success", "data": data }),
+        Err(error) => json!({ "error": error }),
+    };
+    if let Some(request_id) = request_id {
+        response["request_id"] = request_id;
+    }
+    response.to_string()
+}
+
+#[cfg(unix)]
+pub use server::ControlServer;
+
+#[cfg(unix)]
+mod server {
+    use super::handle_line;
+    use crate::app::AppEvent;
+    use anyhow::{bail, Context, Result};
+    use std::path::{Path, PathBuf};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::mpsc;
+    use tokio::task::JoinHandle;
+
+    /// Unix socket server taking commands for a running instance, one per
+    /// line, each answered with a JSON line. Stops and removes the socket
+    /// when dropped.
+    pub struct ControlServer {
+        path: PathBuf,
+        task: JoinHandle<()>,
+    }
+
+    impl ControlServer {
+        /// Listen on the socket at `path`, passing commands to the app
+        /// through `events`. A socket left behind by an instance that is gone
+        /// is replaced; one still answering is an error.
+        pub fn bind(path: &Path, events: mpsc::UnboundedSender<AppEvent>) -> Result<Self> {
+            if path.exists() {
+                if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                    bail!("Another instance is listening on {}", path.display());
+                }
+                std::fs::remove_file(path).with_context(|| format!("Cannot replace {}", path.display()))?;
+            }
+            let listener = UnixListener::bind(path).with_context(|| format!("Cannot listen on {}", path.display()))?;
+            let task = tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, events.clone()));
+                }
+            });
+            Ok(Self { path: path.to_path_buf(), task })
+        }
+    }
+
+    impl Drop for ControlServer {
+        fn drop(&mut self) {
+            self.task.abort();
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// Answer the requests of one client until it disconnects
+    async fn serve(stream: UnixStream, events: mpsc::UnboundedSender<AppEvent>) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut response = handle_line(&line, &events).await;
+            response.push('\n');
+            if writer.write_all(response.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ControlCommand::parse(&words("navigate s3://bucket/logs")),
+            Ok(ControlCommand::Navigate("s3://bucket/logs".to_string()))
+        );
+        assert_eq!(ControlCommand::parse(&words("download")), Ok(ControlCommand::Download(None)));
+        assert_eq!(
+            ControlCommand::parse(&words("download ~/tmp")),
+            Ok(ControlCommand::Download(Some("~/tmp".to_string())))
+        );
+        assert_eq!(ControlCommand::parse(&words("get-selection")), Ok(ControlCommand::GetSelection));
+        assert_eq!(ControlCommand::parse(&words("navigate")), Err("Wrong number of arguments for navigate".to_string()));
+        assert_eq!(ControlCommand::parse(&words("jump")), Err("Unknown command: jump".to_string()));
+        assert_eq!(ControlCommand::parse(&[]), Err("Empty command".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_handle_line() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let app = tokio::spawn(async move {
+            while let Some(AppEvent::Control { command, responder }) = rx.recv().await {
+                match command {
+                    ControlCommand::GetLocation => responder.send(Ok(json!("s3://bucket"))),
+                    ControlCommand::Quit => responder.send(Ok(Value::Null)),
+                    _ => responder.send(Err("Nothing selected".to_string())),
+                }
+            }
+        });
+
+        assert_eq!(
+            handle_line(r#"{"command": ["get-location"], "request_id": 7}"#, &tx).await,
+            r#"{"data":"s3://bucket","error":"success","request_id":7}"#
+        );
+        assert_eq!(handle_line("quit", &tx).await, r#"{"error":"success"}"#);
+        assert_eq!(handle_line("get-selection", &tx).await, r#"{"error":"Nothing selected"}"#);
+        assert_eq!(handle_line("fly away", &tx).await, r#"{"error":"Unknown command: fly"}"#);
+        assert!(handle_line("{nope", &tx).await.starts_with(r#"{"error":"Invalid request"#));
+
+        drop(tx);
+        app.await.unwrap();
+    }
+}
@@ -0,0 +1,384 @@
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, RcIter};
+use jaq_json::Val;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A jq filter runs over this many records from the start of the file, so
+/// typing a filter stays responsive on large files
+pub const MAX_FILTER_RECORDS: usize = 1000;
+
+/// A jq filter is stopped after this many outputs, so one that never ends
+/// (`repeat(.)`) can't fill the memory
+pub const MAX_FILTER_OUTPUTS: usize = 10_000;
+
+/// A jq filter still running after this long is given up
+pub const FILTER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether `path` is a JSON Lines file, judged by its extension
+pub fn is_jsonl_path(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("jsonl") || ext.eq_ignore_ascii_case("ndjson"))
+}
+
+/// A line of the JSON Lines view
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonlLine {
+    pub text: String,
+    /// On the first line of a record: whether the record is expanded
+    pub fold: Option<bool>,
+}
+
+/// One record of a JSON Lines file
+#[derive(Debug)]
+struct Record {
+    /// Parsed value; None if the line isn't valid JSON
+    value: Option<Value>,
+    /// The record on a single line (the raw line if it didn't parse)
+    compact: String,
+}
+
+/// Preview of a JSON Lines file: one collapsed record per line, records
+/// expanded in place, or the output of a jq filter over the first records
+#[derive(Debug)]
+pub struct JsonLines {
+    records: Vec<Record>,
+    /// Parsed values of the records a filter runs over
+    values: Arc<Vec<Value>>,
+    expanded: BTreeSet<usize>,
+    filter: Option<String>,
+    /// Output of the filter, or the error it failed with
+    output: Result<FilterOutput, String>,
+    /// Displayed lines and the record each one belongs to
+    lines: Vec<(JsonlLine, Option<usize>)>,
+}
+
+impl JsonLines {
+    /// Parse newline-delimited JSON. Blank lines are skipped; lines that
+    /// aren't valid JSON are kept as they are.
+    pub fn parse(text: &str) -> Self {
+        let records: Vec<Record> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| match serde_json::from_str::<Value>(line) {
+                Ok(value) => Record { compact: value.to_string(), value: Some(value) },
+                Err(_) => Record { compact: line.to_string(), value: None },
+            })
+            .collect();
+        let values = records.iter().take(MAX_FILTER_RECORDS).filter_map(|r| r.value.clone()).collect();
+        let mut view = Self {
+            records,
+            values: Arc::new(values),
+            expanded: BTreeSet::new(),
+            filter: None,
+            output: Ok(FilterOutput::default()),
+            lines: Vec::new(),
+        };
+        view.rebuild();
+        view
+    }
+
+    /// Number of records in the file
+    pub fn record_count(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Lines currently shown
+    pub fn lines(&self) -> impl ExactSizeIterator<Item = &JsonlLine> {
+        self.lines.iter().map(|(line, _)| line)
+    }
+
+    /// Line `idx` of the lines currently shown
+    pub fn line(&self, idx: usize) -> Option<&JsonlLine> {
+        self.lines.get(idx).map(|(line, _)| line)
+    }
+
+    /// Number of lines currently shown
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The jq filter applied, if any
+    pub fn filter(&self) -> Option<&str> {
+        self.filter.as_deref()
+    }
+
+    /// Error of the applied filter, if it failed
+    pub fn filter_error(&self) -> Option<&str> {
+        self.output.as_ref().err().map(|e| e.as_str())
+    }
+
+    /// Whether the applied filter was stopped at `MAX_FILTER_OUTPUTS` outputs
+    pub fn is_truncated(&self) -> bool {
+        self.output.as_ref().is_ok_and(|output| output.truncated)
+    }
+
+    /// Expand or collapse the record shown on `line`. Returns the line the
+    /// record now starts on, or None if `line` isn't part of a record (e.g.
+    /// while a filter is applied).
+    pub fn toggle_fold(&mut self, line: usize) -> Option<usize> {
+        let record = self.lines.get(line)?.1?;
+        if !self.expanded.remove(&record) {
+            self.expanded.insert(record);
+        }
+        self.rebuild();
+        self.lines.iter().position(|(_, r)| *r == Some(record))
+    }
+
+    /// Show the output of `filter` run on each of the first records instead of
+    /// the records themselves; an empty filter shows the records again. Runs
+    /// the filter in place: see `filter_job` to run it elsewhere.
+    pub fn set_filter(&mut self, filter: &str) {
+        let filter = filter.trim();
+        if filter.is_empty() {
+            self.filter = None;
+            self.output = Ok(FilterOutput::default());
+            self.rebuild();
+        } else if let Some(result) = self.filter_job(filter, 0, Arc::default()).run() {
+            self.set_filter_result(result);
+        }
+    }
+
+    /// A job running `filter` over the first records, for a worker thread;
+    /// its result is shown with `set_filter_result`
+    pub fn filter_job(&self, filter: &str, generation: u64, canceled: Arc<AtomicBool>) -> JqJob {
+        JqJob {
+            generation,
+            filter: filter.trim().to_string(),
+            values: self.values.clone(),
+            canceled,
+        }
+    }
+
+    /// Show the output of a filter run by a [`JqJob`]
+    pub fn set_filter_result(&mut self, result: JqResult) {
+        self.filter = Some(result.filter);
+        self.output = result.output;
+        self.rebuild();
+    }
+
+    fn rebuild(&mut self) {
+        self.lines.clear();
+        if self.filter.is_some() {
+            let output = match &self.output {
+                Ok(output) => output.lines.clone(),
+                Err(e) => vec![format!("error: {}", e)],
+            };
+            self.lines.extend(output.into_iter().map(|text| (JsonlLine { text, fold: None }, None)));
+            return;
+        }
+        for (idx, record) in self.records.iter().enumerate() {
+            let expanded = self.expanded.contains(&idx);
+            match (&record.value, expanded) {
+                (Some(value), true) => {
+                    let pretty = serde_json::to_string_pretty(value).unwrap_or_else(|_| record.compact.clone());
+                    for (i, text) in pretty.lines().enumerate() {
+                        let fold = (i == 0).then_some(true);
+                        self.lines.push((JsonlLine { text: text.to_string(), fold }, Some(idx)));
+                    }
+                }
+                _ => {
+                    let fold = record.value.is_some().then_some(false);
+                    self.lines.push((JsonlLine { text: record.compact.clone(), fold }, Some(idx)));
+                }
+            }
+        }
+    }
+}
+
+/// What a jq filter printed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterOutput {
+    /// Each output on its own line
+    pub lines: Vec<String>,
+    /// Whether the filter was stopped at `MAX_FILTER_OUTPUTS` outputs
+    pub truncated: bool,
+}
+
+/// A jq filter to run over the records of a JSON Lines preview, on a worker
+/// thread so a slow filter doesn't stall typing
+#[derive(Debug)]
+pub struct JqJob {
+    generation: u64,
+    filter: String,
+    values: Arc<Vec<Value>>,
+    canceled: Arc<AtomicBool>,
+}
+
+/// Output of a [`JqJob`]
+#[derive(Debug)]
+pub struct JqResult {
+    /// Filter generation the output belongs to; older ones are dropped
+    pub generation: u64,
+    /// The filter that ran
+    pub filter: String,
+    /// Its output, or the error it failed with
+    pub output: Result<FilterOutput, String>,
+}
+
+impl JqJob {
+    /// Run the filter; None if the job was canceled. A filter that loops
+    /// without output (`last(range(1e12))`) can only be stopped once it
+    /// outputs something: the caller stops waiting for it instead.
+    pub fn run(self) -> Option<JqResult> {
+        let deadline = Instant::now() + FILTER_TIMEOUT;
+        let output = run_filter(&self.filter, self.values.iter(), &self.canceled, deadline);
+        (!self.canceled.load(Ordering::Relaxed)).then_some(JqResult {
+            generation: self.generation,
+            filter: self.filter,
+            output,
+        })
+    }
+}
+
+/// Run a jq filter on each value, collecting every output on its own line,
+/// up to `MAX_FILTER_OUTPUTS` of them. Gives up when `canceled` is set or
+/// after `deadline`.
+pub fn run_filter<'a>(
+    filter: &str,
+    values: impl Iterator<Item = &'a Value>,
+    canceled: &AtomicBool,
+    deadline: Instant,
+) -> Result<FilterOutput, String> {
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let modules = loader
+        .load(&arena, File { code: filter, path: () })
+        .map_err(|_| "invalid filter".to_string())?;
+    let filter = Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(|errors| {
+            let names: Vec<&str> = errors.iter().flat_map(|(_, errs)| errs.iter().map(|(name, _)| *name)).collect();
+            format!("undefined: {}", names.join(", "))
+        })?;
+
+    let inputs = RcIter::new(core::iter::empty());
+    let mut lines = Vec::new();
+    for value in values {
+        for result in filter.run((Ctx::new([], &inputs), Val::from(value.clone()))) {
+            if canceled.load(Ordering::Relaxed) {
+                return Err("canceled".to_string());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("gave up after {}s", FILTER_TIMEOUT.as_secs()));
+            }
+            if lines.len() == MAX_FILTER_OUTPUTS {
+                return Ok(FilterOutput { lines, truncated: true });
+            }
+            lines.push(result.map_err(|e| e.to_string())?.to_string());
+        }
+    }
+    Ok(FilterOutput { lines, truncated: false })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = "{\"id\": 1, \"kind\": \"start\"}\n\n{\"id\": 2, \"kind\": \"stop\", \"tags\": [\"a\"]}\nnot json\n";
+
+    fn texts(view: &JsonLines) -> Vec<&str> {
+        view.lines().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_is_jsonl_path() {
+        assert!(is_jsonl_path("logs/events.jsonl"));
+        assert!(is_jsonl_path("EVENTS.NDJSON"));
+        assert!(!is_jsonl_path("data.json"));
+        assert!(!is_jsonl_path("jsonl"));
+    }
+
+    #[test]
+    fn test_records_collapsed() {
+        let view = JsonLines::parse(EVENTS);
+        assert_eq!(view.record_count(), 3);
+        assert_eq!(
+            texts(&view),
+            vec![r#"{"id":1,"kind":"start"}"#, r#"{"id":2,"kind":"stop","tags":["a"]}"#, "not json"]
+        );
+        let folds: Vec<_> = view.lines().map(|line| line.fold).collect();
+        assert_eq!(folds, vec![Some(false), Some(false), None]);
+    }
+
+    #[test]
+    fn test_toggle_fold() {
+        let mut view = JsonLines::parse(EVENTS);
+        assert_eq!(view.toggle_fold(1), Some(1));
+        assert_eq!(view.line_count(), 2 + 7);
+        assert_eq!(texts(&view)[1], "{");
+        assert_eq!(texts(&view)[2], "  \"id\": 2,");
+        assert_eq!(view.lines().nth(1).unwrap().fold, Some(true));
+
+        // Any line of an expanded record collapses it
+        assert_eq!(view.toggle_fold(4), Some(1));
+        assert_eq!(view.line_count(), 3);
+
+        // Lines that aren't JSON have nothing to expand, but stay a record
+        assert_eq!(view.toggle_fold(2), Some(2));
+        assert_eq!(view.line_count(), 3);
+        assert_eq!(view.toggle_fold(10), None);
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut view = JsonLines::parse(EVENTS);
+        view.set_filter(".kind");
+        assert_eq!(view.filter(), Some(".kind"));
+        assert_eq!(texts(&view), vec!["\"start\"", "\"stop\""]);
+        assert_eq!(view.toggle_fold(0), None);
+
+        view.set_filter("select(.id > 1) | .tags[]");
+        assert_eq!(texts(&view), vec!["\"a\""]);
+
+        view.set_filter(".id +");
+        assert_eq!(view.filter_error(), Some("invalid filter"));
+        assert_eq!(texts(&view), vec!["error: invalid filter"]);
+
+        view.set_filter("nosuchfn");
+        assert!(view.filter_error().unwrap().contains("nosuchfn"));
+
+        view.set_filter(" ");
+        assert!(view.filter().is_none());
+        assert_eq!(view.line_count(), 3);
+    }
+
+    #[test]
+    fn test_filter_limited_to_first_records() {
+        let text: String = (0..MAX_FILTER_RECORDS + 5).map(|i| format!("{{\"n\": {}}}\n", i)).collect();
+        let mut view = JsonLines::parse(&text);
+        view.set_filter(".n");
+        assert_eq!(view.line_count(), MAX_FILTER_RECORDS);
+        assert!(!view.is_truncated());
+    }
+
+    #[test]
+    fn test_filter_output_limit() {
+        let mut view = JsonLines::parse(EVENTS);
+        view.set_filter("repeat(.)");
+        assert_eq!(view.line_count(), MAX_FILTER_OUTPUTS);
+        assert!(view.is_truncated());
+
+        view.set_filter("limit(3; repeat(.id))");
+        assert_eq!(texts(&view), vec!["1", "1", "1", "2", "2", "2"]);
+        assert!(!view.is_truncated());
+    }
+
+    #[test]
+    fn test_filter_job() {
+        let view = JsonLines::parse(EVENTS);
+        let result = view.filter_job(" .id ", 3, Arc::default()).run().unwrap();
+        assert_eq!(result.generation, 3);
+        assert_eq!(result.filter, ".id");
+        assert_eq!(result.output.unwrap().lines, vec!["1", "2"]);
+
+        // A canceled job reports nothing
+        assert!(view.filter_job("repeat(.)", 4, Arc::new(AtomicBool::new(true))).run().is_none());
+    }
+}
@@ -1,12 +1,31 @@
 pub mod app;
+pub mod audit;
 pub mod backend;
+pub mod chosen;
 pub mod clipboard;
 pub mod config;
+pub mod diff;
 pub mod events;
 pub mod fuzzy;
+pub mod grep;
+pub mod history;
+pub mod ipc;
+pub mod jsonl;
+pub mod listing_cache;
+pub mod plugin;
+pub mod preview_handler;
+pub mod runtime;
+pub mod session;
 pub mod state;
 pub mod status;
+pub mod summary;
+pub mod sync;
+pub mod table;
+pub mod terminal;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transfer;
+pub mod tui;
 pub mod ui;
 
-// These will be implemented in later phases
-// pub mod history;
+pub use tui::{run, RunOptions, RunOutcome};
@@ -0,0 +1,109 @@
+use crate::backend::ListResult;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Listings kept for a while after they were fetched, so going back and forth
+/// between prefixes doesn't list them again. Keyed by display path
+/// (`s3://bucket/prefix/`), which names both the backend and the prefix.
+#[derive(Debug)]
+pub struct ListingCache {
+    ttl: Duration,
+    listings: HashMap<String, (Instant, ListResult)>,
+}
+
+impl ListingCache {
+    /// Cache keeping listings for `ttl`; a zero TTL disables caching
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, listings: HashMap::new() }
+    }
+
+    /// Listing of `key`, unless it's older than the TTL
+    pub fn get(&self, key: &str) -> Option<ListResult> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, now: Instant) -> Option<ListResult> {
+        self.listings
+            .get(key)
+            .filter(|(fetched, _)| now.duration_since(*fetched) < self.ttl)
+            .map(|(_, result)| result.clone())
+    }
+
+    /// Remember a listing just fetched
+    pub fn insert(&mut self, key: String, result: ListResult) {
+        self.insert_at(key, result, Instant::now());
+    }
+
+    fn insert_at(&mut self, key: String, result: ListResult, now: Instant) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        // Drop expired listings so the cache doesn't grow with every prefix visited
+        let ttl = self.ttl;
+        self.listings.retain(|_, (fetched, _)| now.duration_since(*fetched) < ttl);
+        self.listings.insert(key, (now, result));
+    }
+
+    /// Forget the listing of `key`, so the next one goes to the backend
+    pub fn invalidate(&mut self, key: &str) {
+        self.listings.remove(key);
+    }
+
+    /// Number of listings held
+    pub fn len(&self) -> usize {
+        self.listings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Entry;
+
+    fn listing(name: &str) -> ListResult {
+        ListResult {
+            entries: vec![Entry {
+                name: name.to_string(),
+                is_dir: false,
+                size: Some(1),
+                modified: None,
+                storage_class: None,
+                restore_state: None,
+                etag: None,
+                permissions: None,
+            }],
+            prefix: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_expires_after_ttl() {
+        let mut cache = ListingCache::new(Duration::from_secs(30));
+        let start = Instant::now();
+        cache.insert_at("s3://b/".to_string(), listing("a"), start);
+
+        let hit = cache.get_at("s3://b/", start + Duration::from_secs(29)).unwrap();
+        assert_eq!(hit.entries[0].name, "a");
+        assert!(cache.get_at("s3://b/", start + Duration::from_secs(30)).is_none());
+        assert!(cache.get_at("s3://other/", start).is_none());
+
+        // Expired listings are dropped when a new one comes in
+        cache.insert_at("s3://b/x/".to_string(), listing("b"), start + Duration::from_secs(31));
+        assert_eq!(cache.len(), 1);
+
+        cache.invalidate("s3://b/x/");
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_zero_ttl_disables() {
+        let mut cache = ListingCache::new(Duration::ZERO);
+        cache.insert("s3://b/".to_string(), listing("a"));
+        assert!(cache.get("s3://b/").is_none());
+        assert!(cache.is_empty());
+    }
+}
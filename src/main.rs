@@ -1,1040 +1,241 @@
-use anyhow::Result;
-use clap::Parser;
-use crossterm::{
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-use ratatui::{backend::CrosstermBackend, Terminal};
+use anyhow::{Context, Result};
+use clap::{builder::PossibleValuesParser, CommandFactory, Parser};
+use clap_complete::Shell;
 use rats3::{
-    app::{App, AppMode, NavigateDirection},
-    backend::{local::LocalBackend, Backend, PreviewContent},
-    clipboard,
+    app::PickMode,
+    backend::{local::LocalBackend, memory::MemoryBackend, registry::BackendRegistry, Backend},
     config::Config,
-    events::{handle_key, read_event, Action},
+    runtime::open_context,
+    session::{Session, DEFAULT_SESSION},
     state::AppState,
-    ui,
+    RunOptions,
 };
-use ratatui::text::Line;
-use std::collections::HashMap;
-#[cfg(feature = "s3")]
-use rats3::backend::s3::S3Backend;
-use std::{io, path::PathBuf, sync::Arc, time::Duration};
-use tokio::sync::mpsc;
-
-/// Progress update messages from download tasks
-#[derive(Debug, Clone)]
-enum ProgressMessage {
-    Update {
-        path: String,
-        downloaded: u64,
-        total: Option<u64>,
-    },
-    Complete {
-        path: String,
-    },
-    Canceled {
-        path: String,
-    },
-    Error {
-        path: String,
-        error: String,
-    },
-}
+use std::{io, path::PathBuf, sync::Arc};
 
 #[derive(Parser, Debug)]
 #[command(name = "rats3")]
 #[command(about = "Rust S3 Navigator - Interactive TUI for browsing S3 and local filesystems")]
 struct Args {
-    /// S3 URI (s3://bucket/prefix) or path to use
+    /// S3 URI (s3://bucket/prefix), HTTP/WebDAV URL (https://host/path) or path to use
     #[arg(value_name = "URI")]
     uri: Option<String>,
 
     /// Use local filesystem backend (for testing)
     #[arg(long, value_name = "PATH")]
     local: Option<PathBuf>,
+
+    /// Browse a built-in sample bucket held in memory (no AWS credentials needed)
+    #[arg(long, conflicts_with_all = ["uri", "local"])]
+    demo: bool,
+
+    /// Open a named context from the config (`[contexts.<name>]`)
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["uri", "local", "demo"])]
+    context: Option<String>,
+
+    /// Resume a session saved with `:mksession [NAME]` (default: "default")
+    #[arg(long, value_name = "NAME", num_args = 0..=1, default_missing_value = DEFAULT_SESSION,
+          conflicts_with_all = ["uri", "local", "demo", "context"])]
+    session: Option<String>,
+
+    /// Pick files like fzf: Enter on a file prints its URI to stdout and exits.
+    /// With "multi", Space selects several files and Enter prints them all.
+    #[arg(long, value_name = "MODE", value_enum, num_args = 0..=1, default_missing_value = "single")]
+    pick: Option<PickArg>,
+
+    /// Write the last location to FILE on exit, for a shell wrapper to cd
+    /// into: the directory for local paths, the URI (s3://...) otherwise
+    #[arg(long, value_name = "FILE")]
+    print_last_dir: Option<PathBuf>,
+
+    /// Browse public buckets without credentials: S3 requests are sent
+    /// unsigned (like the AWS CLI's --no-sign-request)
+    #[arg(long)]
+    no_sign_request: bool,
+
+    /// Append the URI of every file opened (Enter), picked or downloaded to
+    /// FILE, one per line, for other programs to watch (like ranger's
+    /// --choosefiles); FILE is emptied on startup
+    #[arg(long, value_name = "FILE")]
+    choosefiles: Option<PathBuf>,
+
+    /// Take commands on a unix socket at SOCKET, one per line (`navigate
+    /// <uri>`, `download [destination]`, `get-selection`, `get-location`,
+    /// `quit`, or mpv-style JSON), each answered with a JSON line
+    #[arg(long, value_name = "SOCKET")]
+    listen: Option<PathBuf>,
+
+    /// Project config file overlaid on the global config (default: ./.rats3.toml if present)
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-/// Expand tilde (~) in path to home directory
-fn expand_tilde(path: &str) -> PathBuf {
-    if path.starts_with("~/") {
-        if let Some(home) = dirs::home_dir() {
-            return home.join(&path[2..]);
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Print a completion script for SHELL. Contexts defined in the config
+    /// when it is generated complete after --context.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page (roff), e.g. `rats3 man > ~/.local/share/man/man1/rats3.1`
+    Man,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum PickArg {
+    Single,
+    Multi,
+}
+
+/// The command line definition, with the names of the contexts in `config`
+/// as the values of --context
+fn cli_command(config: &Config) -> clap::Command {
+    let contexts: Vec<String> = config.contexts.keys().cloned().collect();
+    Args::command().mut_arg("context", |arg| {
+        if contexts.is_empty() {
+            arg
+        } else {
+            arg.value_parser(PossibleValuesParser::new(contexts))
         }
-    }
-    PathBuf::from(path)
+    })
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
+    // Load config
+    let (config, config_error, config_warning) = match Config::load_with_overlay(args.config.as_deref()) {
+        Ok((config, warning)) => (config, None, warning),
+        Err(e) => {
+            eprintln!("Warning: Failed to load config, using defaults: {:#}", e);
+            (Config::default(), Some(format!("{:#}", e)), None)
+        }
+    };
+
+    match args.command {
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut cli_command(&config), "rats3", &mut io::stdout());
+            return Ok(());
+        }
+        Some(Command::Man) => {
+            clap_mangen::Man::new(cli_command(&config)).render(&mut io::stdout())?;
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let session = args.session.as_deref().map(Session::load).transpose()?;
+
     // Determine backend and initial prefix
-    let (backend, initial_prefix): (Arc<dyn Backend>, String) = if let Some(local_path) = args.local {
+    let registry = if args.no_sign_request {
+        BackendRegistry::default().no_sign_request()
+    } else {
+        BackendRegistry::default()
+    };
+    let demo = args.demo;
+    let (backend, initial_prefix): (Arc<dyn Backend>, String) = if demo {
+        (Arc::new(MemoryBackend::demo()), String::new())
+    } else if let Some(name) = &args.context {
+        let Some(context) = config.contexts.get(name) else {
+            println!("Unknown context: {}", name);
+            println!("Define it in the config as [contexts.{}] with a uri (and optional profile).", name);
+            std::process::exit(1);
+        };
+        open_context(&registry, context).await?
+    } else if let Some(session) = &session {
+        // The context only supplies the AWS profile; the session has the exact location
+        let profile = session
+            .context
+            .as_deref()
+            .and_then(|name| config.contexts.get(name))
+            .and_then(|context| context.profile.as_deref());
+        let (backend, _) = registry.open(&session.root, profile).await?;
+        (backend, session.prefix.clone())
+    } else if let Some(local_path) = args.local {
         let backend = LocalBackend::new(local_path)?;
         (Arc::new(backend), String::new())
     } else if let Some(uri) = args.uri {
-        if uri.starts_with("s3://") {
-            #[cfg(feature = "s3")]
-            {
-                let (bucket, prefix) = S3Backend::from_uri(&uri)?;
-                let backend = S3Backend::new(bucket).await?;
-                (Arc::new(backend), prefix)
-            }
-            #[cfg(not(feature = "s3"))]
-            {
-                println!("S3 support not enabled. Build with --features s3 to enable.");
-                std::process::exit(1);
-            }
-        } else {
-            // Treat as local path
-            let path = PathBuf::from(&uri);
-            let backend = LocalBackend::new(path)?;
-            (Arc::new(backend), String::new())
-        }
+        registry.open(&uri, None).await?
     } else {
         // Load last location from state
         let state = AppState::load()?;
-        if let Some(last_location) = state.last_location {
-            if last_location.starts_with("s3://") {
-                #[cfg(feature = "s3")]
-                {
-                    let (bucket, prefix) = S3Backend::from_uri(&last_location)?;
-                    let backend = S3Backend::new(bucket).await?;
-                    (Arc::new(backend), prefix)
-                }
-                #[cfg(not(feature = "s3"))]
-                {
-                    println!("Last location was S3 but S3 support not enabled.");
-                    println!("Build with --features s3 or provide a local path.");
-                    std::process::exit(1);
-                }
-            } else {
+        match state.last_location {
+            Some(last_location) if last_location.contains("://") => registry.open(&last_location, None).await?,
+            Some(_) => {
                 println!("No URI provided and no valid last location found.");
                 println!("Usage: rats3 [s3://bucket/prefix] or rats3 --local /path/to/dir");
                 std::process::exit(1);
             }
-        } else {
-            println!("No URI provided. Please specify an S3 URI or local path.");
-            println!("Usage: rats3 [s3://bucket/prefix] or rats3 --local /path/to/dir");
-            std::process::exit(1);
-        }
-    };
-
-    // Load config
-    let (config, config_error) = match Config::load() {
-        Ok(config) => (config, None),
-        Err(e) => {
-            eprintln!("Warning: Failed to load config, using defaults: {:#}", e);
-            (Config::default(), Some(format!("{:#}", e)))
+            None => {
+                println!("No URI provided. Please specify an S3 URI or local path.");
+                println!("Usage: rats3 [s3://bucket/prefix] or rats3 --local /path/to/dir");
+                std::process::exit(1);
+            }
         }
     };
 
-    // Initialize terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend_term = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend_term)?;
-
-    // Run app
-    let app_result = run_app(&mut terminal, backend.clone(), initial_prefix, config, config_error).await;
-
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-
-    // Save state before exiting (even if there was an error)
-    if let Ok((app, final_backend)) = &app_result {
-        let mut state = AppState::load().unwrap_or_default();
-        state.set_last_location(final_backend.get_display_path(app.current_prefix()));
-        state.set_history(app.history().to_vec());
-        let _ = state.save();
+    let state = AppState::load().unwrap_or_default();
+    let mut options = RunOptions::new(backend, config)
+        .with_prefix(initial_prefix)
+        .with_registry(registry)
+        .with_history(state.history);
+    if let Some(sizes) = state.pane_sizes {
+        options = options.with_pane_sizes(sizes);
     }
-
-    app_result.map(|_| ())
-}
-
-/// Check if a path should be added to history
-/// Filters out paths ending in just numbers (e.g., "folder/8323")
-fn should_add_to_history(path: &str) -> bool {
-    if path.is_empty() {
-        return false;
+    if let Some(context) = args.context {
+        options = options.with_context(context);
     }
-
-    // Get the last component of the path
-    let last_component = path
-        .trim_end_matches('/')
-        .split('/')
-        .filter(|s| !s.is_empty())
-        .last();
-
-    if let Some(component) = last_component {
-        // Check if it's all digits
-        !component.chars().all(|c| c.is_ascii_digit())
-    } else {
-        false
+    if let Some(session) = session {
+        options = options.with_session(session);
     }
-}
-
-/// Create a backend from a full display URI (e.g. "s3://bucket/prefix").
-/// Returns the backend and the bare prefix to pass to list().
-async fn create_backend_from_uri(uri: &str) -> Result<(Arc<dyn Backend>, String)> {
-    if uri.starts_with("s3://") {
-        #[cfg(feature = "s3")]
-        {
-            let (bucket, prefix) = S3Backend::from_uri(uri)?;
-            let backend = S3Backend::new(bucket).await?;
-            return Ok((Arc::new(backend), prefix));
-        }
-        #[cfg(not(feature = "s3"))]
-        anyhow::bail!("S3 support not enabled (build with --features s3)");
+    if let Some(error) = config_error {
+        options = options.with_config_error(error);
     }
-    anyhow::bail!("Unsupported URI scheme: {}", uri)
-}
-
-async fn run_app(
-    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    backend: Arc<dyn Backend>,
-    initial_prefix: String,
-    config: Config,
-    config_error: Option<String>,
-) -> Result<(App, Arc<dyn Backend>)> {
-    let mut backend = backend;
-    let mut app = App::new(backend.clone(), initial_prefix.clone(), config.preview_width_percent);
-
-    // Load history from state
-    if let Ok(state) = AppState::load() {
-        app.load_history(state.history);
+    if let Some(warning) = config_warning {
+        options = options.with_config_warning(warning);
     }
-
-    // Do initial listing
-    match backend.list(&initial_prefix).await {
-        Ok(result) => {
-            app.update_entries(result);
-            // Add initial location to history (if it's not a numeric folder)
-            if should_add_to_history(&initial_prefix) {
-                app.add_to_history(backend.get_display_path(&initial_prefix));
-            }
-        }
-        Err(e) => {
-            app.show_error(format!("Error listing directory: {}", e));
-        }
+    if let Some(file) = &args.choosefiles {
+        options = options.with_choose_files(file);
     }
-
-    // Show config error if there was one
-    if let Some(error) = config_error {
-        app.show_warning(format!("Config file error (using defaults): {}", error));
+    if let Some(socket) = &args.listen {
+        options = options.with_listen(socket);
     }
-
-    // Create channels for background tasks
-    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<ProgressMessage>();
-    let (preview_tx, mut preview_rx) = mpsc::unbounded_channel::<(String, PreviewContent)>();
-    let mut pending_preview_cancel: Option<tokio::sync::oneshot::Sender<()>> = None;
-    let (highlight_tx, mut highlight_rx) = mpsc::unbounded_channel::<(String, Vec<Line<'static>>)>();
-    let mut highlighted_cache: HashMap<String, Vec<Line<'static>>> = HashMap::new();
-
-    // Load initial preview in background
-    spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-
-    // Initial render before entering the event loop
-    terminal.draw(|f| ui::render(f, &app, &config, &highlighted_cache))?;
-
-    // Main event loop
-    loop {
-        let mut dirty = false;
-
-        // Clear expired status messages
-        app.clear_status_if_expired(config.status_message_timeout_secs);
-
-        // Remove expired downloads (completed > 5s ago)
-        app.remove_expired_downloads();
-
-        // Process download progress messages
-        while let Ok(msg) = progress_rx.try_recv() {
-            dirty = true;
-            match msg {
-                ProgressMessage::Update { path, downloaded, total } => {
-                    app.update_download(path, downloaded, total);
-                }
-                ProgressMessage::Complete { path } => {
-                    app.complete_download(path.clone());
-
-                    // Check if all downloads are complete
-                    let downloads = app.downloads();
-                    let all_complete = downloads.values().all(|d| {
-                        d.status == rats3::app::DownloadState::Complete ||
-                        d.status == rats3::app::DownloadState::Canceled ||
-                        matches!(d.status, rats3::app::DownloadState::Error(_))
-                    });
-
-                    if all_complete {
-                        let completed = downloads.values()
-                            .filter(|d| d.status == rats3::app::DownloadState::Complete)
-                            .count();
-                        let failed = downloads.values()
-                            .filter(|d| matches!(d.status, rats3::app::DownloadState::Error(_)))
-                            .count();
-                        let canceled = downloads.values()
-                            .filter(|d| d.status == rats3::app::DownloadState::Canceled)
-                            .count();
-
-                        if canceled > 0 {
-                            app.show_info(format!("Canceled {} download(s)", canceled));
-                        } else if failed > 0 {
-                            app.show_warning(format!("Downloaded {} file(s), {} failed", completed, failed));
-                        } else {
-                            app.show_success(format!("Downloaded {} file(s)", completed));
-                        }
-                    }
-                }
-                ProgressMessage::Canceled { path } => {
-                    if let Some(info) = app.downloads().get(&path) {
-                        if info.status == rats3::app::DownloadState::InProgress {
-                            // Only mark as canceled if still in progress (not already complete/error)
-                            app.cancel_download(path);
-                        }
-                    }
-                }
-                ProgressMessage::Error { path, error } => {
-                    app.fail_download(path.clone(), error.clone());
-                }
-            }
-        }
-
-        // Process preview results from background tasks
-        while let Ok((path, content)) = preview_rx.try_recv() {
-            // If this is a text file with syntect support, kick off background highlighting
-            if let PreviewContent::Text(ref text, _) = content {
-                if let Some(syntax) = ui::widgets::preview::find_syntax_for_path(&path) {
-                    let text_owned = text.clone();
-                    let path_owned = path.clone();
-                    let line_num_color = config.colors.text_secondary.to_ratatui_color();
-                    let tx = highlight_tx.clone();
-                    std::thread::spawn(move || {
-                        let lines = ui::widgets::preview::build_highlight_lines(
-                            &text_owned,
-                            syntax,
-                            line_num_color,
-                        );
-                        let _ = tx.send((path_owned, lines));
-                    });
-                }
-            }
-            app.receive_preview(path, content);
-            dirty = true;
-        }
-
-        // Process completed highlight jobs
-        while let Ok((path, lines)) = highlight_rx.try_recv() {
-            if highlighted_cache.len() >= config.highlight_cache_size {
-                // Evict the entry whose key sorts first (stable, arbitrary but deterministic)
-                if let Some(oldest) = highlighted_cache.keys().next().map(|k| k.clone()) {
-                    highlighted_cache.remove(&oldest);
-                }
-            }
-            highlighted_cache.insert(path, lines);
-            dirty = true;
-        }
-
-        // Flush pending key in search mode if timeout expired (~300ms)
-        if app.is_search_mode() {
-            if let Some(c) = app.pending_key() {
-                if app.pending_key_elapsed().map(|d| d.as_millis() > 300).unwrap_or(false) {
-                    app.clear_pending_key();
-                    app.append_search_char(c);
-                    dirty = true;
-                }
-            }
-        }
-
-        // Read events with timeout
-        if let Some(event) = read_event(Duration::from_millis(100))? {
-            dirty = true;
-            if let crossterm::event::Event::Key(key) = event {
-                let in_history_mode = app.mode() == &AppMode::History;
-                let in_visual_mode = app.mode() == &AppMode::Visual;
-                let in_download_mode = app.mode() == &AppMode::Download;
-                let preview_focused = matches!(app.focused_panel(), rats3::app::FocusedPanel::Preview);
-                let preview_visual_mode = app.is_preview_visual_mode();
-                let preview_search_mode = app.is_preview_search_active();
-                let was_search_mode = app.is_search_mode();
-                let pending_before = app.pending_key();
-
-                // Check if Escape is pressed while downloads are active (not in a modal mode)
-                let action = if matches!(key.code, crossterm::event::KeyCode::Esc)
-                    && !app.is_search_mode()
-                    && !in_history_mode
-                    && !in_download_mode
-                    && !in_visual_mode
-                    && !preview_visual_mode
-                    && !preview_search_mode
-                    && app.has_active_downloads() {
-                    Action::CancelDownloads
-                } else {
-                    handle_key(key, &config.key_bindings, app.is_search_mode(), in_history_mode, in_visual_mode, in_download_mode, preview_focused, preview_visual_mode, preview_search_mode, app.pending_key())
-                };
-                match action {
-                    Action::Quit => {
-                        app.quit();
-                    }
-                    Action::MoveUp => {
-                        app.clear_pending_key();
-                        if app.mode() == &AppMode::Download {
-                            app.download_move_up();
-                        } else if app.mode() == &AppMode::History || app.is_searching_history() {
-                            app.history_move_up();
-                        } else if matches!(app.focused_panel(), rats3::app::FocusedPanel::Preview) {
-                            // Calculate visible height (terminal height / 2 for preview, minus borders)
-                            let visible_height = terminal.size().unwrap().height.saturating_sub(10) as usize;
-                            app.preview_scroll_up(visible_height);
-                        } else {
-                            app.move_up();
-                            // Update visual selection if in visual mode
-                            if app.mode() == &AppMode::Visual {
-                                app.update_visual_selection();
-                            }
-                            // Load preview for new selection
-                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                        }
-                    }
-                    Action::MoveDown => {
-                        app.clear_pending_key();
-                        if app.mode() == &AppMode::Download {
-                            app.download_move_down(config.download_destinations.len());
-                        } else if app.mode() == &AppMode::History || app.is_searching_history() {
-                            app.history_move_down();
-                        } else if matches!(app.focused_panel(), rats3::app::FocusedPanel::Preview) {
-                            // Calculate max lines from preview content and visible height
-                            if let Some(preview) = app.get_preview() {
-                                let max_lines = match preview {
-                                    rats3::backend::PreviewContent::Text(content, _) => content.lines().count(),
-                                    _ => 0,
-                                };
-                                let visible_height = terminal.size().unwrap().height.saturating_sub(10) as usize;
-                                app.preview_scroll_down(max_lines, visible_height);
-                            }
-                        } else {
-                            app.move_down();
-                            // Update visual selection if in visual mode
-                            if app.mode() == &AppMode::Visual {
-                                app.update_visual_selection();
-                            }
-                            // Load preview for new selection
-                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                        }
-                    }
-                    Action::JumpUp(count) => {
-                        app.clear_pending_key();
-                        if matches!(app.focused_panel(), rats3::app::FocusedPanel::Preview) {
-                            app.preview_scroll_page_up(count);
-                        } else {
-                            app.jump_up(count);
-                            // Update visual selection if in visual mode
-                            if app.mode() == &AppMode::Visual {
-                                app.update_visual_selection();
-                            }
-                            // Load preview for new selection
-                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                        }
-                    }
-                    Action::JumpDown(count) => {
-                        app.clear_pending_key();
-                        if matches!(app.focused_panel(), rats3::app::FocusedPanel::Preview) {
-                            // Calculate max lines from preview content and visible height
-                            if let Some(preview) = app.get_preview() {
-                                let max_lines = match preview {
-                                    rats3::backend::PreviewContent::Text(content, _) => content.lines().count(),
-                                    _ => 0,
-                                };
-                                let visible_height = terminal.size().unwrap().height.saturating_sub(10) as usize;
-                                app.preview_scroll_page_down(count, max_lines, visible_height);
-                            }
-                        } else {
-                            app.jump_down(count);
-                            // Update visual selection if in visual mode
-                            if app.mode() == &AppMode::Visual {
-                                app.update_visual_selection();
-                            }
-                            // Load preview for new selection
-                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                        }
-                    }
-                    Action::JumpToBottom => {
-                        app.clear_pending_key();
-                        if matches!(app.focused_panel(), rats3::app::FocusedPanel::Preview) {
-                            // Calculate max lines from preview content and visible height
-                            if let Some(preview) = app.get_preview() {
-                                let max_lines = match preview {
-                                    rats3::backend::PreviewContent::Text(content, _) => content.lines().count(),
-                                    _ => 0,
-                                };
-                                let visible_height = terminal.size().unwrap().height.saturating_sub(10) as usize;
-                                app.preview_jump_to_bottom(max_lines, visible_height);
-                            }
-                        } else {
-                            app.jump_to_bottom();
-                            // Update visual selection if in visual mode
-                            if app.mode() == &AppMode::Visual {
-                                app.update_visual_selection();
-                            }
-                            // Load preview for new selection
-                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                        }
-                    }
-                    Action::JumpToTop => {
-                        app.clear_pending_key();
-                        if matches!(app.focused_panel(), rats3::app::FocusedPanel::Preview) {
-                            app.preview_jump_to_top();
-                        } else {
-                            app.jump_to_top();
-                            // Update visual selection if in visual mode
-                            if app.mode() == &AppMode::Visual {
-                                app.update_visual_selection();
-                            }
-                            // Load preview for new selection
-                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                        }
-                    }
-                    Action::NavigateInto => {
-                        app.clear_pending_key();
-
-                        // Handle history mode - select entry and navigate
-                        // Check both History mode and Search mode with searching_history flag
-                        if app.mode() == &AppMode::History || (app.is_search_mode() && app.is_searching_history()) {
-                            if let Some(selected_uri) = app.selected_history_entry().map(|s| s.clone()) {
-                                let nav_prefix = if let Some(prefix) = backend.uri_to_prefix(&selected_uri) {
-                                    // Same backend
-                                    Some(prefix)
-                                } else {
-                                    // Different backend — try to switch
-                                    match create_backend_from_uri(&selected_uri).await {
-                                        Ok((new_backend, prefix)) => {
-                                            backend = new_backend;
-                                            app.set_backend(backend.clone());
-                                            Some(prefix)
-                                        }
-                                        Err(e) => {
-                                            app.show_error(format!("Cannot switch backend: {}", e));
-                                            None
-                                        }
-                                    }
-                                };
-
-                                if let Some(nav_prefix) = nav_prefix {
-                                    app.exit_history_mode();
-                                    match backend.list(&nav_prefix).await {
-                                        Ok(result) => {
-                                            app.update_entries(result);
-                                            app.clear_status();
-                                            // Re-add to history to bump it to the top
-                                            if should_add_to_history(&nav_prefix) {
-                                                app.add_to_history(backend.get_display_path(&nav_prefix));
-                                            }
-                                            // Load preview for first item
-                                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                                        }
-                                        Err(e) => {
-                                            app.show_error(format!("Error: {}", e));
-                                        }
-                                    }
-                                }
-                            }
-                        } else {
-                            // Check if selected item is a file or directory
-                            let is_file = app.selected_entry().map(|e| !e.is_dir).unwrap_or(false);
-
-                            if is_file {
-                                // Capture name before any mode change shifts the selection
-                                let selected_name = app.selected_entry().map(|e| e.name.clone());
-
-                                app.focus_preview();
-
-                                if app.is_search_mode() {
-                                    app.exit_search_mode();
-                                    // Restore cursor to the same file in the now-unfiltered list
-                                    if let Some(name) = selected_name {
-                                        app.select_entry_by_name(&name);
-                                    }
-                                }
-
-                                // Ensure the preview is loaded for the (re-)selected file
-                                spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                            } else {
-                                // Get the navigation target BEFORE exiting search mode
-                                // (otherwise the selection index will be wrong)
-                                let nav_result = app.navigate(NavigateDirection::Into);
-
-                                // Exit search mode when navigating
-                                let was_in_search = app.is_search_mode();
-                                if was_in_search {
-                                    app.exit_search_mode();
-                                }
-
-                                if let Some((new_prefix, _)) = nav_result {
-                                    match backend.list(&new_prefix).await {
-                                        Ok(result) => {
-                                            app.update_entries(result);
-                                            app.clear_status();
-                                            // Add to history (skip folders ending in just numbers)
-                                            if should_add_to_history(&new_prefix) {
-                                                app.add_to_history(backend.get_display_path(&new_prefix));
-                                            }
-                                            // Load preview for first item
-                                            spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                                        }
-                                        Err(e) => {
-                                            app.show_error(format!("Error: {}", e));
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Action::NavigateUp => {
-                        app.clear_pending_key();
-                        if let Some((new_prefix, select_name)) = app.navigate(NavigateDirection::Up) {
-                            match backend.list(&new_prefix).await {
-                                Ok(result) => {
-                                    if let Some(name) = select_name {
-                                        app.update_entries_and_select(result, &name);
-                                    } else {
-                                        app.update_entries(result);
-                                    }
-                                    app.clear_status();
-                                    // Load preview for selected item
-                                    spawn_preview_load(&mut app, &backend, &config, &preview_tx, &mut pending_preview_cancel);
-                                }
-                                Err(e) => {
-                                    app.show_error(format!("Error: {}", e));
-                                }
-                            }
-                        }
-                    }
-                    Action::EnterSearchMode => {
-                        app.clear_pending_key();
-                        app.enter_search_mode();
-                        app.clear_status();
-                    }
-                    Action::ExitSearchMode => {
-                        app.clear_pending_key();
-                        app.exit_search_mode();
-                        app.clear_status();
-                    }
-                    Action::AppendChar(c) => {
-                        app.clear_pending_key();
-                        // If a pending key was set and the sequence was broken (e.g. 'j' then 'k'
-                        // with 'jj' as exit sequence), flush the pending char first so both chars
-                        // appear in the search query in the correct order.
-                        if was_search_mode {
-                            if let Some(pending_char) = pending_before {
-                                app.append_search_char(pending_char);
-                            }
-                        }
-                        if app.is_preview_search_active() {
-                            app.append_preview_search_char(c);
-                        } else {
-                            app.append_search_char(c);
-                        }
-                        app.clear_status();
-                    }
-                    Action::Backspace => {
-                        app.clear_pending_key();
-                        if app.is_preview_search_active() {
-                            app.backspace_preview_search();
-                        } else {
-                            app.backspace_search();
-                        }
-                        app.clear_status();
-                    }
-                    Action::ToggleSelection => {
-                        app.clear_pending_key();
-
-                        // If in visual mode, exit it first so individual toggles are preserved
-                        if app.mode() == &AppMode::Visual {
-                            app.exit_visual_mode();
-                        }
-
-                        app.toggle_selection();
-                        let count = app.selected_count();
-                        if count > 0 {
-                            app.show_info(format!("{} file(s) selected", count));
-                        } else {
-                            app.clear_status();
-                        }
-                    }
-                    Action::EnterVisualMode => {
-                        app.clear_pending_key();
-                        app.enter_visual_mode();
-                    }
-                    Action::ExitVisualMode => {
-                        app.clear_pending_key();
-                        app.exit_visual_mode();
-                        let count = app.selected_count();
-                        if count > 0 {
-                            app.show_info(format!("{} file(s) selected", count));
-                        } else {
-                            app.clear_status();
-                        }
-                    }
-                    Action::EnterDownloadMode => {
-                        app.clear_pending_key();
-
-                        // Exit visual mode if we're in it
-                        if app.mode() == &AppMode::Visual {
-                            app.exit_visual_mode();
-                        }
-
-                        // If no files selected, auto-select the current file
-                        if app.selected_count() == 0 {
-                            // Check if current selection is a file (not directory)
-                            let is_file = app.selected_entry().map(|e| !e.is_dir).unwrap_or(false);
-
-                            if is_file {
-                                // Auto-select the current file
-                                app.toggle_selection();
-                            } else {
-                                app.show_warning("Cannot download directories. Select files with Space or 'v' first.");
-                            }
-                        }
-
-                        // Now check if we have files to download
-                        if app.selected_count() == 0 {
-                            // Still no files (was a directory or empty)
-                            if app.selected_entry().is_none() {
-                                app.show_info("No files selected. Select files with Space or 'v' first.");
-                            }
-                        } else if config.download_destinations.is_empty() {
-                            app.show_warning("No download destinations configured. Edit ~/.config/rats3/config.toml");
-                        } else {
-                            app.enter_download_mode();
-                        }
-                    }
-                    Action::ExitDownloadMode => {
-                        app.clear_pending_key();
-                        app.exit_download_mode();
-                        app.clear_status();
-                    }
-                    Action::ConfirmDownload => {
-                        app.clear_pending_key();
-                        let dest_idx = app.download_destination_index();
-                        if let Some(destination) = config.download_destinations.get(dest_idx) {
-                            let selected_paths = app.get_selected_file_paths();
-
-                            // Expand tilde in destination path
-                            let dest_path = expand_tilde(&destination.path);
-
-                            // Check if destination exists, create if needed
-                            if let Err(e) = std::fs::create_dir_all(&dest_path) {
-                                app.show_error(format!("Failed to create directory {}: {}", dest_path.display(), e));
-                                continue;
-                            }
-
-                            // Exit download mode
-                            app.exit_download_mode();
-
-                            // Download files in background with progress tracking
-                            // (progress will be shown in download progress overlay)
-                            let backend_clone = backend.clone();
-                            let dest_path_clone = dest_path.clone();
-
-                            for file_path in selected_paths.clone() {
-                                // Create cancellation channel
-                                let (cancel_tx, mut cancel_rx) = tokio::sync::oneshot::channel();
-
-                                // Initialize download tracking with cancellation support
-                                app.start_download(file_path.clone(), cancel_tx);
-
-                                let backend_clone2 = backend_clone.clone();
-                                let dest_path_clone2 = dest_path_clone.clone();
-                                let file_path_clone = file_path.clone();
-                                let progress_tx_clone = progress_tx.clone();
-
-                                tokio::spawn(async move {
-                                    let file_name = file_path_clone.split('/').last().unwrap_or(&file_path_clone);
-                                    let target_path = dest_path_clone2.join(file_name);
-
-                                    // Create progress callback
-                                    let path_for_callback = file_path_clone.clone();
-                                    let tx_for_callback = progress_tx_clone.clone();
-                                    let progress_callback = Box::new(move |downloaded: u64, total: Option<u64>| {
-                                        let _ = tx_for_callback.send(ProgressMessage::Update {
-                                            path: path_for_callback.clone(),
-                                            downloaded,
-                                            total,
-                                        });
-                                    });
-
-                                    // Download file with cancellation support
-                                    let download_future = backend_clone2.download_file(
-                                        &file_path_clone,
-                                        &target_path,
-                                        Some(progress_callback)
-                                    );
-
-                                    tokio::select! {
-                                        result = download_future => {
-                                            // Download completed (success or error)
-                                            if let Err(e) = result {
-                                                let _ = progress_tx_clone.send(ProgressMessage::Error {
-                                                    path: file_path_clone.clone(),
-                                                    error: e.to_string(),
-                                                });
-                                            } else {
-                                                let _ = progress_tx_clone.send(ProgressMessage::Complete {
-                                                    path: file_path_clone.clone(),
-                                                });
-                                            }
-                                        }
-                                        _ = &mut cancel_rx => {
-                                            // Download was canceled
-                                            // Try to delete the partial file
-                                            let _ = std::fs::remove_file(&target_path);
-
-                                            let _ = progress_tx_clone.send(ProgressMessage::Canceled {
-                                                path: file_path_clone.clone(),
-                                            });
-                                        }
-                                    }
-                                });
-                            }
-
-                            // Clear selection after initiating download
-                            app.clear_selection();
-                        }
-                    }
-                    Action::EnterHistoryMode => {
-                        app.clear_pending_key();
-                        if !app.history().is_empty() {
-                            app.enter_history_mode();
-                        }
-                    }
-                    Action::EnterHistoryModeWithSearch => {
-                        app.clear_pending_key();
-                        if !app.history().is_empty() {
-                            app.enter_history_mode();
-                            app.enter_search_mode();
-                        }
-                    }
-                    Action::ExitHistoryMode => {
-                        app.clear_pending_key();
-                        app.exit_history_mode();
-                        app.clear_status();
-                    }
-                    Action::CopyPath => {
-                        app.clear_pending_key();
-                        let bare = if let Some(entry) = app.selected_entry() {
-                            if app.current_prefix().is_empty() {
-                                entry.name.clone()
-                            } else {
-                                format!("{}/{}", app.current_prefix(), entry.name)
-                            }
-                        } else {
-                            app.current_prefix().to_string()
-                        };
-                        let path = backend.get_display_path(&bare);
-                        match clipboard::copy_to_clipboard(&path) {
-                            Ok(_) => {
-                                app.show_success(format!("Copied to clipboard: {}", path));
-                            }
-                            Err(e) => {
-                                app.show_error(format!("Failed to copy: {}", e));
-                            }
-                        }
-                    }
-                    Action::ToggleWrap => {
-                        app.clear_pending_key();
-                        app.toggle_wrap();
-                        let status = if app.is_wrap_enabled() {
-                            "Text wrapping enabled"
-                        } else {
-                            "Text wrapping disabled"
-                        };
-                        app.show_info(status);
-                    }
-                    Action::FocusPreview => {
-                        app.clear_pending_key();
-                        app.focus_preview();
-                    }
-                    Action::FocusExplorer => {
-                        app.clear_pending_key();
-                        app.focus_explorer();
-                    }
-                    Action::ToggleFocus => {
-                        app.clear_pending_key();
-                        app.toggle_focus();
-                    }
-                    Action::EnterPreviewVisualMode => {
-                        app.clear_pending_key();
-                        app.enter_preview_visual_mode();
-                    }
-                    Action::ExitPreviewVisualMode => {
-                        app.clear_pending_key();
-                        app.exit_preview_visual_mode();
-                    }
-                    Action::YankSelection => {
-                        app.clear_pending_key();
-                        // Get selected lines from preview
-                        if let Some(preview) = app.get_preview() {
-                            match preview {
-                                rats3::backend::PreviewContent::Text(content, _) => {
-                                    let (start, end) = app.get_preview_visual_range();
-                                    let lines: Vec<&str> = content.lines().collect();
-                                    let selected_lines: Vec<&str> = lines.iter()
-                                        .enumerate()
-                                        .filter(|(i, _)| *i >= start && *i <= end)
-                                        .map(|(_, line)| *line)
-                                        .collect();
-                                    let selected_text = selected_lines.join("\n");
-
-                                    match clipboard::copy_to_clipboard(&selected_text) {
-                                        Ok(_) => {
-                                            let line_count = selected_lines.len();
-                                            app.show_success(format!("Copied {} line{} to clipboard",
-                                                line_count,
-                                                if line_count == 1 { "" } else { "s" }));
-                                        }
-                                        Err(e) => {
-                                            app.show_error(format!("Failed to copy: {}", e));
-                                        }
-                                    }
-                                    app.exit_preview_visual_mode();
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    Action::IncreasePreviewWidth => {
-                        app.clear_pending_key();
-                        app.increase_preview_width();
-                    }
-                    Action::DecreasePreviewWidth => {
-                        app.clear_pending_key();
-                        app.decrease_preview_width();
-                    }
-                    Action::ToggleHelp => {
-                        app.clear_pending_key();
-                        app.toggle_help();
-                    }
-                    Action::EnterPreviewSearch => {
-                        app.clear_pending_key();
-                        app.set_preview_search_query(String::new());
-                    }
-                    Action::ExitPreviewSearch => {
-                        app.clear_pending_key();
-                        app.clear_preview_search();
-                    }
-                    Action::PreviewSearchNext => {
-                        app.clear_pending_key();
-                        // Calculate max lines and visible height for scroll limit
-                        if let Some(preview) = app.get_preview() {
-                            let max_lines = match preview {
-                                rats3::backend::PreviewContent::Text(content, _) => content.lines().count(),
-                                _ => 0,
-                            };
-                            let visible_height = terminal.size().unwrap().height.saturating_sub(10) as usize;
-                            app.preview_search_next(max_lines, visible_height);
-                        }
-                    }
-                    Action::PreviewSearchPrev => {
-                        app.clear_pending_key();
-                        // Calculate max lines and visible height for scroll limit
-                        if let Some(preview) = app.get_preview() {
-                            let max_lines = match preview {
-                                rats3::backend::PreviewContent::Text(content, _) => content.lines().count(),
-                                _ => 0,
-                            };
-                            let visible_height = terminal.size().unwrap().height.saturating_sub(10) as usize;
-                            app.preview_search_prev(max_lines, visible_height);
-                        }
-                    }
-                    Action::ConfirmPreviewSearch => {
-                        app.clear_pending_key();
-                        // Calculate max lines and visible height for scroll limit
-                        if let Some(preview) = app.get_preview() {
-                            let max_lines = match preview {
-                                rats3::backend::PreviewContent::Text(content, _) => content.lines().count(),
-                                _ => 0,
-                            };
-                            let visible_height = terminal.size().unwrap().height.saturating_sub(10) as usize;
-                            app.confirm_preview_search(max_lines, visible_height);
-                        }
-                    }
-                    Action::CancelDownloads => {
-                        app.clear_pending_key();
-                        let canceled = app.cancel_all_downloads();
-                        if canceled > 0 {
-                            app.show_info(format!("Canceled {} download(s)", canceled));
-                        }
-                    }
-                    Action::PendingKey(c) => {
-                        app.set_pending_key(c);
-                    }
-                    Action::None => {
-                        app.clear_pending_key();
-                    }
-                }
-            }
-        }
-
-        // Only re-render when something actually changed
-        if dirty {
-            terminal.draw(|f| ui::render(f, &app, &config, &highlighted_cache))?;
-
-            if app.should_quit() {
-                break;
-            }
-        }
+    if let Some(pick) = args.pick {
+        options = options.with_pick(match pick {
+            PickArg::Single => PickMode::Single,
+            PickArg::Multi => PickMode::Multi,
+        });
     }
 
-    Ok((app, backend))
-}
-
-/// Spawn a background task to load the preview for the current selection.
-/// Cancels any previously in-flight preview load first.
-/// Navigation remains responsive while the fetch happens in the background.
-fn spawn_preview_load(
-    app: &mut App,
-    backend: &Arc<dyn Backend>,
-    config: &Config,
-    preview_tx: &mpsc::UnboundedSender<(String, PreviewContent)>,
-    pending_cancel: &mut Option<tokio::sync::oneshot::Sender<()>>,
-) {
-    // Cancel any in-flight load by dropping the old sender
-    *pending_cancel = None;
-
-    if let Some((path, needs_loading)) = app.needs_preview_load() {
-        if needs_loading {
-            // Clear current preview so UI shows "Loading preview..."
-            app.clear_preview();
-
-            let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel::<()>();
-            *pending_cancel = Some(cancel_tx);
+    // Saves the state while running and on the way out, even when running
+    // fails. The demo bucket is gone after exit, so it doesn't belong in the
+    // history.
+    if !demo {
+        options = options.with_autosave();
+    }
 
-            let backend_clone = backend.clone();
-            let max_size = config.preview_max_size;
-            let tx = preview_tx.clone();
+    let outcome = rats3::run(options).await?;
 
-            tokio::spawn(async move {
-                tokio::select! {
-                    result = backend_clone.get_preview(&path, max_size) => {
-                        let content = match result {
-                            Ok(c) => c,
-                            Err(e) => PreviewContent::Error(e.to_string()),
-                        };
-                        let _ = tx.send((path, content));
-                    }
-                    _ = cancel_rx => {
-                        // User moved to another file; discard this result
-                    }
-                }
-            });
-        } else {
-            // Already in cache; just update the current path pointer
-            app.update_current_preview_path(path);
+    // On stderr, so it doesn't mix with picked paths
+    if outcome.usage.has_transfers() {
+        eprintln!("rats3: {}", outcome.usage.summary());
+    }
+    if let Some(file) = &args.print_last_dir {
+        std::fs::write(file, outcome.last_dir())
+            .with_context(|| format!("Cannot write the last location to {}", file.display()))?;
+    }
+    if args.pick.is_some() {
+        // Like fzf, exit with an error when the user quit without picking
+        let Some(picked) = outcome.picked else {
+            std::process::exit(1);
+        };
+        for path in picked {
+            println!("{}", path);
         }
-    } else {
-        // Directory selected or empty list — nothing to preview
-        app.clear_preview();
     }
+    Ok(())
 }
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// How long a plugin whose output goes to a popup may run
+pub const CAPTURE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// An external command bound to a key (`[plugins.<name>]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plugin {
+    /// Key that runs it from the explorer, e.g. "Alt-p"; empty to only run
+    /// it with `:plugin <name>`
+    #[serde(default)]
+    pub key: String,
+
+    /// Shell command; `{files}` is replaced by the URIs of the selected
+    /// entries (the one under the cursor when nothing is selected) and
+    /// `{prefix}` by the URI of the current location
+    pub command: String,
+
+    /// Where the command's output goes: "popup" captures it into a popup,
+    /// "terminal" hands the command the terminal until it exits
+    #[serde(default)]
+    pub output: PluginOutput,
+}
+
+/// Where a plugin's output goes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginOutput {
+    /// Captured and shown in a popup
+    #[default]
+    Popup,
+    /// Run in the terminal, with the browser out of the way
+    Terminal,
+}
+
+/// A plugin about to run on particular files
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginRun {
+    /// Name of the plugin (`[plugins.<name>]`)
+    pub name: String,
+    /// The command as configured
+    pub command: String,
+    /// URIs `{files}` stands for
+    pub files: Vec<String>,
+    /// URI `{prefix}` stands for
+    pub prefix: String,
+    /// AWS profile in use, if any
+    pub profile: Option<String>,
+    /// Where the output goes
+    pub output: PluginOutput,
+}
+
+impl PluginRun {
+    /// The shell invocation. The URIs are passed as "$@" and the location as
+    /// "$RATS3_PREFIX", so they never need quoting.
+    pub fn shell_command(&self) -> tokio::process::Command {
+        let script = self.command.replace("{files}", "\"$@\"").replace("{prefix}", "\"$RATS3_PREFIX\"");
+        let mut command = tokio::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(script)
+            .arg("sh")
+            .args(&self.files)
+            .env("RATS3_PREFIX", &self.prefix)
+            .env("RATS3_PROFILE", self.profile.as_deref().unwrap_or_default());
+        command
+    }
+
+    /// Run with the output captured, giving up after `CAPTURE_TIMEOUT`
+    pub async fn capture(&self) -> CapturedOutput {
+        let output = self
+            .shell_command()
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let output = match tokio::time::timeout(CAPTURE_TIMEOUT, output).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => return CapturedOutput::failed(String::new(), format!("Cannot run {}: {}", self.name, e)),
+            Err(_) => {
+                let error = format!("{} gave no answer within {}s", self.name, CAPTURE_TIMEOUT.as_secs());
+                return CapturedOutput::failed(String::new(), error);
+            }
+        };
+
+        // Errors follow what was printed, as in a terminal
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        if output.status.success() {
+            CapturedOutput { text, error: None }
+        } else {
+            CapturedOutput::failed(text, format!("{} failed ({})", self.name, output.status))
+        }
+    }
+}
+
+/// What a plugin run into a popup printed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedOutput {
+    /// Standard output followed by standard error
+    pub text: String,
+    /// Why the command failed, if it did
+    pub error: Option<String>,
+}
+
+impl CapturedOutput {
+    fn failed(text: String, error: String) -> Self {
+        Self { text, error: Some(error) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(command: &str, files: &[&str]) -> PluginRun {
+        PluginRun {
+            name: "test".to_string(),
+            command: command.to_string(),
+            files: files.iter().map(|file| file.to_string()).collect(),
+            prefix: "s3://bucket/my data".to_string(),
+            profile: Some("dev".to_string()),
+            output: PluginOutput::Popup,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture() {
+        let output = run("for f in {files}; do echo \"$f\"; done; echo {prefix} $RATS3_PROFILE", &["s3://bucket/a b.txt", "s3://bucket/$x"])
+            .capture()
+            .await;
+        assert_eq!(output.text, "s3://bucket/a b.txt\ns3://bucket/$x\ns3://bucket/my data dev\n");
+        assert_eq!(output.error, None);
+
+        let output = run("echo partial; echo oops >&2; exit 3", &[]).capture().await;
+        assert_eq!(output.text, "partial\noops\n");
+        assert_eq!(output.error.as_deref(), Some("test failed (exit status: 3)"));
+    }
+}
@@ -0,0 +1,406 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes shown by the hex handler; a dump of a few MB would be unreadable anyway
+const HEX_DUMP_LIMIT: usize = 64 * 1024;
+
+/// How files with a given extension are previewed (`[preview.handlers]`):
+/// one of the built-in handlers, or a command whose output is shown
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum PreviewHandler {
+    /// Plain text, also for files that look binary (invalid UTF-8 is replaced)
+    Text,
+    /// Hex dump with an ASCII column
+    Hex,
+    /// Table view, comma-separated unless the extension says otherwise
+    Table,
+    /// Image format and dimensions
+    Image,
+    /// Contents of a zip, tar or gzip archive
+    Archive,
+    /// Shell command run on a local copy of the file; `{}` is replaced by its
+    /// path (appended when missing)
+    Command(String),
+}
+
+impl From<String> for PreviewHandler {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "text" => Self::Text,
+            "hex" => Self::Hex,
+            "table" => Self::Table,
+            "image" => Self::Image,
+            "archive" => Self::Archive,
+            _ => Self::Command(value),
+        }
+    }
+}
+
+impl From<PreviewHandler> for String {
+    fn from(handler: PreviewHandler) -> Self {
+        match handler {
+            PreviewHandler::Text => "text".to_string(),
+            PreviewHandler::Hex => "hex".to_string(),
+            PreviewHandler::Table => "table".to_string(),
+            PreviewHandler::Image => "image".to_string(),
+            PreviewHandler::Archive => "archive".to_string(),
+            PreviewHandler::Command(command) => command,
+        }
+    }
+}
+
+impl PreviewHandler {
+    /// Whether the handler needs the file's bytes rather than the backend's preview
+    pub fn reads_file(&self) -> bool {
+        !matches!(self, Self::Table)
+    }
+
+    /// Preview text for the file at `file` (a local copy of the object)
+    pub async fn render(&self, file: &Path) -> Result<String> {
+        match self {
+            Self::Command(command) => run_command(command, file).await,
+            handler => {
+                let bytes = tokio::fs::read(file).await?;
+                let handler = handler.clone();
+                tokio::task::spawn_blocking(move || handler.render_bytes(&bytes)).await?
+            }
+        }
+    }
+
+    fn render_bytes(&self, bytes: &[u8]) -> Result<String> {
+        match self {
+            Self::Text | Self::Table => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            Self::Hex => Ok(hex_dump(bytes)),
+            Self::Image => describe_image(bytes),
+            Self::Archive => list_archive(bytes),
+            Self::Command(_) => unreachable!("commands run on the file"),
+        }
+    }
+}
+
+/// Handler configured for the extension of `path` (matched case-insensitively)
+pub fn handler_for<'a>(handlers: &'a BTreeMap<String, PreviewHandler>, path: &str) -> Option<&'a PreviewHandler> {
+    let name = path.rsplit('/').next().unwrap_or(path).to_lowercase();
+    // The longest matching extension wins, so `tar.gz` can differ from `gz`
+    handlers
+        .iter()
+        .filter(|(ext, _)| name.ends_with(&format!(".{}", ext.trim_start_matches('.').to_lowercase())))
+        .max_by_key(|(ext, _)| ext.len())
+        .map(|(_, handler)| handler)
+}
+
+/// Run `command` through the shell on `file` and return its standard output
+async fn run_command(command: &str, file: &Path) -> Result<String> {
+    // The path is passed as "$1" so it never needs quoting
+    let script = if command.contains("{}") {
+        command.replace("{}", "\"$1\"")
+    } else {
+        format!("{} \"$1\"", command)
+    };
+    let output = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&script)
+        .arg("sh")
+        .arg(file)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("Cannot run {}", command))?;
+    if !output.status.success() && output.stdout.is_empty() {
+        bail!("{} failed: {}", command, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `xxd`-style dump: offset, 16 bytes in groups of two, and their ASCII
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in bytes[..bytes.len().min(HEX_DUMP_LIMIT)].chunks(16).enumerate() {
+        let mut hex = String::new();
+        for (i, byte) in chunk.iter().enumerate() {
+            if i > 0 && i % 2 == 0 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        out.push_str(&format!("{:08x}: {:<39}  {}\n", row * 16, hex, ascii));
+    }
+    if bytes.len() > HEX_DUMP_LIMIT {
+        out.push_str(&format!("... {} more bytes\n", bytes.len() - HEX_DUMP_LIMIT));
+    }
+    out
+}
+
+/// Format and dimensions of a PNG, GIF, JPEG, BMP or WebP image
+pub fn describe_image(bytes: &[u8]) -> Result<String> {
+    let le16 = |at: usize| bytes.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+    let be32 = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let le32 = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    let (format, size) = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        ("PNG", be32(16).zip(be32(20)))
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        ("GIF", le16(6).zip(le16(8)))
+    } else if bytes.starts_with(b"BM") {
+        // Height is negative for top-down bitmaps
+        ("BMP", le32(18).zip(le32(22).map(|h| (h as i32).unsigned_abs())))
+    } else if bytes.starts_with(b"\xff\xd8") {
+        ("JPEG", jpeg_size(bytes))
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        let size = match bytes.get(12..16) {
+            Some(b"VP8 ") => le16(26).zip(le16(28)).map(|(w, h)| (w & 0x3fff, h & 0x3fff)),
+            Some(b"VP8L") => le32(21).map(|bits| ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1)),
+            Some(b"VP8X") => {
+                let le24 = |at: usize| bytes.get(at..at + 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]) + 1);
+                le24(24).zip(le24(27))
+            }
+            _ => None,
+        };
+        ("WebP", size)
+    } else {
+        bail!("Not a PNG, GIF, JPEG, BMP or WebP image");
+    };
+    Ok(match size {
+        Some((width, height)) => format!("{} image, {} × {} pixels\n", format, width, height),
+        None => format!("{} image, dimensions unknown\n", format),
+    })
+}
+
+/// Dimensions from the first start-of-frame segment of a JPEG
+fn jpeg_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    let mut at = 2;
+    while at + 9 < bytes.len() {
+        if bytes[at] != 0xff {
+            return None;
+        }
+        let marker = bytes[at + 1];
+        let length = u16::from_be_bytes([bytes[at + 2], bytes[at + 3]]) as usize;
+        // SOF0..SOF15, except DHT (c4), JPG (c8) and DAC (cc)
+        if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let height = u16::from_be_bytes([bytes[at + 5], bytes[at + 6]]) as u32;
+            let width = u16::from_be_bytes([bytes[at + 7], bytes[at + 8]]) as u32;
+            return Some((width, height));
+        }
+        at += 2 + length;
+    }
+    None
+}
+
+/// One line per member of a zip, tar, gzipped tar or gzip file: size and name
+pub fn list_archive(bytes: &[u8]) -> Result<String> {
+    let members = if bytes.starts_with(b"PK") {
+        zip_members(bytes)?
+    } else if bytes.starts_with(b"\x1f\x8b") {
+        let mut data = Vec::new();
+        flate2::read::MultiGzDecoder::new(bytes).read_to_end(&mut data).context("Invalid gzip data")?;
+        if is_tar(&data) {
+            tar_members(&data)
+        } else {
+            vec![(data.len() as u64, "(gzip-compressed file)".to_string())]
+        }
+    } else if is_tar(bytes) {
+        tar_members(bytes)
+    } else {
+        bail!("Not a zip, tar or gzip archive");
+    };
+
+    let total: u64 = members.iter().map(|(size, _)| size).sum();
+    let mut out = format!("{} entries, {} bytes uncompressed\n\n", members.len(), total);
+    for (size, name) in members {
+        out.push_str(&format!("{:>12}  {}\n", size, name));
+    }
+    Ok(out)
+}
+
+fn is_tar(bytes: &[u8]) -> bool {
+    bytes.get(257..262) == Some(b"ustar")
+}
+
+/// Members of a tar archive from their 512-byte headers
+fn tar_members(bytes: &[u8]) -> Vec<(u64, String)> {
+    let field = |header: &[u8], range: std::ops::Range<usize>| {
+        let raw = &header[range];
+        let end = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+        String::from_utf8_lossy(&raw[..end]).trim().to_string()
+    };
+    let mut members = Vec::new();
+    let mut at = 0;
+    while let Some(header) = bytes.get(at..at + 512) {
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = u64::from_str_radix(&field(header, 124..136), 8).unwrap_or(0);
+        let mut name = field(header, 0..100);
+        let prefix = field(header, 345..500);
+        if !prefix.is_empty() {
+            name = format!("{}/{}", prefix, name);
+        }
+        // Regular files and directories; links, PAX and GNU long-name headers are skipped
+        if matches!(header[156], b'0' | 0 | b'5') {
+            members.push((size, name));
+        }
+        at += 512 + (size as usize).div_ceil(512) * 512;
+    }
+    members
+}
+
+/// Members of a zip archive from its central directory
+fn zip_members(bytes: &[u8]) -> Result<Vec<(u64, String)>> {
+    let le16 = |at: usize| bytes.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+    let le32 = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+    // The end-of-central-directory record is followed by a comment of up to 64 KiB
+    let eocd = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .take(22 + 65535)
+        .find(|&at| bytes[at..].starts_with(b"PK\x05\x06"))
+        .context("Zip central directory not found (is the archive complete?)")?;
+    let count = le16(eocd + 10).unwrap_or(0);
+    let mut at = le32(eocd + 16).unwrap_or(0);
+
+    let mut members = Vec::with_capacity(count);
+    for _ in 0..count {
+        if !bytes[at.min(bytes.len())..].starts_with(b"PK\x01\x02") {
+            bail!("Corrupt zip central directory");
+        }
+        let (Some(size), Some(name_len), Some(extra_len), Some(comment_len)) =
+            (le32(at + 24), le16(at + 28), le16(at + 30), le16(at + 32))
+        else {
+            bail!("Corrupt zip central directory");
+        };
+        let name = bytes.get(at + 46..at + 46 + name_len).context("Corrupt zip central directory")?;
+        members.push((size as u64, String::from_utf8_lossy(name).into_owned()));
+        at += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handler_for_extension() {
+        let handlers: BTreeMap<String, PreviewHandler> = [
+            ("gz", "archive"),
+            ("tar.gz", "tar tzf"),
+            ("PDF", "pdftotext {} -"),
+        ]
+        .into_iter()
+        .map(|(ext, handler)| (ext.to_string(), PreviewHandler::from(handler.to_string())))
+        .collect();
+
+        assert_eq!(handler_for(&handlers, "data/report.pdf"), Some(&PreviewHandler::Command("pdftotext {} -".to_string())));
+        assert_eq!(handler_for(&handlers, "logs/app.log.gz"), Some(&PreviewHandler::Archive));
+        assert_eq!(handler_for(&handlers, "dist/src.TAR.GZ"), Some(&PreviewHandler::Command("tar tzf".to_string())));
+        assert_eq!(handler_for(&handlers, "gz/notes.txt"), None);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDRxyz");
+        assert_eq!(
+            dump,
+            "00000000: 8950 4e47 0d0a 1a0a 0000 000d 4948 4452  .PNG........IHDR\n\
+             00000010: 7879 7a                                  xyz\n"
+        );
+    }
+
+    #[test]
+    fn test_describe_image() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        assert_eq!(describe_image(&png).unwrap(), "PNG image, 640 × 480 pixels\n");
+
+        let gif = b"GIF89a\x0a\x00\x14\x00";
+        assert_eq!(describe_image(gif).unwrap(), "GIF image, 10 × 20 pixels\n");
+
+        // SOI, an APP0 segment, then SOF0 with height 2 and width 3
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x04ab\xff\xc0\x00\x0b\x08\x00\x02\x00\x03\x01";
+        assert_eq!(describe_image(jpeg).unwrap(), "JPEG image, 3 × 2 pixels\n");
+
+        assert!(describe_image(b"plain text").is_err());
+    }
+
+    fn tar_header(name: &str, size: usize) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}", size);
+        header[124..135].copy_from_slice(size.as_bytes());
+        header[156] = b'0';
+        header[257..262].copy_from_slice(b"ustar");
+        header
+    }
+
+    #[test]
+    fn test_list_tar_and_gzip() {
+        let mut tar = tar_header("a.txt", 3);
+        tar.extend(b"abc");
+        tar.resize(1024, 0);
+        tar.extend(tar_header("dir/b.txt", 0));
+        tar.resize(tar.len() + 1024, 0);
+        assert_eq!(list_archive(&tar).unwrap(), "2 entries, 3 bytes uncompressed\n\n           3  a.txt\n           0  dir/b.txt\n");
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &tar).unwrap();
+        let gz = encoder.finish().unwrap();
+        assert!(list_archive(&gz).unwrap().contains("dir/b.txt"));
+    }
+
+    #[test]
+    fn test_list_zip() {
+        // A stored (uncompressed) zip with one member, "hi.txt" containing "hello"
+        let name = b"hi.txt";
+        let mut zip = Vec::new();
+        zip.extend(b"PK\x03\x04");
+        zip.extend([0u8; 14]);
+        zip.extend(5u32.to_le_bytes());
+        zip.extend(5u32.to_le_bytes());
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+        zip.extend(name);
+        zip.extend(b"hello");
+        let directory = zip.len() as u32;
+        zip.extend(b"PK\x01\x02");
+        zip.extend([0u8; 16]);
+        zip.extend(5u32.to_le_bytes());
+        zip.extend(5u32.to_le_bytes());
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend([0u8; 12]);
+        zip.extend(0u32.to_le_bytes());
+        zip.extend(name);
+        let directory_size = zip.len() as u32 - directory;
+        zip.extend(b"PK\x05\x06");
+        zip.extend([0u8; 4]);
+        zip.extend(1u16.to_le_bytes());
+        zip.extend(1u16.to_le_bytes());
+        zip.extend(directory_size.to_le_bytes());
+        zip.extend(directory.to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+
+        assert_eq!(list_archive(&zip).unwrap(), "1 entries, 5 bytes uncompressed\n\n           5  hi.txt\n");
+    }
+
+    #[tokio::test]
+    async fn test_command_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("it's here.txt");
+        std::fs::write(&file, "one\ntwo\n").unwrap();
+
+        let handler = PreviewHandler::from("wc -l <".to_string());
+        assert_eq!(handler.render(&file).await.unwrap().trim(), "2");
+        let handler = PreviewHandler::from("head -n 1 {} | tr a-z A-Z".to_string());
+        assert_eq!(handler.render(&file).await.unwrap(), "ONE\n");
+        assert!(PreviewHandler::from("false".to_string()).render(&file).await.is_err());
+    }
+}
@@ -0,0 +1,2545 @@
+use crate::app::{
+    App, AppEvent, AppMode, DiffView, Effect, FocusedPanel, GuardedOperation, ListingSource, NavigateDirection,
+    PendingOperation, RestoreTaskState,
+};
+use crate::audit::{AuditLog, AuditOperation, AuditOutcome, AuditRecord};
+use crate::chosen::ChosenFiles;
+use crate::backend::registry::{expand_tilde, BackendRegistry};
+use crate::backend::{self, Backend, Entry, FileMetadata, ListResult, ObjectVersion, PreviewContent, RestoreState};
+use crate::clipboard;
+use crate::config::{Config, LocationContext};
+use crate::diff;
+use crate::grep;
+use crate::history;
+use crate::ipc::{ControlCommand, Responder};
+use crate::events::Action;
+use crate::listing_cache::ListingCache;
+use crate::plugin::PluginOutput;
+use crate::preview_handler::PreviewHandler;
+use crate::session::{Session, DEFAULT_SESSION};
+use crate::state::HistoryEntry;
+use crate::summary::PrefixSummary;
+use crate::sync::{self, RemoteFile, SyncDirection, SyncPlan, UploadReport};
+use crate::table;
+use crate::transfer::{self, DownloadSource};
+use crate::ui;
+use anyhow::{Context, Result};
+use std::{path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, time::Duration};
+use ratatui::layout::Rect;
+use syntect::highlighting::Theme;
+use tokio::sync::mpsc;
+
+/// How often a running `:du` scan reports partial totals
+const SUMMARY_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often a running `:grep` reports the matches found since the last update
+const GREP_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often to poll the state of a pending archive restore
+const RESTORE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most single-child directories passed through by one auto-descend
+const AUTO_DESCEND_MAX_DEPTH: usize = 32;
+
+/// How often a running load-all of the flat view reports the keys fetched
+const FLAT_LOAD_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Most recent audit records shown by `:log`
+const AUDIT_LOG_VIEW_LIMIT: usize = 1000;
+
+/// Lines of a preview highlighted in the background before they're shown;
+/// the rest of the file stays plain until its chunk arrives
+const HIGHLIGHT_CHUNK_LINES: usize = 500;
+
+/// Longest a `pre_operation_hook` may take before the operation is refused
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs key actions and effects against the backend, and the background
+/// tasks they start. Shared by the terminal event loop and the test harness.
+pub struct Runtime {
+    backend: Arc<dyn Backend>,
+    config: Config,
+    /// Background tasks post their results here
+    events: mpsc::UnboundedSender<AppEvent>,
+    /// Dropping this cancels the preview load in flight
+    pending_preview_cancel: Option<tokio::sync::oneshot::Sender<()>>,
+    /// Recent listings, reused when navigating back to a prefix. Shared with
+    /// prefetch tasks.
+    listings: Arc<Mutex<ListingCache>>,
+    /// Source of the last listing, handed to the app once it shows it
+    last_listing: Option<ListingSource>,
+    /// Opens the backends of URIs switched to (history, contexts)
+    registry: Arc<BackendRegistry>,
+    /// List every file below a prefix instead of its directories and files
+    flat: bool,
+    /// Whether keys follow the last flat listing, handed to the app with it
+    last_flat_more: Option<bool>,
+    /// Key counts of the listings fetched since the last event, handed to the
+    /// app to count them
+    fetched_listings: Vec<usize>,
+    /// Where transfers, deletes and restores are recorded
+    audit: AuditLog,
+    /// Where the URIs of opened, picked and downloaded files are appended
+    chosen: ChosenFiles,
+    /// Theme previews are syntax-highlighted with
+    syntax_theme: Arc<Theme>,
+    /// Listing awaited by `dispatch`, watched by the event loop to show a spinner
+    listing: InFlight,
+    /// Whether a listing of the effect being run failed because the
+    /// credentials expired
+    credentials_expired: bool,
+}
+
+/// When the request a runtime is waiting on started, if it is waiting on one.
+/// Clones share the request, so the event loop can watch it while `dispatch`
+/// holds the runtime.
+#[derive(Debug, Clone, Default)]
+pub struct InFlight(Arc<Mutex<Option<std::time::Instant>>>);
+
+impl InFlight {
+    /// How long the request in flight has been running
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.0.lock().unwrap().map(|started| started.elapsed())
+    }
+
+    /// Mark a request as in flight until the returned guard is dropped
+    fn start(&self) -> InFlightGuard {
+        *self.0.lock().unwrap() = Some(std::time::Instant::now());
+        InFlightGuard(self.clone())
+    }
+}
+
+/// Clears its `InFlight` when the request finishes or its future is dropped
+struct InFlightGuard(InFlight);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        *self.0 .0.lock().unwrap() = None;
+    }
+}
+
+impl Runtime {
+    /// Create a runtime and the channel its background tasks report through
+    pub fn new(backend: Arc<dyn Backend>, config: Config) -> (Self, mpsc::UnboundedReceiver<AppEvent>) {
+        let (events, event_rx) = mpsc::unbounded_channel();
+        let listings = Arc::new(Mutex::new(ListingCache::new(Duration::from_secs(config.listing_cache_ttl_secs))));
+        let runtime = Self {
+            backend,
+            config,
+            events,
+            pending_preview_cancel: None,
+            listings,
+            last_listing: None,
+            registry: Arc::new(BackendRegistry::default()),
+            flat: false,
+            last_flat_more: None,
+            fetched_listings: Vec::new(),
+            audit: AuditLog::default(),
+            chosen: ChosenFiles::default(),
+            syntax_theme: ui::widgets::preview::DEFAULT_THEME.clone(),
+            listing: InFlight::default(),
+            credentials_expired: false,
+        };
+        (runtime, event_rx)
+    }
+
+    /// Open URIs through `registry` instead of the built-in backends only
+    pub fn set_registry(&mut self, registry: Arc<BackendRegistry>) {
+        self.registry = registry;
+    }
+
+    /// Record transfers, deletes and restores in `audit` (nothing is recorded
+    /// by default)
+    pub fn set_audit_log(&mut self, audit: AuditLog) {
+        self.audit = audit;
+    }
+
+    /// Append the URIs of the files opened, picked or downloaded to `chosen`
+    /// (nothing is recorded by default)
+    pub fn set_chosen_files(&mut self, chosen: ChosenFiles) {
+        self.chosen = chosen;
+    }
+
+    /// Syntax-highlight previews with `theme` instead of the default one
+    pub fn set_syntax_theme(&mut self, theme: Theme) {
+        self.syntax_theme = Arc::new(theme);
+    }
+
+    /// Backend currently browsed (history navigation can switch it)
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Sender for events from outside the runtime (the control socket)
+    pub fn event_sender(&self) -> mpsc::UnboundedSender<AppEvent> {
+        self.events.clone()
+    }
+
+    /// The listing request `dispatch` is waiting on
+    pub fn listing_in_flight(&self) -> InFlight {
+        self.listing.clone()
+    }
+
+    /// Clean up after a `dispatch` dropped while it waited on a listing: the
+    /// app keeps showing the view it had before the listing started
+    pub fn cancel_listing(&mut self, app: &mut App) {
+        self.flat = app.is_flat_view();
+        self.last_listing = None;
+        self.last_flat_more = None;
+        app.show_info("Listing canceled");
+    }
+
+    pub fn into_backend(self) -> Arc<dyn Backend> {
+        self.backend
+    }
+
+    /// List `prefix` of the current backend, from the cache while it's fresh
+    pub async fn list(&mut self, prefix: &str) -> Result<ListResult> {
+        let backend = self.backend.clone();
+        self.list_with(&backend, prefix).await
+    }
+
+    /// List `prefix` of `backend`, from the cache while it's fresh. The flat
+    /// view lists the first page of files below `prefix` and isn't cached.
+    pub async fn list_with(&mut self, backend: &Arc<dyn Backend>, prefix: &str) -> Result<ListResult> {
+        let started = std::time::Instant::now();
+        if self.flat {
+            let _in_flight = self.listing.start();
+            let page = list_flat_page(backend, prefix, None, self.config.flat_page_size);
+            let (result, more) = self.request(page).await?;
+            self.last_listing = Some(ListingSource::Fetched(started.elapsed()));
+            self.last_flat_more = Some(more);
+            self.fetched_listings.push(result.entries.len());
+            return Ok(result);
+        }
+        let key = backend.get_display_path(prefix);
+        if let Some(result) = self.listings.lock().unwrap().get(&key) {
+            self.last_listing = Some(ListingSource::Cached);
+            return Ok(result);
+        }
+        let _in_flight = self.listing.start();
+        let result = self.request(backend.list(prefix)).await?;
+        self.last_listing = Some(ListingSource::Fetched(started.elapsed()));
+        self.fetched_listings.push(result.entries.len());
+        self.listings.lock().unwrap().insert(key, result.clone());
+        Ok(result)
+    }
+
+    /// Await a backend request with the configured timeout, noting when it
+    /// failed because the credentials expired
+    async fn request<T>(&mut self, request: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        let result = with_timeout(self.config.request_timeout_secs, request).await;
+        if result.as_ref().is_err_and(backend::is_auth_error) {
+            self.credentials_expired = true;
+        }
+        result
+    }
+
+    /// List `prefix`; with `auto_descend`, keep going into the only entry of
+    /// the listing while it is a directory. Returns the listing reached and
+    /// the names of the directories passed into beyond `prefix`.
+    pub async fn list_descending(&mut self, prefix: &str) -> Result<(ListResult, Vec<String>)> {
+        let mut result = self.list(prefix).await?;
+        let mut skipped = Vec::new();
+        while self.config.auto_descend && skipped.len() < AUTO_DESCEND_MAX_DEPTH {
+            let [entry] = result.entries.as_slice() else {
+                break;
+            };
+            if !entry.is_dir {
+                break;
+            }
+            let name = entry.name.clone();
+            let next = if result.prefix.is_empty() { name.clone() } else { format!("{}/{}", result.prefix, name) };
+            // Stop at the last directory that could be listed
+            let Ok(next_result) = self.list(&next).await else {
+                break;
+            };
+            skipped.push(name);
+            result = next_result;
+        }
+        Ok((result, skipped))
+    }
+
+    /// Load the preview of the selected file in the background
+    pub fn load_preview(&mut self, app: &mut App) {
+        spawn_preview_load(app, self);
+    }
+
+    /// Start the background filters (fuzzy and jq) the app is waiting for, if any
+    pub fn start_filter(&mut self, app: &mut App) {
+        if let Some(job) = app.take_filter_job() {
+            // Matching is CPU-bound; keep it off the async workers
+            let tx = self.events.clone();
+            std::thread::spawn(move || job.run(|update| tx.send(AppEvent::Filtered(update)).is_ok()));
+        }
+        if let Some(job) = app.take_jq_job() {
+            // jq filters can run long; a canceled one reports nothing
+            let tx = self.events.clone();
+            std::thread::spawn(move || {
+                if let Some(result) = job.run() {
+                    let _ = tx.send(AppEvent::JqFiltered(result));
+                }
+            });
+        }
+    }
+
+    /// Apply an event to the app and run the resulting effects. `area` is the
+    /// terminal's. Returns whether the screen needs to be redrawn.
+    pub async fn dispatch(&mut self, app: &mut App, event: AppEvent, area: Rect) -> Result<bool> {
+        let outcome = app.handle_event(event);
+        for effect in outcome.effects {
+            run_effect(app, self, effect, area).await?;
+        }
+        self.start_filter(app);
+        if let Some(source) = self.last_listing.take() {
+            app.set_listing_source(source);
+        }
+        if let Some(more) = self.last_flat_more.take() {
+            app.start_flat_paging(more);
+        }
+        for keys in self.fetched_listings.drain(..) {
+            app.record_listing(keys);
+        }
+        Ok(outcome.redraw)
+    }
+}
+
+/// Open the location of a named context: its backend and the prefix to start at
+pub async fn open_context(registry: &BackendRegistry, context: &LocationContext) -> Result<(Arc<dyn Backend>, String)> {
+    registry.open(&context.uri, context.profile.as_deref()).await
+}
+
+/// Open a location picked from the history, switching backends (with the
+/// profile it was visited with) if it's on another one
+async fn open_history_entry(app: &mut App, rt: &mut Runtime, entry: &HistoryEntry) {
+    if let Err(e) = open_location(app, rt, &entry.uri, entry.profile.as_deref()).await {
+        app.show_error(e);
+    }
+}
+
+/// Open the location `uri`, switching backends (with `profile`) if it's on
+/// another one. Returns the message to show when it can't be opened.
+async fn open_location(app: &mut App, rt: &mut Runtime, uri: &str, profile: Option<&str>) -> Result<(), String> {
+    let from = app.current_location();
+    let nav_prefix = if let Some(prefix) = rt.backend.uri_to_prefix(uri) {
+        // Same backend
+        prefix
+    } else {
+        // Different backend — try to switch
+        let (new_backend, prefix) =
+            rt.registry.open(uri, profile).await.map_err(|e| format!("Cannot switch backend: {}", e))?;
+        rt.backend = new_backend;
+        app.set_backend(rt.backend.clone());
+        app.set_active_context(None);
+        prefix
+    };
+
+    app.exit_history_mode();
+    let result = rt.list(&nav_prefix).await.map_err(|e| format!("Error: {}", e))?;
+    app.record_location(from);
+    app.update_entries(result);
+    app.clear_status();
+    // Re-add to history to bump it to the top
+    app.remember_location(&nav_prefix);
+    // Load preview for first item
+    spawn_preview_load(app, rt);
+    Ok(())
+}
+
+/// Download the selected files into `dest_path`, asking first when the
+/// confirmations call for it. Returns the number of files, or the message to
+/// show when the directory can't be created.
+fn download_selection(app: &mut App, rt: &mut Runtime, dest_path: &Path) -> Result<usize, String> {
+    let selected_paths = app.get_selected_file_paths();
+
+    // Check if destination exists, create if needed
+    std::fs::create_dir_all(dest_path)
+        .map_err(|e| format!("Failed to create directory {}: {}", dest_path.display(), e))?;
+
+    // Exit download mode
+    if app.mode() == &AppMode::Download {
+        app.exit_download_mode();
+    }
+
+    // Files with the same name would overwrite each other
+    let (targets, renamed) = transfer::download_targets(&selected_paths, dest_path);
+    if renamed > 0 {
+        app.show_warning(format!("Renamed {} file(s) with duplicate names", renamed));
+    }
+
+    let count = targets.len();
+    let confirmations = &rt.config.confirmations;
+    let size = app.selected_files_size();
+    let existing = targets.iter().filter(|(_, target)| target.exists()).count();
+    let large = confirmations.download_needs_confirmation(targets.len(), size);
+    if large || (confirmations.overwrites && existing > 0) {
+        let cost = confirmations
+            .egress_cost(size)
+            .map(|cost| format!(", ≈ ${:.2} egress", cost))
+            .unwrap_or_default();
+        let mut question = format!(
+            "Download {} file(s) ({}{}) to {}",
+            targets.len(),
+            ui::text_utils::format_size(size),
+            cost,
+            dest_path.display()
+        );
+        if existing > 0 {
+            question.push_str(&format!(", replacing {} existing file(s)", existing));
+        }
+        question.push('?');
+        app.ask_confirmation(question, PendingOperation::Download(targets));
+    } else {
+        start_downloads(app, rt, targets);
+    }
+
+    // Clear selection after initiating download
+    app.clear_selection();
+    Ok(count)
+}
+
+/// Carry out a command from the control socket and answer it
+async fn run_control(app: &mut App, rt: &mut Runtime, command: ControlCommand, responder: Responder) {
+    let reply = match command {
+        ControlCommand::Navigate(target) => {
+            // Anything but a URI or path is a prefix of the current backend
+            let uri = if target.contains("://") || target.starts_with('/') || target.starts_with('~') {
+                expand_tilde(&target).to_string_lossy().into_owned()
+            } else {
+                rt.backend.get_display_path(target.trim_matches('/'))
+            };
+            let profile = rt.backend.profile();
+            match open_location(app, rt, &uri, profile.as_deref()).await {
+                Ok(()) => Ok(rt.backend.get_display_path(app.current_prefix()).into()),
+                Err(e) => Err(e),
+            }
+        }
+        ControlCommand::Download(destination) => {
+            let destinations = rt.config.download_destinations_for(&app.location_name());
+            let dest_path = match destination {
+                Some(name_or_path) => match destinations.iter().find(|d| d.name == name_or_path) {
+                    Some(destination) => Some(expand_tilde(&destination.path)),
+                    None => Some(expand_tilde(&name_or_path)),
+                },
+                None => destinations.first().map(|destination| expand_tilde(&destination.path)),
+            };
+            if app.selected_count() == 0 && app.selected_entry().is_some_and(|entry| !entry.is_dir) {
+                app.toggle_selection();
+            }
+            match dest_path {
+                None => Err("No download destination configured; name a directory".to_string()),
+                Some(_) if app.selected_count() == 0 => Err("No files selected".to_string()),
+                Some(dest_path) => download_selection(app, rt, &dest_path)
+                    .map(|count| serde_json::json!({ "files": count, "destination": dest_path.display().to_string() })),
+            }
+        }
+        // Answered by the app
+        ControlCommand::GetSelection | ControlCommand::GetLocation | ControlCommand::Quit => return,
+    };
+    responder.send(reply);
+}
+
+/// Switch to the named context, asking first if backend switches need confirmation
+async fn request_context_switch(app: &mut App, rt: &mut Runtime, name: &str) {
+    if rt.config.confirmations.backend_switches {
+        app.ask_confirmation(format!("Switch to context {}?", name), PendingOperation::SwitchContext(name.to_string()));
+    } else {
+        switch_context(app, rt, name).await;
+    }
+}
+
+/// Switch to the named context from the config
+async fn switch_context(app: &mut App, rt: &mut Runtime, name: &str) {
+    let Some(context) = rt.config.contexts.get(name).cloned() else {
+        app.show_error(format!("Unknown context: {}", name));
+        return;
+    };
+    let (backend, prefix) = match open_context(&rt.registry, &context).await {
+        Ok(opened) => opened,
+        Err(e) => {
+            app.show_error(format!("Cannot open context {}: {:#}", name, e));
+            return;
+        }
+    };
+    match rt.list_with(&backend, &prefix).await {
+        Ok(result) => {
+            app.record_location(app.current_location());
+            rt.backend = backend;
+            app.set_backend(rt.backend.clone());
+            app.update_entries(result);
+            app.set_active_context(Some(name.to_string()));
+            app.remember_location(&prefix);
+            app.show_info(format!("Switched to context {}", name));
+            spawn_preview_load(app, rt);
+        }
+        Err(e) => app.show_error(format!("Cannot open context {}: {:#}", name, e)),
+    }
+}
+
+/// Path of a file below `prefix` relative to it (`2024/01/events.json`), the
+/// name it has in the flat view
+fn flat_name(prefix: &str, path: &str) -> String {
+    match prefix.trim_end_matches('/') {
+        "" => path.to_string(),
+        base => path.strip_prefix(base).and_then(|rest| rest.strip_prefix('/')).unwrap_or(path).to_string(),
+    }
+}
+
+/// Fail `request` with a timeout error if it takes longer than `secs` seconds
+/// (0 = no limit), so a hung backend call doesn't block the app forever
+async fn with_timeout<T>(secs: u64, request: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    if secs == 0 {
+        return request.await;
+    }
+    match tokio::time::timeout(Duration::from_secs(secs), request).await {
+        Ok(result) => result,
+        Err(_) => anyhow::bail!("request timed out after {}s", secs),
+    }
+}
+
+/// List up to `max` files below `prefix` whose paths sort after `after`, as
+/// one flat listing. Returns whether more files follow.
+async fn list_flat_page(
+    backend: &Arc<dyn Backend>,
+    prefix: &str,
+    after: Option<&str>,
+    max: usize,
+) -> Result<(ListResult, bool)> {
+    let page = backend.walk_page(prefix, after, max.max(1)).await?;
+    let entries = page
+        .files
+        .into_iter()
+        .map(|(path, entry)| Entry { name: flat_name(prefix, &path), ..entry })
+        .collect();
+    Ok((ListResult { entries, prefix: prefix.to_string() }, page.more))
+}
+
+/// Carry out work the reducer handed back
+async fn run_effect(app: &mut App, rt: &mut Runtime, effect: Effect, area: Rect) -> Result<()> {
+    // An action whose listing failed because the credentials expired is
+    // retried once they are refreshed
+    let retry = match &effect {
+        Effect::Action { action, .. } => Some(action.clone()),
+        _ => None,
+    };
+    match effect {
+        Effect::Action { action, flush } => handle_action(app, rt, action, flush, area).await?,
+        Effect::HighlightPreview { path, text } => highlight_preview(rt, path, text),
+        Effect::RefreshListing => {
+            let _ = refresh_listing(app, rt).await;
+        }
+        Effect::Prefetch { prefixes, files } => prefetch(rt, prefixes, files),
+        Effect::CredentialsExpired { retry } => offer_login(app, rt, retry),
+        Effect::Reconnect { retry } => reconnect(app, rt, retry, area).await?,
+        Effect::Control { command, responder } => run_control(app, rt, command, responder).await,
+        Effect::RunGuarded(operation) => run_guarded(app, rt, operation),
+    }
+    if std::mem::take(&mut rt.credentials_expired) {
+        offer_login(app, rt, retry);
+    }
+    Ok(())
+}
+
+/// The credential command with the profile in use filled in, unless it's
+/// disabled
+fn credential_command(rt: &Runtime) -> Option<String> {
+    let command = rt.config.credential_command.trim();
+    let profile = rt.backend.profile().unwrap_or_else(|| "default".to_string());
+    (!command.is_empty()).then(|| command.replace("{profile}", &profile))
+}
+
+/// Carry out `operation` once the `pre_operation_hook` allows it: right away
+/// without a hook (or nothing for it to judge), otherwise when its answer
+/// arrives, so a slow hook doesn't hold up the UI
+fn check_then_run(app: &mut App, rt: &Runtime, operation: GuardedOperation) {
+    let command = rt.config.pre_operation_hook.trim().to_string();
+    let Some((checked, location, paths)) = hook_request(rt, &operation).filter(|_| !command.is_empty()) else {
+        run_guarded(app, rt, operation);
+        return;
+    };
+    app.show_info(format!("Asking pre_operation_hook about the {}...", checked.name()));
+    let profile = rt.backend.profile().unwrap_or_default();
+    let tx = rt.events.clone();
+    tokio::spawn(async move {
+        let verdict = check_operation(&command, checked, &location, &paths, &profile).await;
+        let _ = tx.send(AppEvent::HookAnswered { operation, verdict });
+    });
+}
+
+/// What the `pre_operation_hook` is asked about `operation`: the operation,
+/// its location and the paths it changes; None if it changes nothing remote
+/// or local that needs a say (a sync that only downloads)
+fn hook_request(rt: &Runtime, operation: &GuardedOperation) -> Option<(AuditOperation, String, Vec<String>)> {
+    match operation {
+        GuardedOperation::Sync(plan) if plan.direction == SyncDirection::Upload => {
+            let paths = plan.transfers.iter().map(|transfer| rt.backend.get_display_path(&transfer.remote)).collect();
+            Some((AuditOperation::Upload, plan.location.clone(), paths))
+        }
+        GuardedOperation::Sync(plan) if plan.deletions.is_empty() => None,
+        GuardedOperation::Sync(plan) => {
+            let paths = plan.deletions.iter().map(|path| path.display().to_string()).collect();
+            Some((AuditOperation::Delete, plan.local_dir.display().to_string(), paths))
+        }
+        GuardedOperation::Restore(path) => {
+            let source = rt.backend.get_display_path(path);
+            Some((AuditOperation::Restore, source.clone(), vec![source]))
+        }
+    }
+}
+
+/// Carry out an operation the `pre_operation_hook` allowed
+fn run_guarded(app: &mut App, rt: &Runtime, operation: GuardedOperation) {
+    match operation {
+        GuardedOperation::Sync(plan) if plan.direction == SyncDirection::Upload => {
+            app.show_info(format!("Uploading {} file(s) to {}...", plan.transfers.len(), plan.location));
+            tokio::spawn(run_upload(
+                rt.backend.clone(),
+                plan,
+                rt.config.upload_concurrency,
+                rt.audit.clone(),
+                rt.events.clone(),
+            ));
+        }
+        GuardedOperation::Sync(plan) => {
+            let mut errors = Vec::new();
+            let mut deleted = 0;
+            for path in &plan.deletions {
+                let source = path.display().to_string();
+                match std::fs::remove_file(path) {
+                    Ok(()) => {
+                        let _ = rt.audit.record(&AuditRecord::new(AuditOperation::Delete, source, AuditOutcome::Ok));
+                        deleted += 1;
+                    }
+                    Err(e) => {
+                        let _ = rt.audit.record(&AuditRecord::failed(AuditOperation::Delete, source, e.to_string()));
+                        errors.push(format!("{}: {}", path.display(), e));
+                    }
+                }
+            }
+
+            let mut started = 0;
+            for transfer in &plan.transfers {
+                if let Some(parent) = transfer.local.parent() {
+                    if let Err(e) = std::fs::create_dir_all(parent) {
+                        errors.push(format!("{}: {}", parent.display(), e));
+                        return;
+                    }
+                }
+                spawn_download(app, rt, transfer.remote.clone(), transfer.local.clone());
+                started += 1;
+            }
+
+            if let Some(error) = errors.first() {
+                app.show_warning(format!("Sync: {} error(s), first: {}", errors.len(), error));
+            } else if plan.is_empty() {
+                app.show_info(format!("{} is already in sync", plan.local_dir.display()));
+            } else {
+                app.show_info(format!(
+                    "Syncing {} file(s) to {}, deleted {}",
+                    started,
+                    plan.local_dir.display(),
+                    deleted
+                ));
+            }
+        }
+        GuardedOperation::Restore(path) => {
+            app.update_restore(path.clone(), RestoreTaskState::InProgress);
+            app.show_info(format!("Requested restore of {} for {} day(s)", path, rt.config.restore_days));
+
+            let backend_clone = rt.backend.clone();
+            let tx = rt.events.clone();
+            let days = rt.config.restore_days;
+            let audit = rt.audit.clone();
+            tokio::spawn(async move {
+                let source = backend_clone.get_display_path(&path);
+                if let Err(e) = backend_clone.restore_object(&path, days).await {
+                    let error = format!("{:#}", e);
+                    let _ = audit.record(&AuditRecord::failed(AuditOperation::Restore, source, &error));
+                    let _ = tx.send(AppEvent::RestoreFailed { path, error });
+                    return;
+                }
+                let _ = audit.record(&AuditRecord::new(AuditOperation::Restore, source, AuditOutcome::Ok));
+
+                // Restores take hours; poll until the object becomes readable
+                while !tx.is_closed() {
+                    tokio::time::sleep(RESTORE_POLL_INTERVAL).await;
+                    match backend_clone.restore_state(&path).await {
+                        Ok(Some(RestoreState::Archived)) | Ok(Some(RestoreState::InProgress)) => {}
+                        Ok(Some(RestoreState::Restored { expiry })) => {
+                            let _ = tx.send(AppEvent::RestoreComplete { path, expiry });
+                            break;
+                        }
+                        Ok(None) => {
+                            let _ = tx.send(AppEvent::RestoreComplete { path, expiry: None });
+                            break;
+                        }
+                        Err(e) => {
+                            let _ = tx.send(AppEvent::RestoreFailed {
+                                path,
+                                error: format!("{:#}", e),
+                            });
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Ask the hook `command` whether `operation` on `paths` (at `location`) may
+/// go ahead. Returns the message to show when the hook vetoes it, or can't
+/// run or answer in time.
+async fn check_operation(
+    command: &str,
+    operation: AuditOperation,
+    location: &str,
+    paths: &[String],
+    profile: &str,
+) -> Result<(), String> {
+    match tokio::time::timeout(HOOK_TIMEOUT, run_hook(command, operation, location, paths, profile)).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("pre_operation_hook gave no answer within {}s", HOOK_TIMEOUT.as_secs())),
+    }
+}
+
+/// Run the hook `command` through the shell, the paths on its stdin
+async fn run_hook(command: &str, operation: AuditOperation, location: &str, paths: &[String], profile: &str) -> Result<(), String> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("RATS3_OPERATION", operation.name())
+        .env("RATS3_LOCATION", location)
+        .env("RATS3_COUNT", paths.len().to_string())
+        .env("RATS3_PROFILE", profile)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Cannot run pre_operation_hook: {}", e))?;
+
+    // Written while the hook runs, which may not read it all
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input: String = paths.iter().map(|path| format!("{}\n", path)).collect();
+    let write = async move {
+        let _ = stdin.write_all(input.as_bytes()).await;
+    };
+    let ((), output) = tokio::join!(write, child.wait_with_output());
+    let output = output.map_err(|e| format!("pre_operation_hook failed: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    // What the hook printed, stdout first, as its reason
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stdout.lines().chain(stderr.lines()).map(str::trim).find(|line| !line.is_empty());
+    Err(match reason {
+        Some(reason) => reason.to_string(),
+        None => format!("pre_operation_hook refused ({})", output.status),
+    })
+}
+
+/// Run the plugin `name` on the selected entries: in the background with its
+/// output going to a popup, or in the terminal once the UI is out of the way
+fn run_plugin(app: &mut App, rt: &Runtime, name: &str) {
+    let Some(run) = app.plugin_run(name) else {
+        return;
+    };
+    match run.output {
+        PluginOutput::Terminal => app.request_terminal_plugin(run),
+        PluginOutput::Popup => {
+            app.open_plugin_output(&run.name);
+            let tx = rt.events.clone();
+            tokio::spawn(async move {
+                let output = run.capture().await;
+                let _ = tx.send(AppEvent::PluginFinished { name: run.name, output });
+            });
+        }
+    }
+}
+
+/// Ask to run the credential command after a request failed because the
+/// credentials expired, retrying `retry` once they're refreshed
+fn offer_login(app: &mut App, rt: &Runtime, retry: Option<Action>) {
+    // Already being taken care of
+    if app.is_logging_in() || app.confirmation().is_some() {
+        return;
+    }
+    match credential_command(rt) {
+        None => app.show_error("Credentials expired; refresh them and press F5"),
+        Some(command) if app.is_login_declined() => {
+            app.show_error(format!("Credentials expired; :login runs {}", command));
+        }
+        Some(command) => {
+            app.ask_confirmation(format!("Credentials expired. Run {} and retry?", command), PendingOperation::Login(retry));
+        }
+    }
+}
+
+/// Run the credential command in the background; `retry` runs once it succeeds
+fn start_login(app: &mut App, rt: &Runtime, retry: Option<Action>) {
+    let Some(command) = credential_command(rt) else {
+        app.show_warning("No credential_command configured");
+        return;
+    };
+    if app.is_logging_in() {
+        app.show_info("Already logging in");
+        return;
+    }
+    app.start_login(command.clone(), retry);
+    let tx = rt.events.clone();
+    tokio::spawn(async move {
+        let result = run_login(&command, &tx).await.map_err(|e| format!("{:#}", e));
+        let _ = tx.send(AppEvent::LoginDone(result));
+    });
+}
+
+/// Run `command` through the shell, forwarding the lines it prints (the URL
+/// and code of an SSO login)
+async fn run_login(command: &str, tx: &mpsc::UnboundedSender<AppEvent>) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Cannot run {}", command))?;
+    let mut lines = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let forward = async {
+        while let Ok(Some(line)) = lines.next_line().await {
+            if !line.trim().is_empty() {
+                let _ = tx.send(AppEvent::LoginOutput(line.trim().to_string()));
+            }
+        }
+    };
+    let errors = async {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors).await;
+        errors
+    };
+    let ((), errors) = tokio::join!(forward, errors);
+    let status = child.wait().await?;
+    if !status.success() {
+        anyhow::bail!("{} ({})", errors.trim(), status);
+    }
+    Ok(())
+}
+
+/// Open the S3 backend again so it picks up the refreshed credentials, then
+/// run `retry` (or load the preview that failed)
+async fn reconnect(app: &mut App, rt: &mut Runtime, retry: Option<Action>, area: Rect) -> Result<()> {
+    // Other backends have no credentials to reload
+    if rt.backend.kind() == "s3" {
+        let location = rt.backend.get_display_path("");
+        match rt.registry.open(&location, rt.backend.profile().as_deref()).await {
+            Ok((backend, _)) => {
+                rt.backend = backend;
+                app.set_backend(rt.backend.clone());
+            }
+            Err(e) => app.show_error(format!("Cannot reconnect to {}: {:#}", location, e)),
+        }
+    }
+    match retry {
+        Some(action) => handle_action(app, rt, action, None, area).await?,
+        None => spawn_preview_load(app, rt),
+    }
+    Ok(())
+}
+
+/// Syntax-highlight previewed text in the background
+fn highlight_preview(rt: &Runtime, path: String, text: String) {
+    // Highlighting is CPU-bound; keep it off the async workers
+    if let Some(syntax) = ui::widgets::preview::find_syntax(&path, &text) {
+        let line_num_color = rt.config.colors.text_secondary.to_ratatui_color();
+        let theme = rt.syntax_theme.clone();
+        let tx = rt.events.clone();
+        std::thread::spawn(move || {
+            // Stream the lines so the top of a big file is colored early
+            let text: Arc<str> = Arc::from(text);
+            let mut start = 0;
+            ui::widgets::preview::highlight_chunked(&text, syntax, &theme, line_num_color, HIGHLIGHT_CHUNK_LINES, |lines, complete| {
+                let count = lines.len();
+                let chunk = AppEvent::Highlighted { path: path.clone(), text: text.clone(), start, lines, complete };
+                start += count;
+                tx.send(chunk).is_ok()
+            });
+        });
+    }
+}
+
+/// List directories into the listing cache and load file previews ahead of
+/// time, so moving the cursor onto them doesn't wait for the backend
+fn prefetch(rt: &Runtime, prefixes: Vec<String>, files: Vec<String>) {
+    for prefix in prefixes {
+        let key = rt.backend.get_display_path(&prefix);
+        if rt.listings.lock().unwrap().get(&key).is_some() {
+            continue;
+        }
+        let backend = rt.backend.clone();
+        let listings = rt.listings.clone();
+        let tx = rt.events.clone();
+        let timeout = rt.config.request_timeout_secs;
+        tokio::spawn(async move {
+            if let Ok(result) = with_timeout(timeout, backend.list(&prefix)).await {
+                let _ = tx.send(AppEvent::ListingPrefetched(result.entries.len()));
+                listings.lock().unwrap().insert(key, result);
+            }
+        });
+    }
+    for path in files {
+        let backend = rt.backend.clone();
+        let max_size = rt.config.preview_max_size;
+        let tx = rt.events.clone();
+        let timeout = rt.config.request_timeout_secs;
+        tokio::spawn(async move {
+            if let Ok(content) = with_timeout(timeout, backend.get_preview(&path, max_size)).await {
+                let _ = tx.send(AppEvent::PreviewPrefetched(path, content));
+            }
+        });
+    }
+}
+
+/// List the current prefix again, bypassing the cache and keeping the selection
+async fn refresh_listing(app: &mut App, rt: &mut Runtime) -> Result<()> {
+    let prefix = app.current_prefix().to_string();
+    let key = rt.backend.get_display_path(&prefix);
+    rt.listings.lock().unwrap().invalidate(&key);
+    let result = rt.list(&prefix).await?;
+    match app.selected_entry().map(|e| e.name.clone()) {
+        Some(name) => app.update_entries_and_select(result, &name),
+        None => app.update_entries(result),
+    }
+    Ok(())
+}
+
+/// Run the action a key press resolved to. `area` is the terminal's, for
+/// actions that scroll by the visible area.
+async fn handle_action(
+    app: &mut App,
+    rt: &mut Runtime,
+    action: Action,
+    flush: Option<char>,
+    area: Rect,
+) -> Result<()> {
+    // The search key sequence was broken (e.g. 'j' then 'k' or Enter with
+    // 'jj' as exit sequence): the withheld key is typed before the new one
+    if let Some(pending_char) = flush {
+        app.clear_pending_key();
+        app.append_search_char(pending_char);
+    }
+
+    match action {
+        Action::Quit => {
+            app.quit();
+        }
+        Action::Suspend => {
+            app.request_suspend();
+        }
+        Action::MoveUp => {
+            app.clear_pending_key();
+            if app.mode() == &AppMode::Download {
+                app.download_move_up();
+            } else if app.mode() == &AppMode::Context {
+                app.context_move_up();
+            } else if app.mode() == &AppMode::Grep {
+                app.grep_move_up();
+            } else if app.mode() == &AppMode::Properties {
+                app.properties_move_up();
+            } else if app.mode() == &AppMode::AuditLog {
+                app.audit_log_move_up();
+            } else if app.mode() == &AppMode::History || app.is_searching_history() {
+                app.history_move_up();
+            } else if matches!(app.focused_panel(), FocusedPanel::Preview) {
+                app.preview_scroll_up();
+            } else {
+                app.move_up();
+                // Update visual selection if in visual mode
+                if app.mode() == &AppMode::Visual {
+                    app.update_visual_selection();
+                }
+                // Load preview for new selection
+                spawn_preview_load(app, rt);
+            }
+        }
+        Action::MoveDown => {
+            app.clear_pending_key();
+            if app.mode() == &AppMode::Download {
+                app.download_move_down();
+            } else if app.mode() == &AppMode::Context {
+                app.context_move_down(rt.config.contexts.len());
+            } else if app.mode() == &AppMode::Grep {
+                app.grep_move_down();
+            } else if app.mode() == &AppMode::Properties {
+                app.properties_move_down();
+            } else if app.mode() == &AppMode::AuditLog {
+                app.audit_log_move_down();
+            } else if app.mode() == &AppMode::History || app.is_searching_history() {
+                app.history_move_down();
+            } else if matches!(app.focused_panel(), FocusedPanel::Preview) {
+                // Calculate max lines from preview content and visible height
+                let max_lines = app.preview_line_count();
+                if max_lines > 0 {
+                    let geometry = ui::layout::preview_geometry(area, app, &rt.config);
+                    app.preview_scroll_down(max_lines, &geometry);
+                }
+            } else {
+                app.move_down();
+                // Update visual selection if in visual mode
+                if app.mode() == &AppMode::Visual {
+                    app.update_visual_selection();
+                }
+                // Load preview for new selection
+                spawn_preview_load(app, rt);
+            }
+        }
+        Action::JumpUp(count) => {
+            app.clear_pending_key();
+            if matches!(app.focused_panel(), FocusedPanel::Preview) {
+                app.preview_scroll_page_up(count);
+            } else {
+                app.jump_up(count);
+                // Update visual selection if in visual mode
+                if app.mode() == &AppMode::Visual {
+                    app.update_visual_selection();
+                }
+                // Load preview for new selection
+                spawn_preview_load(app, rt);
+            }
+        }
+        Action::JumpDown(count) => {
+            app.clear_pending_key();
+            if matches!(app.focused_panel(), FocusedPanel::Preview) {
+                // Calculate max lines from preview content and visible height
+                let max_lines = app.preview_line_count();
+                if max_lines > 0 {
+                    let geometry = ui::layout::preview_geometry(area, app, &rt.config);
+                    app.preview_scroll_page_down(count, max_lines, &geometry);
+                }
+            } else {
+                app.jump_down(count);
+                // Update visual selection if in visual mode
+                if app.mode() == &AppMode::Visual {
+                    app.update_visual_selection();
+                }
+                // Load preview for new selection
+                spawn_preview_load(app, rt);
+            }
+        }
+        Action::JumpToBottom => {
+            app.clear_pending_key();
+            if matches!(app.focused_panel(), FocusedPanel::Preview) {
+                // Calculate max lines from preview content and visible height
+                let max_lines = app.preview_line_count();
+                if max_lines > 0 {
+                    let geometry = ui::layout::preview_geometry(area, app, &rt.config);
+                    app.preview_jump_to_bottom(max_lines, &geometry);
+                }
+            } else {
+                app.jump_to_bottom();
+                // Update visual selection if in visual mode
+                if app.mode() == &AppMode::Visual {
+                    app.update_visual_selection();
+                }
+                // Load preview for new selection
+                spawn_preview_load(app, rt);
+            }
+        }
+        Action::JumpToTop => {
+            app.clear_pending_key();
+            if matches!(app.focused_panel(), FocusedPanel::Preview) {
+                app.preview_jump_to_top();
+            } else {
+                app.jump_to_top();
+                // Update visual selection if in visual mode
+                if app.mode() == &AppMode::Visual {
+                    app.update_visual_selection();
+                }
+                // Load preview for new selection
+                spawn_preview_load(app, rt);
+            }
+        }
+        Action::NavigateInto => {
+            app.clear_pending_key();
+
+            // Handle history mode - select entry and navigate
+            // Check both History mode and Search mode with searching_history flag
+            if app.mode() == &AppMode::History || (app.is_search_mode() && app.is_searching_history()) {
+                if let Some(selected) = app.selected_history_entry().cloned() {
+                    if rt.config.confirmations.backend_switches && rt.backend.uri_to_prefix(&selected.uri).is_none() {
+                        app.exit_history_mode();
+                        app.ask_confirmation(
+                            format!("Switch to {}?", selected.uri),
+                            PendingOperation::OpenHistory(selected),
+                        );
+                    } else {
+                        open_history_entry(app, rt, &selected).await;
+                    }
+                }
+            } else if app.selected_entry().is_none() && app.toggle_group_collapsed() {
+                // Enter on a group header expands or collapses it
+                spawn_preview_load(app, rt);
+            } else {
+                // Check if selected item is a file or directory
+                let is_file = app.selected_entry().map(|e| !e.is_dir).unwrap_or(false);
+
+                if is_file && app.pick_mode().is_some() {
+                    // Hand the file to the program that started rats3, which quits
+                    if app.pick() {
+                        let picked = app.picked().unwrap_or_default().to_vec();
+                        record_chosen(app, rt, &picked);
+                    }
+                } else if is_file {
+                    record_chosen(app, rt, &[app.selected_display_path()]);
+                    // Capture name before any mode change shifts the selection
+                    let selected_name = app.selected_entry().map(|e| e.name.clone());
+
+                    app.focus_preview();
+
+                    if app.is_search_mode() {
+                        app.exit_search_mode();
+                        // Restore cursor to the same file in the now-unfiltered list
+                        if let Some(name) = selected_name {
+                            app.select_entry_by_name(&name);
+                        }
+                    }
+
+                    // Ensure the preview is loaded for the (re-)selected file
+                    spawn_preview_load(app, rt);
+                } else {
+                    // Get the navigation target BEFORE exiting search mode
+                    // (otherwise the selection index will be wrong)
+                    let from = app.current_location();
+                    let nav_result = app.navigate(NavigateDirection::Into);
+
+                    // Exit search mode when navigating
+                    let was_in_search = app.is_search_mode();
+                    if was_in_search {
+                        app.exit_search_mode();
+                    }
+
+                    if let Some((new_prefix, _)) = nav_result {
+                        match rt.list_descending(&new_prefix).await {
+                            Ok((result, skipped)) => {
+                                let reached = result.prefix.clone();
+                                app.record_location(from);
+                                app.update_entries(result);
+                                app.clear_status();
+                                if !skipped.is_empty() {
+                                    // The chain entered in one keypress, from the directory picked
+                                    let entered = new_prefix.rsplit('/').next().unwrap_or(&new_prefix);
+                                    let chain = format!("{}/{}", entered, skipped.join("/"));
+                                    app.show_info(format!("Descended into {}", chain));
+                                    app.set_descended_chain(chain);
+                                }
+                                app.remember_location(&reached);
+                                // Load preview for first item
+                                spawn_preview_load(app, rt);
+                            }
+                            Err(e) => {
+                                app.show_error(format!("Error: {}", e));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Action::NavigateUp => {
+            app.clear_pending_key();
+            let from = app.current_location();
+            if let Some((new_prefix, select_name)) = app.navigate(NavigateDirection::Up) {
+                match rt.list(&new_prefix).await {
+                    Ok(result) => {
+                        app.record_location(from);
+                        if let Some(name) = select_name {
+                            app.update_entries_and_select(result, &name);
+                        } else {
+                            app.update_entries(result);
+                        }
+                        app.clear_status();
+                        // Load preview for selected item
+                        spawn_preview_load(app, rt);
+                    }
+                    Err(e) => {
+                        app.show_error(format!("Error: {}", e));
+                    }
+                }
+            }
+        }
+        Action::EnterSearchMode => {
+            app.clear_pending_key();
+            // `/` on a confirmed filter starts a second one narrowing its matches
+            app.pin_search_query();
+            app.enter_search_mode();
+            app.clear_status();
+        }
+        Action::ExitSearchMode => {
+            app.clear_pending_key();
+            app.exit_search_mode();
+            app.clear_status();
+        }
+        Action::AcceptFilter => {
+            app.clear_pending_key();
+            app.accept_search();
+            app.clear_status();
+        }
+        Action::ClearFilter => {
+            app.clear_pending_key();
+            // Keep the cursor on the same entry in the unfiltered listing
+            let selected_name = app.selected_entry().map(|e| e.name.clone());
+            app.exit_search_mode();
+            if let Some(name) = selected_name {
+                app.select_entry_by_name(&name);
+            }
+            app.show_info("Filter cleared");
+        }
+        Action::AppendChar(c) => {
+            app.clear_pending_key();
+            if app.is_preview_search_active() {
+                app.append_preview_search_char(c);
+            } else {
+                app.append_search_char(c);
+            }
+            app.clear_status();
+        }
+        Action::Backspace => {
+            app.clear_pending_key();
+            if app.is_preview_search_active() {
+                app.backspace_preview_search();
+            } else {
+                app.backspace_search();
+            }
+            app.clear_status();
+        }
+        Action::ToggleSelection => {
+            app.clear_pending_key();
+
+            // If in visual mode, exit it first so individual toggles are preserved
+            if app.mode() == &AppMode::Visual {
+                app.exit_visual_mode();
+            }
+
+            app.toggle_selection();
+            let count = app.selected_count();
+            if count > 0 {
+                app.show_info(format!("{} file(s) selected", count));
+            } else {
+                app.clear_status();
+            }
+        }
+        Action::EnterVisualMode => {
+            app.clear_pending_key();
+            app.enter_visual_mode();
+        }
+        Action::ExitVisualMode => {
+            app.clear_pending_key();
+            app.exit_visual_mode();
+            let count = app.selected_count();
+            if count > 0 {
+                app.show_info(format!("{} file(s) selected", count));
+            } else {
+                app.clear_status();
+            }
+        }
+        Action::EnterDownloadMode => {
+            app.clear_pending_key();
+
+            // Exit visual mode if we're in it
+            if app.mode() == &AppMode::Visual {
+                app.exit_visual_mode();
+            }
+
+            // If no files selected, auto-select the current file
+            if app.selected_count() == 0 {
+                // Check if current selection is a file (not directory)
+                let is_file = app.selected_entry().map(|e| !e.is_dir).unwrap_or(false);
+
+                if is_file {
+                    // Auto-select the current file
+                    app.toggle_selection();
+                } else {
+                    app.show_warning("Cannot download directories. Select files with Space or 'v' first.");
+                }
+            }
+
+            // Archived objects can't be downloaded until they are restored
+            let skipped = app.deselect_unreadable();
+
+            // Now check if we have files to download
+            if app.selected_count() == 0 {
+                if skipped > 0 {
+                    app.show_warning("Archived objects must be restored before they can be downloaded");
+                } else if app.selected_entry().is_none() {
+                    // Still no files (was a directory or empty)
+                    app.show_info("No files selected. Select files with Space or 'v' first.");
+                }
+            } else {
+                let destinations = rt.config.download_destinations_for(&rt.backend.location_name());
+                if destinations.is_empty() {
+                    app.show_warning("No download destinations configured for this bucket. Edit ~/.config/rats3/config.toml");
+                } else {
+                    if skipped > 0 {
+                        app.show_warning(format!("Skipping {} archived object(s) that need to be restored first", skipped));
+                    }
+                    app.enter_download_mode(destinations);
+                }
+            }
+        }
+        Action::ExitDownloadMode => {
+            app.clear_pending_key();
+            app.exit_download_mode();
+            app.clear_status();
+        }
+        Action::ConfirmDownload => {
+            app.clear_pending_key();
+            confirm_download(app, rt);
+        }
+        Action::DownloadDestinationDigit(digit) => {
+            if app.download_type_digit(digit) {
+                confirm_download(app, rt);
+            }
+        }
+        Action::AcceptConfirmation => match app.take_confirmation().map(|c| c.operation) {
+            Some(PendingOperation::Download(targets)) => start_downloads(app, rt, targets),
+            Some(PendingOperation::SwitchContext(name)) => switch_context(app, rt, &name).await,
+            Some(PendingOperation::OpenHistory(entry)) => open_history_entry(app, rt, &entry).await,
+            Some(PendingOperation::Login(retry)) => start_login(app, rt, retry),
+            None => {}
+        },
+        Action::RejectConfirmation => {
+            if let Some(confirmation) = app.take_confirmation() {
+                if matches!(confirmation.operation, PendingOperation::Login(_)) {
+                    app.decline_login();
+                }
+                app.show_info("Canceled");
+            }
+        }
+        Action::EnterHistoryMode => {
+            app.clear_pending_key();
+            if !app.history().is_empty() {
+                app.enter_history_mode();
+            }
+        }
+        Action::EnterHistoryModeWithSearch => {
+            app.clear_pending_key();
+            if !app.history().is_empty() {
+                app.enter_history_mode();
+                app.enter_search_mode();
+            }
+        }
+        Action::ExitHistoryMode => {
+            app.clear_pending_key();
+            app.exit_history_mode();
+            app.clear_status();
+        }
+        Action::CopyPath => {
+            app.clear_pending_key();
+            let path = app.selected_display_path();
+            match clipboard::copy_to_clipboard(&path) {
+                Ok(_) => {
+                    app.show_success(format!("Copied to clipboard: {}", path));
+                }
+                Err(e) => {
+                    app.show_error(format!("Failed to copy: {}", e));
+                }
+            }
+        }
+        Action::ToggleWrap => {
+            app.clear_pending_key();
+            app.toggle_wrap();
+            let status = if app.is_wrap_enabled() {
+                "Text wrapping enabled"
+            } else {
+                "Text wrapping disabled"
+            };
+            app.show_info(status);
+        }
+        Action::FocusPreview => {
+            app.clear_pending_key();
+            app.focus_preview();
+        }
+        Action::FocusExplorer => {
+            app.clear_pending_key();
+            app.focus_explorer();
+        }
+        Action::ToggleFocus => {
+            app.clear_pending_key();
+            app.toggle_focus();
+        }
+        Action::EnterPreviewVisualMode => {
+            app.clear_pending_key();
+            app.enter_preview_visual_mode();
+        }
+        Action::ExitPreviewVisualMode => {
+            app.clear_pending_key();
+            app.exit_preview_visual_mode();
+        }
+        Action::YankSelection => {
+            app.clear_pending_key();
+            // Get selected lines from preview
+            if let Some(PreviewContent::Text(..)) = app.get_preview() {
+                let (start, end) = app.get_preview_visual_range();
+                let lines = app.preview_line_texts();
+                let selected_lines: Vec<&str> = lines.iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i >= start && *i <= end)
+                    .map(|(_, line)| *line)
+                    .collect();
+                let selected_text = selected_lines.join("\n");
+
+                match clipboard::copy_to_clipboard(&selected_text) {
+                    Ok(_) => {
+                        let line_count = selected_lines.len();
+                        app.show_success(format!("Copied {} line{} to clipboard",
+                            line_count,
+                            if line_count == 1 { "" } else { "s" }));
+                    }
+                    Err(e) => {
+                        app.show_error(format!("Failed to copy: {}", e));
+                    }
+                }
+                app.exit_preview_visual_mode();
+            }
+        }
+        Action::IncreasePreviewWidth => {
+            app.clear_pending_key();
+            app.increase_preview_width();
+        }
+        Action::DecreasePreviewWidth => {
+            app.clear_pending_key();
+            app.decrease_preview_width();
+        }
+        Action::GrowStatusPane => app.resize_status_pane(1),
+        Action::ShrinkStatusPane => app.resize_status_pane(-1),
+        Action::GrowProgressPane => app.resize_progress_pane(rt.config.progress_pane_position, 1),
+        Action::ShrinkProgressPane => app.resize_progress_pane(rt.config.progress_pane_position, -1),
+        Action::ToggleHelp => {
+            app.clear_pending_key();
+            app.toggle_help();
+        }
+        Action::HelpScrollUp(amount) => {
+            app.help_scroll_up(amount);
+        }
+        Action::HelpScrollDown(amount) => {
+            // Popup fills the terminal minus a 1-line margin and its borders
+            let visible = area.height.saturating_sub(4) as usize;
+            let max_scroll = ui::widgets::help_popup::line_count(&rt.config).saturating_sub(visible);
+            app.help_scroll_down(amount, max_scroll);
+        }
+        Action::ToggleZoom => {
+            app.clear_pending_key();
+            app.toggle_zoom();
+        }
+        Action::ToggleZenMode => {
+            app.clear_pending_key();
+            app.toggle_zen_mode();
+        }
+        Action::TogglePreviewFold => {
+            app.clear_pending_key();
+            app.toggle_preview_fold();
+        }
+        Action::PreviewNextFile | Action::PreviewPreviousFile => {
+            app.clear_pending_key();
+            if app.select_adjacent_file(action == Action::PreviewNextFile) {
+                spawn_preview_load(app, rt);
+            } else if action == Action::PreviewNextFile {
+                app.show_info("Last file");
+            } else {
+                app.show_info("First file");
+            }
+        }
+        Action::CycleEncoding => {
+            app.clear_pending_key();
+            if let Some((path, text)) = app.cycle_preview_encoding() {
+                highlight_preview(rt, path, text);
+            }
+        }
+        Action::ScrollColumnsLeft => {
+            app.clear_pending_key();
+            app.scroll_columns_left();
+        }
+        Action::ScrollColumnsRight => {
+            app.clear_pending_key();
+            app.scroll_columns_right();
+        }
+        Action::EnterCommandMode => {
+            app.clear_pending_key();
+            app.enter_command_mode();
+        }
+        Action::ExitCommandMode => {
+            app.exit_command_mode();
+        }
+        Action::CommandChar(c) => {
+            app.append_command_char(c);
+        }
+        Action::CommandBackspace => {
+            app.command_backspace();
+        }
+        Action::ExecuteCommand => {
+            let command = app.exit_command_mode();
+            match command.split_whitespace().next() {
+                Some("du") => {
+                    let prefix = app.current_prefix().to_string();
+                    let location = rt.backend.get_display_path(&prefix);
+                    let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+                    app.start_summary(location.clone(), cancel_tx);
+
+                    let backend_clone = rt.backend.clone();
+                    let tx = rt.events.clone();
+                    tokio::spawn(async move {
+                        let scan = async {
+                            let mut summary = PrefixSummary::default();
+                            let mut last_update = std::time::Instant::now();
+                            backend_clone
+                                .walk(&prefix, &mut |_, entry| {
+                                    summary.add(entry);
+                                    if last_update.elapsed() >= SUMMARY_UPDATE_INTERVAL {
+                                        last_update = std::time::Instant::now();
+                                        let _ = tx.send(AppEvent::SummaryProgress {
+                                            location: location.clone(),
+                                            summary: summary.clone(),
+                                            complete: false,
+                                        });
+                                    }
+                                })
+                                .await
+                                .map(|_| summary)
+                        };
+
+                        tokio::select! {
+                            result = scan => {
+                                let msg = match result {
+                                    Ok(summary) => AppEvent::SummaryProgress { location, summary, complete: true },
+                                    Err(e) => AppEvent::SummaryFailed { location, error: format!("{:#}", e) },
+                                };
+                                let _ = tx.send(msg);
+                            }
+                            _ = cancel_rx => {}
+                        }
+                    });
+                }
+                Some("grep") => {
+                    let pattern = command.trim().strip_prefix("grep").unwrap_or_default().trim().to_string();
+                    if pattern.is_empty() {
+                        app.show_error("Usage: :grep <text>");
+                    } else {
+                        let prefix = app.current_prefix().to_string();
+                        let location = rt.backend.get_display_path(&prefix);
+                        let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+                        app.start_grep(pattern.clone(), location.clone(), cancel_tx);
+
+                        let backend_clone = rt.backend.clone();
+                        let max_size = rt.config.preview_max_size;
+                        let tx = rt.events.clone();
+                        tokio::spawn(async move {
+                            tokio::select! {
+                                result = grep_prefix(&backend_clone, &prefix, &location, &pattern, max_size, &tx) => {
+                                    if let Err(e) = result {
+                                        let _ = tx.send(AppEvent::GrepFailed { location, error: format!("{:#}", e) });
+                                    }
+                                }
+                                _ = cancel_rx => {}
+                            }
+                        });
+                    }
+                }
+                Some(word) if word.eq_ignore_ascii_case("select") => {
+                    let query = command.trim().to_string();
+                    if let Some(path) = app.get_selected_file_path() {
+                        app.forget_highlight(&path);
+                        if query.eq_ignore_ascii_case("select") {
+                            // A bare `:select` drops the query result and shows the file again
+                            if app.clear_preview_query(&path) {
+                                spawn_preview_load(app, rt);
+                            }
+                        } else {
+                            // The query result must not be overwritten by a preview load still in flight
+                            rt.pending_preview_cancel = None;
+                            app.start_preview_query(path.clone(), query.clone());
+
+                            let backend_clone = rt.backend.clone();
+                            let max_size = rt.config.preview_max_size;
+                            let tx = rt.events.clone();
+                            tokio::spawn(async move {
+                                let content = match backend_clone.select_object(&path, &query, max_size).await {
+                                    Ok(c) => c,
+                                    Err(e) => PreviewContent::Error(format!("{:#}", e)),
+                                };
+                                let _ = tx.send(AppEvent::PreviewReady(path, content));
+                            });
+                        }
+                    } else {
+                        app.show_error("Select a file to query");
+                    }
+                }
+                Some("diff") => {
+                    // Two selected files, or the two newest versions of one object
+                    let mut paths = app.get_selected_file_paths();
+                    if paths.is_empty() {
+                        paths.extend(app.get_selected_file_path());
+                    }
+                    if paths.len() > 2 {
+                        app.show_error("Select at most two files to diff");
+                    } else if paths.is_empty() {
+                        app.show_error("Select a file to diff");
+                    } else {
+                        app.show_info("Loading diff...");
+                        let backend_clone = rt.backend.clone();
+                        let max_size = rt.config.preview_max_size;
+                        let tx = rt.events.clone();
+                        tokio::spawn(async move {
+                            let _ = tx.send(AppEvent::DiffReady(load_diff(&backend_clone, &paths, max_size).await));
+                        });
+                    }
+                }
+                Some("sync") => {
+                    let (flags, dirs): (Vec<&str>, Vec<&str>) =
+                        command.split_whitespace().skip(1).partition(|arg| arg.starts_with("--"));
+                    if let Some(flag) = flags.iter().find(|f| **f != "--delete") {
+                        app.show_error(format!("Unknown sync option: {}", flag));
+                    } else if dirs.len() != 1 {
+                        app.show_error("Usage: :sync <local-dir> [--delete]");
+                    } else {
+                        let prefix = app.current_prefix().to_string();
+                        let location = rt.backend.get_display_path(&prefix);
+                        let local_dir = expand_tilde(dirs[0]);
+                        let delete = !flags.is_empty();
+                        app.show_info(format!("Comparing {} with {}...", location, local_dir.display()));
+
+                        let backend_clone = rt.backend.clone();
+                        let tx = rt.events.clone();
+                        tokio::spawn(async move {
+                            let _ = tx.send(AppEvent::SyncPlanned(plan_sync(&backend_clone, &prefix, location, local_dir, delete).await));
+                        });
+                    }
+                }
+                Some("upload") => {
+                    let dirs: Vec<&str> = command.split_whitespace().skip(1).collect();
+                    if !rt.config.allow_writes {
+                        app.show_error("Uploads are disabled; set allow_writes = true in the config");
+                    } else if dirs.len() != 1 {
+                        app.show_error("Usage: :upload <local-dir>");
+                    } else {
+                        let prefix = app.current_prefix().to_string();
+                        let location = rt.backend.get_display_path(&prefix);
+                        let local_dir = expand_tilde(dirs[0]);
+                        app.show_info(format!("Comparing {} with {}...", local_dir.display(), location));
+
+                        let backend_clone = rt.backend.clone();
+                        let tx = rt.events.clone();
+                        tokio::spawn(async move {
+                            let _ = tx.send(AppEvent::SyncPlanned(plan_upload(&backend_clone, &prefix, location, local_dir).await));
+                        });
+                    }
+                }
+                Some("delimiter") => {
+                    let arg = command.split_whitespace().nth(1);
+                    match (app.get_selected_file_path(), arg.map(table::parse_delimiter)) {
+                        (None, _) => app.show_error("Select a file to set its delimiter"),
+                        (Some(_), Some(None)) => {
+                            app.show_error(format!("Invalid delimiter: {}", arg.unwrap_or_default()));
+                        }
+                        (Some(path), delimiter) => {
+                            // No argument restores the default for the file's extension
+                            app.set_preview_delimiter(&path, delimiter.flatten());
+                        }
+                    }
+                }
+                Some("jq") => {
+                    let filter = command.trim().strip_prefix("jq").unwrap_or_default().trim();
+                    if !app.set_preview_jq_filter(filter) {
+                        app.show_error("Select a JSON Lines file to filter it");
+                    } else if filter.is_empty() {
+                        app.show_info("jq filter cleared");
+                    }
+                }
+                Some("log") => match rt.audit.read_recent(AUDIT_LOG_VIEW_LIMIT) {
+                    Ok(records) if records.is_empty() => app.show_info("The audit log is empty"),
+                    Ok(records) => app.open_audit_log(records, false),
+                    Err(e) => app.show_error(format!("Cannot read the audit log: {:#}", e)),
+                },
+                Some("downloads") => match rt.audit.read_downloads(AUDIT_LOG_VIEW_LIMIT) {
+                    Ok(records) if records.is_empty() => app.show_info("No downloads recorded yet"),
+                    Ok(records) => app.open_audit_log(records, true),
+                    Err(e) => app.show_error(format!("Cannot read the audit log: {:#}", e)),
+                },
+                Some("login") => start_login(app, rt, None),
+                Some("plugin") => match command.split_whitespace().nth(1) {
+                    Some(name) => run_plugin(app, rt, name),
+                    None if rt.config.plugins.is_empty() => {
+                        app.show_error("No plugins configured; add [plugins.<name>] to the config");
+                    }
+                    None => {
+                        let names: Vec<&str> = rt.config.plugins.keys().map(String::as_str).collect();
+                        app.show_error(format!("Usage: :plugin <name> ({})", names.join(", ")));
+                    }
+                },
+                Some("import-history") => {
+                    // Files named on the command line, or the usual histories
+                    let sources: Vec<PathBuf> = command.split_whitespace().skip(1).map(expand_tilde).collect();
+                    let sources = if sources.is_empty() { history::default_sources() } else { sources };
+                    app.show_info("Importing history...");
+                    let tx = rt.events.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let _ = tx.send(AppEvent::HistoryImported(history::import(&sources)));
+                    });
+                }
+                Some("usage") => app.show_info(format!("This session {}", app.usage().summary())),
+                Some("mksession") => {
+                    let name = command.split_whitespace().nth(1).unwrap_or(DEFAULT_SESSION);
+                    match Session::capture(app, rt.backend.as_ref()).save(name) {
+                        Ok(()) => app.show_success(format!("Saved session {} (resume with --session {})", name, name)),
+                        Err(e) => app.show_error(format!("Cannot save session: {:#}", e)),
+                    }
+                }
+                Some("context") => match command.split_whitespace().nth(1) {
+                    Some(name) => request_context_switch(app, rt, name).await,
+                    None if rt.config.contexts.is_empty() => {
+                        app.show_error("No contexts configured; add [contexts.<name>] to the config");
+                    }
+                    None => {
+                        let active = app.active_context().and_then(|name| rt.config.contexts.keys().position(|n| n == name));
+                        app.enter_context_picker(active);
+                    }
+                },
+                Some(other) => {
+                    app.show_error(format!("Unknown command: {}", other));
+                }
+                None => {}
+            }
+        }
+        Action::ToggleDiffLayout => {
+            app.toggle_diff_layout();
+        }
+        Action::CloseDiff => {
+            app.close_diff();
+        }
+        Action::CloseSummary => {
+            app.close_summary();
+        }
+        Action::SyncScrollUp(amount) => {
+            app.sync_plan_scroll_up(amount);
+        }
+        Action::SyncScrollDown(amount) => {
+            if let Some(plan) = app.sync_plan() {
+                let visible = ui::layout::sync_plan_visible_height(area.height, app);
+                let max_scroll = ui::widgets::sync_plan::line_count(plan).saturating_sub(visible);
+                app.sync_plan_scroll_down(amount, max_scroll);
+            }
+        }
+        Action::ToggleFilterPreset(filter) => {
+            if app.toggle_filter(&filter) {
+                app.show_info(format!("Filter: {}", filter));
+            } else {
+                app.show_info("Filter cleared");
+            }
+            spawn_preview_load(app, rt);
+        }
+        Action::Refresh => {
+            app.clear_pending_key();
+            match refresh_listing(app, rt).await {
+                Ok(()) => {
+                    app.show_info("Refreshed");
+                    spawn_preview_load(app, rt);
+                }
+                Err(e) => app.show_error(format!("Error: {}", e)),
+            }
+        }
+        Action::NavigateBack | Action::NavigateForward => {
+            app.clear_pending_key();
+            let forward = action == Action::NavigateForward;
+            // Only taken off the stack once listed, so canceling the listing keeps it
+            let Some(target) = app.peek_location(forward).cloned() else {
+                app.show_info(if forward { "Already at the newest location" } else { "Already at the oldest location" });
+                return Ok(());
+            };
+            match rt.list_with(&target.backend, &target.prefix).await {
+                Ok(result) => {
+                    app.pop_location(forward);
+                    app.push_location(app.current_location(), !forward);
+                    if app.is_search_mode() {
+                        app.exit_search_mode();
+                    }
+                    rt.backend = target.backend.clone();
+                    app.set_backend(rt.backend.clone());
+                    app.set_active_context(target.context.clone());
+                    match &target.selected {
+                        Some(name) => app.update_entries_and_select(result, name),
+                        None => app.update_entries(result),
+                    }
+                    app.clear_status();
+                    spawn_preview_load(app, rt);
+                }
+                // Kept on the stack for another try
+                Err(e) => app.show_error(format!("Error: {}", e)),
+            }
+        }
+        Action::ExitContextPicker => {
+            app.exit_context_picker();
+        }
+        Action::CloseGrep => {
+            app.close_grep();
+        }
+        Action::ShowProperties => {
+            app.clear_pending_key();
+            if let Some(path) = app.open_properties() {
+                let location = app.selected_display_path();
+                let backend = rt.backend.clone();
+                let tx = rt.events.clone();
+                tokio::spawn(async move {
+                    let result = backend.properties(&path).await.map_err(|e| format!("{:#}", e));
+                    let _ = tx.send(AppEvent::PropertiesLoaded { location, result });
+                });
+            }
+        }
+        Action::CloseAuditLog => {
+            app.close_audit_log();
+        }
+        Action::RerunDownload => match app.selected_audit_record().cloned() {
+            Some(record) if record.is_completed_download() => {
+                app.close_audit_log();
+                rerun_download(app, rt, &record);
+            }
+            Some(_) => app.show_error("Only completed downloads can be run again"),
+            None => {}
+        },
+        Action::CloseProperties => {
+            app.close_properties();
+        }
+        Action::RunPlugin(name) => {
+            app.clear_pending_key();
+            run_plugin(app, rt, &name);
+        }
+        Action::PluginOutputScrollUp(lines) => {
+            app.plugin_output_scroll_up(lines);
+        }
+        Action::PluginOutputScrollDown(lines) => {
+            app.plugin_output_scroll_down(lines);
+        }
+        Action::YankPluginOutput => {
+            let Some(view) = app.plugin_output_view() else {
+                return Ok(());
+            };
+            match clipboard::copy_to_clipboard(&view.lines.join("\n")) {
+                Ok(_) => app.show_success(format!("Copied the output of {}", view.name)),
+                Err(e) => app.show_error(format!("Failed to copy: {}", e)),
+            }
+        }
+        Action::ClosePluginOutput => {
+            app.close_plugin_output();
+        }
+        Action::ToggleGroupByExtension => {
+            app.clear_pending_key();
+            app.toggle_group_by_extension();
+            app.show_info(if app.is_grouped_by_extension() { "Grouped by extension" } else { "Not grouped" });
+            spawn_preview_load(app, rt);
+        }
+        Action::ToggleFlatView => {
+            app.clear_pending_key();
+            rt.flat = !rt.flat;
+            let prefix = app.current_prefix().to_string();
+            match rt.list(&prefix).await {
+                Ok(result) => {
+                    app.set_flat_view(rt.flat);
+                    app.update_entries(result);
+                    app.show_info(if rt.flat { "Flat view: all files below here" } else { "Directory view" });
+                    spawn_preview_load(app, rt);
+                }
+                Err(e) => {
+                    rt.flat = !rt.flat;
+                    app.show_error(format!("Error: {}", e));
+                }
+            }
+        }
+        Action::NextPage => {
+            app.clear_pending_key();
+            if app.flat_paging().is_none() {
+                app.show_warning("Pages are only used by the flat view");
+                return Ok(());
+            }
+            let Some(start) = app.next_flat_page_start() else {
+                app.show_info("Last page");
+                return Ok(());
+            };
+            let prefix = app.current_prefix().to_string();
+            let _in_flight = rt.listing.start();
+            let backend = rt.backend.clone();
+            let page = list_flat_page(&backend, &prefix, Some(&start), rt.config.flat_page_size);
+            match rt.request(page).await {
+                Ok((result, more)) => {
+                    app.show_next_flat_page(result, start, more);
+                    spawn_preview_load(app, rt);
+                }
+                Err(e) => app.show_error(format!("Error: {}", e)),
+            }
+        }
+        Action::PreviousPage => {
+            app.clear_pending_key();
+            if app.flat_paging().is_none() {
+                app.show_warning("Pages are only used by the flat view");
+                return Ok(());
+            }
+            let Some(start) = app.previous_flat_page_start() else {
+                app.show_info("First page");
+                return Ok(());
+            };
+            let prefix = app.current_prefix().to_string();
+            let _in_flight = rt.listing.start();
+            let backend = rt.backend.clone();
+            let page = list_flat_page(&backend, &prefix, start.as_deref(), rt.config.flat_page_size);
+            match rt.request(page).await {
+                Ok((result, more)) => {
+                    app.show_previous_flat_page(result, more);
+                    spawn_preview_load(app, rt);
+                }
+                Err(e) => app.show_error(format!("Error: {}", e)),
+            }
+        }
+        Action::LoadAll => {
+            app.clear_pending_key();
+            let Some(paging) = app.flat_paging() else {
+                app.show_warning("Load all is only used by the flat view");
+                return Ok(());
+            };
+            if paging.loaded().is_some() {
+                return Ok(());
+            }
+            if paging.page() == 1 && !paging.has_more() {
+                app.show_info("All keys are shown");
+                return Ok(());
+            }
+            let prefix = app.current_prefix().to_string();
+            let location = rt.backend.get_display_path(&prefix);
+            let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+            app.start_flat_load(location.clone(), cancel_tx);
+
+            let backend = rt.backend.clone();
+            let tx = rt.events.clone();
+            tokio::spawn(async move {
+                let load = async {
+                    let mut entries = Vec::new();
+                    let mut last_update = std::time::Instant::now();
+                    backend
+                        .walk(&prefix, &mut |path, entry| {
+                            entries.push(Entry { name: flat_name(&prefix, path), ..entry.clone() });
+                            if last_update.elapsed() >= FLAT_LOAD_UPDATE_INTERVAL {
+                                last_update = std::time::Instant::now();
+                                let _ = tx.send(AppEvent::FlatLoadProgress {
+                                    location: location.clone(),
+                                    loaded: entries.len(),
+                                });
+                            }
+                        })
+                        .await?;
+                    entries.sort_by(|a, b| a.name.cmp(&b.name));
+                    Ok::<_, anyhow::Error>(ListResult { entries, prefix: prefix.clone() })
+                };
+
+                tokio::select! {
+                    result = load => {
+                        let result = result.map_err(|e| format!("{:#}", e));
+                        let _ = tx.send(AppEvent::FlatLoaded { location, result });
+                    }
+                    _ = cancel_rx => {}
+                }
+            });
+        }
+        Action::CycleKindFilter => {
+            app.clear_pending_key();
+            app.cycle_kind_filter();
+            app.show_info(format!("Showing {}", app.kind_filter().label()));
+            spawn_preview_load(app, rt);
+        }
+        Action::ToggleGroup => {
+            app.clear_pending_key();
+            if app.toggle_group_collapsed() {
+                spawn_preview_load(app, rt);
+            } else if !app.is_grouped_by_extension() {
+                app.show_warning("Not grouped by extension");
+            }
+        }
+        Action::YankProperty | Action::YankAllProperties => {
+            let Some(view) = app.properties_view() else {
+                return Ok(());
+            };
+            let (text, what) = if action == Action::YankProperty {
+                match view.rows.get(view.selected) {
+                    Some((name, value)) => (value.clone(), name.clone()),
+                    None => return Ok(()),
+                }
+            } else {
+                let lines: Vec<String> = view.rows.iter().map(|(name, value)| format!("{}: {}", name, value)).collect();
+                (lines.join("\n"), "all properties".to_string())
+            };
+            match clipboard::copy_to_clipboard(&text) {
+                Ok(_) => app.show_success(format!("Copied {}", what)),
+                Err(e) => app.show_error(format!("Failed to copy: {}", e)),
+            }
+        }
+        Action::ConfirmGrep => {
+            if let Some(found) = app.take_grep_match() {
+                let (parent, _) = grep::split_path(&found.path);
+                let from = app.current_location();
+                match rt.list(parent).await {
+                    Ok(result) => {
+                        if parent != app.current_prefix() {
+                            app.record_location(from);
+                            app.remember_location(parent);
+                        }
+                        app.open_grep_match(&found, result);
+                        app.clear_status();
+                        spawn_preview_load(app, rt);
+                    }
+                    Err(e) => app.show_error(format!("Error: {}", e)),
+                }
+            }
+        }
+        Action::ConfirmContext => {
+            app.exit_context_picker();
+            if let Some(name) = rt.config.contexts.keys().nth(app.context_index()).cloned() {
+                request_context_switch(app, rt, &name).await;
+            }
+        }
+        Action::CancelSync => {
+            app.take_sync_plan();
+            app.show_info("Sync canceled");
+        }
+        Action::ConfirmSync => match app.take_sync_plan() {
+            Some(plan) if plan.direction == SyncDirection::Upload && plan.is_empty() => {
+                app.show_info(format!("{} is already up to date", plan.location));
+            }
+            Some(plan) => check_then_run(app, rt, GuardedOperation::Sync(plan)),
+            None => {}
+        },
+        Action::RestoreObject => {
+            app.clear_pending_key();
+            let restore_state = app.selected_entry().and_then(|e| e.restore_state.clone());
+            match (restore_state, app.get_selected_file_path()) {
+                (Some(RestoreState::Archived), Some(path)) => {
+                    check_then_run(app, rt, GuardedOperation::Restore(path));
+                }
+                (Some(RestoreState::InProgress), _) => {
+                    app.show_info("Restore is already in progress");
+                }
+                (Some(RestoreState::Restored { expiry }), _) => {
+                    app.show_info(match expiry {
+                        Some(expiry) => format!("Object is already restored (until {})", expiry),
+                        None => "Object is already restored".to_string(),
+                    });
+                }
+                _ => {
+                    app.show_info("Selected entry is not an archived object");
+                }
+            }
+        }
+        Action::ToggleProgressPane => {
+            app.clear_pending_key();
+            app.toggle_progress_pane();
+        }
+        Action::CycleLayout => {
+            app.clear_pending_key();
+            app.cycle_preview_layout();
+            app.show_info(format!("Preview layout: {}", app.preview_layout().name()));
+        }
+        Action::EnterPreviewSearch => {
+            app.clear_pending_key();
+            app.set_preview_search_query(String::new());
+        }
+        Action::ExitPreviewSearch => {
+            app.clear_pending_key();
+            app.cancel_preview_search();
+        }
+        Action::PreviewSearchNext => {
+            app.clear_pending_key();
+            let geometry = ui::layout::preview_geometry(area, app, &rt.config);
+            app.preview_search_next(&geometry);
+        }
+        Action::PreviewSearchPrev => {
+            app.clear_pending_key();
+            let geometry = ui::layout::preview_geometry(area, app, &rt.config);
+            app.preview_search_prev(&geometry);
+        }
+        Action::ConfirmPreviewSearch => {
+            app.clear_pending_key();
+            // Calculate max lines and visible height for scroll limit
+            let max_lines = app.preview_line_count();
+            let geometry = ui::layout::preview_geometry(area, app, &rt.config);
+            app.confirm_preview_search(max_lines, &geometry);
+        }
+        Action::CancelPreview => {
+            app.clear_pending_key();
+            // Dropping the sender stops the load
+            rt.pending_preview_cancel = None;
+            app.cancel_preview_load();
+            app.show_info("Preview canceled");
+        }
+        Action::CancelDownloads => {
+            app.clear_pending_key();
+            let canceled = app.cancel_all_downloads();
+            if canceled > 0 {
+                app.show_info(format!("Canceled {} download(s)", canceled));
+            }
+        }
+        Action::PendingKey(c) => {
+            app.set_pending_key(c);
+        }
+        Action::None => {
+            app.clear_pending_key();
+        }
+    }
+    Ok(())
+}
+
+/// Download the selection to the destination selected in download mode
+fn confirm_download(app: &mut App, rt: &mut Runtime) {
+    if let Some(destination) = app.selected_download_destination().cloned() {
+        // Expand tilde in destination path
+        let dest_path = expand_tilde(&destination.path);
+        if let Err(e) = download_selection(app, rt, &dest_path) {
+            app.show_error(e);
+        }
+    }
+}
+
+/// Download the source of a recorded download to its destination again,
+/// asking first when that replaces a file and the confirmations call for it
+fn rerun_download(app: &mut App, rt: &mut Runtime, record: &AuditRecord) {
+    let Some(destination) = record.destination.as_deref().map(PathBuf::from) else {
+        return;
+    };
+    let Some(path) = rt.backend.uri_to_prefix(&record.source).filter(|path| !path.is_empty()) else {
+        app.show_error(format!("{} isn't on the open backend; open it first", record.source));
+        return;
+    };
+    if let Some(parent) = destination.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            app.show_error(format!("Failed to create directory {}: {}", parent.display(), e));
+            return;
+        }
+    }
+
+    let targets = vec![(path, destination.clone())];
+    if rt.config.confirmations.overwrites && destination.exists() {
+        let question = format!("Download {} again, replacing {}?", record.source, destination.display());
+        app.ask_confirmation(question, PendingOperation::Download(targets));
+    } else {
+        start_downloads(app, rt, targets);
+    }
+}
+
+/// Download each remote path to its local target in the background
+/// (progress is shown in the download progress overlay), noting the remote
+/// paths as chosen
+fn start_downloads(app: &mut App, rt: &Runtime, targets: Vec<(String, PathBuf)>) {
+    let sources: Vec<String> = targets.iter().map(|(file_path, _)| rt.backend.get_display_path(file_path)).collect();
+    record_chosen(app, rt, &sources);
+    for (file_path, target_path) in targets {
+        spawn_download(app, rt, file_path, target_path);
+    }
+}
+
+/// Append `uris` to the `--choosefiles` file, if there is one
+fn record_chosen(app: &mut App, rt: &Runtime, uris: &[String]) {
+    if let Err(e) = rt.chosen.append(uris) {
+        app.show_error(format!("{:#}", e));
+    }
+}
+
+/// Download a file in the background, reporting progress (and supporting
+/// cancellation) through the download tracking in `app`
+fn spawn_download(app: &mut App, rt: &Runtime, file_path: String, target_path: PathBuf) {
+    // Create cancellation channel
+    let (cancel_tx, mut cancel_rx) = tokio::sync::oneshot::channel();
+
+    // The listing tells when the object was last modified, if it's listed
+    let object = DownloadSource {
+        uri: rt.backend.get_display_path(&file_path),
+        modified: app.listed_file(&file_path).and_then(|entry| entry.modified.clone()),
+        etag: app.listed_file(&file_path).and_then(|entry| entry.etag.clone()),
+    };
+    let preserve_mtime = rt.config.preserve_download_mtime;
+    let metadata = rt.config.download_metadata;
+
+    // Initialize download tracking with cancellation support
+    app.start_download(file_path.clone(), cancel_tx);
+
+    let backend = rt.backend.clone();
+    let events = rt.events.clone();
+    let audit = rt.audit.clone();
+    tokio::spawn(async move {
+        let source = object.uri.clone();
+        let destination = target_path.display().to_string();
+
+        // Create progress callback
+        let path_for_callback = file_path.clone();
+        let tx_for_callback = events.clone();
+        let progress_callback = Box::new(move |downloaded: u64, total: Option<u64>| {
+            let _ = tx_for_callback.send(AppEvent::DownloadProgress {
+                path: path_for_callback.clone(),
+                downloaded,
+                total,
+            });
+        });
+
+        // Written next to the target and renamed once complete, so an
+        // interrupted transfer never leaves a truncated file under the real name
+        let part_path = part_path(&target_path);
+
+        // Download file with cancellation support
+        let download_future = backend.download_file(&file_path, &part_path, Some(progress_callback));
+
+        tokio::select! {
+            result = download_future => {
+                // Download completed (success or error)
+                let result = result.and_then(|()| {
+                    std::fs::rename(&part_path, &target_path)
+                        .with_context(|| format!("Failed to move download into place at {}", destination))
+                });
+                if let Err(e) = result {
+                    let _ = std::fs::remove_file(&part_path);
+                    let record = AuditRecord::failed(AuditOperation::Download, source, format!("{:#}", e));
+                    let _ = audit.record(&record.with_destination(destination));
+                    let _ = events.send(AppEvent::DownloadFailed {
+                        path: file_path.clone(),
+                        error: e.to_string(),
+                    });
+                } else {
+                    let bytes = std::fs::metadata(&target_path).map(|m| m.len()).ok();
+                    let record = AuditRecord::new(AuditOperation::Download, source, AuditOutcome::Ok);
+                    let _ = audit.record(&record.with_destination(destination).with_bytes(bytes));
+                    // The content is in place either way; a file that can't be
+                    // marked is only worth a warning
+                    let warning = transfer::mark_download(&target_path, &object, preserve_mtime, metadata)
+                        .err()
+                        .map(|e| format!("{:#}", e));
+                    let _ = events.send(AppEvent::DownloadComplete {
+                        path: file_path.clone(),
+                        warning,
+                    });
+                }
+            }
+            _ = &mut cancel_rx => {
+                // Download was canceled; the target itself was never written
+                let _ = std::fs::remove_file(&part_path);
+
+                let record = AuditRecord::new(AuditOperation::Download, source, AuditOutcome::Canceled);
+                let _ = audit.record(&record.with_destination(destination));
+                let _ = events.send(AppEvent::DownloadCanceled {
+                    path: file_path.clone(),
+                });
+            }
+        }
+    });
+}
+
+/// Where a download to `target` is written until complete: `name.part` next to it
+fn part_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    target.with_file_name(name)
+}
+
+/// Walk the prefix and compare it with the local directory (`:sync`)
+async fn plan_sync(
+    backend: &Arc<dyn Backend>,
+    prefix: &str,
+    location: String,
+    local_dir: PathBuf,
+    delete: bool,
+) -> Result<SyncPlan> {
+    let remote = walk_remote(backend, prefix).await?;
+
+    // Hashing local files can take a while; keep it off the async workers
+    tokio::task::spawn_blocking(move || sync::plan(location, &local_dir, remote, delete)).await?
+}
+
+/// Every file below the prefix, with paths relative to it
+async fn walk_remote(backend: &Arc<dyn Backend>, prefix: &str) -> Result<Vec<RemoteFile>> {
+    let root = prefix.trim_matches('/');
+    let mut remote = Vec::new();
+    backend
+        .walk(prefix, &mut |path, entry| {
+            let trimmed = path.trim_start_matches('/');
+            let relative = trimmed.strip_prefix(root).unwrap_or(trimmed).trim_start_matches('/');
+            remote.push(RemoteFile {
+                path: path.to_string(),
+                relative: relative.to_string(),
+                size: entry.size.unwrap_or(0),
+                etag: entry.etag.clone(),
+            });
+        })
+        .await?;
+    Ok(remote)
+}
+
+/// List the prefix and compare it with the local directory (`:upload`)
+async fn plan_upload(backend: &Arc<dyn Backend>, prefix: &str, location: String, local_dir: PathBuf) -> Result<SyncPlan> {
+    let remote = walk_remote(backend, prefix).await?;
+    let prefix = prefix.to_string();
+    tokio::task::spawn_blocking(move || sync::plan_upload(location, &local_dir, &prefix, remote)).await?
+}
+
+/// Upload the files of a plan, at most `concurrency` at a time, and report the outcome
+async fn run_upload(
+    backend: Arc<dyn Backend>,
+    plan: SyncPlan,
+    concurrency: usize,
+    audit: AuditLog,
+    tx: mpsc::UnboundedSender<AppEvent>,
+) {
+    let started = std::time::Instant::now();
+    let total = plan.transfers.len();
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+    let mut join_set = tokio::task::JoinSet::new();
+
+    for transfer in plan.transfers {
+        let backend = backend.clone();
+        let semaphore = semaphore.clone();
+        join_set.spawn(async move {
+            let _permit = semaphore.acquire().await.expect("semaphore closed unexpectedly");
+            let result = backend.upload_file(&transfer.local, &transfer.remote).await;
+            (transfer, result)
+        });
+    }
+
+    let mut report = UploadReport {
+        location: plan.location,
+        ..Default::default()
+    };
+    let mut done = 0;
+    while let Some(result) = join_set.join_next().await {
+        done += 1;
+        match result {
+            Ok((transfer, Ok(()))) => {
+                let record = AuditRecord::new(AuditOperation::Upload, transfer.local.display().to_string(), AuditOutcome::Ok);
+                let destination = backend.get_display_path(&transfer.remote);
+                let _ = audit.record(&record.with_destination(destination).with_bytes(Some(transfer.size)));
+                report.uploaded += 1;
+                report.bytes += transfer.size;
+            }
+            Ok((transfer, Err(e))) => {
+                let error = format!("{:#}", e);
+                let record = AuditRecord::failed(AuditOperation::Upload, transfer.local.display().to_string(), &error);
+                let _ = audit.record(&record.with_destination(backend.get_display_path(&transfer.remote)));
+                report.failed.push((transfer.remote, error));
+            }
+            Err(e) => report.failed.push((String::from("?"), e.to_string())),
+        }
+        let _ = tx.send(AppEvent::UploadProgress { done, total });
+    }
+
+    report.elapsed = started.elapsed();
+    let _ = tx.send(AppEvent::UploadDone(report));
+}
+
+/// Search the text files below `prefix` that fit the preview size limit for
+/// lines containing `pattern` (`:grep`), reporting matches as they're found
+async fn grep_prefix(
+    backend: &Arc<dyn Backend>,
+    prefix: &str,
+    location: &str,
+    pattern: &str,
+    max_size: usize,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+) -> Result<()> {
+    let mut paths = Vec::new();
+    backend
+        .walk(prefix, &mut |path, entry| {
+            if entry.size.is_none_or(|size| size as usize <= max_size) {
+                paths.push(path.to_string());
+            }
+        })
+        .await?;
+
+    let mut found = Vec::new();
+    let mut total = 0;
+    let mut searched = 0;
+    let mut last_update = std::time::Instant::now();
+    for path in &paths {
+        searched += 1;
+        // Unreadable and binary files are skipped rather than ending the search
+        if let Ok(PreviewContent::Text(text, _)) = backend.get_preview(path, max_size).await {
+            let mut matches = grep::grep_text(path, &text, pattern);
+            matches.truncate(grep::MAX_MATCHES - total);
+            total += matches.len();
+            found.extend(matches);
+        }
+        if total >= grep::MAX_MATCHES {
+            break;
+        }
+        if last_update.elapsed() >= GREP_UPDATE_INTERVAL {
+            last_update = std::time::Instant::now();
+            let _ = tx.send(AppEvent::GrepProgress {
+                location: location.to_string(),
+                matches: std::mem::take(&mut found),
+                files_searched: searched,
+                complete: false,
+            });
+        }
+    }
+    let _ = tx.send(AppEvent::GrepProgress {
+        location: location.to_string(),
+        matches: found,
+        files_searched: searched,
+        complete: true,
+    });
+    Ok(())
+}
+
+/// Local copy of an object for a preview handler, deleted when dropped, so a
+/// preview dropped halfway (the cursor moved on, or it timed out) leaves
+/// nothing behind
+struct TempCopy(PathBuf);
+
+impl Drop for TempCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Preview a file with its configured handler, run on a temporary local copy
+async fn handler_preview(
+    backend: &Arc<dyn Backend>,
+    path: &str,
+    handler: &PreviewHandler,
+    metadata: FileMetadata,
+) -> Result<PreviewContent> {
+    static NEXT_COPY: AtomicUsize = AtomicUsize::new(0);
+    // Keep the file name: some commands go by the extension
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let copy = TempCopy(std::env::temp_dir().join(format!(
+        "rats3-preview-{}-{}-{}",
+        std::process::id(),
+        NEXT_COPY.fetch_add(1, Ordering::Relaxed),
+        name
+    )));
+    let rendered = async {
+        backend.download_file(path, &copy.0, None).await?;
+        handler.render(&copy.0).await
+    }
+    .await;
+    Ok(match rendered {
+        Ok(text) => PreviewContent::Text(text, metadata),
+        Err(e) => PreviewContent::Error(format!("{:#}", e)),
+    })
+}
+
+/// Read a file for diffing; only text files within the preview size limit can be compared
+async fn read_text(backend: &Arc<dyn Backend>, path: &str, max_size: usize) -> Result<String> {
+    match backend.get_preview(path, max_size).await? {
+        PreviewContent::Text(_, metadata) if metadata.truncated => {
+            anyhow::bail!("{} is too large to diff ({} bytes, limit {})", path, metadata.size.unwrap_or(0), max_size)
+        }
+        PreviewContent::Text(text, _) => Ok(text),
+        PreviewContent::Binary { .. } => anyhow::bail!("{} is a binary file", path),
+        PreviewContent::TooLarge { size, .. } => {
+            anyhow::bail!("{} is too large to diff ({} bytes, limit {})", path, size, max_size)
+        }
+        PreviewContent::Error(e) => anyhow::bail!("{}: {}", path, e),
+    }
+}
+
+/// Diff two files, or the previous and latest version of a single object
+async fn load_diff(backend: &Arc<dyn Backend>, paths: &[String], max_size: usize) -> Result<DiffView> {
+    let (old_label, old, new_label, new) = if let [old_path, new_path] = paths {
+        let old = read_text(backend, old_path, max_size).await?;
+        let new = read_text(backend, new_path, max_size).await?;
+        (old_path.clone(), old, new_path.clone(), new)
+    } else {
+        let path = &paths[0];
+        let versions = backend.list_versions(path).await?;
+        let [latest, previous, ..] = versions.as_slice() else {
+            anyhow::bail!("{} has no earlier version; select two files to compare", path);
+        };
+        let label = |v: &ObjectVersion| {
+            format!("{} @ {}", path, v.modified.as_deref().unwrap_or(&v.version_id))
+        };
+        let old = backend.read_version(path, &previous.version_id, max_size).await?;
+        let new = backend.read_version(path, &latest.version_id, max_size).await?;
+        (label(previous), old, label(latest), new)
+    };
+
+    // Diffing large files takes a moment; keep it off the async workers
+    let lines = tokio::task::spawn_blocking(move || diff::diff_lines(&old, &new)).await?;
+    Ok(DiffView { old_label, new_label, lines, side_by_side: false })
+}
+
+/// Spawn a background task to load the preview for the current selection.
+/// Cancels any previously in-flight preview load first.
+/// Navigation remains responsive while the fetch happens in the background.
+fn spawn_preview_load(app: &mut App, rt: &mut Runtime) {
+    // Cancel any in-flight load by dropping the old sender
+    rt.pending_preview_cancel = None;
+
+    if let Some((path, needs_loading)) = app.needs_preview_load() {
+        if needs_loading {
+            // Clear current preview so UI shows "Loading preview..."
+            app.clear_preview();
+            app.start_preview_load(path.clone());
+
+            let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel::<()>();
+            rt.pending_preview_cancel = Some(cancel_tx);
+
+            let backend_clone = rt.backend.clone();
+            let max_size = rt.config.preview_max_size;
+            let tx = rt.events.clone();
+            let timeout = rt.config.request_timeout_secs;
+            // Files larger than handler_max_size get the regular preview
+            let handler = app
+                .preview_handler_for(&path)
+                .filter(|handler| handler.reads_file())
+                .cloned()
+                .zip(app.selected_entry().map(|e| FileMetadata { size: e.size, modified: e.modified.clone(), ..Default::default() }))
+                .filter(|(_, metadata)| metadata.size.is_none_or(|size| size <= rt.config.preview.handler_max_size));
+
+            tokio::spawn(async move {
+                let load = with_timeout(timeout, async {
+                    match handler {
+                        Some((handler, metadata)) => handler_preview(&backend_clone, &path, &handler, metadata).await,
+                        None => backend_clone.get_preview(&path, max_size).await,
+                    }
+                });
+                tokio::select! {
+                    result = load => {
+                        let expired = result.as_ref().is_err_and(backend::is_auth_error);
+                        let content = match result {
+                            Ok(c) => c,
+                            Err(e) => PreviewContent::Error(e.to_string()),
+                        };
+                        let _ = tx.send(AppEvent::PreviewReady(path, content));
+                        if expired {
+                            let _ = tx.send(AppEvent::CredentialsExpired);
+                        }
+                    }
+                    _ = cancel_rx => {
+                        // User moved to another file; discard this result
+                    }
+                }
+            });
+        } else {
+            // Already in cache; just update the current path pointer
+            app.update_current_preview_path(path.clone());
+            // Prefetched previews (and ones whose highlighting was evicted) still need highlighting
+            if app.highlighted(&path).is_none() {
+                if let Some(PreviewContent::Text(text, _)) = app.get_preview() {
+                    highlight_preview(rt, path, text.clone());
+                }
+            }
+        }
+    } else {
+        // Directory selected or empty list — nothing to preview
+        app.clear_preview();
+    }
+}
@@ -0,0 +1,200 @@
+use crate::app::{App, FocusedPanel};
+use crate::backend::Backend;
+use crate::state::AppState;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Session used by `:mksession` and `--session` when no name is given
+pub const DEFAULT_SESSION: &str = "default";
+
+/// Cursor and scroll position in the preview pane
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviewPosition {
+    pub cursor_line: usize,
+    pub scroll_offset: usize,
+    #[serde(default)]
+    pub column_offset: usize,
+}
+
+/// Where the user was, saved with `:mksession` and resumed with `--session`:
+/// location, filter, selection and preview position
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Root of the backend (its display path for the empty prefix), e.g. `s3://bucket`
+    pub root: String,
+    /// Prefix below the root
+    #[serde(default)]
+    pub prefix: String,
+    /// Named context the location was opened from (for its AWS profile)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Search filter applied to the listing
+    #[serde(default)]
+    pub filter: String,
+    /// Name of the selected entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected: Option<String>,
+    /// Position in the selected file's preview
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<PreviewPosition>,
+    /// Whether the preview pane had focus
+    #[serde(default)]
+    pub preview_focused: bool,
+}
+
+impl Session {
+    /// Capture the current view of `app`
+    pub fn capture(app: &App, backend: &dyn Backend) -> Self {
+        let selected = app.selected_entry().map(|e| e.name.clone());
+        let preview = app
+            .current_preview_path()
+            .filter(|path| app.selected_object_path().as_deref() == Some(*path))
+            .map(|_| app.preview_position());
+        Self {
+            root: backend.get_display_path(""),
+            prefix: app.current_prefix().to_string(),
+            context: app.active_context().map(str::to_string),
+            filter: if app.is_search_mode() { app.search_query().to_string() } else { String::new() },
+            selected,
+            preview,
+            preview_focused: matches!(app.focused_panel(), FocusedPanel::Preview),
+        }
+    }
+
+    /// Restore the view on an app that has listed `prefix`. The preview
+    /// position is applied once the selected file's preview arrives.
+    pub fn apply(&self, app: &mut App) {
+        app.set_active_context(self.context.clone());
+        if !self.filter.is_empty() {
+            app.enter_search_mode();
+            app.set_search_query(self.filter.clone());
+        }
+        if let Some(name) = &self.selected {
+            app.select_entry_by_name(name);
+        }
+        if let (Some(position), Some(path)) = (self.preview, app.selected_object_path()) {
+            app.restore_preview_position(path, position);
+        }
+        if self.preview_focused && app.selected_entry().is_some_and(|e| !e.is_dir) {
+            app.focus_preview();
+        }
+    }
+
+    /// File a named session is stored in
+    pub fn file(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            bail!("Invalid session name: {:?}", name);
+        }
+        let dir = AppState::state_dir()?.join("sessions");
+        fs::create_dir_all(&dir).context("Failed to create sessions directory")?;
+        Ok(dir.join(format!("{}.json", name)))
+    }
+
+    /// Load a named session
+    pub fn load(name: &str) -> Result<Self> {
+        Self::load_from(&Self::file(name)?).with_context(|| format!("Cannot load session {}", name))
+    }
+
+    /// Save as a named session, replacing an existing one
+    pub fn save(&self, name: &str) -> Result<()> {
+        self.save_to(&Self::file(name)?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid session file {}", path.display()))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize session")?;
+        fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppMode;
+    use crate::backend::memory::MemoryBackend;
+    use crate::backend::{FileMetadata, PreviewContent};
+    use std::sync::Arc;
+
+    async fn listed_app() -> (App, Arc<MemoryBackend>) {
+        let backend = Arc::new(
+            MemoryBackend::new("test")
+                .with_file("data/notes.txt", "one\ntwo\nthree\n")
+                .with_file("data/report.csv", "a,b\n")
+                .with_file("data/summary.md", "# Summary\n"),
+        );
+        let mut app = App::new(backend.clone(), "data".to_string(), 50);
+        app.update_entries(backend.list("data").await.unwrap());
+        (app, backend)
+    }
+
+    #[tokio::test]
+    async fn test_capture_and_apply() {
+        let (mut app, backend) = listed_app().await;
+        app.set_active_context(Some("prod".to_string()));
+        app.enter_search_mode();
+        app.set_search_query("s".to_string());
+        app.select_entry_by_name("summary.md");
+
+        let session = Session::capture(&app, backend.as_ref());
+        assert_eq!(session.root, "memory://test");
+        assert_eq!(session.prefix, "data");
+        assert_eq!(session.filter, "s");
+        assert_eq!(session.selected.as_deref(), Some("summary.md"));
+        assert_eq!(session.context.as_deref(), Some("prod"));
+
+        let (mut restored, _) = listed_app().await;
+        session.apply(&mut restored);
+        assert_eq!(restored.mode(), &AppMode::Search);
+        assert_eq!(restored.search_query(), "s");
+        assert_eq!(restored.selected_entry().unwrap().name, "summary.md");
+        assert_eq!(restored.active_context(), Some("prod"));
+    }
+
+    #[tokio::test]
+    async fn test_preview_position_restored_when_preview_arrives() {
+        let (mut app, _) = listed_app().await;
+        let session = Session {
+            root: "memory://test".to_string(),
+            prefix: "data".to_string(),
+            selected: Some("notes.txt".to_string()),
+            preview: Some(PreviewPosition { cursor_line: 2, scroll_offset: 1, column_offset: 0 }),
+            preview_focused: true,
+            ..Default::default()
+        };
+        session.apply(&mut app);
+        assert!(matches!(app.focused_panel(), FocusedPanel::Preview));
+
+        let content = PreviewContent::Text("one\ntwo\nthree\n".to_string(), Default::default());
+        app.receive_preview("data/notes.txt".to_string(), content.clone());
+        assert_eq!(app.preview_position(), session.preview.unwrap());
+
+        // Only once: a changed file starts at the top again
+        let meta = FileMetadata { etag: Some("v2".to_string()), ..Default::default() };
+        app.receive_preview("data/notes.txt".to_string(), PreviewContent::Text("one\n".to_string(), meta));
+        assert_eq!(app.preview_position(), PreviewPosition::default());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("work.json");
+        let session = Session {
+            root: "s3://bucket".to_string(),
+            prefix: "logs/2024".to_string(),
+            filter: "err".to_string(),
+            ..Default::default()
+        };
+        session.save_to(&path).unwrap();
+        assert_eq!(Session::load_from(&path).unwrap(), session);
+
+        assert!(Session::load_from(&dir.path().join("missing.json")).is_err());
+        assert!(Session::file("../escape").is_err());
+        assert!(Session::file("").is_err());
+    }
+}
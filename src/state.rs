@@ -1,28 +1,144 @@
+use crate::config::PreviewLayout;
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Most history entries kept in the state file, however many instances add
+/// to it; sessions load as many of them as `history_max_entries` allows
+const MAX_SAVED_HISTORY: usize = 1000;
 
 /// Persistent state for the application
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppState {
     pub last_location: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_history")]
+    pub history: Vec<HistoryEntry>,
+    /// Pane sizes set with the resize keys, and the layout and zoom; None
+    /// until they were changed
+    #[serde(default)]
+    pub pane_sizes: Option<PaneSizes>,
+}
+
+/// A location in the history, with what it takes to open it again
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Display URI (`s3://bucket/prefix`, `local:///home/me`), which the
+    /// history shows and searches
+    pub uri: String,
+    /// Storage type, the URI's scheme ("s3", "local", ...)
+    pub scheme: String,
+    /// Bucket, root directory or host the location is on
+    pub root: String,
+    /// Prefix within the root
     #[serde(default)]
-    pub history: Vec<String>,
+    pub prefix: String,
+    /// AWS profile the backend was opened with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// AWS region the backend talked to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// When the location was last visited, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visited_at: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// An entry known only by its URI, as the history was saved before it
+    /// kept more: scheme, root and prefix are read off the URI. Local paths
+    /// keep the whole path as their root.
+    pub fn from_uri(uri: impl Into<String>) -> Self {
+        let uri = uri.into();
+        let (scheme, rest) = uri.split_once("://").unwrap_or(("local", &uri));
+        let (root, prefix) = if scheme == "local" { (rest, "") } else { rest.split_once('/').unwrap_or((rest, "")) };
+        Self {
+            scheme: scheme.to_string(),
+            root: root.to_string(),
+            prefix: prefix.to_string(),
+            uri: uri.clone(),
+            profile: None,
+            region: None,
+            visited_at: None,
+        }
+    }
 }
 
-impl Default for AppState {
+impl From<String> for HistoryEntry {
+    fn from(uri: String) -> Self {
+        Self::from_uri(uri)
+    }
+}
+
+/// History entries as records, or as the plain URIs older versions saved
+fn deserialize_history<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<HistoryEntry>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Entry(HistoryEntry),
+        Uri(String),
+    }
+
+    let stored = Vec::<Stored>::deserialize(deserializer)?;
+    Ok(stored
+        .into_iter()
+        .map(|entry| match entry {
+            Stored::Entry(entry) => entry,
+            Stored::Uri(uri) => HistoryEntry::from_uri(uri),
+        })
+        .collect())
+}
+
+/// Sizes of the resizable panes, and how they are arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaneSizes {
+    /// Share of the content area taken by the preview, in percent
+    pub preview_percent: u16,
+    /// Height of the status pane, borders included
+    pub status_height: u16,
+    /// Width of the progress pane docked on the right
+    pub progress_pane_width: u16,
+    /// Height of the progress pane docked at the bottom
+    pub progress_pane_height: u16,
+    /// Placement of the preview; None (as saved by older versions) keeps the
+    /// configured one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<PreviewLayout>,
+    /// Whether the focused pane is zoomed
+    pub zoomed: bool,
+}
+
+impl Default for PaneSizes {
     fn default() -> Self {
         Self {
-            last_location: None,
-            history: Vec::new(),
+            preview_percent: 50,
+            status_height: 5,
+            progress_pane_width: 40,
+            progress_pane_height: 12,
+            layout: None,
+            zoomed: false,
+        }
+    }
+}
+
+impl PaneSizes {
+    /// The sizes limited to what leaves every pane usable
+    pub fn clamped(self) -> Self {
+        Self {
+            preview_percent: self.preview_percent.clamp(20, 80),
+            status_height: self.status_height.clamp(3, 20),
+            progress_pane_width: self.progress_pane_width.clamp(20, 120),
+            progress_pane_height: self.progress_pane_height.clamp(4, 40),
+            ..self
         }
     }
 }
 
 impl AppState {
-    /// Get the state file path
-    pub fn state_file() -> Result<PathBuf> {
+    /// Get (and create) the rats3 state directory
+    pub fn state_dir() -> Result<PathBuf> {
         let state_dir = dirs::state_dir()
             .or_else(|| dirs::home_dir().map(|h| h.join(".local/state")))
             .context("Could not determine state directory")?;
@@ -31,18 +147,25 @@ impl AppState {
         fs::create_dir_all(&app_state_dir)
             .context("Failed to create state directory")?;
 
-        Ok(app_state_dir.join("last_location"))
+        Ok(app_state_dir)
+    }
+
+    /// Get the state file path
+    pub fn state_file() -> Result<PathBuf> {
+        Ok(Self::state_dir()?.join("last_location"))
     }
 
     /// Load state from disk
     pub fn load() -> Result<Self> {
-        let path = Self::state_file()?;
+        Self::load_from(&Self::state_file()?)
+    }
 
+    fn load_from(path: &Path) -> Result<Self> {
         if !path.exists() {
             return Ok(Self::default());
         }
 
-        let content = fs::read_to_string(&path)
+        let content = fs::read_to_string(path)
             .context("Failed to read state file")?;
 
         Ok(serde_json::from_str(&content).unwrap_or_default())
@@ -50,11 +173,19 @@ impl AppState {
 
     /// Save state to disk
     pub fn save(&self) -> Result<()> {
-        let path = Self::state_file()?;
+        self.save_to(&Self::state_file()?)
+    }
+
+    /// Write to a temporary file first, so a crash mid-write leaves the old
+    /// state rather than half of the new one
+    fn save_to(&self, path: &Path) -> Result<()> {
         let content = serde_json::to_string_pretty(self)
             .context("Failed to serialize state")?;
 
-        fs::write(&path, content)
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, content)
+            .context("Failed to write state file")?;
+        fs::rename(&temp, path)
             .context("Failed to write state file")?;
 
         Ok(())
@@ -66,15 +197,161 @@ impl AppState {
     }
 
     /// Update the history
-    pub fn set_history(&mut self, history: Vec<String>) {
+    pub fn set_history(&mut self, history: Vec<HistoryEntry>) {
         self.history = history;
     }
+
+    /// Update the pane sizes
+    pub fn set_pane_sizes(&mut self, sizes: PaneSizes) {
+        self.pane_sizes = Some(sizes);
+    }
+
+    /// Merge in what a session left behind. Its location replaces the saved
+    /// one only if the session `moved` since it last saved, so an instance
+    /// idling somewhere doesn't undo where another one went; the histories
+    /// are joined, the newer first.
+    pub fn record(&mut self, snapshot: &SessionSnapshot, moved: bool) {
+        if moved || self.last_location.is_none() {
+            self.set_last_location(snapshot.location.clone());
+        }
+
+        let saved = std::mem::take(&mut self.history);
+        let (newer, older) = if moved { (snapshot.history.clone(), saved) } else { (saved, snapshot.history.clone()) };
+        let mut history = newer;
+        for entry in older {
+            if !history.iter().any(|newer| newer.uri == entry.uri) {
+                history.push(entry);
+            }
+        }
+        history.truncate(MAX_SAVED_HISTORY);
+        self.set_history(history);
+
+        if let Some(sizes) = snapshot.pane_sizes {
+            self.set_pane_sizes(sizes);
+        }
+    }
+
+    /// Merge `snapshot` into the state file (see [`AppState::record`]). The
+    /// file is locked meanwhile, so instances saving at the same time don't
+    /// drop each other's changes.
+    pub fn save_snapshot(snapshot: &SessionSnapshot, moved: bool) -> Result<()> {
+        Self::save_snapshot_to(&Self::state_file()?, snapshot, moved)
+    }
+
+    fn save_snapshot_to(path: &Path, snapshot: &SessionSnapshot, moved: bool) -> Result<()> {
+        let _lock = StateLock::acquire(&path.with_extension("lock"))?;
+        let mut state = Self::load_from(path)?;
+        state.record(snapshot, moved);
+        state.save_to(path)
+    }
+}
+
+/// Exclusive lock on the state file, held until dropped. Advisory: it only
+/// keeps out other rats3 instances, which take it before writing.
+struct StateLock {
+    _file: fs::File,
+}
+
+impl StateLock {
+    /// Wait for the lock at `path`, creating the lock file if needed
+    fn acquire(path: &Path) -> Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .context("Failed to open state lock file")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            // SAFETY: flock only takes the descriptor of a file open for the
+            // lifetime of the call; closing the file releases the lock
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                return Err(std::io::Error::last_os_error()).context("Failed to lock state file");
+            }
+        }
+        Ok(Self { _file: file })
+    }
+}
+
+/// What a session contributes to the state file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSnapshot {
+    pub location: String,
+    pub history: Vec<HistoryEntry>,
+    /// Pane sizes, when they were changed in the session
+    pub pane_sizes: Option<PaneSizes>,
+}
+
+/// Writes a snapshot, and whether the session moved since the last one,
+/// somewhere: the state file, or a test's log
+type Saver = Box<dyn FnMut(&SessionSnapshot, bool) -> Result<()> + Send>;
+
+/// Saves a running session now and then, so a crash or a dropped connection
+/// loses at most `interval` of it. Whatever is still unsaved is saved when
+/// dropped, which includes unwinding from a panic.
+pub struct Autosave {
+    interval: Duration,
+    save: Saver,
+    saved: Option<SessionSnapshot>,
+    pending: Option<SessionSnapshot>,
+    saved_at: Instant,
+}
+
+impl Autosave {
+    /// Save to the state file at most once per `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self::with_saver(interval, AppState::save_snapshot)
+    }
+
+    fn with_saver(interval: Duration, save: impl FnMut(&SessionSnapshot, bool) -> Result<()> + Send + 'static) -> Self {
+        Self { interval, save: Box::new(save), saved: None, pending: None, saved_at: Instant::now() }
+    }
+
+    /// Note where the session is now
+    pub fn update(&mut self, snapshot: SessionSnapshot) {
+        self.pending = (self.saved.as_ref() != Some(&snapshot)).then_some(snapshot);
+    }
+
+    /// When the unsaved changes are to be saved; None when there are none
+    pub fn due(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|_| self.saved_at + self.interval)
+    }
+
+    /// Save the unsaved changes now. A failed save is tried again after the
+    /// next interval.
+    pub fn flush(&mut self) {
+        let Some(snapshot) = self.pending.take() else {
+            return;
+        };
+        self.saved_at = Instant::now();
+        let moved = self.saved.as_ref().is_none_or(|saved| saved.location != snapshot.location);
+        if (self.save)(&snapshot, moved).is_ok() {
+            self.saved = Some(snapshot);
+        } else {
+            self.pending = Some(snapshot);
+        }
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        self.flush();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn entries(uris: &[&str]) -> Vec<HistoryEntry> {
+        uris.iter().map(|uri| HistoryEntry::from_uri(*uri)).collect()
+    }
+
+    fn uris(history: &[HistoryEntry]) -> Vec<&str> {
+        history.iter().map(|entry| entry.uri.as_str()).collect()
+    }
+
     #[test]
     fn test_default_state() {
         let state = AppState::default();
@@ -92,7 +369,7 @@ mod tests {
     #[test]
     fn test_set_history() {
         let mut state = AppState::default();
-        let history = vec!["/path1".to_string(), "/path2".to_string()];
+        let history = entries(&["/path1", "/path2"]);
         state.set_history(history.clone());
         assert_eq!(state.history, history);
     }
@@ -100,10 +377,10 @@ mod tests {
     #[test]
     fn test_multiple_history_updates() {
         let mut state = AppState::default();
-        state.set_history(vec!["/path1".to_string()]);
+        state.set_history(entries(&["/path1"]));
         assert_eq!(state.history.len(), 1);
 
-        state.set_history(vec!["/path1".to_string(), "/path2".to_string()]);
+        state.set_history(entries(&["/path1", "/path2"]));
         assert_eq!(state.history.len(), 2);
     }
 
@@ -111,7 +388,7 @@ mod tests {
     fn test_serialize_deserialize() {
         let mut state = AppState::default();
         state.set_last_location("/test/location".to_string());
-        state.set_history(vec!["/hist1".to_string(), "/hist2".to_string()]);
+        state.set_history(entries(&["/hist1", "/hist2"]));
 
         let json = serde_json::to_string(&state).unwrap();
         let deserialized: AppState = serde_json::from_str(&json).unwrap();
@@ -120,6 +397,24 @@ mod tests {
         assert_eq!(deserialized.history, state.history);
     }
 
+    #[test]
+    fn test_pane_sizes() {
+        let mut state = AppState::default();
+        state.set_pane_sizes(PaneSizes { status_height: 8, ..Default::default() });
+        let json = serde_json::to_string(&state).unwrap();
+        let deserialized: AppState = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.pane_sizes.unwrap().status_height, 8);
+
+        // Sizes added later fall back to their defaults
+        let json = r#"{"pane_sizes":{"preview_percent":30}}"#;
+        let state: AppState = serde_json::from_str(json).unwrap();
+        assert_eq!(state.pane_sizes, Some(PaneSizes { preview_percent: 30, ..Default::default() }));
+
+        let json = r#"{"pane_sizes":{"layout":"bottom","zoomed":true}}"#;
+        let sizes = serde_json::from_str::<AppState>(json).unwrap().pane_sizes.unwrap();
+        assert_eq!((sizes.layout, sizes.zoomed), (Some(PreviewLayout::Bottom), true));
+    }
+
     #[test]
     fn test_deserialize_empty_json() {
         let json = "{}";
@@ -136,6 +431,103 @@ mod tests {
         assert!(state.history.is_empty()); // Should default to empty
     }
 
+    #[test]
+    fn test_history_records() {
+        // Plain URIs as saved by older versions, next to records
+        let json = r#"{"history":["s3://bucket/logs/2024","local:///home/me/src",
+            {"uri":"s3://other/data","scheme":"s3","root":"other","prefix":"data","profile":"prod","visited_at":1700000000}]}"#;
+        let state: AppState = serde_json::from_str(json).unwrap();
+        let old = &state.history[0];
+        assert_eq!((old.scheme.as_str(), old.root.as_str(), old.prefix.as_str()), ("s3", "bucket", "logs/2024"));
+        assert_eq!(old.profile, None);
+        let local = &state.history[1];
+        assert_eq!((local.scheme.as_str(), local.root.as_str(), local.prefix.as_str()), ("local", "/home/me/src", ""));
+        let record = &state.history[2];
+        assert_eq!(record.profile.as_deref(), Some("prod"));
+        assert_eq!(record.visited_at, Some(1_700_000_000));
+
+        // Saved back as records
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains(r#""root":"bucket""#));
+        let reloaded: AppState = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.history, state.history);
+    }
+
+    #[test]
+    fn test_autosave() {
+        use std::sync::{Arc, Mutex};
+
+        let saved = Arc::new(Mutex::new(Vec::new()));
+        let log = saved.clone();
+        let mut autosave = Autosave::with_saver(Duration::from_secs(5), move |snapshot, moved| {
+            log.lock().unwrap().push((snapshot.location.clone(), moved));
+            Ok(())
+        });
+        let snapshot = |location: &str| SessionSnapshot {
+            location: location.to_string(),
+            history: entries(&[location]),
+            pane_sizes: None,
+        };
+        assert!(autosave.due().is_none());
+
+        autosave.update(snapshot("s3://bucket/a"));
+        let due = autosave.due().unwrap();
+        assert!(due > Instant::now() + Duration::from_secs(4));
+        autosave.flush();
+        assert_eq!(*saved.lock().unwrap(), vec![("s3://bucket/a".to_string(), true)]);
+
+        // Nothing to save while the session stays where it was saved
+        autosave.update(snapshot("s3://bucket/a"));
+        assert!(autosave.due().is_none());
+
+        // A resize alone saves without claiming the location
+        autosave.update(SessionSnapshot { pane_sizes: Some(PaneSizes::default()), ..snapshot("s3://bucket/a") });
+        autosave.flush();
+        assert_eq!(saved.lock().unwrap()[1], ("s3://bucket/a".to_string(), false));
+
+        // So does changing the layout or zoom
+        let arranged = PaneSizes { layout: Some(PreviewLayout::Bottom), zoomed: true, ..PaneSizes::default() };
+        autosave.update(SessionSnapshot { pane_sizes: Some(arranged), ..snapshot("s3://bucket/a") });
+        assert!(autosave.due().is_some());
+        autosave.flush();
+        assert_eq!(saved.lock().unwrap()[2], ("s3://bucket/a".to_string(), false));
+
+        // Changes still unsaved are saved on drop
+        autosave.update(snapshot("s3://bucket/b"));
+        assert!(autosave.due().is_some());
+        drop(autosave);
+        assert_eq!(saved.lock().unwrap()[3], ("s3://bucket/b".to_string(), true));
+    }
+
+    #[test]
+    fn test_record_merges_instances() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("last_location");
+        let snapshot = |location: &str, history: &[&str]| SessionSnapshot {
+            location: location.to_string(),
+            history: entries(history),
+            pane_sizes: None,
+        };
+
+        // Two instances started from the same history
+        AppState::save_snapshot_to(&path, &snapshot("s3://b/one", &["s3://b/one", "s3://b/old"]), true).unwrap();
+        AppState::save_snapshot_to(&path, &snapshot("s3://b/two", &["s3://b/two", "s3://b/old"]), true).unwrap();
+        let state = AppState::load_from(&path).unwrap();
+        assert_eq!(state.last_location.as_deref(), Some("s3://b/two"));
+        assert_eq!(uris(&state.history), vec!["s3://b/two", "s3://b/old", "s3://b/one"]);
+
+        // The first one saving again without having moved keeps the other's location
+        let sizes = PaneSizes { preview_percent: 60, layout: Some(PreviewLayout::Hidden), ..PaneSizes::default() };
+        let resized = SessionSnapshot { pane_sizes: Some(sizes), ..snapshot("s3://b/one", &["s3://b/one", "s3://b/old"]) };
+        AppState::save_snapshot_to(&path, &resized, false).unwrap();
+        let state = AppState::load_from(&path).unwrap();
+        assert_eq!(state.last_location.as_deref(), Some("s3://b/two"));
+        assert_eq!(uris(&state.history), vec!["s3://b/two", "s3://b/old", "s3://b/one"]);
+        assert_eq!(state.pane_sizes, Some(sizes));
+
+        assert!(!dir.path().join("last_location.tmp").exists());
+    }
+
     #[test]
     fn test_state_file_path_exists() {
         // Just verify it can generate a path without panic
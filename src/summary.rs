@@ -0,0 +1,92 @@
+use crate::backend::Entry;
+use std::collections::HashMap;
+
+/// Object count and total size for one group (storage class or extension)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupTotals {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// Recursive size/count summary of a prefix (`:du`)
+#[derive(Debug, Clone, Default)]
+pub struct PrefixSummary {
+    pub object_count: u64,
+    pub total_bytes: u64,
+    pub by_storage_class: HashMap<String, GroupTotals>,
+    pub by_extension: HashMap<String, GroupTotals>,
+}
+
+impl PrefixSummary {
+    /// Storage class used for objects that don't report one
+    pub const DEFAULT_STORAGE_CLASS: &'static str = "STANDARD";
+    /// Group name for files without an extension
+    pub const NO_EXTENSION: &'static str = "(none)";
+
+    /// Add a file to the summary
+    pub fn add(&mut self, entry: &Entry) {
+        let size = entry.size.unwrap_or(0);
+        self.object_count += 1;
+        self.total_bytes += size;
+
+        let storage_class = entry
+            .storage_class
+            .clone()
+            .unwrap_or_else(|| Self::DEFAULT_STORAGE_CLASS.to_string());
+        let class_totals = self.by_storage_class.entry(storage_class).or_default();
+        class_totals.count += 1;
+        class_totals.bytes += size;
+
+        let extension = std::path::Path::new(&entry.name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_else(|| Self::NO_EXTENSION.to_string());
+        let ext_totals = self.by_extension.entry(extension).or_default();
+        ext_totals.count += 1;
+        ext_totals.bytes += size;
+    }
+
+    /// Groups sorted by size (largest first), then name
+    pub fn sorted(groups: &HashMap<String, GroupTotals>) -> Vec<(&String, &GroupTotals)> {
+        let mut sorted: Vec<_> = groups.iter().collect();
+        sorted.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(b.0)));
+        sorted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64, storage_class: Option<&str>) -> Entry {
+        Entry {
+            name: name.to_string(),
+            is_dir: false,
+            size: Some(size),
+            modified: None,
+            storage_class: storage_class.map(|s| s.to_string()),
+            restore_state: None,
+            etag: None,
+            permissions: None,
+        }
+    }
+
+    #[test]
+    fn test_summary_groups() {
+        let mut summary = PrefixSummary::default();
+        summary.add(&file("a.csv", 100, None));
+        summary.add(&file("b.CSV", 50, Some("GLACIER")));
+        summary.add(&file("README", 10, None));
+
+        assert_eq!(summary.object_count, 3);
+        assert_eq!(summary.total_bytes, 160);
+        assert_eq!(summary.by_extension["csv"], GroupTotals { count: 2, bytes: 150 });
+        assert_eq!(summary.by_extension[PrefixSummary::NO_EXTENSION].count, 1);
+        assert_eq!(summary.by_storage_class["STANDARD"].bytes, 110);
+        assert_eq!(summary.by_storage_class["GLACIER"].count, 1);
+
+        let sorted = PrefixSummary::sorted(&summary.by_extension);
+        assert_eq!(sorted[0].0, "csv");
+    }
+}
@@ -0,0 +1,429 @@
+use crate::ui::text_utils::format_size;
+use anyhow::{Context, Result};
+use md5::{Digest, Md5};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Part size the AWS CLI and SDKs use for multipart uploads by default
+const DEFAULT_PART_SIZE: u64 = 8 * 1024 * 1024;
+/// Multipart part sizes are usually a whole number of MiB
+const PART_SIZE_ALIGNMENT: u64 = 1024 * 1024;
+
+/// A remote file below the synced prefix
+#[derive(Debug, Clone)]
+pub struct RemoteFile {
+    /// Full backend path (used to download the file)
+    pub path: String,
+    /// Path relative to the synced prefix ('/'-separated)
+    pub relative: String,
+    pub size: u64,
+    pub etag: Option<String>,
+}
+
+/// Which way files are copied
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncDirection {
+    /// Remote prefix into the local directory (`:sync`)
+    #[default]
+    Download,
+    /// Local directory into the remote prefix (`:upload`)
+    Upload,
+}
+
+/// Why a file is transferred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncReason {
+    /// No local copy yet
+    New,
+    /// Local copy differs in size or content
+    Changed,
+}
+
+/// A file the sync will download or upload
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncTransfer {
+    pub remote: String,
+    pub relative: String,
+    pub local: PathBuf,
+    pub size: u64,
+    pub reason: SyncReason,
+}
+
+/// What `:sync`/`:upload` would do, shown for confirmation before anything is changed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncPlan {
+    pub direction: SyncDirection,
+    /// Display path of the remote prefix
+    pub location: String,
+    pub local_dir: PathBuf,
+    pub transfers: Vec<SyncTransfer>,
+    /// Local files without a remote counterpart (only collected with `--delete`)
+    pub deletions: Vec<PathBuf>,
+    /// Number of files whose size and ETag already match
+    pub unchanged: usize,
+    /// Remote keys that don't map to a path inside the local directory (or
+    /// local paths that aren't valid UTF-8 keys)
+    pub skipped: Vec<String>,
+    pub delete: bool,
+}
+
+impl SyncPlan {
+    /// Total bytes to transfer
+    pub fn transfer_bytes(&self) -> u64 {
+        self.transfers.iter().map(|t| t.size).sum()
+    }
+
+    /// Whether the local directory is already in sync
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty() && self.deletions.is_empty()
+    }
+}
+
+/// Outcome of running an upload plan
+#[derive(Debug, Clone, Default)]
+pub struct UploadReport {
+    /// Display path of the remote prefix
+    pub location: String,
+    pub uploaded: usize,
+    pub bytes: u64,
+    /// (key, error) for every failed upload
+    pub failed: Vec<(String, String)>,
+    pub elapsed: Duration,
+}
+
+impl UploadReport {
+    /// One-line summary for the status bar
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Uploaded {} file(s) ({}) to {} in {:.1}s",
+            self.uploaded,
+            format_size(self.bytes),
+            self.location,
+            self.elapsed.as_secs_f64()
+        );
+        if let Some((key, error)) = self.failed.first() {
+            summary.push_str(&format!(", {} failed ({}: {})", self.failed.len(), key, error));
+        }
+        summary
+    }
+}
+
+/// Compare the remote files against `local_dir` and work out what to transfer.
+/// A local file is up to date if its size matches and, when the object has an
+/// ETag, its MD5 matches the ETag.
+pub fn plan(location: String, local_dir: &Path, remote: Vec<RemoteFile>, delete: bool) -> Result<SyncPlan> {
+    let mut plan = SyncPlan {
+        location,
+        local_dir: local_dir.to_path_buf(),
+        delete,
+        ..Default::default()
+    };
+    let mut expected = HashSet::new();
+
+    for file in remote {
+        let Some(target) = local_path(local_dir, &file.relative) else {
+            plan.skipped.push(file.path);
+            continue;
+        };
+        expected.insert(target.clone());
+
+        let reason = match fs::metadata(&target) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Some(SyncReason::New),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", target.display())),
+            Ok(meta) if !meta.is_file() => Some(SyncReason::Changed),
+            Ok(meta) => compare(&target, meta.len(), &file)?,
+        };
+
+        match reason {
+            Some(reason) => plan.transfers.push(SyncTransfer {
+                remote: file.path,
+                relative: file.relative,
+                local: target,
+                size: file.size,
+                reason,
+            }),
+            None => plan.unchanged += 1,
+        }
+    }
+
+    if delete && local_dir.is_dir() {
+        let mut local_files = Vec::new();
+        collect_files(local_dir, &mut local_files)?;
+        plan.deletions = local_files.into_iter().filter(|p| !expected.contains(p)).collect();
+    }
+
+    plan.transfers.sort_by(|a, b| a.relative.cmp(&b.relative));
+    plan.deletions.sort();
+    Ok(plan)
+}
+
+/// Compare every file below `local_dir` with the remote files and work out
+/// what to upload into `remote_prefix` (remote files are never deleted)
+pub fn plan_upload(location: String, local_dir: &Path, remote_prefix: &str, remote: Vec<RemoteFile>) -> Result<SyncPlan> {
+    let mut plan = SyncPlan {
+        direction: SyncDirection::Upload,
+        location,
+        local_dir: local_dir.to_path_buf(),
+        ..Default::default()
+    };
+    let remote: HashMap<String, RemoteFile> = remote.into_iter().map(|f| (f.relative.clone(), f)).collect();
+    let root = remote_prefix.trim_matches('/');
+
+    let mut local_files = Vec::new();
+    collect_files(local_dir, &mut local_files)?;
+    for local in local_files {
+        let Some(relative) = relative_key(local_dir, &local) else {
+            plan.skipped.push(local.display().to_string());
+            continue;
+        };
+        let size = fs::metadata(&local)
+            .with_context(|| format!("Failed to read {}", local.display()))?
+            .len();
+
+        let reason = match remote.get(&relative) {
+            None => Some(SyncReason::New),
+            Some(file) => compare(&local, size, file)?,
+        };
+        match reason {
+            Some(reason) => plan.transfers.push(SyncTransfer {
+                remote: if root.is_empty() { relative.clone() } else { format!("{}/{}", root, relative) },
+                relative,
+                local,
+                size,
+                reason,
+            }),
+            None => plan.unchanged += 1,
+        }
+    }
+
+    plan.transfers.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(plan)
+}
+
+/// Whether a local file of `size` bytes differs from the remote file, and how
+fn compare(local: &Path, size: u64, remote: &RemoteFile) -> Result<Option<SyncReason>> {
+    let matches = size == remote.size
+        && match &remote.etag {
+            Some(etag) => etag_matches(local, etag, size)?,
+            None => true,
+        };
+    Ok((!matches).then_some(SyncReason::Changed))
+}
+
+/// '/'-separated key of a local file relative to `local_dir` (None for
+/// names that aren't valid UTF-8)
+fn relative_key(local_dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(local_dir).ok()?;
+    let parts: Option<Vec<&str>> = relative.components().map(|c| c.as_os_str().to_str()).collect();
+    Some(parts?.join("/"))
+}
+
+/// Local path for a '/'-separated relative key, or None if the key would
+/// escape the local directory
+fn local_path(local_dir: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    let mut path = local_dir.to_path_buf();
+    let mut has_file = false;
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => {
+                path.push(part);
+                has_file = true;
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    has_file.then_some(path)
+}
+
+/// All regular files below `dir` (symlinked directories are not followed)
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Whether a local file's content matches an S3 ETag. Single-part ETags are
+/// the MD5 of the content; multipart ETags ("<md5>-<parts>") are the MD5 of the
+/// concatenated part digests, so the part size has to be guessed.
+fn etag_matches(path: &Path, etag: &str, size: u64) -> Result<bool> {
+    let etag = etag.trim_matches('"').to_ascii_lowercase();
+    let part_size = match etag.split_once('-') {
+        None => None,
+        Some((_, parts)) => {
+            let Ok(parts) = parts.parse::<u64>() else {
+                // Not an MD5 based ETag (e.g. SSE-KMS); size is all we can compare
+                return Ok(true);
+            };
+            Some(guess_part_size(size, parts))
+        }
+    };
+    let local = file_etag(path, part_size).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(local == etag)
+}
+
+/// Part size that splits `size` bytes into `parts` parts: the SDK default if it
+/// fits, otherwise the smallest MiB-aligned size that does
+fn guess_part_size(size: u64, parts: u64) -> u64 {
+    if parts > 0 && size.div_ceil(DEFAULT_PART_SIZE) == parts {
+        return DEFAULT_PART_SIZE;
+    }
+    let per_part = size.div_ceil(parts.max(1));
+    per_part.div_ceil(PART_SIZE_ALIGNMENT).max(1) * PART_SIZE_ALIGNMENT
+}
+
+/// ETag S3 would compute for the file: plain MD5, or the multipart form when
+/// uploaded in parts of `part_size` bytes
+fn file_etag(path: &Path, part_size: Option<u64>) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let Some(part_size) = part_size else {
+        let mut hasher = Md5::new();
+        io::copy(&mut file, &mut hasher)?;
+        return Ok(hex(&hasher.finalize()));
+    };
+
+    let mut digests = Md5::new();
+    let mut parts = 0;
+    loop {
+        let mut hasher = Md5::new();
+        let copied = io::copy(&mut (&mut file).take(part_size), &mut hasher)?;
+        if copied == 0 && parts > 0 {
+            break;
+        }
+        digests.update(hasher.finalize());
+        parts += 1;
+        if copied < part_size {
+            break;
+        }
+    }
+    Ok(format!("{}-{}", hex(&digests.finalize()), parts))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(relative: &str, size: u64, etag: Option<&str>) -> RemoteFile {
+        RemoteFile {
+            path: format!("data/{}", relative),
+            relative: relative.to_string(),
+            size,
+            etag: etag.map(|e| e.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_plan_skips_matching_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("same.txt"), "hello").unwrap();
+        fs::write(dir.path().join("edited.txt"), "hellO").unwrap();
+        fs::write(dir.path().join("short.txt"), "hi").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/extra.txt"), "x").unwrap();
+
+        let hello_md5 = "5d41402abc4b2a76b9719d911017c592";
+        let files = vec![
+            remote("same.txt", 5, Some(hello_md5)),
+            remote("edited.txt", 5, Some(hello_md5)),
+            remote("short.txt", 5, Some(hello_md5)),
+            remote("sub/new.txt", 5, Some(hello_md5)),
+            remote("../escape.txt", 5, None),
+        ];
+
+        let plan = plan("s3://bucket/data".to_string(), dir.path(), files.clone(), false).unwrap();
+        assert_eq!(plan.unchanged, 1);
+        let transfers: Vec<_> = plan.transfers.iter().map(|t| (t.relative.as_str(), t.reason)).collect();
+        assert_eq!(
+            transfers,
+            vec![
+                ("edited.txt", SyncReason::Changed),
+                ("short.txt", SyncReason::Changed),
+                ("sub/new.txt", SyncReason::New),
+            ]
+        );
+        assert_eq!(plan.transfers[2].local, dir.path().join("sub").join("new.txt"));
+        assert_eq!(plan.transfer_bytes(), 15);
+        assert_eq!(plan.skipped, vec!["data/../escape.txt".to_string()]);
+        assert!(plan.deletions.is_empty());
+
+        // Deleting is opt-in
+        let plan = super::plan("s3://bucket/data".to_string(), dir.path(), files, true).unwrap();
+        assert_eq!(plan.deletions, vec![dir.path().join("sub").join("extra.txt")]);
+    }
+
+    #[test]
+    fn test_plan_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("same.txt"), "hello").unwrap();
+        fs::write(dir.path().join("edited.txt"), "hellO").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/new.txt"), "new").unwrap();
+
+        let hello_md5 = "5d41402abc4b2a76b9719d911017c592";
+        let files = vec![
+            remote("same.txt", 5, Some(hello_md5)),
+            remote("edited.txt", 5, Some(hello_md5)),
+            remote("remote-only.txt", 5, None),
+        ];
+
+        let plan = plan_upload("s3://bucket/data".to_string(), dir.path(), "data/", files).unwrap();
+        assert_eq!(plan.direction, SyncDirection::Upload);
+        assert_eq!(plan.unchanged, 1);
+        let transfers: Vec<_> = plan.transfers.iter().map(|t| (t.remote.as_str(), t.reason)).collect();
+        assert_eq!(
+            transfers,
+            vec![("data/edited.txt", SyncReason::Changed), ("data/sub/new.txt", SyncReason::New)]
+        );
+        assert_eq!(plan.transfer_bytes(), 8);
+        assert!(plan.deletions.is_empty());
+    }
+
+    #[test]
+    fn test_upload_report_summary() {
+        let mut report = UploadReport {
+            location: "s3://bucket/data/".to_string(),
+            uploaded: 2,
+            bytes: 2048,
+            failed: Vec::new(),
+            elapsed: Duration::from_millis(1500),
+        };
+        assert_eq!(report.summary(), "Uploaded 2 file(s) (2.00 KB) to s3://bucket/data/ in 1.5s");
+
+        report.failed.push(("data/a.txt".to_string(), "Access Denied".to_string()));
+        assert!(report.summary().ends_with(", 1 failed (data/a.txt: Access Denied)"));
+    }
+
+    #[test]
+    fn test_multipart_etag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, "hello world").unwrap();
+
+        // Parts are exactly `part_size` bytes except the last
+        let mut digests = Vec::new();
+        digests.extend_from_slice(&Md5::digest(b"hello "));
+        digests.extend_from_slice(&Md5::digest(b"world"));
+        let expected = format!("{}-2", hex(&Md5::digest(&digests)));
+        assert_eq!(file_etag(&path, Some(6)).unwrap(), expected);
+
+        assert_eq!(file_etag(&path, None).unwrap(), "5eb63bbbe01eeed093cb22bb8f5acdc3");
+        assert_eq!(guess_part_size(20 * 1024 * 1024, 3), DEFAULT_PART_SIZE);
+        assert_eq!(guess_part_size(20 * 1024 * 1024, 2), 10 * 1024 * 1024);
+    }
+}
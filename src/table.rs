@@ -0,0 +1,168 @@
+use unicode_width::UnicodeWidthStr;
+
+/// Widest a column is drawn; longer cells are truncated
+pub const MAX_COLUMN_WIDTH: usize = 40;
+
+/// A delimited text file (CSV, TSV, ...) split into records and fields
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    /// Records in file order; the first one is the header
+    pub rows: Vec<Vec<String>>,
+    /// Display width of each column (capped at MAX_COLUMN_WIDTH)
+    pub widths: Vec<usize>,
+}
+
+impl Table {
+    /// Parse delimited text. Quoted fields may contain the delimiter, newlines
+    /// and doubled quotes (`""`), as in RFC 4180.
+    pub fn parse(content: &str, delimiter: char) -> Self {
+        let rows = parse_records(content, delimiter);
+
+        let mut widths: Vec<usize> = Vec::new();
+        for row in &rows {
+            if row.len() > widths.len() {
+                widths.resize(row.len(), 0);
+            }
+            for (width, field) in widths.iter_mut().zip(row) {
+                *width = (*width).max(field.width().min(MAX_COLUMN_WIDTH));
+            }
+        }
+
+        Self { rows, widths }
+    }
+
+    /// Number of columns in the widest record
+    pub fn column_count(&self) -> usize {
+        self.widths.len()
+    }
+}
+
+/// Delimiter of a tabular file, judged by its extension
+pub fn delimiter_for_path(path: &str) -> Option<char> {
+    let ext = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "csv" => Some(','),
+        "tsv" | "tab" => Some('\t'),
+        "psv" => Some('|'),
+        _ => None,
+    }
+}
+
+/// Parse a delimiter given on the command line: a single character or one of
+/// the names `comma`, `tab`, `pipe`, `semicolon`, `space`
+pub fn parse_delimiter(s: &str) -> Option<char> {
+    match s.to_lowercase().as_str() {
+        "comma" => Some(','),
+        "tab" | "\\t" => Some('\t'),
+        "pipe" => Some('|'),
+        "semicolon" => Some(';'),
+        "space" => Some(' '),
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '"' && c != '\n' => Some(c),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn parse_records(content: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == '"' && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' {
+            if field.ends_with('\r') {
+                field.pop();
+            }
+            record.push(std::mem::take(&mut field));
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+
+    // Last record without a trailing newline
+    if !field.is_empty() || !record.is_empty() {
+        if field.ends_with('\r') {
+            field.pop();
+        }
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quoted_fields() {
+        let table = Table::parse("name,notes\r\n\"Doe, Jane\",\"said \"\"hi\"\"\"\nbob,\"two\nlines\"\n", ',');
+        assert_eq!(
+            table.rows,
+            vec![
+                vec!["name".to_string(), "notes".to_string()],
+                vec!["Doe, Jane".to_string(), "said \"hi\"".to_string()],
+                vec!["bob".to_string(), "two\nlines".to_string()],
+            ]
+        );
+        assert_eq!(table.widths, vec![9, 9]);
+    }
+
+    #[test]
+    fn test_ragged_rows_and_width_cap() {
+        let long = "x".repeat(100);
+        let table = Table::parse(&format!("a\n1,{}", long), ',');
+        assert_eq!(table.column_count(), 2);
+        assert_eq!(table.widths[1], MAX_COLUMN_WIDTH);
+        assert_eq!(table.rows[1][1].len(), 100);
+    }
+
+    #[test]
+    fn test_delimiter_for_path() {
+        assert_eq!(delimiter_for_path("data/users.CSV"), Some(','));
+        assert_eq!(delimiter_for_path("export.tsv"), Some('\t'));
+        assert_eq!(delimiter_for_path("dump.psv"), Some('|'));
+        assert_eq!(delimiter_for_path("notes.txt"), None);
+        assert_eq!(delimiter_for_path("Makefile"), None);
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter(";"), Some(';'));
+        assert_eq!(parse_delimiter("tab"), Some('\t'));
+        assert_eq!(parse_delimiter("\\t"), Some('\t'));
+        assert_eq!(parse_delimiter("Pipe"), Some('|'));
+        assert_eq!(parse_delimiter("\""), None);
+        assert_eq!(parse_delimiter("ab"), None);
+        assert_eq!(parse_delimiter(""), None);
+    }
+
+    #[test]
+    fn test_parse_tab_separated() {
+        let table = Table::parse("id\tname\n1\tcomma, inside\n", '\t');
+        assert_eq!(table.rows[1], vec!["1", "comma, inside"]);
+    }
+}
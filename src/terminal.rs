@@ -0,0 +1,186 @@
+use crate::config::Background;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Detect whether the terminal has a light or dark background.
+///
+/// Sends an OSC 11 query to the controlling terminal and parses the reply.
+/// Falls back to the `COLORFGBG` environment variable when the terminal does
+/// not answer in time. The terminal must already be in raw mode, otherwise the
+/// reply would be echoed and line-buffered.
+pub fn detect_background(timeout: Duration) -> Option<Background> {
+    query_osc11(timeout)
+        .and_then(|reply| parse_osc11_reply(&reply))
+        .map(classify_rgb)
+        .or_else(|| {
+            std::env::var("COLORFGBG")
+                .ok()
+                .and_then(|value| parse_colorfgbg(&value))
+        })
+}
+
+#[cfg(unix)]
+fn query_osc11(timeout: Duration) -> Option<String> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::time::Instant;
+
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    tty.write_all(b"\x1b]11;?\x07").ok()?;
+    tty.flush().ok()?;
+
+    let deadline = Instant::now() + timeout;
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 64];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return None;
+        }
+
+        let mut pollfd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: pollfd points to a single valid, initialized pollfd struct
+        let ready = unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            return None;
+        }
+
+        let n = tty.read(&mut buf).ok()?;
+        if n == 0 {
+            return None;
+        }
+        reply.extend_from_slice(&buf[..n]);
+
+        // Reply is terminated by BEL or ST (ESC \)
+        if reply.ends_with(b"\x07") || reply.ends_with(b"\x1b\\") {
+            return Some(String::from_utf8_lossy(&reply).into_owned());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn query_osc11(_timeout: Duration) -> Option<String> {
+    None
+}
+
+/// Parse an OSC 11 reply like `ESC ] 11 ; rgb:ffff/ffff/ffff BEL` into 8-bit RGB
+fn parse_osc11_reply(reply: &str) -> Option<(u8, u8, u8)> {
+    let start = reply.find("rgb:")? + "rgb:".len();
+    let body = reply[start..].trim_end_matches(['\x07', '\\', '\x1b']);
+
+    let mut channels = body.split('/').map(parse_channel);
+    let r = channels.next()??;
+    let g = channels.next()??;
+    let b = channels.next()??;
+    Some((r, g, b))
+}
+
+/// Save the window title on the terminal's title stack (XTWINOPS), so
+/// `pop_title` restores it on exit. Terminals without the stack ignore this.
+pub fn push_title(out: &mut impl Write) -> io::Result<()> {
+    out.write_all(b"\x1b[22;0t")?;
+    out.flush()
+}
+
+/// Restore the window title saved by `push_title`
+pub fn pop_title(out: &mut impl Write) -> io::Result<()> {
+    out.write_all(b"\x1b[23;0t")?;
+    out.flush()
+}
+
+/// Set the window and tab title (OSC 0). Control characters, which keys may
+/// contain, are left out so they can't end the sequence early.
+pub fn set_title(out: &mut impl Write, title: &str) -> io::Result<()> {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    write!(out, "\x1b]0;{}\x07", title)?;
+    out.flush()
+}
+
+/// Parse a 1-4 digit hex color channel and scale it to 8 bits
+fn parse_channel(hex: &str) -> Option<u8> {
+    if hex.is_empty() || hex.len() > 4 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let max = (1u32 << (4 * hex.len())) - 1;
+    Some((value * 255 / max) as u8)
+}
+
+/// Classify a background color by its relative luminance
+fn classify_rgb((r, g, b): (u8, u8, u8)) -> Background {
+    let luminance = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+    if luminance > 127.5 {
+        Background::Light
+    } else {
+        Background::Dark
+    }
+}
+
+/// Parse `COLORFGBG` ("fg;bg" or "fg;default;bg"), where the background is an ANSI color index
+fn parse_colorfgbg(value: &str) -> Option<Background> {
+    let bg: u8 = value.rsplit(';').next()?.trim().parse().ok()?;
+    match bg {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osc11_reply_bel() {
+        let reply = "\x1b]11;rgb:ffff/ffff/ffff\x07";
+        assert_eq!(parse_osc11_reply(reply), Some((255, 255, 255)));
+    }
+
+    #[test]
+    fn test_parse_osc11_reply_st() {
+        let reply = "\x1b]11;rgb:1a1a/1b1b/2626\x1b\\";
+        assert_eq!(parse_osc11_reply(reply), Some((26, 27, 38)));
+    }
+
+    #[test]
+    fn test_parse_osc11_reply_short_channels() {
+        assert_eq!(parse_osc11_reply("\x1b]11;rgb:f/8/0\x07"), Some((255, 136, 0)));
+        assert_eq!(parse_osc11_reply("\x1b]11;rgb:ff/80/00\x07"), Some((255, 128, 0)));
+    }
+
+    #[test]
+    fn test_parse_osc11_reply_invalid() {
+        assert_eq!(parse_osc11_reply("garbage"), None);
+        assert_eq!(parse_osc11_reply("\x1b]11;rgb:zz/00/00\x07"), None);
+        assert_eq!(parse_osc11_reply("\x1b]11;rgb:ff/00\x07"), None);
+    }
+
+    #[test]
+    fn test_classify_rgb() {
+        assert_eq!(classify_rgb((255, 255, 255)), Background::Light);
+        assert_eq!(classify_rgb((225, 226, 231)), Background::Light);
+        assert_eq!(classify_rgb((26, 27, 38)), Background::Dark);
+        assert_eq!(classify_rgb((0, 0, 0)), Background::Dark);
+    }
+
+    #[test]
+    fn test_set_title() {
+        let mut out = Vec::new();
+        set_title(&mut out, "rats3 — s3://bucket/odd\x07key").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b]0;rats3 — s3://bucket/oddkey\x07");
+    }
+
+    #[test]
+    fn test_parse_colorfgbg() {
+        assert_eq!(parse_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(parse_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(parse_colorfgbg("0;default;7"), Some(Background::Light));
+        assert_eq!(parse_colorfgbg("0;default"), None);
+    }
+}
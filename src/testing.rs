@@ -0,0 +1,1366 @@
+use crate::app::{App, AppEvent};
+use crate::backend::Backend;
+use crate::config::Config;
+use crate::runtime::Runtime;
+use crate::ui;
+use anyhow::{bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{backend::TestBackend, buffer::Buffer, Terminal};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// How long `Harness::settle` waits for another background event before it
+/// considers the app idle
+const SETTLE_QUIET: Duration = Duration::from_millis(50);
+
+/// Longest `Harness::settle` keeps handling background events
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Drives an `App` the way the terminal event loop does, but renders into
+/// ratatui's `TestBackend` so UI behavior can be checked without a terminal.
+///
+/// Each input is followed by `settle`, which handles the events of the
+/// background tasks it started (preview loads, downloads, ...) until the app
+/// is idle, so assertions see the finished result.
+pub struct Harness {
+    app: App,
+    runtime: Runtime,
+    events: mpsc::UnboundedReceiver<AppEvent>,
+    terminal: Terminal<TestBackend>,
+}
+
+impl Harness {
+    /// Open `prefix` of `backend` on a `width` x `height` terminal
+    pub async fn new(backend: Arc<dyn Backend>, prefix: &str, config: Config, width: u16, height: u16) -> Result<Self> {
+        let mut app = App::new(backend.clone(), prefix.to_string(), config.preview_width_percent);
+        app.apply_config(&config);
+        let result = backend.list(prefix).await?;
+        app.record_listing(result.entries.len());
+        app.update_entries(result);
+
+        let (mut runtime, events) = Runtime::new(backend, config);
+        runtime.load_preview(&mut app);
+
+        let mut harness = Self {
+            app,
+            runtime,
+            events,
+            terminal: Terminal::new(TestBackend::new(width, height))?,
+        };
+        harness.settle().await?;
+        Ok(harness)
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    pub fn runtime_mut(&mut self) -> &mut Runtime {
+        &mut self.runtime
+    }
+
+    /// Handle one event, without waiting for the background work it starts
+    pub async fn send(&mut self, event: AppEvent) -> Result<()> {
+        let area = self.terminal.size()?;
+        self.runtime.dispatch(&mut self.app, event, area).await?;
+        Ok(())
+    }
+
+    /// Handle one event, dropping it after `after` like Escape does while the
+    /// terminal event loop waits on a listing
+    pub async fn send_with_cancel(&mut self, event: AppEvent, after: Duration) -> Result<()> {
+        let area = self.terminal.size()?;
+        match tokio::time::timeout(after, self.runtime.dispatch(&mut self.app, event, area)).await {
+            Ok(result) => {
+                result?;
+            }
+            Err(_) => self.runtime.cancel_listing(&mut self.app),
+        }
+        Ok(())
+    }
+
+    /// Press a key and wait for its background work
+    pub async fn key(&mut self, key: KeyEvent) -> Result<()> {
+        self.send(AppEvent::KeyPress(key)).await?;
+        self.settle().await
+    }
+
+    /// Press a key without modifiers (other than Shift for uppercase
+    /// letters, as terminals send them)
+    pub async fn press(&mut self, code: KeyCode) -> Result<()> {
+        let modifiers = match code {
+            KeyCode::Char(c) if c.is_uppercase() => KeyModifiers::SHIFT,
+            _ => KeyModifiers::NONE,
+        };
+        self.key(KeyEvent::new(code, modifiers)).await
+    }
+
+    /// Press the keys of each character in turn
+    pub async fn type_text(&mut self, text: &str) -> Result<()> {
+        for c in text.chars() {
+            self.press(KeyCode::Char(c)).await?;
+        }
+        Ok(())
+    }
+
+    /// Run the timer-driven housekeeping once
+    pub async fn tick(&mut self) -> Result<()> {
+        self.send(AppEvent::Tick).await
+    }
+
+    /// Handle background events until none arrives for a short while
+    pub async fn settle(&mut self) -> Result<()> {
+        let started = Instant::now();
+        while let Ok(Some(event)) = tokio::time::timeout(SETTLE_QUIET, self.events.recv()).await {
+            self.send(event).await?;
+            if started.elapsed() > SETTLE_TIMEOUT {
+                bail!("App did not settle within {:?}", SETTLE_TIMEOUT);
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the UI and return the screen as text
+    pub fn snapshot(&mut self) -> Result<String> {
+        let (app, config) = (&self.app, self.runtime.config());
+        self.terminal.draw(|f| ui::render(f, app, config))?;
+        Ok(buffer_text(self.terminal.backend().buffer()))
+    }
+}
+
+/// Text of a rendered buffer, one line per row with trailing spaces removed
+pub fn buffer_text(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    (area.top()..area.bottom())
+        .map(|y| {
+            let row: String = (area.left()..area.right()).map(|x| buffer.get(x, y).symbol()).collect();
+            row.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+    use crate::app::{AppMode, FocusedPanel, PickMode};
+    use crate::audit::AuditLog;
+    use crate::config::{Confirmations, DownloadDestination, DownloadMetadata, EntryTime, LocationContext, StatusSegment};
+    use crate::plugin::{Plugin, PluginOutput};
+    use crate::state::HistoryEntry;
+
+    fn backend() -> Arc<MemoryBackend> {
+        Arc::new(
+            MemoryBackend::new("test")
+                .with_file("README.md", "# Demo\nhello")
+                .with_file("data/report.csv", "a,b\n1,2\n")
+                .with_file("data/notes.txt", "first line\nsecond line\n")
+                .with_file("logs/app.log", "started\n"),
+        )
+    }
+
+    async fn harness(config: Config) -> Harness {
+        Harness::new(backend(), "", config, 100, 20).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_selection_and_preview() {
+        let mut h = harness(Config::default()).await;
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("README.md"), "{}", screen);
+
+        h.press(KeyCode::Char('G')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("# Demo"), "{}", screen);
+
+        // Into a directory and back out again
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "data");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("first line"), "{}", screen);
+
+        h.press(KeyCode::Char('h')).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "");
+        assert_eq!(h.app().selected_entry().unwrap().name, "data");
+    }
+
+    #[tokio::test]
+    async fn test_preview_next_file() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Tab).await.unwrap();
+        assert!(matches!(h.app().focused_panel(), FocusedPanel::Preview));
+
+        // Directories are skipped
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        h.key(ctrl('n')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+        assert!(matches!(h.app().focused_panel(), FocusedPanel::Preview));
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("# Demo"), "{}", screen);
+
+        h.key(ctrl('n')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+        assert!(h.snapshot().unwrap().contains("Last file"));
+        h.key(ctrl('p')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+        assert!(h.snapshot().unwrap().contains("First file"));
+    }
+
+    #[tokio::test]
+    async fn test_preview_position_remembered() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "notes.txt");
+        h.press(KeyCode::Tab).await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        assert_eq!(h.app().preview_position().cursor_line, 1);
+
+        // Another file starts at the top; coming back returns to the line left
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        h.key(ctrl('n')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "report.csv");
+        assert_eq!(h.app().preview_position().cursor_line, 0);
+        h.key(ctrl('p')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "notes.txt");
+        assert_eq!(h.app().preview_position().cursor_line, 1);
+    }
+
+    #[tokio::test]
+    async fn test_filtering() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("read").await.unwrap();
+        assert_eq!(h.app().filtered_indices().len(), 1);
+
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("README.md"), "{}", screen);
+        assert!(!screen.contains("logs"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_background_filtering() {
+        let mut h = harness(Config::default()).await;
+        h.app_mut().set_background_filter_threshold(1);
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("read").await.unwrap();
+        assert!(!h.app().is_filtering());
+        assert_eq!(h.app().filtered_indices().len(), 1);
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+    }
+
+    #[tokio::test]
+    async fn test_narrowing_filter() {
+        let config = Config {
+            filter_presets: [("1".to_string(), "*.md".to_string())].into_iter().collect(),
+            ..Default::default()
+        };
+        let mut h = harness(config).await;
+        h.press(KeyCode::Char('1')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Filtered: *.md (Press / to narrow)"), "{}", screen);
+
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("re").await.unwrap();
+        assert_eq!(h.app().filtered_indices().len(), 1);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("/ *.md › re█"), "{}", screen);
+        assert!(screen.contains("/*.md /re"), "{}", screen);
+
+        h.press(KeyCode::Backspace).await.unwrap();
+        h.press(KeyCode::Backspace).await.unwrap();
+        h.press(KeyCode::Backspace).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("/ *.md█"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_exit_search_sequence() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("jj").await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+
+        // A j that turns out not to start the sequence still counts
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("aj").await.unwrap();
+        h.press(KeyCode::Backspace).await.unwrap();
+        assert_eq!(h.app().search_query(), "a");
+        h.type_text("j").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+        assert_eq!(h.app().search_query(), "aj");
+
+        // Without the sequence every key is typed
+        let mut config = Config::default();
+        config.key_bindings.exit_search_mode = String::new();
+        let mut h = harness(config).await;
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("jj").await.unwrap();
+        assert!(h.app().is_search_mode());
+        assert_eq!(h.app().search_query(), "jj");
+    }
+
+    #[tokio::test]
+    async fn test_kept_filter() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("a").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Filtered: a (Press / to narrow)"), "{}", screen);
+        assert!(!screen.contains("logs"), "{}", screen);
+
+        // Normal-mode keys browse the filtered listing
+        h.press(KeyCode::Char('G')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+
+        h.press(KeyCode::Esc).await.unwrap();
+        assert_eq!(h.app().search_query(), "");
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("logs"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_grep_opens_match() {
+        let mut h = harness(Config::default()).await;
+        h.type_text(":grep SECOND").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("0 matches in 4 files"), "{}", screen);
+        h.press(KeyCode::Esc).await.unwrap();
+        assert!(h.app().grep_view().is_none());
+
+        h.type_text(":grep line").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Grep);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("2 matches in 4 files"), "{}", screen);
+        assert!(screen.contains("data/notes.txt:2  second line"), "{}", screen);
+
+        // Enter on the second match opens the file with the cursor on its line
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+        assert_eq!(h.app().current_prefix(), "data");
+        assert_eq!(h.app().selected_entry().unwrap().name, "notes.txt");
+        assert!(matches!(h.app().focused_panel(), crate::app::FocusedPanel::Preview));
+        assert_eq!(h.app().preview_position().cursor_line, 1);
+    }
+
+    #[tokio::test]
+    async fn test_preview_search_filtered_view() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "notes.txt");
+        h.press(KeyCode::Tab).await.unwrap();
+
+        // Only matching lines are shown, under their own line numbers
+        h.type_text("/second").await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("[match 1/1]"), "{}", screen);
+        assert!(screen.contains("2 │ second line"), "{}", screen);
+        assert!(!screen.contains("first line"), "{}", screen);
+
+        // Enter shows the whole file again with the cursor on the match
+        h.press(KeyCode::Enter).await.unwrap();
+        assert!(!h.app().is_preview_search_active());
+        assert_eq!(h.app().preview_position().cursor_line, 1);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("first line"), "{}", screen);
+
+        // Esc goes back to where the search started
+        h.type_text("/first").await.unwrap();
+        h.press(KeyCode::Esc).await.unwrap();
+        assert_eq!(h.app().preview_position().cursor_line, 1);
+    }
+
+    #[tokio::test]
+    async fn test_preview_handlers() {
+        let mut config = Config::default();
+        for (ext, handler) in [("md", "hex"), ("log", "tr a-z A-Z <"), ("csv", "text")] {
+            config.preview.handlers.insert(ext.to_string(), handler.to_string().into());
+        }
+        let mut h = harness(config).await;
+        h.press(KeyCode::Char('G')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("00000000: 2320 4465 6d6f 0a68 656c 6c6f"), "{}", screen);
+
+        h.press(KeyCode::Char('k')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("STARTED"), "{}", screen);
+
+        // `text` turns off the table view of CSV files
+        h.press(KeyCode::Char('h')).await.unwrap();
+        h.press(KeyCode::Char('k')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "report.csv");
+        assert!(h.app().preview_table().is_none());
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("a,b"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_pre_operation_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("new.txt"), "new").unwrap();
+        let backend = Arc::new(MemoryBackend::new("test").with_file("data/a.txt", "a"));
+        let config = Config {
+            allow_writes: true,
+            // Lets uploads through once one of the keys mentions "approved"
+            pre_operation_hook: r#"grep -q approved || { echo "no ${RATS3_OPERATION}s to $RATS3_LOCATION"; exit 1; }"#
+                .to_string(),
+            confirmations: Confirmations { sync_plans: false, ..Default::default() },
+            ..Default::default()
+        };
+        let mut h = Harness::new(backend.clone(), "data", config, 100, 20).await.unwrap();
+        let upload = format!("upload {}", dir.path().display());
+
+        h.press(KeyCode::Char(':')).await.unwrap();
+        h.type_text(&upload).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        h.settle().await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Upload refused: no uploads to memory://test/data"), "{}", screen);
+        assert!(backend.file("data/new.txt").is_none());
+
+        // The hook runs in the background; the upload starts once it answers
+        std::fs::write(dir.path().join("approved.txt"), "ok").unwrap();
+        h.press(KeyCode::Char(':')).await.unwrap();
+        h.type_text(&upload).await.unwrap();
+        h.send(AppEvent::KeyPress(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))).await.unwrap();
+        let asking = |app: &App| app.status_message().is_some_and(|m| m.content.contains("Asking pre_operation_hook"));
+        while !asking(h.app()) {
+            let event = tokio::time::timeout(SETTLE_TIMEOUT, h.events.recv()).await.unwrap().unwrap();
+            h.send(event).await.unwrap();
+        }
+        assert!(backend.file("data/new.txt").is_none());
+        h.settle().await.unwrap();
+        assert_eq!(backend.file("data/new.txt").unwrap(), b"new");
+    }
+
+    #[tokio::test]
+    async fn test_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            download_destinations: vec![DownloadDestination {
+                name: "Temp".to_string(),
+                path: dir.path().display().to_string(),
+                for_buckets: Vec::new(),
+            }],
+            download_metadata: DownloadMetadata::Sidecar,
+            ..Default::default()
+        };
+        let mut h = harness(config).await;
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.press(KeyCode::Char('s')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Temp"), "{}", screen);
+
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join("README.md")).unwrap(), "# Demo\nhello");
+        assert!(!dir.path().join("README.md.part").exists());
+        let sidecar = std::fs::read_to_string(dir.path().join("README.md.rats3.json")).unwrap();
+        assert!(sidecar.contains("README.md\""), "{}", sidecar);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Downloaded 1 file(s)"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_download_by_number() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let destination = |name: &str, dir: &tempfile::TempDir| DownloadDestination {
+            name: name.to_string(),
+            path: dir.path().display().to_string(),
+            for_buckets: Vec::new(),
+        };
+        let config = Config {
+            download_destinations: vec![destination("First", &first), destination("Second", &second)],
+            ..Default::default()
+        };
+        let mut h = harness(config).await;
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.press(KeyCode::Char('s')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("2 \u{f019} Second"), "{}", screen);
+
+        h.press(KeyCode::Char('2')).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+        assert_eq!(std::fs::read_to_string(second.path().join("README.md")).unwrap(), "# Demo\nhello");
+        assert!(!first.path().join("README.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_choose_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let chosen = dir.path().join("chosen");
+        let config = Config {
+            download_destinations: vec![DownloadDestination {
+                name: "Temp".to_string(),
+                path: dir.path().display().to_string(),
+                for_buckets: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let mut h = harness(config).await;
+        h.runtime_mut().set_chosen_files(crate::chosen::ChosenFiles::create(chosen.clone()).unwrap());
+
+        // Opening a file chooses it, entering a directory doesn't
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&chosen).unwrap(), "memory://test/data/report.csv\n");
+
+        h.app_mut().focus_explorer();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('s')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&chosen).unwrap(),
+            "memory://test/data/report.csv\nmemory://test/data/notes.txt\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            download_destinations: vec![DownloadDestination {
+                name: "Temp".to_string(),
+                path: dir.path().display().to_string(),
+                for_buckets: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let mut h = harness(config).await;
+        h.runtime_mut().set_audit_log(AuditLog::new(dir.path().join("audit.jsonl")));
+        h.press(KeyCode::Char(':')).await.unwrap();
+        h.type_text("log").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert!(h.snapshot().unwrap().contains("The audit log is empty"));
+
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.press(KeyCode::Char('s')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char(':')).await.unwrap();
+        h.type_text("log").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Audit log, most recent last"), "{}", screen);
+        assert!(screen.contains("download  ok"), "{}", screen);
+        assert!(screen.contains("README.md →"), "{}", screen);
+
+        h.press(KeyCode::Esc).await.unwrap();
+        assert!(h.app().audit_log_view().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rerun_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            download_destinations: vec![DownloadDestination {
+                name: "Temp".to_string(),
+                path: dir.path().display().to_string(),
+                for_buckets: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let mut h = harness(config).await;
+        h.runtime_mut().set_audit_log(AuditLog::new(dir.path().join("audit.jsonl")));
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.press(KeyCode::Char('s')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let target = dir.path().join("README.md");
+        std::fs::write(&target, "stale").unwrap();
+
+        h.press(KeyCode::Char(':')).await.unwrap();
+        h.type_text("downloads").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Downloads, most recent last"), "{}", screen);
+        assert_eq!(h.app().audit_log_view().unwrap().records.len(), 1);
+
+        h.press(KeyCode::Char('r')).await.unwrap();
+        assert!(h.app().audit_log_view().is_none());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "# Demo\nhello");
+    }
+
+    #[tokio::test]
+    async fn test_session_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            download_destinations: vec![DownloadDestination {
+                name: "Temp".to_string(),
+                path: dir.path().display().to_string(),
+                for_buckets: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let mut h = harness(config).await;
+        assert_eq!(h.app().usage().listings, 1);
+        assert!(!h.snapshot().unwrap().contains("↓"));
+
+        // Previewing and downloading README.md fetch its 12 bytes twice
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.settle().await.unwrap();
+        assert_eq!(h.app().usage().preview_bytes, 12);
+        h.press(KeyCode::Char('s')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("3/3 files   ↓ 24B"), "{}", screen);
+
+        h.press(KeyCode::Char(':')).await.unwrap();
+        h.type_text("usage").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(
+            h.app().status_message().unwrap().content,
+            "This session listed 3 keys in 1 listing(s), previewed 12 B, downloaded 12 B"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resize_panes() {
+        let mut h = harness(Config::default()).await;
+        let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+        let status_top = |screen: &str| screen.lines().position(|line| line.contains(" Status ")).unwrap();
+        let before = status_top(&h.snapshot().unwrap());
+
+        // Works from search mode too
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.key(alt(KeyCode::Up)).await.unwrap();
+        h.key(alt(KeyCode::Up)).await.unwrap();
+        assert!(h.app().is_search_mode());
+        assert_eq!(status_top(&h.snapshot().unwrap()), before - 2);
+
+        h.key(alt(KeyCode::Left)).await.unwrap();
+        assert_eq!(h.app().preview_width_percent(), 55);
+        assert_eq!(h.app().resized_pane_sizes().unwrap().status_height, 7);
+    }
+
+    #[tokio::test]
+    async fn test_zen_mode() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Char('Z')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(!screen.contains("Normal Mode"), "{}", screen);
+        assert!(!screen.contains(" Status "), "{}", screen);
+        assert!(screen.lines().next().unwrap().contains("test (3 objects)"), "{}", screen);
+
+        // The search bar comes back while typing, messages take the bottom line
+        h.press(KeyCode::Char('/')).await.unwrap();
+        assert!(h.snapshot().unwrap().contains("Search Mode"));
+        h.press(KeyCode::Esc).await.unwrap();
+        h.press(KeyCode::Char(':')).await.unwrap();
+        h.type_text("usage").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(!screen.contains(" : "), "{}", screen);
+        assert!(screen.lines().last().unwrap().contains("This session listed"), "{}", screen);
+
+        h.press(KeyCode::Char('Z')).await.unwrap();
+        assert!(h.snapshot().unwrap().contains(" Status "));
+    }
+
+    #[tokio::test]
+    async fn test_confirmations() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            download_destinations: vec![DownloadDestination {
+                name: "Temp".to_string(),
+                path: dir.path().display().to_string(),
+                for_buckets: Vec::new(),
+            }],
+            confirmations: Confirmations {
+                download_files: 1,
+                egress_cost_per_gb: 0.09,
+                overwrites: true,
+                backend_switches: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.contexts.insert("local".to_string(), LocationContext { uri: dir.path().display().to_string(), profile: None });
+        let mut h = harness(config).await;
+
+        // Two files are over the limit; declining downloads nothing
+        h.press(KeyCode::Enter).await.unwrap();
+        h.type_text("v").await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Char('s')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Confirm);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Download 2 file(s) (31 B, ≈ $0.00 egress)"), "{}", screen);
+        h.press(KeyCode::Char('n')).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+        assert!(!dir.path().join("notes.txt").exists());
+
+        // A single file that already exists asks before it's replaced
+        std::fs::write(dir.path().join("report.csv"), "old").unwrap();
+        h.press(KeyCode::Char('s')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("existing file(s)?"), "{}", screen);
+        h.press(KeyCode::Char('y')).await.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join("report.csv")).unwrap(), "a,b\n1,2\n");
+
+        // Switching to another backend asks first
+        h.type_text(":context local").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert!(h.snapshot().unwrap().contains("Switch to context local?"));
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().active_context(), Some("local"));
+    }
+
+    #[tokio::test]
+    async fn test_context_picker() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("local.txt"), "local file").unwrap();
+        let mut config = Config::default();
+        for (name, uri) in [("local", dir.path().display().to_string()), ("missing", "/no/such/dir".to_string())] {
+            config.contexts.insert(name.to_string(), LocationContext { uri, profile: None });
+        }
+        let mut h = harness(config).await;
+
+        h.type_text(":context").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Context);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Switch Context"), "{}", screen);
+        assert!(screen.contains("missing"), "{}", screen);
+
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+        assert_eq!(h.app().active_context(), Some("local"));
+        assert_eq!(h.app().selected_entry().unwrap().name, "local.txt");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("local file"), "{}", screen);
+
+        // A context that can't be opened leaves the current location alone
+        h.type_text(":context missing").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().active_context(), Some("local"));
+        assert_eq!(h.app().selected_entry().unwrap().name, "local.txt");
+
+        h.type_text(":context nope").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Unknown context: nope"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_back_and_forward() {
+        let mut h = harness(Config::default()).await;
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+
+        // Into data/, then jump to logs/ through the parent
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Char('h')).await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "logs");
+
+        h.key(ctrl('o')).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "");
+        assert_eq!(h.app().selected_entry().unwrap().name, "logs");
+        h.key(ctrl('o')).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "data");
+        // The entry that was selected when data/ was left
+        assert_eq!(h.app().selected_entry().unwrap().name, "report.csv");
+
+        h.key(ctrl('i')).await.unwrap();
+        h.key(ctrl('i')).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "logs");
+        h.key(ctrl('i')).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "logs");
+
+        // Navigating somewhere new drops the forward locations
+        h.key(ctrl('o')).await.unwrap();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "data");
+        assert_eq!(h.app().navigation_depth().1, 0);
+    }
+
+    #[tokio::test]
+    async fn test_hung_requests() {
+        let backend = backend();
+        let config = Config { request_timeout_secs: 1, ..Default::default() };
+        let mut h = Harness::new(backend.clone(), "", config, 100, 20).await.unwrap();
+        backend.set_latency(Duration::from_secs(60));
+
+        // Escape stops a preview that is still loading
+        h.press(KeyCode::Char('G')).await.unwrap();
+        assert!(h.app().preview_load_elapsed().is_some());
+        h.press(KeyCode::Esc).await.unwrap();
+        assert!(h.app().is_preview_canceled());
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Preview canceled"), "{}", screen);
+
+        // A hung listing fails once it times out, on the view it started from
+        h.type_text("gg").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "");
+        let message = &h.app().status_message().unwrap().content;
+        assert!(message.contains("request timed out after 1s"), "{}", message);
+
+        // Canceling the flat view's listing keeps the directory view
+        let f = AppEvent::KeyPress(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::NONE));
+        h.send_with_cancel(f, Duration::from_millis(50)).await.unwrap();
+        assert_eq!(h.app().status_message().unwrap().content, "Listing canceled");
+        assert!(!h.app().is_flat_view());
+        backend.set_latency(Duration::ZERO);
+        h.press(KeyCode::Char('f')).await.unwrap();
+        assert!(h.app().is_flat_view());
+        assert_eq!(h.app().entries().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_expired_credentials() {
+        let backend = backend();
+        let config = Config { credential_command: "echo https://device.sso/start; echo CODE-1234".to_string(), ..Default::default() };
+        let mut h = Harness::new(backend.clone(), "", config, 100, 20).await.unwrap();
+        backend.set_failure(Some("unhandled error (ExpiredToken)"));
+
+        // The listing that failed runs again once the login succeeds
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "");
+        let question = &h.app().confirmation().unwrap().question;
+        assert_eq!(question, "Credentials expired. Run echo https://device.sso/start; echo CODE-1234 and retry?");
+        backend.set_failure(None);
+        h.press(KeyCode::Char('y')).await.unwrap();
+        let started = Instant::now();
+        while h.app().is_logging_in() && started.elapsed() < SETTLE_TIMEOUT {
+            h.settle().await.unwrap();
+        }
+        assert_eq!(h.app().current_prefix(), "data");
+
+        // Once declined, expired credentials are only reported
+        backend.set_failure(Some("unhandled error (ExpiredToken)"));
+        h.press(KeyCode::Char('h')).await.unwrap();
+        h.press(KeyCode::Char('n')).await.unwrap();
+        assert!(h.app().is_login_declined());
+        h.press(KeyCode::Char('h')).await.unwrap();
+        assert!(h.app().confirmation().is_none());
+        let message = &h.app().status_message().unwrap().content;
+        assert!(message.starts_with("Credentials expired; :login runs echo"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_listing_cache_and_refresh() {
+        let backend = backend();
+        let mut h = Harness::new(backend.clone(), "", Config::default(), 100, 20).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().entries().len(), 2);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("(2 objects in "), "{}", screen);
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("new.txt");
+        std::fs::write(&source, "new").unwrap();
+        backend.upload_file(&source, "data/new.txt").await.unwrap();
+
+        // Back into data/ reuses the listing
+        h.press(KeyCode::Char('h')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "data");
+        assert_eq!(h.app().entries().len(), 2);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("(2 objects, cached)"), "{}", screen);
+
+        h.press(KeyCode::F(5)).await.unwrap();
+        assert_eq!(h.app().entries().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() {
+        let backend = backend();
+        let config = Config { prefetch_distance: 1, ..Default::default() };
+        let mut h = Harness::new(backend.clone(), "", config, 100, 20).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "data");
+        h.send(AppEvent::Idle).await.unwrap();
+        h.settle().await.unwrap();
+
+        // data/ and logs/ were listed ahead of time, so a file added since doesn't show
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("new.txt");
+        std::fs::write(&source, "new").unwrap();
+        backend.upload_file(&source, "logs/new.txt").await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "logs");
+        assert_eq!(h.app().entries().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pick() {
+        let mut h = harness(Config::default()).await;
+        h.app_mut().set_pick_mode(Some(PickMode::Single));
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "data");
+        assert!(!h.app().should_quit());
+
+        h.press(KeyCode::Enter).await.unwrap();
+        assert!(h.app().should_quit());
+        assert_eq!(h.app().picked(), Some(&["memory://test/data/notes.txt".to_string()][..]));
+
+        // Multi mode picks the selected files
+        let mut h = harness(Config::default()).await;
+        h.app_mut().set_pick_mode(Some(PickMode::Multi));
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("PICK"), "{}", screen);
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char(' ')).await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Char(' ')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(
+            h.app().picked(),
+            Some(&["memory://test/data/notes.txt".to_string(), "memory://test/data/report.csv".to_string()][..])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entry_time() {
+        let recent = (chrono::Utc::now() - chrono::TimeDelta::hours(3)).format("%Y-%m-%d %H:%M:%S");
+        let fixture = format!(
+            r#"
+            [files]
+            "new.csv" = {{ contents = "a", modified = "{}" }}
+            "old.csv" = {{ contents = "b", modified = "2020-01-02 03:04:05" }}
+            "#,
+            recent
+        );
+        let backend = Arc::new(MemoryBackend::from_fixture("test", &fixture).unwrap());
+        let mut h = Harness::new(backend.clone(), "", Config::default(), 100, 20).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("new.csv  1 B  3h ago"), "{}", screen);
+        assert!(screen.contains("y ago"), "{}", screen);
+
+        let config = Config { entry_time: EntryTime::Absolute, ..Default::default() };
+        let mut h = Harness::new(backend.clone(), "", config, 100, 20).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("old.csv  1 B  2020-01-02 03:04"), "{}", screen);
+
+        let config = Config { entry_time: EntryTime::Hidden, ..Default::default() };
+        let mut h = Harness::new(backend, "", config, 100, 20).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(!screen.contains("ago"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_flat_view() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Char('f')).await.unwrap();
+        let names: Vec<&str> = h.app().entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["README.md", "data/notes.txt", "data/report.csv", "logs/app.log"]);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test, flat (4 objects"), "{}", screen);
+
+        // The filter matches the whole relative key
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("datrep").await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "data/report.csv");
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("data/report.csv [1/2"), "{}", screen);
+
+        // Back in the explorer, toggled off again
+        h.press(KeyCode::Char('h')).await.unwrap();
+        h.press(KeyCode::Char('f')).await.unwrap();
+        assert!(!h.app().is_flat_view());
+        assert_eq!(h.app().entries().len(), 3);
+
+        // Names are relative to the prefix
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char('f')).await.unwrap();
+        let names: Vec<&str> = h.app().entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["notes.txt", "report.csv"]);
+    }
+
+    #[tokio::test]
+    async fn test_flat_paging() {
+        let mut h = harness(Config { flat_page_size: 3, ..Config::default() }).await;
+        h.press(KeyCode::Char('f')).await.unwrap();
+        let names: Vec<&str> = h.app().entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["README.md", "data/notes.txt", "data/report.csv"]);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test, flat, page 1 of more"), "{}", screen);
+
+        h.press(KeyCode::Char(']')).await.unwrap();
+        let names: Vec<&str> = h.app().entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["logs/app.log"]);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test, flat, page 2, last"), "{}", screen);
+        h.press(KeyCode::Char(']')).await.unwrap();
+        assert_eq!(h.app().flat_paging().unwrap().page(), 2);
+
+        h.press(KeyCode::Char('[')).await.unwrap();
+        assert_eq!(h.app().entries().len(), 3);
+        assert_eq!(h.app().flat_paging().unwrap().page(), 1);
+
+        // Load all keeps the selection
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Char('A')).await.unwrap();
+        h.settle().await.unwrap();
+        assert_eq!(h.app().entries().len(), 4);
+        assert_eq!(h.app().selected_entry().unwrap().name, "data/notes.txt");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test, flat (4 objects"), "{}", screen);
+
+        // Directory view has no pages
+        h.press(KeyCode::Char('f')).await.unwrap();
+        h.press(KeyCode::Char(']')).await.unwrap();
+        assert_eq!(h.app().entries().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_auto_descend() {
+        let backend = Arc::new(
+            MemoryBackend::new("test")
+                .with_file("events/2024/01/15/a.json", "{}")
+                .with_file("events/2024/01/15/b.json", "{}")
+                .with_file("other.txt", "x"),
+        );
+        let mut h = Harness::new(backend.clone(), "", Config::default(), 100, 20).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "events");
+
+        let config = Config { auto_descend: true, ..Default::default() };
+        let mut h = Harness::new(backend, "", config, 100, 20).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "events/2024/01/15");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test › events › 2024 › 01 › 15 (2 objects"), "{}", screen);
+
+        // Going up is one level at a time, and forgets the chain
+        h.press(KeyCode::Char('h')).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "events/2024/01");
+        assert!(h.app().descended_chain().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_folder_markers() {
+        let backend = Arc::new(
+            MemoryBackend::new("test")
+                .with_file("emr/out_$folder$", "")
+                .with_file("emr/logs_$folder$", "")
+                .with_file("emr/logs/step.log", "ok")
+                .with_file("emr/run.txt", "x"),
+        );
+        let mut h = Harness::new(backend.clone(), "emr/", Config::default(), 100, 20).await.unwrap();
+        let names: Vec<_> = h.app().entries().iter().map(|e| (e.name.as_str(), e.is_dir)).collect();
+        assert_eq!(names, vec![("logs", true), ("out", true), ("run.txt", false)]);
+
+        // A marker without objects below it still opens as an empty directory
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "emr/out");
+        assert!(h.app().entries().is_empty());
+
+        let config = Config { hide_folder_markers: false, ..Default::default() };
+        let h = Harness::new(backend, "emr/", config, 100, 20).await.unwrap();
+        let names: Vec<_> = h.app().entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["logs", "logs_$folder$", "out_$folder$", "run.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_object_named_like_directory() {
+        let backend = Arc::new(
+            MemoryBackend::new("test")
+                .with_file("report", "marker object")
+                .with_file("report/part-0.csv", "a,b"),
+        );
+        let mut h = Harness::new(backend, "", Config::default(), 100, 20).await.unwrap();
+        assert_eq!(h.app().entries().len(), 1);
+        assert!(h.app().entries()[0].has_object());
+        h.settle().await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("report  +object 13 B"), "{}", screen);
+        // The preview shows the object; entering lists the directory
+        assert!(screen.contains("marker object"), "{}", screen);
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "report");
+        assert_eq!(h.app().entries()[0].name, "part-0.csv");
+    }
+
+    #[tokio::test]
+    async fn test_preview_encodings() {
+        let backend = Arc::new(MemoryBackend::new("test").with_file("app.log", b"caf\xe9 opened\n".to_vec()));
+        let mut h = Harness::new(backend, "", Config::default(), 100, 20).await.unwrap();
+        h.settle().await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("app.log [windows-1252]"), "{}", screen);
+        assert!(screen.contains("café opened"), "{}", screen);
+
+        // Overriding the guess decodes the same bytes again
+        h.press(KeyCode::Char('e')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("app.log [Shift_JIS, lossy]"), "{}", screen);
+        assert!(screen.contains("Decoded as Shift_JIS, lossy"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_scrollbars() {
+        let content: String = (1..=100).map(|i| if i == 90 { "needle\n".to_string() } else { format!("line {}\n", i) }).collect();
+        let backend = Arc::new(MemoryBackend::new("test").with_file("long.txt", content.into_bytes()));
+        let mut h = Harness::new(backend, "", Config::default(), 100, 20).await.unwrap();
+        h.settle().await.unwrap();
+        let thumb_rows = |screen: &str| screen.lines().filter(|l| l.ends_with('┃')).count();
+
+        // Only the long preview gets one, with its thumb at the top
+        let screen = h.snapshot().unwrap();
+        assert_eq!(thumb_rows(&screen), 1, "{}", screen);
+        let row = |screen: &str, text: &str| screen.lines().find(|l| l.contains(text)).unwrap().to_string();
+        assert!(row(&screen, "│ line 1 ").ends_with('┃'), "{}", screen);
+
+        // The filtered search view ticks where the matches are
+        h.press(KeyCode::Tab).await.unwrap();
+        h.type_text("/needle").await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.lines().any(|l| l.ends_with('━')), "{}", screen);
+
+        h.press(KeyCode::Esc).await.unwrap();
+        h.press(KeyCode::Char('G')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(!screen.lines().any(|l| l.ends_with('━')), "{}", screen);
+        assert!(row(&screen, "ETag:").ends_with('┃'), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_wrapped_preview_follows_cursor() {
+        let content: String = (0..20).map(|i| format!("{:02} {}end{:02}\n", i, "word ".repeat(12), i)).collect();
+        let backend = Arc::new(MemoryBackend::new("test").with_file("long.txt", content.into_bytes()));
+        let mut h = Harness::new(backend, "", Config::default(), 100, 20).await.unwrap();
+        h.settle().await.unwrap();
+        h.press(KeyCode::Tab).await.unwrap();
+        h.press(KeyCode::Char('w')).await.unwrap();
+
+        // Each line takes two rows, continuing past the line numbers
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("│     word word word word end00"), "{}", screen);
+        for _ in 0..6 {
+            h.press(KeyCode::Char('j')).await.unwrap();
+        }
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains(" 7 │ 06 word"), "{}", screen);
+        assert!(screen.contains("end06"), "{}", screen);
+        assert!(!screen.contains("end01"), "{}", screen);
+    }
+
+    #[tokio::test]
+    async fn test_kind_filter() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Char('F')).await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test, directories only (2/3 objects"), "{}", screen);
+        assert!(!screen.contains("README.md"), "{}", screen);
+
+        h.press(KeyCode::Char('F')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "README.md");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test, files only (1/3 objects"), "{}", screen);
+
+        // Stays on in the directories navigated into
+        h.press(KeyCode::Char('F')).await.unwrap();
+        h.press(KeyCode::Char('F')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "data");
+        assert_eq!(h.app().filtered_indices().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_group_by_extension() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char('E')).await.unwrap();
+        assert_eq!(h.app().selected_entry().unwrap().name, "notes.txt");
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("test by extension"), "{}", screen);
+        assert!(screen.contains("▾ csv  1 file, 8 B"), "{}", screen);
+        assert!(screen.contains("▾ txt  1 file, 23 B"), "{}", screen);
+
+        // Collapse the group under the cursor, then expand it with Enter on its header
+        h.press(KeyCode::Char('o')).await.unwrap();
+        assert!(h.app().selected_entry().is_none());
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("▸ txt  1 file, 23 B"), "{}", screen);
+        assert!(screen.contains("2/2 files"), "{}", screen);
+        assert_eq!(h.app().filtered_indices().len(), 3);
+
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.app().current_prefix(), "data");
+        assert_eq!(h.app().filtered_indices().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_properties_popup() {
+        let mut h = harness(Config::default()).await;
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('i')).await.unwrap();
+        let view = h.app().properties_view().unwrap();
+        assert!(view.complete);
+        assert!(view.rows.contains(&("Path".to_string(), "memory://test/README.md".to_string())));
+        assert!(view.rows.contains(&("Content type".to_string(), "text/markdown".to_string())));
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("Properties"), "{}", screen);
+        assert!(screen.contains("text/markdown"), "{}", screen);
+
+        h.press(KeyCode::Esc).await.unwrap();
+        assert!(h.app().properties_view().is_none());
+
+        // Directories have no properties
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('I')).await.unwrap();
+        assert!(h.app().properties_view().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_plugin_popup() {
+        let plugin = Plugin {
+            key: "Alt-p".to_string(),
+            command: "echo in {prefix}; for f in {files}; do echo \"got $f\"; done".to_string(),
+            output: PluginOutput::Popup,
+        };
+        let config = Config { plugins: [("list".to_string(), plugin)].into_iter().collect(), ..Default::default() };
+        let mut h = harness(config).await;
+        h.press(KeyCode::Char('G')).await.unwrap();
+        h.key(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::ALT)).await.unwrap();
+        h.settle().await.unwrap();
+        assert_eq!(h.app().mode(), &AppMode::PluginOutput);
+        let view = h.app().plugin_output_view().unwrap();
+        assert!(view.complete);
+        assert_eq!(view.lines, vec!["in memory://test", "got memory://test/README.md"]);
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("got memory://test/README.md"), "{}", screen);
+
+        h.press(KeyCode::Esc).await.unwrap();
+        assert!(h.app().plugin_output_view().is_none());
+        assert_eq!(h.app().mode(), &AppMode::Normal);
+
+        // Selected entries are passed together
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Char('g')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        h.press(KeyCode::Char(' ')).await.unwrap();
+        h.press(KeyCode::Char('j')).await.unwrap();
+        h.press(KeyCode::Char(' ')).await.unwrap();
+        h.type_text(":plugin list").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        h.settle().await.unwrap();
+        let view = h.app().plugin_output_view().unwrap();
+        assert_eq!(
+            view.lines,
+            vec!["in memory://test/data", "got memory://test/data/notes.txt", "got memory://test/data/report.csv"]
+        );
+
+        h.press(KeyCode::Esc).await.unwrap();
+        h.type_text(":plugin missing").await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert!(h.snapshot().unwrap().contains("No plugin named missing"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_control_socket() {
+        use crate::ipc::ControlServer;
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("rats3.sock");
+        let out = dir.path().join("out");
+        let destination = DownloadDestination { name: "out".to_string(), path: out.display().to_string(), for_buckets: Vec::new() };
+        let mut h = harness(Config { download_destinations: vec![destination], ..Default::default() }).await;
+        let _server = ControlServer::bind(&socket, h.runtime_mut().event_sender()).unwrap();
+        // Another instance can't take the socket over
+        assert!(ControlServer::bind(&socket, h.runtime_mut().event_sender()).is_err());
+
+        let stream = tokio::net::UnixStream::connect(&socket).await.unwrap();
+        let client = tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut responses = Vec::new();
+            for request in [
+                "navigate data",
+                "get-selection",
+                "download out",
+                r#"{"command": ["get-location"], "request_id": 3}"#,
+                "navigate /nonexistent-rats3-dir",
+            ] {
+                writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+                responses.push(lines.next_line().await.unwrap().unwrap());
+            }
+            responses
+        });
+        while !client.is_finished() {
+            h.settle().await.unwrap();
+        }
+        let responses = client.await.unwrap();
+        assert_eq!(responses[0], r#"{"data":"memory://test/data","error":"success"}"#);
+        assert_eq!(responses[1], r#"{"data":["memory://test/data/notes.txt"],"error":"success"}"#);
+        assert_eq!(
+            responses[2],
+            format!(r#"{{"data":{{"destination":"{}","files":1}},"error":"success"}}"#, out.display())
+        );
+        assert_eq!(responses[3], r#"{"data":"memory://test/data","error":"success","request_id":3}"#);
+        assert!(responses[4].starts_with(r#"{"error":"Cannot switch backend"#), "{}", responses[4]);
+
+        h.settle().await.unwrap();
+        assert!(out.join("notes.txt").exists());
+        assert_eq!(h.app().current_prefix(), "data");
+    }
+
+    #[tokio::test]
+    async fn test_history_opens_registered_scheme() {
+        use crate::backend::registry::{tests::MemoryFactory, BackendRegistry};
+
+        let mut h = harness(Config::default()).await;
+        let mut registry = BackendRegistry::default();
+        registry.register(Arc::new(MemoryFactory));
+        h.runtime_mut().set_registry(Arc::new(registry));
+        h.app_mut().load_history(vec![HistoryEntry::from_uri("memory://archive")]);
+
+        h.press(KeyCode::Char('R')).await.unwrap();
+        h.press(KeyCode::Enter).await.unwrap();
+        assert_eq!(h.runtime_mut().backend().location_name(), "archive");
+        assert_eq!(h.app().selected_entry().unwrap().name, "a.txt");
+    }
+
+    #[tokio::test]
+    async fn test_status_segments() {
+        let mut h = harness(Config::default()).await;
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("NORMAL   memory   3/3 files   ? help"), "{}", screen);
+
+        h.press(KeyCode::Char('/')).await.unwrap();
+        h.type_text("read").await.unwrap();
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("SEARCH   memory   /read   1/3 files"), "{}", screen);
+
+        let config = Config { status_segments: vec![StatusSegment::Count], ..Default::default() };
+        let mut h = harness(config).await;
+        let screen = h.snapshot().unwrap();
+        assert!(screen.contains("│  3/3 files"), "{}", screen);
+        assert!(!screen.contains("NORMAL"), "{}", screen);
+    }
+}
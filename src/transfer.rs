@@ -0,0 +1,348 @@
+use crate::config::DownloadMetadata;
+use crate::ui::text_utils::{self, format_count, format_size};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Span of recent progress the transfer rate is averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+/// Minimum span before a rate is reported (the first samples are too noisy)
+const MIN_RATE_SPAN: Duration = Duration::from_millis(500);
+
+/// Rolling transfer rate, fed with cumulative byte counts
+#[derive(Debug, Clone, Default)]
+pub struct TransferRate {
+    /// (time, cumulative bytes); the oldest sample is the last one recorded
+    /// before the window, so the rate covers the whole window
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl TransferRate {
+    /// Record the cumulative number of bytes transferred at `now`
+    pub fn record(&mut self, now: Instant, bytes: u64) {
+        self.samples.push_back((now, bytes));
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Bytes per second over the last few seconds; decays towards zero when
+    /// no progress is recorded. None until enough progress has been seen.
+    pub fn bytes_per_sec(&self, now: Instant) -> Option<f64> {
+        let (first_time, first_bytes) = *self.samples.front()?;
+        let (_, last_bytes) = *self.samples.back()?;
+        let span = now.saturating_duration_since(first_time);
+        if span < MIN_RATE_SPAN {
+            return None;
+        }
+        Some(last_bytes.saturating_sub(first_bytes) as f64 / span.as_secs_f64())
+    }
+}
+
+/// Time left to transfer `remaining` bytes at `bytes_per_sec` (None if stalled)
+pub fn eta(remaining: u64, bytes_per_sec: f64) -> Option<Duration> {
+    if bytes_per_sec < 1.0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(remaining as f64 / bytes_per_sec))
+}
+
+/// Data moved by this session, to keep an eye on metered connections.
+/// Listings are counted in keys; backends don't report their response sizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionUsage {
+    /// Listings fetched from the backend (not served from the cache)
+    pub listings: u64,
+    /// Keys those listings returned
+    pub keys_listed: u64,
+    /// Bytes of file content fetched for previews
+    pub preview_bytes: u64,
+    /// Bytes written by downloads, including canceled and failed ones
+    pub download_bytes: u64,
+    /// Bytes uploaded by `:upload`
+    pub upload_bytes: u64,
+}
+
+impl SessionUsage {
+    pub fn record_listing(&mut self, keys: usize) {
+        self.listings += 1;
+        self.keys_listed += keys as u64;
+    }
+
+    /// Bytes fetched from the backend: previews and downloads
+    pub fn received_bytes(&self) -> u64 {
+        self.preview_bytes + self.download_bytes
+    }
+
+    /// Whether any file content was transferred
+    pub fn has_transfers(&self) -> bool {
+        self.received_bytes() > 0 || self.upload_bytes > 0
+    }
+
+    /// `listed 1,024 keys in 3 listings, previewed 12.00 KB, downloaded 1.50 GB`
+    /// (uploads only when there were any)
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "listed {} keys in {} listing(s), previewed {}, downloaded {}",
+            format_count(self.keys_listed as usize),
+            self.listings,
+            format_size(self.preview_bytes),
+            format_size(self.download_bytes),
+        );
+        if self.upload_bytes > 0 {
+            summary.push_str(&format!(", uploaded {}", format_size(self.upload_bytes)));
+        }
+        summary
+    }
+}
+
+/// Destination file for each downloaded path. Files that share a basename
+/// (compared case-insensitively, as on macOS and Windows) would overwrite each
+/// other, so they get the parent prefixes that tell them apart as a suffix:
+/// `a/x/report.csv` and `b/x/report.csv` become `report (a_x).csv` and
+/// `report (b_x).csv`. Returns the targets and the number of renamed files.
+pub fn download_targets(paths: &[String], destination: &Path) -> (Vec<(String, PathBuf)>, usize) {
+    let segments: Vec<Vec<&str>> = paths
+        .iter()
+        .map(|p| p.split('/').filter(|s| !s.is_empty()).collect())
+        .collect();
+    let basename = |i: usize| segments[i].last().copied().unwrap_or(paths[i].as_str());
+
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for i in 0..paths.len() {
+        groups.entry(basename(i).to_lowercase()).or_default().push(i);
+    }
+
+    let mut names: Vec<String> = (0..paths.len()).map(|i| basename(i).to_string()).collect();
+    let mut renamed = 0;
+    for group in groups.values().filter(|g| g.len() > 1) {
+        // Use as many parent segments as it takes to make the group unique
+        let max_depth = group.iter().map(|&i| segments[i].len().saturating_sub(1)).max().unwrap_or(0);
+        let suffixes = (1..=max_depth)
+            .map(|depth| {
+                group
+                    .iter()
+                    .map(|&i| {
+                        let parents = &segments[i][..segments[i].len().saturating_sub(1)];
+                        parents[parents.len().saturating_sub(depth)..].join("_")
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .find(|suffixes| suffixes.iter().map(|s| s.to_lowercase()).collect::<HashSet<_>>().len() == group.len());
+
+        for (n, &i) in group.iter().enumerate() {
+            let suffix = match &suffixes {
+                Some(suffixes) if !suffixes[n].is_empty() => suffixes[n].clone(),
+                // Same key in a different case, or no parents to tell apart
+                _ => (n + 1).to_string(),
+            };
+            names[i] = with_suffix(basename(i), &suffix);
+            renamed += 1;
+        }
+    }
+
+    let targets = paths.iter().cloned().zip(names.into_iter().map(|name| destination.join(name))).collect();
+    (targets, renamed)
+}
+
+/// Insert " (suffix)" before the file extension
+fn with_suffix(name: &str, suffix: &str) -> String {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => format!("{} ({}){}", &name[..dot], suffix, &name[dot..]),
+        _ => format!("{} ({})", name, suffix),
+    }
+}
+
+/// The object a download came from, as recorded on the downloaded file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownloadSource {
+    /// URI of the object
+    pub uri: String,
+    /// Last-modified time as the backends format it, when listed
+    pub modified: Option<String>,
+    /// Entity tag, when listed
+    pub etag: Option<String>,
+}
+
+/// Extended attribute holding the URI a file was downloaded from (the one
+/// browsers and wget set)
+const ORIGIN_XATTR: &str = "user.xdg.origin.url";
+/// Extended attribute holding the ETag of the object a file was downloaded from
+const ETAG_XATTR: &str = "user.rats3.etag";
+
+/// Sidecar file next to a download at `path`: `name.rats3.json`
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".rats3.json");
+    path.with_file_name(name)
+}
+
+/// Give the downloaded file at `path` the object's last-modified time (with
+/// `preserve_mtime`) and record where it came from as `metadata` says
+pub fn mark_download(path: &Path, source: &DownloadSource, preserve_mtime: bool, metadata: DownloadMetadata) -> Result<()> {
+    let modified = source.modified.as_deref().and_then(text_utils::parse_timestamp);
+    if let Some(modified) = modified.filter(|_| preserve_mtime) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::from(modified)))
+            .with_context(|| format!("Failed to set the modification time of {}", path.display()))?;
+    }
+
+    match metadata {
+        DownloadMetadata::None => {}
+        DownloadMetadata::Xattr => {
+            set_xattr(path, ORIGIN_XATTR, &source.uri)?;
+            if let Some(etag) = &source.etag {
+                set_xattr(path, ETAG_XATTR, etag)?;
+            }
+        }
+        DownloadMetadata::Sidecar => {
+            let sidecar = serde_json::json!({
+                "source": source.uri,
+                "etag": source.etag,
+                "last_modified": modified.map(|t| t.to_rfc3339()),
+            });
+            let sidecar_path = sidecar_path(path);
+            fs::write(&sidecar_path, format!("{:#}\n", sidecar))
+                .with_context(|| format!("Failed to write {}", sidecar_path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Set the extended attribute `name` of the file at `path`
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn set_xattr(path: &Path, name: &str, value: &str) -> Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let c_name = CString::new(name)?;
+    let value_ptr = value.as_ptr() as *const libc::c_void;
+    #[cfg(target_os = "linux")]
+    let status = unsafe { libc::setxattr(c_path.as_ptr(), c_name.as_ptr(), value_ptr, value.len(), 0) };
+    #[cfg(target_os = "macos")]
+    let status = unsafe { libc::setxattr(c_path.as_ptr(), c_name.as_ptr(), value_ptr, value.len(), 0, 0) };
+    if status != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to set {} on {}", name, path.display()));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn set_xattr(_path: &Path, _name: &str, _value: &str) -> Result<()> {
+    anyhow::bail!("Extended attributes are not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(paths: &[&str]) -> (Vec<String>, usize) {
+        let paths: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+        let (targets, renamed) = download_targets(&paths, Path::new("/dl"));
+        let names = targets
+            .into_iter()
+            .map(|(_, target)| target.strip_prefix("/dl").unwrap().display().to_string())
+            .collect();
+        (names, renamed)
+    }
+
+    #[test]
+    fn test_download_targets_disambiguate_collisions() {
+        assert_eq!(names(&["a/one.txt", "b/two.txt"]), (vec!["one.txt".into(), "two.txt".into()], 0));
+        assert_eq!(
+            names(&["a/x/report.csv", "b/x/report.csv", "c/other.csv"]),
+            (vec!["report (a_x).csv".into(), "report (b_x).csv".into(), "other.csv".into()], 2)
+        );
+        // Case-insensitive file systems would merge these too
+        assert_eq!(
+            names(&["logs/README", "logs/readme"]),
+            (vec!["README (1)".into(), "readme (2)".into()], 2)
+        );
+        assert_eq!(
+            names(&["2024/.env", "2025/.env"]),
+            (vec![".env (2024)".into(), ".env (2025)".into()], 2)
+        );
+    }
+
+    #[test]
+    fn test_transfer_rate() {
+        let start = Instant::now();
+        let mut rate = TransferRate::default();
+        assert_eq!(rate.bytes_per_sec(start), None);
+
+        rate.record(start, 0);
+        rate.record(start + Duration::from_millis(100), 100);
+        // Too early for a meaningful rate
+        assert_eq!(rate.bytes_per_sec(start + Duration::from_millis(100)), None);
+
+        rate.record(start + Duration::from_secs(2), 2000);
+        assert_eq!(rate.bytes_per_sec(start + Duration::from_secs(2)), Some(1000.0));
+
+        // Old samples leave the window: the rate follows the recent speed
+        for secs in 3..=10 {
+            rate.record(start + Duration::from_secs(secs), 2000 + (secs - 2) * 100);
+        }
+        let now = start + Duration::from_secs(10);
+        let bps = rate.bytes_per_sec(now).unwrap();
+        assert!((bps - 100.0).abs() < 1.0, "rate {}", bps);
+
+        // A stalled transfer decays towards zero
+        let later = rate.bytes_per_sec(now + Duration::from_secs(20)).unwrap();
+        assert!(later < 30.0, "rate {}", later);
+    }
+
+    #[test]
+    fn test_eta() {
+        assert_eq!(eta(1000, 100.0), Some(Duration::from_secs(10)));
+        assert_eq!(eta(1000, 0.0), None);
+    }
+
+    #[test]
+    fn test_session_usage() {
+        let mut usage = SessionUsage::default();
+        assert!(!usage.has_transfers());
+        usage.record_listing(1500);
+        usage.record_listing(2);
+        assert!(!usage.has_transfers());
+        usage.preview_bytes = 2048;
+        usage.download_bytes = 1024;
+        assert_eq!(usage.received_bytes(), 3072);
+        assert_eq!(usage.summary(), "listed 1,502 keys in 2 listing(s), previewed 2.00 KB, downloaded 1.00 KB");
+        usage.upload_bytes = 10;
+        assert!(usage.summary().ends_with(", uploaded 10 B"), "{}", usage.summary());
+    }
+
+    #[test]
+    fn test_mark_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.csv");
+        fs::write(&path, "a,b\n").unwrap();
+        let source = DownloadSource {
+            uri: "s3://bucket/data/report.csv".to_string(),
+            modified: Some("2024-03-01 12:30:00".to_string()),
+            etag: Some("9a0364b9e99bb480dd25e1f0284c8555".to_string()),
+        };
+
+        mark_download(&path, &source, false, DownloadMetadata::None).unwrap();
+        assert!(fs::metadata(&path).unwrap().modified().unwrap() > SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_296_200));
+
+        mark_download(&path, &source, true, DownloadMetadata::Sidecar).unwrap();
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(modified, SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_296_200));
+        let sidecar: serde_json::Value = serde_json::from_str(&fs::read_to_string(sidecar_path(&path)).unwrap()).unwrap();
+        assert_eq!(sidecar["source"], "s3://bucket/data/report.csv");
+        assert_eq!(sidecar["etag"], "9a0364b9e99bb480dd25e1f0284c8555");
+        assert_eq!(sidecar["last_modified"], "2024-03-01T12:30:00+00:00");
+
+        // Unlisted times leave the file alone
+        let unlisted = DownloadSource { modified: None, ..source };
+        mark_download(&path, &unlisted, true, DownloadMetadata::None).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+    }
+}
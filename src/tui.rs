@@ -0,0 +1,759 @@
+use crate::app::{App, AppEvent, PickMode};
+use crate::audit::AuditLog;
+use crate::backend::{registry::BackendRegistry, Backend};
+use crate::chosen::ChosenFiles;
+use crate::config::{Background, Config};
+#[cfg(unix)]
+use crate::ipc::ControlServer;
+use crate::plugin::PluginRun;
+use crate::runtime::Runtime;
+use crate::session::Session;
+use crate::state::{Autosave, HistoryEntry, PaneSizes, SessionSnapshot};
+use crate::transfer::SessionUsage;
+use crate::ui;
+use anyhow::Result;
+use crossterm::{
+    cursor::Show,
+    event::{Event, KeyCode, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    execute,
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen, LeaveAlternateScreen,
+    },
+};
+use futures::StreamExt;
+use ratatui::{
+    backend::Backend as TerminalBackend,
+    backend::CrosstermBackend,
+    buffer::Buffer,
+    layout::Rect,
+    style::Style,
+    Terminal,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Once, PoisonError},
+    thread::{self, ThreadId},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Frame interval for animations (spinner, speed and ETA) while downloads run
+const ANIMATION_TICK: Duration = Duration::from_millis(80);
+
+/// Interval for time-based housekeeping: expiring status messages and finished
+/// downloads, giving up on a pending key whose sequence timed out
+const HOUSEKEEPING_TICK: Duration = Duration::from_millis(250);
+
+/// Shortest time between two frames. Changes arriving faster than this (a
+/// burst of progress updates or highlight chunks) are folded into the next frame.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Longest a change to the location, history or pane sizes goes unsaved
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Quiet time after which the entries around the cursor are prefetched
+const PREFETCH_IDLE: Duration = Duration::from_millis(300);
+
+/// What to browse and how: the backend and prefix to open, the config, and
+/// the state to start from
+pub struct RunOptions {
+    backend: Arc<dyn Backend>,
+    prefix: String,
+    config: Config,
+    registry: BackendRegistry,
+    context: Option<String>,
+    session: Option<Session>,
+    history: Vec<HistoryEntry>,
+    config_error: Option<String>,
+    config_warning: Option<String>,
+    pick: Option<PickMode>,
+    pane_sizes: Option<PaneSizes>,
+    autosave: bool,
+    listen: Option<PathBuf>,
+    choose_files: Option<PathBuf>,
+}
+
+impl RunOptions {
+    /// Browse the root of `backend` with `config`
+    pub fn new(backend: Arc<dyn Backend>, config: Config) -> Self {
+        Self {
+            backend,
+            prefix: String::new(),
+            config,
+            registry: BackendRegistry::default(),
+            context: None,
+            session: None,
+            history: Vec::new(),
+            config_error: None,
+            config_warning: None,
+            pick: None,
+            pane_sizes: None,
+            autosave: false,
+            listen: None,
+            choose_files: None,
+        }
+    }
+
+    /// Start at `prefix` instead of the root
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Open the URIs switched to (history, contexts) through `registry`
+    pub fn with_registry(mut self, registry: BackendRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Name of the context the backend was opened from
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    /// Restore a saved session once the first listing is shown
+    pub fn with_session(mut self, session: Session) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Locations for the history (R), most recent first
+    pub fn with_history(mut self, history: Vec<HistoryEntry>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Error the config failed to load with, shown as a warning on startup
+    pub fn with_config_error(mut self, error: impl Into<String>) -> Self {
+        self.config_error = Some(error.into());
+        self
+    }
+
+    /// Warning about the loaded config (such as ignored project settings),
+    /// shown on startup
+    pub fn with_config_warning(mut self, warning: impl Into<String>) -> Self {
+        self.config_warning = Some(warning.into());
+        self
+    }
+
+    /// Pick files: Enter on a file picks it (or in multi mode the selected
+    /// files) and quits, returning them in [`RunOutcome::picked`]
+    pub fn with_pick(mut self, mode: PickMode) -> Self {
+        self.pick = Some(mode);
+        self
+    }
+
+    /// Pane sizes, layout and zoom saved by an earlier session, in place of
+    /// the configured preview width and layout
+    pub fn with_pane_sizes(mut self, sizes: PaneSizes) -> Self {
+        self.pane_sizes = Some(sizes);
+        self
+    }
+
+    /// Save the location, history and pane sizes to the state file every
+    /// few seconds while they change, and once more on the way out, so they
+    /// survive a crash or a dropped connection. Merges with what other
+    /// instances saved meanwhile.
+    pub fn with_autosave(mut self) -> Self {
+        self.autosave = true;
+        self
+    }
+
+    /// Append the URIs of the files opened, picked or downloaded to the file
+    /// at `path`, one per line, emptying it first
+    pub fn with_choose_files(mut self, path: impl Into<PathBuf>) -> Self {
+        self.choose_files = Some(path.into());
+        self
+    }
+
+    /// Take commands from scripts and editors on the unix socket at `path`
+    /// while running (see [`crate::ipc`])
+    pub fn with_listen(mut self, path: impl Into<PathBuf>) -> Self {
+        self.listen = Some(path.into());
+        self
+    }
+}
+
+/// Where the browser was left when it quit
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    /// Display path of the last location (`s3://bucket/prefix`, a local path, ...)
+    pub location: String,
+    /// Display paths of the files selected in the last location, sorted
+    pub selected: Vec<String>,
+    /// The history, most recent first
+    pub history: Vec<HistoryEntry>,
+    /// Display paths of the files picked in pick mode; None if the user quit
+    /// without picking
+    pub picked: Option<Vec<String>>,
+    /// What the session listed, previewed and transferred
+    pub usage: SessionUsage,
+    /// Pane sizes to save, if the panes were resized
+    pub pane_sizes: Option<PaneSizes>,
+    /// The last location on the filesystem, for local backends
+    pub local_dir: Option<PathBuf>,
+}
+
+impl RunOutcome {
+    /// The last location for a shell to follow: the directory for local
+    /// backends (to `cd` into, byte for byte even if it isn't UTF-8), the URI
+    /// (`s3://bucket/prefix`) otherwise
+    pub fn last_dir(&self) -> Vec<u8> {
+        match &self.local_dir {
+            Some(dir) => dir.as_os_str().as_encoded_bytes().to_vec(),
+            None => self.location.strip_prefix("local://").unwrap_or(&self.location).as_bytes().to_vec(),
+        }
+    }
+}
+
+/// Run the browser full screen on the terminal of this process until the
+/// user quits. Sets up raw mode and the alternate screen, and restores the
+/// terminal afterwards, also when running fails or panics. When stdout isn't
+/// a terminal (`files=$(rats3 --pick)`), the UI is drawn on the controlling
+/// terminal so stdout only gets what the caller prints.
+pub async fn run(mut options: RunOptions) -> Result<RunOutcome> {
+    install_panic_hook();
+    enable_raw_mode()?;
+    let guard = TerminalGuard::enter();
+
+    // Pick light/dark colors (queries the terminal only for background = "auto")
+    let detected_background = if options.config.background == Background::Auto {
+        crate::terminal::detect_background(Duration::from_millis(100))
+    } else {
+        None
+    };
+    options.config.apply_background(detected_background);
+
+    let mut stdout = ui_output()?;
+    execute!(stdout, EnterAlternateScreen)?;
+
+    // Terminals with the kitty keyboard protocol can tell Ctrl-i from Tab
+    // (the default forward key); the rest keep sending Tab for both
+    let keyboard_enhanced = matches!(supports_keyboard_enhancement(), Ok(true))
+        && execute!(stdout, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)).is_ok();
+    let terminal_title = options.config.terminal_title && crate::terminal::push_title(&mut stdout).is_ok();
+    guard.update(|setup| {
+        setup.keyboard_enhanced = keyboard_enhanced;
+        setup.terminal_title = terminal_title;
+    });
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let outcome = run_in(&mut terminal, options).await;
+    drop(terminal);
+    drop(guard);
+    outcome
+}
+
+/// What `run` changed about the terminal, beyond raw mode and the alternate
+/// screen, and the thread the UI runs on
+#[derive(Clone, Copy)]
+struct TerminalSetup {
+    keyboard_enhanced: bool,
+    terminal_title: bool,
+    thread: ThreadId,
+}
+
+/// The terminal setup still to be undone. Taken by whichever restores the
+/// terminal first: the guard, or the panic hook before it prints the panic.
+static TERMINAL_SETUP: Mutex<Option<TerminalSetup>> = Mutex::new(None);
+
+/// Restores the terminal when dropped, however `run` ends
+struct TerminalGuard;
+
+impl TerminalGuard {
+    /// Record that the terminal is in raw mode and has to be restored
+    fn enter() -> Self {
+        let setup = TerminalSetup { keyboard_enhanced: false, terminal_title: false, thread: thread::current().id() };
+        *TERMINAL_SETUP.lock().unwrap_or_else(PoisonError::into_inner) = Some(setup);
+        TerminalGuard
+    }
+
+    /// Record further changes to undo
+    fn update(&self, change: impl FnOnce(&mut TerminalSetup)) {
+        if let Some(setup) = TERMINAL_SETUP.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            change(setup);
+        }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore_terminal();
+    }
+}
+
+/// Undo the terminal setup, if it hasn't been undone yet: pop the keyboard
+/// flags and title, leave raw mode and the alternate screen, show the cursor.
+/// Returns the setup that was undone.
+fn restore_terminal() -> Option<TerminalSetup> {
+    let setup = TERMINAL_SETUP.lock().unwrap_or_else(PoisonError::into_inner).take()?;
+    let _ = disable_raw_mode();
+    if let Ok(mut out) = ui_output() {
+        if setup.keyboard_enhanced {
+            let _ = execute!(out, PopKeyboardEnhancementFlags);
+        }
+        if setup.terminal_title {
+            let _ = crate::terminal::pop_title(&mut out);
+        }
+        let _ = execute!(out, LeaveAlternateScreen, Show);
+    }
+    Some(setup)
+}
+
+/// Set the terminal up again the way `setup` describes, after
+/// `restore_terminal`
+fn reenter_terminal(setup: TerminalSetup) -> io::Result<()> {
+    enable_raw_mode()?;
+    *TERMINAL_SETUP.lock().unwrap_or_else(PoisonError::into_inner) = Some(setup);
+    let mut out = ui_output()?;
+    execute!(out, EnterAlternateScreen)?;
+    if setup.keyboard_enhanced {
+        execute!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))?;
+    }
+    if setup.terminal_title {
+        crate::terminal::push_title(&mut out)?;
+    }
+    Ok(())
+}
+
+/// Hand the terminal back to the shell and stop the process until the shell
+/// continues it (`fg`), then take the terminal over again. Does nothing when
+/// `run` didn't set the terminal up.
+#[cfg(unix)]
+fn suspend() -> io::Result<()> {
+    let Some(setup) = restore_terminal() else {
+        return Ok(());
+    };
+    // SIGSTOP rather than SIGTSTP, which is caught to get here; both stop the
+    // process as a job the shell can resume. Returns once continued.
+    // SAFETY: raise has no memory safety requirements
+    unsafe {
+        libc::raise(libc::SIGSTOP);
+    }
+    reenter_terminal(setup)
+}
+
+/// Job control needs signals; elsewhere the session just carries on
+#[cfg(not(unix))]
+fn suspend() -> io::Result<()> {
+    Ok(())
+}
+
+/// Start the control socket server at `path`
+#[cfg(unix)]
+fn listen_on(path: &Path, rt: &Runtime) -> Result<ControlServer> {
+    ControlServer::bind(path, rt.event_sender())
+}
+
+/// Control sockets are unix sockets
+#[cfg(not(unix))]
+fn listen_on(_path: &Path, _rt: &Runtime) -> Result<()> {
+    anyhow::bail!("--listen needs unix sockets")
+}
+
+/// Run a plugin on the terminal, with the UI out of the way until it exits
+async fn run_in_terminal(run: &PluginRun) -> io::Result<std::process::ExitStatus> {
+    let setup = restore_terminal();
+    let mut command = run.shell_command();
+    // Printed on the terminal, not into a redirected stdout (`--pick`)
+    if !io::stdout().is_terminal() {
+        if let Ok(tty) = std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+            command.stdout(tty);
+        }
+    }
+    let status = command.status().await;
+    if let Some(setup) = setup {
+        reenter_terminal(setup)?;
+    }
+    status
+}
+
+/// Restore the terminal before a panic on the UI thread is printed, so the
+/// message lands on the normal screen and the shell is usable afterwards.
+/// Panics on other threads (a background task) leave the UI running.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let on_ui_thread = TERMINAL_SETUP
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_ref()
+                .is_some_and(|setup| setup.thread == thread::current().id());
+            if on_ui_thread {
+                let _ = restore_terminal();
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Where the UI is drawn: stdout, or /dev/tty when stdout is redirected
+fn ui_output() -> io::Result<Box<dyn Write + Send>> {
+    if io::stdout().is_terminal() {
+        return Ok(Box::new(io::stdout()));
+    }
+    let tty = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
+    Ok(Box::new(tty))
+}
+
+/// Run the browser on `terminal`, reading keys from the terminal of this
+/// process, until the user quits. The caller sets the terminal up (raw mode,
+/// alternate screen) and restores it. The window title is set through the
+/// terminal backend when `terminal_title` is enabled.
+pub async fn run_in<B: TerminalBackend + Write>(terminal: &mut Terminal<B>, options: RunOptions) -> Result<RunOutcome> {
+    let RunOptions {
+        backend,
+        prefix,
+        config,
+        registry,
+        context,
+        session,
+        history,
+        config_error,
+        config_warning,
+        pick,
+        pane_sizes,
+        autosave,
+        listen,
+        choose_files,
+    } = options;
+
+    let mut app = App::new(backend.clone(), prefix.clone(), config.preview_width_percent);
+    app.apply_config(&config);
+    app.set_active_context(context);
+    app.load_history(history);
+    app.set_pick_mode(pick);
+    if let Some(sizes) = pane_sizes {
+        app.set_pane_sizes(sizes);
+    }
+
+    // Do initial listing
+    match backend.list(&prefix).await {
+        Ok(result) => {
+            app.record_listing(result.entries.len());
+            app.update_entries(result);
+            app.remember_location(&prefix);
+            if let Some(session) = &session {
+                session.apply(&mut app);
+            }
+        }
+        Err(e) => {
+            app.show_error(format!("Error listing directory: {}", e));
+        }
+    }
+
+    // Show config error if there was one
+    if let Some(error) = config_error {
+        app.show_warning(format!("Config file error (using defaults): {}", error));
+    }
+    if let Some(warning) = config_warning {
+        app.show_warning(warning);
+    }
+
+    // Background tasks, timers and input all report through one event channel
+    let syntax_theme = ui::widgets::preview::load_theme(&config.syntax_theme);
+    let (mut rt, mut event_rx) = Runtime::new(backend, config);
+    rt.set_registry(Arc::new(registry));
+    match syntax_theme {
+        Ok(theme) => rt.set_syntax_theme(theme),
+        Err(e) => app.show_warning(format!("{:#} (using the default)", e)),
+    }
+    if let Ok(path) = AuditLog::file() {
+        rt.set_audit_log(AuditLog::new(path));
+    }
+    if let Some(path) = choose_files {
+        rt.set_chosen_files(ChosenFiles::create(path)?);
+    }
+    // Stops listening and removes the socket on the way out
+    let _control = listen.map(|path| listen_on(&path, &rt)).transpose()?;
+
+    // Load initial preview in background
+    rt.load_preview(&mut app);
+    rt.start_filter(&mut app);
+
+    // Initial render before entering the event loop. The last frame is kept
+    // to draw the listing spinner over while a listing blocks the app.
+    let mut last_frame = terminal.draw(|f| ui::render(f, &app, rt.config()))?.buffer.clone();
+    let listing = rt.listing_in_flight();
+    let spinner_style = Style::default().fg(rt.config().colors.accent_normal.to_ratatui_color());
+    let mut title = None;
+    update_title(terminal.backend_mut(), &mut title, &app, &rt);
+
+    let mut input = crossterm::event::EventStream::new();
+    // Input read while a listing was awaited
+    let mut typed = VecDeque::new();
+    let mut animation = tokio::time::interval(ANIMATION_TICK);
+    animation.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut housekeeping = tokio::time::interval(HOUSEKEEPING_TICK);
+    housekeeping.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut signals = Signals::new()?;
+    // Also saves on the way out when running fails or panics
+    let mut autosave = autosave.then(|| Autosave::new(AUTOSAVE_INTERVAL));
+    let mut last_draw = Instant::now();
+    // A change that came in too soon after the last frame to be drawn yet
+    let mut redraw_pending = false;
+
+    // Main event loop: sleep until input, a background event or a due timer
+    // wakes it up, so an idle app uses no CPU
+    loop {
+        // Timers only run while there is something to animate or expire
+        let animating = app.has_active_downloads() || app.preview_load_elapsed().is_some();
+        let autosave_due = autosave.as_ref().and_then(Autosave::due).map(Instant::from_std);
+        let needs_housekeeping = app.status_message().is_some()
+            || !app.downloads().is_empty()
+            || app.pending_key().is_some();
+
+        let first = tokio::select! {
+            maybe_event = input.next() => match maybe_event {
+                Some(Ok(Event::Key(key))) => Some(AppEvent::KeyPress(key)),
+                Some(Ok(Event::Resize(_, _))) => Some(AppEvent::Resize),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+                // Terminal input closed
+                None => break,
+            },
+            Some(event) = event_rx.recv() => Some(event),
+            _ = animation.tick(), if animating => Some(AppEvent::Tick),
+            _ = housekeeping.tick(), if needs_housekeeping => Some(AppEvent::Tick),
+            // Restarted on every event, so it only fires once input has paused
+            _ = tokio::time::sleep(PREFETCH_IDLE), if app.wants_prefetch() => Some(AppEvent::Idle),
+            event = signals.recv() => Some(event),
+            _ = tokio::time::sleep_until(autosave_due.unwrap_or_else(Instant::now)), if autosave_due.is_some() => {
+                if let Some(autosave) = &mut autosave {
+                    autosave.flush();
+                }
+                continue;
+            }
+            // The deferred frame is due
+            _ = tokio::time::sleep_until(last_draw + FRAME_INTERVAL), if redraw_pending => None,
+        };
+
+        // Handle everything already queued so a burst of events costs one redraw
+        let mut dirty = redraw_pending;
+        let mut next = first;
+        while let Some(event) = next.take().or_else(|| typed.pop_front()).or_else(|| event_rx.try_recv().ok()) {
+            let size = terminal.size()?;
+            let listing_area = ui::layout::listing_area(size, &app, rt.config());
+            // Listings are awaited here; show a spinner once one gets slow, and
+            // drop the dispatch (canceling the listing) on Escape
+            let redraw = {
+                let dispatching = rt.dispatch(&mut app, event, size);
+                tokio::pin!(dispatching);
+                loop {
+                    tokio::select! {
+                        result = &mut dispatching => break Some(result?),
+                        _ = animation.tick() => {
+                            if let Some(elapsed) = listing.elapsed().filter(|elapsed| *elapsed >= ui::SPINNER_DELAY) {
+                                draw_listing_spinner(terminal, &last_frame, listing_area, elapsed, spinner_style)?;
+                                // The spinner must be drawn over again
+                                dirty = true;
+                            }
+                        }
+                        maybe_event = input.next(), if listing.elapsed().is_some() => match maybe_event {
+                            Some(Ok(Event::Key(key))) if key.code == KeyCode::Esc => break None,
+                            // Handled once the listing is done
+                            Some(Ok(Event::Key(key))) => typed.push_back(AppEvent::KeyPress(key)),
+                            Some(Ok(Event::Resize(_, _))) => typed.push_back(AppEvent::Resize),
+                            Some(Ok(_)) | None => {}
+                            Some(Err(e)) => return Err(e.into()),
+                        },
+                    }
+                }
+            };
+            match redraw {
+                Some(redraw) => dirty |= redraw,
+                None => {
+                    rt.cancel_listing(&mut app);
+                    dirty = true;
+                }
+            }
+        }
+
+        if let Some(autosave) = autosave.as_mut().filter(|_| dirty) {
+            autosave.update(SessionSnapshot {
+                location: rt.backend().get_display_path(app.current_prefix()),
+                history: app.history().to_vec(),
+                pane_sizes: app.resized_pane_sizes(),
+            });
+        }
+        if app.should_quit() {
+            break;
+        }
+        if app.take_suspend_request() {
+            suspend()?;
+            // The shell may have drawn anything meanwhile, and reset the title
+            terminal.clear()?;
+            title = None;
+            dirty = true;
+        }
+        if let Some(run) = app.take_terminal_plugin() {
+            // A live input stream would take keys meant for the command
+            input = crossterm::event::EventStream::new();
+            match run_in_terminal(&run).await {
+                Ok(status) if status.success() => {}
+                Ok(status) => app.show_error(format!("{} exited with {}", run.name, status)),
+                Err(e) => app.show_error(format!("Cannot run {}: {}", run.name, e)),
+            }
+            terminal.clear()?;
+            title = None;
+            dirty = true;
+        }
+
+        // Only re-render when something actually changed, and at most once
+        // per frame interval
+        redraw_pending = dirty && last_draw.elapsed() < FRAME_INTERVAL;
+        if dirty && !redraw_pending {
+            last_frame = terminal.draw(|f| ui::render(f, &app, rt.config()))?.buffer.clone();
+            update_title(terminal.backend_mut(), &mut title, &app, &rt);
+            last_draw = Instant::now();
+        }
+    }
+
+    Ok(outcome(&app, rt.backend()))
+}
+
+/// Draw `frame` again with a spinner and the time the listing in flight has
+/// been running on the top border of `area`
+fn draw_listing_spinner<B: TerminalBackend>(
+    terminal: &mut Terminal<B>,
+    frame: &Buffer,
+    area: Rect,
+    elapsed: Duration,
+    style: Style,
+) -> Result<()> {
+    // The frame is stale after a resize; wait for the next full render
+    if terminal.size()? != frame.area {
+        return Ok(());
+    }
+    let label = format!(
+        " {} listing {} ",
+        ui::text_utils::spinner_frame(),
+        ui::text_utils::format_elapsed(elapsed)
+    );
+    let width = (label.chars().count() as u16).min(area.width.saturating_sub(2));
+    terminal.draw(|f| {
+        let buffer = f.buffer_mut();
+        *buffer = frame.clone();
+        // Right-aligned, clear of the corner and the pane title
+        let x = area.x + area.width.saturating_sub(width + 1);
+        buffer.set_stringn(x, area.y, &label, width as usize, style);
+    })?;
+    Ok(())
+}
+
+/// Job control and shutdown requests from outside: SIGTSTP suspends, while
+/// SIGTERM, SIGHUP (the terminal went away) and SIGINT quit. In raw mode
+/// Ctrl-C and Ctrl-Z arrive as keys instead, but `kill` still sends these.
+#[cfg(unix)]
+struct Signals {
+    suspend: tokio::signal::unix::Signal,
+    shutdown: Vec<tokio::signal::unix::Signal>,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        let kinds = [SignalKind::terminate(), SignalKind::hangup(), SignalKind::interrupt()];
+        Ok(Signals {
+            suspend: signal(SignalKind::from_raw(libc::SIGTSTP))?,
+            shutdown: kinds.into_iter().map(signal).collect::<io::Result<_>>()?,
+        })
+    }
+
+    /// Wait for the next signal, as the event it asks for
+    async fn recv(&mut self) -> AppEvent {
+        let shutdown = futures::future::select_all(self.shutdown.iter_mut().map(|signal| Box::pin(signal.recv())));
+        tokio::select! {
+            _ = self.suspend.recv() => AppEvent::Suspend,
+            _ = shutdown => AppEvent::Quit,
+        }
+    }
+}
+
+/// Shutdown requests from outside: Ctrl-C sent to the process
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> io::Result<Self> {
+        Ok(Signals)
+    }
+
+    /// Wait for the next signal, as the event it asks for
+    async fn recv(&mut self) -> AppEvent {
+        let _ = tokio::signal::ctrl_c().await;
+        AppEvent::Quit
+    }
+}
+
+/// `rats3 — s3://bucket/prefix` for the location shown in `app`
+fn window_title(app: &App, backend: &Arc<dyn Backend>) -> String {
+    format!("rats3 — {}", backend.get_display_path(app.current_prefix()))
+}
+
+/// Set the window title to the current location if it changed since `title`
+/// was last set (and titles are enabled)
+fn update_title(out: &mut impl Write, title: &mut Option<String>, app: &App, rt: &Runtime) {
+    if !rt.config().terminal_title {
+        return;
+    }
+    let current = window_title(app, rt.backend());
+    if title.as_ref() != Some(&current) {
+        let _ = crate::terminal::set_title(out, &current);
+        *title = Some(current);
+    }
+}
+
+/// Where `app` was left, with paths displayed by `backend`
+fn outcome(app: &App, backend: &Arc<dyn Backend>) -> RunOutcome {
+    RunOutcome {
+        location: backend.get_display_path(app.current_prefix()),
+        selected: app.get_selected_file_paths().iter().map(|path| backend.get_display_path(path)).collect(),
+        history: app.history().to_vec(),
+        picked: app.picked().map(<[String]>::to_vec),
+        usage: *app.usage(),
+        pane_sizes: app.resized_pane_sizes(),
+        local_dir: backend.local_path(app.current_prefix()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    #[tokio::test]
+    async fn test_outcome() {
+        let backend: Arc<dyn Backend> = Arc::new(MemoryBackend::new("test").with_file("data/a.txt", "a").with_file("data/b.txt", "b"));
+        let mut app = App::new(backend.clone(), "data".to_string(), 50);
+        app.update_entries(backend.list("data").await.unwrap());
+        app.toggle_selection();
+
+        let outcome = outcome(&app, &backend);
+        assert_eq!(outcome.location, "memory://test/data");
+        assert_eq!(outcome.selected, vec!["memory://test/data/a.txt"]);
+        assert!(outcome.picked.is_none());
+        assert_eq!(outcome.last_dir(), b"memory://test/data");
+
+        let local = RunOutcome { location: "local:///home/me/src".to_string(), ..outcome };
+        assert_eq!(local.last_dir(), b"/home/me/src");
+
+        // The real directory, not the escaped name it's displayed as
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let dir = PathBuf::from(std::ffi::OsStr::from_bytes(b"/home/me/dir\xff"));
+            let local = RunOutcome { location: "local:///home/me/dir\\xff".to_string(), local_dir: Some(dir), ..local };
+            assert_eq!(local.last_dir(), b"/home/me/dir\xff");
+        }
+
+        assert_eq!(window_title(&app, &backend), "rats3 — memory://test/data");
+    }
+}
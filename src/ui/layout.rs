@@ -1,62 +1,219 @@
-use crate::app::{App, AppMode, FocusedPanel};
-use crate::config::Config;
-use crate::ui::widgets::{download_selector, file_list, history_list, preview, search_bar, status_bar};
+use crate::app::{App, AppMode, FocusedPanel, PreviewGeometry};
+use crate::config::{Config, PreviewLayout, ProgressPanePosition};
+use crate::ui::widgets::{audit_log, confirm_popup, context_picker, download_selector, file_list, grep_results, help_popup, history_list, plugin_output, preview, progress_pane, properties, search_bar, status_bar, summary_view, sync_plan};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    text::Line,
     Frame,
 };
-use std::collections::HashMap;
 
 /// Render the main UI
-pub fn render(frame: &mut Frame, app: &App, config: &Config, highlighted: &HashMap<String, Vec<Line<'static>>>) {
+pub fn render(frame: &mut Frame, app: &App, config: &Config) {
     let area = frame.size();
 
-    let vertical_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),  // Search bar with border
-            Constraint::Min(0),     // Main content area
-            Constraint::Length(5),  // Status pane with borders all around (fixed 5 lines)
-        ])
-        .split(area);
-
-    // Split main content area horizontally: file list | preview
-    let preview_width = app.preview_width_percent();
-    let explorer_width = 100 - preview_width;
-    let content_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(explorer_width), // File list (left)
-            Constraint::Percentage(preview_width),   // Preview (right)
-        ])
-        .split(vertical_chunks[1]);
+    let (search_area, main_area, status_area) = split_screen(area, app);
 
     // Render search bar
-    search_bar::render(frame, vertical_chunks[0], app, config);
+    if let Some(search_area) = search_area {
+        search_bar::render(frame, search_area, app, config);
+    }
+
+    // Carve the progress pane out of the content area if it's shown
+    let (content_area, progress_area) = split_progress_pane(main_area, app, config);
+    if let Some(progress_area) = progress_area {
+        progress_pane::render(frame, progress_area, app, config);
+    }
 
     // Check which panel is focused
     let explorer_focused = app.focused_panel() == &FocusedPanel::Explorer;
     let preview_focused = app.focused_panel() == &FocusedPanel::Preview;
 
-    // Always render file list and preview
-    file_list::render(frame, content_chunks[0], app, config, explorer_focused);
-    preview::render(frame, content_chunks[1], app, config, preview_focused, highlighted);
+    match split_content(content_area, app) {
+        Some((explorer_area, preview_area)) => {
+            file_list::render(frame, explorer_area, app, config, explorer_focused);
+            preview::render(frame, preview_area, app, config, preview_focused);
+        }
+        // Zoomed or preview hidden: the focused pane fills the content area
+        None if preview_focused => preview::render(frame, content_area, app, config, true),
+        None => file_list::render(frame, content_area, app, config, true),
+    }
 
-    // Render status bar
-    status_bar::render(frame, vertical_chunks[2], app, config);
+    // Render status bar; in zen mode status messages take the bottom line
+    match status_area {
+        Some(status_area) => status_bar::render(frame, status_area, app, config),
+        None => status_bar::render_message_line(frame, area, app, config),
+    }
 
     // Render history overlay if in history mode or searching history
     if app.mode() == &AppMode::History || (app.is_search_mode() && app.is_searching_history()) {
-        let history_area = centered_rect(80, 30, vertical_chunks[1]);
+        let history_area = centered_rect(80, 30, main_area);
         history_list::render(frame, history_area, app, config, true);
     }
 
     // Render download destination selector if in download mode
     if app.mode() == &AppMode::Download {
-        let download_area = centered_rect(70, 20, vertical_chunks[1]);
-        download_selector::render(frame, download_area, app, config, &config.download_destinations);
+        let download_area = centered_rect(70, 20, main_area);
+        download_selector::render(frame, download_area, app, config, app.download_destinations());
+    }
+
+    // Render the context picker (`:context`)
+    if app.mode() == &AppMode::Context {
+        let picker_height = (config.contexts.len() as u16 * 2 + 2).min(main_area.height);
+        let picker_area = centered_rect(70, picker_height, main_area);
+        context_picker::render(frame, picker_area, app, config);
+    }
+
+    // Render the :du prefix summary
+    if let Some(view) = app.summary_view() {
+        let summary_height = (summary_view::line_count(view) as u16 + 2).min(main_area.height);
+        let summary_area = centered_rect(70, summary_height, main_area);
+        summary_view::render(frame, summary_area, view, config);
+    }
+
+    // Render the :grep results
+    if let Some(view) = app.grep_view() {
+        let grep_area = centered_rect(90, main_area.height.saturating_sub(2), main_area);
+        grep_results::render(frame, grep_area, view, config);
+    }
+
+    // Render the :log overlay
+    if let Some(view) = app.audit_log_view() {
+        let log_height = (view.records.len() as u16 + 2).min(main_area.height);
+        let log_area = centered_rect(90, log_height, main_area);
+        audit_log::render(frame, log_area, view, config);
+    }
+
+    // Render the properties popup of the selected object
+    if let Some(view) = app.properties_view() {
+        let properties_height = (properties::line_count(view) as u16 + 2).min(main_area.height);
+        let properties_area = centered_rect(80, properties_height, main_area);
+        properties::render(frame, properties_area, view, config);
+    }
+
+    // Render what a plugin printed
+    if let Some(view) = app.plugin_output_view() {
+        let output_area = centered_rect(80, main_area.height, main_area);
+        let output_height = (plugin_output::line_count(view, output_area.width) as u16 + 2).min(main_area.height);
+        let output_area = centered_rect(80, output_height, main_area);
+        plugin_output::render(frame, output_area, view, config);
+    }
+
+    // Render the :sync plan awaiting confirmation
+    if let Some(plan) = app.sync_plan() {
+        let plan_height = (sync_plan::line_count(plan) as u16 + 2).min(main_area.height);
+        let plan_area = centered_rect(80, plan_height, main_area);
+        sync_plan::render(frame, plan_area, plan, app.sync_plan_scroll(), config);
+    }
+
+    // Render the question asked before an operation runs
+    if let Some(confirmation) = app.confirmation() {
+        // Borders and up to two lines of question
+        let confirm_area = centered_rect(60, 4.min(main_area.height), main_area);
+        confirm_popup::render(frame, confirm_area, confirmation, config);
+    }
+
+    // Render help cheatsheet on top of everything else
+    if app.is_help_shown() {
+        let help_height = (help_popup::line_count(config) as u16 + 2).min(area.height.saturating_sub(2));
+        let help_area = centered_rect(70, help_height, area);
+        help_popup::render(frame, help_area, app, config);
+    }
+}
+
+/// Number of sync plan lines visible in the popup for a terminal of the given height
+pub fn sync_plan_visible_height(terminal_height: u16, app: &App) -> usize {
+    // Minus the popup borders
+    terminal_height.saturating_sub(chrome_height(app) + 2) as usize
+}
+
+/// Split the screen into the search bar, the content area and the status
+/// pane. Zen mode hides the search bar (unless something is being typed into
+/// it) and the status pane.
+fn split_screen(area: Rect, app: &App) -> (Option<Rect>, Rect, Option<Rect>) {
+    let (search_height, status_height) = chrome_heights(app);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(search_height), // Search bar with border
+            Constraint::Min(0),                // Main content area
+            Constraint::Length(status_height), // Status pane with borders all around
+        ])
+        .split(area);
+    let search_area = (search_height > 0).then_some(chunks[0]);
+    let status_area = (status_height > 0).then_some(chunks[2]);
+    (search_area, chunks[1], status_area)
+}
+
+/// Split the content area into the file list and the preview, side by side
+/// or stacked. None when a single pane fills it.
+fn split_content(content_area: Rect, app: &App) -> Option<(Rect, Rect)> {
+    if app.is_single_pane() {
+        return None;
     }
+    let direction = match app.preview_layout() {
+        PreviewLayout::Bottom => Direction::Vertical,
+        _ => Direction::Horizontal,
+    };
+    let preview_width = app.preview_width_percent();
+    let explorer_width = 100 - preview_width;
+    let content_chunks = Layout::default()
+        .direction(direction)
+        .constraints([
+            Constraint::Percentage(explorer_width), // File list (left/top)
+            Constraint::Percentage(preview_width),   // Preview (right/bottom)
+        ])
+        .split(content_area);
+    Some((content_chunks[0], content_chunks[1]))
+}
+
+/// Area of the file list on a screen of `area`, or of the pane filling the
+/// content area when zoomed. Listing spinners are drawn on its border.
+pub fn listing_area(area: Rect, app: &App, config: &Config) -> Rect {
+    let (_, main_area, _) = split_screen(area, app);
+    let (content_area, _) = split_progress_pane(main_area, app, config);
+    split_content(content_area, app).map_or(content_area, |(explorer_area, _)| explorer_area)
+}
+
+/// Lines taken by the search bar and by the status pane
+fn chrome_heights(app: &App) -> (u16, u16) {
+    let search_height = if app.shows_search_bar() { 3 } else { 0 };
+    let status_height = if app.is_zen_mode() { 0 } else { app.status_height() };
+    (search_height, status_height)
+}
+
+/// Lines taken by the search bar and status pane together
+fn chrome_height(app: &App) -> u16 {
+    let (search_height, status_height) = chrome_heights(app);
+    search_height + status_height
+}
+
+/// Split off the progress pane (if shown) from the content area.
+/// Returns the remaining content area and the progress pane area.
+fn split_progress_pane(area: Rect, app: &App, config: &Config) -> (Rect, Option<Rect>) {
+    if !app.is_progress_pane_shown() {
+        return (area, None);
+    }
+
+    let chunks = match config.progress_pane_position {
+        ProgressPanePosition::Right => Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(app.progress_pane_width())])
+            .split(area),
+        ProgressPanePosition::Bottom => Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(app.progress_pane_height())])
+            .split(area),
+    };
+    (chunks[0], Some(chunks[1]))
+}
+
+/// Geometry of the preview's text area on a screen of `area`: its rows, and
+/// the rows each line of the preview wraps to
+pub fn preview_geometry(area: Rect, app: &App, config: &Config) -> PreviewGeometry {
+    let (_, main_area, _) = split_screen(area, app);
+    let (content_area, _) = split_progress_pane(main_area, app, config);
+    let preview_area = split_content(content_area, app).map_or(content_area, |(_, preview_area)| preview_area);
+    // Minus the preview borders
+    app.preview_geometry(preview_area.width.saturating_sub(2) as usize, preview_area.height.saturating_sub(2) as usize)
 }
 
 /// Create a centered rectangle within the given area
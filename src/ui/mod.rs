@@ -3,3 +3,7 @@ pub mod widgets;
 pub mod text_utils;
 
 pub use layout::render;
+
+/// Listings and previews in flight for longer than this show a spinner and
+/// the time they've been running
+pub const SPINNER_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
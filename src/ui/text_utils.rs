@@ -1,7 +1,94 @@
 use ratatui::{
-    style::{Color, Style},
-    text::Span,
+    style::Style,
+    text::{Line, Span},
 };
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Number of terminal columns `text` takes (wide CJK characters and emoji
+/// take two)
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// Cut `text` to at most `max_width` columns, ending in "…" when shortened
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut result = String::new();
+    let mut width = 0;
+    for ch in text.chars() {
+        let ch_width = ch.width().unwrap_or(0);
+        // Leave a column for the ellipsis
+        if width + ch_width + 1 > max_width {
+            break;
+        }
+        result.push(ch);
+        width += ch_width;
+    }
+    if max_width > 0 {
+        result.push('…');
+    }
+    result
+}
+
+/// Left-align `text` in `width` columns, padding with spaces
+pub fn pad_to_width(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.width());
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// Number of rows `text` takes when wrapped at `width` columns: at least one,
+/// breaking before a character that doesn't fit the row
+pub fn wrapped_rows(text: &str, width: usize) -> usize {
+    let mut rows = 1;
+    let mut used = 0;
+    for ch in text.chars() {
+        let ch_width = ch.width().unwrap_or(0);
+        if used > 0 && used + ch_width > width {
+            rows += 1;
+            used = 0;
+        }
+        used += ch_width;
+    }
+    rows
+}
+
+/// Break a styled line into rows of at most `width` columns. Rows after the
+/// first start with `indent` blank columns, so text continues under the end of
+/// a gutter that wide; a line whose text is `indent` columns in takes
+/// `wrapped_rows(text, width - indent)` rows.
+pub fn wrap_line(line: Line<'static>, width: usize, indent: usize) -> Vec<Line<'static>> {
+    let indent = if indent < width { indent } else { 0 };
+    let mut rows = Vec::new();
+    let mut row: Vec<Span<'static>> = Vec::new();
+    let mut used = 0;
+    for span in line.spans {
+        let mut current = String::new();
+        for ch in span.content.chars() {
+            let ch_width = ch.width().unwrap_or(0);
+            if used > indent && used + ch_width > width {
+                if !current.is_empty() {
+                    row.push(Span::styled(std::mem::take(&mut current), span.style));
+                }
+                rows.push(Line::from(std::mem::take(&mut row)));
+                if indent > 0 {
+                    row.push(Span::raw(" ".repeat(indent)));
+                }
+                used = indent;
+            }
+            current.push(ch);
+            used += ch_width;
+        }
+        if !current.is_empty() {
+            row.push(Span::styled(current, span.style));
+        }
+    }
+    rows.push(Line::from(row));
+    rows
+}
 
 /// Split text into spans with specific character positions highlighted.
 /// Positions are char indices into `text` (as returned by nucleo-matcher).
@@ -9,13 +96,12 @@ pub fn highlight_positions(
     text: &str,
     positions: &[u32],
     normal_style: Style,
-    highlight_color: Color,
+    highlight_style: Style,
 ) -> Vec<Span<'static>> {
     if positions.is_empty() {
         return vec![Span::styled(text.to_string(), normal_style)];
     }
 
-    let highlight_style = normal_style.fg(highlight_color);
     let pos_set: std::collections::HashSet<u32> = positions.iter().copied().collect();
 
     let mut spans = Vec::new();
@@ -44,10 +130,91 @@ pub fn highlight_positions(
     }
 }
 
+/// Case-insensitive occurrences of `query` in `text`, as char index ranges.
+/// Matching runs on the lowercased text, which can differ in length from the
+/// original (`İ` lowercases to two chars), so matches are mapped back to the
+/// chars they came from and widened to whole graphemes: a highlight never
+/// splits a letter from its combining accent.
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let query = query.to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    // Lowercased text, and for each of its bytes the char it came from
+    let mut lower = String::with_capacity(text.len());
+    let mut owner = Vec::with_capacity(text.len());
+    for (char_idx, ch) in text.chars().enumerate() {
+        for lower_ch in ch.to_lowercase() {
+            lower.push(lower_ch);
+            owner.extend(std::iter::repeat_n(char_idx, lower_ch.len_utf8()));
+        }
+    }
+
+    // First char of each grapheme, to widen matches to grapheme boundaries
+    let mut grapheme_starts = Vec::new();
+    let mut char_idx = 0;
+    for grapheme in text.graphemes(true) {
+        grapheme_starts.push(char_idx);
+        char_idx += grapheme.chars().count();
+    }
+    let char_count = char_idx;
+    let grapheme_start = |c: usize| grapheme_starts[grapheme_starts.partition_point(|&s| s <= c) - 1];
+    let grapheme_end = |c: usize| grapheme_starts.get(grapheme_starts.partition_point(|&s| s <= c)).copied().unwrap_or(char_count);
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (byte_idx, found) in lower.match_indices(&query) {
+        let start = grapheme_start(owner[byte_idx]);
+        let end = grapheme_end(owner[byte_idx + found.len() - 1]);
+        match ranges.last_mut() {
+            Some(last) if last.end >= start => last.end = last.end.max(end),
+            _ => ranges.push(start..end),
+        }
+    }
+    ranges
+}
+
+/// Restyle the case-insensitive occurrences of `query` in an already styled
+/// line with `highlight` (given each span's style), splitting its spans at
+/// char (never byte) boundaries
+pub fn highlight_matches(line: Line<'static>, query: &str, highlight: impl Fn(Style) -> Style) -> Line<'static> {
+    let full_text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+    let ranges = find_matches(&full_text, query);
+    if ranges.is_empty() {
+        return line;
+    }
+
+    let mut spans = Vec::new();
+    let mut char_idx = 0;
+    let mut ranges = ranges.into_iter().peekable();
+    for span in line.spans {
+        let mut current = String::new();
+        let mut current_highlighted = false;
+        for ch in span.content.chars() {
+            while ranges.peek().is_some_and(|r| r.end <= char_idx) {
+                ranges.next();
+            }
+            let highlighted = ranges.peek().is_some_and(|r| r.contains(&char_idx));
+            if highlighted != current_highlighted && !current.is_empty() {
+                let style = if current_highlighted { highlight(span.style) } else { span.style };
+                spans.push(Span::styled(std::mem::take(&mut current), style));
+            }
+            current_highlighted = highlighted;
+            current.push(ch);
+            char_idx += 1;
+        }
+        if !current.is_empty() {
+            let style = if current_highlighted { highlight(span.style) } else { span.style };
+            spans.push(Span::styled(current, style));
+        }
+    }
+    Line::from(spans)
+}
+
 /// Truncate a path if too long, keeping the most relevant (rightmost) parts.
 /// Shows ".../" prefix when truncated.
 pub fn truncate_path(path: &str, max_width: usize) -> String {
-    if path.len() <= max_width {
+    if path.width() <= max_width {
         return path.to_string();
     }
 
@@ -67,7 +234,7 @@ pub fn truncate_path(path: &str, max_width: usize) -> String {
     let mut remaining = available;
 
     for part in parts.iter().rev() {
-        let part_len = part.len() + 1; // +1 for '/'
+        let part_len = part.width() + 1; // +1 for '/'
         if remaining >= part_len {
             if result.is_empty() {
                 result = part.to_string();
@@ -83,13 +250,238 @@ pub fn truncate_path(path: &str, max_width: usize) -> String {
     format!("{}{}", prefix, result)
 }
 
+/// Frame of the spinner shown while something is in progress, from the clock
+pub fn spinner_frame() -> &'static str {
+    const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+    use std::time::SystemTime;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let millis = now.as_millis();
+    let frame_idx = (millis / 80) as usize % SPINNER_FRAMES.len();
+    SPINNER_FRAMES[frame_idx]
+}
+
+/// Format the time a request has been running: "0.4s", "12.3s", then like
+/// `format_duration` from a minute on
+pub fn format_elapsed(duration: std::time::Duration) -> String {
+    if duration.as_secs() < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format_duration(duration)
+    }
+}
+
+/// Format a remaining time compactly: "45s", "3m 05s", "2h 10m"
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Parse an entry's last-modified time as the backends format it
+/// ("2024-01-01 10:00:00", optionally followed by " UTC"); always UTC
+pub fn parse_timestamp(text: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let text = text.strip_suffix(" UTC").unwrap_or(text);
+    chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").ok().map(|dt| dt.and_utc())
+}
+
+/// Format how long ago something happened compactly: "now", "5m ago",
+/// "3h ago", "2d ago", "4mo ago", "2y ago". Times in the future (clock
+/// skew) count as now.
+pub fn format_age(age: chrono::TimeDelta) -> String {
+    let secs = age.num_seconds();
+    if secs < 60 {
+        "now".to_string()
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h ago", secs / 3600)
+    } else if secs < 60 * 86400 {
+        format!("{}d ago", secs / 86400)
+    } else if secs < 365 * 86400 {
+        format!("{}mo ago", secs / (30 * 86400))
+    } else {
+        format!("{}y ago", secs / (365 * 86400))
+    }
+}
+
+/// Format a byte size with binary units: "512 B", "1.50 KB"
+pub fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut size = size as f64;
+    let mut unit_idx = 0;
+
+    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_idx += 1;
+    }
+
+    if unit_idx == 0 {
+        format!("{} {}", size as u64, UNITS[unit_idx])
+    } else {
+        format!("{:.2} {}", size, UNITS[unit_idx])
+    }
+}
+
+/// Format a count with thousands separators: "1,024"
+pub fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(c);
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ratatui::style::Color;
+
+    fn highlighted(line: &Line) -> Vec<String> {
+        line.spans.iter().filter(|s| s.style.fg == Some(Color::Red)).map(|s| s.content.to_string()).collect()
+    }
+
+    fn red(style: Style) -> Style {
+        style.fg(Color::Red)
+    }
+
+    #[test]
+    fn test_wrap_line() {
+        let text = |rows: &[Line]| rows.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        let line = Line::from(vec![Span::raw("1 │ "), Span::styled("abcdefgh", Style::default().fg(Color::Red))]);
+        let rows = wrap_line(line, 7, 4);
+        assert_eq!(text(&rows), vec!["1 │ abc", "    def", "    gh"]);
+        assert_eq!(rows.len(), wrapped_rows("abcdefgh", 3));
+        assert_eq!(highlighted(&rows[1]), vec!["def"]);
+
+        // Wide characters move to the next row rather than being split
+        assert_eq!(text(&wrap_line(Line::from("ab漢字"), 5, 0)), vec!["ab漢", "字"]);
+        assert_eq!(wrapped_rows("ab漢字", 5), 2);
+        assert_eq!(wrapped_rows("", 5), 1);
+        assert_eq!(text(&wrap_line(Line::from("abc"), 5, 0)), vec!["abc"]);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = chrono::DateTime::from_timestamp(1704103200, 0);
+        assert_eq!(parse_timestamp("2024-01-01 10:00:00"), expected);
+        assert_eq!(parse_timestamp("2024-01-01 10:00:00 UTC"), expected);
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn test_format_age() {
+        let age = |secs| format_age(chrono::TimeDelta::seconds(secs));
+        assert_eq!(age(-30), "now");
+        assert_eq!(age(59), "now");
+        assert_eq!(age(5 * 60), "5m ago");
+        assert_eq!(age(3 * 3600 + 59), "3h ago");
+        assert_eq!(age(2 * 86400), "2d ago");
+        assert_eq!(age(59 * 86400), "59d ago");
+        assert_eq!(age(120 * 86400), "4mo ago");
+        assert_eq!(age(800 * 86400), "2y ago");
+    }
+
+    #[test]
+    fn test_find_matches() {
+        assert_eq!(find_matches("Report.csv report", "REPORT"), vec![0..6, 11..17]);
+        assert_eq!(find_matches("abc", ""), Vec::<Range<usize>>::new());
+        assert_eq!(find_matches("報告書.csv", "告書"), vec![1..3]);
+        // Overlapping occurrences become one range
+        assert_eq!(find_matches("aaa", "aa"), vec![0..2]);
+    }
+
+    #[test]
+    fn test_find_matches_length_changing_lowercase() {
+        // `İ` lowercases to `i` plus a combining dot: two chars, three bytes
+        assert_eq!(find_matches("İstanbul.txt", "stan"), vec![1..5]);
+        assert_eq!(find_matches("xİx.txt", "x"), vec![0..1, 2..3, 5..6]);
+        // Matching only part of the lowercased `İ` highlights the whole letter
+        assert_eq!(find_matches("İx", "i"), vec![0..1]);
+    }
+
+    #[test]
+    fn test_find_matches_whole_graphemes() {
+        // `e` followed by a combining acute accent is one grapheme
+        assert_eq!(find_matches("cafe\u{301}.txt", "cafe"), vec![0..5]);
+    }
+
+    #[test]
+    fn test_highlight_matches_across_spans() {
+        let line = Line::from(vec![Span::raw("1 │ "), Span::raw("Ünïcödé "), Span::raw("ünï")]);
+        let result = highlight_matches(line, "ÜNÏ", red);
+        assert_eq!(highlighted(&result), vec!["Ünï", "ünï"]);
+        let text: String = result.spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, "1 │ Ünïcödé ünï");
+
+        let line = Line::from(vec![Span::raw("İstan"), Span::raw("bul")]);
+        assert_eq!(highlighted(&highlight_matches(line, "anbu", red)), vec!["an", "bu"]);
+
+        let line = Line::from("plain");
+        assert_eq!(highlight_matches(line.clone(), "x", red), line);
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("report.csv"), 10);
+        assert_eq!(display_width("報告書.csv"), 10);
+        assert_eq!(display_width("🚀 launch"), 9);
+    }
+
+    #[test]
+    fn test_truncate_to_width() {
+        assert_eq!(truncate_to_width("report.csv", 10), "report.csv");
+        assert_eq!(truncate_to_width("report.csv", 7), "report…");
+        // A wide character that would straddle the limit is left out
+        assert_eq!(truncate_to_width("報告書.csv", 6), "報告…");
+        assert_eq!(truncate_to_width("報告書.csv", 5), "報告…");
+        assert_eq!(display_width(&truncate_to_width("報告書.csv", 4)), 3);
+        assert_eq!(truncate_to_width("abc", 0), "");
+    }
+
+    #[test]
+    fn test_pad_to_width() {
+        assert_eq!(pad_to_width("ab", 4), "ab  ");
+        assert_eq!(pad_to_width("報告", 6), "報告  ");
+        assert_eq!(pad_to_width("報告書", 4), "報告書");
+    }
+
+    #[test]
+    fn test_truncate_path_wide_characters() {
+        assert_eq!(truncate_path("データ/2024/報告書.csv", 30), "データ/2024/報告書.csv");
+        // 報告書.csv is 10 columns wide, so only it fits after ".../"
+        assert_eq!(truncate_path("データ/2024/報告書.csv", 16), ".../報告書.csv");
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.50 KB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.00 GB");
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1024), "1,024");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+    }
 
     #[test]
     fn test_no_positions_returns_original() {
-        let result = highlight_positions("Hello World", &[], Style::default(), Color::Red);
+        let result = highlight_positions("Hello World", &[], Style::default(), Style::default().fg(Color::Red));
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].content, "Hello World");
     }
@@ -97,7 +489,7 @@ mod tests {
     #[test]
     fn test_single_position() {
         // Highlight char at index 6 ('W')
-        let result = highlight_positions("Hello World", &[6], Style::default(), Color::Red);
+        let result = highlight_positions("Hello World", &[6], Style::default(), Style::default().fg(Color::Red));
         assert_eq!(result[0].content, "Hello ");
         assert_eq!(result[1].content, "W");
         assert_eq!(result[1].style.fg, Some(Color::Red));
@@ -107,7 +499,7 @@ mod tests {
     #[test]
     fn test_consecutive_positions() {
         // Highlight chars 6,7,8,9,10 → "World"
-        let result = highlight_positions("Hello World", &[6, 7, 8, 9, 10], Style::default(), Color::Red);
+        let result = highlight_positions("Hello World", &[6, 7, 8, 9, 10], Style::default(), Style::default().fg(Color::Red));
         assert_eq!(result[0].content, "Hello ");
         assert_eq!(result[1].content, "World");
         assert_eq!(result[1].style.fg, Some(Color::Red));
@@ -116,7 +508,7 @@ mod tests {
     #[test]
     fn test_scattered_positions() {
         // Highlight first and last char
-        let result = highlight_positions("Hello", &[0, 4], Style::default(), Color::Red);
+        let result = highlight_positions("Hello", &[0, 4], Style::default(), Style::default().fg(Color::Red));
         assert_eq!(result[0].content, "H");
         assert_eq!(result[0].style.fg, Some(Color::Red));
         assert_eq!(result[1].content, "ell");
@@ -127,9 +519,25 @@ mod tests {
     #[test]
     fn test_unicode_positions() {
         // '世' is char index 6, '界' is char index 7
-        let result = highlight_positions("Hello 世界", &[6, 7], Style::default(), Color::Red);
+        let result = highlight_positions("Hello 世界", &[6, 7], Style::default(), Style::default().fg(Color::Red));
         assert_eq!(result[0].content, "Hello ");
         assert_eq!(result[1].content, "世界");
         assert_eq!(result[1].style.fg, Some(Color::Red));
     }
+
+    #[test]
+    fn test_format_duration() {
+        use std::time::Duration;
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m 05s");
+        assert_eq!(format_duration(Duration::from_secs(7800)), "2h 10m");
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        use std::time::Duration;
+        assert_eq!(format_elapsed(Duration::from_millis(430)), "0.4s");
+        assert_eq!(format_elapsed(Duration::from_millis(12_345)), "12.3s");
+        assert_eq!(format_elapsed(Duration::from_secs(185)), "3m 05s");
+    }
 }
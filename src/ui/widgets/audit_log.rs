@@ -0,0 +1,98 @@
+use crate::app::AuditLogView;
+use crate::audit::{AuditOutcome, AuditRecord};
+use crate::config::Config;
+use crate::ui::text_utils::format_size;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+/// Render the `:log` overlay: one line per audited operation, oldest first
+/// (or per completed download, for `:downloads`)
+pub fn render(frame: &mut Frame, area: Rect, view: &AuditLogView, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let accent = config.colors.accent_normal.to_ratatui_color();
+    let secondary = config.colors.text_secondary.to_ratatui_color();
+    let background = config.colors.background.to_ratatui_color();
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+    let error_style = Style::default().fg(config.colors.text_error.to_ratatui_color());
+
+    let items: Vec<ListItem> = view
+        .records
+        .iter()
+        .map(|record| {
+            let outcome_style = match record.outcome {
+                AuditOutcome::Ok => text_style,
+                AuditOutcome::Failed => error_style,
+                AuditOutcome::Canceled => Style::default().fg(secondary),
+            };
+            let mut spans = vec![
+                Span::styled(format!(" {}  ", format_time(&record.time)), Style::default().fg(secondary)),
+                Span::styled(format!("{:<8}  ", record.operation.name()), Style::default().fg(accent)),
+                Span::styled(format!("{:<8}  ", outcome_name(record.outcome)), outcome_style),
+                Span::styled(paths(record), text_style),
+            ];
+            if let Some(bytes) = record.bytes {
+                spans.push(Span::styled(format!("  {}", format_size(bytes)), Style::default().fg(secondary)));
+            }
+            if let Some(error) = &record.error {
+                spans.push(Span::styled(format!("  {}", error), error_style));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(accent))
+                .style(Style::default().bg(background))
+                .title(if view.downloads_only {
+                    " Downloads, most recent last (r download again, Esc close) "
+                } else {
+                    " Audit log, most recent last (r download again, Esc close) "
+                }),
+        )
+        .style(Style::default().bg(background))
+        .highlight_style(
+            Style::default()
+                .bg(config.colors.selection_bg.to_ratatui_color())
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("❯ ");
+
+    let mut list_state = ListState::default();
+    list_state.select((!view.records.is_empty()).then_some(view.selected));
+
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+/// `2026-10-16 12:00:01` from the RFC 3339 time of a record
+fn format_time(time: &str) -> String {
+    match chrono::DateTime::parse_from_rfc3339(time) {
+        Ok(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        Err(_) => time.to_string(),
+    }
+}
+
+fn outcome_name(outcome: AuditOutcome) -> &'static str {
+    match outcome {
+        AuditOutcome::Ok => "ok",
+        AuditOutcome::Failed => "failed",
+        AuditOutcome::Canceled => "canceled",
+    }
+}
+
+/// `source → destination`, or just the source
+fn paths(record: &AuditRecord) -> String {
+    match &record.destination {
+        Some(destination) => format!("{} → {}", record.source, destination),
+        None => record.source.clone(),
+    }
+}
@@ -0,0 +1,27 @@
+use crate::app::Confirmation;
+use crate::config::Config;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Padding, Paragraph, Wrap},
+    Frame,
+};
+
+/// Render the popup asking whether to run an operation
+pub fn render(frame: &mut Frame, area: Rect, confirmation: &Confirmation, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(config.colors.accent_search.to_ratatui_color()))
+        .style(Style::default().bg(config.colors.background.to_ratatui_color()))
+        .title(" Confirm (y/Enter yes, n/Esc no) ")
+        .padding(Padding::horizontal(1));
+
+    let line = Line::from(Span::styled(confirmation.question.clone(), text_style.add_modifier(Modifier::BOLD)));
+    let paragraph = Paragraph::new(line).block(block).wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
@@ -0,0 +1,56 @@
+use crate::app::App;
+use crate::config::Config;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let accent = config.colors.accent_normal.to_ratatui_color();
+    let secondary = config.colors.text_secondary.to_ratatui_color();
+    let background = config.colors.background.to_ratatui_color();
+
+    let items: Vec<ListItem> = config
+        .contexts
+        .iter()
+        .map(|(name, context)| {
+            let mut title = vec![Span::styled(format!(" {}", name), Style::default().fg(accent).add_modifier(Modifier::BOLD))];
+            if app.active_context() == Some(name.as_str()) {
+                title.push(Span::styled(" (active)", Style::default().fg(secondary)));
+            }
+            let location = match &context.profile {
+                Some(profile) => format!("    {}  [profile: {}]", context.uri, profile),
+                None => format!("    {}", context.uri),
+            };
+            ListItem::new(vec![Line::from(title), Line::from(Span::styled(location, Style::default().fg(secondary)))])
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(accent))
+                .style(Style::default().bg(background))
+                .title(" Switch Context "),
+        )
+        .style(Style::default().bg(background))
+        .highlight_style(
+            Style::default()
+                .bg(config.colors.selection_bg.to_ratatui_color())
+                .fg(config.colors.text_primary.to_ratatui_color())
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("❯ ");
+
+    let mut list_state = ListState::default();
+    list_state.select(Some(app.context_index()));
+
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
@@ -34,19 +34,25 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, destina
         return;
     }
 
+    // Numbers pick a destination by typing them
+    let number_width = destinations.len().to_string().len();
+
     // Create list items from destinations
     let items: Vec<ListItem> = destinations
         .iter()
-        .map(|dest| {
+        .enumerate()
+        .map(|(i, dest)| {
             // Use download icon
             let icon = "\u{f019}"; //
             let color = config.colors.accent_normal.to_ratatui_color();
 
+            let number = format!(" {:>width$}", i + 1, width = number_width);
             let name = format!(" {} {}", icon, dest.name);
-            let path = format!("    {}", dest.path);
+            let path = format!("{:width$}    {}", "", dest.path, width = number_width + 2);
 
             let lines = vec![
                 Line::from(vec![
+                    Span::styled(number, Style::default().fg(config.colors.text_secondary.to_ratatui_color())),
                     Span::styled(name, Style::default().fg(color).add_modifier(Modifier::BOLD)),
                 ]),
                 Line::from(vec![
@@ -59,7 +65,10 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, destina
         .collect();
 
     let selected_count = app.selected_count();
-    let title = format!(" Select Download Destination ({} files selected) ", selected_count);
+    let mut title = format!(" Select Download Destination ({} files selected) ", selected_count);
+    if !app.download_number().is_empty() {
+        title.push_str(&format!("#{}_ ", app.download_number()));
+    }
 
     let list = List::new(items)
         .block(
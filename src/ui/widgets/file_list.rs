@@ -1,6 +1,8 @@
-use crate::app::App;
-use crate::config::Config;
-use crate::ui::text_utils;
+use crate::app::{App, EntryGroup, KindFilter, ListingSource};
+use crate::backend::{Entry, RestoreState};
+use crate::config::{Config, EntryTime, RgbColor};
+use crate::ui::text_utils::{self, format_size};
+use crate::ui::widgets::scrollbar;
 use ratatui::{
     layout::Rect,
     style::{Color, Modifier, Style},
@@ -9,6 +11,9 @@ use ratatui::{
     Frame,
 };
 
+/// Names are shortened to fit the pane, but never below this many columns
+const MIN_NAME_WIDTH: usize = 12;
+
 /// Get Nerd Font icon and color for a file or directory
 /// Based on nvim-web-devicons
 fn get_file_icon(name: &str, is_dir: bool, config: &Config) -> (&'static str, Color) {
@@ -127,6 +132,63 @@ fn get_file_icon(name: &str, is_dir: bool, config: &Config) -> (&'static str, Co
 }
 
 
+/// Storage class label shown after the size, e.g. "  [GLACIER, archived]"
+fn storage_class_label(entry: &Entry) -> String {
+    let Some(storage_class) = &entry.storage_class else {
+        return String::new();
+    };
+    match &entry.restore_state {
+        Some(RestoreState::Archived) => format!("  [{}, archived]", storage_class),
+        Some(RestoreState::InProgress) => format!("  [{}, restoring]", storage_class),
+        Some(RestoreState::Restored { .. }) => format!("  [{}, restored]", storage_class),
+        None => format!("  [{}]", storage_class),
+    }
+}
+
+/// Last-modified time shown after the size ("  3h ago" or "  2024-01-01 10:00")
+/// and the entry's age, if the entry has a time
+fn entry_time_label(entry: &Entry, mode: EntryTime, now: chrono::DateTime<chrono::Utc>) -> Option<(String, chrono::TimeDelta)> {
+    let modified = text_utils::parse_timestamp(entry.modified.as_deref()?)?;
+    let age = now - modified;
+    match mode {
+        EntryTime::Relative => Some((format!("  {}", text_utils::format_age(age)), age)),
+        EntryTime::Absolute => Some((format!("  {}", modified.format("%Y-%m-%d %H:%M")), age)),
+        EntryTime::Hidden => None,
+    }
+}
+
+/// Color of a relative time: primary text for files from the last hour,
+/// fading to secondary text for files older than a week
+fn recency_color(age: chrono::TimeDelta, config: &Config) -> Color {
+    let fade = match age.num_hours() {
+        ..=0 => 0.0,
+        1..=23 => 0.33,
+        24..=167 => 0.67,
+        _ => 1.0,
+    };
+    let (fresh, old) = (&config.colors.text_primary, &config.colors.text_secondary);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * fade).round() as u8;
+    RgbColor::new(mix(fresh.r, old.r), mix(fresh.g, old.g), mix(fresh.b, old.b)).to_ratatui_color()
+}
+
+/// Header row of an extension group: "▾ csv  12 files, 3.40 MB"
+fn group_header_line(group: &EntryGroup, line_num_width: usize, config: &Config) -> Line<'static> {
+    let marker = if group.collapsed { "▸" } else { "▾" };
+    let extension = if group.extension.is_empty() { "(no extension)" } else { &group.extension };
+    let files = if group.count == 1 { "file" } else { "files" };
+    Line::from(vec![
+        Span::raw(" ".repeat(line_num_width + 2)),
+        Span::styled(
+            format!("{} {}", marker, extension),
+            Style::default().fg(config.colors.accent_normal.to_ratatui_color()).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!("  {} {}, {}", text_utils::format_count(group.count), files, format_size(group.size)),
+            Style::default().fg(config.colors.text_secondary.to_ratatui_color()),
+        ),
+    ])
+}
+
 pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focused: bool) {
     let entries = app.entries();
     let filtered_indices = app.filtered_indices();
@@ -166,10 +228,14 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
     let line_num_width = max_line_num.to_string().len().max(2);
 
     // Create list items
+    let now = chrono::Utc::now();
     let items: Vec<ListItem> = filtered_indices
         .iter()
         .enumerate()
         .map(|(display_idx, &entry_idx)| {
+            if let Some(group) = app.group_header(entry_idx) {
+                return ListItem::new(group_header_line(group, line_num_width, config));
+            }
             let entry = &entries[entry_idx];
 
             // Line number (1-indexed for display)
@@ -179,15 +245,39 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
             // Check if file is selected
             let is_selected = app.is_file_selected(entry_idx);
 
-            // Format entry name with icon
+            // Format entry name with icon; local entries that can't be read are dimmed
             let (icon, color) = get_file_icon(&entry.name, entry.is_dir, config);
+            let denied = entry.permissions.as_ref().is_some_and(|p| !p.readable);
+            let color = if denied { config.colors.text_secondary.to_ratatui_color() } else { color };
             let selection_dot = "● "; // Blue dot for selected files
 
             // Add size info if file
-            let size_info = if let Some(size) = entry.size {
-                format!("  {}", format_size(size))
+            let size_info = match entry.size {
+                // A directory that is also an object of the same name
+                Some(size) if entry.is_dir => format!("  +object {}", format_size(size)),
+                Some(size) => format!("  {}", format_size(size)),
+                None => String::new(),
+            };
+
+            // Last-modified time, brighter for fresher files when relative
+            let (time_info, time_color) = match entry_time_label(entry, config.entry_time, now) {
+                Some((label, age)) if config.entry_time == EntryTime::Relative => (label, recency_color(age, config)),
+                Some((label, _)) => (label, config.colors.text_secondary.to_ratatui_color()),
+                None => (String::new(), Color::Reset),
+            };
+
+            // Storage class tag for non-standard classes (with restore state for archives)
+            let storage_info = storage_class_label(entry);
+            let permissions_info = match &entry.permissions {
+                Some(permissions) if config.show_permissions => {
+                    format!("  {} {} {}", permissions.mode_string(), permissions.owner, permissions.group).trim_end().to_string()
+                }
+                _ => String::new(),
+            };
+            let storage_color = if entry.is_readable() {
+                config.colors.text_secondary.to_ratatui_color()
             } else {
-                String::new()
+                config.colors.accent_search.to_ratatui_color()
             };
 
             // Apply visual mode style: background color for selected files
@@ -199,16 +289,28 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
 
             // Highlight matched characters in the file name using nucleo positions.
             // Icon and name are kept separate so positions (which are for the bare name) apply directly.
-            let highlight_color = config.colors.accent_search.to_ratatui_color();
-            let base_style = Style::default().fg(color).bg(bg);
+            let mut base_style = Style::default().fg(color).bg(bg);
+            if denied {
+                base_style = base_style.add_modifier(Modifier::DIM);
+            }
+            // Selected rows and the cursor row share the selection background
+            let highlight_style = config.colors.match_style(base_style, is_selected || display_idx == selected_index);
             let positions = app.match_positions_for(entry_idx);
 
-            let icon_span = Span::styled(format!("{} ", icon), base_style);
+            // Shorten long names so the size and storage class stay visible:
+            // borders, highlight symbol, line number, dot and icon come first
+            let icon_text = format!("{} ", icon);
+            let reserved = 4 + line_num_str.len() + 2 + text_utils::display_width(&icon_text)
+                + size_info.len() + time_info.len() + text_utils::display_width(&storage_info)
+                + text_utils::display_width(&permissions_info);
+            let name = text_utils::truncate_to_width(&entry.name, (area.width as usize).saturating_sub(reserved).max(MIN_NAME_WIDTH));
+
+            let icon_span = Span::styled(icon_text, base_style);
             let name_spans = text_utils::highlight_positions(
-                &entry.name,
+                &name,
                 positions,
                 base_style,
-                highlight_color,
+                highlight_style,
             );
 
             let line = if is_selected {
@@ -226,6 +328,11 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
                 spans.push(Span::styled(size_info, Style::default()
                     .fg(config.colors.text_secondary.to_ratatui_color())
                     .bg(bg)));
+                spans.push(Span::styled(time_info, Style::default().fg(time_color).bg(bg)));
+                spans.push(Span::styled(storage_info, Style::default().fg(storage_color).bg(bg)));
+                spans.push(Span::styled(permissions_info, Style::default()
+                    .fg(config.colors.text_secondary.to_ratatui_color())
+                    .bg(bg)));
                 Line::from(spans)
             } else {
                 // Not selected: normal style
@@ -238,6 +345,10 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
                 spans.extend(name_spans);
                 spans.push(Span::styled(size_info, Style::default()
                     .fg(config.colors.text_secondary.to_ratatui_color())));
+                spans.push(Span::styled(time_info, Style::default().fg(time_color)));
+                spans.push(Span::styled(storage_info, Style::default().fg(storage_color)));
+                spans.push(Span::styled(permissions_info, Style::default()
+                    .fg(config.colors.text_secondary.to_ratatui_color())));
                 Line::from(spans)
             };
 
@@ -245,29 +356,62 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
         })
         .collect();
 
-    let location = app.location_name();
+    let mut location = app.location_name().to_string();
+    if let Some(chain) = app.descended_chain() {
+        location = format!("{} › {}", location, chain.replace('/', " › "));
+    }
+    if app.is_flat_view() {
+        location.push_str(", flat");
+        if let Some(paging) = app.flat_paging() {
+            if let Some(loaded) = paging.loaded() {
+                location = format!("{}, loading {} keys…", location, text_utils::format_count(loaded));
+            } else if paging.has_more() || paging.page() > 1 {
+                let more = if paging.has_more() { " of more" } else { ", last" };
+                location = format!("{}, page {}{}", location, paging.page(), more);
+            }
+        }
+    }
+    if app.is_grouped_by_extension() {
+        location.push_str(" by extension");
+    }
+    if app.kind_filter() != KindFilter::All {
+        location = format!("{}, {}", location, app.kind_filter().label());
+    }
     let selected_count = app.selected_count();
-    let title = if app.search_query().is_empty() {
+    let title = if !app.is_filtered() {
+        let listing = match app.listing_source() {
+            Some(ListingSource::Fetched(elapsed)) => format!(" in {:.2}s", elapsed.as_secs_f64()),
+            Some(ListingSource::Cached) => ", cached".to_string(),
+            None => String::new(),
+        };
+        let count = if app.kind_filter() == KindFilter::All {
+            format!("{} objects{}", text_utils::format_count(entries.len()), listing)
+        } else {
+            format!("{}/{} objects{}", text_utils::format_count(app.match_count()), text_utils::format_count(entries.len()), listing)
+        };
         if selected_count > 0 {
-            format!(" {} [{} selected] ", location, selected_count)
+            format!(" {} ({}) [{} selected] ", location, count, selected_count)
         } else {
-            format!(" {} ", location)
+            format!(" {} ({}) ", location, count)
         }
     } else {
+        let filtering = if app.is_filtering() { ", filtering..." } else { "" };
         if selected_count > 0 {
             format!(
-                " {} ({}/{} matches) [{} selected] ",
+                " {} ({}/{} matches{}) [{} selected] ",
                 location,
-                filtered_indices.len(),
+                app.match_count(),
                 entries.len(),
+                filtering,
                 selected_count
             )
         } else {
             format!(
-                " {} ({}/{} matches) ",
+                " {} ({}/{} matches{}) ",
                 location,
-                filtered_indices.len(),
-                entries.len()
+                app.match_count(),
+                entries.len(),
+                filtering
             )
         }
     };
@@ -308,21 +452,6 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
     }
 
     frame.render_stateful_widget(list, area, &mut list_state);
-}
-
-fn format_size(size: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    let mut size = size as f64;
-    let mut unit_idx = 0;
-
-    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_idx += 1;
-    }
-
-    if unit_idx == 0 {
-        format!("{} {}", size as u64, UNITS[unit_idx])
-    } else {
-        format!("{:.2} {}", size, UNITS[unit_idx])
-    }
+    let rows = area.height.saturating_sub(2) as usize;
+    scrollbar::render(frame, area, filtered_indices.len(), list_state.offset(), rows, &[], config);
 }
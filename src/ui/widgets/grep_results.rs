@@ -0,0 +1,63 @@
+use crate::app::GrepView;
+use crate::config::Config;
+use crate::grep::MAX_MATCHES;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+/// Render the `:grep` results popup
+pub fn render(frame: &mut Frame, area: Rect, view: &GrepView, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let accent = config.colors.accent_normal.to_ratatui_color();
+    let secondary = config.colors.text_secondary.to_ratatui_color();
+    let background = config.colors.background.to_ratatui_color();
+
+    let items: Vec<ListItem> = view
+        .matches
+        .iter()
+        .map(|found| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {}", found.path), Style::default().fg(accent)),
+                Span::styled(format!(":{}  ", found.line + 1), Style::default().fg(secondary)),
+                Span::styled(found.text.as_str(), Style::default().fg(config.colors.text_primary.to_ratatui_color())),
+            ]))
+        })
+        .collect();
+
+    let status = if let Some(error) = &view.error {
+        format!("error: {}", error)
+    } else if view.matches.len() >= MAX_MATCHES {
+        format!("first {} matches", MAX_MATCHES)
+    } else if view.complete {
+        format!("{} matches in {} files", view.matches.len(), view.files_searched)
+    } else {
+        format!("{} matches, searching {} files…", view.matches.len(), view.files_searched)
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(accent))
+                .style(Style::default().bg(background))
+                .title(format!(" grep {} in {} ({}) ", view.pattern, view.location, status)),
+        )
+        .style(Style::default().bg(background))
+        .highlight_style(
+            Style::default()
+                .bg(config.colors.selection_bg.to_ratatui_color())
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("❯ ");
+
+    let mut list_state = ListState::default();
+    list_state.select((!view.matches.is_empty()).then_some(view.selected));
+
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
@@ -0,0 +1,232 @@
+use crate::app::App;
+use crate::config::{Config, KeyBindings};
+use crate::ui::text_utils;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// A group of bindings shown under one heading
+struct HelpSection {
+    title: &'static str,
+    entries: Vec<(String, &'static str)>,
+}
+
+/// Join the configured keys for an action for display (e.g. "Up/k")
+fn keys(keys: &[String]) -> String {
+    keys.join("/")
+}
+
+/// Build the cheatsheet from the active key bindings so remapped keys show up correctly.
+/// Keys that are not configurable are listed literally.
+fn help_sections(bindings: &KeyBindings) -> Vec<HelpSection> {
+    let exit_search = if bindings.exit_search_mode.chars().count() == 2 {
+        format!("Esc/{}", bindings.exit_search_mode)
+    } else {
+        "Esc".to_string()
+    };
+
+    vec![
+        HelpSection {
+            title: "General",
+            entries: vec![
+                (keys(&bindings.quit), "quit"),
+                (keys(&bindings.suspend), "suspend to the shell (fg resumes)"),
+                ("?".to_string(), "toggle this help"),
+                (":".to_string(), "command line (:du = prefix summary)"),
+                (":select <sql>".to_string(), "S3 Select query on the file (:select resets)"),
+                (":delimiter <c>".to_string(), "show the file as a table split on c (tab, pipe, ;)"),
+                (":jq <filter>".to_string(), "jq filter over a JSON Lines file (:jq resets)"),
+                (":diff".to_string(), "diff two selected files / previous version (s: layout)"),
+                (":grep TEXT".to_string(), "search file contents below here (Enter: open match)"),
+                (":sync <dir> [--delete]".to_string(), "mirror the prefix into dir (preview, Enter to run)"),
+                (":upload <dir>".to_string(), "upload new/changed files from dir (needs allow_writes)"),
+                (":context [name]".to_string(), "switch to a named context (no name: pick one)"),
+                (":mksession [name]".to_string(), "save location, filter and preview position (--session)"),
+                (":downloads".to_string(), "past downloads; r downloads one again"),
+                (":log".to_string(), "audit log of downloads, uploads, deletes and restores"),
+                (":usage".to_string(), "keys listed and bytes previewed/transferred this session"),
+                (":login".to_string(), "refresh expired credentials (runs credential_command)"),
+                (":import-history [file...]".to_string(), "add s3:// locations from shell and AWS CLI history"),
+                (":plugin <name>".to_string(), "run a [plugins] command on the selection (or its key)"),
+                (keys(&bindings.toggle_focus), "switch explorer/preview"),
+                (keys(&bindings.focus_preview), "focus preview"),
+                (keys(&bindings.focus_explorer), "focus explorer"),
+                (keys(&bindings.zoom_pane), "zoom focused pane"),
+                (keys(&bindings.zen_mode), "zen mode (hide search bar and status)"),
+                (keys(&bindings.cycle_layout), "cycle preview layout"),
+                (keys(&bindings.toggle_progress_pane), "toggle progress pane"),
+                (format!("{}/{}", keys(&bindings.grow_preview), keys(&bindings.shrink_preview)), "grow/shrink preview"),
+                (format!("{}/{}", keys(&bindings.grow_status_pane), keys(&bindings.shrink_status_pane)), "grow/shrink status pane"),
+                (format!("{}/{}", keys(&bindings.grow_progress_pane), keys(&bindings.shrink_progress_pane)), "grow/shrink progress pane"),
+                (keys(&bindings.wrap_text), "toggle line wrap"),
+                ("Esc".to_string(), "cancel a slow listing or preview, then active downloads"),
+            ],
+        },
+        HelpSection {
+            title: "Explorer",
+            entries: vec![
+                (keys(&bindings.move_up), "move up"),
+                (keys(&bindings.move_down), "move down"),
+                (keys(&bindings.jump_up), "jump up 10"),
+                (keys(&bindings.jump_down), "jump down 10"),
+                (bindings.jump_to_top.clone(), "jump to top"),
+                (keys(&bindings.jump_to_bottom), "jump to bottom"),
+                (keys(&bindings.navigate_into), "open"),
+                (keys(&bindings.navigate_up), "parent directory"),
+                (keys(&bindings.navigate_back), "back (previous location)"),
+                (keys(&bindings.navigate_forward), "forward"),
+                (keys(&bindings.refresh), "refresh listing"),
+                ("/".to_string(), "search (narrows an active filter)"),
+                ("Esc".to_string(), "clear filter"),
+                ("Space".to_string(), "toggle selection"),
+                ("v".to_string(), "visual selection"),
+                (keys(&bindings.download_mode), "download"),
+                (keys(&bindings.history_mode), "history"),
+                (keys(&bindings.history_mode_with_search), "search history"),
+                (keys(&bindings.copy_path), "copy path of selected entry"),
+                (format!("{}/{}", keys(&bindings.properties), bindings.properties_sequence), "object properties (y/Y: copy)"),
+                (keys(&bindings.restore_object), "restore archived object"),
+                (keys(&bindings.toggle_flat_view), "flat view: all files below here"),
+                (format!("{}/{}", keys(&bindings.next_page), keys(&bindings.previous_page)), "next/previous page of the flat view"),
+                (keys(&bindings.load_all), "load all keys of the flat view"),
+                (keys(&bindings.cycle_kind_filter), "show all / directories only / files only"),
+                (keys(&bindings.group_by_extension), "group files by extension"),
+                (keys(&bindings.toggle_group), "collapse/expand extension group"),
+            ],
+        },
+        HelpSection {
+            title: "Preview",
+            entries: vec![
+                (keys(&bindings.move_up), "scroll up"),
+                (keys(&bindings.move_down), "scroll down"),
+                (keys(&bindings.jump_up), "page up"),
+                (keys(&bindings.jump_down), "page down"),
+                (bindings.jump_to_top.clone(), "top"),
+                (keys(&bindings.jump_to_bottom), "bottom"),
+                ("H/L".to_string(), "resize preview"),
+                (format!("{}/{}", keys(&bindings.scroll_columns_left), keys(&bindings.scroll_columns_right)), "scroll table columns"),
+                (keys(&bindings.toggle_fold), "expand/collapse JSON Lines record"),
+                (keys(&bindings.cycle_encoding), "decode with the next encoding"),
+                (format!("{}/{}", keys(&bindings.next_file), keys(&bindings.previous_file)), "next/previous file"),
+                ("/".to_string(), "show matching lines (Enter: jump to match, Esc: back)"),
+                (keys(&bindings.preview_visual_mode), "visual line selection"),
+                (keys(&bindings.yank_selection), "yank selection (visual)"),
+                (keys(&bindings.navigate_up), "back to explorer"),
+            ],
+        },
+        HelpSection {
+            title: "Search",
+            entries: vec![
+                ("Ctrl-j/Ctrl-k/Down/Up".to_string(), "next/previous match"),
+                (keys(&bindings.accept_filter), "keep filter"),
+                ("Right".to_string(), "open"),
+                ("Backspace".to_string(), "delete (pops a filter at empty query)"),
+                (exit_search, "exit search"),
+            ],
+        },
+        HelpSection {
+            title: "Visual",
+            entries: vec![
+                (format!("{}/{}", keys(&bindings.move_down), keys(&bindings.move_up)), "extend selection"),
+                ("Space".to_string(), "toggle selection"),
+                (keys(&bindings.download_mode), "download selection"),
+                ("v/Esc".to_string(), "exit visual mode"),
+            ],
+        },
+        HelpSection {
+            title: "History",
+            entries: vec![
+                (format!("{}/{}", keys(&bindings.move_down), keys(&bindings.move_up)), "move"),
+                ("/".to_string(), "search"),
+                ("Enter".to_string(), "go to location"),
+                ("Esc".to_string(), "close"),
+            ],
+        },
+        HelpSection {
+            title: "Download",
+            entries: vec![
+                (format!("{}/{}", keys(&bindings.move_down), keys(&bindings.move_up)), "select destination"),
+                ("1-9".to_string(), "download to destination by number"),
+                ("Enter".to_string(), "confirm"),
+                ("Esc".to_string(), "cancel"),
+            ],
+        },
+    ]
+}
+
+/// Build the styled cheatsheet lines
+fn help_lines(bindings: &KeyBindings, config: &Config) -> Vec<Line<'static>> {
+    let sections = help_sections(bindings);
+    let key_width = sections
+        .iter()
+        .flat_map(|s| s.entries.iter())
+        .map(|(k, _)| text_utils::display_width(k))
+        .max()
+        .unwrap_or(0);
+
+    let header_style = Style::default()
+        .fg(config.colors.accent_normal.to_ratatui_color())
+        .add_modifier(Modifier::BOLD);
+    let key_style = Style::default().fg(config.colors.accent_search.to_ratatui_color());
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+
+    let mut lines = Vec::new();
+    for (i, section) in sections.into_iter().enumerate() {
+        if i > 0 {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(Span::styled(format!(" {}", section.title), header_style)));
+        for (key, description) in section.entries {
+            lines.push(Line::from(vec![
+                Span::styled(format!("   {}  ", text_utils::pad_to_width(&key, key_width)), key_style),
+                Span::styled(description, text_style),
+            ]));
+        }
+    }
+    lines
+}
+
+/// Total number of lines in the cheatsheet (for scroll clamping)
+pub fn line_count(config: &Config) -> usize {
+    help_lines(&config.key_bindings, config).len()
+}
+
+/// Render the help cheatsheet popup
+pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(config.colors.accent_normal.to_ratatui_color()))
+        .style(Style::default().bg(config.colors.background.to_ratatui_color()))
+        .title(" Help (j/k scroll, ?/Esc close) ");
+
+    let lines = help_lines(&config.key_bindings, config);
+    let visible = block.inner(area).height as usize;
+    let max_scroll = lines.len().saturating_sub(visible);
+    let scroll = app.help_scroll().min(max_scroll);
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .scroll((scroll as u16, 0));
+    frame.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_reflects_remapped_keys() {
+        let mut config = Config::default();
+        config.key_bindings.quit = vec!["Ctrl-x".to_string()];
+        let sections = help_sections(&config.key_bindings);
+        let general = &sections[0];
+        assert!(general.entries.iter().any(|(k, d)| k == "Ctrl-x" && *d == "quit"));
+    }
+}
@@ -54,20 +54,27 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
     // Create list items from filtered history
     let items: Vec<ListItem> = filtered_indices
         .iter()
-        .map(|&idx| {
-            let path = &history[idx];
+        .enumerate()
+        .map(|(display_idx, &idx)| {
+            let entry = &history[idx];
+            let path = &entry.uri;
             // Use folder icon for all history entries
             let icon = "\u{f07b}"; //
             let color = config.colors.file_icon_dir.to_ratatui_color();
 
+            // The profile a location was visited with, since the same bucket
+            // can look different through another one
+            let profile = entry.profile.as_ref().map(|profile| format!("  {}", profile)).unwrap_or_default();
+
             // Truncate path if needed (history entries are already full display URIs)
-            let display_path = truncate_path(path, max_path_width);
+            let display_path = truncate_path(path, max_path_width.saturating_sub(profile.chars().count()));
 
             // Adjust nucleo positions (which are for the full path) to the display_path.
             // If truncated, display_path = ".../suffix": positions in the visible suffix are
             // shifted by (path_chars - suffix_chars), then shifted back by the 4-char ".../" prefix.
-            let highlight_color = config.colors.accent_search.to_ratatui_color();
             let base_style = Style::default().fg(color);
+            // The cursor row has the selection background
+            let highlight_style = config.colors.match_style(base_style, display_idx == selected_index);
             let raw_positions = app.history_match_positions_for(idx);
             let positions: Vec<u32> = if display_path == *path {
                 raw_positions.to_vec()
@@ -91,8 +98,11 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
                 &display_path,
                 &positions,
                 base_style,
-                highlight_color,
+                highlight_style,
             ));
+            if !profile.is_empty() {
+                spans.push(Span::styled(profile, Style::default().fg(config.colors.text_secondary.to_ratatui_color())));
+            }
 
             ListItem::new(Line::from(spans))
         })
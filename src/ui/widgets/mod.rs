@@ -1,8 +1,17 @@
-pub mod download_progress;
+pub mod audit_log;
+pub mod confirm_popup;
+pub mod context_picker;
 pub mod download_selector;
 pub mod file_list;
+pub mod grep_results;
+pub mod help_popup;
 pub mod history_list;
+pub mod plugin_output;
 pub mod preview;
 pub mod progress_pane;
+pub mod properties;
+pub mod scrollbar;
 pub mod search_bar;
 pub mod status_bar;
+pub mod summary_view;
+pub mod sync_plan;
@@ -0,0 +1,51 @@
+use crate::app::PluginOutputView;
+use crate::config::Config;
+use crate::ui::text_utils;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Render the popup with what a plugin printed
+pub fn render(frame: &mut Frame, area: Rect, view: &PluginOutputView, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+    let dim_style = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
+
+    // Wrapped by character: presigned URLs and the like have nowhere to break
+    let width = (area.width as usize).saturating_sub(2).max(1);
+    let mut lines: Vec<Line> = view
+        .lines
+        .iter()
+        .flat_map(|line| text_utils::wrap_line(Line::from(Span::styled(line.clone(), text_style)), width, 0))
+        .collect();
+    if let Some(error) = &view.error {
+        let error_style = Style::default().fg(config.colors.text_error.to_ratatui_color());
+        lines.push(Line::from(Span::styled(error.clone(), error_style)));
+    } else if !view.complete {
+        lines.push(Line::from(Span::styled("running…", dim_style.add_modifier(Modifier::ITALIC))));
+    } else if view.lines.is_empty() {
+        lines.push(Line::from(Span::styled("(no output)", dim_style.add_modifier(Modifier::ITALIC))));
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(config.colors.accent_normal.to_ratatui_color()))
+        .style(Style::default().bg(config.colors.background.to_ratatui_color()))
+        .title(format!(" {} (y copy, Esc close) ", view.name));
+
+    let paragraph = Paragraph::new(lines).block(block).scroll((view.scroll as u16, 0));
+    frame.render_widget(paragraph, area);
+}
+
+/// Number of rows the popup needs when `width` columns wide (for sizing the overlay)
+pub fn line_count(view: &PluginOutputView, width: u16) -> usize {
+    let width = (width as usize).saturating_sub(2).max(1);
+    let rows: usize = view.lines.iter().map(|line| text_utils::wrapped_rows(line, width)).sum();
+    rows + usize::from(view.error.is_some() || view.lines.is_empty())
+}
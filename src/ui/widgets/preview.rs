@@ -1,7 +1,12 @@
-use crate::app::{App, FocusedPanel};
+use crate::app::{App, DiffView, FocusedPanel};
 use crate::backend::{FileMetadata, PreviewContent};
 use crate::config::Config;
-use crate::ui::text_utils::truncate_path;
+use crate::diff::{self, DiffLine, DiffTag};
+use crate::jsonl::{self, JsonLines};
+use crate::table::Table;
+use crate::ui::text_utils::{self, format_size, truncate_path};
+use crate::ui::widgets::scrollbar;
+use anyhow::{bail, Context, Result};
 use ratatui::{
     layout::Rect,
     style::{Color, Modifier, Style},
@@ -9,53 +14,156 @@ use ratatui::{
     widgets::{Block, Borders, Paragraph, Wrap},
     Frame,
 };
-use std::collections::HashMap;
+use std::sync::Arc;
 use syntect::{
     easy::HighlightLines,
     highlighting::{Theme, ThemeSet},
-    parsing::{SyntaxReference, SyntaxSet},
+    parsing::{SyntaxDefinition, SyntaxReference, SyntaxSet},
     util::LinesWithEndings,
 };
 
+/// Syntax definitions bundled on top of syntect's defaults, which lack them
+const BUNDLED_SYNTAXES: &[&str] = &[
+    include_str!("../../../syntaxes/TOML.sublime-syntax"),
+    include_str!("../../../syntaxes/Dockerfile.sublime-syntax"),
+    include_str!("../../../syntaxes/HCL.sublime-syntax"),
+    include_str!("../../../syntaxes/Protobuf.sublime-syntax"),
+];
+
+/// Default syntaxes plus the bundled ones. A bundled definition that fails to
+/// load is skipped rather than losing highlighting altogether.
+fn build_syntax_set() -> SyntaxSet {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    for source in BUNDLED_SYNTAXES {
+        if let Ok(syntax) = SyntaxDefinition::load_from_str(source, true, None) {
+            builder.add(syntax);
+        }
+    }
+    builder.build()
+}
+
+/// Syntax themes bundled to match the UI's color schemes: Tokyo Night Moon
+/// for dark backgrounds, Tokyo Night Day for light ones
+const BUNDLED_THEMES: &[(&str, &str)] = &[
+    ("tokyonight-moon", include_str!("../../../themes/tokyonight_moon.tmTheme")),
+    ("tokyonight-day", include_str!("../../../themes/tokyonight_day.tmTheme")),
+];
+
 lazy_static::lazy_static! {
-    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
-    static ref THEME: Theme = {
-        // Try to load custom Tokyo Night Moon theme
-        const TOKYONIGHT_THEME: &str = include_str!("../../../themes/tokyonight_moon.tmTheme");
-        let mut cursor = std::io::Cursor::new(TOKYONIGHT_THEME.as_bytes());
-        ThemeSet::load_from_reader(&mut cursor)
-            .unwrap_or_else(|_| {
-                // Fallback to built-in theme if custom theme fails to load
-                ThemeSet::load_defaults().themes["base16-ocean.dark"].clone()
-            })
-    };
+    static ref SYNTAX_SET: SyntaxSet = build_syntax_set();
+    /// Theme used when none is configured (or the configured one can't be loaded)
+    pub static ref DEFAULT_THEME: Arc<Theme> = Arc::new(load_theme(BUNDLED_THEMES[0].0).unwrap_or_else(|_| {
+        // Fallback to built-in theme if custom theme fails to load
+        ThemeSet::load_defaults().themes["base16-ocean.dark"].clone()
+    }));
 }
 
-/// Return the syntect SyntaxReference for a file path, if one exists.
+/// Load the syntax theme `name`: a bundled one ("tokyonight-moon",
+/// "tokyonight-day"), one of syntect's ("InspiredGitHub", "Solarized (light)",
+/// ...) or the path of a `.tmTheme` file. Empty is the default theme.
+pub fn load_theme(name: &str) -> Result<Theme> {
+    let name = if name.is_empty() { BUNDLED_THEMES[0].0 } else { name };
+    if let Some((_, source)) = BUNDLED_THEMES.iter().find(|(bundled, _)| *bundled == name) {
+        return ThemeSet::load_from_reader(&mut std::io::Cursor::new(source.as_bytes()))
+            .with_context(|| format!("Cannot load the bundled theme {}", name));
+    }
+    if name.ends_with(".tmTheme") || name.contains('/') {
+        let path = crate::backend::registry::expand_tilde(name);
+        return ThemeSet::get_theme(&path).with_context(|| format!("Cannot load the theme {}", path.display()));
+    }
+    let mut defaults = ThemeSet::load_defaults();
+    match defaults.themes.remove(name) {
+        Some(theme) => Ok(theme),
+        None => {
+            let known: Vec<&str> = BUNDLED_THEMES
+                .iter()
+                .map(|(bundled, _)| *bundled)
+                .chain(defaults.themes.keys().map(String::as_str))
+                .collect();
+            bail!("Unknown syntax theme {:?}; known themes: {}", name, known.join(", "))
+        }
+    }
+}
+
+/// Well-known file names (or name prefixes such as `Dockerfile.dev`) and their
+/// syntax, for files that have no telling extension
+const FILENAME_SYNTAXES: &[(&str, &str)] = &[
+    ("Jenkinsfile", "Groovy"),
+    ("Dockerfile", "Dockerfile"),
+    ("Containerfile", "Dockerfile"),
+    (".zshrc", "Bourne Again Shell (bash)"),
+    (".zprofile", "Bourne Again Shell (bash)"),
+    (".envrc", "Bourne Again Shell (bash)"),
+    ("PKGBUILD", "Bourne Again Shell (bash)"),
+];
+
+/// Return the syntect SyntaxReference for a file, if one exists.
+/// Tries well-known file names, then the extension, then the first line of
+/// `content` (shebangs, `<?xml`, ...) and finally sniffs JSON.
 /// The reference is `'static` because SYNTAX_SET is a lazy_static.
-pub fn find_syntax_for_path(path: &str) -> Option<&'static SyntaxReference> {
-    let ext = std::path::Path::new(path).extension().and_then(|e| e.to_str())?;
-    // CSV is handled separately; skip it here
-    if ext == "csv" {
+pub fn find_syntax(path: &str, content: &str) -> Option<&'static SyntaxReference> {
+    // Tabular files are rendered as tables; skip them here
+    if crate::table::delimiter_for_path(path).is_some() {
         return None;
     }
-    SYNTAX_SET.find_syntax_by_extension(ext)
+
+    let path_ref = std::path::Path::new(path);
+    if let Some(name) = path_ref.file_name().and_then(|n| n.to_str()) {
+        let known = FILENAME_SYNTAXES.iter().find(|(file_name, _)| {
+            name == *file_name || name.strip_prefix(file_name).is_some_and(|rest| rest.starts_with('.'))
+        });
+        if let Some(syntax) = known.and_then(|(_, syntax)| SYNTAX_SET.find_syntax_by_name(syntax)) {
+            return Some(syntax);
+        }
+        // Syntaxes list some files by full name (`Makefile`, `.bashrc`, `Cargo.lock`)
+        if let Some(syntax) = SYNTAX_SET.find_syntax_by_extension(name) {
+            return Some(syntax);
+        }
+    }
+
+    if let Some(syntax) = path_ref
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(|ext| SYNTAX_SET.find_syntax_by_extension(ext))
+    {
+        return Some(syntax);
+    }
+
+    let first_line = content.lines().next().unwrap_or("");
+    if let Some(syntax) = SYNTAX_SET.find_syntax_by_first_line(first_line) {
+        return Some(syntax);
+    }
+
+    // Extension-less S3 keys often hold JSON documents or JSON lines
+    if matches!(content.trim_start().chars().next(), Some('{') | Some('[')) {
+        return SYNTAX_SET.find_syntax_by_name("JSON");
+    }
+
+    None
 }
 
-/// Highlight all lines in `content` using syntect.  Intended to be called
-/// from a background thread; returns the complete `Vec<Line<'static>>` that
-/// can be cached and sliced cheaply on every subsequent render.
-pub fn build_highlight_lines(
+/// Highlight all lines in `content` using syntect, passing them to `send` in
+/// chunks of `chunk_size` lines (the last one flagged complete) until it
+/// returns false. Intended to be called from a background thread; the lines
+/// can be cached and sliced cheaply on every subsequent render, and those of
+/// the first chunks shown while the rest are highlighted.
+pub fn highlight_chunked(
     content: &str,
     syntax: &SyntaxReference,
+    theme: &Theme,
     line_num_color: Color,
-) -> Vec<Line<'static>> {
-    let mut highlighter = HighlightLines::new(syntax, &THEME);
+    chunk_size: usize,
+    mut send: impl FnMut(Vec<Line<'static>>, bool) -> bool,
+) {
+    let mut highlighter = HighlightLines::new(syntax, theme);
     let total_lines = content.lines().count();
     let line_num_width = format!("{}", total_lines).len();
-    let mut lines = Vec::with_capacity(total_lines);
+    let mut lines = Vec::with_capacity(chunk_size.min(total_lines));
 
     for (line_idx, line) in LinesWithEndings::from(content).enumerate() {
+        if lines.len() >= chunk_size && !send(std::mem::take(&mut lines), false) {
+            return;
+        }
         let ranges = highlighter.highlight_line(line, &SYNTAX_SET).unwrap_or_default();
 
         let line_num_str = format!("{:>width$} │ ", line_idx + 1, width = line_num_width);
@@ -74,100 +182,10 @@ pub fn build_highlight_lines(
         lines.push(Line::from(all_spans));
     }
 
-    lines
-}
-
-/// Highlight matches within an already-styled line
-fn highlight_line_matches(line: Line<'static>, query: &str, highlight_color: Color) -> Line<'static> {
-    if query.is_empty() {
-        return line;
-    }
-
-    let query_lower = query.to_lowercase();
-
-    // Reconstruct the full text to find match positions
-    let full_text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
-    let full_text_lower = full_text.to_lowercase();
-
-    // Find all match positions
-    let mut match_ranges: Vec<(usize, usize)> = Vec::new();
-    for (idx, _) in full_text_lower.match_indices(&query_lower) {
-        match_ranges.push((idx, idx + query.len()));
-    }
-
-    if match_ranges.is_empty() {
-        return line;
-    }
-
-    // Process each span and split it if it contains matches
-    let mut new_spans = Vec::new();
-    let mut char_offset = 0;
-
-    for span in line.spans {
-        let span_text = span.content.to_string();
-        let span_len = span_text.len();
-        let span_end = char_offset + span_len;
-
-        // Find matches that overlap with this span
-        let mut last_split = 0;
-        let mut span_had_match = false;
-
-        for (match_start, match_end) in &match_ranges {
-            // Check if match overlaps with this span
-            if *match_end > char_offset && *match_start < span_end {
-                span_had_match = true;
-
-                // Calculate positions within the span
-                let local_start = if *match_start > char_offset {
-                    match_start - char_offset
-                } else {
-                    0
-                };
-
-                let local_end = if *match_end < span_end {
-                    match_end - char_offset
-                } else {
-                    span_len
-                };
-
-                // Add text before match
-                if local_start > last_split {
-                    new_spans.push(Span::styled(
-                        span_text[last_split..local_start].to_string(),
-                        span.style,
-                    ));
-                }
-
-                // Add highlighted match
-                new_spans.push(Span::styled(
-                    span_text[local_start..local_end].to_string(),
-                    span.style.fg(highlight_color),
-                ));
-
-                last_split = local_end;
-            }
-        }
-
-        if span_had_match {
-            // Add remaining text in span
-            if last_split < span_len {
-                new_spans.push(Span::styled(
-                    span_text[last_split..].to_string(),
-                    span.style,
-                ));
-            }
-        } else {
-            // No match in this span, keep it as is
-            new_spans.push(span);
-        }
-
-        char_offset = span_end;
-    }
-
-    Line::from(new_spans)
+    send(lines, true);
 }
 
-pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focused: bool, highlighted: &HashMap<String, Vec<Line<'static>>>) {
+pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focused: bool) {
     // Determine border color based on focus
     let border_color = if is_focused {
         config.colors.accent_normal.to_ratatui_color()
@@ -175,6 +193,11 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
         config.colors.border.to_ratatui_color()
     };
 
+    if let Some(view) = app.diff_view() {
+        render_diff(frame, area, view, app, config, border_color);
+        return;
+    }
+
     // Build path string for the preview title: use the previewed file path when
     // available (file selected), otherwise fall back to the current directory prefix.
     let current_path = {
@@ -191,32 +214,89 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
     if let Some(preview) = app.get_preview() {
         match preview {
             PreviewContent::Text(content, meta) => {
-                // Use cached highlighted lines if the background task has finished,
-                // otherwise fall back to a plain-text count.
+                // Use cached highlighted lines once the background task has
+                // highlighted them all, otherwise fall back to a plain-text count.
                 let preview_path = app.current_preview_path().unwrap_or("");
-                let hl_lines = highlighted.get(preview_path).map(|v| v.as_slice());
-                let total_lines = hl_lines
-                    .map(|h| h.len())
-                    .unwrap_or_else(|| content.lines().count());
+                let hl_lines = app.highlighted(preview_path);
+                let table = app.preview_table();
+                let jsonl = app.preview_jsonl();
+                let total_lines = if let Some(table) = table {
+                    table.rows.len()
+                } else if let Some(view) = jsonl {
+                    view.line_count()
+                } else {
+                    hl_lines
+                        .filter(|_| app.is_highlight_complete(preview_path))
+                        .map(|h| h.len())
+                        .unwrap_or_else(|| content.lines().count())
+                };
                 let cursor_line = app.preview_cursor_line();
                 let visual_mode = app.is_preview_visual_mode();
 
                 let visual_indicator = if visual_mode { " VISUAL" } else { "" };
+                let query_indicator = if app.preview_query(preview_path).is_some() { " [S3 Select]" } else { "" };
+                let encoding_indicator = match &meta.encoding {
+                    Some(decoding) => format!(" [{}]", decoding.label()),
+                    None => String::new(),
+                };
+                let jq_indicator = match jsonl.and_then(|view| view.filter().map(|filter| (view, filter))) {
+                    Some((view, filter)) if view.is_truncated() => format!(
+                        " [jq {}, first {} outputs]",
+                        filter,
+                        text_utils::format_count(jsonl::MAX_FILTER_OUTPUTS)
+                    ),
+                    Some((_, filter)) => format!(" [jq {}]", filter),
+                    None => String::new(),
+                };
+                let jq_indicator = if app.is_jq_filtering() {
+                    format!("{} [jq running…]", jq_indicator)
+                } else {
+                    jq_indicator
+                };
 
-                let scroll_info = if total_lines > 0 {
-                    let percentage = ((cursor_line + 1) * 100) / total_lines;
-                    format!(" [{}/{} {}%]", cursor_line + 1, total_lines, percentage)
+                let results = app.preview_search_results();
+                let scroll_info = if app.is_preview_filtered() {
+                    // The filtered view counts matches rather than lines
+                    match results.len() {
+                        0 => " [no matches]".to_string(),
+                        n => format!(" [match {}/{}]", app.preview_search_selected() + 1, n),
+                    }
                 } else {
-                    String::new()
+                    match ((cursor_line + 1) * 100).checked_div(total_lines) {
+                        Some(percentage) => format!(" [{}/{} {}%]", cursor_line + 1, total_lines, percentage),
+                        None => String::new(),
+                    }
+                };
+                let column_info = match table {
+                    Some(table) if table.column_count() > 1 => {
+                        format!(" [col {}/{}]", app.preview_column_offset() + 1, table.column_count())
+                    }
+                    _ => String::new(),
                 };
-                let title = format!(" {}{}{}{} ", current_path, wrap_indicator, visual_indicator, scroll_info);
+                let title = format!(
+                    " {}{}{}{}{}{}{}{} ",
+                    current_path,
+                    encoding_indicator,
+                    query_indicator,
+                    jq_indicator,
+                    wrap_indicator,
+                    visual_indicator,
+                    scroll_info,
+                    column_info
+                );
 
                 let block = Block::default()
                     .borders(Borders::ALL)
                     .border_style(Style::default().fg(border_color))
                     .title(title);
 
-                render_text_preview(frame, area, content, meta, app, block, config, hl_lines);
+                if let Some(table) = table {
+                    render_table_preview(frame, area, table, app, block, config);
+                } else if let Some(view) = jsonl {
+                    render_jsonl_preview(frame, area, view, app, block, config);
+                } else {
+                    render_text_preview(frame, area, content, meta, app, block, config, hl_lines);
+                }
             }
             PreviewContent::Binary { size, mime_type, modified, etag, storage_class, version_id, version_number } => {
                 let title = format!(" {}{} ", current_path, wrap_indicator);
@@ -362,7 +442,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
         // No preview available
         let entry = app.selected_entry();
         let text = if let Some(e) = entry {
-            if e.is_dir {
+            if e.is_dir && !e.has_object() {
                 vec![
                     Line::from(""),
                     Line::from(Span::styled(
@@ -371,10 +451,19 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
                     )),
                 ]
             } else {
+                let loading = match app.preview_load_elapsed().filter(|elapsed| *elapsed >= crate::ui::SPINNER_DELAY) {
+                    Some(elapsed) => format!(
+                        "{} Loading preview... {}",
+                        text_utils::spinner_frame(),
+                        text_utils::format_elapsed(elapsed)
+                    ),
+                    None if app.is_preview_canceled() => "Preview canceled".to_string(),
+                    None => "Loading preview...".to_string(),
+                };
                 vec![
                     Line::from(""),
                     Line::from(Span::styled(
-                        "Loading preview...",
+                        loading,
                         Style::default().fg(config.colors.text_secondary.to_ratatui_color()).add_modifier(Modifier::ITALIC),
                     )),
                 ]
@@ -391,6 +480,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config, is_focu
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_text_preview(
     frame: &mut Frame,
     area: Rect,
@@ -401,33 +491,36 @@ fn render_text_preview(
     config: &Config,
     highlighted_lines: Option<&[Line<'static>]>,
 ) {
-    let file_path = app.get_selected_file_path();
+    let file_path = app.selected_object_path();
     let extension = file_path
         .as_ref()
         .and_then(|p| std::path::Path::new(p).extension())
         .and_then(|e| e.to_str());
 
+    let complete = app.current_preview_path().is_some_and(|path| app.is_highlight_complete(path));
     let total_lines = highlighted_lines
+        .filter(|_| complete)
         .map(|h| h.len())
         .unwrap_or_else(|| content.lines().count());
     // Subtract 2 for the block borders
     let visible_height = area.height.saturating_sub(2) as usize;
+    let available_width = area.width.saturating_sub(2) as usize;
 
-    // When search-filtering we need every line to match against search_results;
-    // otherwise only request the visible window so highlight functions can skip work.
-    let scroll_offset = app.preview_scroll_offset();
-    let search_active = app.is_preview_search_active();
+    // Only request the lines shown so highlight functions can skip work: the
+    // visible window, or the span of the matches shown by the filtered view
     let search_query = app.preview_search_query();
-    let should_filter = search_active && !search_query.is_empty();
-
-    let (hl_start, hl_count) = if should_filter {
-        (0, total_lines)
+    let should_filter = app.is_preview_filtered();
+    let (shown, cursor_line) = if should_filter {
+        filtered_rows(app, available_width, visible_height)
     } else {
-        (scroll_offset, visible_height)
+        let scroll_offset = app.preview_scroll_offset();
+        ((scroll_offset..total_lines).take(visible_height).collect(), Some(app.preview_cursor_line()))
     };
+    let hl_start = shown.first().copied().unwrap_or(app.preview_scroll_offset());
+    let hl_count = shown.last().map_or(visible_height, |&last| last + 1 - hl_start);
 
-    let mut all_lines = if let Some(cached) = highlighted_lines {
-        // Background highlighting is ready: slice the visible window cheaply.
+    let mut all_lines = if let Some(cached) = highlighted_lines.filter(|cached| hl_start + hl_count <= cached.len()) {
+        // Background highlighting has reached the window: slice it cheaply.
         cached.iter().skip(hl_start).take(hl_count).cloned().collect()
     } else if extension == Some("csv") {
         highlight_csv(content, config, hl_start, hl_count, total_lines)
@@ -443,7 +536,8 @@ fn render_text_preview(
         || meta.storage_class.is_some()
         || meta.version_id.is_some();
 
-    if meta_has_content {
+    let content_rows = all_lines.len();
+    if meta_has_content && !should_filter {
         let sep_style = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
         all_lines.push(Line::from(Span::styled("", sep_style)));
         all_lines.push(Line::from(Span::styled(
@@ -451,8 +545,13 @@ fn render_text_preview(
             sep_style,
         )));
         if let Some(s) = meta.size {
+            let shown = if meta.truncated {
+                format!(" (first {} shown)", format_size(content.len() as u64))
+            } else {
+                String::new()
+            };
             all_lines.push(Line::from(Span::styled(
-                format!("Size:     {}", format_size(s)),
+                format!("Size:     {}{}", format_size(s), shown),
                 sep_style,
             )));
         }
@@ -483,8 +582,10 @@ fn render_text_preview(
         }
     }
 
-    // Apply scroll offset and cursor/visual highlighting
-    let cursor_line = app.preview_cursor_line();
+    // The footer scrolls into view below the last line
+    let footer_rows = all_lines.len() - content_rows;
+
+    // Apply cursor/visual highlighting
     let is_focused = matches!(app.focused_panel(), FocusedPanel::Preview);
     let visual_mode = app.is_preview_visual_mode();
     let (visual_start, visual_end) = if visual_mode {
@@ -493,29 +594,19 @@ fn render_text_preview(
         (0, 0)
     };
 
-    let search_results = if should_filter {
-        app.preview_search_results()
-    } else {
-        &[]
-    };
-
-    // Calculate available width for padding (subtract borders)
-    let available_width = area.width.saturating_sub(2) as usize;
+    // Wrapped lines continue under their text, past the line number gutter
+    let wrap = app.is_wrap_enabled();
+    let gutter_width = format!("{}", total_lines).len() + 3;
+    let selection_bg = config.colors.selection_bg.to_ratatui_color();
 
-    let lines: Vec<Line> = all_lines
+    let line_rows: Vec<Vec<Line>> = all_lines
         .into_iter()
         .enumerate()
         // Restore the original document line indices: highlight functions already
         // skipped to hl_start, so element 0 here corresponds to line hl_start.
+        // Lines past the document are the metadata footer.
         .map(|(i, line)| (i + hl_start, line))
-        .filter(|(line_idx, _)| {
-            // If search is active with a query, only show matching lines
-            if should_filter {
-                search_results.contains(line_idx)
-            } else {
-                true
-            }
-        })
+        .filter(|(line_idx, _)| !should_filter || shown.binary_search(line_idx).is_ok())
         .map(|(line_idx, mut line)| {
             // Determine if this line should be highlighted
             let should_highlight = is_focused && if visual_mode {
@@ -523,52 +614,376 @@ fn render_text_preview(
                 line_idx >= visual_start && line_idx <= visual_end
             } else {
                 // In normal mode, only highlight the cursor line
-                line_idx == cursor_line
+                Some(line_idx) == cursor_line
             };
 
+            // Highlight search matches in the filtered view
+            if should_filter {
+                line = text_utils::highlight_matches(line, search_query, |style| config.colors.match_style(style, should_highlight));
+            }
+
+            let mut rows = if wrap {
+                let indent = if line_idx < total_lines { gutter_width } else { 0 };
+                text_utils::wrap_line(line, available_width, indent)
+            } else {
+                vec![line]
+            };
             if should_highlight {
-                // Calculate current line width (approximate)
-                let mut line_width = 0;
-                for span in &line.spans {
-                    // Count visible characters (approximation)
-                    line_width += span.content.chars().count();
+                for row in &mut rows {
+                    highlight_row(row, available_width, selection_bg);
                 }
+            }
+            rows
+        })
+        .collect();
 
-                // Apply highlight style to all spans in the line
-                for span in &mut line.spans {
-                    span.style = span.style.bg(config.colors.selection_bg.to_ratatui_color());
-                }
+    // Lines that fit the pane, fewer than its rows when they wrap
+    let mut used_rows = 0;
+    let fitting_lines = line_rows
+        .iter()
+        .take_while(|rows| {
+            used_rows += rows.len();
+            used_rows <= visible_height
+        })
+        .count()
+        .max(1);
+    let lines: Vec<Line> = line_rows.into_iter().flatten().collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+    render_scrollbar(frame, area, app, total_lines + footer_rows, fitting_lines, cursor_line, config);
+}
 
-                // Add padding to fill the rest of the line
-                if line_width < available_width {
-                    let padding = " ".repeat(available_width.saturating_sub(line_width));
-                    line.spans.push(Span::styled(
-                        padding,
-                        Style::default().bg(config.colors.selection_bg.to_ratatui_color())
-                    ));
+/// Give a row of the preview the selection background, padded to `width`
+/// columns so it fills the row
+fn highlight_row(row: &mut Line<'static>, width: usize, selection_bg: Color) {
+    let row_width = row.width();
+    for span in &mut row.spans {
+        span.style = span.style.bg(selection_bg);
+    }
+    if row_width < width {
+        row.spans.push(Span::styled(" ".repeat(width - row_width), Style::default().bg(selection_bg)));
+    }
+}
+
+/// Scrollbar for a preview of `total` lines. The filtered search view marks
+/// where its matches fall in the whole file and follows the selected one.
+fn render_scrollbar(
+    frame: &mut Frame,
+    area: Rect,
+    app: &App,
+    total: usize,
+    visible: usize,
+    cursor_line: Option<usize>,
+    config: &Config,
+) {
+    if app.is_preview_filtered() {
+        let offset = cursor_line.unwrap_or(0).saturating_sub(visible / 2);
+        scrollbar::render(frame, area, total, offset, visible, app.preview_search_results(), config);
+    } else {
+        scrollbar::render(frame, area, total, app.preview_scroll_offset(), visible, &[], config);
+    }
+}
+
+/// Lines shown by the filtered preview search view in a `width` by `height`
+/// text area, and the line of the selected result
+fn filtered_rows(app: &App, width: usize, height: usize) -> (Vec<usize>, Option<usize>) {
+    let results = app.preview_search_results();
+    let geometry = app.preview_geometry(width, height);
+    let rows = results.iter().skip(app.preview_search_scroll(&geometry)).take(height).copied().collect();
+    (rows, results.get(app.preview_search_selected()).copied())
+}
+
+/// Render a JSON Lines file one record per line, with a fold marker on each
+/// record, or the output of the jq filter applied to it
+fn render_jsonl_preview(frame: &mut Frame, area: Rect, view: &JsonLines, app: &App, block: Block, config: &Config) {
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+    let marker_style = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
+    let selection_bg = config.colors.selection_bg.to_ratatui_color();
+    let visible_height = area.height.saturating_sub(2) as usize;
+    let available_width = area.width.saturating_sub(2) as usize;
+
+    let is_focused = matches!(app.focused_panel(), FocusedPanel::Preview);
+    let visual_mode = app.is_preview_visual_mode();
+    let (visual_start, visual_end) = if visual_mode { app.get_preview_visual_range() } else { (0, 0) };
+
+    let search_query = app.preview_search_query();
+    let should_filter = app.is_preview_filtered();
+    let (shown, cursor_line) = if should_filter {
+        filtered_rows(app, available_width, visible_height)
+    } else {
+        let rows = (app.preview_scroll_offset()..view.line_count()).take(visible_height).collect();
+        (rows, Some(app.preview_cursor_line()))
+    };
+    let wrap = app.is_wrap_enabled();
+
+    let lines: Vec<Line> = shown
+        .into_iter()
+        .filter_map(|idx| view.line(idx).map(|line| (idx, line)))
+        .flat_map(|(idx, line)| {
+            let marker = match line.fold {
+                Some(true) => "▾ ",
+                Some(false) => "▸ ",
+                None => "  ",
+            };
+            let style = if view.filter_error().is_some() {
+                Style::default().fg(config.colors.text_error.to_ratatui_color())
+            } else {
+                text_style
+            };
+            let mut line = Line::from(vec![Span::styled(marker, marker_style), Span::styled(line.text.clone(), style)]);
+            let highlighted = is_focused && if visual_mode {
+                idx >= visual_start && idx <= visual_end
+            } else {
+                Some(idx) == cursor_line
+            };
+
+            if should_filter {
+                line = text_utils::highlight_matches(line, search_query, |style| config.colors.match_style(style, highlighted));
+            }
+            // Wrapped records continue past the fold marker
+            let mut rows = if wrap { text_utils::wrap_line(line, available_width, 2) } else { vec![line] };
+            if highlighted {
+                for row in &mut rows {
+                    highlight_row(row, available_width, selection_bg);
                 }
             }
+            rows
+        })
+        .collect();
 
-            // Highlight search matches if preview search is active
-            if search_active && !search_query.is_empty() {
-                let highlight_color = config.colors.accent_search.to_ratatui_color();
-                line = highlight_line_matches(line, search_query, highlight_color);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+    render_scrollbar(frame, area, app, view.line_count(), visible_height, cursor_line, config);
+}
+
+/// Render a tabular file as aligned columns. The header row stays pinned at the
+/// top; the body follows the preview cursor and scrolls horizontally by column.
+fn render_table_preview(frame: &mut Frame, area: Rect, table: &Table, app: &App, block: Block, config: &Config) {
+    let column_colors = [
+        config.colors.accent_normal.to_ratatui_color(),
+        config.colors.accent_search.to_ratatui_color(),
+        config.colors.file_icon_script.to_ratatui_color(),
+        config.colors.file_icon_config.to_ratatui_color(),
+        config.colors.file_icon_doc.to_ratatui_color(),
+    ];
+    let secondary = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
+    let selection_bg = config.colors.selection_bg.to_ratatui_color();
+
+    let inner_width = area.width.saturating_sub(2) as usize;
+    // Header row and separator take two lines
+    let body_height = area.height.saturating_sub(4) as usize;
+    let line_num_width = format!("{}", table.rows.len()).len();
+    let first_column = app.preview_column_offset().min(table.column_count().saturating_sub(1));
+
+    let row_line = |idx: usize, row: &[String], bold: bool| -> Line<'static> {
+        let mut spans = vec![Span::styled(
+            if bold {
+                format!("{:>width$} │ ", "", width = line_num_width)
+            } else {
+                format!("{:>width$} │ ", idx, width = line_num_width)
+            },
+            secondary,
+        )];
+        for col in first_column..table.column_count() {
+            if col > first_column {
+                spans.push(Span::styled(" │ ", secondary));
+            }
+            let mut style = Style::default().fg(column_colors[col % column_colors.len()]);
+            if bold {
+                style = style.add_modifier(Modifier::BOLD);
             }
+            let cell = row.get(col).map(|s| s.as_str()).unwrap_or("");
+            spans.push(Span::styled(fit_cell(cell, table.widths[col]), style));
+        }
+        Line::from(spans)
+    };
 
-            line
-        })
-        .collect();
+    let mut lines = Vec::with_capacity(body_height + 2);
+    if let Some(header) = table.rows.first() {
+        lines.push(row_line(0, header, true));
+        lines.push(Line::from(Span::styled("─".repeat(inner_width), secondary)));
+    }
 
-    let mut paragraph = Paragraph::new(lines).block(block);
+    // Body rows (document rows 1..); the filtered view shows only matching rows
+    let search_query = app.preview_search_query();
+    let should_filter = app.is_preview_filtered();
+    let (body, cursor_line, start) = if should_filter {
+        let (rows, selected) = filtered_rows(app, inner_width, body_height);
+        (rows, selected, 0)
+    } else {
+        let body: Vec<usize> = (1..table.rows.len()).collect();
+
+        // Keep the cursor row in view despite the pinned header
+        let cursor_line = app.preview_cursor_line();
+        let cursor_pos = body.iter().position(|&i| i == cursor_line);
+        let mut start = body.iter().position(|&i| i >= app.preview_scroll_offset()).unwrap_or(0);
+        if let Some(pos) = cursor_pos {
+            if pos >= start + body_height {
+                start = pos + 1 - body_height;
+            } else if pos < start {
+                start = pos;
+            }
+        }
+        (body, Some(cursor_line), start)
+    };
 
-    // Apply wrapping if enabled
-    if app.is_wrap_enabled() {
-        paragraph = paragraph.wrap(Wrap { trim: false });
+    let is_focused = matches!(app.focused_panel(), FocusedPanel::Preview);
+    let visual_mode = app.is_preview_visual_mode();
+    let (visual_start, visual_end) = if visual_mode {
+        app.get_preview_visual_range()
+    } else {
+        (0, 0)
+    };
+
+    for &row_idx in body.iter().skip(start).take(body_height) {
+        let mut line = row_line(row_idx, &table.rows[row_idx], false);
+
+        let selected = is_focused && if visual_mode {
+            row_idx >= visual_start && row_idx <= visual_end
+        } else {
+            Some(row_idx) == cursor_line
+        };
+        if selected {
+            let width = line.width();
+            for span in &mut line.spans {
+                span.style = span.style.bg(selection_bg);
+            }
+            if width < inner_width {
+                line.spans.push(Span::styled(" ".repeat(inner_width - width), Style::default().bg(selection_bg)));
+            }
+        }
+
+        if should_filter {
+            line = text_utils::highlight_matches(line, search_query, |style| config.colors.match_style(style, selected));
+        }
+        lines.push(line);
+    }
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+    if should_filter {
+        render_scrollbar(frame, area, app, table.rows.len(), body_height, cursor_line, config);
+    } else {
+        scrollbar::render(frame, area, body.len(), start, body_height, &[], config);
+    }
+}
+
+/// Render a diff (`:diff`) in unified or side-by-side layout
+fn render_diff(frame: &mut Frame, area: Rect, view: &DiffView, app: &App, config: &Config, border_color: Color) {
+    let (deleted, inserted) = diff::change_counts(&view.lines);
+    let layout = if view.side_by_side { "side-by-side" } else { "unified" };
+    let max_label = (area.width as usize).saturating_sub(40) / 2;
+    let title = format!(
+        " {} ↔ {}  -{} +{} [{}] (s layout, Esc close) ",
+        truncate_path(&view.old_label, max_label),
+        truncate_path(&view.new_label, max_label),
+        deleted,
+        inserted,
+        layout
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(border_color))
+        .title(title);
+
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    let max_line = view.lines.iter().filter_map(|l| l.old_line.max(l.new_line)).max().unwrap_or(0);
+    let num_width = format!("{}", max_line).len();
+
+    let secondary = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
+    let style_for = |tag: DiffTag| match tag {
+        DiffTag::Equal => Style::default().fg(config.colors.text_primary.to_ratatui_color()),
+        DiffTag::Delete => Style::default().fg(config.colors.text_error.to_ratatui_color()),
+        DiffTag::Insert => Style::default().fg(config.colors.file_icon_script.to_ratatui_color()),
+    };
+    let sign = |tag: DiffTag| match tag {
+        DiffTag::Equal => ' ',
+        DiffTag::Delete => '-',
+        DiffTag::Insert => '+',
+    };
+    let number = |n: Option<usize>| match n {
+        Some(n) => format!("{:>width$}", n, width = num_width),
+        None => " ".repeat(num_width),
+    };
+
+    let rows: Vec<Line<'static>> = if view.side_by_side {
+        // Each half: "<num> <sign> <text>" padded to half the width
+        let half = inner_width.saturating_sub(3) / 2;
+        let text_width = half.saturating_sub(num_width + 3);
+        let side = |line: Option<&DiffLine>, old: bool| -> Vec<Span<'static>> {
+            match line {
+                Some(line) => {
+                    let n = if old { line.old_line } else { line.new_line };
+                    vec![
+                        Span::styled(format!("{} ", number(n)), secondary),
+                        Span::styled(
+                            format!("{} {}", sign(line.tag), fit_cell(&line.text.replace('\t', "    "), text_width)),
+                            style_for(line.tag),
+                        ),
+                    ]
+                }
+                None => vec![Span::raw(" ".repeat(half))],
+            }
+        };
+        diff::side_by_side(&view.lines)
+            .into_iter()
+            .map(|(old, new)| {
+                let mut spans = side(old, true);
+                spans.push(Span::styled(" ┃ ", secondary));
+                spans.extend(side(new, false));
+                Line::from(spans)
+            })
+            .collect()
+    } else {
+        view.lines
+            .iter()
+            .map(|line| {
+                Line::from(vec![
+                    Span::styled(format!("{} {} │ ", number(line.old_line), number(line.new_line)), secondary),
+                    Span::styled(format!("{} {}", sign(line.tag), line.text), style_for(line.tag)),
+                ])
+            })
+            .collect()
+    };
+
+    // Keep the cursor row in view
+    let cursor = app.preview_cursor_line();
+    let mut start = app.preview_scroll_offset().min(rows.len().saturating_sub(1));
+    if cursor >= start + height {
+        start = cursor + 1 - height;
+    } else if cursor < start {
+        start = cursor;
     }
 
-    frame.render_widget(paragraph, area);
+    let is_focused = matches!(app.focused_panel(), FocusedPanel::Preview);
+    let selection_bg = config.colors.selection_bg.to_ratatui_color();
+    let total_rows = rows.len();
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .enumerate()
+        .skip(start)
+        .take(height)
+        .map(|(idx, mut line)| {
+            if is_focused && idx == cursor {
+                let width = line.width();
+                for span in &mut line.spans {
+                    span.style = span.style.bg(selection_bg);
+                }
+                if width < inner_width {
+                    line.spans.push(Span::styled(" ".repeat(inner_width - width), Style::default().bg(selection_bg)));
+                }
+            }
+            line
+        })
+        .collect();
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+    scrollbar::render(frame, area, total_rows, start, height, &[], config);
 }
 
+/// Pad or truncate a cell to exactly `width` characters (line breaks shown as spaces)
+fn fit_cell(cell: &str, width: usize) -> String {
+    let cell: String = cell.chars().map(|c| if c == '\n' || c == '\r' { ' ' } else { c }).collect();
+    text_utils::pad_to_width(&text_utils::truncate_to_width(&cell, width), width)
+}
 
 fn plain_text_lines(content: &str, config: &Config, start: usize, count: usize, total_lines: usize) -> Vec<Line<'static>> {
     let line_num_width = format!("{}", total_lines).len();
@@ -667,19 +1082,80 @@ fn syntect_to_ratatui_color(color: syntect::highlighting::Color) -> Color {
     Color::Rgb(color.r, color.g, color.b)
 }
 
-fn format_size(size: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    let mut size = size as f64;
-    let mut unit_idx = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_idx += 1;
+    #[test]
+    fn test_bundled_syntaxes_load() {
+        for source in BUNDLED_SYNTAXES {
+            if let Err(e) = SyntaxDefinition::load_from_str(source, true, None) {
+                panic!("bundled syntax failed to load: {}", e);
+            }
+        }
+
+        let samples = [
+            ("Cargo.toml", "[package]\nname = \"rats3\" # comment\nversion = 1.5\ndeps = { a = [1, 2] }\n"),
+            ("Dockerfile", "FROM rust:1.87 AS build\nRUN cargo build --release && \\\n    strip ${BIN}\n"),
+            ("main.tf", "resource \"aws_s3_bucket\" \"b\" {\n  bucket = \"${var.name}-logs\"\n}\n"),
+            ("api.proto", "syntax = \"proto3\";\nmessage Req { repeated string keys = 1; }\n"),
+        ];
+        for (path, content) in samples {
+            let syntax = find_syntax(path, content).unwrap_or_else(|| panic!("no syntax for {}", path));
+            assert_ne!(syntax.name, "Plain Text");
+            let mut highlighter = HighlightLines::new(syntax, &DEFAULT_THEME);
+            for line in LinesWithEndings::from(content) {
+                assert!(highlighter.highlight_line(line, &SYNTAX_SET).is_ok(), "{}: {}", path, line);
+            }
+        }
     }
 
-    if unit_idx == 0 {
-        format!("{} {}", size as u64, UNITS[unit_idx])
-    } else {
-        format!("{:.2} {}", size, UNITS[unit_idx])
+    #[test]
+    fn test_load_theme() {
+        assert_eq!(load_theme("").unwrap().name.as_deref(), Some("TokyoNight"));
+        assert_eq!(load_theme("tokyonight-day").unwrap().name.as_deref(), Some("TokyoNight Day"));
+        assert!(load_theme("InspiredGitHub").is_ok());
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/themes/tokyonight_day.tmTheme");
+        assert_eq!(load_theme(path).unwrap().name.as_deref(), Some("TokyoNight Day"));
+
+        let error = load_theme("nope").unwrap_err().to_string();
+        assert!(error.starts_with("Unknown syntax theme \"nope\"; known themes: tokyonight-moon, tokyonight-day"), "{}", error);
+        assert!(load_theme("/missing/x.tmTheme").is_err());
+    }
+
+    #[test]
+    fn test_highlight_chunked() {
+        let content = "a = 1\nb = 2\nc = 3\nd = 4\ne = 5\n";
+        let syntax = find_syntax("x.py", content).unwrap();
+        let mut chunks = Vec::new();
+        highlight_chunked(content, syntax, &DEFAULT_THEME, Color::Gray, 2, |lines, complete| {
+            chunks.push((lines.len(), complete));
+            true
+        });
+        assert_eq!(chunks, vec![(2, false), (2, false), (1, true)]);
+
+        // Stops once the receiver is gone
+        let mut sent = 0;
+        highlight_chunked(content, syntax, &DEFAULT_THEME, Color::Gray, 2, |_, _| {
+            sent += 1;
+            false
+        });
+        assert_eq!(sent, 1);
+    }
+
+    #[test]
+    fn test_syntax_detection_without_extension() {
+        let name = |path: &str, content: &str| find_syntax(path, content).map(|s| s.name.as_str());
+
+        assert_eq!(name("ci/Jenkinsfile", "pipeline {\n}\n"), Some("Groovy"));
+        assert_eq!(name("Dockerfile.prod", "FROM alpine\n"), Some("Dockerfile"));
+        assert_eq!(name("Cargo.lock", "version = 3\n"), Some("TOML"));
+        assert_eq!(name("bin/deploy", "#!/bin/bash\nset -e\n"), Some("Bourne Again Shell (bash)"));
+        assert_eq!(name("tools/gen", "#!/usr/bin/env python3\nprint(1)\n"), Some("Python"));
+        assert_eq!(name("raw/part-0000", "  {\"id\": 1}\n"), Some("JSON"));
+        assert_eq!(name("LICENSE", "MIT License\n"), None);
+        // The extension still wins over content
+        assert_eq!(name("notes.md", "#!/bin/bash\n"), Some("Markdown"));
+        assert_eq!(name("data.csv", "a,b\n"), None);
     }
 }
@@ -1,5 +1,6 @@
-use crate::app::{App, DownloadState};
+use crate::app::{App, DownloadState, RestoreTaskState};
 use crate::config::Config;
+use crate::ui::text_utils::{self, format_duration, format_size};
 use ratatui::{
     layout::Rect,
     style::{Modifier, Style},
@@ -11,8 +12,9 @@ use ratatui::{
 /// Render the progress pane showing active downloads and other background tasks
 pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
     let downloads = app.downloads();
+    let restore_lines = restore_lines(app, config);
 
-    if downloads.is_empty() {
+    if downloads.is_empty() && restore_lines.is_empty() {
         // Show empty state
         let block = Block::default()
             .borders(Borders::ALL)
@@ -32,9 +34,18 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
         return;
     }
 
+    if downloads.is_empty() {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(config.colors.border.to_ratatui_color()))
+            .title(" Restores ");
+        frame.render_widget(Paragraph::new(restore_lines).block(block), area);
+        return;
+    }
+
     // Calculate totals
     let mut sorted_downloads: Vec<_> = downloads.iter().collect();
-    sorted_downloads.sort_by(|(path_a, _), (path_b, _)| path_a.cmp(path_b));
+    sorted_downloads.sort_by_key(|(path, _)| *path);
 
     let total_files = sorted_downloads.len();
     let completed_files = sorted_downloads
@@ -63,7 +74,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
     };
 
     // Get spinner character
-    let spinner = get_spinner_char();
+    let spinner = text_utils::spinner_frame();
 
     // Create block with title
     let title = if in_progress_files > 0 {
@@ -119,6 +130,22 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
         ),
     ]));
 
+    // Speed and time remaining while downloads are running
+    if in_progress_files > 0 {
+        let now = std::time::Instant::now();
+        let speed = app
+            .download_rate(now)
+            .map(|rate| format!("{}/s", format_size(rate as u64)))
+            .unwrap_or_else(|| "–".to_string());
+        let eta = app.download_eta(now).map(format_duration).unwrap_or_else(|| "–".to_string());
+        lines.push(Line::from(vec![
+            Span::styled("Speed: ", Style::default().fg(config.colors.text_secondary.to_ratatui_color())),
+            Span::styled(speed, summary_style),
+            Span::styled("  ETA: ", Style::default().fg(config.colors.text_secondary.to_ratatui_color())),
+            Span::styled(eta, summary_style),
+        ]));
+    }
+
     // Status line
     if failed_files > 0 {
         lines.push(Line::from(vec![
@@ -151,24 +178,13 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
     // Empty line
     lines.push(Line::from(""));
 
-    // Individual file statuses (show up to 5)
-    for (path, info) in sorted_downloads.iter().take(5) {
-        let filename = path.split('/').last().unwrap_or(path);
-
-        // Truncate filename if too long
-        let max_name_len = (inner.width as usize).saturating_sub(5);
-        let display_name = if filename.len() > max_name_len {
-            format!("{}...", &filename[..max_name_len.saturating_sub(3)])
-        } else {
-            filename.to_string()
-        };
-
-        let (icon, status_color) = match &info.status {
-            DownloadState::InProgress => ("⬇", config.colors.accent_normal.to_ratatui_color()),
-            DownloadState::Complete => ("✓", config.colors.accent_search.to_ratatui_color()),
-            DownloadState::Canceled => ("⊘", config.colors.text_secondary.to_ratatui_color()),
-            DownloadState::Error(_) => ("✗", config.colors.text_error.to_ratatui_color()),
-        };
+    // Individual file statuses (as many as fit, keeping a line for the overflow note
+    // and room for the restores section)
+    let reserved = if restore_lines.is_empty() { 1 } else { restore_lines.len() + 2 };
+    let max_files = (inner.height as usize).saturating_sub(lines.len() + reserved).max(1);
+    let now = std::time::Instant::now();
+    for (path, info) in sorted_downloads.iter().take(max_files) {
+        let filename = path.rsplit('/').next().unwrap_or(path);
 
         let file_progress = if let Some(total) = info.total {
             if total > 0 {
@@ -179,6 +195,21 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
         } else {
             0
         };
+        let mut suffix = format!(" {}%", file_progress);
+        if let Some(rate) = info.bytes_per_sec(now) {
+            suffix.push_str(&format!(" {}/s", format_size(rate as u64)));
+        }
+
+        // Truncate filename if too long
+        let max_name_width = (inner.width as usize).saturating_sub(2 + text_utils::display_width(&suffix)).max(4);
+        let display_name = text_utils::truncate_to_width(filename, max_name_width);
+
+        let (icon, status_color) = match &info.status {
+            DownloadState::InProgress => ("⬇", config.colors.accent_normal.to_ratatui_color()),
+            DownloadState::Complete => ("✓", config.colors.accent_search.to_ratatui_color()),
+            DownloadState::Canceled => ("⊘", config.colors.text_secondary.to_ratatui_color()),
+            DownloadState::Error(_) => ("✗", config.colors.text_error.to_ratatui_color()),
+        };
 
         lines.push(Line::from(vec![
             Span::styled(format!("{} ", icon), Style::default().fg(status_color)),
@@ -186,50 +217,59 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
                 display_name,
                 Style::default().fg(config.colors.text_primary.to_ratatui_color()),
             ),
-            Span::styled(
-                format!(" {}%", file_progress),
-                Style::default().fg(config.colors.text_secondary.to_ratatui_color()),
-            ),
+            Span::styled(suffix, Style::default().fg(config.colors.text_secondary.to_ratatui_color())),
         ]));
     }
 
     // Show "and N more" if there are more files
-    if sorted_downloads.len() > 5 {
+    if sorted_downloads.len() > max_files {
         lines.push(Line::from(Span::styled(
-            format!("  ...and {} more", sorted_downloads.len() - 5),
+            format!("  ...and {} more", sorted_downloads.len() - max_files),
             Style::default().fg(config.colors.text_secondary.to_ratatui_color()),
         )));
     }
 
+    if !restore_lines.is_empty() {
+        lines.push(Line::from(""));
+        lines.extend(restore_lines);
+    }
+
     let paragraph = Paragraph::new(lines);
     frame.render_widget(paragraph, inner);
 }
 
-/// Get current spinner character based on time
-fn get_spinner_char() -> &'static str {
-    const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-    use std::time::SystemTime;
-    let now = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap();
-    let millis = now.as_millis();
-    let frame_idx = (millis / 80) as usize % SPINNER_FRAMES.len();
-    SPINNER_FRAMES[frame_idx]
-}
+/// Lines describing tracked archive restores (empty if there are none)
+fn restore_lines(app: &App, config: &Config) -> Vec<Line<'static>> {
+    let restores = app.restores();
+    if restores.is_empty() {
+        return Vec::new();
+    }
 
-fn format_size(size: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    let mut size = size as f64;
-    let mut unit_idx = 0;
+    let mut sorted: Vec<_> = restores.iter().collect();
+    sorted.sort_by_key(|(path, _)| *path);
 
-    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_idx += 1;
-    }
+    let mut lines = vec![Line::from(Span::styled(
+        "Restores:",
+        Style::default().fg(config.colors.text_secondary.to_ratatui_color()),
+    ))];
 
-    if unit_idx == 0 {
-        format!("{} {}", size as u64, UNITS[unit_idx])
-    } else {
-        format!("{:.2} {}", size, UNITS[unit_idx])
+    for (path, state) in sorted {
+        let filename = path.rsplit('/').next().unwrap_or(path).to_string();
+        let (icon, label, color) = match state {
+            RestoreTaskState::InProgress => ("⏳", "restoring".to_string(), config.colors.accent_normal.to_ratatui_color()),
+            RestoreTaskState::Restored { expiry: Some(expiry) } => {
+                ("✓", format!("until {}", expiry), config.colors.accent_search.to_ratatui_color())
+            }
+            RestoreTaskState::Restored { expiry: None } => ("✓", "restored".to_string(), config.colors.accent_search.to_ratatui_color()),
+            RestoreTaskState::Error(err) => ("✗", err.clone(), config.colors.text_error.to_ratatui_color()),
+        };
+
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} ", icon), Style::default().fg(color)),
+            Span::styled(filename, Style::default().fg(config.colors.text_primary.to_ratatui_color())),
+            Span::styled(format!(" {}", label), Style::default().fg(config.colors.text_secondary.to_ratatui_color())),
+        ]));
     }
+
+    lines
 }
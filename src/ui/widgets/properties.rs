@@ -0,0 +1,68 @@
+use crate::app::PropertiesView;
+use crate::config::Config;
+use crate::ui::text_utils;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+/// Render the properties popup of an object
+pub fn render(frame: &mut Frame, area: Rect, view: &PropertiesView, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let accent = config.colors.accent_normal.to_ratatui_color();
+    let secondary = config.colors.text_secondary.to_ratatui_color();
+    let background = config.colors.background.to_ratatui_color();
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+
+    let name_width = view.rows.iter().map(|(name, _)| text_utils::display_width(name)).max().unwrap_or(0);
+    // Borders, highlight symbol and the gaps around the name column
+    let value_width = (area.width as usize).saturating_sub(name_width + 7);
+
+    let mut items: Vec<ListItem> = view
+        .rows
+        .iter()
+        .map(|(name, value)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {}  ", text_utils::pad_to_width(name, name_width)), Style::default().fg(secondary)),
+                Span::styled(text_utils::truncate_to_width(value, value_width), text_style),
+            ]))
+        })
+        .collect();
+    if let Some(error) = &view.error {
+        let error_style = Style::default().fg(config.colors.text_error.to_ratatui_color());
+        items.push(ListItem::new(Span::styled(format!(" error: {}", error), error_style)));
+    } else if !view.complete {
+        items.push(ListItem::new(Span::styled(" loading…", Style::default().fg(secondary).add_modifier(Modifier::ITALIC))));
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(accent))
+                .style(Style::default().bg(background))
+                .title(" Properties (y copy value, Y copy all, Esc close) "),
+        )
+        .style(Style::default().bg(background))
+        .highlight_style(
+            Style::default()
+                .bg(config.colors.selection_bg.to_ratatui_color())
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("❯ ");
+
+    let mut list_state = ListState::default();
+    list_state.select((!view.rows.is_empty()).then_some(view.selected));
+
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+/// Number of lines the popup needs (for sizing the overlay)
+pub fn line_count(view: &PropertiesView) -> usize {
+    view.rows.len() + usize::from(view.error.is_some() || !view.complete)
+}
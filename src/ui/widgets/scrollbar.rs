@@ -0,0 +1,48 @@
+use crate::config::Config;
+use ratatui::{
+    layout::{Margin, Rect},
+    style::{Modifier, Style},
+    widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState},
+    Frame,
+};
+
+/// Draw a slim scrollbar over the right border of a bordered pane: the thumb
+/// covers the `visible` rows starting at `offset` out of `total`, and each row
+/// in `marks` (search matches, say) gets a tick on the track. Nothing is drawn
+/// when everything fits.
+pub fn render(frame: &mut Frame, area: Rect, total: usize, offset: usize, visible: usize, marks: &[usize], config: &Config) {
+    if visible == 0 || total <= visible {
+        return;
+    }
+    // Keep the corners of the border
+    let track = area.inner(Margin { vertical: 1, horizontal: 0 });
+    if track.height == 0 || track.width == 0 {
+        return;
+    }
+
+    // Positions run over the offsets the view can scroll to, so the thumb
+    // reaches the bottom of the track together with the last row
+    let max_offset = total - visible;
+    let mut state = ScrollbarState::new(max_offset + 1)
+        .viewport_content_length(visible)
+        .position(offset.min(max_offset));
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .track_symbol(Some("│"))
+        .track_style(Style::default().fg(config.colors.border.to_ratatui_color()))
+        .thumb_symbol("┃")
+        .thumb_style(Style::default().fg(config.colors.text_secondary.to_ratatui_color()));
+    frame.render_stateful_widget(scrollbar, track, &mut state);
+
+    let x = track.right() - 1;
+    let height = track.height as usize;
+    let mark_style = Style::default()
+        .fg(config.colors.accent_search.to_ratatui_color())
+        .add_modifier(Modifier::BOLD);
+    let buffer = frame.buffer_mut();
+    for &mark in marks {
+        let row = (mark * height / total).min(height - 1);
+        buffer.get_mut(x, track.y + row as u16).set_symbol("━").set_style(mark_style);
+    }
+}
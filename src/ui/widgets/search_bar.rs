@@ -1,4 +1,4 @@
-use crate::app::App;
+use crate::app::{App, AppMode};
 use crate::config::Config;
 use ratatui::{
     layout::Rect,
@@ -18,23 +18,41 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
     };
     let in_search_mode = app.is_search_mode() || preview_search_active;
 
-    let text = if in_search_mode {
+    let in_command_mode = app.mode() == &AppMode::Command;
+
+    // Pinned filters precede the query they narrow: `*.csv › err › 2024`
+    let pinned: Vec<Span> = if preview_search_active {
+        Vec::new()
+    } else {
+        app.pinned_filters()
+            .iter()
+            .flat_map(|filter| {
+                [
+                    Span::styled(filter.as_str(), Style::default().fg(config.colors.text_secondary.to_ratatui_color())),
+                    Span::styled(" › ", Style::default().fg(config.colors.text_secondary.to_ratatui_color())),
+                ]
+            })
+            .collect()
+    };
+
+    let text = if in_command_mode {
+        Line::from(vec![
+            Span::styled(" : ", Style::default().fg(config.colors.accent_search.to_ratatui_color()).add_modifier(Modifier::BOLD)),
+            Span::styled(app.command_input(), Style::default().fg(config.colors.text_primary.to_ratatui_color())),
+            Span::styled("█", Style::default().fg(config.colors.accent_search.to_ratatui_color())), // Cursor
+        ])
+    } else if in_search_mode {
         // In search mode - show search prompt
-        if query.is_empty() {
-            Line::from(vec![
-                Span::styled(" / ", Style::default().fg(config.colors.accent_search.to_ratatui_color()).add_modifier(Modifier::BOLD)),
-                Span::styled("█", Style::default().fg(config.colors.accent_search.to_ratatui_color())), // Cursor
-            ])
-        } else {
-            Line::from(vec![
-                Span::styled(" / ", Style::default().fg(config.colors.accent_search.to_ratatui_color()).add_modifier(Modifier::BOLD)),
-                Span::styled(query, Style::default().fg(config.colors.text_primary.to_ratatui_color())),
-                Span::styled("█", Style::default().fg(config.colors.accent_search.to_ratatui_color())), // Cursor
-            ])
+        let mut spans = vec![Span::styled(" / ", Style::default().fg(config.colors.accent_search.to_ratatui_color()).add_modifier(Modifier::BOLD))];
+        spans.extend(pinned);
+        if !query.is_empty() {
+            spans.push(Span::styled(query, Style::default().fg(config.colors.text_primary.to_ratatui_color())));
         }
+        spans.push(Span::styled("█", Style::default().fg(config.colors.accent_search.to_ratatui_color()))); // Cursor
+        Line::from(spans)
     } else {
         // Normal mode - show hint or filtered results
-        if query.is_empty() {
+        if !app.is_filtered() {
             Line::from(vec![
                 Span::styled(" ❯ ", Style::default().fg(config.colors.accent_normal.to_ratatui_color()).add_modifier(Modifier::BOLD)),
                 Span::styled(
@@ -44,21 +62,23 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
             ])
         } else {
             // Filtered but not in search mode - show results
-            Line::from(vec![
+            let mut spans = vec![
                 Span::styled(" ❯ ", Style::default().fg(config.colors.accent_normal.to_ratatui_color()).add_modifier(Modifier::BOLD)),
-                Span::styled(
-                    format!("Filtered: {}", query),
-                    Style::default().fg(config.colors.accent_search.to_ratatui_color()),
-                ),
-                Span::styled(
-                    " (Press / to edit)",
-                    Style::default().fg(config.colors.text_secondary.to_ratatui_color()).add_modifier(Modifier::ITALIC),
-                ),
-            ])
+                Span::styled("Filtered: ", Style::default().fg(config.colors.accent_search.to_ratatui_color())),
+            ];
+            spans.extend(pinned);
+            spans.push(Span::styled(query, Style::default().fg(config.colors.accent_search.to_ratatui_color())));
+            spans.push(Span::styled(
+                " (Press / to narrow)",
+                Style::default().fg(config.colors.text_secondary.to_ratatui_color()).add_modifier(Modifier::ITALIC),
+            ));
+            Line::from(spans)
         }
     };
 
-    let title = if preview_search_active {
+    let title = if in_command_mode {
+        " Command Mode "
+    } else if preview_search_active {
         " Preview Search Mode "
     } else if in_search_mode {
         " Search Mode "
@@ -66,7 +86,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
         " Normal Mode "
     };
 
-    let border_color = if in_search_mode {
+    let border_color = if in_search_mode || in_command_mode {
         config.colors.accent_search.to_ratatui_color()
     } else {
         config.colors.accent_normal.to_ratatui_color()
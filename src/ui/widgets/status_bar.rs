@@ -1,11 +1,12 @@
-use crate::app::{App, DownloadState};
-use crate::config::Config;
+use crate::app::{App, AppMode, DownloadState};
+use crate::config::{Config, StatusSegment};
 use crate::status::StatusSeverity;
+use crate::ui::text_utils::{self, format_duration};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Modifier, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph},
     Frame,
 };
 
@@ -19,12 +20,6 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
-    // If help is shown, render help instead of normal status
-    if app.is_help_shown() {
-        render_help(frame, inner, app, config);
-        return;
-    }
-
     // Split inner area into left (status message) and right (progress)
     // Progress area should be flexible based on content, but we'll allocate space for it
     let has_progress = !app.downloads().is_empty();
@@ -46,19 +41,20 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
             .split(inner)
     };
 
+    // Segments on the first line, the status message below them
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(chunks[0]);
+    frame.render_widget(Paragraph::new(Line::from(segment_spans(app, config))), left[0]);
+
     // Render status message on the left
     let status_text = if let Some(status_msg) = app.status_message() {
-        // Determine color based on severity
-        let color = match status_msg.severity {
-            StatusSeverity::Info => config.colors.accent_normal.to_ratatui_color(),
-            StatusSeverity::Success => config.colors.accent_search.to_ratatui_color(),
-            StatusSeverity::Warning => config.colors.accent_search.to_ratatui_color(),
-            StatusSeverity::Error => config.colors.text_error.to_ratatui_color(),
-        };
+        let color = severity_color(&status_msg.severity, config);
 
         // Word-wrap message to fit in multiple lines
-        let max_width = (chunks[0].width as usize).saturating_sub(2); // Account for padding
-        let max_lines = chunks[0].height as usize; // Available lines
+        let max_width = (left[1].width as usize).saturating_sub(2); // Account for padding
+        let max_lines = left[1].height as usize; // Available lines
 
         // Split message into words and wrap to fit width
         let mut lines = Vec::new();
@@ -71,7 +67,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
                 format!("{} {}", current_line, word)
             };
 
-            if test_line.len() <= max_width {
+            if text_utils::display_width(&test_line) <= max_width {
                 current_line = test_line;
             } else {
                 if !current_line.is_empty() {
@@ -79,7 +75,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
                     current_line = word.to_string();
                 } else {
                     // Word is longer than max_width, truncate it
-                    lines.push(format!("{}...", &word[..max_width.saturating_sub(3)]));
+                    lines.push(text_utils::truncate_to_width(word, max_width));
                     current_line = String::new();
                 }
             }
@@ -101,24 +97,11 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
             )]))
             .collect::<Vec<_>>()
     } else {
-        // Show minimal status by default (just file count)
-        let count = app.filtered_indices().len();
-        let total = app.entries().len();
-
-        vec![Line::from(vec![
-            Span::styled(
-                format!(" {}/{} files", count, total),
-                Style::default().fg(config.colors.text_secondary.to_ratatui_color()),
-            ),
-            Span::styled(
-                "  Press ? for help",
-                Style::default().fg(config.colors.text_secondary.to_ratatui_color()),
-            ),
-        ])]
+        Vec::new()
     };
 
     let status_paragraph = Paragraph::new(status_text);
-    frame.render_widget(status_paragraph, chunks[0]);
+    frame.render_widget(status_paragraph, left[1]);
 
     // Render progress message on the right (if downloads are active)
     if has_progress {
@@ -132,8 +115,122 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
     }
 }
 
+/// Zen mode: the status message, if any, on the bottom line of `area`
+pub fn render_message_line(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
+    let Some(status_msg) = app.status_message() else {
+        return;
+    };
+    let line_area = Rect { y: area.bottom().saturating_sub(1), height: area.height.min(1), ..area };
+    let text = text_utils::truncate_to_width(&status_msg.content, (line_area.width as usize).saturating_sub(2));
+    let style = Style::default().fg(severity_color(&status_msg.severity, config));
+    let paragraph = Paragraph::new(Line::from(Span::styled(format!(" {} ", text), style)))
+        .style(Style::default().bg(config.colors.background.to_ratatui_color()));
+    frame.render_widget(Clear, line_area);
+    frame.render_widget(paragraph, line_area);
+}
+
+/// Color of a status message of the given severity
+fn severity_color(severity: &StatusSeverity, config: &Config) -> Color {
+    match severity {
+        StatusSeverity::Info => config.colors.accent_normal.to_ratatui_color(),
+        StatusSeverity::Success => config.colors.accent_search.to_ratatui_color(),
+        StatusSeverity::Warning => config.colors.accent_search.to_ratatui_color(),
+        StatusSeverity::Error => config.colors.text_error.to_ratatui_color(),
+    }
+}
+
+/// Name of the current mode, as shown in the mode segment
+fn mode_label(app: &App) -> &'static str {
+    match app.mode() {
+        AppMode::Normal if app.is_preview_visual_mode() => "VISUAL",
+        AppMode::Normal if app.pick_mode().is_some() => "PICK",
+        AppMode::Normal => "NORMAL",
+        AppMode::Search => "SEARCH",
+        AppMode::Visual => "VISUAL",
+        AppMode::History => "HISTORY",
+        AppMode::Download => "DOWNLOAD",
+        AppMode::Command => "COMMAND",
+        AppMode::Summary => "SUMMARY",
+        AppMode::Diff => "DIFF",
+        AppMode::Sync => "SYNC",
+        AppMode::Context => "CONTEXT",
+        AppMode::Grep => "GREP",
+        AppMode::Properties => "PROPERTIES",
+        AppMode::AuditLog => "LOG",
+        AppMode::Confirm => "CONFIRM",
+        AppMode::PluginOutput => "PLUGIN",
+    }
+}
+
+/// Pinned filters and the current query, each as `/query`
+fn filter_stack(app: &App) -> String {
+    app.pinned_filters()
+        .iter()
+        .map(String::as_str)
+        .chain(Some(app.search_query()).filter(|q| !q.is_empty()))
+        .map(|f| format!("/{}", f))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Text of a segment, None when it has nothing to show
+fn segment_text(segment: StatusSegment, app: &App) -> Option<String> {
+    match segment {
+        StatusSegment::Mode => Some(mode_label(app).to_string()),
+        StatusSegment::Backend => Some(app.backend().kind()),
+        StatusSegment::Profile => app.backend().profile().map(|p| format!("profile {}", p)),
+        StatusSegment::Region => app.backend().region(),
+        StatusSegment::Filter => Some(filter_stack(app)).filter(|f| !f.is_empty()),
+        StatusSegment::Selection => Some(app.selected_count()).filter(|&n| n > 0).map(|n| format!("{} selected", n)),
+        StatusSegment::Count => Some(format!("{}/{} files", app.match_count(), app.entries().len())),
+        StatusSegment::Transfer => transfer_text(app),
+        StatusSegment::Help => Some("? help".to_string()),
+    }
+}
+
+/// `↓ 1.2MB` received this session, with `↑ 3.0KB` when something was uploaded
+fn transfer_text(app: &App) -> Option<String> {
+    let usage = app.usage();
+    let mut parts = Vec::new();
+    if usage.received_bytes() > 0 {
+        parts.push(format!("↓ {}", format_size(usage.received_bytes())));
+    }
+    if usage.upload_bytes > 0 {
+        parts.push(format!("↑ {}", format_size(usage.upload_bytes)));
+    }
+    Some(parts.join(" ")).filter(|text| !text.is_empty())
+}
+
+/// The configured segments as blocks: the mode in the accent color, the
+/// others on the selection background
+fn segment_spans(app: &App, config: &Config) -> Vec<Span<'static>> {
+    let background = config.colors.background.to_ratatui_color();
+    let mode_color = if app.is_search_mode() {
+        config.colors.accent_search.to_ratatui_color()
+    } else {
+        config.colors.accent_normal.to_ratatui_color()
+    };
+    let segment_style = Style::default()
+        .fg(config.colors.text_primary.to_ratatui_color())
+        .bg(config.colors.selection_bg.to_ratatui_color());
+
+    let mut spans = Vec::new();
+    for &segment in &config.status_segments {
+        let Some(text) = segment_text(segment, app) else {
+            continue;
+        };
+        let style = match segment {
+            StatusSegment::Mode => Style::default().fg(background).bg(mode_color).add_modifier(Modifier::BOLD),
+            _ => segment_style,
+        };
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!(" {} ", text), style));
+    }
+    spans
+}
+
 /// Format download progress message
-/// Format: "downloading n/m files x/y b (x1/y1 b total) z%"
+/// Format: "downloading n/m files (x / y total) z% s/s ETA t"
 fn format_download_progress(app: &App) -> String {
     let downloads = app.downloads();
     if downloads.is_empty() {
@@ -167,14 +264,22 @@ fn format_download_progress(app: &App) -> String {
     };
 
     if !in_progress_downloads.is_empty() {
-        format!(
+        let now = std::time::Instant::now();
+        let mut text = format!(
             "downloading {}/{} files ({} / {} total) {}%",
             in_progress_count,
             total_files,
             format_size(downloaded_size),
             format_size(total_size),
             overall_progress
-        )
+        );
+        if let Some(rate) = app.download_rate(now) {
+            text.push_str(&format!(" {}/s", format_size(rate as u64)));
+        }
+        if let Some(eta) = app.download_eta(now) {
+            text.push_str(&format!(" ETA {}", format_duration(eta)));
+        }
+        text
     } else {
         // All done or all failed
         format!(
@@ -204,94 +309,3 @@ fn format_size(size: u64) -> String {
         format!("{:.1}{}", size, UNITS[unit_idx])
     }
 }
-
-/// Render context-sensitive help
-fn render_help(frame: &mut Frame, area: Rect, app: &App, config: &Config) {
-    use crate::app::{AppMode, FocusedPanel};
-
-    let mode = app.mode();
-    let focused_panel = app.focused_panel();
-    let preview_visual = app.is_preview_visual_mode();
-
-    let help_lines = match mode {
-        AppMode::Search if app.is_searching_history() => {
-            vec![
-                "Searching History:",
-                "Type=filter  Ctrl-j/k=navigate  Enter=select  Esc=exit search",
-            ]
-        }
-        AppMode::Search => {
-            vec![
-                "Search Mode:",
-                "Type=filter  Ctrl-j/k/↑/↓=navigate  Enter=open  Esc=exit search",
-            ]
-        }
-        AppMode::Visual => {
-            vec![
-                "Visual Mode:",
-                "j/k=move & select  Space=toggle  s/S=download  v/Esc=exit",
-            ]
-        }
-        AppMode::History => {
-            vec![
-                "History Mode:",
-                "j/k=move  /=search  Enter=navigate  Esc=exit",
-            ]
-        }
-        AppMode::Download => {
-            vec![
-                "Download Mode:",
-                "j/k=select destination  Enter=confirm  Esc=cancel",
-            ]
-        }
-        AppMode::Normal => {
-            if focused_panel == &FocusedPanel::Preview {
-                if app.is_preview_search_active() {
-                    vec![
-                        "Preview Search Mode:",
-                        "Type=filter  Ctrl-j/k/↑/↓=next/prev result  Enter=jump  Esc=exit",
-                    ]
-                } else if preview_visual {
-                    vec![
-                        "Preview Visual Mode:",
-                        "j/k=move  Ctrl-u/d=page  gg/G=top/bottom  y=yank  v/Esc=exit",
-                    ]
-                } else {
-                    vec![
-                        "Preview Mode:",
-                        "j/k=scroll  Ctrl-u/d=page  gg/G=top/bottom  /=search  v=visual",
-                        "w=wrap  Tab=switch to explorer  H/L=resize  ?=help",
-                    ]
-                }
-            } else {
-                vec![
-                    "Explorer Mode:",
-                    "j/k=move  Enter/l=open  h=back  /=search  Space=select  v=visual",
-                    "s/S=download  Ctrl-r=history  Y=copy path  q=quit  ?=help",
-                ]
-            }
-        }
-    };
-
-    let key_color = config.colors.accent_normal.to_ratatui_color();
-    let text_color = config.colors.text_secondary.to_ratatui_color();
-
-    let mut lines = Vec::new();
-    for (i, line) in help_lines.iter().enumerate() {
-        if i == 0 {
-            // Header line
-            lines.push(Line::from(Span::styled(
-                format!(" {}", line),
-                Style::default().fg(key_color).add_modifier(Modifier::BOLD),
-            )));
-        } else {
-            lines.push(Line::from(Span::styled(
-                format!(" {}", line),
-                Style::default().fg(text_color),
-            )));
-        }
-    }
-
-    let paragraph = Paragraph::new(lines);
-    frame.render_widget(paragraph, area);
-}
@@ -0,0 +1,110 @@
+use crate::app::SummaryView;
+use crate::config::Config;
+use crate::summary::{GroupTotals, PrefixSummary};
+use crate::ui::text_utils::{self, format_size};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::collections::HashMap;
+
+/// Width of the size bars in characters
+const BAR_WIDTH: usize = 20;
+/// Maximum number of extensions listed
+const MAX_EXTENSIONS: usize = 10;
+
+/// Render the `:du` prefix summary popup
+pub fn render(frame: &mut Frame, area: Rect, view: &SummaryView, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let header_style = Style::default()
+        .fg(config.colors.accent_normal.to_ratatui_color())
+        .add_modifier(Modifier::BOLD);
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+    let dim_style = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
+
+    let status = if let Some(error) = &view.error {
+        Span::styled(
+            format!("  error: {}", error),
+            Style::default().fg(config.colors.text_error.to_ratatui_color()),
+        )
+    } else if view.complete {
+        Span::styled("  done", Style::default().fg(config.colors.accent_normal.to_ratatui_color()))
+    } else {
+        Span::styled("  scanning…", dim_style.add_modifier(Modifier::ITALIC))
+    };
+
+    let summary = &view.summary;
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                format!(" {} objects, {}", summary.object_count, format_size(summary.total_bytes)),
+                text_style.add_modifier(Modifier::BOLD),
+            ),
+            status,
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(" By storage class", header_style)),
+    ];
+    lines.extend(group_lines(&summary.by_storage_class, summary.total_bytes, usize::MAX, config));
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        format!(" By extension (top {})", MAX_EXTENSIONS),
+        header_style,
+    )));
+    lines.extend(group_lines(&summary.by_extension, summary.total_bytes, MAX_EXTENSIONS, config));
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(config.colors.accent_normal.to_ratatui_color()))
+        .style(Style::default().bg(config.colors.background.to_ratatui_color()))
+        .title(format!(" Summary: {} (Esc close) ", view.location));
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// One row per group: name, size bar, size and object count
+fn group_lines(
+    groups: &HashMap<String, GroupTotals>,
+    total_bytes: u64,
+    limit: usize,
+    config: &Config,
+) -> Vec<Line<'static>> {
+    let sorted = PrefixSummary::sorted(groups);
+    let name_width = sorted.iter().take(limit).map(|(name, _)| text_utils::display_width(name)).max().unwrap_or(0);
+
+    let bar_style = Style::default().fg(config.colors.accent_search.to_ratatui_color());
+    let empty_style = Style::default().fg(config.colors.border.to_ratatui_color());
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+    let dim_style = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
+
+    sorted
+        .into_iter()
+        .take(limit)
+        .map(|(name, totals)| {
+            let filled = if total_bytes == 0 {
+                0
+            } else {
+                ((totals.bytes as f64 / total_bytes as f64) * BAR_WIDTH as f64).round() as usize
+            };
+            Line::from(vec![
+                Span::styled(format!("   {}  ", text_utils::pad_to_width(name, name_width)), text_style),
+                Span::styled("█".repeat(filled), bar_style),
+                Span::styled("░".repeat(BAR_WIDTH - filled), empty_style),
+                Span::styled(format!(" {:>10}", format_size(totals.bytes)), text_style),
+                Span::styled(format!("  {} objects", totals.count), dim_style),
+            ])
+        })
+        .collect()
+}
+
+/// Number of lines the popup needs (for sizing the overlay)
+pub fn line_count(view: &SummaryView) -> usize {
+    // Totals, blank line, two headings and the blank line between sections
+    5 + view.summary.by_storage_class.len() + view.summary.by_extension.len().min(MAX_EXTENSIONS)
+}
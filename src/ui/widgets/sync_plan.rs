@@ -0,0 +1,108 @@
+use crate::config::Config;
+use crate::sync::{SyncDirection, SyncPlan, SyncReason};
+use crate::ui::text_utils::format_size;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Render the `:sync`/`:upload` plan popup (what would be transferred and deleted)
+pub fn render(frame: &mut Frame, area: Rect, plan: &SyncPlan, scroll: usize, config: &Config) {
+    // Clear the area first to hide underlying content
+    frame.render_widget(Clear, area);
+
+    let text_style = Style::default().fg(config.colors.text_primary.to_ratatui_color());
+    let dim_style = Style::default().fg(config.colors.text_secondary.to_ratatui_color());
+    let new_style = Style::default().fg(config.colors.file_icon_script.to_ratatui_color());
+    let changed_style = Style::default().fg(config.colors.accent_search.to_ratatui_color());
+    let delete_style = Style::default().fg(config.colors.text_error.to_ratatui_color());
+
+    let verb = match plan.direction {
+        SyncDirection::Download => "download",
+        SyncDirection::Upload => "upload",
+    };
+    let cost = match plan.direction {
+        SyncDirection::Download => config.confirmations.egress_cost(plan.transfer_bytes()),
+        SyncDirection::Upload => None,
+    };
+    let mut lines = vec![Line::from(vec![
+        Span::styled(
+            format!(
+                " {} to {} ({}{}), {} unchanged",
+                plan.transfers.len(),
+                verb,
+                format_size(plan.transfer_bytes()),
+                cost.map(|cost| format!(", ≈ ${:.2} egress", cost)).unwrap_or_default(),
+                plan.unchanged
+            ),
+            text_style.add_modifier(Modifier::BOLD),
+        ),
+        match plan.direction {
+            SyncDirection::Download if plan.delete => Span::styled(
+                format!(", {} to delete", plan.deletions.len()),
+                delete_style.add_modifier(Modifier::BOLD),
+            ),
+            SyncDirection::Download => Span::styled("  (local-only files kept; add --delete to remove)", dim_style),
+            SyncDirection::Upload => Span::styled("  (remote-only files kept)", dim_style),
+        },
+    ])];
+    if plan.is_empty() {
+        lines.push(Line::from(Span::styled(" Already in sync", dim_style.add_modifier(Modifier::ITALIC))));
+    }
+    if !plan.skipped.is_empty() {
+        lines.push(Line::from(Span::styled(
+            match plan.direction {
+                SyncDirection::Download => {
+                    format!(" Skipping {} object(s) whose keys leave the local directory", plan.skipped.len())
+                }
+                SyncDirection::Upload => format!(" Skipping {} file(s) with non-UTF-8 names", plan.skipped.len()),
+            },
+            delete_style,
+        )));
+    }
+    lines.push(Line::from(""));
+
+    for transfer in &plan.transfers {
+        let (label, style) = match transfer.reason {
+            SyncReason::New => ("  new     ", new_style),
+            SyncReason::Changed => ("  changed ", changed_style),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(label, style),
+            Span::styled(transfer.relative.clone(), text_style),
+            Span::styled(format!("  {}", format_size(transfer.size)), dim_style),
+        ]));
+    }
+    for path in &plan.deletions {
+        let relative = path.strip_prefix(&plan.local_dir).unwrap_or(path);
+        lines.push(Line::from(vec![
+            Span::styled("  delete  ", delete_style),
+            Span::styled(relative.display().to_string(), text_style),
+        ]));
+    }
+
+    let title = match plan.direction {
+        SyncDirection::Download => format!(" Sync {} → {} ", plan.location, plan.local_dir.display()),
+        SyncDirection::Upload => format!(" Upload {} → {} ", plan.local_dir.display(), plan.location),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(config.colors.accent_normal.to_ratatui_color()))
+        .style(Style::default().bg(config.colors.background.to_ratatui_color()))
+        .title(format!("{}(Enter run, Esc cancel) ", title));
+
+    let paragraph = Paragraph::new(lines).block(block).scroll((scroll as u16, 0));
+    frame.render_widget(paragraph, area);
+}
+
+/// Number of lines the popup needs (for sizing the overlay and scrolling)
+pub fn line_count(plan: &SyncPlan) -> usize {
+    // Totals line, optional notes and the blank line before the file list
+    2 + usize::from(plan.is_empty())
+        + usize::from(!plan.skipped.is_empty())
+        + plan.transfers.len()
+        + plan.deletions.len()
+}